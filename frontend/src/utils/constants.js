@@ -60,19 +60,35 @@ export const EVENT_LEVELS = [
     'Critical'
 ]
 export const EVENT_TYPES = [
+    'ClientCreated',
+    'ClientDeleted',
+    'ClientSecretRotated',
     'InvalidLogins',
     'IpBlacklisted',
     'IpBlacklistRemoved',
     'JwksRotated',
+    'MfaEnrolled',
+    'MfaFailed',
+    'MfaRemoved',
     'NewUserRegistered',
+    'PasskeyHybridDenied',
+    'PasskeyHybridLogin',
     'NewRauthyAdmin',
     'NewRauthyVersion',
     'PossibleBruteForce',
+    'RefreshTokenReused',
     'RauthyStarted',
     'RauthyHealthy',
     'RauthyUnhealthy',
     'SecretsMigrated',
+    'SessionAnomaly',
+    'SessionLimitReached',
+    'SessionRevoked',
     'UserEmailChange',
+    'UserExpired',
+    'UserImpersonated',
+    'UserLocked',
+    'UserUnlocked',
     'UserPasswordReset',
     'Test',
 ]
@@ -81,6 +97,7 @@ export const TOKEN_ALGS = [
     'RS256',
     'RS384',
     'RS512',
+    'ES384',
     'EdDSA'
 ]
 
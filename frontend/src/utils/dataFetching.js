@@ -32,6 +32,27 @@ export async function authorize(data) {
     return res;
 }
 
+export async function authorizePasskeyStart() {
+    return await fetch('/auth/v1/oidc/authorize/passkey/start', {
+        method: 'POST',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function authorizePasskey(data) {
+    const res = await fetch('/auth/v1/oidc/authorize/passkey', {
+        method: 'POST',
+        headers: getCsrfHeaders(),
+        body: JSON.stringify(data),
+    });
+
+    if (res.status === 202) {
+        window.location.replace(res.headers.get('location'));
+    }
+
+    return res;
+}
+
 export async function authorizeRefresh(data) {
     const res = await fetch('/auth/v1/oidc/authorize/refresh', {
         method: 'POST',
@@ -123,6 +144,13 @@ export async function getPasswordPolicy() {
     });
 }
 
+export async function getUserPasswordPolicy(id) {
+    return await fetch(`/auth/v1/users/${id}/password_policy`, {
+        method: 'GET',
+        headers: HEADERS.json,
+    });
+}
+
 export async function getProvidersTemplate() {
     return await fetch('/auth/v1/providers/minimal', {
         method: 'GET',
@@ -217,6 +245,48 @@ export async function deleteUserDeviceRefresh(id, data) {
     });
 }
 
+export async function getUserSessionDevices(id) {
+    return await fetch(`/auth/v1/users/${id}/devices/sessions`, {
+        method: 'GET',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function deleteUserSessionDevice(id, sessionId) {
+    return await fetch(`/auth/v1/users/${id}/devices/sessions/${sessionId}`, {
+        method: 'DELETE',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function getUserTrustedDevices(id) {
+    return await fetch(`/auth/v1/users/${id}/devices/trusted`, {
+        method: 'GET',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function deleteUserTrustedDevice(id, deviceId) {
+    return await fetch(`/auth/v1/users/${id}/devices/trusted/${deviceId}`, {
+        method: 'DELETE',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function getUserConsents(id) {
+    return await fetch(`/auth/v1/users/${id}/consents`, {
+        method: 'GET',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function deleteUserConsent(id, clientId) {
+    return await fetch(`/auth/v1/users/${id}/consents/${clientId}`, {
+        method: 'DELETE',
+        headers: getCsrfHeaders(),
+    });
+}
+
 export async function getUserPasskeys(id) {
     return await fetch(`/auth/v1/users/${id}/webauthn`, {
         method: 'GET',
@@ -224,6 +294,123 @@ export async function getUserPasskeys(id) {
     });
 }
 
+export async function getUserTotp(id) {
+    return await fetch(`/auth/v1/users/${id}/totp`, {
+        method: 'GET',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function postUserTotp(id) {
+    return await fetch(`/auth/v1/users/${id}/totp`, {
+        method: 'POST',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function postUserTotpConfirm(id, data) {
+    return await fetch(`/auth/v1/users/${id}/totp/confirm`, {
+        method: 'POST',
+        headers: getCsrfHeaders(),
+        body: JSON.stringify(data),
+    });
+}
+
+export async function deleteUserTotp(id) {
+    return await fetch(`/auth/v1/users/${id}/totp`, {
+        method: 'DELETE',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function getUserSmsOtp(id) {
+    return await fetch(`/auth/v1/users/${id}/sms_otp`, {
+        method: 'GET',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function postUserSmsOtp(id, data) {
+    return await fetch(`/auth/v1/users/${id}/sms_otp`, {
+        method: 'POST',
+        headers: getCsrfHeaders(),
+        body: JSON.stringify(data),
+    });
+}
+
+export async function postUserSmsOtpConfirm(id, data) {
+    return await fetch(`/auth/v1/users/${id}/sms_otp/confirm`, {
+        method: 'POST',
+        headers: getCsrfHeaders(),
+        body: JSON.stringify(data),
+    });
+}
+
+export async function deleteUserSmsOtp(id) {
+    return await fetch(`/auth/v1/users/${id}/sms_otp`, {
+        method: 'DELETE',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function getUserYubicoOtp(id) {
+    return await fetch(`/auth/v1/users/${id}/yubico_otp`, {
+        method: 'GET',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function postUserYubicoOtp(id, data) {
+    return await fetch(`/auth/v1/users/${id}/yubico_otp`, {
+        method: 'POST',
+        headers: getCsrfHeaders(),
+        body: JSON.stringify(data),
+    });
+}
+
+export async function deleteUserYubicoOtp(id) {
+    return await fetch(`/auth/v1/users/${id}/yubico_otp`, {
+        method: 'DELETE',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function postUserMfaReset(id) {
+    return await fetch(`/auth/v1/users/${id}/mfa/reset`, {
+        method: 'POST',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function getUserEmailOtp(id) {
+    return await fetch(`/auth/v1/users/${id}/email_otp`, {
+        method: 'GET',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function putUserEmailOtp(id, data) {
+    return await fetch(`/auth/v1/users/${id}/email_otp`, {
+        method: 'PUT',
+        headers: getCsrfHeaders(),
+        body: JSON.stringify(data),
+    });
+}
+
+export async function getUserRecoveryCodes(id) {
+    return await fetch(`/auth/v1/users/${id}/mfa/recovery_codes`, {
+        method: 'GET',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function postUserRecoveryCodes(id) {
+    return await fetch(`/auth/v1/users/${id}/mfa/recovery_codes`, {
+        method: 'POST',
+        headers: getCsrfHeaders(),
+    });
+}
+
 export async function postUserProviderLink(id, data) {
     return await fetch(`/auth/v1/providers/${id}/link`, {
         method: 'POST',
@@ -336,3 +523,38 @@ export async function webauthnDelete(id, name) {
         headers: getCsrfHeaders(),
     });
 }
+
+export async function webauthnRename(id, name, data) {
+    return await fetch(`/auth/v1/users/${id}/webauthn/rename/${name}`, {
+        method: 'PUT',
+        headers: getCsrfHeaders(),
+        body: JSON.stringify(data),
+    });
+}
+
+export async function putUserPicture(id, data) {
+    const formData = new FormData();
+    formData.append("picture", data);
+
+    return await fetch(`/auth/v1/users/${id}/picture`, {
+        method: 'PUT',
+        headers: {
+            'csrf-token': getCsrfToken(),
+        },
+        body: formData,
+    });
+}
+
+export async function deleteUserPicture(id) {
+    return await fetch(`/auth/v1/users/${id}/picture`, {
+        method: 'DELETE',
+        headers: getCsrfHeaders(),
+    });
+}
+
+export async function postUserEmailVerification(id) {
+    return await fetch(`/auth/v1/users/${id}/email_verification`, {
+        method: 'POST',
+        headers: getCsrfHeaders(),
+    });
+}
@@ -74,6 +74,23 @@ export async function deleteClient(id) {
     return await checkRedirectForbidden(res);
 }
 
+export async function getClientAccessPolicy(id) {
+    const res = await fetch(`/auth/v1/clients/${id}/access_policy`, {
+        method: 'GET',
+        headers: HEADERS,
+    });
+    return await checkRedirectForbidden(res);
+}
+
+export async function putClientAccessPolicy(id, policy) {
+    const res = await fetch(`/auth/v1/clients/${id}/access_policy`, {
+        method: 'PUT',
+        headers: getHeaders(),
+        body: JSON.stringify(policy),
+    });
+    return await checkRedirectForbidden(res);
+}
+
 export async function getClientColors(id) {
     const res = await fetch(`/auth/v1/clients/${id}/colors`, {
         method: 'GET',
@@ -342,6 +359,14 @@ export async function deleteSessionsForUser(uid) {
     return await checkRedirectForbidden(res);
 }
 
+export async function deleteSession(uid, sid) {
+    const res = await fetch(`/auth/v1/sessions/${uid}/${sid}`, {
+        method: 'DELETE',
+        headers: getHeaders(),
+    });
+    return await checkRedirectForbidden(res);
+}
+
 export async function getUsers() {
     const res = await fetch('/auth/v1/users', {
         method: 'GET',
@@ -395,6 +420,14 @@ export async function deleteUser(uid) {
     return await checkRedirectForbidden(res);
 }
 
+export async function postUserUnlock(uid) {
+    const res = await fetch(`/auth/v1/users/${uid}/unlock`, {
+        method: 'POST',
+        headers: getHeaders(),
+    });
+    return await checkRedirectForbidden(res);
+}
+
 export async function getAttr() {
     const res = await fetch('/auth/v1/users/attr', {
         method: 'GET',
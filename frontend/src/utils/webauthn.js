@@ -1,4 +1,4 @@
-import {webauthnAuthFinish, webauthnAuthStart} from "./dataFetching.js";
+import {authorizePasskeyStart, webauthnAuthFinish, webauthnAuthStart} from "./dataFetching.js";
 import {arrBufToBase64UrlSafe, base64UrlSafeToArrBuf} from "./helpers.js";
 
 export async function webauthnAuth(uid, data, errorMsg) {
@@ -37,7 +37,9 @@ export async function webauthnAuth(uid, data, errorMsg) {
                     signature: arrBufToBase64UrlSafe(challengePk.response.signature),
                 },
                 type: challengePk.type,
-            }
+            },
+            // needed to detect cross-device logins via hybrid transport
+            authenticator_attachment: challengePk.authenticatorAttachment || null,
         }
 
         // send the data to the backend
@@ -64,3 +66,53 @@ export async function webauthnAuth(uid, data, errorMsg) {
         };
     }
 }
+
+// Starts a login with a discoverable passkey, where the user is identified by the passkey itself.
+// With `conditional`, the passkeys are offered inside the browsers' autofill of the E-Mail input.
+// Returns `undefined`, if the ceremony has been aborted or is not supported.
+export async function webauthnDiscoverable(conditional) {
+    if (conditional && !(await window.PublicKeyCredential?.isConditionalMediationAvailable?.())) {
+        return;
+    }
+
+    let res = await authorizePasskeyStart();
+    if (res.status !== 200) {
+        console.error(res);
+        return;
+    }
+    let resp = await res.json();
+    let challenge = resp.rcr;
+    challenge.publicKey.challenge = base64UrlSafeToArrBuf(challenge.publicKey.challenge);
+    for (let cred of challenge.publicKey.allowCredentials || []) {
+        cred.id = base64UrlSafeToArrBuf(cred.id);
+    }
+    if (conditional) {
+        challenge.mediation = 'conditional';
+    } else {
+        delete challenge.mediation;
+    }
+
+    let challengePk;
+    try {
+        challengePk = await navigator.credentials.get(challenge);
+    } catch (e) {
+        console.error(e);
+        return;
+    }
+
+    return {
+        code: resp.code,
+        data: {
+            id: challengePk.id,
+            rawId: arrBufToBase64UrlSafe(challengePk.rawId),
+            response: {
+                authenticatorData: arrBufToBase64UrlSafe(challengePk.response.authenticatorData),
+                clientDataJSON: arrBufToBase64UrlSafe(challengePk.response.clientDataJSON),
+                signature: arrBufToBase64UrlSafe(challengePk.response.signature),
+                userHandle: arrBufToBase64UrlSafe(challengePk.response.userHandle),
+            },
+            type: challengePk.type,
+        },
+        authenticator_attachment: challengePk.authenticatorAttachment || null,
+    };
+}
@@ -0,0 +1,2 @@
+ALTER TABLE events
+    ADD request_id TEXT;
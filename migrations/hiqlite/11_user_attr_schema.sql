@@ -0,0 +1,8 @@
+ALTER TABLE user_attr_config
+    ADD typ TEXT NOT NULL DEFAULT 'string';
+
+ALTER TABLE user_attr_config
+    ADD required INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE user_attr_config
+    ADD regex TEXT;
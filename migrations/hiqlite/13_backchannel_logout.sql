@@ -0,0 +1,2 @@
+ALTER TABLE clients
+    ADD backchannel_logout_uri TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE sessions
+    ADD impersonator_id TEXT;
@@ -0,0 +1,18 @@
+CREATE TABLE user_expiry_notifications
+(
+    user_id      TEXT    NOT NULL
+        CONSTRAINT user_expiry_notifications_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    user_expires INTEGER NOT NULL,
+    sent         INTEGER NOT NULL,
+    CONSTRAINT user_expiry_notifications_pk
+        PRIMARY KEY (user_id, user_expires)
+) STRICT;
+
+-- users, that expired before this table existed, have been handled already
+INSERT INTO user_expiry_notifications (user_id, user_expires, sent)
+SELECT id, user_expires, unixepoch()
+FROM users
+WHERE user_expires IS NOT NULL
+  AND user_expires < unixepoch();
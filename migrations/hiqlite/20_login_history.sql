@@ -0,0 +1,22 @@
+CREATE TABLE login_history
+(
+    id         TEXT    NOT NULL
+        CONSTRAINT login_history_pk
+            PRIMARY KEY,
+    user_id    TEXT    NOT NULL
+        CONSTRAINT login_history_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    timestamp  INTEGER NOT NULL,
+    ip         TEXT    NOT NULL,
+    user_agent TEXT,
+    client_id  TEXT,
+    mfa        INTEGER NOT NULL,
+    success    INTEGER NOT NULL
+) STRICT;
+
+CREATE INDEX login_history_user_id_timestamp_index
+    ON login_history (user_id, timestamp);
+
+CREATE INDEX login_history_timestamp_index
+    ON login_history (timestamp);
@@ -0,0 +1,13 @@
+CREATE TABLE role_mapping_rules
+(
+    id      TEXT    NOT NULL
+        CONSTRAINT role_mapping_rules_pk
+            PRIMARY KEY,
+    name    TEXT    NOT NULL,
+    enabled INTEGER NOT NULL,
+    source  TEXT    NOT NULL,
+    key     TEXT,
+    value   TEXT    NOT NULL,
+    roles   TEXT    NOT NULL,
+    groups  TEXT    NOT NULL
+) STRICT;
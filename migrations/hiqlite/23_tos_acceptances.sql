@@ -0,0 +1,12 @@
+CREATE TABLE tos_acceptances
+(
+    user_id     TEXT    NOT NULL
+        CONSTRAINT tos_acceptances_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    version     TEXT    NOT NULL,
+    accepted_at INTEGER NOT NULL,
+    ip          TEXT    NOT NULL,
+    CONSTRAINT tos_acceptances_pk
+        PRIMARY KEY (user_id, version)
+) STRICT;
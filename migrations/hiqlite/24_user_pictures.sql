@@ -0,0 +1,12 @@
+CREATE TABLE user_pictures
+(
+    user_id      TEXT    NOT NULL
+        CONSTRAINT user_pictures_pk
+            PRIMARY KEY
+        CONSTRAINT user_pictures_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    content_type TEXT    NOT NULL,
+    data         BLOB    NOT NULL,
+    updated      INTEGER NOT NULL
+) STRICT;
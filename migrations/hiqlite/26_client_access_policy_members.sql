@@ -0,0 +1,43 @@
+CREATE TABLE client_access_policy_users
+(
+    client_id TEXT NOT NULL
+        CONSTRAINT client_access_policy_users_client_access_policies_client_id_fk
+            REFERENCES client_access_policies
+            ON DELETE CASCADE,
+    user_id   TEXT NOT NULL
+        CONSTRAINT client_access_policy_users_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    CONSTRAINT client_access_policy_users_pk
+        PRIMARY KEY (client_id, user_id)
+) STRICT;
+
+CREATE TABLE client_access_policy_groups
+(
+    client_id TEXT NOT NULL
+        CONSTRAINT client_access_policy_groups_client_access_policies_client_id_fk
+            REFERENCES client_access_policies
+            ON DELETE CASCADE,
+    group_id  TEXT NOT NULL
+        CONSTRAINT client_access_policy_groups_groups_id_fk
+            REFERENCES groups
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    CONSTRAINT client_access_policy_groups_pk
+        PRIMARY KEY (client_id, group_id)
+) STRICT;
+
+-- IDs, which do not exist anymore, are dropped on the way
+INSERT INTO client_access_policy_users (client_id, user_id)
+SELECT p.client_id, u.id
+FROM client_access_policies p
+         JOIN users u ON instr(',' || p.users || ',', ',' || u.id || ',') > 0;
+
+INSERT INTO client_access_policy_groups (client_id, group_id)
+SELECT p.client_id, g.id
+FROM client_access_policies p
+         JOIN groups g ON instr(',' || p.groups || ',', ',' || g.id || ',') > 0;
+
+ALTER TABLE client_access_policies
+    DROP COLUMN users;
+ALTER TABLE client_access_policies
+    DROP COLUMN groups;
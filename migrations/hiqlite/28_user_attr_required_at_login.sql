@@ -0,0 +1,2 @@
+ALTER TABLE user_attr_config
+    ADD required_at_login INTEGER NOT NULL DEFAULT 0;
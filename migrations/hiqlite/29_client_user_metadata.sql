@@ -0,0 +1,15 @@
+CREATE TABLE client_user_metadata
+(
+    client_id TEXT    NOT NULL
+        CONSTRAINT client_user_metadata_clients_id_fk
+            REFERENCES clients
+            ON DELETE CASCADE,
+    user_id   TEXT    NOT NULL
+        CONSTRAINT client_user_metadata_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    data      BLOB    NOT NULL,
+    updated   INTEGER NOT NULL,
+    CONSTRAINT client_user_metadata_pk
+        PRIMARY KEY (client_id, user_id)
+) STRICT;
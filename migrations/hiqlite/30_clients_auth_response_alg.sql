@@ -0,0 +1,2 @@
+ALTER TABLE clients
+    ADD auth_response_alg TEXT;
@@ -0,0 +1,5 @@
+ALTER TABLE clients
+    ADD jwks TEXT;
+
+ALTER TABLE clients
+    ADD request_uris TEXT;
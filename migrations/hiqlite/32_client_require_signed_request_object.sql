@@ -0,0 +1,2 @@
+ALTER TABLE clients
+    ADD require_signed_request_object INTEGER NOT NULL DEFAULT false;
@@ -0,0 +1,5 @@
+ALTER TABLE clients
+    ADD tls_client_auth TEXT;
+
+ALTER TABLE clients
+    ADD tls_client_auth_subject_dn TEXT;
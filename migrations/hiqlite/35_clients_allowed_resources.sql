@@ -0,0 +1,2 @@
+ALTER TABLE clients
+    ADD allowed_resources TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE clients
+    ADD token_endpoint_auth_method TEXT;
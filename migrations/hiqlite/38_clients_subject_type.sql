@@ -0,0 +1,5 @@
+ALTER TABLE clients
+    ADD subject_type TEXT;
+
+ALTER TABLE clients
+    ADD sector_identifier_uri TEXT;
@@ -0,0 +1,30 @@
+-- Opaque tokens store the access token claims instead of a signed JWT and are bound to their
+-- session. Existing tokens cannot be converted and will be dropped.
+DROP TABLE opaque_tokens;
+
+CREATE TABLE opaque_tokens
+(
+    id         TEXT    NOT NULL
+        CONSTRAINT opaque_tokens_pk
+            PRIMARY KEY,
+    client_id  TEXT    NOT NULL
+        CONSTRAINT opaque_tokens_clients_id_fk
+            REFERENCES clients
+            ON DELETE CASCADE,
+    user_id    TEXT
+        CONSTRAINT opaque_tokens_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    session_id TEXT,
+    exp        INTEGER NOT NULL,
+    claims     TEXT    NOT NULL
+) STRICT;
+
+CREATE INDEX opaque_tokens_client_id_index
+    ON opaque_tokens (client_id);
+
+CREATE INDEX opaque_tokens_user_id_index
+    ON opaque_tokens (user_id);
+
+CREATE INDEX opaque_tokens_session_id_index
+    ON opaque_tokens (session_id);
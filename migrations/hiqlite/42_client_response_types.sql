@@ -0,0 +1,5 @@
+ALTER TABLE clients
+    ADD require_pkce_s256 INTEGER NOT NULL DEFAULT false;
+
+ALTER TABLE clients
+    ADD response_types TEXT NOT NULL DEFAULT 'code';
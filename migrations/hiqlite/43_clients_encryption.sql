@@ -0,0 +1,11 @@
+ALTER TABLE clients
+    ADD id_token_encrypted_response_alg TEXT;
+
+ALTER TABLE clients
+    ADD id_token_encrypted_response_enc TEXT;
+
+ALTER TABLE clients
+    ADD userinfo_encrypted_response_alg TEXT;
+
+ALTER TABLE clients
+    ADD userinfo_encrypted_response_enc TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE clients
+    ADD userinfo_signed_response_alg TEXT;
@@ -0,0 +1,11 @@
+ALTER TABLE clients
+    ADD refresh_token_rotation INTEGER NOT NULL DEFAULT true;
+
+ALTER TABLE clients
+    ADD refresh_token_grace_time INTEGER;
+
+ALTER TABLE clients
+    ADD refresh_token_reuse_detection INTEGER NOT NULL DEFAULT false;
+
+ALTER TABLE refresh_tokens
+    ADD rotated_at INTEGER;
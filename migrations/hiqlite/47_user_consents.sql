@@ -0,0 +1,19 @@
+ALTER TABLE clients
+    ADD consent_required INTEGER NOT NULL DEFAULT false;
+
+CREATE TABLE user_consents
+(
+    user_id      TEXT    NOT NULL
+        CONSTRAINT user_consents_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    client_id    TEXT    NOT NULL
+        CONSTRAINT user_consents_clients_id_fk
+            REFERENCES clients
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    scopes       TEXT    NOT NULL,
+    created_at   INTEGER NOT NULL,
+    last_updated INTEGER NOT NULL,
+    CONSTRAINT user_consents_pk
+        PRIMARY KEY (user_id, client_id)
+) STRICT;
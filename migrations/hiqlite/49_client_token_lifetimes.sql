@@ -0,0 +1,8 @@
+ALTER TABLE clients
+    ADD id_token_lifetime INTEGER;
+
+ALTER TABLE clients
+    ADD refresh_token_lifetime INTEGER;
+
+ALTER TABLE clients
+    ADD claim_template TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE users_values
+    ADD tz TEXT;
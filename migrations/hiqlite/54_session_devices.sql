@@ -0,0 +1,22 @@
+CREATE TABLE session_devices
+(
+    session_id TEXT    NOT NULL
+        CONSTRAINT session_devices_pk
+            PRIMARY KEY
+        CONSTRAINT session_devices_sessions_id_fk
+            REFERENCES sessions
+            ON DELETE CASCADE,
+    user_id    TEXT    NOT NULL
+        CONSTRAINT session_devices_users_id_fk
+            REFERENCES users
+            ON DELETE CASCADE,
+    name       TEXT    NOT NULL,
+    user_agent TEXT,
+    ip         TEXT,
+    location   TEXT,
+    first_seen INTEGER NOT NULL,
+    last_seen  INTEGER NOT NULL
+) STRICT;
+
+CREATE INDEX session_devices_user_id_index
+    ON session_devices (user_id);
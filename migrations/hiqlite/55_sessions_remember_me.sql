@@ -0,0 +1,2 @@
+ALTER TABLE sessions
+    ADD remember_me INTEGER DEFAULT false NOT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE sessions
+    ADD auth_time INTEGER;
@@ -0,0 +1,4 @@
+ALTER TABLE sessions
+    ADD client_id TEXT;
+ALTER TABLE sessions
+    ADD auth_method TEXT;
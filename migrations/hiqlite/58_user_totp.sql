@@ -0,0 +1,13 @@
+CREATE TABLE user_totp
+(
+    user_id   TEXT    NOT NULL
+        CONSTRAINT user_totp_pk
+            PRIMARY KEY
+        CONSTRAINT user_totp_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    secret    BLOB    NOT NULL,
+    confirmed INTEGER NOT NULL DEFAULT false,
+    created   INTEGER NOT NULL,
+    last_step INTEGER NOT NULL DEFAULT 0
+) STRICT;
@@ -0,0 +1,11 @@
+CREATE TABLE mfa_recovery_codes
+(
+    user_id   TEXT    NOT NULL
+        CONSTRAINT mfa_recovery_codes_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    code_hash TEXT    NOT NULL,
+    created   INTEGER NOT NULL,
+    CONSTRAINT mfa_recovery_codes_pk
+        PRIMARY KEY (user_id, code_hash)
+) STRICT;
@@ -0,0 +1,15 @@
+CREATE TABLE password_expiry_reminders
+(
+    user_id          TEXT    NOT NULL
+        CONSTRAINT password_expiry_reminders_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    password_expires INTEGER NOT NULL,
+    remind_days      INTEGER NOT NULL,
+    sent             INTEGER NOT NULL,
+    CONSTRAINT password_expiry_reminders_pk
+        PRIMARY KEY (user_id, password_expires, remind_days)
+) STRICT;
+
+CREATE INDEX password_expiry_reminders_password_expires_index
+    ON password_expiry_reminders (password_expires);
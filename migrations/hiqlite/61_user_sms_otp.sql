@@ -0,0 +1,16 @@
+CREATE TABLE user_sms_otp
+(
+    user_id   TEXT    NOT NULL
+        CONSTRAINT user_sms_otp_pk
+            PRIMARY KEY
+        CONSTRAINT user_sms_otp_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    phone     TEXT    NOT NULL,
+    verified  INTEGER NOT NULL DEFAULT false,
+    created   INTEGER NOT NULL,
+    code_hash TEXT,
+    expires   INTEGER,
+    attempts  INTEGER NOT NULL DEFAULT 0,
+    last_sent INTEGER
+) STRICT;
@@ -0,0 +1,2 @@
+ALTER TABLE groups
+    ADD force_mfa INTEGER DEFAULT false NOT NULL;
@@ -0,0 +1,18 @@
+CREATE TABLE trusted_devices
+(
+    id         TEXT    NOT NULL
+        CONSTRAINT trusted_devices_pk
+            PRIMARY KEY,
+    user_id    TEXT    NOT NULL
+        CONSTRAINT trusted_devices_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    name       TEXT    NOT NULL,
+    user_agent TEXT,
+    created    INTEGER NOT NULL,
+    expires    INTEGER NOT NULL,
+    last_used  INTEGER NOT NULL
+) STRICT;
+
+CREATE INDEX trusted_devices_user_id_index
+    ON trusted_devices (user_id);
@@ -0,0 +1,2 @@
+ALTER TABLE users
+    ADD mfa_reenroll INTEGER NOT NULL DEFAULT false;
@@ -0,0 +1,12 @@
+CREATE TABLE user_yubico_otp
+(
+    user_id   TEXT    NOT NULL
+        CONSTRAINT user_yubico_otp_pk
+            PRIMARY KEY
+        CONSTRAINT user_yubico_otp_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    public_id TEXT    NOT NULL,
+    created   INTEGER NOT NULL,
+    last_used INTEGER NOT NULL
+) STRICT;
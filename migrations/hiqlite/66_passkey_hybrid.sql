@@ -0,0 +1,5 @@
+ALTER TABLE passkeys
+    ADD last_used_hybrid INTEGER;
+
+ALTER TABLE clients
+    ADD deny_hybrid_passkeys INTEGER NOT NULL DEFAULT false;
@@ -0,0 +1,18 @@
+CREATE TABLE audit_log
+(
+    seq        INTEGER NOT NULL
+        CONSTRAINT audit_log_pk
+            PRIMARY KEY,
+    timestamp  INTEGER NOT NULL,
+    actor      TEXT    NOT NULL,
+    action     TEXT    NOT NULL,
+    entity     TEXT    NOT NULL,
+    entity_id  TEXT    NOT NULL,
+    diff       TEXT    NOT NULL,
+    request_id TEXT,
+    prev_hash  TEXT    NOT NULL,
+    hash       TEXT    NOT NULL
+) STRICT;
+
+CREATE INDEX audit_log_entity_entity_id_index
+    ON audit_log (entity, entity_id);
@@ -0,0 +1,5 @@
+ALTER TABLE events
+    ADD user_id TEXT;
+
+CREATE INDEX events_user_id_index
+    ON events (user_id);
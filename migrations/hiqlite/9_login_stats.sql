@@ -0,0 +1,10 @@
+CREATE TABLE login_stats
+(
+    bucket      INTEGER NOT NULL,
+    kind        TEXT    NOT NULL,
+    subject     TEXT    NOT NULL,
+    failed      INTEGER NOT NULL,
+    blacklisted INTEGER NOT NULL,
+    CONSTRAINT login_stats_pk
+        PRIMARY KEY (bucket, kind, subject)
+) STRICT;
@@ -0,0 +1,15 @@
+create table password_expiry_reminders
+(
+    user_id          varchar not null
+        constraint password_expiry_reminders_users_id_fk
+            references users
+            on update cascade on delete cascade,
+    password_expires bigint  not null,
+    remind_days      bigint  not null,
+    sent             bigint  not null,
+    constraint password_expiry_reminders_pk
+        primary key (user_id, password_expires, remind_days)
+);
+
+create index password_expiry_reminders_password_expires_index
+    on password_expiry_reminders (password_expires);
@@ -0,0 +1,18 @@
+create table audit_log
+(
+    seq        bigint  not null
+        constraint audit_log_pk
+            primary key,
+    timestamp  bigint  not null,
+    actor      varchar not null,
+    action     varchar not null,
+    entity     varchar not null,
+    entity_id  varchar not null,
+    diff       varchar not null,
+    request_id varchar,
+    prev_hash  varchar not null,
+    hash       varchar not null
+);
+
+create index audit_log_entity_entity_id_index
+    on audit_log (entity, entity_id);
@@ -0,0 +1,10 @@
+create table login_stats
+(
+    bucket      bigint  not null,
+    kind        varchar not null,
+    subject     varchar not null,
+    failed      bigint  not null,
+    blacklisted bigint  not null,
+    constraint login_stats_pk
+        primary key (bucket, kind, subject)
+);
@@ -0,0 +1,8 @@
+ALTER TABLE user_attr_config
+    ADD typ VARCHAR NOT NULL DEFAULT 'string';
+
+ALTER TABLE user_attr_config
+    ADD required BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE user_attr_config
+    ADD regex VARCHAR;
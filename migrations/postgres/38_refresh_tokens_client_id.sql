@@ -0,0 +1,5 @@
+ALTER TABLE refresh_tokens
+    ADD client_id VARCHAR;
+
+CREATE INDEX refresh_tokens_client_id_index
+    ON refresh_tokens (client_id);
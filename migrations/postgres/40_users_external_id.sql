@@ -0,0 +1,5 @@
+ALTER TABLE users
+    ADD external_id VARCHAR;
+
+CREATE UNIQUE INDEX users_external_id_uindex
+    ON users (external_id);
@@ -0,0 +1,5 @@
+ALTER TABLE users
+    ADD deleted_at BIGINT;
+
+CREATE INDEX users_deleted_at_index
+    ON users (deleted_at);
@@ -0,0 +1,18 @@
+create table user_expiry_notifications
+(
+    user_id      varchar not null
+        constraint user_expiry_notifications_users_id_fk
+            references users
+            on update cascade on delete cascade,
+    user_expires bigint  not null,
+    sent         bigint  not null,
+    constraint user_expiry_notifications_pk
+        primary key (user_id, user_expires)
+);
+
+-- users, that expired before this table existed, have been handled already
+insert into user_expiry_notifications (user_id, user_expires, sent)
+select id, user_expires, extract(epoch from now())::bigint
+from users
+where user_expires is not null
+  and user_expires < extract(epoch from now())::bigint;
@@ -0,0 +1,17 @@
+CREATE TABLE password_policies_groups
+(
+    group_id           VARCHAR NOT NULL
+        CONSTRAINT password_policies_groups_pk
+            PRIMARY KEY
+        CONSTRAINT password_policies_groups_groups_id_fk
+            REFERENCES groups
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    length_min         INTEGER NOT NULL,
+    length_max         INTEGER NOT NULL,
+    include_lower_case INTEGER,
+    include_upper_case INTEGER,
+    include_digits     INTEGER,
+    include_special    INTEGER,
+    valid_days         INTEGER,
+    not_recently_used  INTEGER
+);
@@ -0,0 +1,2 @@
+ALTER TABLE users
+    ADD must_change_password BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,22 @@
+CREATE TABLE login_history
+(
+    id         VARCHAR NOT NULL
+        CONSTRAINT login_history_pk
+            PRIMARY KEY,
+    user_id    VARCHAR NOT NULL
+        CONSTRAINT login_history_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    timestamp  BIGINT  NOT NULL,
+    ip         VARCHAR NOT NULL,
+    user_agent VARCHAR,
+    client_id  VARCHAR,
+    mfa        BOOLEAN NOT NULL,
+    success    BOOLEAN NOT NULL
+);
+
+CREATE INDEX login_history_user_id_timestamp_index
+    ON login_history (user_id, timestamp);
+
+CREATE INDEX login_history_timestamp_index
+    ON login_history (timestamp);
@@ -0,0 +1,27 @@
+CREATE TABLE groups_nested
+(
+    parent_id VARCHAR NOT NULL
+        CONSTRAINT groups_nested_groups_parent_id_fk
+            REFERENCES groups
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    child_id  VARCHAR NOT NULL
+        CONSTRAINT groups_nested_groups_child_id_fk
+            REFERENCES groups
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    CONSTRAINT groups_nested_pk
+        PRIMARY KEY (parent_id, child_id)
+);
+
+CREATE TABLE groups_roles
+(
+    group_id VARCHAR NOT NULL
+        CONSTRAINT groups_roles_groups_id_fk
+            REFERENCES groups
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    role_id  VARCHAR NOT NULL
+        CONSTRAINT groups_roles_roles_id_fk
+            REFERENCES roles
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    CONSTRAINT groups_roles_pk
+        PRIMARY KEY (group_id, role_id)
+);
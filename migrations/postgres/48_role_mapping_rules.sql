@@ -0,0 +1,13 @@
+CREATE TABLE role_mapping_rules
+(
+    id      VARCHAR NOT NULL
+        CONSTRAINT role_mapping_rules_pk
+            PRIMARY KEY,
+    name    VARCHAR NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    source  VARCHAR NOT NULL,
+    key     VARCHAR,
+    value   VARCHAR NOT NULL,
+    roles   VARCHAR NOT NULL,
+    groups  VARCHAR NOT NULL
+);
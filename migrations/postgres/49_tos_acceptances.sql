@@ -0,0 +1,12 @@
+CREATE TABLE tos_acceptances
+(
+    user_id     VARCHAR NOT NULL
+        CONSTRAINT tos_acceptances_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    version     VARCHAR NOT NULL,
+    accepted_at BIGINT  NOT NULL,
+    ip          VARCHAR NOT NULL,
+    CONSTRAINT tos_acceptances_pk
+        PRIMARY KEY (user_id, version)
+);
@@ -0,0 +1,12 @@
+CREATE TABLE user_pictures
+(
+    user_id      VARCHAR NOT NULL
+        CONSTRAINT user_pictures_pk
+            PRIMARY KEY
+        CONSTRAINT user_pictures_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    content_type VARCHAR NOT NULL,
+    data         BYTEA   NOT NULL,
+    updated      BIGINT  NOT NULL
+);
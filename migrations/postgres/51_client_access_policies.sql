@@ -0,0 +1,11 @@
+CREATE TABLE client_access_policies
+(
+    client_id VARCHAR NOT NULL
+        CONSTRAINT client_access_policies_pk
+            PRIMARY KEY
+        CONSTRAINT client_access_policies_clients_id_fk
+            REFERENCES clients
+            ON DELETE CASCADE,
+    users     VARCHAR,
+    groups    VARCHAR
+);
@@ -0,0 +1,42 @@
+CREATE TABLE client_access_policy_users
+(
+    client_id VARCHAR NOT NULL
+        CONSTRAINT client_access_policy_users_client_access_policies_client_id_fk
+            REFERENCES client_access_policies
+            ON DELETE CASCADE,
+    user_id   VARCHAR NOT NULL
+        CONSTRAINT client_access_policy_users_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    CONSTRAINT client_access_policy_users_pk
+        PRIMARY KEY (client_id, user_id)
+);
+
+CREATE TABLE client_access_policy_groups
+(
+    client_id VARCHAR NOT NULL
+        CONSTRAINT client_access_policy_groups_client_access_policies_client_id_fk
+            REFERENCES client_access_policies
+            ON DELETE CASCADE,
+    group_id  VARCHAR NOT NULL
+        CONSTRAINT client_access_policy_groups_groups_id_fk
+            REFERENCES groups
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    CONSTRAINT client_access_policy_groups_pk
+        PRIMARY KEY (client_id, group_id)
+);
+
+-- IDs, which do not exist anymore, are dropped on the way
+INSERT INTO client_access_policy_users (client_id, user_id)
+SELECT p.client_id, u.id
+FROM client_access_policies p
+         JOIN users u ON u.id = ANY (string_to_array(p.users, ','));
+
+INSERT INTO client_access_policy_groups (client_id, group_id)
+SELECT p.client_id, g.id
+FROM client_access_policies p
+         JOIN groups g ON g.id = ANY (string_to_array(p.groups, ','));
+
+ALTER TABLE client_access_policies
+    DROP COLUMN users,
+    DROP COLUMN groups;
@@ -0,0 +1,5 @@
+ALTER TABLE clients
+    ADD jwks VARCHAR;
+
+ALTER TABLE clients
+    ADD request_uris VARCHAR;
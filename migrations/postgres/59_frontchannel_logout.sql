@@ -0,0 +1,16 @@
+ALTER TABLE clients
+    ADD frontchannel_logout_uri VARCHAR;
+
+CREATE TABLE sessions_clients
+(
+    session_id VARCHAR NOT NULL
+        CONSTRAINT sessions_clients_sessions_id_fk
+            REFERENCES sessions
+            ON DELETE CASCADE,
+    client_id  VARCHAR NOT NULL
+        CONSTRAINT sessions_clients_clients_id_fk
+            REFERENCES clients
+            ON DELETE CASCADE,
+    CONSTRAINT sessions_clients_pk
+        PRIMARY KEY (session_id, client_id)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE clients
+    ADD jwks_uri VARCHAR;
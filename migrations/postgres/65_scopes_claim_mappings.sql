@@ -0,0 +1,2 @@
+ALTER TABLE scopes
+    ADD claim_mappings VARCHAR;
@@ -0,0 +1,25 @@
+ALTER TABLE clients
+    ADD opaque_access_tokens BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE opaque_tokens
+(
+    id        VARCHAR NOT NULL
+        CONSTRAINT opaque_tokens_pk
+            PRIMARY KEY,
+    client_id VARCHAR NOT NULL
+        CONSTRAINT opaque_tokens_clients_id_fk
+            REFERENCES clients
+            ON DELETE CASCADE,
+    user_id   VARCHAR
+        CONSTRAINT opaque_tokens_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    exp       BIGINT  NOT NULL,
+    token     VARCHAR NOT NULL
+);
+
+CREATE INDEX opaque_tokens_client_id_index
+    ON opaque_tokens (client_id);
+
+CREATE INDEX opaque_tokens_user_id_index
+    ON opaque_tokens (user_id);
@@ -0,0 +1,5 @@
+ALTER TABLE clients
+    ADD require_pkce_s256 BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE clients
+    ADD response_types VARCHAR NOT NULL DEFAULT 'code';
@@ -0,0 +1,11 @@
+ALTER TABLE clients
+    ADD id_token_encrypted_response_alg VARCHAR;
+
+ALTER TABLE clients
+    ADD id_token_encrypted_response_enc VARCHAR;
+
+ALTER TABLE clients
+    ADD userinfo_encrypted_response_alg VARCHAR;
+
+ALTER TABLE clients
+    ADD userinfo_encrypted_response_enc VARCHAR;
@@ -0,0 +1,8 @@
+ALTER TABLE refresh_tokens
+    ADD family_id VARCHAR;
+
+ALTER TABLE refresh_tokens
+    ADD session_id VARCHAR;
+
+CREATE INDEX refresh_tokens_family_id_index
+    ON refresh_tokens (family_id);
@@ -0,0 +1,11 @@
+ALTER TABLE clients
+    ADD refresh_token_rotation BOOLEAN NOT NULL DEFAULT TRUE;
+
+ALTER TABLE clients
+    ADD refresh_token_grace_time INTEGER;
+
+ALTER TABLE clients
+    ADD refresh_token_reuse_detection BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE refresh_tokens
+    ADD rotated_at BIGINT;
@@ -0,0 +1,19 @@
+ALTER TABLE clients
+    ADD consent_required BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE user_consents
+(
+    user_id      VARCHAR NOT NULL
+        CONSTRAINT user_consents_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    client_id    VARCHAR NOT NULL
+        CONSTRAINT user_consents_clients_id_fk
+            REFERENCES clients
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    scopes       VARCHAR NOT NULL,
+    created_at   BIGINT  NOT NULL,
+    last_updated BIGINT  NOT NULL,
+    CONSTRAINT user_consents_pk
+        PRIMARY KEY (user_id, client_id)
+);
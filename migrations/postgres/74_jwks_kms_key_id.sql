@@ -0,0 +1,2 @@
+ALTER TABLE jwks
+    ADD kms_key_id VARCHAR;
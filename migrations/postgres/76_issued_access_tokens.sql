@@ -0,0 +1,18 @@
+CREATE TABLE issued_access_tokens
+(
+    jti        VARCHAR NOT NULL
+        CONSTRAINT issued_access_tokens_pk
+            PRIMARY KEY,
+    user_id    VARCHAR,
+    session_id VARCHAR,
+    exp        BIGINT  NOT NULL
+);
+
+CREATE INDEX issued_access_tokens_user_id_index
+    ON issued_access_tokens (user_id);
+
+CREATE INDEX issued_access_tokens_session_id_index
+    ON issued_access_tokens (session_id);
+
+CREATE INDEX issued_access_tokens_exp_index
+    ON issued_access_tokens (exp);
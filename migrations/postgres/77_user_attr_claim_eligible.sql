@@ -0,0 +1,2 @@
+ALTER TABLE user_attr_config
+    ADD claim_eligible BOOLEAN NOT NULL DEFAULT TRUE;
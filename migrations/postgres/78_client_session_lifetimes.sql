@@ -0,0 +1,5 @@
+ALTER TABLE clients
+    ADD session_lifetime INTEGER;
+
+ALTER TABLE clients
+    ADD session_timeout INTEGER;
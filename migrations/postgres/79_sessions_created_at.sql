@@ -0,0 +1,6 @@
+ALTER TABLE sessions
+    ADD created_at BIGINT NOT NULL DEFAULT 0;
+
+-- the creation time of existing sessions is unknown -> use the closest known value
+UPDATE sessions
+SET created_at = last_seen;
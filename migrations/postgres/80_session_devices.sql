@@ -0,0 +1,22 @@
+CREATE TABLE session_devices
+(
+    session_id VARCHAR NOT NULL
+        CONSTRAINT session_devices_pk
+            PRIMARY KEY
+        CONSTRAINT session_devices_sessions_id_fk
+            REFERENCES sessions
+            ON DELETE CASCADE,
+    user_id    VARCHAR NOT NULL
+        CONSTRAINT session_devices_users_id_fk
+            REFERENCES users
+            ON DELETE CASCADE,
+    name       VARCHAR NOT NULL,
+    user_agent VARCHAR,
+    ip         VARCHAR,
+    location   VARCHAR,
+    first_seen BIGINT  NOT NULL,
+    last_seen  BIGINT  NOT NULL
+);
+
+CREATE INDEX session_devices_user_id_index
+    ON session_devices (user_id);
@@ -0,0 +1,4 @@
+ALTER TABLE sessions
+    ADD client_id VARCHAR;
+ALTER TABLE sessions
+    ADD auth_method VARCHAR;
@@ -0,0 +1,13 @@
+CREATE TABLE user_totp
+(
+    user_id   VARCHAR NOT NULL
+        CONSTRAINT user_totp_pk
+            PRIMARY KEY
+        CONSTRAINT user_totp_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    secret    BYTEA   NOT NULL,
+    confirmed BOOLEAN NOT NULL DEFAULT false,
+    created   BIGINT  NOT NULL,
+    last_step BIGINT  NOT NULL DEFAULT 0
+);
@@ -0,0 +1,11 @@
+CREATE TABLE mfa_recovery_codes
+(
+    user_id   VARCHAR NOT NULL
+        CONSTRAINT mfa_recovery_codes_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    code_hash VARCHAR NOT NULL,
+    created   BIGINT  NOT NULL,
+    CONSTRAINT mfa_recovery_codes_pk
+        PRIMARY KEY (user_id, code_hash)
+);
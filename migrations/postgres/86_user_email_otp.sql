@@ -0,0 +1,14 @@
+CREATE TABLE user_email_otp
+(
+    user_id   VARCHAR NOT NULL
+        CONSTRAINT user_email_otp_pk
+            PRIMARY KEY
+        CONSTRAINT user_email_otp_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    enabled   BOOLEAN NOT NULL DEFAULT false,
+    code_hash VARCHAR,
+    expires   BIGINT,
+    attempts  BIGINT  NOT NULL DEFAULT 0,
+    last_sent BIGINT
+);
@@ -0,0 +1,16 @@
+CREATE TABLE user_sms_otp
+(
+    user_id   VARCHAR NOT NULL
+        CONSTRAINT user_sms_otp_pk
+            PRIMARY KEY
+        CONSTRAINT user_sms_otp_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    phone     VARCHAR NOT NULL,
+    verified  BOOLEAN NOT NULL DEFAULT false,
+    created   BIGINT  NOT NULL,
+    code_hash VARCHAR,
+    expires   BIGINT,
+    attempts  BIGINT  NOT NULL DEFAULT 0,
+    last_sent BIGINT
+);
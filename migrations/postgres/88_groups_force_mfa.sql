@@ -0,0 +1,2 @@
+ALTER TABLE groups
+    ADD force_mfa BOOLEAN DEFAULT false NOT NULL;
@@ -0,0 +1,18 @@
+CREATE TABLE trusted_devices
+(
+    id         VARCHAR NOT NULL
+        CONSTRAINT trusted_devices_pk
+            PRIMARY KEY,
+    user_id    VARCHAR NOT NULL
+        CONSTRAINT trusted_devices_users_id_fk
+            REFERENCES users
+            ON UPDATE CASCADE ON DELETE CASCADE,
+    name       VARCHAR NOT NULL,
+    user_agent VARCHAR,
+    created    BIGINT  NOT NULL,
+    expires    BIGINT  NOT NULL,
+    last_used  BIGINT  NOT NULL
+);
+
+CREATE INDEX trusted_devices_user_id_index
+    ON trusted_devices (user_id);
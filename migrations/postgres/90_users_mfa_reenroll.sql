@@ -0,0 +1,2 @@
+ALTER TABLE users
+    ADD mfa_reenroll BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,5 @@
+ALTER TABLE passkeys
+    ADD last_used_hybrid BIGINT;
+
+ALTER TABLE clients
+    ADD deny_hybrid_passkeys BOOLEAN NOT NULL DEFAULT FALSE;
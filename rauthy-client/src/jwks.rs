@@ -34,6 +34,7 @@ pub(crate) enum JwkKeyPairAlg {
     RS256,
     RS384,
     RS512,
+    ES384,
     EdDSA,
 }
 
@@ -42,6 +43,7 @@ pub(crate) enum JwkKeyPairAlg {
 pub(crate) enum JwkKeyPairType {
     RSA,
     OKP,
+    EC,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -50,10 +52,11 @@ pub(crate) struct JwkPublicKey {
     pub kty: JwkKeyPairType,
     pub alg: JwkKeyPairAlg,
     pub kid: String,
-    pub crv: Option<String>,      // Ed25519
+    pub crv: Option<String>,      // Ed25519 / P-384
     pub n: Option<String>,        // RSA
     pub e: Option<String>,        // RSA
-    pub x: Option<String>,        // OCT
+    pub x: Option<String>,        // OCT / EC
+    pub y: Option<String>,        // EC
     pub x_bytes: Option<Vec<u8>>, // pre-decoded base64 string to speed up checking
 }
 
@@ -145,6 +148,23 @@ pub(crate) async fn jwks_handler() {
                                         }
                                     }
                                 }
+                            } else if key.alg == JwkKeyPairAlg::ES384 {
+                                // pre-build the uncompressed SEC1 point `0x04 || x || y`
+                                if let (Some(x), Some(y)) = (&key.x, &key.y) {
+                                    match (base64_url_no_pad_decode(x), base64_url_no_pad_decode(y)) {
+                                        (Ok(x), Ok(y)) => {
+                                            let mut bytes = Vec::with_capacity(1 + x.len() + y.len());
+                                            bytes.push(0x04);
+                                            bytes.extend(x);
+                                            bytes.extend(y);
+                                            key.x_bytes = Some(bytes);
+                                        }
+                                        _ => {
+                                            error!("Error pre-decoding given ES384 'x' / 'y' pub key bytes");
+                                            return None;
+                                        }
+                                    }
+                                }
                             }
                             Some(key)
                         })
@@ -183,6 +183,7 @@ pub enum Algorithm {
     RS256,
     RS384,
     RS512,
+    ES384,
     EdDSA,
 }
 
@@ -2,7 +2,7 @@ use crate::jwks::{JwkKeyPairAlg, JwkPublicKey};
 use crate::provider::OidcProvider;
 use crate::rauthy_error::RauthyError;
 use crate::{base64_url_no_pad_decode, validate_jwt};
-use jwt_simple::algorithms::{EdDSAPublicKeyLike, RSAPublicKeyLike};
+use jwt_simple::algorithms::{ECDSAP384PublicKeyLike, EdDSAPublicKeyLike, RSAPublicKeyLike};
 use jwt_simple::claims;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
@@ -275,6 +275,20 @@ macro_rules! validate_jwt {
                 pk.verify_token::<$type>($token, Some($options))
             }
 
+            JwkKeyPairAlg::ES384 => {
+                let bytes = $pub_key.x_bytes.as_ref().ok_or_else(|| {
+                    RauthyError::JWK(Cow::from("Invalid 'x' / 'y' for ES384 key"))
+                })?;
+                let pk = jwt_simple::algorithms::ES384PublicKey::from_bytes(bytes.as_slice())
+                    .map_err(|err| {
+                        RauthyError::JWK(Cow::from(format!(
+                            "Cannot build ES384 key from public key bytes: {:?}",
+                            err,
+                        )))
+                    })?;
+                pk.verify_token::<$type>($token, Some($options))
+            }
+
             JwkKeyPairAlg::EdDSA => {
                 let bytes = $pub_key
                     .x_bytes
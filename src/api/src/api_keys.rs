@@ -1,10 +1,18 @@
 use crate::ReqPrincipal;
-use actix_web::{delete, get, post, put, web, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 use actix_web_validator::Json;
 use mime_guess::mime::TEXT_PLAIN_UTF_8;
-use rauthy_api_types::api_keys::{ApiKeyRequest, ApiKeyResponse, ApiKeysResponse};
+use rauthy_api_types::api_keys::{
+    ApiKeyExchangeRequest, ApiKeyExchangeResponse, ApiKeyRequest, ApiKeyResponse, ApiKeysResponse,
+};
+use rauthy_api_types::users::MfaPurpose;
+use rauthy_common::clock;
+use rauthy_common::constants::{API_KEY_EXCHANGE_PREFIX, API_KEY_EXCHANGE_TTL};
+use rauthy_common::utils::get_rand;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::entity::api_keys::ApiKeyEntity;
+use rauthy_models::entity::audit_log::{AuditAction, AuditLog};
+use rauthy_models::entity::webauthn::WebauthnServiceReq;
 
 /// Returns all API Keys
 ///
@@ -36,6 +44,8 @@ pub async fn get_api_keys(principal: ReqPrincipal) -> Result<HttpResponse, Error
 
 /// Create a new API Key
 ///
+/// The name prefix `passkey-` is reserved for keys from the Passkey exchange.
+///
 /// **Permissions**
 /// - rauthy_admin
 #[utoipa::path(
@@ -52,13 +62,29 @@ pub async fn get_api_keys(principal: ReqPrincipal) -> Result<HttpResponse, Error
 #[post("/api_keys")]
 pub async fn post_api_key(
     principal: ReqPrincipal,
+    req: HttpRequest,
     payload: Json<ApiKeyRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_admin_session()?;
 
-    let req = payload.into_inner();
-    let access = req.access.into_iter().map(|a| a.into()).collect();
-    let secret = ApiKeyEntity::create(req.name, req.exp, access).await?;
+    let payload = payload.into_inner();
+    // expired keys with this prefix are cleaned up by the scheduler
+    if payload.name.starts_with(API_KEY_EXCHANGE_PREFIX) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!(
+                "The name prefix '{}' is reserved for API Keys from a Passkey exchange",
+                API_KEY_EXCHANGE_PREFIX
+            ),
+        ));
+    }
+
+    let name = payload.name.clone();
+    let access = payload.access.into_iter().map(|a| a.into()).collect();
+    let secret = ApiKeyEntity::create(payload.name, payload.exp, access).await?;
+
+    let after = ApiKeyResponse::from(ApiKeyEntity::find(&name).await?.into_api_key()?);
+    AuditLog::created(&principal, &req, "api_key", &name, &after).await;
 
     Ok(HttpResponse::Ok()
         .content_type(TEXT_PLAIN_UTF_8)
@@ -83,22 +109,27 @@ pub async fn post_api_key(
 #[put("/api_keys/{name}")]
 pub async fn put_api_key(
     principal: ReqPrincipal,
+    req: HttpRequest,
     name: web::Path<String>,
     payload: Json<ApiKeyRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_admin_session()?;
 
     let name = name.into_inner();
-    let req = payload.into_inner();
-    if req.name != name {
+    let payload = payload.into_inner();
+    if payload.name != name {
         return Err(ErrorResponse::new(
             ErrorResponseType::BadRequest,
             "JSON payload does not match the Name from the path".to_string(),
         ));
     }
 
-    let access = req.access.into_iter().map(|a| a.into()).collect();
-    ApiKeyEntity::update(&name, req.exp, access).await?;
+    let before = ApiKeyResponse::from(ApiKeyEntity::find(&name).await?.into_api_key()?);
+    let access = payload.access.into_iter().map(|a| a.into()).collect();
+    ApiKeyEntity::update(&name, payload.exp, access).await?;
+
+    let after = ApiKeyResponse::from(ApiKeyEntity::find(&name).await?.into_api_key()?);
+    AuditLog::updated(&principal, &req, "api_key", &name, &before, &after).await;
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -120,12 +151,15 @@ pub async fn put_api_key(
 #[delete("/api_keys/{name}")]
 pub async fn delete_api_key(
     principal: ReqPrincipal,
+    req: HttpRequest,
     name: web::Path<String>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_admin_session()?;
 
     let name = name.into_inner();
+    let before = ApiKeyResponse::from(ApiKeyEntity::find(&name).await?.into_api_key()?);
     ApiKeyEntity::delete(&name).await?;
+    AuditLog::deleted(&principal, &req, "api_key", &name, &before).await;
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -185,14 +219,78 @@ pub async fn get_api_key_test(
 #[put("/api_keys/{name}/secret")]
 pub async fn put_api_key_secret(
     principal: ReqPrincipal,
+    req: HttpRequest,
     name: web::Path<String>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_admin_session()?;
 
     let name = name.into_inner();
     let secret = ApiKeyEntity::generate_secret(&name).await?;
+    AuditLog::action(
+        &principal,
+        &req,
+        AuditAction::Update,
+        "api_key_secret",
+        &name,
+    )
+    .await;
 
     Ok(HttpResponse::Ok()
         .content_type(TEXT_PLAIN_UTF_8)
         .body(secret))
 }
+
+/// Exchange a WebAuthn assertion for a short-lived API Key
+///
+/// The `mfa_code` must come from a successful `/users/{id}/webauthn/auth/finish` with the purpose
+/// `ApiKeyExchange` for the currently logged-in admin. This makes it possible to use the API
+/// interactively from scripts without any long-lived static API Keys.
+///
+/// The lifetime of the new key is capped at `API_KEY_EXCHANGE_TTL`. Expired keys will be
+/// cleaned up automatically.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/api_keys/exchange",
+    tag = "api_keys",
+    request_body = ApiKeyExchangeRequest,
+    responses(
+        (status = 200, description = "Ok", body = ApiKeyExchangeResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/api_keys/exchange")]
+pub async fn post_api_key_exchange(
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    payload: Json<ApiKeyExchangeRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+    let user_id = principal.user_id()?;
+
+    let payload = payload.into_inner();
+    let svc_req = WebauthnServiceReq::find(payload.mfa_code).await?;
+    svc_req.validate(user_id, MfaPurpose::ApiKeyExchange)?;
+    svc_req.delete().await?;
+
+    let ttl = payload
+        .ttl
+        .unwrap_or(*API_KEY_EXCHANGE_TTL)
+        .min(*API_KEY_EXCHANGE_TTL);
+    let expires = clock::now().timestamp() + ttl as i64;
+    let name = format!("{}{}", API_KEY_EXCHANGE_PREFIX, get_rand(12));
+    let access = payload.access.into_iter().map(|a| a.into()).collect();
+    let secret = ApiKeyEntity::create(name.clone(), Some(expires), access).await?;
+
+    let after = ApiKeyResponse::from(ApiKeyEntity::find(&name).await?.into_api_key()?);
+    AuditLog::created(&principal, &req, "api_key", &name, &after).await;
+
+    Ok(HttpResponse::Ok().json(ApiKeyExchangeResponse {
+        name,
+        secret,
+        expires,
+    }))
+}
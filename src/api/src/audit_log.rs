@@ -0,0 +1,68 @@
+use crate::ReqPrincipal;
+use actix_web::{get, HttpResponse};
+use rauthy_api_types::audit_log::{AuditLogEntryResponse, AuditLogParams, AuditLogVerifyResponse};
+use rauthy_error::ErrorResponse;
+use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::audit_log::AuditLog;
+
+/// Get the latest audit log entries
+///
+/// The audit log records every admin mutation. The entries are returned ordered by `seq`
+/// descending. Use `before_seq` to fetch older entries.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/audit_log",
+    tag = "events",
+    params(AuditLogParams),
+    responses(
+        (status = 200, description = "Ok", body = [AuditLogEntryResponse]),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/audit_log")]
+pub async fn get_audit_log(
+    principal: ReqPrincipal,
+    params: actix_web_validator::Query<AuditLogParams>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Events, AccessRights::Read)?;
+
+    let params = params.into_inner();
+    let entries = AuditLog::find_latest(params.limit.unwrap_or(100) as i64, params.before_seq)
+        .await?
+        .into_iter()
+        .map(AuditLogEntryResponse::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+/// Verify the integrity of the audit log
+///
+/// Walks the whole hash chain and validates each entry. The returned `head_hash` can be stored
+/// externally to detect a truncation of the log later on.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/audit_log/verify",
+    tag = "events",
+    responses(
+        (status = 200, description = "Ok", body = AuditLogVerifyResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/audit_log/verify")]
+pub async fn get_audit_log_verify(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Events, AccessRights::Read)?;
+
+    AuditLog::verify()
+        .await
+        .map(|res| HttpResponse::Ok().json(res))
+}
@@ -10,6 +10,7 @@ use rauthy_api_types::auth_providers::{ProviderLookupResponse, ProviderResponse}
 use rauthy_common::constants::{HEADER_HTML, HEADER_JSON};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
+use rauthy_models::entity::audit_log::{AuditAction, AuditLog};
 use rauthy_models::entity::auth_providers::{
     AuthProvider, AuthProviderCallback, AuthProviderLinkCookie, AuthProviderTemplate,
 };
@@ -63,6 +64,7 @@ pub async fn post_providers(principal: ReqPrincipal) -> Result<HttpResponse, Err
 )]
 #[post("/providers/create")]
 pub async fn post_provider(
+    req: HttpRequest,
     payload: Json<ProviderRequest>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
@@ -76,6 +78,9 @@ pub async fn post_provider(
     }
 
     let provider = AuthProvider::create(payload.into_inner()).await?;
+    let after = audit_value(provider.clone())?;
+    AuditLog::created(&principal, &req, "auth_provider", &provider.id, &after).await;
+
     Ok(HttpResponse::Ok().json(ProviderResponse::try_from(provider)?))
 }
 
@@ -211,6 +216,7 @@ pub async fn post_provider_callback(
 )]
 #[delete("/providers/link")]
 pub async fn delete_provider_link(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
     principal.validate_session_auth()?;
 
     let user_id = principal.user_id()?.to_string();
@@ -255,6 +261,7 @@ pub async fn get_providers_minimal() -> Result<HttpResponse, ErrorResponse> {
 )]
 #[put("/providers/{id}")]
 pub async fn put_provider(
+    req: HttpRequest,
     id: web::Path<String>,
     payload: Json<ProviderRequest>,
     principal: ReqPrincipal,
@@ -276,7 +283,12 @@ pub async fn put_provider(
         ));
     }
 
-    AuthProvider::update(id.into_inner(), payload.into_inner()).await?;
+    let id = id.into_inner();
+    let before = audit_value(AuthProvider::find(&id).await?)?;
+    AuthProvider::update(id.clone(), payload.into_inner()).await?;
+    let after = audit_value(AuthProvider::find(&id).await?)?;
+    AuditLog::updated(&principal, &req, "auth_provider", &id, &before, &after).await;
+
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -294,12 +306,17 @@ pub async fn put_provider(
 )]
 #[delete("/providers/{id}")]
 pub async fn delete_provider(
+    req: HttpRequest,
     id: web::Path<String>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_admin_session()?;
 
-    AuthProvider::delete(&id.into_inner()).await?;
+    let id = id.into_inner();
+    let before = audit_value(AuthProvider::find(&id).await?)?;
+    AuthProvider::delete(&id).await?;
+    AuditLog::deleted(&principal, &req, "auth_provider", &id, &before).await;
+
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -372,6 +389,7 @@ pub async fn get_provider_img(id: web::Path<String>) -> Result<HttpResponse, Err
 )]
 #[put("/providers/{id}/img")]
 pub async fn put_provider_img(
+    req: HttpRequest,
     id: web::Path<String>,
     principal: ReqPrincipal,
     mut payload: actix_multipart::Multipart,
@@ -405,12 +423,20 @@ pub async fn put_provider_img(
 
     // content_type unwrap cannot panic -> checked above
     Logo::upsert(
-        id.into_inner(),
+        id.to_string(),
         buf,
         content_type.unwrap(),
         LogoType::AuthProvider,
     )
     .await?;
+    AuditLog::action(
+        &principal,
+        &req,
+        AuditAction::Update,
+        "auth_provider_img",
+        &id,
+    )
+    .await;
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -436,6 +462,7 @@ pub async fn post_provider_link(
     principal: ReqPrincipal,
     payload: Json<ProviderLoginRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
     principal.validate_session_auth()?;
 
     let user_id = principal.user_id()?.to_string();
@@ -466,3 +493,12 @@ pub async fn post_provider_link(
         .cookie(link_cookie.build_cookie()?)
         .body(xsrf_token))
 }
+
+/// The client secret must never end up in the audit log.
+fn audit_value(provider: AuthProvider) -> Result<ProviderResponse, ErrorResponse> {
+    let mut resp = ProviderResponse::try_from(provider)?;
+    if resp.client_secret.is_some() {
+        resp.client_secret = Some("***".to_string());
+    }
+    Ok(resp)
+}
@@ -1,11 +1,12 @@
 use crate::ReqPrincipal;
-use actix_web::{delete, get, post, web, HttpResponse};
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
 use actix_web_validator::Json;
 use chrono::DateTime;
 use rauthy_api_types::blacklist::{BlacklistResponse, BlacklistedIp, IpBlacklistRequest};
 use rauthy_error::ErrorResponse;
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::audit_log::{AuditAction, AuditLog};
 use rauthy_models::events::event::Event;
 use rauthy_models::events::ip_blacklist_handler::IpBlacklistReq;
 use tokio::sync::oneshot;
@@ -69,17 +70,20 @@ pub async fn get_blacklist(
 pub async fn post_blacklist(
     data: web::Data<AppState>,
     principal: ReqPrincipal,
+    req: HttpRequest,
     payload: Json<IpBlacklistRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Blacklist, AccessRights::Create)?;
 
+    let ip = payload.ip.to_string();
     data.tx_events
         .send_async(Event::ip_blacklisted(
             DateTime::from_timestamp(payload.exp, 0).unwrap_or_default(),
-            payload.ip.to_string(),
+            ip.clone(),
         ))
         .await
         .unwrap();
+    AuditLog::created(&principal, &req, "ip_blacklist", &ip, &*payload).await;
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -102,14 +106,16 @@ pub async fn post_blacklist(
 pub async fn delete_blacklist(
     data: web::Data<AppState>,
     principal: ReqPrincipal,
+    req: HttpRequest,
     ip: web::Path<String>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Blacklist, AccessRights::Delete)?;
 
     data.tx_events
-        .send_async(Event::ip_blacklist_removed(ip.into_inner()))
+        .send_async(Event::ip_blacklist_removed(ip.to_string()))
         .await
         .unwrap();
+    AuditLog::action(&principal, &req, AuditAction::Delete, "ip_blacklist", &ip).await;
 
     Ok(HttpResponse::Ok().finish())
 }
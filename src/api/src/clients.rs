@@ -5,18 +5,22 @@ use actix_web::http::header::{
 use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 use actix_web_lab::__reexports::futures_util::StreamExt;
 use rauthy_api_types::clients::{
-    ClientResponse, ClientSecretResponse, ColorsRequest, DynamicClientRequest,
-    DynamicClientResponse, NewClientRequest, UpdateClientRequest,
+    ClientAccessPolicyRequest, ClientAccessPolicyResponse, ClientResponse, ClientRevokeAllResponse,
+    ClientSecretResponse, ColorsRequest, DynamicClientRequest, DynamicClientResponse,
+    NewClientRequest, UpdateClientRequest,
 };
 use rauthy_common::constants::{DYN_CLIENT_REG_TOKEN, ENABLE_DYN_CLIENT_REG};
 use rauthy_common::utils::real_ip_from_req;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::audit_log::{AuditAction, AuditLog};
 use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::clients_access::ClientAccessPolicy;
 use rauthy_models::entity::clients_dyn::ClientDyn;
 use rauthy_models::entity::colors::{ColorEntity, Colors};
 use rauthy_models::entity::logos::{Logo, LogoType};
+use rauthy_models::events::event::Event;
 use rauthy_service::client;
 use rauthy_service::oidc::helpers;
 use tracing::debug;
@@ -130,13 +134,21 @@ pub async fn get_client_secret(
 )]
 #[post("/clients")]
 pub async fn post_clients(
+    data: web::Data<AppState>,
     client: actix_web_validator::Json<NewClientRequest>,
     principal: ReqPrincipal,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Create)?;
 
-    let client = Client::create(client.into_inner()).await?;
-    Ok(HttpResponse::Ok().json(ClientResponse::from(client)))
+    let client = ClientResponse::from(Client::create(client.into_inner()).await?);
+    AuditLog::created(&principal, &req, "client", &client.id, &client).await;
+
+    Event::client_created(client.id.clone(), Some(real_ip_from_req(&req)?.to_string()))
+        .send(&data.tx_events)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(client))
 }
 
 /// OIDC Dynamic Client Registration (if enabled)
@@ -178,14 +190,19 @@ pub async fn post_clients_dyn(
         ClientDyn::rate_limit_ip(ip).await?;
     }
 
-    Client::create_dynamic(&data, payload.into_inner())
-        .await
-        .map(|resp| {
-            HttpResponse::Created()
-                // The registration should be possible from another Web UI by RFC
-                .insert_header((ACCESS_CONTROL_ALLOW_ORIGIN, "*"))
-                .json(resp)
-        })
+    let resp = Client::create_dynamic(&data, payload.into_inner()).await?;
+
+    Event::client_created(
+        resp.client_id.clone(),
+        Some(real_ip_from_req(&req)?.to_string()),
+    )
+    .send(&data.tx_events)
+    .await?;
+
+    Ok(HttpResponse::Created()
+        // The registration should be possible from another Web UI by RFC
+        .insert_header((ACCESS_CONTROL_ALLOW_ORIGIN, "*"))
+        .json(resp))
 }
 
 /// GET a dynamic OIDC client
@@ -275,12 +292,99 @@ pub async fn put_clients(
     client: actix_web_validator::Json<UpdateClientRequest>,
     path: web::Path<String>,
     principal: ReqPrincipal,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Update)?;
 
-    client::update_client(path.into_inner(), client.into_inner())
-        .await
-        .map(|r| HttpResponse::Ok().json(ClientResponse::from(r)))
+    let before = ClientResponse::from(Client::find(path.to_string()).await?);
+    let client =
+        ClientResponse::from(client::update_client(path.into_inner(), client.into_inner()).await?);
+    AuditLog::updated(&principal, &req, "client", &client.id, &before, &client).await;
+
+    Ok(HttpResponse::Ok().json(client))
+}
+
+/// Returns the access policy for this client
+///
+/// Empty `users` and `groups` mean, that every active user may log in to this client.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/clients/{id}/access_policy",
+    tag = "clients",
+    responses(
+        (status = 200, description = "Ok", body = ClientAccessPolicyResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/clients/{id}/access_policy")]
+pub async fn get_client_access_policy(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Read)?;
+
+    let policy = ClientAccessPolicy::find(id.as_str())
+        .await?
+        .map(ClientAccessPolicyResponse::from)
+        .unwrap_or_default();
+    Ok(HttpResponse::Ok().json(policy))
+}
+
+/// Restricts which users may log in to this client
+///
+/// A user is allowed, if it is listed in `users`, or if it is an effective member of any of the
+/// `groups`, including inherited groups. Empty lists remove the restriction. The `rauthy` client
+/// cannot be restricted.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/clients/{id}/access_policy",
+    tag = "clients",
+    request_body = ClientAccessPolicyRequest,
+    responses(
+        (status = 200, description = "Ok", body = ClientAccessPolicyResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/clients/{id}/access_policy")]
+pub async fn put_client_access_policy(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    payload: actix_web_validator::Json<ClientAccessPolicyRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Update)?;
+
+    // make sure the client exists
+    let client = Client::find(id.into_inner()).await?;
+    let before = ClientAccessPolicy::find(&client.id)
+        .await?
+        .map(ClientAccessPolicyResponse::from)
+        .unwrap_or_default();
+    let after = ClientAccessPolicy::upsert(client.id.clone(), payload.into_inner())
+        .await?
+        .map(ClientAccessPolicyResponse::from)
+        .unwrap_or_default();
+    AuditLog::updated(
+        &principal,
+        &req,
+        "client_access_policy",
+        &client.id,
+        &before,
+        &after,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(after))
 }
 
 /// Returns the color scheme for the login page for this client
@@ -329,13 +433,17 @@ pub async fn get_client_colors(
 pub async fn put_client_colors(
     id: web::Path<String>,
     principal: ReqPrincipal,
+    req: HttpRequest,
     req_data: actix_web_validator::Json<ColorsRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Update)?;
 
     let colors = req_data.into_inner();
     colors.validate_css()?;
+    let before = ColorEntity::find(id.as_str()).await?;
     ColorEntity::update(id.as_str(), colors).await?;
+    let after = ColorEntity::find(id.as_str()).await?;
+    AuditLog::updated(&principal, &req, "client_colors", &id, &before, &after).await;
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -358,10 +466,13 @@ pub async fn put_client_colors(
 pub async fn delete_client_colors(
     id: web::Path<String>,
     principal: ReqPrincipal,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Delete)?;
 
+    let before = ColorEntity::find(id.as_str()).await?;
     ColorEntity::delete(id.as_str()).await?;
+    AuditLog::deleted(&principal, &req, "client_colors", &id, &before).await;
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -420,6 +531,7 @@ pub async fn get_client_logo(id: web::Path<String>) -> Result<HttpResponse, Erro
 pub async fn put_client_logo(
     id: web::Path<String>,
     principal: ReqPrincipal,
+    req: HttpRequest,
     mut payload: actix_multipart::Multipart,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Update)?;
@@ -450,13 +562,9 @@ pub async fn put_client_logo(
     }
 
     // content_type unwrap cannot panic -> checked above
-    Logo::upsert(
-        id.into_inner(),
-        buf,
-        content_type.unwrap(),
-        LogoType::Client,
-    )
-    .await?;
+    Logo::upsert(id.to_string(), buf, content_type.unwrap(), LogoType::Client).await?;
+    AuditLog::action(&principal, &req, AuditAction::Update, "client_logo", &id).await;
+
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -478,6 +586,7 @@ delete,
 pub async fn delete_client_logo(
     id: web::Path<String>,
     principal: ReqPrincipal,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Delete)?;
 
@@ -486,6 +595,7 @@ pub async fn delete_client_logo(
     } else {
         Logo::delete(id.as_str(), &LogoType::Client).await?;
     }
+    AuditLog::action(&principal, &req, AuditAction::Delete, "client_logo", &id).await;
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -511,14 +621,71 @@ pub async fn delete_client_logo(
 )]
 #[put("/clients/{id}/secret")]
 pub async fn put_generate_client_secret(
+    data: web::Data<AppState>,
     id: web::Path<String>,
     principal: ReqPrincipal,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Update)?;
 
-    client::generate_new_secret(id.into_inner())
-        .await
-        .map(|r| HttpResponse::Ok().json(r))
+    let resp = client::generate_new_secret(id.into_inner()).await?;
+    AuditLog::action(
+        &principal,
+        &req,
+        AuditAction::Update,
+        "client_secret",
+        &resp.id,
+    )
+    .await;
+
+    Event::client_secret_rotated(resp.id.clone(), Some(real_ip_from_req(&req)?.to_string()))
+        .send(&data.tx_events)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Revokes all tokens and sessions for a client
+///
+/// Invalidates all refresh tokens, which have been issued for this client, including the ones
+/// for devices, and all consents given to it. Every user with an active refresh token or a session
+/// with this client will have all sessions invalidated too, and all clients inside these sessions
+/// receive a back-channel logout. This is meant to be used after a client-side breach. Already
+/// issued JWT access tokens stay valid until they expire.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/clients/{id}/revoke_all",
+    tag = "clients",
+    responses(
+        (status = 200, description = "Ok", body = ClientRevokeAllResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/clients/{id}/revoke_all")]
+pub async fn post_client_revoke_all(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Sessions, AccessRights::Delete)?;
+
+    let resp = client::revoke_all(id.into_inner(), data.issuer.clone()).await?;
+    AuditLog::action(
+        &principal,
+        &req,
+        AuditAction::Delete,
+        "client_tokens",
+        &resp.id,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(resp))
 }
 
 /// Deletes an OIDC client
@@ -539,8 +706,10 @@ pub async fn put_generate_client_secret(
 )]
 #[delete("/clients/{id}")]
 pub async fn delete_client(
+    data: web::Data<AppState>,
     id: web::Path<String>,
     principal: ReqPrincipal,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Clients, AccessRights::Delete)?;
 
@@ -555,5 +724,12 @@ pub async fn delete_client(
 
     let client = Client::find(id).await?;
     client.delete().await?;
+    let before = ClientResponse::from(client.clone());
+    AuditLog::deleted(&principal, &req, "client", &client.id, &before).await;
+
+    Event::client_deleted(client.id, Some(real_ip_from_req(&req)?.to_string()))
+        .send(&data.tx_events)
+        .await?;
+
     Ok(HttpResponse::Ok().finish())
 }
@@ -2,13 +2,15 @@ use crate::ReqPrincipal;
 use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
 use actix_web_lab::sse;
 use actix_web_validator::Json;
-use chrono::Utc;
 use rauthy_api_types::events::{EventsListenParams, EventsRequest};
-use rauthy_common::constants::SSE_KEEP_ALIVE;
+use rauthy_common::clock;
+use rauthy_common::constants::{ADMIN_FORCE_MFA, SSE_KEEP_ALIVE};
 use rauthy_common::utils::real_ip_from_req;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::continuation_token::ContinuationToken;
+use rauthy_models::entity::principal::Principal;
 use rauthy_models::events::event::Event;
 use rauthy_models::events::listener::EventRouterMsg;
 use std::time::Duration;
@@ -16,12 +18,23 @@ use tokio::sync::mpsc;
 use validator::Validate;
 
 /// Get events
+///
+/// If either `limit` or `continuation_token` is given, the result will be paginated with a
+/// stable ascending `(timestamp, id)` ordering. As long as there are more events available, the
+/// backend returns an HTTP 206 and the `x-continuation-token` header will contain the token for
+/// the next page. The last page is returned with an HTTP 200 without a token. This makes it
+/// possible to incrementally pull events.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - delegated admins with `EVENTS_GROUP_ADMIN_ROLE_PREFIX` roles for the given `group`
 #[utoipa::path(
     post,
     path = "/events",
     tag = "events",
     responses(
         (status = 200, description = "Ok"),
+        (status = 206, description = "PartialContent"),
         (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
@@ -32,22 +45,87 @@ pub async fn post_events(
     principal: ReqPrincipal,
     payload: Json<EventsRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    principal.validate_api_key_or_admin_session(AccessGroup::Events, AccessRights::Read)?;
-
     payload.validate()?;
     let payload = payload.into_inner();
+    let group = validate_events_access(&principal, payload.group.as_deref())?;
+
+    if payload.limit.is_some() || payload.continuation_token.is_some() {
+        let limit = payload.limit.unwrap_or(100) as i64;
+        let continuation_token = if let Some(token) = &payload.continuation_token {
+            Some(ContinuationToken::try_from_millis(token)?)
+        } else {
+            None
+        };
+
+        let (events, continuation_token) = Event::find_paginated(
+            payload.from,
+            payload.until.unwrap_or_else(|| clock::now().timestamp()),
+            payload.level.into(),
+            payload.typ.map(|t| t.into()),
+            group,
+            continuation_token,
+            limit,
+        )
+        .await?;
+
+        return if let Some(token) = continuation_token {
+            Ok(HttpResponse::PartialContent()
+                .insert_header(token.into_header_pair())
+                .json(events))
+        } else {
+            Ok(HttpResponse::Ok().json(events))
+        };
+    }
 
     let events = Event::find_all(
         payload.from,
-        payload.until.unwrap_or_else(|| Utc::now().timestamp()),
+        payload.until.unwrap_or_else(|| clock::now().timestamp()),
         payload.level.into(),
         payload.typ.map(|t| t.into()),
+        group,
     )
     .await?;
 
     Ok(HttpResponse::Ok().json(events))
 }
 
+/// Admins and API Keys may query all events. Delegated admins may only query events about users
+/// inside one of the groups they manage, which makes the `group` mandatory for them.
+fn validate_events_access<'a>(
+    principal: &Principal,
+    group: Option<&'a str>,
+) -> Result<Option<&'a str>, ErrorResponse> {
+    let err = match principal
+        .validate_api_key_or_admin_session(AccessGroup::Events, AccessRights::Read)
+    {
+        Ok(()) => return Ok(group),
+        Err(err) => err,
+    };
+    if principal.api_key.is_some() {
+        return Err(err);
+    }
+
+    principal.validate_session_auth()?;
+    let managed = principal.event_admin_groups();
+    if managed.is_empty() {
+        return Err(err);
+    }
+    if *ADMIN_FORCE_MFA && !principal.has_mfa_active() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::MfaRequired,
+            "Delegated admin access only allowed with MFA active",
+        ));
+    }
+
+    match group {
+        Some(group) if managed.contains(&group) => Ok(Some(group)),
+        _ => Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "Delegated admins must provide one of the groups they manage",
+        )),
+    }
+}
+
 /// Listen to the Events SSE stream
 #[utoipa::path(
     get,
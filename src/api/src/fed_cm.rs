@@ -1,9 +1,9 @@
 use actix_web::http::header;
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{get, post, web, HttpRequest, HttpResponse};
-use chrono::Utc;
 use rauthy_api_types::clients::EphemeralClientRequest;
 use rauthy_api_types::fed_cm::{FedCMAssertionRequest, FedCMClientMetadataRequest};
+use rauthy_common::clock;
 use rauthy_common::constants::{
     COOKIE_SESSION_FED_CM, EXPERIMENTAL_FED_CM_ENABLE, HEADER_ALLOW_ALL_ORIGINS, HEADER_JSON,
     PUB_URL_WITH_SCHEME, RAUTHY_ADMIN_EMAIL, SESSION_TIMEOUT_FED_CM,
@@ -13,11 +13,13 @@ use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::api_cookie::ApiCookie;
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::clients_access::ClientAccessPolicy;
 use rauthy_models::entity::fed_cm::{
     FedCMAccount, FedCMAccounts, FedCMClientMetadata, FedCMIdPConfig, FedCMLoginStatus,
     FedCMTokenResponse, WebIdentity,
 };
 use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::user_pictures::UserPicture;
 use rauthy_models::entity::users::User;
 use rauthy_models::ListenScheme;
 use rauthy_service::token_set::{AuthCodeFlow, AuthTime, DeviceCodeFlow, TokenNonce, TokenSet};
@@ -39,7 +41,10 @@ const HEADER_ALLOW_CREDENTIALS: (&str, &str) = ("access-control-allow-credential
 )]
 #[get("/fed_cm/accounts")]
 #[tracing::instrument(level = "debug", skip_all)]
-pub async fn get_fed_cm_accounts(req: HttpRequest) -> Result<HttpResponse, ErrorResponse> {
+pub async fn get_fed_cm_accounts(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
     is_fed_cm_enabled()?;
     is_web_identity_fetch(&req)?;
 
@@ -53,7 +58,10 @@ pub async fn get_fed_cm_accounts(req: HttpRequest) -> Result<HttpResponse, Error
     }
 
     let user = User::find_for_fed_cm_validated(user_id).await?;
-    let account = FedCMAccount::build(user);
+    let picture = UserPicture::find_updated(&user.id)
+        .await?
+        .map(|updated| UserPicture::claim_url(&data.issuer, &user.id, updated));
+    let account = FedCMAccount::build(user, picture);
     let accounts = FedCMAccounts {
         accounts: vec![account],
     };
@@ -286,19 +294,25 @@ pub async fn post_fed_cm_token(
             "The `account_id` does not match the `user_id` from the active session".to_string(),
         ));
     }
+    ClientAccessPolicy::validate_user(&client.id, &user).await?;
 
     // We are good - issue a TokenSet
     let ts = TokenSet::from_user(
         &user,
         &data,
         &client,
-        AuthTime::given(user.last_login.unwrap_or_else(|| Utc::now().timestamp())),
+        AuthTime::given(user.last_login.unwrap_or_else(|| clock::now().timestamp())),
+        None,
         None,
         payload.nonce.map(TokenNonce),
         // TODO add something like `fedcm` to the scopes? Maybe depending on new allowed flow?
         None,
         AuthCodeFlow::No,
         DeviceCodeFlow::No,
+        None,
+        None,
+        None,
+        None,
     )
     .await?;
 
@@ -2,13 +2,13 @@ use crate::{Assets, ReqPrincipal};
 use actix_web::http::header::{HeaderValue, CONTENT_TYPE};
 use actix_web::http::{header, StatusCode};
 use actix_web::{get, post, put, web, HttpRequest, HttpResponse, Responder};
-use chrono::Utc;
 use cryptr::EncKeys;
 use rauthy_api_types::generic::{
     AppVersionResponse, Argon2ParamsResponse, EncKeyMigrateRequest, EncKeysResponse,
     HealthResponse, I18nContent, I18nRequest, LoginTimeResponse, PasswordHashTimesRequest,
     PasswordPolicyRequest, PasswordPolicyResponse, SearchParams, SearchParamsType,
 };
+use rauthy_common::clock;
 use rauthy_common::constants::{
     APPLICATION_JSON, APP_START, HEADER_ALLOW_ALL_ORIGINS, HEADER_HTML, HEALTH_CHECK_DELAY_SECS,
     IDX_LOGIN_TIME, RAUTHY_VERSION, SUSPICIOUS_REQUESTS_BLACKLIST, SUSPICIOUS_REQUESTS_LOG,
@@ -19,6 +19,7 @@ use rauthy_models::app_state::AppState;
 use rauthy_models::database::{Cache, DB};
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
 use rauthy_models::entity::app_version::LatestAppVersion;
+use rauthy_models::entity::audit_log::{AuditAction, AuditLog};
 use rauthy_models::entity::auth_providers::AuthProviderTemplate;
 use rauthy_models::entity::colors::ColorEntity;
 use rauthy_models::entity::is_db_alive;
@@ -39,6 +40,7 @@ use rauthy_models::i18n::password_reset::I18nPasswordReset;
 use rauthy_models::i18n::register::I18nRegister;
 use rauthy_models::i18n::SsrJson;
 use rauthy_models::language::Language;
+use rauthy_models::static_assets::StaticAssetsOverride;
 use rauthy_models::templates::{
     AccountHtml, AdminApiKeysHtml, AdminAttributesHtml, AdminBlacklistHtml, AdminClientsHtml,
     AdminConfigHtml, AdminDocsHtml, AdminGroupsHtml, AdminHtml, AdminRolesHtml, AdminScopesHtml,
@@ -62,6 +64,7 @@ pub async fn get_index(req: HttpRequest) -> Result<HttpResponse, ErrorResponse>
 
 #[get("/{_:.*}")]
 pub async fn get_static_assets(
+    req: HttpRequest,
     path: web::Path<String>,
     accept_encoding: web::Header<header::AcceptEncoding>,
 ) -> HttpResponse {
@@ -74,9 +77,39 @@ pub async fn get_static_assets(
     } else if accept_encoding.contains(&"gzip".parse().unwrap()) {
         (Cow::from(format!("{}.gz", path)), "gzip")
     } else {
-        (Cow::from(path), "none")
+        (Cow::from(path.as_str()), "none")
     };
 
+    if let Some(assets) = StaticAssetsOverride::get() {
+        // Overrides may not come pre-compressed -> fall back to the plain file in that case.
+        let found = match assets.find(p.as_ref()).await {
+            Some(asset) => Some((asset, encoding)),
+            None if encoding != "none" => assets.find(&path).await.map(|a| (a, "none")),
+            None => None,
+        };
+
+        if let Some((asset, encoding)) = found {
+            let if_none_match = req
+                .headers()
+                .get(header::IF_NONE_MATCH)
+                .and_then(|v| v.to_str().ok());
+            if if_none_match == Some(asset.etag.as_str()) {
+                return HttpResponse::NotModified()
+                    .insert_header((header::ETAG, asset.etag.as_str()))
+                    .finish();
+            }
+
+            // Overrides usually keep their names when they are updated -> always revalidate
+            // via the `ETag` instead of caching them for a long time.
+            return HttpResponse::Ok()
+                .insert_header(("cache-control", "no-cache"))
+                .insert_header((header::ETAG, asset.etag.as_str()))
+                .insert_header(("content-encoding", encoding))
+                .content_type(mime.first_or_octet_stream().as_ref())
+                .body(asset.data.clone());
+        }
+    }
+
     match Assets::get(p.as_ref()) {
         Some(content) => HttpResponse::Ok()
             .insert_header(("cache-control", "max-age=2592000"))
@@ -347,6 +380,14 @@ pub async fn post_migrate_enc_key(
     let ip = real_ip_from_req(&req)?;
 
     encryption::migrate_encryption_alg(&data, &req_data.key_id).await?;
+    AuditLog::action(
+        &principal,
+        &req,
+        AuditAction::Update,
+        "encryption_key",
+        &req_data.key_id,
+    )
+    .await;
 
     data.tx_events
         .send_async(Event::secrets_migrated(ip))
@@ -466,13 +507,25 @@ pub async fn get_password_policy(principal: ReqPrincipal) -> Result<HttpResponse
 #[put("/password_policy")]
 pub async fn put_password_policy(
     principal: ReqPrincipal,
+    req: HttpRequest,
     req_data: actix_web_validator::Json<PasswordPolicyRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Update)?;
 
     let mut rules = PasswordPolicy::find().await?;
+    let before = rules.clone();
     rules.apply_req(req_data.into_inner());
     rules.save().await?;
+    AuditLog::updated(
+        &principal,
+        &req,
+        "password_policy",
+        "global",
+        &before,
+        &rules,
+    )
+    .await;
+
     Ok(HttpResponse::Ok().json(PasswordPolicyResponse::from(rules)))
 }
 
@@ -553,6 +606,7 @@ pub async fn post_update_language(
     principal: ReqPrincipal,
     req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
     principal.validate_session_auth()?;
 
     let user_id = principal.user_id()?;
@@ -577,7 +631,7 @@ pub async fn post_update_language(
 )]
 #[get("/health")]
 pub async fn get_health() -> impl Responder {
-    if Utc::now().sub(*APP_START).num_seconds() < *HEALTH_CHECK_DELAY_SECS as i64 {
+    if clock::now().sub(*APP_START).num_seconds() < *HEALTH_CHECK_DELAY_SECS as i64 {
         info!("Early health check within the HEALTH_CHECK_DELAY_SECS timeframe - returning true");
         HttpResponse::Ok().json(HealthResponse {
             db_healthy: true,
@@ -638,7 +692,7 @@ pub async fn catch_all(
             "Blacklisting suspicious target path request '{}' from {}",
             path, ip,
         );
-        let exp = Utc::now().add(chrono::Duration::minutes(
+        let exp = clock::now().add(chrono::Duration::minutes(
             *SUSPICIOUS_REQUESTS_BLACKLIST as i64,
         ));
         if let Err(err) = data
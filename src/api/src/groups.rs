@@ -1,9 +1,14 @@
 use crate::ReqPrincipal;
-use actix_web::{delete, get, post, put, web, HttpResponse};
-use rauthy_api_types::groups::NewGroupRequest;
-use rauthy_error::ErrorResponse;
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use rauthy_api_types::generic::{PasswordPolicyRequest, PasswordPolicyResponse};
+use rauthy_api_types::groups::{
+    GroupInheritanceRequest, GroupInheritanceResponse, NewGroupRequest,
+};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
-use rauthy_models::entity::groups::Group;
+use rauthy_models::entity::audit_log::AuditLog;
+use rauthy_models::entity::groups::{Group, GroupInheritance};
+use rauthy_models::entity::password::{PasswordPolicy, PasswordPolicyGroup};
 
 /// Returns all existing *groups*
 ///
@@ -45,14 +50,16 @@ pub async fn get_groups(principal: ReqPrincipal) -> Result<HttpResponse, ErrorRe
 )]
 #[post("/groups")]
 pub async fn post_group(
+    req: HttpRequest,
     group_req: actix_web_validator::Json<NewGroupRequest>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Create)?;
 
-    Group::create(group_req.into_inner())
-        .await
-        .map(|r| HttpResponse::Ok().json(r))
+    let group = Group::create(group_req.into_inner()).await?;
+    AuditLog::created(&principal, &req, "group", &group.id, &group).await;
+
+    Ok(HttpResponse::Ok().json(group))
 }
 
 /// Modifies a groups name
@@ -72,15 +79,19 @@ pub async fn post_group(
 )]
 #[put("/groups/{id}")]
 pub async fn put_group(
+    req: HttpRequest,
     id: web::Path<String>,
     group_req: actix_web_validator::Json<NewGroupRequest>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Update)?;
 
-    Group::update(id.into_inner(), group_req.group.to_owned())
-        .await
-        .map(|g| HttpResponse::Ok().json(g))
+    let before = Group::find(id.to_string()).await?;
+    let group_req = group_req.into_inner();
+    let group = Group::update(id.into_inner(), group_req.group, group_req.force_mfa).await?;
+    AuditLog::updated(&principal, &req, "group", &group.id, &before, &group).await;
+
+    Ok(HttpResponse::Ok().json(group))
 }
 
 /// Deletes a group
@@ -101,12 +112,221 @@ pub async fn put_group(
 )]
 #[delete("/groups/{id}")]
 pub async fn delete_group(
+    req: HttpRequest,
     id: web::Path<String>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Delete)?;
 
-    Group::delete(id.into_inner())
-        .await
-        .map(|_| HttpResponse::Ok().finish())
+    let before = Group::find(id.to_string()).await?;
+    Group::delete(id.into_inner()).await?;
+    AuditLog::deleted(&principal, &req, "group", &before.id, &before).await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns the password policy bound to a group
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/password_policy",
+    tag = "groups",
+    responses(
+        (status = 200, description = "Ok", body = PasswordPolicyResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/groups/{id}/password_policy")]
+pub async fn get_group_password_policy(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Read)?;
+
+    let policy = PasswordPolicyGroup::find(&id).await?.ok_or_else(|| {
+        ErrorResponse::new(
+            ErrorResponseType::NotFound,
+            "No password policy for this group",
+        )
+    })?;
+
+    Ok(HttpResponse::Ok().json(PasswordPolicyResponse::from(PasswordPolicy::from(policy))))
+}
+
+/// Creates or updates the password policy bound to a group
+///
+/// It overrides the global password policy for all members of this group. If a user is a member
+/// of multiple groups with a policy, the strictest combination of all of them is used.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/groups/{id}/password_policy",
+    tag = "groups",
+    request_body = PasswordPolicyRequest,
+    responses(
+        (status = 200, description = "Ok", body = PasswordPolicyResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/groups/{id}/password_policy")]
+pub async fn put_group_password_policy(
+    req: HttpRequest,
+    id: web::Path<String>,
+    req_data: actix_web_validator::Json<PasswordPolicyRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Update)?;
+
+    let group = Group::find(id.into_inner()).await?;
+    let before = PasswordPolicyGroup::find(&group.id).await?;
+    let policy = PasswordPolicyGroup::upsert(group.id, req_data.into_inner()).await?;
+    match before {
+        Some(before) => {
+            AuditLog::updated(
+                &principal,
+                &req,
+                "group_password_policy",
+                &policy.group_id,
+                &before,
+                &policy,
+            )
+            .await
+        }
+        None => {
+            AuditLog::created(
+                &principal,
+                &req,
+                "group_password_policy",
+                &policy.group_id,
+                &policy,
+            )
+            .await
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(PasswordPolicyResponse::from(PasswordPolicy::from(policy))))
+}
+
+/// Deletes the password policy bound to a group
+///
+/// Its members will fall back to the global password policy afterward.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/groups/{id}/password_policy",
+    tag = "groups",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/groups/{id}/password_policy")]
+pub async fn delete_group_password_policy(
+    req: HttpRequest,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Update)?;
+
+    let before = PasswordPolicyGroup::find(&id).await?.ok_or_else(|| {
+        ErrorResponse::new(
+            ErrorResponseType::NotFound,
+            "No password policy for this group",
+        )
+    })?;
+    PasswordPolicyGroup::delete(&before.group_id).await?;
+    AuditLog::deleted(
+        &principal,
+        &req,
+        "group_password_policy",
+        &before.group_id,
+        &before,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns the child groups and roles of a group
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/groups/{id}/inheritance",
+    tag = "groups",
+    responses(
+        (status = 200, description = "Ok", body = GroupInheritanceResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/groups/{id}/inheritance")]
+pub async fn get_group_inheritance(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Read)?;
+
+    let inheritance = GroupInheritance::find_for_group(&id).await?;
+    Ok(HttpResponse::Ok().json(inheritance))
+}
+
+/// Replaces the child groups and roles of a group
+///
+/// Members of all child groups are effectively members of this group as well, transitively. All
+/// effective members of a group get its roles. Both are resolved at token issuance time.
+/// A nesting, which would create a cycle, will be rejected.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/groups/{id}/inheritance",
+    tag = "groups",
+    request_body = GroupInheritanceRequest,
+    responses(
+        (status = 200, description = "Ok", body = GroupInheritanceResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/groups/{id}/inheritance")]
+pub async fn put_group_inheritance(
+    req: HttpRequest,
+    id: web::Path<String>,
+    req_data: actix_web_validator::Json<GroupInheritanceRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Update)?;
+
+    let id = id.into_inner();
+    let before = GroupInheritance::find_for_group(&id).await?;
+    let inheritance = GroupInheritance::update(id.clone(), req_data.into_inner()).await?;
+    AuditLog::updated(
+        &principal,
+        &req,
+        "group_inheritance",
+        &id,
+        &before,
+        &inheritance,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(inheritance))
 }
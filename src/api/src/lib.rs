@@ -2,8 +2,11 @@
 
 #![forbid(unsafe_code)]
 
+use actix_web::http::StatusCode;
 use actix_web::{web, HttpRequest, HttpResponse};
-use rauthy_api_types::users::WebauthnLoginResponse;
+use rauthy_api_types::users::{
+    ConsentRequiredResponse, ProfileRequiredResponse, TosRequiredResponse, WebauthnLoginResponse,
+};
 use rauthy_common::constants::COOKIE_MFA;
 use rauthy_error::ErrorResponse;
 use rauthy_models::api_cookie::ApiCookie;
@@ -11,12 +14,14 @@ use rauthy_models::entity::api_keys::ApiKey;
 use rauthy_models::entity::fed_cm::FedCMLoginStatus;
 use rauthy_models::entity::principal::Principal;
 use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::trusted_devices::TrustedDevice;
 use rauthy_models::entity::webauthn::WebauthnCookie;
 use rauthy_models::AuthStep;
 use rust_embed::RustEmbed;
 use tracing::error;
 
 pub mod api_keys;
+pub mod audit_log;
 pub mod auth_providers;
 pub mod blacklist;
 pub mod clients;
@@ -24,9 +29,11 @@ pub mod events;
 pub mod fed_cm;
 pub mod generic;
 pub mod groups;
+pub mod login_stats;
 pub mod oidc;
 pub mod openapi;
 pub mod roles;
+pub mod scim;
 pub mod scopes;
 pub mod sessions;
 pub mod users;
@@ -58,6 +65,9 @@ pub async fn map_auth_step(
             if let Some((name, value)) = res.header_origin {
                 resp.headers_mut().insert(name, value);
             }
+            if res.trust_device {
+                add_trusted_device_cookie(&mut resp, res.user_id, req).await;
+            }
             Ok(resp)
         }
 
@@ -66,6 +76,7 @@ pub async fn map_auth_step(
                 code: res.code,
                 user_id: res.user_id,
                 exp: res.exp,
+                has_totp: res.has_totp,
             };
             let mut resp = HttpResponse::Ok()
                 .insert_header(fed_cm_header)
@@ -90,6 +101,46 @@ pub async fn map_auth_step(
             Ok(resp)
         }
 
+        AuthStep::AwaitTos(res) => {
+            let body = TosRequiredResponse {
+                version: res.version,
+                url: res.url,
+            };
+            let mut resp = HttpResponse::build(StatusCode::PRECONDITION_REQUIRED)
+                .insert_header(res.header_csrf)
+                .json(&body);
+            if let Some((name, value)) = res.header_origin {
+                resp.headers_mut().insert(name, value);
+            }
+            Ok(resp)
+        }
+
+        AuthStep::AwaitProfile(res) => {
+            let body = ProfileRequiredResponse { attrs: res.attrs };
+            let mut resp = HttpResponse::build(StatusCode::PRECONDITION_REQUIRED)
+                .insert_header(res.header_csrf)
+                .json(&body);
+            if let Some((name, value)) = res.header_origin {
+                resp.headers_mut().insert(name, value);
+            }
+            Ok(resp)
+        }
+
+        AuthStep::AwaitConsent(res) => {
+            let body = ConsentRequiredResponse {
+                client_id: res.client_id,
+                client_name: res.client_name,
+                scopes: res.scopes,
+            };
+            let mut resp = HttpResponse::build(StatusCode::PRECONDITION_REQUIRED)
+                .insert_header(res.header_csrf)
+                .json(&body);
+            if let Some((name, value)) = res.header_origin {
+                resp.headers_mut().insert(name, value);
+            }
+            Ok(resp)
+        }
+
         AuthStep::ProviderLink => {
             // TODO generate a new event type in this case?
             Ok(HttpResponse::NoContent()
@@ -99,6 +150,27 @@ pub async fn map_auth_step(
     }
 }
 
+/// Re-sets the session cookie, which is necessary after the session lifetime has been extended
+/// for a "remember me" session.
+fn add_session_cookie(resp: &mut HttpResponse, session: &Session) {
+    if let Err(err) = resp.add_cookie(&session.client_cookie()) {
+        error!("Error adding session cookie: {}", err);
+    }
+}
+
+/// Trusts the device of the current request, so the 2nd factor can be skipped for future logins.
+/// A failure must not break the already successful login.
+async fn add_trusted_device_cookie(resp: &mut HttpResponse, user_id: String, req: &HttpRequest) {
+    match TrustedDevice::create(user_id, req).await {
+        Ok(cookie) => {
+            if let Err(err) = resp.add_cookie(&cookie) {
+                error!("Error adding trusted device cookie: {}", err);
+            }
+        }
+        Err(err) => error!("Error creating trusted device: {:?}", err),
+    }
+}
+
 #[inline]
 fn add_req_mfa_cookie(resp: &mut HttpResponse, email: String) -> Result<(), ErrorResponse> {
     let binding = WebauthnCookie::new(email);
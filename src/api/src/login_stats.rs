@@ -0,0 +1,52 @@
+use crate::ReqPrincipal;
+use actix_web::{get, HttpResponse};
+use rauthy_api_types::login_stats::{LoginStatsParams, LoginStatsResponse};
+use rauthy_common::clock;
+use rauthy_error::ErrorResponse;
+use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::login_stats::{LoginStats, LoginStatsKind};
+
+/// Get aggregated failed login and blacklist statistics
+///
+/// Returns the totals per hour together with the most active IP prefixes and the most targeted
+/// users inside the given range. All values are pre-aggregated and will be cleaned up together
+/// with the events after `EVENT_CLEANUP_DAYS`.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/login_stats",
+    tag = "events",
+    params(LoginStatsParams),
+    responses(
+        (status = 200, description = "Ok", body = LoginStatsResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/login_stats")]
+pub async fn get_login_stats(
+    principal: ReqPrincipal,
+    params: actix_web_validator::Query<LoginStatsParams>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Events, AccessRights::Read)?;
+
+    let params = params.into_inner();
+    let until = params.until.unwrap_or_else(|| clock::now().timestamp());
+    let from = params.from.unwrap_or(until - 86400);
+    // make sure to include the bucket `from` falls into
+    let from = from - from % 3600;
+    let limit = params.limit.unwrap_or(10) as i64;
+
+    let hours = LoginStats::find_hourly(from, until).await?;
+    let ip_prefixes = LoginStats::find_top(LoginStatsKind::IpPrefix, from, until, limit).await?;
+    let users = LoginStats::find_top(LoginStatsKind::User, from, until, limit).await?;
+
+    Ok(HttpResponse::Ok().json(LoginStatsResponse {
+        hours: hours.into_iter().map(From::from).collect(),
+        ip_prefixes: ip_prefixes.into_iter().map(From::from).collect(),
+        users: users.into_iter().map(From::from).collect(),
+    }))
+}
@@ -1,49 +1,69 @@
-use crate::{map_auth_step, ReqPrincipal};
+use crate::{add_session_cookie, map_auth_step, ReqPrincipal};
 use actix_web::cookie::time::OffsetDateTime;
 use actix_web::cookie::SameSite;
 use actix_web::http::header::{HeaderValue, CONTENT_TYPE};
 use actix_web::http::{header, StatusCode};
 use actix_web::{get, post, web, HttpRequest, HttpResponse, HttpResponseBuilder, ResponseError};
-use chrono::Utc;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use rauthy_api_types::oidc::{
     AuthRequest, DeviceAcceptedRequest, DeviceCodeResponse, DeviceGrantRequest,
-    DeviceVerifyRequest, DeviceVerifyResponse, JWKSCerts, JWKSPublicKeyCerts, LoginRefreshRequest,
-    LoginRequest, LogoutRequest, OAuth2ErrorResponse, OAuth2ErrorTypeResponse, SessionInfoResponse,
-    TokenInfo, TokenRequest, TokenValidationRequest,
+    DeviceVerifyRequest, DeviceVerifyResponse, JWKSCerts, JWKSPublicKeyCerts, JwkImportRequest,
+    LoginPasskeyRequest, LoginRefreshRequest, LoginRequest, LogoutRequest, LogoutResponse,
+    OAuth2ErrorResponse, OAuth2ErrorTypeResponse, SessionInfoResponse, TokenInfo, TokenRequest,
+    TokenRevocationRequest, TokenValidationRequest,
 };
 use rauthy_api_types::sessions::SessionState;
-use rauthy_api_types::users::{Userinfo, WebauthnLoginResponse};
+use rauthy_api_types::users::{
+    ConsentRequiredResponse, ProfileRequiredResponse, TosRequiredResponse, Userinfo,
+    WebauthnDiscoverableStartResponse, WebauthnLoginResponse,
+};
+use rauthy_common::clock;
 use rauthy_common::constants::{
     APPLICATION_JSON, AUTH_HEADERS_ENABLE, AUTH_HEADER_EMAIL, AUTH_HEADER_EMAIL_VERIFIED,
     AUTH_HEADER_FAMILY_NAME, AUTH_HEADER_GIVEN_NAME, AUTH_HEADER_GROUPS, AUTH_HEADER_MFA,
     AUTH_HEADER_ROLES, AUTH_HEADER_USER, COOKIE_MFA, COOKIE_SESSION, COOKIE_SESSION_FED_CM,
-    DEVICE_GRANT_CODE_LIFETIME, DEVICE_GRANT_POLL_INTERVAL, DEVICE_GRANT_RATE_LIMIT,
-    EXPERIMENTAL_FED_CM_ENABLE, GRANT_TYPE_DEVICE_CODE, HEADER_HTML, HEADER_RETRY_NOT_BEFORE,
-    OPEN_USER_REG, SESSION_LIFETIME,
+    COOKIE_SESSION_STATE, DEVICE_GRANT_CODE_LIFETIME, DEVICE_GRANT_POLL_INTERVAL,
+    DEVICE_GRANT_RATE_LIMIT, EXPERIMENTAL_FED_CM_ENABLE, FEDERATION_ENABLE, GRANT_TYPE_DEVICE_CODE,
+    HEADER_HTML, HEADER_RETRY_NOT_BEFORE, MFA_TRUSTED_DEVICE_DAYS, OPEN_USER_REG, SESSION_LIFETIME,
+    SESSION_REMEMBER_ME_DAYS,
 };
 use rauthy_common::utils::real_ip_from_req;
-use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
 use rauthy_models::api_cookie::ApiCookie;
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::audit_log::{AuditAction, AuditLog};
+use rauthy_models::entity::auth_codes::{AuthCode, AuthResponseMode};
 use rauthy_models::entity::auth_providers::AuthProviderTemplate;
+use rauthy_models::entity::claims_request::ClaimsRequest;
 use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::clients_access::ClientAccessPolicy;
 use rauthy_models::entity::colors::ColorEntity;
 use rauthy_models::entity::devices::DeviceAuthCode;
 use rauthy_models::entity::fed_cm::FedCMLoginStatus;
+use rauthy_models::entity::federation::{EntityStatement, CONTENT_TYPE_ENTITY_STATEMENT};
+use rauthy_models::entity::groups::Group;
 use rauthy_models::entity::ip_rate_limit::DeviceIpRateLimit;
 use rauthy_models::entity::jwk::{JWKSPublicKey, JwkKeyPair, JWKS};
+use rauthy_models::entity::login_history::LoginHistory;
 use rauthy_models::entity::pow::PowEntity;
+use rauthy_models::entity::request_objects::RequestObject;
+use rauthy_models::entity::session_devices::SessionDevice;
 use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::sessions_clients::SessionClient;
+use rauthy_models::entity::sms_otp::UserSmsOtp;
+use rauthy_models::entity::totp::UserTotp;
 use rauthy_models::entity::users::User;
+use rauthy_models::entity::webauthn;
 use rauthy_models::entity::webauthn::WebauthnCookie;
 use rauthy_models::entity::well_known::WellKnown;
+use rauthy_models::entity::yubico_otp::UserYubicoOtp;
 use rauthy_models::language::Language;
 use rauthy_models::templates::{
-    AuthorizeHtml, CallbackHtml, Error1Html, ErrorHtml, FrontendAction,
+    AuthorizeHtml, CallbackHtml, CheckSessionHtml, Error1Html, ErrorHtml, FrontendAction,
 };
-use rauthy_models::JwtCommonClaims;
-use rauthy_service::oidc::{authorize, logout, token_info, userinfo, validation};
+use rauthy_models::{AuthStep, JwtAcrValue, JwtCommonClaims};
+use rauthy_service::oidc::{authorize, logout, revocation, token_info, userinfo, validation};
 use rauthy_service::token_set::TokenSet;
 use rauthy_service::{login_delay, oidc};
 use spow::pow::Pow;
@@ -63,6 +83,7 @@ use tracing::{debug, error};
     params(AuthRequest),
     responses(
         (status = 200, description = "If the params match the allowed settings, returns the pre-rendered HTML",),
+        (status = 302, description = "Redirect to the user registration with `prompt=create`, or back to the client with an OAuth error, if the `redirect_uri` is valid",),
         (status = 400, description = "If any params do not match the backend config", body = ErrorResponse),
     ),
 )]
@@ -78,42 +99,172 @@ pub async fn get_authorize(
         .unwrap_or_default();
     let lang = Language::try_from(&req).unwrap_or_default();
 
-    let (client, origin_header) = match validation::validate_auth_req_param(
-        &data,
-        &req,
-        &req_data.client_id,
-        &req_data.redirect_uri,
-        &req_data.code_challenge,
-        &req_data.code_challenge_method,
-    )
-    .await
-    {
-        Ok(res) => res,
-        Err(err) => {
-            let status = err.status_code();
-            let body = Error1Html::build(&colors, &lang, status, Some(err.message));
-            return Ok(ErrorHtml::response(body, status));
+    // Params passed inside a signed request object (JAR) replace all params of the request.
+    // After a successful validation, they are handled just like a plain request.
+    let mut req_data = req_data.into_inner();
+    let mut jar_query = None;
+    if req_data.request.is_some() || req_data.request_uri.is_some() {
+        let res = match Client::find(req_data.client_id.clone()).await {
+            Ok(client) => RequestObject::resolve(
+                &data.issuer,
+                &client,
+                req_data.request.as_deref(),
+                req_data.request_uri.as_deref(),
+            )
+            .await
+            .and_then(|obj| obj.into_auth_request(&client.id)),
+            Err(err) => Err(err),
+        };
+
+        match res {
+            Ok((req_jar, query)) => {
+                req_data = req_jar;
+                jar_query = Some(query);
+            }
+            Err(err) => {
+                debug!("Invalid request object: {}", err.message);
+
+                // the error can only be sent back to the client, if the `redirect_uri` of the
+                // outer request is valid
+                if let Some(redirect_uri) = req_data.redirect_uri.as_deref() {
+                    if validation::validate_auth_req_client(
+                        &data,
+                        &req,
+                        &req_data.client_id,
+                        redirect_uri,
+                    )
+                    .await
+                    .is_ok()
+                    {
+                        let loc =
+                            AuthCode::error_location(redirect_uri, req_data.state.as_deref(), &err);
+                        return Ok(HttpResponse::Found()
+                            .insert_header((header::LOCATION, loc))
+                            .finish());
+                    }
+                }
+
+                let status = err.status_code();
+                let body = Error1Html::build(&colors, &lang, status, Some(err.message));
+                return Ok(ErrorHtml::response(body, status));
+            }
         }
+    }
+
+    let Some(redirect_uri) = req_data.redirect_uri.as_deref() else {
+        let status = StatusCode::BAD_REQUEST;
+        let body = Error1Html::build(&colors, &lang, status, Some("'redirect_uri' is missing"));
+        return Ok(ErrorHtml::response(body, status));
     };
 
-    // check prompt and max_age to possibly force a new session
-    let mut force_new_session = if req_data
+    let (client, origin_header) =
+        match validation::validate_auth_req_client(&data, &req, &req_data.client_id, redirect_uri)
+            .await
+        {
+            Ok(res) => res,
+            Err(err) => {
+                let status = err.status_code();
+                let body = Error1Html::build(&colors, &lang, status, Some(err.message));
+                return Ok(ErrorHtml::response(body, status));
+            }
+        };
+
+    // the `redirect_uri` is valid at this point -> errors are sent back to the client
+    if let Err(err) = client
+        .validate_request_object(jar_query.is_some())
+        .and_then(|_| client.validate_response_type(req_data.response_type.as_deref()))
+        .and_then(|_| {
+            validation::validate_auth_req_policy(
+                &client,
+                &req_data.code_challenge,
+                &req_data.code_challenge_method,
+            )
+        })
+        .and_then(|_| AuthResponseMode::parse(req_data.response_mode.as_deref(), &client))
+        .and_then(|_| client.validate_resource(req_data.resource.as_deref()))
+        .and_then(|_| match req_data.claims.as_deref() {
+            Some(claims) => ClaimsRequest::parse(claims).map(|_| ()),
+            None => Ok(()),
+        })
+    {
+        debug!("Invalid authorization request: {}", err.message);
+        let loc = AuthCode::error_location(redirect_uri, req_data.state.as_deref(), &err);
+        return Ok(HttpResponse::Found()
+            .insert_header((header::LOCATION, loc))
+            .finish());
+    }
+
+    // `prompt=create` skips the login and starts the user registration right away
+    if req_data.prompt.as_deref() == Some("create") {
+        if !*OPEN_USER_REG {
+            let err = ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "prompt=create is not supported, because the open registration is disabled",
+            );
+            let loc = AuthCode::error_location(redirect_uri, req_data.state.as_deref(), &err);
+            return Ok(HttpResponse::Found()
+                .insert_header((header::LOCATION, loc))
+                .finish());
+        }
+
+        // after a successful registration, the user will be sent back to this exact request
+        // without the `prompt`, which will then start the login
+        let query = jar_query
+            .as_deref()
+            .unwrap_or(req.query_string())
+            .split('&')
+            .filter(|param| !param.starts_with("prompt="))
+            .collect::<Vec<_>>()
+            .join("&");
+        let authorize_uri = format!("{}/oidc/authorize?{}", data.issuer, query);
+        let loc = format!(
+            "{}/users/register?redirect_uri={}",
+            data.issuer,
+            utf8_percent_encode(&authorize_uri, NON_ALPHANUMERIC)
+        );
+        return Ok(HttpResponse::Found()
+            .insert_header((header::LOCATION, loc))
+            .finish());
+    }
+
+    // A client can demand a step-up re-authentication with `prompt=login`, a `max_age` (which
+    // is compared to the last authentication, so `max_age=0` always forces a new login) or
+    // `acr_values` the session does not satisfy. The session will be re-used in that case and
+    // the new tokens contain the updated `auth_time` and `acr`.
+    let prompt_login = req_data
         .prompt
         .as_ref()
         .map(|p| p.as_str() == "login")
-        .unwrap_or(false)
-    {
-        true
-    } else if let Some(max_age) = req_data.max_age {
-        if let Some(session) = &principal.session {
-            let session_created = session.exp - *SESSION_LIFETIME as i64;
-            Utc::now().timestamp() > session_created + max_age
-        } else {
-            true
-        }
-    } else {
-        false
+        .unwrap_or(false);
+    let max_age_exceeded = match (req_data.max_age, &principal.session) {
+        (Some(max_age), Some(session)) => clock::now().timestamp() >= session.last_auth() + max_age,
+        (Some(_), None) => true,
+        (None, _) => false,
     };
+    let acr_unsatisfied = principal
+        .session
+        .as_ref()
+        .map(|session| {
+            JwtAcrValue::validate_session(req_data.acr_values.as_deref(), session).is_err()
+        })
+        .unwrap_or(false);
+    let step_up = prompt_login || max_age_exceeded || acr_unsatisfied;
+    let mut force_new_session = step_up;
+
+    // A client may have a stricter session lifetime or idle timeout. The `last_seen` has not been
+    // updated by the principal middleware for this request, so it can be validated here.
+    let now = clock::now().timestamp();
+    let client_session_expired = principal
+        .session
+        .as_ref()
+        .map(|session| {
+            principal.validate_session_auth().is_ok()
+                && client.validate_session(session, now).is_err()
+        })
+        .unwrap_or(false);
+    if client_session_expired {
+        force_new_session = true;
+    }
 
     // check if the user needs to do the Webauthn login each time
     let mut action = FrontendAction::None;
@@ -126,7 +277,10 @@ pub async fn get_authorize(
 
                 // if the user must do another MFA login anyway, we do never force a new session creation,
                 // because the authentication happens each time anyway
-                force_new_session = false;
+                // -> a step-up demanded by the client must never end in a silent refresh though
+                if !step_up {
+                    force_new_session = false;
+                }
             }
         }
     }
@@ -145,15 +299,23 @@ pub async fn get_authorize(
     }
 
     let auth_providers_json = AuthProviderTemplate::get_all_json_template().await?;
+    // the login UI reads the params from the query string, unless they came from a request object
     let tpl_data = Some(format!(
-        "{}\n{}\n{}",
+        "{}\n{}\n{}\n{}\n{}\n{}",
         client.name.unwrap_or_default(),
         client.client_uri.unwrap_or_default(),
         *OPEN_USER_REG,
+        *SESSION_REMEMBER_ME_DAYS > 0,
+        *MFA_TRUSTED_DEVICE_DAYS > 0,
+        jar_query.unwrap_or_default(),
     ));
 
     // if the user is still authenticated and everything is valid -> immediate refresh
     if !force_new_session && principal.validate_session_auth().is_ok() {
+        let mut session = principal.get_session()?.clone();
+        session.last_seen = now;
+        session.save().await?;
+
         let csrf = principal.get_session_csrf_token()?;
         let body = AuthorizeHtml::build(
             &tpl_data,
@@ -173,10 +335,16 @@ pub async fn get_authorize(
         return Ok(HttpResponse::Ok().append_header(HEADER_HTML).body(body));
     }
     // check if we can re-use a still valid session or need to create a new one
+    // If the session does not satisfy the client, a fresh one is needed. Otherwise, the idle
+    // timeout would be renewed without a new login.
     let session = if let Some(session) = &principal.session {
         match principal.validate_session_auth_or_init() {
-            Ok(_) => session.clone(),
-            Err(_) => Session::new(*SESSION_LIFETIME, Some(real_ip_from_req(&req)?)),
+            Ok(_) if !client_session_expired => {
+                let mut session = session.clone();
+                session.last_seen = now;
+                session
+            }
+            _ => Session::new(*SESSION_LIFETIME, Some(real_ip_from_req(&req)?)),
         }
     } else {
         Session::new(*SESSION_LIFETIME, Some(real_ip_from_req(&req)?))
@@ -198,10 +366,12 @@ pub async fn get_authorize(
     );
 
     let cookie = session.client_cookie();
+    let cookie_state = session.client_cookie_session_state();
     if let Some(o) = origin_header {
         // TODO is 'Access-Control-Allow-Credentials: true' needed as well?
         return Ok(HttpResponse::Ok()
             .cookie(cookie)
+            .cookie(cookie_state)
             .insert_header(o)
             .insert_header(HEADER_HTML)
             .body(body));
@@ -211,11 +381,13 @@ pub async fn get_authorize(
         Ok(HttpResponse::build(StatusCode::OK)
             .cookie(session.client_cookie_fed_cm())
             .cookie(cookie)
+            .cookie(cookie_state)
             .insert_header(HEADER_HTML)
             .body(body))
     } else {
         Ok(HttpResponse::build(StatusCode::OK)
             .cookie(cookie)
+            .cookie(cookie_state)
             .insert_header(HEADER_HTML)
             .body(body))
     }
@@ -242,6 +414,9 @@ pub async fn get_authorize(
         (status = 200, description = "Correct credentials, but needs to continue with Webauthn MFA Login", body = WebauthnLoginResponse),
         (status = 202, description = "Correct credentials and no MFA Login required, adds Location header"),
         (status = 400, description = "Missing / bad input data", body = ErrorResponse),
+        (status = 428, description = "Correct credentials, but the current terms of service must be accepted via `tos_accept`", body = TosRequiredResponse),
+        (status = 428, description = "Correct credentials, but missing custom attributes must be provided via `profile_values`", body = ProfileRequiredResponse),
+        (status = 428, description = "Correct credentials, but the requested scopes must be granted via `consent_accept`", body = ConsentRequiredResponse),
         (status = 401, description = "Bad input or CSRF Token error", body = ErrorResponse),
     ),
 )]
@@ -264,26 +439,69 @@ pub async fn post_authorize(
     let mut has_password_been_hashed = false;
     let mut add_login_delay = true;
     let mut user_needs_mfa = false;
+    let target = payload.email.clone();
+    let client_id = payload.client_id.clone();
+    let remember_me = payload.remember_me == Some(true);
 
     let res = match authorize::post_authorize(
         &data,
         &req,
         payload.into_inner(),
         session.clone(),
+        false,
         &mut has_password_been_hashed,
         &mut add_login_delay,
         &mut user_needs_mfa,
     )
     .await
     {
-        Ok(auth_step) => map_auth_step(auth_step, &req).await,
+        Ok(auth_step) => {
+            // with an active passkey, the login will be recorded after the MFA step
+            let is_logged_in = if let AuthStep::LoggedIn(step) = &auth_step {
+                LoginHistory::record(&req, step.user_id.clone(), Some(client_id), false, true)
+                    .await;
+                true
+            } else {
+                false
+            };
+
+            let mut resp = map_auth_step(auth_step, &req).await;
+            // a "remember me" session has been extended and needs a longer living cookie
+            if remember_me && is_logged_in {
+                if let Ok(resp) = resp.as_mut() {
+                    let session = Session::find(session.id.clone()).await?;
+                    if session.remember_me {
+                        add_session_cookie(resp, &session);
+                    }
+                }
+            }
+            resp
+        }
         Err(err) => {
             debug!("{:?}", err);
+
+            // only an existing user with a checked password is an actual failed login
+            if has_password_been_hashed {
+                if let Ok(user) = User::find_by_email(target.clone()).await {
+                    LoginHistory::record(&req, user.id, Some(client_id), false, false).await;
+                }
+            }
+
             // We always must return the exact same error type, no matter what the actual error is,
             // to prevent information enumeration. The only exception is when the user needs to add
-            // a passkey to the account while having given the correct credentials. In that case,
-            // we return the original error to be able to display the info message in the UI.
-            if user_needs_mfa {
+            // a passkey to the account while having given the correct credentials, when the
+            // user is not allowed to access the client or has too many active sessions, when a TOTP,
+            // Yubico OTP, SMS or E-Mail code is missing, or when provided profile values are
+            // invalid. In that case, we return the original error to be able to display the info
+            // message in the UI.
+            if user_needs_mfa
+                || err.code == ErrorCode::AccessDenied
+                || err.code == ErrorCode::TotpRequired
+                || err.code == ErrorCode::EmailOtpRequired
+                || err.code == ErrorCode::SmsOtpRequired
+                || err.code == ErrorCode::YubicoOtpRequired
+                || err.code == ErrorCode::ValidationFailed
+            {
                 // in this case, we can return directly without any login delay
                 return Err(err);
             }
@@ -291,7 +509,8 @@ pub async fn post_authorize(
             let err = Err(ErrorResponse::new(
                 ErrorResponseType::Unauthorized,
                 "Invalid user credentials",
-            ));
+            )
+            .with_code(ErrorCode::InvalidCredentials));
             if !add_login_delay {
                 return err;
             }
@@ -300,7 +519,141 @@ pub async fn post_authorize(
     };
 
     let ip = real_ip_from_req(&req)?;
-    login_delay::handle_login_delay(&data, ip, start, res, has_password_been_hashed).await
+    login_delay::handle_login_delay(
+        &data,
+        ip,
+        Some(target),
+        start,
+        res,
+        has_password_been_hashed,
+    )
+    .await
+}
+
+/// Starts a login with a discoverable passkey
+///
+/// Returns a challenge for a passkey login without an E-Mail. It can be used with conditional
+/// mediation to show the users' passkeys inside the browsers' autofill on the login page. The
+/// signed challenge must be sent to `POST /oidc/authorize/passkey`.
+///
+/// **Permissions**
+/// - `session-init`
+/// - `session-auth`
+#[utoipa::path(
+    post,
+    path = "/oidc/authorize/passkey/start",
+    tag = "oidc",
+    responses(
+        (status = 200, description = "Ok", body = WebauthnDiscoverableStartResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+    ),
+)]
+#[post("/oidc/authorize/passkey/start")]
+pub async fn post_authorize_passkey_start(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_session_auth_or_init()?;
+
+    webauthn::auth_start_discoverable(data.webauthn.for_req(&req))
+        .await
+        .map(|resp| HttpResponse::Ok().json(resp))
+}
+
+/// Login with a discoverable passkey
+///
+/// Finishes the login started with `POST /oidc/authorize/passkey/start`. The user is identified
+/// by the passkey, which replaces the E-Mail, the password and the 2nd factor. User verification
+/// is mandatory. The responses are the same as for `POST /oidc/authorize`, apart from the
+/// additional Webauthn step, which will never be requested.
+///
+/// **Permissions**
+/// - `session-init`
+/// - `session-auth`
+#[utoipa::path(
+    post,
+    path = "/oidc/authorize/passkey",
+    tag = "oidc",
+    request_body = LoginPasskeyRequest,
+    responses(
+        (status = 202, description = "Correct passkey, adds Location header"),
+        (status = 400, description = "Missing / bad input data", body = ErrorResponse),
+        (status = 428, description = "Correct passkey, but the current terms of service must be accepted via `tos_accept`", body = TosRequiredResponse),
+        (status = 428, description = "Correct passkey, but missing custom attributes must be provided via `profile_values`", body = ProfileRequiredResponse),
+        (status = 428, description = "Correct passkey, but the requested scopes must be granted via `consent_accept`", body = ConsentRequiredResponse),
+        (status = 401, description = "Unknown or invalid passkey", body = ErrorResponse),
+    ),
+)]
+#[post("/oidc/authorize/passkey")]
+pub async fn post_authorize_passkey(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    payload: actix_web_validator::Json<LoginPasskeyRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_session_auth_or_init()?;
+
+    let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let session = principal.get_session()?;
+
+    let mut add_login_delay = true;
+    let mut user_needs_mfa = false;
+    let client_id = payload.client_id.clone();
+    let remember_me = payload.remember_me == Some(true);
+
+    let res = match authorize::post_authorize_passkey(
+        &data,
+        &req,
+        payload.into_inner(),
+        session.clone(),
+        &mut add_login_delay,
+        &mut user_needs_mfa,
+    )
+    .await
+    {
+        Ok(auth_step) => {
+            let is_logged_in = if let AuthStep::LoggedIn(step) = &auth_step {
+                LoginHistory::record(&req, step.user_id.clone(), Some(client_id), true, true).await;
+                true
+            } else {
+                false
+            };
+
+            let mut resp = map_auth_step(auth_step, &req).await;
+            if remember_me && is_logged_in {
+                if let Ok(resp) = resp.as_mut() {
+                    let session = Session::find(session.id.clone()).await?;
+                    if session.remember_me {
+                        add_session_cookie(resp, &session);
+                    }
+                }
+            }
+            resp
+        }
+        Err(err) => {
+            debug!("{:?}", err);
+
+            // the same exceptions as for the regular login apply here
+            if user_needs_mfa
+                || err.code == ErrorCode::AccessDenied
+                || err.code == ErrorCode::ValidationFailed
+            {
+                return Err(err);
+            }
+
+            Err(
+                ErrorResponse::new(ErrorResponseType::Unauthorized, "Invalid user credentials")
+                    .with_code(ErrorCode::InvalidCredentials),
+            )
+        }
+    };
+    if !add_login_delay {
+        return res;
+    }
+
+    let ip = real_ip_from_req(&req)?;
+    login_delay::handle_login_delay(&data, ip, None, start, res, false).await
 }
 
 /// Immediate login refresh with valid session
@@ -317,6 +670,7 @@ pub async fn post_authorize(
         (status = 202, description = "Accepted"),
         (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 428, description = "The requested scopes must be granted via `consent_accept`", body = ConsentRequiredResponse),
     ),
 )]
 #[post("/oidc/authorize/refresh")]
@@ -338,9 +692,18 @@ pub async fn post_authorize_refresh(
     )
     .await?;
 
-    let auth_step =
-        authorize::post_authorize_refresh(session, client, header_origin, req_data.into_inner())
-            .await?;
+    if let Some(user_id) = &session.user_id {
+        SessionDevice::upsert(&session.id, user_id, &req).await?;
+    }
+
+    let auth_step = authorize::post_authorize_refresh(
+        &data,
+        session,
+        client,
+        header_origin,
+        req_data.into_inner(),
+    )
+    .await?;
     map_auth_step(auth_step, &req).await
 }
 
@@ -399,6 +762,8 @@ pub async fn get_cert_by_kid(kid: web::Path<String>) -> Result<HttpResponse, Err
     responses(
         (status = 200, description = "Ok", body = DeviceCodeResponse),
         (status = 400, description = "BadRequest", body = OAuth2ErrorResponse),
+        (status = 401, description = "Unauthorized", body = OAuth2ErrorResponse),
+        (status = 429, description = "TooManyRequests", body = OAuth2ErrorResponse),
     ),
 )]
 #[post("/oidc/device")]
@@ -458,7 +823,7 @@ pub async fn post_device_auth(
     let client = match Client::find(payload.client_id).await {
         Ok(client) => client,
         Err(_) => {
-            return HttpResponse::NotFound().json(OAuth2ErrorResponse {
+            return HttpResponse::Unauthorized().json(OAuth2ErrorResponse {
                 error: OAuth2ErrorTypeResponse::InvalidClient,
                 error_description: Some(Cow::from("`client_id` does not exist")),
             });
@@ -473,7 +838,7 @@ pub async fn post_device_auth(
     }
 
     if let Err(err) = client.validate_flow(GRANT_TYPE_DEVICE_CODE) {
-        return HttpResponse::Forbidden().json(OAuth2ErrorResponse {
+        return HttpResponse::BadRequest().json(OAuth2ErrorResponse {
             error: OAuth2ErrorTypeResponse::UnauthorizedClient,
             error_description: Some(err.message),
         });
@@ -483,7 +848,7 @@ pub async fn post_device_auth(
         let iter = scopes.split(' ').collect::<Vec<&str>>();
         for scope in iter {
             if !client.scopes.contains(scope) {
-                return HttpResponse::InternalServerError().json(OAuth2ErrorResponse {
+                return HttpResponse::BadRequest().json(OAuth2ErrorResponse {
                     error: OAuth2ErrorTypeResponse::InvalidScope,
                     error_description: Some(Cow::from(format!(
                         "Allowed scopes: {}",
@@ -499,8 +864,8 @@ pub async fn post_device_auth(
 
     if let Ok(secret) = client.get_secret_cleartext() {
         if secret != payload.client_secret {
-            return HttpResponse::InternalServerError().json(OAuth2ErrorResponse {
-                error: OAuth2ErrorTypeResponse::UnauthorizedClient,
+            return HttpResponse::Unauthorized().json(OAuth2ErrorResponse {
+                error: OAuth2ErrorTypeResponse::InvalidClient,
                 error_description: Some(Cow::from("Invalid `client_secret`")),
             });
         }
@@ -559,6 +924,7 @@ pub async fn post_device_verify(
 
     let mut device_code = DeviceAuthCode::find(payload.user_code)
         .await?
+        .filter(|code| !code.denied)
         .ok_or_else(|| {
             ErrorResponse::new(
                 ErrorResponseType::BadRequest,
@@ -568,12 +934,26 @@ pub async fn post_device_verify(
 
     match payload.device_accepted {
         DeviceAcceptedRequest::Accept => {
-            device_code.verified_by = Some(principal.user_id()?.to_string());
+            let user = User::find(principal.user_id()?.to_string()).await?;
+            let client = Client::find(device_code.client_id.clone()).await?;
+            ClientAccessPolicy::validate_user(&client.id, &user).await?;
+
+            // The session for the `rauthy` client may exist without a 2nd factor, which must not
+            // be a way around the MFA requirements of the device client.
+            let has_otp = UserTotp::is_enabled(&user.id).await?
+                || UserYubicoOtp::is_enabled(&user.id).await?
+                || UserSmsOtp::is_enabled(&user.id).await?;
+            client.validate_mfa(&user, has_otp)?;
+            Group::validate_mfa(&client.id, &user, has_otp).await?;
+
+            device_code.verified_by = Some(user.id);
             device_code.save().await?;
             Ok(HttpResponse::Accepted().finish())
         }
         DeviceAcceptedRequest::Decline => {
-            device_code.delete().await?;
+            // keep the code until the next poll to be able to tell the device about it
+            device_code.denied = true;
+            device_code.save().await?;
             Ok(HttpResponse::NoContent().finish())
         }
         DeviceAcceptedRequest::Pending => Ok(HttpResponse::Ok().json(DeviceVerifyResponse {
@@ -582,6 +962,35 @@ pub async fn post_device_verify(
     }
 }
 
+/// The OIDC Session Management `check_session_iframe`
+///
+/// Returns a minimal HTML page, which is meant to be embedded into a client application as a
+/// hidden iframe. The client posts `<client_id> <session_state>` to it and receives either
+/// `unchanged`, `changed` or `error`, depending on whether the session in this browser is still
+/// the one the `session_state` has been issued for.
+#[utoipa::path(
+    get,
+    path = "/oidc/session/check",
+    tag = "oidc",
+    responses(
+        (status = 200, description = "Ok"),
+    ),
+)]
+#[get("/oidc/session/check")]
+pub async fn get_check_session_iframe() -> HttpResponse {
+    let cookie_name = ApiCookie::prefixed_name(COOKIE_SESSION_STATE);
+    let body = CheckSessionHtml::build(&cookie_name);
+
+    // this page must be embeddable by any client
+    HttpResponse::Ok()
+        .insert_header(HEADER_HTML)
+        .insert_header((
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("frame-ancestors *; object-src 'none';"),
+        ))
+        .body(body)
+}
+
 /// Logout HTML page
 ///
 /// Returns an HTML page which can be used for logging the user out. Invalidates the session and deletes
@@ -639,17 +1048,21 @@ pub async fn get_logout(
     tag = "oidc",
     params(LogoutRequest),
     responses(
-        (status = 200, description = "Ok without `post_logout_redirect_uri`"),
+        (status = 200, description = "Ok without `post_logout_redirect_uri`", body = LogoutResponse),
         (status = 301, description = "if a `post_logout_redirect_uri` was given"),
         (status = 400, description = "BadRequest", body = ErrorResponse),
     ),
 )]
 #[post("/oidc/logout")]
 pub async fn post_logout(
+    data: web::Data<AppState>,
     req_data: actix_web_validator::Query<LogoutRequest>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
     let session = principal.get_session()?.clone();
+    // must be resolved while the session is still valid
+    let frontchannel_logout_uris =
+        SessionClient::frontchannel_logout_uris(&data.issuer, &session.id).await?;
     let cookie_fed_cm = ApiCookie::build_with_same_site(
         COOKIE_SESSION_FED_CM,
         Cow::from(&session.id),
@@ -658,6 +1071,7 @@ pub async fn post_logout(
     );
     let sid = session.id.clone();
     let cookie = ApiCookie::build(COOKIE_SESSION, &sid, 0);
+    let cookie_state = ApiCookie::build_plain(COOKIE_SESSION_STATE, "", 0);
     session.invalidate().await?;
 
     if req_data.post_logout_redirect_uri.is_some() {
@@ -675,13 +1089,17 @@ pub async fn post_logout(
             .append_header((header::LOCATION, loc))
             .cookie(cookie)
             .cookie(cookie_fed_cm)
+            .cookie(cookie_state)
             .finish());
     }
 
     Ok(HttpResponse::build(StatusCode::OK)
         .cookie(cookie)
         .cookie(cookie_fed_cm)
-        .finish())
+        .cookie(cookie_state)
+        .json(LogoutResponse {
+            frontchannel_logout_uris,
+        }))
 }
 
 /// Rotate JWKs
@@ -706,12 +1124,50 @@ pub async fn post_logout(
 pub async fn rotate_jwk(
     data: web::Data<AppState>,
     principal: ReqPrincipal,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Update)?;
 
-    JWKS::rotate(&data)
-        .await
-        .map(|_| HttpResponse::Ok().finish())
+    JWKS::rotate(&data).await?;
+    AuditLog::action(&principal, &req, AuditAction::Create, "jwks", "all").await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Import a JWK
+///
+/// Imports an externally generated private key as PEM or JWK with a chosen `kid`, for instance
+/// when migrating from another IdP. The key becomes the latest one for its algorithm and signs new
+/// tokens until the next rotation. After that, it stays published for `JWK_RETIREMENT_DAYS` like
+/// any other key, so tokens issued with it stay valid.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/oidc/import_jwk",
+    tag = "oidc",
+    request_body = JwkImportRequest,
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+    ),
+)]
+#[post("/oidc/import_jwk")]
+pub async fn post_import_jwk(
+    payload: actix_web_validator::Json<JwkImportRequest>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Secrets, AccessRights::Create)?;
+
+    let payload = payload.into_inner();
+    let kid = payload.kid.clone();
+    JWKS::import(payload).await?;
+    AuditLog::action(&principal, &req, AuditAction::Create, "jwks", &kid).await;
+
+    Ok(HttpResponse::Ok().finish())
 }
 
 /// Create a new session
@@ -757,10 +1213,14 @@ pub async fn post_session(
     if *EXPERIMENTAL_FED_CM_ENABLE {
         Ok(HttpResponse::Created()
             .cookie(cookie)
+            .cookie(session.client_cookie_session_state())
             .cookie(session.client_cookie_fed_cm())
             .json(info))
     } else {
-        Ok(HttpResponse::Created().cookie(cookie).json(info))
+        Ok(HttpResponse::Created()
+            .cookie(cookie)
+            .cookie(session.client_cookie_session_state())
+            .json(info))
     }
 }
 
@@ -792,9 +1252,8 @@ pub async fn get_session_info(data: web::Data<AppState>, principal: ReqPrincipal
         }
     };
 
-    let timeout = OffsetDateTime::from_unix_timestamp(session.last_seen)
-        .unwrap()
-        .add(::time::Duration::seconds(data.session_timeout as i64));
+    let timeout =
+        OffsetDateTime::from_unix_timestamp(session.timeout(data.session_timeout)).unwrap();
     let info = SessionInfoResponse {
         id: session.id.as_str().into(),
         csrf_token: None,
@@ -844,9 +1303,8 @@ pub async fn get_session_xsrf(
     principal.validate_session_auth()?;
     let session = principal.get_session()?;
 
-    let timeout = OffsetDateTime::from_unix_timestamp(session.last_seen)
-        .unwrap()
-        .add(::time::Duration::seconds(data.session_timeout as i64));
+    let timeout =
+        OffsetDateTime::from_unix_timestamp(session.timeout(data.session_timeout)).unwrap();
     let info = SessionInfoResponse {
         id: session.id.as_str().into(),
         csrf_token: Some(session.csrf_token.as_str().into()),
@@ -896,6 +1354,7 @@ pub async fn post_token(
 
     let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
     let has_password_been_hashed = payload.grant_type == "password";
+    let target = payload.username.clone();
 
     let res = match oidc::get_token_set(payload.into_inner(), &data, req).await {
         Ok((token_set, headers)) => {
@@ -916,7 +1375,7 @@ pub async fn post_token(
         }
     };
 
-    login_delay::handle_login_delay(&data, ip, start, res, has_password_been_hashed).await
+    login_delay::handle_login_delay(&data, ip, target, start, res, has_password_been_hashed).await
 }
 
 /// The token introspection endpoint for OAuth2
@@ -925,6 +1384,7 @@ pub async fn post_token(
 /// You can authorize in 2 different ways:
 /// 1. `Basic` auth with `client_id:client_secret`
 /// 2. `Bearer` JWT token
+/// 3. `client_assertion` with `private_key_jwt` or `client_secret_jwt`
 ///
 /// If your client application can't provide any, you can disable authorization for this endpoint
 /// by setting `DANGER_DISABLE_INTROSPECT_AUTH=true` in the Rauthy config.
@@ -947,7 +1407,8 @@ pub async fn post_token_introspect(
     req: HttpRequest,
     req_data: actix_web_validator::Form<TokenValidationRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    match token_info::get_token_info(&data, &req, &req_data.token).await {
+    let client_assertion = req_data.client_assertion()?;
+    match token_info::get_token_info(&data, &req, &req_data.token, client_assertion).await {
         Ok(info) => Ok(HttpResponse::Ok().json(info)),
         Err(err) => {
             error!("{:?}", err);
@@ -956,6 +1417,35 @@ pub async fn post_token_introspect(
     }
 }
 
+/// The token revocation endpoint for OAuth2 (RFC 7009)
+///
+/// Accepts access and refresh tokens and requires the same client authentication as the token
+/// endpoint. Revoking a refresh token invalidates all refresh tokens rotated from the same
+/// original one, as well as the session it has been issued for.
+///
+/// JWT access tokens are stateless and stay valid until they expire. Opaque access tokens are
+/// revoked immediately.
+#[utoipa::path(
+    post,
+    path = "/oidc/revoke",
+    tag = "oidc",
+    request_body(content = TokenRevocationRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+    ),
+)]
+#[post("/oidc/revoke")]
+pub async fn post_token_revoke(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    payload: actix_web_validator::Form<TokenRevocationRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    revocation::revoke_token(&data, &req, payload.into_inner()).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
 /// DEPRECATED
 ///
 /// This is an older endpoint for validating tokens manually. This is not being used anymore an will
@@ -990,7 +1480,7 @@ pub async fn post_validate_token(
     path = "/oidc/userinfo",
     tag = "oidc",
     responses(
-        (status = 200, description = "Ok", body = Userinfo),
+        (status = 200, description = "Ok, or an encrypted JWT if configured for the client", body = Userinfo),
         (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 404, description = "NotFound", body = ErrorResponse),
@@ -1001,9 +1491,7 @@ pub async fn get_userinfo(
     data: web::Data<AppState>,
     req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
-    userinfo::get_userinfo(&data, req)
-        .await
-        .map(|u| HttpResponse::Ok().json(u))
+    userinfo::get_userinfo_response(&data, req).await
 }
 
 /// The userinfo endpoint for the OIDC standard.
@@ -1015,7 +1503,7 @@ pub async fn get_userinfo(
     path = "/oidc/userinfo",
     tag = "oidc",
     responses(
-        (status = 200, description = "Ok", body = Userinfo),
+        (status = 200, description = "Ok, or an encrypted JWT if configured for the client", body = Userinfo),
         (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 404, description = "NotFound", body = ErrorResponse),
@@ -1026,9 +1514,7 @@ pub async fn post_userinfo(
     data: web::Data<AppState>,
     req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
-    userinfo::get_userinfo(&data, req)
-        .await
-        .map(|u| HttpResponse::Ok().json(u))
+    userinfo::get_userinfo_response(&data, req).await
 }
 
 /// GET forward authentication
@@ -1089,6 +1575,41 @@ pub async fn get_forward_auth(
     }
 }
 
+/// The OpenID Federation 1.0 entity configuration
+///
+/// Returns the signed entity statement of this instance, which contains the OIDC discovery
+/// metadata, the public JWKS and the configured authority hints.
+/// Only available with `FEDERATION_ENABLE=true`.
+#[utoipa::path(
+    get,
+    path = "/.well-known/openid-federation",
+    tag = "oidc",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/.well-known/openid-federation")]
+pub async fn get_openid_federation(
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, ErrorResponse> {
+    if !*FEDERATION_ENABLE {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::NotFound,
+            "OpenID Federation is not enabled",
+        ));
+    }
+
+    let statement = EntityStatement::own_configuration(&data).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, CONTENT_TYPE_ENTITY_STATEMENT))
+        .insert_header((
+            header::ACCESS_CONTROL_ALLOW_ORIGIN,
+            HeaderValue::from_static("*"),
+        ))
+        .body(statement))
+}
+
 /// The `.well-known` endpoint for OIDC Client auto discovery.
 ///
 /// Capable OIDC clients can use this endpoint to auto-discover all necessary OIDC information and
@@ -1,14 +1,15 @@
 use crate::{
-    api_keys, auth_providers, blacklist, clients, events, fed_cm, generic, groups, oidc, roles,
-    scopes, sessions, users,
+    api_keys, audit_log, auth_providers, blacklist, clients, events, fed_cm, generic, groups,
+    login_stats, oidc, roles, scim, scopes, sessions, users,
 };
 use actix_web::web;
 use rauthy_api_types::{
-    api_keys::*, auth_providers::*, blacklist::*, clients::*, events::*, fed_cm::*, generic::*,
-    groups::*, oidc::*, roles::*, scopes::*, sessions::*, users::*,
+    api_keys::*, audit_log::*, auth_providers::*, blacklist::*, clients::*, events::*, fed_cm::*,
+    generic::*, groups::*, login_stats::*, oidc::*, roles::*, scim::*, scopes::*, sessions::*,
+    users::*,
 };
 use rauthy_common::constants::{PROXY_MODE, RAUTHY_VERSION};
-use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity;
 use rauthy_models::ListenScheme;
@@ -25,6 +26,10 @@ use utoipa::{openapi, OpenApi};
         api_keys::delete_api_key,
         api_keys::get_api_key_test,
         api_keys::put_api_key_secret,
+        api_keys::post_api_key_exchange,
+
+        audit_log::get_audit_log,
+        audit_log::get_audit_log_verify,
 
         auth_providers::post_providers,
         auth_providers::post_provider,
@@ -46,6 +51,8 @@ use utoipa::{openapi, OpenApi};
 
         clients::get_clients,
         clients::get_client_by_id,
+        clients::get_client_access_policy,
+        clients::put_client_access_policy,
         clients::get_client_colors,
         clients::put_client_colors,
         clients::delete_client_colors,
@@ -56,6 +63,7 @@ use utoipa::{openapi, OpenApi};
         clients::post_clients,
         clients::put_clients,
         clients::put_generate_client_secret,
+        clients::post_client_revoke_all,
         clients::delete_client,
 
         events::post_events,
@@ -88,30 +96,58 @@ use utoipa::{openapi, OpenApi};
         groups::post_group,
         groups::put_group,
         groups::delete_group,
+        groups::get_group_password_policy,
+        groups::put_group_password_policy,
+        groups::delete_group_password_policy,
+        groups::get_group_inheritance,
+        groups::put_group_inheritance,
+
+        login_stats::get_login_stats,
 
         oidc::get_authorize,
         oidc::post_authorize,
         oidc::post_authorize_refresh,
+        oidc::post_authorize_passkey_start,
+        oidc::post_authorize_passkey,
         oidc::get_certs,
         oidc::get_cert_by_kid,
         oidc::post_device_auth,
+        oidc::get_check_session_iframe,
         oidc::get_logout,
         oidc::post_logout,
         oidc::rotate_jwk,
+        oidc::post_import_jwk,
         oidc::post_session,
         oidc::get_session_info,
         oidc::get_session_xsrf,
         oidc::post_token,
         oidc::post_token_introspect,
+        oidc::post_token_revoke,
         oidc::post_validate_token,
         oidc::get_userinfo,
         oidc::get_forward_auth,
         oidc::get_well_known,
+        oidc::get_openid_federation,
 
         roles::get_roles,
         roles::post_role,
         roles::put_role,
         roles::delete_role,
+        roles::get_role_mapping_rules,
+        roles::post_role_mapping_rule,
+        roles::put_role_mapping_rule,
+        roles::delete_role_mapping_rule,
+
+        scim::get_scim_users,
+        scim::post_scim_user,
+        scim::get_scim_user,
+        scim::patch_scim_user,
+        scim::delete_scim_user,
+        scim::get_scim_groups,
+        scim::post_scim_group,
+        scim::get_scim_group,
+        scim::patch_scim_group,
+        scim::delete_scim_group,
 
         scopes::get_scopes,
         scopes::post_scope,
@@ -121,9 +157,12 @@ use utoipa::{openapi, OpenApi};
         sessions::get_sessions,
         sessions::delete_sessions,
         sessions::delete_sessions_for_user,
+        sessions::delete_session,
+        sessions::post_session_ping,
 
         users::get_users,
         users::post_users,
+        users::post_users_import,
         users::get_cust_attr,
         users::post_cust_attr,
         users::put_cust_attr,
@@ -131,16 +170,51 @@ use utoipa::{openapi, OpenApi};
         users::get_users_register,
         users::post_users_register,
         users::get_user_by_id,
+        users::get_user_session_devices,
+        users::delete_user_session_device,
+        users::get_user_trusted_devices,
+        users::delete_user_trusted_devices,
+        users::delete_user_trusted_device,
+        users::get_user_activity,
+        users::get_user_export,
+        users::get_user_login_history,
+        users::get_user_password_policy,
+        users::get_user_picture,
+        users::put_user_picture,
+        users::delete_user_picture,
         users::get_user_attr,
         users::put_user_attr,
+        users::get_user_client_metadata,
+        users::put_user_client_metadata,
+        users::delete_user_client_metadata,
+        users::get_user_consents,
+        users::delete_user_consent,
         users::get_user_webid,
         users::get_user_webid_data,
         users::put_user_webid_data,
+        users::post_user_email_verification,
         users::get_user_password_reset,
         users::put_user_password_reset,
         users::post_webauthn_auth_start,
         users::post_webauthn_auth_finish,
         users::delete_webauthn,
+        users::put_webauthn_rename,
+        users::get_user_totp,
+        users::post_user_totp,
+        users::post_user_totp_confirm,
+        users::delete_user_totp,
+        users::get_user_email_otp,
+        users::put_user_email_otp,
+        users::get_user_sms_otp,
+        users::post_user_sms_otp,
+        users::post_user_sms_otp_confirm,
+        users::delete_user_sms_otp,
+        users::get_user_yubico_otp,
+        users::post_user_yubico_otp,
+        users::delete_user_yubico_otp,
+        users::get_user_recovery_codes,
+        users::post_user_recovery_codes,
+        users::post_user_mfa_reset,
         users::post_webauthn_reg_start,
         users::post_user_password_request_reset,
         users::get_user_by_email,
@@ -148,6 +222,9 @@ use utoipa::{openapi, OpenApi};
         users::put_user_self,
         users::post_user_self_convert_passkey,
         users::delete_user_by_id,
+        users::post_user_restore,
+        users::post_user_unlock,
+        users::post_user_impersonate,
     ),
     components(
         schemas(
@@ -175,20 +252,27 @@ use utoipa::{openapi, OpenApi};
             AuthProviderType,
             EventLevel,
             EventType,
-            JktClaim,
+            CnfClaim,
             JwkKeyPairAlg,
             JwkKeyPairType,
             Language,
 
             rauthy_models::JwtTokenType,
 
+            ErrorCode,
             ErrorResponse,
             ErrorResponseType,
 
             ApiKeyRequest,
+            ApiKeyExchangeRequest,
+            ApiKeyExchangeResponse,
+            AuditLogParams,
             AuthCodeRequest,
             AuthRequest,
             IpBlacklistRequest,
+            LoginStatsParams,
+            ClientAccessPolicyRequest,
+            ClientUserMetadataRequest,
             ColorsRequest,
             DeviceGrantRequest,
             EncKeyMigrateRequest,
@@ -203,11 +287,15 @@ use utoipa::{openapi, OpenApi};
             EventLevel,
             EventsListenParams,
             EventsRequest,
+            GroupInheritanceRequest,
             LoginRefreshRequest,
+            LoginPasskeyRequest,
             NewGroupRequest,
             NewUserRequest,
             NewUserRegistrationRequest,
             NewRoleRequest,
+            RoleMappingRuleRequest,
+            RoleMappingSource,
             PaginationParams,
             PasswordHashTimesRequest,
             PasswordPolicyRequest,
@@ -217,18 +305,40 @@ use utoipa::{openapi, OpenApi};
             ProviderLookupRequest,
             ProviderCallbackRequest,
             RequestResetRequest,
+            ScimEmail,
+            ScimErrorResponse,
+            ScimGroup,
+            ScimGroupListResponse,
+            ScimMeta,
+            ScimName,
+            ScimPatchOperation,
+            ScimPatchRequest,
+            ScimRef,
+            ScimUser,
+            ScimUserListResponse,
             ScopeRequest,
             SessionState,
             TokenRequest,
+            TokenRevocationRequest,
             TokenValidationRequest,
+            JwkImportRequest,
+            JwkImportPrivateKey,
             UpdateClientRequest,
             UpdateUserRequest,
             UpdateUserSelfRequest,
+            UserActivityParams,
+            LoginHistoryParams,
+            UserExportParams,
+            UsersSearchParams,
+            UserSortKey,
+            SortOrder,
             UserValuesRequest,
             UserAttrConfigRequest,
+            UserAttrType,
             UserAttrValueRequest,
             UserAttrValuesUpdateRequest,
             WebauthnRegStartRequest,
+            WebauthnRenameRequest,
             WebauthnRegFinishRequest,
             WebauthnAuthStartRequest,
             WebauthnAuthFinishRequest,
@@ -237,13 +347,22 @@ use utoipa::{openapi, OpenApi};
             ApiKeyResponse,
             ApiKeysResponse,
             AppVersionResponse,
+            AuditLogEntryResponse,
+            AuditLogVerifyResponse,
             BlacklistResponse,
             BlacklistedIp,
+            LoginStatsResponse,
+            LoginStatsHourResponse,
+            LoginStatsTopResponse,
             PasswordResetResponse,
             LoginTimeResponse,
+            LogoutResponse,
+            ClientAccessPolicyResponse,
+            ClientUserMetadataResponse,
             ClientResponse,
             DeviceCodeResponse,
             DynamicClientResponse,
+            ClientRevokeAllResponse,
             ClientSecretResponse,
             EncKeysResponse,
             HealthResponse,
@@ -252,12 +371,21 @@ use utoipa::{openapi, OpenApi};
             Argon2ParamsResponse,
             OAuth2ErrorResponse,
             OAuth2ErrorTypeResponse,
+            GroupInheritanceResponse,
+            RoleMappingRuleResponse,
+            TosAcceptanceResponse,
+            TosRequiredResponse,
+            ProfileRequiredResponse,
+            ConsentRequiredResponse,
             PasswordPolicyResponse,
             ProviderResponse,
             ProviderLinkedUserResponse,
             ProviderLookupResponse,
             ScopeResponse,
             SessionResponse,
+            SessionDeleteParams,
+            SessionFilterParams,
+            SessionPingResponse,
             SessionInfoResponse,
             TokenInfo,
             UserAttrConfigResponse,
@@ -265,12 +393,33 @@ use utoipa::{openapi, OpenApi};
             UserAttrValueResponse,
             UserAttrValuesResponse,
             Userinfo,
+            UserActivityResponse,
+            LoginHistoryResponse,
+            UserExportResponse,
             UserValuesResponse,
             UserAccountTypeResponse,
+            UserImportResponse,
+            UserImportRow,
+            UserImportRowResult,
             UserResponse,
+            SessionDeviceResponse,
+            TrustedDeviceResponse,
+            EmailOtpRequest,
+            EmailOtpStatusResponse,
+            SmsOtpConfirmRequest,
+            SmsOtpEnrollRequest,
+            SmsOtpStatusResponse,
+            YubicoOtpRequest,
+            YubicoOtpStatusResponse,
+            MfaRecoveryCodesResponse,
+            MfaRecoveryCodesStatusResponse,
+            TotpConfirmRequest,
+            TotpEnrollResponse,
+            TotpStatusResponse,
             WebauthnAuthStartResponse,
             WebauthnLoginFinishResponse,
             WebauthnLoginResponse,
+            WebauthnDiscoverableStartResponse,
             WebId,
             WebIdResponse,
 
@@ -286,6 +435,7 @@ use utoipa::{openapi, OpenApi};
         (name = "groups", description = "Groups endpoints"),
         (name = "roles", description = "Roles endpoints"),
         (name = "scopes", description = "Scopes endpoints"),
+        (name = "scim", description = "SCIM 2.0 provisioning"),
         (name = "events", description = "Events Stream"),
         (name = "providers", description = "Upstream Auth Providers"),
         (name = "health", description = "Ping, Health, Ready Check"),
@@ -301,33 +451,256 @@ pub struct ApiDoc;
 
 impl ApiDoc {
     pub fn build(app_state: &web::Data<AppState>) -> openapi::OpenApi {
-        let mut doc = Self::openapi();
+        let desc = "OpenID Connect Single Sign-On Identity & Access Management";
+        with_meta(Self::openapi(), app_state, "Rauthy Single Sign-on", desc)
+    }
+}
 
-        doc.info = openapi::Info::new("Rauthy Single Sign-on", &format!("v{}", RAUTHY_VERSION));
+/// Only contains the public and self-service endpoints, which are meant to be used by end users
+/// and OIDC clients. Integrators can generate clients from this document without depending on
+/// the admin API.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        auth_providers::post_provider_lookup,
+        auth_providers::post_provider_login,
+        auth_providers::post_provider_callback,
+        auth_providers::post_provider_link,
+        auth_providers::delete_provider_link,
+        auth_providers::get_providers_minimal,
+        auth_providers::get_provider_img,
+
+        fed_cm::get_fed_cm_accounts,
+        fed_cm::get_fed_cm_client_meta,
+        fed_cm::get_fed_cm_config,
+        fed_cm::get_fed_cm_status,
+        fed_cm::post_fed_cm_token,
+        fed_cm::get_fed_cm_well_known,
 
-        doc.external_docs = Some(ExternalDocs::new("https://sebadob.github.io/rauthy/"));
+        generic::get_login_time,
+        generic::get_password_policy,
+        generic::get_health,
+        generic::post_pow,
+        generic::get_ready,
+        generic::ping,
+        generic::get_version,
+        generic::get_whoami,
 
-        let desc = "OpenID Connect Single Sign-On Identity & Access Management";
-        doc.info.description = Some(desc.to_string());
-
-        // let mut contact = Contact::new();
-        // contact.name = Some("".to_string());
-        // contact.url = Some("".to_string());
-        // contact.email = Some(ADMIN);
-        // doc.info.contact = Some(contact);
-
-        let scheme = if (!*PROXY_MODE && app_state.listen_scheme == ListenScheme::Http)
-            || app_state.listen_scheme == ListenScheme::UnixHttp
-        {
-            "http://"
-        } else {
-            "https://"
-        };
-
-        let pub_url = &app_state.public_url;
-        let url = format!("{}{}/auth/v1", scheme, pub_url);
-        doc.servers = Some(vec![Server::new(url)]);
-
-        doc
+        oidc::get_authorize,
+        oidc::post_authorize,
+        oidc::post_authorize_refresh,
+        oidc::post_authorize_passkey_start,
+        oidc::post_authorize_passkey,
+        oidc::get_certs,
+        oidc::get_cert_by_kid,
+        oidc::post_device_auth,
+        oidc::get_check_session_iframe,
+        oidc::get_logout,
+        oidc::post_logout,
+        oidc::post_session,
+        oidc::get_session_info,
+        oidc::get_session_xsrf,
+        oidc::post_token,
+        oidc::post_token_introspect,
+        oidc::post_token_revoke,
+        oidc::post_validate_token,
+        oidc::get_userinfo,
+        oidc::get_forward_auth,
+        oidc::get_well_known,
+        oidc::get_openid_federation,
+
+        sessions::post_session_ping,
+
+        users::get_users_register,
+        users::post_users_register,
+        users::get_user_by_id,
+        users::get_user_activity,
+        users::get_user_export,
+        users::get_user_login_history,
+        users::get_user_password_policy,
+        users::get_user_picture,
+        users::put_user_picture,
+        users::delete_user_picture,
+        users::get_user_webid,
+        users::get_user_webid_data,
+        users::put_user_webid_data,
+        users::post_user_email_verification,
+        users::get_user_password_reset,
+        users::put_user_password_reset,
+        users::post_webauthn_auth_start,
+        users::post_webauthn_auth_finish,
+        users::delete_webauthn,
+        users::put_webauthn_rename,
+        users::get_user_totp,
+        users::post_user_totp,
+        users::post_user_totp_confirm,
+        users::delete_user_totp,
+        users::get_user_email_otp,
+        users::put_user_email_otp,
+        users::get_user_sms_otp,
+        users::post_user_sms_otp,
+        users::post_user_sms_otp_confirm,
+        users::delete_user_sms_otp,
+        users::get_user_yubico_otp,
+        users::post_user_yubico_otp,
+        users::delete_user_yubico_otp,
+        users::get_user_recovery_codes,
+        users::post_user_recovery_codes,
+        users::post_user_mfa_reset,
+        users::post_webauthn_reg_start,
+        users::post_user_password_request_reset,
+        users::put_user_self,
+        users::post_user_self_convert_passkey,
+    ),
+    components(
+        schemas(
+            entity::fed_cm::FedCMAccount,
+            entity::fed_cm::FedCMAccounts,
+            entity::fed_cm::FedCMIdPBranding,
+            entity::fed_cm::FedCMIdPConfig,
+            entity::fed_cm::FedCMIdPIcon,
+            entity::fed_cm::WebIdentity,
+            entity::webauthn::WebauthnAdditionalData,
+            entity::webauthn::WebauthnLoginReq,
+            entity::webauthn::WebauthnServiceReq,
+            entity::well_known::WellKnown,
+
+            AddressClaim,
+            AuthProviderType,
+            EventType,
+            CnfClaim,
+            JwkKeyPairAlg,
+            JwkKeyPairType,
+            Language,
+
+            rauthy_models::JwtTokenType,
+
+            ErrorCode,
+            ErrorResponse,
+            ErrorResponseType,
+
+            AuthCodeRequest,
+            AuthRequest,
+            DeviceGrantRequest,
+            FedCMAssertionRequest,
+            FedCMClientMetadataRequest,
+            LoginRequest,
+            LoginRefreshRequest,
+            LoginPasskeyRequest,
+            LogoutRequest,
+            MfaAwaitRequest,
+            MfaPurpose,
+            NewUserRegistrationRequest,
+            PasswordResetRequest,
+            ProviderLoginRequest,
+            ProviderLookupRequest,
+            ProviderCallbackRequest,
+            RequestResetRequest,
+            TokenRequest,
+            TokenRevocationRequest,
+            TokenValidationRequest,
+            UpdateUserSelfRequest,
+            UserActivityParams,
+            LoginHistoryParams,
+            UserExportParams,
+            UserValuesRequest,
+            WebauthnRegStartRequest,
+            WebauthnRenameRequest,
+            WebauthnRegFinishRequest,
+            WebauthnAuthStartRequest,
+            WebauthnAuthFinishRequest,
+            WebIdRequest,
+
+            AppVersionResponse,
+            PasswordResetResponse,
+            LoginTimeResponse,
+            DeviceCodeResponse,
+            HealthResponse,
+            JWKSCerts,
+            JWKSPublicKeyCerts,
+            OAuth2ErrorResponse,
+            OAuth2ErrorTypeResponse,
+            PasswordPolicyResponse,
+            ProviderLinkedUserResponse,
+            ProviderLookupResponse,
+            SessionPingResponse,
+            SessionInfoResponse,
+            TokenInfo,
+            Userinfo,
+            UserActivityResponse,
+            LoginHistoryResponse,
+            ClientUserMetadataResponse,
+            UserConsentResponse,
+            UserExportResponse,
+            UserValuesResponse,
+            UserAccountTypeResponse,
+            UserResponse,
+            WebauthnAuthStartResponse,
+            WebauthnLoginFinishResponse,
+            WebauthnLoginResponse,
+            WebauthnDiscoverableStartResponse,
+            WebId,
+            WebIdResponse,
+
+            token_set::TokenSet,
+        ),
+    ),
+    tags(
+        (name = "oidc", description = "OpenID Connect endpoints"),
+        (name = "users", description = "Users endpoints"),
+        (name = "mfa", description = "MFA endpoints"),
+        (name = "sessions", description = "Sessions endpoints"),
+        (name = "providers", description = "Upstream Auth Providers"),
+        (name = "health", description = "Ping, Health, Ready Check"),
+        (name = "generic", description = "Generic endpoints"),
+        (name = "webid", description = "WebID endpoints"),
+        (name = "fed_cm", description = "Experimental FedCM endpoints"),
+        (name = "deprecated", description = "Deprecated endpoints - will be removed in a future version"),
+    ),
+)]
+pub struct SelfServiceApiDoc;
+
+impl SelfServiceApiDoc {
+    pub fn build(app_state: &web::Data<AppState>) -> openapi::OpenApi {
+        let desc = "Public and self-service endpoints of Rauthy, without the admin API";
+        with_meta(
+            Self::openapi(),
+            app_state,
+            "Rauthy Single Sign-on - Self-Service",
+            desc,
+        )
     }
 }
+
+fn with_meta(
+    mut doc: openapi::OpenApi,
+    app_state: &web::Data<AppState>,
+    title: &str,
+    desc: &str,
+) -> openapi::OpenApi {
+    doc.info = openapi::Info::new(title, &format!("v{}", RAUTHY_VERSION));
+
+    doc.external_docs = Some(ExternalDocs::new("https://sebadob.github.io/rauthy/"));
+
+    doc.info.description = Some(desc.to_string());
+
+    // let mut contact = Contact::new();
+    // contact.name = Some("".to_string());
+    // contact.url = Some("".to_string());
+    // contact.email = Some(ADMIN);
+    // doc.info.contact = Some(contact);
+
+    let scheme = if (!*PROXY_MODE && app_state.listen_scheme == ListenScheme::Http)
+        || app_state.listen_scheme == ListenScheme::UnixHttp
+    {
+        "http://"
+    } else {
+        "https://"
+    };
+
+    let pub_url = &app_state.public_url;
+    let url = format!("{}{}/auth/v1", scheme, pub_url);
+    doc.servers = Some(vec![Server::new(url)]);
+
+    doc
+}
@@ -1,8 +1,10 @@
 use crate::ReqPrincipal;
-use actix_web::{delete, get, post, put, web, HttpResponse};
-use rauthy_api_types::roles::NewRoleRequest;
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use rauthy_api_types::roles::{NewRoleRequest, RoleMappingRuleRequest, RoleMappingRuleResponse};
 use rauthy_error::ErrorResponse;
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::audit_log::AuditLog;
+use rauthy_models::entity::role_mapping::RoleMappingRule;
 use rauthy_models::entity::roles::Role;
 
 /// Returns all existing roles
@@ -45,14 +47,16 @@ pub async fn get_roles(principal: ReqPrincipal) -> Result<HttpResponse, ErrorRes
 )]
 #[post("/roles")]
 pub async fn post_role(
+    req: HttpRequest,
     role_req: actix_web_validator::Json<NewRoleRequest>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Roles, AccessRights::Create)?;
 
-    Role::create(role_req.into_inner())
-        .await
-        .map(|r| HttpResponse::Ok().json(r))
+    let role = Role::create(role_req.into_inner()).await?;
+    AuditLog::created(&principal, &req, "role", &role.id, &role).await;
+
+    Ok(HttpResponse::Ok().json(role))
 }
 
 /// Modifies a roles name
@@ -72,15 +76,18 @@ pub async fn post_role(
 )]
 #[put("/roles/{id}")]
 pub async fn put_role(
+    req: HttpRequest,
     id: web::Path<String>,
     role_req: actix_web_validator::Json<NewRoleRequest>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Roles, AccessRights::Update)?;
 
-    Role::update(id.into_inner(), role_req.role.to_owned())
-        .await
-        .map(|r| HttpResponse::Ok().json(r))
+    let before = Role::find(&id).await?;
+    let role = Role::update(id.into_inner(), role_req.role.to_owned()).await?;
+    AuditLog::updated(&principal, &req, "role", &role.id, &before, &role).await;
+
+    Ok(HttpResponse::Ok().json(role))
 }
 
 /// Deletes a role
@@ -101,12 +108,150 @@ pub async fn put_role(
 )]
 #[delete("/roles/{id}")]
 pub async fn delete_role(
+    req: HttpRequest,
     id: web::Path<String>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Roles, AccessRights::Delete)?;
 
-    Role::delete(id.as_str())
-        .await
-        .map(|_| HttpResponse::Ok().finish())
+    let before = Role::find(&id).await?;
+    Role::delete(id.as_str()).await?;
+    AuditLog::deleted(&principal, &req, "role", &before.id, &before).await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns all role mapping rules
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/roles/mapping_rules",
+    tag = "roles",
+    responses(
+        (status = 200, description = "Ok", body = [RoleMappingRuleResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/roles/mapping_rules")]
+pub async fn get_role_mapping_rules(
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Roles, AccessRights::Read)?;
+
+    let rules = RoleMappingRule::find_all()
+        .await?
+        .into_iter()
+        .map(RoleMappingRuleResponse::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(rules))
+}
+
+/// Adds a new role mapping rule
+///
+/// Matching rules add their roles and groups when a user is created, and on each password or
+/// upstream provider login. They never remove any existing assignments.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/roles/mapping_rules",
+    tag = "roles",
+    request_body = RoleMappingRuleRequest,
+    responses(
+        (status = 200, description = "Ok", body = RoleMappingRuleResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/roles/mapping_rules")]
+pub async fn post_role_mapping_rule(
+    req: HttpRequest,
+    payload: actix_web_validator::Json<RoleMappingRuleRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Roles, AccessRights::Create)?;
+
+    let rule = RoleMappingRule::create(payload.into_inner()).await?;
+    AuditLog::created(&principal, &req, "role_mapping_rule", &rule.id, &rule).await;
+
+    Ok(HttpResponse::Ok().json(RoleMappingRuleResponse::from(rule)))
+}
+
+/// Modifies a role mapping rule
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/roles/mapping_rules/{id}",
+    tag = "roles",
+    request_body = RoleMappingRuleRequest,
+    responses(
+        (status = 200, description = "Ok", body = RoleMappingRuleResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/roles/mapping_rules/{id}")]
+pub async fn put_role_mapping_rule(
+    req: HttpRequest,
+    id: web::Path<String>,
+    payload: actix_web_validator::Json<RoleMappingRuleRequest>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Roles, AccessRights::Update)?;
+
+    let before = RoleMappingRule::find(&id).await?;
+    let rule = RoleMappingRule::update(id.into_inner(), payload.into_inner()).await?;
+    AuditLog::updated(
+        &principal,
+        &req,
+        "role_mapping_rule",
+        &rule.id,
+        &before,
+        &rule,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(RoleMappingRuleResponse::from(rule)))
+}
+
+/// Deletes a role mapping rule
+///
+/// Roles and groups, which have been assigned by this rule already, will not be removed.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/roles/mapping_rules/{id}",
+    tag = "roles",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/roles/mapping_rules/{id}")]
+pub async fn delete_role_mapping_rule(
+    req: HttpRequest,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Roles, AccessRights::Delete)?;
+
+    let before = RoleMappingRule::find(&id).await?;
+    RoleMappingRule::delete(&before.id).await?;
+    AuditLog::deleted(&principal, &req, "role_mapping_rule", &before.id, &before).await;
+
+    Ok(HttpResponse::Ok().finish())
 }
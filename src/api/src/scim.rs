@@ -0,0 +1,429 @@
+use crate::ReqPrincipal;
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse, ResponseError};
+use rauthy_api_types::scim::{
+    ScimErrorResponse, ScimGroup, ScimGroupListResponse, ScimListParams, ScimPatchRequest,
+    ScimUser, ScimUserListResponse, SCIM_SCHEMA_ERROR,
+};
+use rauthy_common::utils::real_ip_from_req;
+use rauthy_error::ErrorResponse;
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::audit_log::AuditLog;
+use rauthy_models::entity::groups::Group;
+use rauthy_models::entity::users::User;
+use rauthy_models::events::event::Event;
+use rauthy_service::scim;
+use serde::Serialize;
+use validator::Validate;
+
+const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+
+/// SCIM clients expect their own content type and error format, which is why all SCIM handlers
+/// convert their result with this function.
+fn scim_response<T: Serialize>(status: StatusCode, res: Result<T, ErrorResponse>) -> HttpResponse {
+    match res {
+        Ok(body) => HttpResponse::build(status)
+            .content_type(SCIM_CONTENT_TYPE)
+            .json(body),
+        Err(err) => {
+            let status = err.status_code();
+            let scim_type = err
+                .message
+                .split_once(':')
+                .map(|(typ, _)| typ)
+                .filter(|typ| matches!(*typ, "invalidFilter" | "invalidSyntax" | "invalidValue"))
+                .map(String::from);
+
+            HttpResponse::build(status)
+                .content_type(SCIM_CONTENT_TYPE)
+                .json(ScimErrorResponse {
+                    schemas: vec![SCIM_SCHEMA_ERROR.to_string()],
+                    status: status.as_u16().to_string(),
+                    scim_type,
+                    detail: err.message.to_string(),
+                })
+        }
+    }
+}
+
+/// SCIM: List and filter users
+///
+/// Only the `eq` operator is supported for filters on `id`, `userName` and `emails.value`.
+/// Users will always be matched by their E-Mail address, which is the `userName` in Rauthy.
+///
+/// **Permissions**
+/// - users:read
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Users",
+    tag = "scim",
+    params(ScimListParams),
+    responses(
+        (status = 200, description = "Ok", body = ScimUserListResponse),
+        (status = 400, description = "BadRequest", body = ScimErrorResponse),
+        (status = 401, description = "Unauthorized", body = ScimErrorResponse),
+        (status = 403, description = "Forbidden", body = ScimErrorResponse),
+    ),
+)]
+#[get("/scim/v2/Users")]
+pub async fn get_scim_users(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+    params: web::Query<ScimListParams>,
+) -> HttpResponse {
+    let res = async {
+        principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
+        params.validate()?;
+        scim::list_users(&params, &data.issuer).await
+    }
+    .await;
+
+    scim_response(StatusCode::OK, res)
+}
+
+/// SCIM: Provision a new user
+///
+/// The new user will receive an E-Mail to set a password, just like when created via the
+/// admin UI.
+///
+/// **Permissions**
+/// - users:create
+#[utoipa::path(
+    post,
+    path = "/scim/v2/Users",
+    tag = "scim",
+    request_body = ScimUser,
+    responses(
+        (status = 201, description = "Created", body = ScimUser),
+        (status = 400, description = "BadRequest", body = ScimErrorResponse),
+        (status = 401, description = "Unauthorized", body = ScimErrorResponse),
+        (status = 403, description = "Forbidden", body = ScimErrorResponse),
+    ),
+)]
+#[post("/scim/v2/Users")]
+pub async fn post_scim_user(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    payload: web::Json<ScimUser>,
+) -> HttpResponse {
+    let res = async {
+        principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Create)?;
+
+        let user = scim::create_user(&data, payload.into_inner()).await?;
+        let resp = user.clone().into_response(None);
+        AuditLog::created(&principal, &req, "user", &user.id, &resp).await;
+
+        data.tx_events
+            .send_async(
+                Event::new_user(user.email.clone(), real_ip_from_req(&req)?.to_string())
+                    .with_user_id(user.id.clone()),
+            )
+            .await
+            .unwrap();
+
+        Ok::<_, ErrorResponse>(scim::user_to_scim(user, &[], &data.issuer))
+    }
+    .await;
+
+    scim_response(StatusCode::CREATED, res)
+}
+
+/// SCIM: Get a single user
+///
+/// **Permissions**
+/// - users:read
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Users/{id}",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok", body = ScimUser),
+        (status = 401, description = "Unauthorized", body = ScimErrorResponse),
+        (status = 403, description = "Forbidden", body = ScimErrorResponse),
+        (status = 404, description = "NotFound", body = ScimErrorResponse),
+    ),
+)]
+#[get("/scim/v2/Users/{id}")]
+pub async fn get_scim_user(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+    id: web::Path<String>,
+) -> HttpResponse {
+    let res = async {
+        principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
+
+        let user = User::find(id.into_inner()).await?;
+        let groups = Group::find_all().await?;
+        Ok::<_, ErrorResponse>(scim::user_to_scim(user, &groups, &data.issuer))
+    }
+    .await;
+
+    scim_response(StatusCode::OK, res)
+}
+
+/// SCIM: Modify a user
+///
+/// Supports `active`, `userName`, `emails` and `name`. All other attributes will be ignored.
+/// Deactivating a user with `active: false` is the recommended way of deprovisioning.
+///
+/// **Permissions**
+/// - users:update
+#[utoipa::path(
+    patch,
+    path = "/scim/v2/Users/{id}",
+    tag = "scim",
+    request_body = ScimPatchRequest,
+    responses(
+        (status = 200, description = "Ok", body = ScimUser),
+        (status = 400, description = "BadRequest", body = ScimErrorResponse),
+        (status = 401, description = "Unauthorized", body = ScimErrorResponse),
+        (status = 403, description = "Forbidden", body = ScimErrorResponse),
+        (status = 404, description = "NotFound", body = ScimErrorResponse),
+    ),
+)]
+#[patch("/scim/v2/Users/{id}")]
+pub async fn patch_scim_user(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    id: web::Path<String>,
+    payload: web::Json<ScimPatchRequest>,
+) -> HttpResponse {
+    let res = async {
+        principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)?;
+
+        let id = id.into_inner();
+        let before = User::find(id.clone()).await?.into_response(None);
+        let user = scim::patch_user(&data, id, payload.into_inner()).await?;
+        let after = user.clone().into_response(None);
+        AuditLog::updated(&principal, &req, "user", &user.id, &before, &after).await;
+
+        let groups = Group::find_all().await?;
+        Ok::<_, ErrorResponse>(scim::user_to_scim(user, &groups, &data.issuer))
+    }
+    .await;
+
+    scim_response(StatusCode::OK, res)
+}
+
+/// SCIM: Delete a user
+///
+/// **Permissions**
+/// - users:delete
+#[utoipa::path(
+    delete,
+    path = "/scim/v2/Users/{id}",
+    tag = "scim",
+    responses(
+        (status = 204, description = "NoContent"),
+        (status = 401, description = "Unauthorized", body = ScimErrorResponse),
+        (status = 403, description = "Forbidden", body = ScimErrorResponse),
+        (status = 404, description = "NotFound", body = ScimErrorResponse),
+    ),
+)]
+#[delete("/scim/v2/Users/{id}")]
+pub async fn delete_scim_user(
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    id: web::Path<String>,
+) -> HttpResponse {
+    let res = async {
+        principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Delete)?;
+
+        let user = User::find(id.into_inner()).await?;
+        user.delete().await?;
+        let before = user.clone().into_response(None);
+        AuditLog::deleted(&principal, &req, "user", &user.id, &before).await;
+
+        Ok::<(), ErrorResponse>(())
+    }
+    .await;
+
+    match res {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(err) => scim_response::<()>(StatusCode::NO_CONTENT, Err(err)),
+    }
+}
+
+/// SCIM: List and filter groups
+///
+/// Only the `eq` operator is supported for filters on `id` and `displayName`.
+///
+/// **Permissions**
+/// - groups:read
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Groups",
+    tag = "scim",
+    params(ScimListParams),
+    responses(
+        (status = 200, description = "Ok", body = ScimGroupListResponse),
+        (status = 400, description = "BadRequest", body = ScimErrorResponse),
+        (status = 401, description = "Unauthorized", body = ScimErrorResponse),
+        (status = 403, description = "Forbidden", body = ScimErrorResponse),
+    ),
+)]
+#[get("/scim/v2/Groups")]
+pub async fn get_scim_groups(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+    params: web::Query<ScimListParams>,
+) -> HttpResponse {
+    let res = async {
+        principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Read)?;
+        params.validate()?;
+        scim::list_groups(&params, &data.issuer).await
+    }
+    .await;
+
+    scim_response(StatusCode::OK, res)
+}
+
+/// SCIM: Provision a new group
+///
+/// The `displayName` must be a valid Rauthy group name.
+///
+/// **Permissions**
+/// - groups:create
+#[utoipa::path(
+    post,
+    path = "/scim/v2/Groups",
+    tag = "scim",
+    request_body = ScimGroup,
+    responses(
+        (status = 201, description = "Created", body = ScimGroup),
+        (status = 400, description = "BadRequest", body = ScimErrorResponse),
+        (status = 401, description = "Unauthorized", body = ScimErrorResponse),
+        (status = 403, description = "Forbidden", body = ScimErrorResponse),
+    ),
+)]
+#[post("/scim/v2/Groups")]
+pub async fn post_scim_group(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    payload: web::Json<ScimGroup>,
+) -> HttpResponse {
+    let res = async {
+        principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Create)?;
+
+        let group = scim::create_group(payload.into_inner()).await?;
+        AuditLog::created(&principal, &req, "group", &group.id, &group).await;
+
+        scim::find_group(group.id, &data.issuer).await
+    }
+    .await;
+
+    scim_response(StatusCode::CREATED, res)
+}
+
+/// SCIM: Get a single group
+///
+/// **Permissions**
+/// - groups:read
+#[utoipa::path(
+    get,
+    path = "/scim/v2/Groups/{id}",
+    tag = "scim",
+    responses(
+        (status = 200, description = "Ok", body = ScimGroup),
+        (status = 401, description = "Unauthorized", body = ScimErrorResponse),
+        (status = 403, description = "Forbidden", body = ScimErrorResponse),
+        (status = 404, description = "NotFound", body = ScimErrorResponse),
+    ),
+)]
+#[get("/scim/v2/Groups/{id}")]
+pub async fn get_scim_group(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+    id: web::Path<String>,
+) -> HttpResponse {
+    let res = async {
+        principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Read)?;
+        scim::find_group(id.into_inner(), &data.issuer).await
+    }
+    .await;
+
+    scim_response(StatusCode::OK, res)
+}
+
+/// SCIM: Modify a group
+///
+/// Supports renaming via `displayName` and adding, replacing or removing `members`.
+///
+/// **Permissions**
+/// - groups:update
+#[utoipa::path(
+    patch,
+    path = "/scim/v2/Groups/{id}",
+    tag = "scim",
+    request_body = ScimPatchRequest,
+    responses(
+        (status = 200, description = "Ok", body = ScimGroup),
+        (status = 400, description = "BadRequest", body = ScimErrorResponse),
+        (status = 401, description = "Unauthorized", body = ScimErrorResponse),
+        (status = 403, description = "Forbidden", body = ScimErrorResponse),
+        (status = 404, description = "NotFound", body = ScimErrorResponse),
+    ),
+)]
+#[patch("/scim/v2/Groups/{id}")]
+pub async fn patch_scim_group(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    id: web::Path<String>,
+    payload: web::Json<ScimPatchRequest>,
+) -> HttpResponse {
+    let res = async {
+        principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Update)?;
+
+        let before = Group::find(id.into_inner()).await?;
+        let group = scim::patch_group(before.id.clone(), payload.into_inner()).await?;
+        AuditLog::updated(&principal, &req, "group", &group.id, &before, &group).await;
+
+        scim::find_group(group.id, &data.issuer).await
+    }
+    .await;
+
+    scim_response(StatusCode::OK, res)
+}
+
+/// SCIM: Delete a group
+///
+/// **Permissions**
+/// - groups:delete
+#[utoipa::path(
+    delete,
+    path = "/scim/v2/Groups/{id}",
+    tag = "scim",
+    responses(
+        (status = 204, description = "NoContent"),
+        (status = 401, description = "Unauthorized", body = ScimErrorResponse),
+        (status = 403, description = "Forbidden", body = ScimErrorResponse),
+        (status = 404, description = "NotFound", body = ScimErrorResponse),
+    ),
+)]
+#[delete("/scim/v2/Groups/{id}")]
+pub async fn delete_scim_group(
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    id: web::Path<String>,
+) -> HttpResponse {
+    let res = async {
+        principal.validate_api_key_or_admin_session(AccessGroup::Groups, AccessRights::Delete)?;
+
+        let before = Group::find(id.into_inner()).await?;
+        Group::delete(before.id.clone()).await?;
+        AuditLog::deleted(&principal, &req, "group", &before.id, &before).await;
+
+        Ok::<(), ErrorResponse>(())
+    }
+    .await;
+
+    match res {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(err) => scim_response::<()>(StatusCode::NO_CONTENT, Err(err)),
+    }
+}
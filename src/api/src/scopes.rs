@@ -1,9 +1,10 @@
 use crate::ReqPrincipal;
-use actix_web::{delete, get, post, put, web, HttpResponse};
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
 use rauthy_api_types::scopes::{ScopeRequest, ScopeResponse};
 use rauthy_error::ErrorResponse;
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::audit_log::AuditLog;
 use rauthy_models::entity::scopes::Scope;
 
 /// Returns all existing scopes
@@ -51,14 +52,16 @@ pub async fn get_scopes(principal: ReqPrincipal) -> Result<HttpResponse, ErrorRe
 #[post("/scopes")]
 pub async fn post_scope(
     data: web::Data<AppState>,
+    req: HttpRequest,
     principal: ReqPrincipal,
     scope_req: actix_web_validator::Json<ScopeRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Scopes, AccessRights::Create)?;
 
-    Scope::create(&data, scope_req.into_inner())
-        .await
-        .map(|s| HttpResponse::Ok().json(s))
+    let scope = Scope::create(&data, scope_req.into_inner()).await?;
+    AuditLog::created(&principal, &req, "scope", &scope.id, &scope).await;
+
+    Ok(HttpResponse::Ok().json(scope))
 }
 
 /// Modifies a scopes name
@@ -79,15 +82,18 @@ pub async fn post_scope(
 #[put("/scopes/{id}")]
 pub async fn put_scope(
     data: web::Data<AppState>,
+    req: HttpRequest,
     path: web::Path<String>,
     principal: ReqPrincipal,
     scope_req: actix_web_validator::Json<ScopeRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Scopes, AccessRights::Update)?;
 
-    Scope::update(&data, path.as_str(), scope_req.into_inner())
-        .await
-        .map(|s| HttpResponse::Ok().json(ScopeResponse::from(s)))
+    let before = Scope::find(path.as_str()).await?;
+    let scope = Scope::update(&data, path.as_str(), scope_req.into_inner()).await?;
+    AuditLog::updated(&principal, &req, "scope", &scope.id, &before, &scope).await;
+
+    Ok(HttpResponse::Ok().json(ScopeResponse::from(scope)))
 }
 
 /// Deletes a scope
@@ -109,12 +115,15 @@ pub async fn put_scope(
 #[delete("/scopes/{id}")]
 pub async fn delete_scope(
     data: web::Data<AppState>,
+    req: HttpRequest,
     path: web::Path<String>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Scopes, AccessRights::Delete)?;
 
-    Scope::delete(&data, path.as_str())
-        .await
-        .map(|_| HttpResponse::Ok().finish())
+    let before = Scope::find(path.as_str()).await?;
+    Scope::delete(&data, path.as_str()).await?;
+    AuditLog::deleted(&principal, &req, "scope", &before.id, &before).await;
+
+    Ok(HttpResponse::Ok().finish())
 }
@@ -1,27 +1,42 @@
 use crate::ReqPrincipal;
-use actix_web::{delete, get, web, HttpResponse};
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
 use actix_web_validator::Query;
 use rauthy_api_types::generic::PaginationParams;
-use rauthy_api_types::sessions::{SessionResponse, SessionState};
+use rauthy_api_types::sessions::{
+    SessionDeleteParams, SessionFilterParams, SessionPingResponse, SessionResponse, SessionState,
+};
+use rauthy_common::clock;
 use rauthy_common::constants::SSP_THRESHOLD;
-use rauthy_error::ErrorResponse;
+use rauthy_common::utils::real_ip_from_req;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::audit_log::{AuditAction, AuditLog};
 use rauthy_models::entity::continuation_token::ContinuationToken;
+use rauthy_models::entity::jti_denylist::JtiDenylist;
+use rauthy_models::entity::logout_token::BackchannelLogout;
+use rauthy_models::entity::opaque_tokens::OpaqueToken;
 use rauthy_models::entity::refresh_tokens::RefreshToken;
 use rauthy_models::entity::sessions::Session;
 use rauthy_models::entity::users::User;
+use rauthy_models::events::event::Event;
+use std::cmp::min;
+use tracing::error;
 
 /// Returns all existing sessions
 ///
 /// TODO update pagination usage description
 ///
+/// As soon as any of the `SessionFilterParams` is given, all matching sessions will be returned
+/// without pagination.
+///
 /// **Permissions**
 /// - rauthy_admin
 #[utoipa::path(
     get,
     path = "/sessions",
     tag = "sessions",
-    params(PaginationParams),
+    params(PaginationParams, SessionFilterParams),
     responses(
         (status = 200, description = "Ok", body = [SessionResponse]),
         (status = 401, description = "Unauthorized"),
@@ -32,9 +47,15 @@ use rauthy_models::entity::users::User;
 pub async fn get_sessions(
     principal: ReqPrincipal,
     params: Query<PaginationParams>,
+    filter: Query<SessionFilterParams>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Sessions, AccessRights::Read)?;
 
+    if filter.is_filter() {
+        let sessions = Session::find_filtered(&filter).await?;
+        return Ok(HttpResponse::Ok().json(sessions_response(&sessions)));
+    }
+
     // sessions will be dynamically paginated based on the same setting as users
     let user_count = User::count().await?;
     if user_count >= *SSP_THRESHOLD as i64 || params.page_size.is_some() {
@@ -69,30 +90,40 @@ pub async fn get_sessions(
         }
     } else {
         let sessions = Session::find_all().await?;
+        Ok(HttpResponse::Ok().json(sessions_response(&sessions)))
+    }
+}
 
-        let mut resp = Vec::with_capacity(sessions.len());
-        for s in &sessions {
-            resp.push(SessionResponse {
-                id: &s.id,
-                user_id: s.user_id.as_deref(),
-                is_mfa: s.is_mfa,
-                state: SessionState::from(
-                    s.state()
-                        .unwrap_or(rauthy_models::entity::sessions::SessionState::Unknown),
-                ),
-                exp: s.exp,
-                last_seen: s.last_seen,
-                remote_ip: s.remote_ip.as_deref(),
-            })
-        }
-
-        Ok(HttpResponse::Ok().json(resp))
+fn sessions_response(sessions: &[Session]) -> Vec<SessionResponse<'_>> {
+    let mut resp = Vec::with_capacity(sessions.len());
+    for s in sessions {
+        resp.push(SessionResponse {
+            id: &s.id,
+            user_id: s.user_id.as_deref(),
+            is_mfa: s.is_mfa,
+            state: SessionState::from(
+                s.state()
+                    .unwrap_or(rauthy_models::entity::sessions::SessionState::Unknown),
+            ),
+            exp: s.exp,
+            last_seen: s.last_seen,
+            remote_ip: s.remote_ip.as_deref(),
+            impersonator_id: s.impersonator_id.as_deref(),
+            remember_me: s.remember_me,
+            client_id: s.client_id.as_deref(),
+            auth_method: s.auth_method.as_deref(),
+        })
     }
+    resp
 }
 
 /// Invalidates all existing sessions and therefore logs out every single user.
 ///
+/// With `remember_me=true`, only the long-lived "remember me" sessions and their refresh tokens
+/// will be invalidated, while all others stay intact.
+///
 /// **Important:** Since JWT Tokens are stateless, it cannot invalidate already existing tokens.
+/// Opaque access tokens will be revoked though.
 ///
 /// **Permissions**
 /// - rauthy_admin
@@ -100,6 +131,7 @@ pub async fn get_sessions(
     delete,
     path = "/sessions",
     tag = "sessions",
+    params(SessionDeleteParams),
     responses(
         (status = 200, description = "Ok"),
         (status = 401, description = "Unauthorized"),
@@ -107,11 +139,36 @@ pub async fn get_sessions(
     ),
 )]
 #[delete("/sessions")]
-pub async fn delete_sessions(principal: ReqPrincipal) -> Result<HttpResponse, ErrorResponse> {
+pub async fn delete_sessions(
+    data: web::Data<AppState>,
+    params: Query<SessionDeleteParams>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Sessions, AccessRights::Delete)?;
 
+    if params.remember_me == Some(true) {
+        Session::invalidate_remember_me().await?;
+        AuditLog::action(
+            &principal,
+            &req,
+            AuditAction::Delete,
+            "sessions",
+            "remember_me",
+        )
+        .await;
+
+        send_session_revoked(&data, &req, "All 'remember me' sessions".to_string(), None).await;
+
+        return Ok(HttpResponse::Ok().finish());
+    }
+
     Session::invalidate_all().await?;
     RefreshToken::invalidate_all().await?;
+    OpaqueToken::invalidate_all().await?;
+    AuditLog::action(&principal, &req, AuditAction::Delete, "sessions", "all").await;
+
+    send_session_revoked(&data, &req, "All sessions".to_string(), None).await;
 
     Ok(HttpResponse::Ok().finish())
 }
@@ -119,6 +176,8 @@ pub async fn delete_sessions(principal: ReqPrincipal) -> Result<HttpResponse, Er
 /// Invalidates all existing sessions for the given `user_id`.
 ///
 ///**Important:** Since JWT Tokens are stateless, it cannot invalidate already existing tokens.
+/// Opaque access tokens will be revoked though. All clients inside these sessions with a
+/// `backchannel_logout_uri` receive a back-channel logout.
 ///
 /// **Permissions**
 /// - rauthy_admin
@@ -134,14 +193,154 @@ pub async fn delete_sessions(principal: ReqPrincipal) -> Result<HttpResponse, Er
 )]
 #[delete("/sessions/{user_id}")]
 pub async fn delete_sessions_for_user(
+    data: web::Data<AppState>,
     path: web::Path<String>,
     principal: ReqPrincipal,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Sessions, AccessRights::Delete)?;
 
     let uid = path.into_inner();
+    // must be resolved while the sessions still exist
+    let logouts = BackchannelLogout::find_for_user(&uid).await?;
     Session::invalidate_for_user(&uid).await?;
     RefreshToken::invalidate_for_user(&uid).await?;
+    OpaqueToken::invalidate_for_user(&uid).await?;
+    JtiDenylist::deny_for_user(&uid).await?;
+    AuditLog::action(&principal, &req, AuditAction::Delete, "sessions", &uid).await;
+    BackchannelLogout::send_all(data.issuer.clone(), logouts);
+
+    send_session_revoked(
+        &data,
+        &req,
+        format!("Sessions for user: {}", uid),
+        Some(uid),
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Invalidates a single session of the given `user_id`.
+///
+/// All refresh tokens linked to this session will be revoked and access tokens issued for it will
+/// be denied until they expire. All clients inside this session with a `backchannel_logout_uri`
+/// receive a back-channel logout.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/sessions/{user_id}/{session_id}",
+    tag = "sessions",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "NotFound"),
+    ),
+)]
+#[delete("/sessions/{user_id}/{session_id}")]
+pub async fn delete_session(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Sessions, AccessRights::Delete)?;
+
+    let (uid, sid) = path.into_inner();
+    let session = Session::find(sid.clone()).await?;
+    if session.user_id.as_deref() != Some(uid.as_str()) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::NotFound,
+            "Session does not exist for this user",
+        ));
+    }
+
+    let logouts = BackchannelLogout::find_for_session(&sid, &uid).await?;
+    RefreshToken::invalidate_for_session(&sid).await?;
+    session.invalidate().await?;
+    AuditLog::action(&principal, &req, AuditAction::Delete, "sessions", &sid).await;
+    BackchannelLogout::send_all(data.issuer.clone(), logouts);
+
+    send_session_revoked(
+        &data,
+        &req,
+        format!("Session {} for user: {}", sid, uid),
+        Some(uid),
+    )
+    .await;
 
     Ok(HttpResponse::Ok().finish())
 }
+
+/// Must only be called after the sessions have been revoked. The revocation has already
+/// happened at this point, so a failing notification must not turn the request into an error.
+async fn send_session_revoked(
+    data: &AppState,
+    req: &HttpRequest,
+    text: String,
+    user_id: Option<String>,
+) {
+    let ip = match real_ip_from_req(req) {
+        Ok(ip) => ip.to_string(),
+        Err(err) => {
+            error!(
+                "Cannot extract the IP for the SessionRevoked event: {}",
+                err.message
+            );
+            String::default()
+        }
+    };
+
+    let mut event = Event::session_revoked(text, ip);
+    if let Some(user_id) = user_id {
+        event = event.with_user_id(user_id);
+    }
+    // errors are logged inside `send()` already
+    let _ = event.send(&data.tx_events).await;
+}
+
+/// Session keep-alive
+///
+/// Renews the idle timeout of the current session and returns its remaining lifetime. This can be
+/// used by SPAs to show a "your session expires soon - stay signed in?" prompt, which is backed by
+/// the real server state. The absolute session lifetime (`exp`) can never be extended.
+///
+/// **Permissions**
+/// - authenticated session
+#[utoipa::path(
+    post,
+    path = "/session/ping",
+    tag = "sessions",
+    responses(
+        (status = 200, description = "Ok", body = SessionPingResponse),
+        (status = 401, description = "Unauthorized"),
+    ),
+)]
+#[post("/session/ping")]
+pub async fn post_session_ping(
+    data: web::Data<AppState>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_session_auth()?;
+    let mut session = principal.get_session()?.clone();
+
+    // The principal middleware only updates `last_seen` if it is older than 10 seconds.
+    // The ping should always reflect the exact renewed timeout.
+    let now = clock::now().timestamp();
+    if session.last_seen < now {
+        session.last_seen = now;
+        session.save().await?;
+    }
+
+    let timeout = session.timeout(data.session_timeout);
+    let remaining = min(session.exp, timeout) - now;
+
+    Ok(HttpResponse::Ok().json(SessionPingResponse {
+        exp: session.exp,
+        timeout,
+        remaining: remaining.max(0),
+    }))
+}
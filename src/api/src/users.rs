@@ -1,45 +1,73 @@
-use crate::ReqPrincipal;
-use actix_web::http::header::{ACCEPT, LOCATION};
+use crate::{add_session_cookie, add_trusted_device_cookie, ReqPrincipal};
+use actix_web::http::header::{ACCEPT, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, LOCATION};
 use actix_web::http::StatusCode;
 use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, ResponseError};
+use actix_web_lab::__reexports::futures_util::StreamExt;
 use actix_web_validator::{Json, Query};
 use rauthy_api_types::generic::{PaginationParams, PasswordPolicyResponse};
 use rauthy_api_types::oidc::PasswordResetResponse;
 use rauthy_api_types::users::{
-    DeviceRequest, DeviceResponse, MfaPurpose, NewUserRegistrationRequest, NewUserRequest,
-    PasskeyResponse, PasswordResetRequest, RequestResetRequest, UpdateUserRequest,
-    UpdateUserSelfRequest, UserAttrConfigRequest, UserAttrConfigResponse, UserAttrValueResponse,
-    UserAttrValuesResponse, UserAttrValuesUpdateRequest, UserResponse, WebIdRequest, WebIdResponse,
-    WebauthnAuthFinishRequest, WebauthnAuthStartRequest, WebauthnAuthStartResponse,
-    WebauthnRegFinishRequest, WebauthnRegStartRequest,
+    ClientUserMetadataRequest, ClientUserMetadataResponse, DeviceRequest, DeviceResponse,
+    EmailOtpRequest, EmailOtpStatusResponse, LoginHistoryParams, LoginHistoryResponse, MfaPurpose,
+    MfaRecoveryCodesResponse, MfaRecoveryCodesStatusResponse, NewUserRegistrationRequest,
+    NewUserRequest, PasskeyResponse, PasswordResetRequest, RequestResetRequest,
+    SessionDeviceResponse, SmsOtpConfirmRequest, SmsOtpEnrollRequest, SmsOtpStatusResponse,
+    TotpConfirmRequest, TotpEnrollResponse, TotpStatusResponse, TrustedDeviceResponse,
+    UpdateUserRequest, UpdateUserSelfRequest, UserActivityParams, UserActivityResponse,
+    UserAttrConfigRequest, UserAttrConfigResponse, UserAttrValueResponse, UserAttrValuesResponse,
+    UserAttrValuesUpdateRequest, UserConsentResponse, UserExportParams, UserExportResponse,
+    UserImportResponse, UserImportRow, UserImportRowResult, UserResponse, UsersSearchParams,
+    WebIdRequest, WebIdResponse, WebauthnAuthFinishRequest, WebauthnAuthStartRequest,
+    WebauthnAuthStartResponse, WebauthnRegFinishRequest, WebauthnRegStartRequest,
+    WebauthnRenameRequest, YubicoOtpRequest, YubicoOtpStatusResponse,
 };
 use rauthy_common::constants::{
-    COOKIE_MFA, ENABLE_WEB_ID, HEADER_ALLOW_ALL_ORIGINS, HEADER_HTML, HEADER_JSON, OPEN_USER_REG,
-    PWD_CSRF_HEADER, PWD_RESET_COOKIE, SSP_THRESHOLD, TEXT_TURTLE, USER_REG_DOMAIN_BLACKLIST,
-    USER_REG_DOMAIN_RESTRICTION, USER_REG_OPEN_REDIRECT,
+    EmailOtpMode, COOKIE_MFA, ENABLE_WEB_ID, HEADER_ALLOW_ALL_ORIGINS, HEADER_HTML, HEADER_JSON,
+    MFA_EMAIL_OTP, OPEN_USER_REG, PWD_CSRF_HEADER, PWD_RESET_COOKIE, SESSION_LIFETIME,
+    SSP_THRESHOLD, TEXT_TURTLE, USER_REG_DOMAIN_BLACKLIST, USER_REG_DOMAIN_RESTRICTION,
+    USER_REG_OPEN_REDIRECT,
 };
 use rauthy_common::utils::real_ip_from_req;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::api_cookie::ApiCookie;
 use rauthy_models::app_state::AppState;
+use rauthy_models::email::send_user_export;
 use rauthy_models::entity::api_keys::{AccessGroup, AccessRights};
+use rauthy_models::entity::audit_log::{AuditAction, AuditLog};
 use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::clients_user_metadata::ClientUserMetadata;
 use rauthy_models::entity::colors::ColorEntity;
+use rauthy_models::entity::consents::UserConsent;
 use rauthy_models::entity::continuation_token::ContinuationToken;
 use rauthy_models::entity::devices::DeviceEntity;
+use rauthy_models::entity::email_otp::UserEmailOtp;
+use rauthy_models::entity::fido_mds::FidoMds;
+use rauthy_models::entity::login_history::LoginHistory;
 use rauthy_models::entity::password::PasswordPolicy;
 use rauthy_models::entity::pow::PowEntity;
+use rauthy_models::entity::recovery_codes::MfaRecoveryCode;
+use rauthy_models::entity::refresh_tokens::RefreshToken;
+use rauthy_models::entity::session_devices::SessionDevice;
+use rauthy_models::entity::sessions::{Session, SessionState};
+use rauthy_models::entity::sms_otp::UserSmsOtp;
+use rauthy_models::entity::tos::TosAcceptance;
+use rauthy_models::entity::totp::UserTotp;
+use rauthy_models::entity::trusted_devices::TrustedDevice;
 use rauthy_models::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use rauthy_models::entity::user_pictures::UserPicture;
 use rauthy_models::entity::users::User;
 use rauthy_models::entity::users_values::UserValues;
 use rauthy_models::entity::webauthn;
 use rauthy_models::entity::webauthn::{PasskeyEntity, WebauthnAdditionalData};
 use rauthy_models::entity::webids::WebId;
+use rauthy_models::entity::yubico_otp::UserYubicoOtp;
 use rauthy_models::events::event::Event;
 use rauthy_models::language::Language;
+use rauthy_models::sms::Sms;
 use rauthy_models::templates::{Error1Html, Error3Html, ErrorHtml, UserRegisterHtml};
-use rauthy_service::password_reset;
+use rauthy_service::{password_reset, user_export, user_import};
 use spow::pow::Pow;
+use std::collections::BTreeMap;
 use tracing::{error, warn};
 
 /// Returns all existing users
@@ -49,13 +77,17 @@ use tracing::{error, warn};
 /// If the response contains all existing users, the status code will be an HTTP 200.
 /// If the backend is in server side pagination mode, it will return an HTTP 206.
 ///
+/// As soon as any of the `UsersSearchParams` is given, the users will be searched, filtered and
+/// sorted inside the database instead. The response will contain an `x-next-cursor` header in
+/// this case, if there are more results, which can be given as the `cursor` for the next page.
+///
 /// **Permissions**
 /// - rauthy_admin
 #[utoipa::path(
     get,
     path = "/users",
     tag = "users",
-    params(PaginationParams),
+    params(PaginationParams, UsersSearchParams),
     responses(
         (status = 200, description = "Ok", body = [UserResponse]),
         (status = 206, description = "PartialContent", body = [UserResponse]),
@@ -67,9 +99,19 @@ use tracing::{error, warn};
 pub async fn get_users(
     principal: ReqPrincipal,
     params: Query<PaginationParams>,
+    search: Query<UsersSearchParams>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)?;
 
+    if search.is_search() {
+        let (users, next_cursor) = User::search_filtered(&search).await?;
+        let mut resp = HttpResponse::Ok();
+        if let Some(cursor) = next_cursor {
+            resp.insert_header(("x-next-cursor", cursor));
+        }
+        return Ok(resp.json(users));
+    }
+
     let user_count = User::count().await?;
 
     if user_count >= *SSP_THRESHOLD as i64 || params.page_size.is_some() {
@@ -133,25 +175,121 @@ pub async fn post_users(
     principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Create)?;
 
     let user = User::create_from_new(&data, user.into_inner()).await?;
+    let resp = user.clone().into_response(None);
+    AuditLog::created(&principal, &req, "user", &user.id, &resp).await;
 
     data.tx_events
-        .send_async(Event::new_user(
-            user.email.clone(),
-            real_ip_from_req(&req)?.to_string(),
-        ))
+        .send_async(
+            Event::new_user(user.email.clone(), real_ip_from_req(&req)?.to_string())
+                .with_user_id(user.id.clone()),
+        )
         .await
         .unwrap();
     if user.is_admin() {
         data.tx_events
-            .send_async(Event::new_rauthy_admin(
-                user.email.clone(),
-                real_ip_from_req(&req)?.to_string(),
-            ))
+            .send_async(
+                Event::new_rauthy_admin(user.email.clone(), real_ip_from_req(&req)?.to_string())
+                    .with_user_id(user.id.clone()),
+            )
             .await
             .unwrap();
     }
 
-    Ok(HttpResponse::Ok().json(user.into_response(None)))
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Imports multiple users at once
+///
+/// Accepts either a JSON array of `UserImportRow` or a CSV, if the `content-type` is `text/csv`.
+/// Each row is validated and created on its own, which means a bad row will not abort the whole
+/// import. The response contains the result for each single row.
+///
+/// A single import must not contain more than 1000 users.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/import",
+    tag = "users",
+    request_body(content = [UserImportRow], description = "JSON array or CSV with a header row"),
+    responses(
+        (status = 200, description = "Ok", body = UserImportResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/import")]
+pub async fn post_users_import(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    principal: ReqPrincipal,
+    body: web::Bytes,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Create)?;
+
+    let is_csv = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("text/csv"))
+        .unwrap_or(false);
+    let rows = user_import::parse_rows(&body, is_csv)?;
+    let ip = real_ip_from_req(&req)?.to_string();
+
+    let mut results = Vec::with_capacity(rows.len());
+    let mut created = 0;
+    for (idx, row) in rows.into_iter().enumerate() {
+        let row_nr = idx + 1;
+        let row = match row {
+            Ok(row) => row,
+            Err(err) => {
+                results.push(UserImportRowResult {
+                    row: row_nr,
+                    email: None,
+                    user_id: None,
+                    error: Some(err),
+                });
+                continue;
+            }
+        };
+
+        let email = row.email.clone();
+        match user_import::import_row(&data, row).await {
+            Ok(user) => {
+                let resp = user.clone().into_response(None);
+                AuditLog::created(&principal, &req, "user", &user.id, &resp).await;
+                data.tx_events
+                    .send_async(
+                        Event::new_user(user.email.clone(), ip.clone())
+                            .with_user_id(user.id.clone()),
+                    )
+                    .await
+                    .unwrap();
+
+                created += 1;
+                results.push(UserImportRowResult {
+                    row: row_nr,
+                    email: Some(user.email),
+                    user_id: Some(user.id),
+                    error: None,
+                });
+            }
+            Err(err) => results.push(UserImportRowResult {
+                row: row_nr,
+                email: Some(email),
+                user_id: None,
+                error: Some(err.message.to_string()),
+            }),
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(UserImportResponse {
+        created,
+        failed: results.len() - created,
+        rows: results,
+    }))
 }
 
 /// Get the configured / allowed additional custom user attribute
@@ -189,14 +327,16 @@ pub async fn get_cust_attr(principal: ReqPrincipal) -> Result<HttpResponse, Erro
 #[post("/users/attr")]
 pub async fn post_cust_attr(
     principal: ReqPrincipal,
+    req: HttpRequest,
     req_data: Json<UserAttrConfigRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal
         .validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Create)?;
 
-    UserAttrConfigEntity::create(req_data.into_inner())
-        .await
-        .map(|attr| HttpResponse::Ok().json(attr))
+    let attr = UserAttrConfigEntity::create(req_data.into_inner()).await?;
+    AuditLog::created(&principal, &req, "user_attr", &attr.name, &attr).await;
+
+    Ok(HttpResponse::Ok().json(attr))
 }
 
 /// Update an additional custom user attribute
@@ -216,14 +356,17 @@ pub async fn post_cust_attr(
 pub async fn put_cust_attr(
     path: web::Path<String>,
     principal: ReqPrincipal,
+    req: HttpRequest,
     req_data: Json<UserAttrConfigRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal
         .validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Update)?;
 
-    UserAttrConfigEntity::update(path.into_inner(), req_data.into_inner())
-        .await
-        .map(|a| HttpResponse::Ok().json(a))
+    let before = UserAttrConfigEntity::find(path.to_string()).await?;
+    let attr = UserAttrConfigEntity::update(path.into_inner(), req_data.into_inner()).await?;
+    AuditLog::updated(&principal, &req, "user_attr", &attr.name, &before, &attr).await;
+
+    Ok(HttpResponse::Ok().json(attr))
 }
 
 /// Delete an additional custom user attribute
@@ -241,11 +384,15 @@ pub async fn put_cust_attr(
 pub async fn delete_cust_attr(
     path: web::Path<String>,
     principal: ReqPrincipal,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal
         .validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Delete)?;
 
+    let before = UserAttrConfigEntity::find(path.to_string()).await?;
     UserAttrConfigEntity::delete(path.into_inner()).await?;
+    AuditLog::deleted(&principal, &req, "user_attr", &before.name, &before).await;
+
     Ok(HttpResponse::Ok().finish())
 }
 
@@ -328,7 +475,9 @@ pub async fn post_users_register(
         }
     }
     if let Some(redirect_uri) = &req_data.redirect_uri {
-        if !*USER_REG_OPEN_REDIRECT {
+        // a registration started with `prompt=create` leads back to our own authorize endpoint,
+        // which is only allowed, if the authorization request itself leads to a valid client
+        if !*USER_REG_OPEN_REDIRECT && !Client::is_authorize_uri(&data.issuer, redirect_uri).await {
             let mut allow = false;
             for uri in Client::find_all_client_uris().await? {
                 if uri.starts_with(redirect_uri) {
@@ -353,10 +502,9 @@ pub async fn post_users_register(
     let user = User::create_from_reg(&data, req_data.into_inner(), lang).await?;
 
     data.tx_events
-        .send_async(Event::new_user(
-            user.email,
-            real_ip_from_req(&req)?.to_string(),
-        ))
+        .send_async(
+            Event::new_user(user.email, real_ip_from_req(&req)?.to_string()).with_user_id(user.id),
+        )
         .await
         .unwrap();
 
@@ -397,315 +545,2042 @@ pub async fn get_user_by_id(
     Ok(HttpResponse::Ok().json(user.into_response(values)))
 }
 
-/// Returns the additional custom attributes for the given user id
+/// Returns the recent security activity for a user
+///
+/// Contains a subset of the events concerning this user, like password and E-Mail changes, added
+/// or removed passkeys, failed MFA requests and revoked sessions, newest first.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - the user itself
 #[utoipa::path(
     get,
-    path = "/users/{id}/attr",
+    path = "/users/{id}/activity",
     tag = "users",
+    params(UserActivityParams),
     responses(
-        (status = 200, description = "Ok", body = UserAttrValuesResponse),
+        (status = 200, description = "Ok", body = [UserActivityResponse]),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
-#[get("/users/{id}/attr")]
-pub async fn get_user_attr(
+#[get("/users/{id}/activity")]
+pub async fn get_user_activity(
     path: web::Path<String>,
     principal: ReqPrincipal,
+    params: Query<UserActivityParams>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    principal.validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Read)?;
+    let id = path.into_inner();
 
-    let values = UserAttrValueEntity::find_for_user(&path.into_inner())
+    // principal must either be an admin or have the same user id
+    let api_key_or_admin = principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)
+        .is_ok();
+    if !api_key_or_admin {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    let limit = params.limit.unwrap_or(20) as i64;
+    let activity = Event::find_user_activity(&id, limit)
         .await?
-        .drain(..)
-        .map(UserAttrValueResponse::from)
-        .collect::<Vec<UserAttrValueResponse>>();
+        .into_iter()
+        .map(UserActivityResponse::from)
+        .collect::<Vec<_>>();
 
-    Ok(HttpResponse::Ok().json(UserAttrValuesResponse { values }))
+    Ok(HttpResponse::Ok().json(activity))
 }
 
-/// Updates the additional custom attributes for the given user id
+/// Exports all data about a user
+///
+/// Returns a machine-readable JSON archive of all data Rauthy holds about the user, like the
+/// profile, custom attributes, sessions, devices, passkeys, the login history and events. This
+/// can be used to answer data subject access requests. With `email=true`, the export will be sent
+/// to the users E-Mail address in addition.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - the user itself
 #[utoipa::path(
-    put,
-    path = "/users/{id}/attr",
+    get,
+    path = "/users/{id}/export",
     tag = "users",
-    request_body = UserAttrValuesUpdateRequest,
+    params(UserExportParams),
     responses(
-        (status = 204, description = "NoContent"),
+        (status = 200, description = "Ok", body = UserExportResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
     ),
 )]
-#[put("/users/{id}/attr")]
-pub async fn put_user_attr(
+#[get("/users/{id}/export")]
+pub async fn get_user_export(
+    data: web::Data<AppState>,
     path: web::Path<String>,
     principal: ReqPrincipal,
-    req_data: Json<UserAttrValuesUpdateRequest>,
+    params: Query<UserExportParams>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    principal
-        .validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Update)?;
+    let id = path.into_inner();
 
-    let values = UserAttrValueEntity::update_for_user(&path.into_inner(), req_data.into_inner())
-        .await?
-        .drain(..)
-        .map(UserAttrValueResponse::from)
-        .collect::<Vec<UserAttrValueResponse>>();
-    Ok(HttpResponse::Ok().json(UserAttrValuesResponse { values }))
+    // principal must either be an admin or have the same user id
+    let api_key_or_admin = principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)
+        .is_ok();
+    if !api_key_or_admin {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    let user = User::find(id).await?;
+    let export = user_export::build(user.clone()).await?;
+
+    if params.email == Some(true) {
+        send_user_export(&data, &user, export.clone()).await;
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header((
+            CONTENT_DISPOSITION,
+            format!("attachment; filename=\"rauthy_export_{}.json\"", user.id),
+        ))
+        .insert_header(HEADER_JSON)
+        .body(export))
 }
 
-/// GET all devices for this user linked via the `device_code` flow
+/// Returns the login history for a user
+///
+/// Contains successful and failed password and passkey logins with the IP, user agent and client,
+/// newest first. Entries are kept for `LOGIN_HISTORY_RETENTION_DAYS`.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - the user itself
 #[utoipa::path(
     get,
-    path = "/users/{id}/devices",
+    path = "/users/{id}/login_history",
     tag = "users",
+    params(LoginHistoryParams),
     responses(
-        (status = 200, description = "Ok", body = [DeviceResponse]),
+        (status = 200, description = "Ok", body = [LoginHistoryResponse]),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
-#[get("/users/{id}/devices")]
-pub async fn get_user_devices(
+#[get("/users/{id}/login_history")]
+pub async fn get_user_login_history(
     path: web::Path<String>,
     principal: ReqPrincipal,
+    params: Query<LoginHistoryParams>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    let user_id = path.into_inner();
-    principal.validate_user_or_admin(&user_id)?;
+    let id = path.into_inner();
 
-    let resp = DeviceEntity::find_for_user(&user_id)
+    // principal must either be an admin or have the same user id
+    let api_key_or_admin = principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)
+        .is_ok();
+    if !api_key_or_admin {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    let limit = params.limit.unwrap_or(20);
+    let history = LoginHistory::find_for_user(&id, limit)
         .await?
         .into_iter()
-        .map(DeviceResponse::from)
-        .collect::<Vec<DeviceResponse>>();
+        .map(LoginHistoryResponse::from)
+        .collect::<Vec<_>>();
 
-    Ok(HttpResponse::Ok().json(resp))
+    Ok(HttpResponse::Ok().json(history))
 }
 
-/// GET all devices for this user linked via the `device_code` flow
+/// Returns the effective password policy for a user
+///
+/// This is the global password policy, unless the user is a member of any groups with their own
+/// password policy.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - the user itself
 #[utoipa::path(
-    put,
-    path = "/users/{id}/devices",
+    get,
+    path = "/users/{id}/password_policy",
     tag = "users",
     responses(
-        (status = 200, description = "Ok"),
+        (status = 200, description = "Ok", body = PasswordPolicyResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
     ),
 )]
-#[put("/users/{id}/devices")]
-pub async fn put_user_device_name(
+#[get("/users/{id}/password_policy")]
+pub async fn get_user_password_policy(
     path: web::Path<String>,
     principal: ReqPrincipal,
-    payload: actix_web_validator::Json<DeviceRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    let user_id = path.into_inner();
-    principal.validate_user_or_admin(&user_id)?;
+    let id = path.into_inner();
 
-    let payload = payload.into_inner();
-    if let Some(name) = &payload.name {
-        DeviceEntity::update_name(&payload.device_id, &user_id, name).await?;
+    // principal must either be an admin or have the same user id
+    let api_key_or_admin = principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)
+        .is_ok();
+    if !api_key_or_admin {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
     }
 
-    Ok(HttpResponse::Ok().finish())
+    let user = User::find(id).await?;
+    let policy = PasswordPolicy::find_for_user(&user).await?;
+
+    Ok(HttpResponse::Ok().json(PasswordPolicyResponse::from(policy)))
 }
 
-/// DELETE all existing refresh tokens for a user device
+/// Returns the picture for the given user id
 ///
-/// This does NOT revoke already existing access tokens, since they are stateless!
+/// This endpoint is public, because the URL is handed out via the `picture` claim.
+/// The `v` query param is set to the time of the last upload, which is why the picture can be
+/// cached for a long time.
 #[utoipa::path(
-    delete,
-    path = "/users/{id}/devices",
+    get,
+    path = "/users/{id}/picture",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/picture")]
+pub async fn get_user_picture(path: web::Path<String>) -> Result<HttpResponse, ErrorResponse> {
+    let picture = UserPicture::find(&path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, picture.content_type))
+        .insert_header((CACHE_CONTROL, "max-age=2592000"))
+        .body(picture.data))
+}
+
+/// Upload a picture for the given user
+///
+/// Accepts JPG, PNG or WebP. The picture will be resized to 256x256 and saved as WebP.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - the user itself
+#[utoipa::path(
+    put,
+    path = "/users/{id}/picture",
     tag = "users",
     responses(
         (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
     ),
 )]
-#[delete("/users/{id}/devices")]
-pub async fn delete_user_device(
+#[put("/users/{id}/picture")]
+pub async fn put_user_picture(
     path: web::Path<String>,
     principal: ReqPrincipal,
-    payload: actix_web_validator::Json<DeviceRequest>,
+    req: HttpRequest,
+    mut payload: actix_multipart::Multipart,
 ) -> Result<HttpResponse, ErrorResponse> {
-    let user_id = path.into_inner();
-    principal.validate_user_or_admin(&user_id)?;
+    principal.validate_not_impersonated()?;
 
-    let payload = payload.into_inner();
-    let device = DeviceEntity::find(&payload.device_id).await?;
-    if device.user_id.as_deref() != Some(&user_id) {
+    let id = path.into_inner();
+
+    // principal must either be an admin or have the same user id
+    let api_key_or_admin = principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)
+        .is_ok();
+    if !api_key_or_admin {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    // make sure we return a 404 instead of a foreign key error
+    let user = User::find(id).await?;
+
+    // we only accept a single field from the Multipart upload -> no looping here
+    let mut buf: Vec<u8> = Vec::with_capacity(128 * 1024);
+    let mut content_type = None;
+    if let Some(part) = payload.next().await {
+        let mut field = part?;
+
+        match field.content_type() {
+            Some(mime) => content_type = Some(mime.clone()),
+            None => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "content_type is missing",
+                ));
+            }
+        }
+
+        while let Some(chunk) = field.next().await {
+            let bytes = chunk?;
+            buf.extend(bytes);
+        }
+    }
+    let Some(content_type) = content_type else {
         return Err(ErrorResponse::new(
-            ErrorResponseType::Forbidden,
-            "You don't have access to this device".to_string(),
+            ErrorResponseType::BadRequest,
+            "no picture given",
         ));
-    }
+    };
 
-    DeviceEntity::revoke_refresh_tokens(&payload.device_id).await?;
+    UserPicture::upsert(user.id.clone(), buf, content_type).await?;
+    AuditLog::action(
+        &principal,
+        &req,
+        AuditAction::Update,
+        "user_picture",
+        &user.id,
+    )
+    .await;
 
     Ok(HttpResponse::Ok().finish())
 }
 
-/// Endpoint for resetting passwords
+/// Deletes the picture for the given user
 ///
-/// The `id` is the user id and `reset_id` is a random 64 character long string sent via E-Mail for a
-/// pre-authenticated request.
+/// **Permissions**
+/// - rauthy_admin
+/// - the user itself
 #[utoipa::path(
-    get,
-    path = "/users/{id}/email_confirm/{confirm_id}",
+    delete,
+    path = "/users/{id}/picture",
     tag = "users",
     responses(
         (status = 200, description = "Ok"),
-        (status = 404, description = "NotFound", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
-#[get("/users/{id}/email_confirm/{confirm_id}")]
-pub async fn get_user_email_confirm(
-    data: web::Data<AppState>,
-    path: web::Path<(String, String)>,
+#[delete("/users/{id}/picture")]
+pub async fn delete_user_picture(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
     req: HttpRequest,
-) -> HttpResponse {
-    let lang = Language::try_from(&req).unwrap_or_default();
-    let (user_id, confirm_id) = path.into_inner();
-    match User::confirm_email_address(&data, req, user_id, confirm_id).await {
-        Ok(html) => HttpResponse::Ok().insert_header(HEADER_HTML).body(html),
-        Err(err) => {
-            let colors = ColorEntity::find_rauthy().await.unwrap_or_default();
-            let status = err.status_code();
-            let body = Error3Html::build(&colors, &lang, status, Some(err.message));
-            ErrorHtml::response(body, status)
-        }
-    }
-}
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
 
-/// Endpoint for resetting passwords
-///
-/// The `id` is the user id and `reset_id` is a random 64 character long string sent via E-Mail for a
+    let id = path.into_inner();
+
+    // principal must either be an admin or have the same user id
+    let api_key_or_admin = principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)
+        .is_ok();
+    if !api_key_or_admin {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    UserPicture::delete(&id).await?;
+    AuditLog::action(&principal, &req, AuditAction::Delete, "user_picture", &id).await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns the additional custom attributes for the given user id
+#[utoipa::path(
+    get,
+    path = "/users/{id}/attr",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserAttrValuesResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/attr")]
+pub async fn get_user_attr(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Read)?;
+
+    let values = UserAttrValueEntity::find_for_user(&path.into_inner())
+        .await?
+        .drain(..)
+        .map(UserAttrValueResponse::from)
+        .collect::<Vec<UserAttrValueResponse>>();
+
+    Ok(HttpResponse::Ok().json(UserAttrValuesResponse { values }))
+}
+
+/// Updates the additional custom attributes for the given user id
+#[utoipa::path(
+    put,
+    path = "/users/{id}/attr",
+    tag = "users",
+    request_body = UserAttrValuesUpdateRequest,
+    responses(
+        (status = 204, description = "NoContent"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+    ),
+)]
+#[put("/users/{id}/attr")]
+pub async fn put_user_attr(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    req_data: Json<UserAttrValuesUpdateRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Update)?;
+
+    let user_id = path.into_inner();
+    let before = UserAttrValueEntity::find_for_user(&user_id)
+        .await?
+        .into_iter()
+        .map(UserAttrValueResponse::from)
+        .map(|v| (v.key, v.value))
+        .collect::<BTreeMap<_, _>>();
+    let values = UserAttrValueEntity::update_for_user(&user_id, req_data.into_inner())
+        .await?
+        .drain(..)
+        .map(UserAttrValueResponse::from)
+        .collect::<Vec<UserAttrValueResponse>>();
+    let after = values
+        .iter()
+        .map(|v| (v.key.clone(), v.value.clone()))
+        .collect::<BTreeMap<_, _>>();
+    AuditLog::updated(
+        &principal,
+        &req,
+        "user_attr_values",
+        &user_id,
+        &before,
+        &after,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(UserAttrValuesResponse { values }))
+}
+
+/// Returns the client specific metadata for the given user id
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/client_metadata",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [ClientUserMetadataResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/client_metadata")]
+pub async fn get_user_client_metadata(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Read)?;
+
+    let metadata = ClientUserMetadata::find_for_user(&path.into_inner())
+        .await?
+        .into_iter()
+        .map(ClientUserMetadataResponse::from)
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(metadata))
+}
+
+/// Sets the client specific metadata for the given user id
+///
+/// The `data` will be added as the `client_metadata` claim to all tokens and the userinfo for this
+/// client, and only for this client. An empty `data` removes the metadata.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    put,
+    path = "/users/{id}/client_metadata/{client_id}",
+    tag = "users",
+    request_body = ClientUserMetadataRequest,
+    responses(
+        (status = 200, description = "Ok", body = ClientUserMetadataResponse),
+        (status = 204, description = "NoContent"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/users/{id}/client_metadata/{client_id}")]
+pub async fn put_user_client_metadata(
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    payload: Json<ClientUserMetadataRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Update)?;
+
+    let (user_id, client_id) = path.into_inner();
+    User::exists(user_id.clone()).await?;
+    let client = Client::find(client_id).await?;
+
+    let before = ClientUserMetadata::find(&client.id, &user_id)
+        .await?
+        .map(|m| m.data_map());
+    let metadata =
+        ClientUserMetadata::upsert(client.id, user_id.clone(), payload.into_inner().data).await?;
+    let after = metadata.as_ref().map(|m| m.data_map());
+    AuditLog::updated(
+        &principal,
+        &req,
+        "client_user_metadata",
+        &user_id,
+        &before,
+        &after,
+    )
+    .await;
+
+    match metadata {
+        None => Ok(HttpResponse::NoContent().finish()),
+        Some(m) => Ok(HttpResponse::Ok().json(ClientUserMetadataResponse::from(m))),
+    }
+}
+
+/// Deletes the client specific metadata for the given user id
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/client_metadata/{client_id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/client_metadata/{client_id}")]
+pub async fn delete_user_client_metadata(
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal
+        .validate_api_key_or_admin_session(AccessGroup::UserAttributes, AccessRights::Delete)?;
+
+    let (user_id, client_id) = path.into_inner();
+    ClientUserMetadata::delete(&client_id, &user_id).await?;
+    AuditLog::action(
+        &principal,
+        &req,
+        AuditAction::Delete,
+        "client_user_metadata",
+        &user_id,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns all clients the user has authorized on the consent screen with the granted scopes
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - the user itself
+#[utoipa::path(
+    get,
+    path = "/users/{id}/consents",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [UserConsentResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/consents")]
+pub async fn get_user_consents(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    let consents = UserConsent::find_for_user(&user_id).await?;
+    let mut resp: Vec<UserConsentResponse> = Vec::with_capacity(consents.len());
+    for consent in consents {
+        resp.push(consent.into_response().await);
+    }
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Revokes the consent the user has given to a client
+///
+/// All refresh tokens the user holds for this client are revoked as well, so the client loses
+/// access as soon as its current access token expires. The consent screen will show up again
+/// during the next login to this client.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - the user itself
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/consents/{client_id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/consents/{client_id}")]
+pub async fn delete_user_consent(
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
+    let (user_id, client_id) = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    UserConsent::delete(&user_id, &client_id).await?;
+    RefreshToken::invalidate_for_user_client(&user_id, &client_id).await?;
+    AuditLog::action(
+        &principal,
+        &req,
+        AuditAction::Delete,
+        "user_consents",
+        &user_id,
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// GET all devices for this user linked via the `device_code` flow
+#[utoipa::path(
+    get,
+    path = "/users/{id}/devices",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [DeviceResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/devices")]
+pub async fn get_user_devices(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    let resp = DeviceEntity::find_for_user(&user_id)
+        .await?
+        .into_iter()
+        .map(DeviceResponse::from)
+        .collect::<Vec<DeviceResponse>>();
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// GET all devices for this user linked via the `device_code` flow
+#[utoipa::path(
+    put,
+    path = "/users/{id}/devices",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[put("/users/{id}/devices")]
+pub async fn put_user_device_name(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    payload: actix_web_validator::Json<DeviceRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    let payload = payload.into_inner();
+    if let Some(name) = &payload.name {
+        DeviceEntity::update_name(&payload.device_id, &user_id, name).await?;
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// DELETE all existing refresh tokens for a user device
+///
+/// This does NOT revoke already existing access tokens, since they are stateless!
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/devices",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/devices")]
+pub async fn delete_user_device(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    payload: actix_web_validator::Json<DeviceRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    let payload = payload.into_inner();
+    let device = DeviceEntity::find(&payload.device_id).await?;
+    if device.user_id.as_deref() != Some(&user_id) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "You don't have access to this device".to_string(),
+        ));
+    }
+
+    DeviceEntity::revoke_refresh_tokens(&payload.device_id).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// GET all devices with an active session for this user
+///
+/// In contrast to `/users/{id}/devices`, which are linked via the `device_code` flow, these are
+/// browsers the user has logged in with.
+///
+/// **Permissions**
+/// - authenticated user for own devices
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/devices/sessions",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [SessionDeviceResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/devices/sessions")]
+pub async fn get_user_session_devices(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    let devices = SessionDevice::find_for_user(&user_id).await?;
+    let sessions = Session::find_for_user(&user_id).await?;
+    let current_sid = principal.get_session().ok().map(|s| s.id.as_str());
+    let resp = SessionDevice::into_responses(devices, &sessions, current_sid);
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// DELETE a device session for this user
+///
+/// Signs out the device by invalidating its session. All refresh tokens linked to this session
+/// will be revoked and access tokens issued for it will be denied until they expire.
+///
+/// **Permissions**
+/// - authenticated user for own devices
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/devices/sessions/{session_id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/devices/sessions/{session_id}")]
+pub async fn delete_user_session_device(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
+    let (user_id, session_id) = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    let session = Session::find(session_id.clone()).await?;
+    if session.user_id.as_deref() != Some(user_id.as_str()) {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::NotFound,
+            "Device does not exist for this user",
+        ));
+    }
+
+    RefreshToken::invalidate_for_session(&session_id).await?;
+    session.invalidate().await?;
+
+    Event::session_revoked(
+        format!("Device session {} for user: {}", session_id, user_id),
+        real_ip_from_req(&req)?.to_string(),
+    )
+    .with_user_id(user_id)
+    .send(&data.tx_events)
+    .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// GET all trusted devices for this user
+///
+/// On these devices, the 2nd factor will be skipped for password logins until they expire.
+///
+/// **Permissions**
+/// - authenticated user for own devices
+/// - rauthy_admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/devices/trusted",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [TrustedDeviceResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/devices/trusted")]
+pub async fn get_user_trusted_devices(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    let resp = TrustedDevice::find_for_user(&user_id)
+        .await?
+        .into_iter()
+        .map(|d| d.into_response(&req))
+        .collect::<Vec<TrustedDeviceResponse>>();
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// DELETE all trusted devices for this user
+///
+/// The 2nd factor will be requested again on the next login on any device.
+///
+/// **Permissions**
+/// - authenticated user for own devices
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/devices/trusted",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/devices/trusted")]
+pub async fn delete_user_trusted_devices(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
+    let user_id = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    TrustedDevice::delete_all_for_user(&user_id).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// DELETE a single trusted device for this user
+///
+/// **Permissions**
+/// - authenticated user for own devices
+/// - rauthy_admin
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/devices/trusted/{device_id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/devices/trusted/{device_id}")]
+pub async fn delete_user_trusted_device(
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
+    let (user_id, device_id) = path.into_inner();
+    principal.validate_user_or_admin(&user_id)?;
+
+    TrustedDevice::delete(&user_id, &device_id).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Endpoint for resetting passwords
+///
+/// The `id` is the user id and `reset_id` is a random 64 character long string sent via E-Mail for a
+/// pre-authenticated request.
+#[utoipa::path(
+    get,
+    path = "/users/{id}/email_confirm/{confirm_id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/email_confirm/{confirm_id}")]
+pub async fn get_user_email_confirm(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let lang = Language::try_from(&req).unwrap_or_default();
+    let (user_id, confirm_id) = path.into_inner();
+    match User::confirm_email_address(&data, req, user_id, confirm_id).await {
+        Ok(html) => HttpResponse::Ok().insert_header(HEADER_HTML).body(html),
+        Err(err) => {
+            let colors = ColorEntity::find_rauthy().await.unwrap_or_default();
+            let status = err.status_code();
+            let body = Error3Html::build(&colors, &lang, status, Some(err.message));
+            ErrorHtml::response(body, status)
+        }
+    }
+}
+
+/// Sends out a new E-Mail verification Magic Link
+///
+/// Any previously sent verification link will be invalidated. Fails if the E-Mail address has
+/// been verified already.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - the user itself
+#[utoipa::path(
+    post,
+    path = "/users/{id}/email_verification",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/email_verification")]
+pub async fn post_user_email_verification(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
+    let id = path.into_inner();
+
+    // principal must either be an admin or have the same user id
+    let api_key_or_admin = principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)
+        .is_ok();
+    if !api_key_or_admin {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    let user = User::find(id).await?;
+    user.request_email_verification(&data).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Endpoint for resetting passwords
+///
+/// The `id` is the user id and `reset_id` is a random 64 character long string sent via E-Mail for a
 /// pre-authenticated request.
 #[utoipa::path(
-    get,
-    path = "/users/{id}/reset/{reset_id}",
-    tag = "users",
+    get,
+    path = "/users/{id}/reset/{reset_id}",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = PasswordResetResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/reset/{reset_id}")]
+pub async fn get_user_password_reset(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let (user_id, reset_id) = path.into_inner();
+    let lang = Language::try_from(&req).unwrap_or_default();
+    let accept = req
+        .headers()
+        .get(ACCEPT)
+        .map(|v| v.to_str().unwrap_or("text/html"))
+        .unwrap_or("text/html");
+    let no_html = accept == "application/json";
+
+    match password_reset::handle_get_pwd_reset(req, user_id.clone(), reset_id, no_html).await {
+        Ok((content, cookie)) => {
+            if no_html {
+                let policy = match User::find(user_id).await {
+                    Ok(user) => PasswordPolicy::find_for_user(&user).await,
+                    Err(err) => Err(err),
+                };
+                let password_policy = match policy {
+                    Ok(policy) => PasswordPolicyResponse::from(policy),
+                    Err(err) => {
+                        let colors = ColorEntity::find_rauthy().await.unwrap_or_default();
+                        let status = err.status_code();
+                        let body = Error3Html::build(&colors, &lang, status, Some(err.message));
+                        return ErrorHtml::response(body, status);
+                    }
+                };
+
+                HttpResponse::Ok()
+                    .cookie(cookie)
+                    .insert_header(HEADER_JSON)
+                    .json(PasswordResetResponse {
+                        csrf_token: content,
+                        password_policy,
+                    })
+            } else {
+                HttpResponse::Ok()
+                    .cookie(cookie)
+                    .insert_header(HEADER_HTML)
+                    .body(content)
+            }
+        }
+        Err(err) => {
+            let colors = ColorEntity::find_rauthy().await.unwrap_or_default();
+            let status = err.status_code();
+            let body = Error3Html::build(&colors, &lang, status, Some(err.message));
+            ErrorHtml::response(body, status)
+        }
+    }
+}
+
+/// Endpoint for resetting passwords
+///
+/// On this endpoint, a password reset can be posted. This only works with a valid
+/// `PWD_RESET_COOKIE` + CSRF token.
+///
+/// Expects the CSRF token to be provided with an HTTP Header called `PWD_CSRF_HEADER`
+///
+/// **Permissions**
+/// - pre-authenticated with pwd-reset cookie from `GET /auth/v1/users/{id}/reset/{reset_id}`
+#[utoipa::path(
+    put,
+    path = "/users/{id}/reset",
+    tag = "users",
+    request_body = PasswordResetRequest,
+    responses(
+        (status = 202, description = "Accepted"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[put("/users/{id}/reset")]
+pub async fn put_user_password_reset(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    req: HttpRequest,
+    req_data: Json<PasswordResetRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    password_reset::handle_put_user_password_reset(
+        &data,
+        req,
+        path.into_inner(),
+        req_data.into_inner(),
+    )
+    .await
+    .map(|(cookie, location)| {
+        if let Some(loc) = location {
+            HttpResponse::Ok()
+                .insert_header((LOCATION, loc))
+                .cookie(cookie)
+                .status(StatusCode::ACCEPTED)
+                .finish()
+        } else {
+            HttpResponse::Ok()
+                .cookie(cookie)
+                .status(StatusCode::ACCEPTED)
+                .finish()
+        }
+    })
+}
+
+/// Get all registered Webauthn Passkeys for a user
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+/// - authenticated and logged in admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/webauthn",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = [PasskeyResponse]),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/webauthn")]
+pub async fn get_user_webauthn_passkeys(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let id = id.into_inner();
+
+    if principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)
+        .is_err()
+    {
+        // make sure a non-admin can only access its own information
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    let mds = FidoMds::find().await;
+    let pks = PasskeyEntity::find_for_user(&id)
+        .await?
+        .into_iter()
+        .map(|pk| pk.into_response(mds.as_ref()))
+        .collect::<Vec<PasskeyResponse>>();
+
+    Ok(HttpResponse::Ok().json(pks))
+}
+
+/// Starts the authentication process for a WebAuthn Device for this user
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    post,
+    path = "/users/{id}/webauthn/auth/start",
+    tag = "mfa",
+    request_body = WebauthnAuthStartRequest,
+    responses(
+        (status = 200, description = "Ok", body = WebauthnAuthStartResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/webauthn/auth/start")]
+pub async fn post_webauthn_auth_start(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    // The principal here must be optional to make cases like user password reset in a
+    // fully new / different browser which does not have any lefter data or cookies
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    req_data: Json<WebauthnAuthStartRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let purpose = req_data.into_inner().purpose;
+    let id = match purpose {
+        // only for a Login purpose, this can be accessed without authentication (yet)
+        MfaPurpose::Login(_) => {
+            // During Login, the session is allowed to be in init only state
+            principal.validate_session_auth_or_init()?;
+            id.into_inner()
+        }
+
+        MfaPurpose::PasswordReset => {
+            // A password reset webauthn req can be opened without any session at all.
+            // This is mandatory to make password reset flows fully work, even with an old
+            // account with linked Passkeys.
+            match ApiCookie::from_req(&req, PWD_RESET_COOKIE) {
+                None => {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        "You are not allowed to do this operation without an active binding cookie"
+                            .to_string(),
+                    ));
+                }
+                Some(c) => {
+                    if c.len() != 48 {
+                        return Err(ErrorResponse::new(
+                            ErrorResponseType::BadRequest,
+                            "Malformed magic link binding cookie".to_string(),
+                        ));
+                    };
+                }
+            };
+
+            id.into_inner()
+        }
+
+        _ => {
+            // for all other purposes, we need an authenticated session
+            principal.validate_session_auth()?;
+            principal.validate_not_impersonated()?;
+
+            // make sure the principal is this very user
+            let id = id.into_inner();
+            principal.is_user(&id)?;
+            id
+        }
+    };
+
+    webauthn::auth_start(data.webauthn.for_req(&req), id, purpose)
+        .await
+        .map(|res| HttpResponse::Ok().json(res))
+}
+
+/// Finishes the authentication process for a WebAuthn Device for this user
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    post,
+    path = "/users/{id}/webauthn/auth/finish",
+    tag = "mfa",
+    request_body = WebauthnAuthFinishRequest,
+    responses(
+        (status = 200, description = "Ok", body = WebauthnAdditionalData),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/webauthn/auth/finish")]
+pub async fn post_webauthn_auth_finish(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    req_data: Json<WebauthnAuthFinishRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    let id = id.into_inner();
+
+    // We do not need to further validate the principal here.
+    // All of this is done at the /start endpoint.
+    // This here will simply fail, if the secret code from the /start does not exist.
+
+    match webauthn::auth_finish(
+        data.webauthn.for_req(&req),
+        id.clone(),
+        req_data.into_inner(),
+    )
+    .await
+    {
+        Ok((res, hybrid)) => {
+            let mut remember_me_session = None;
+            let mut trust_device_user = None;
+            if let WebauthnAdditionalData::Login(login_req) = &res {
+                if hybrid {
+                    if let Err(err) = webauthn::validate_hybrid_login(
+                        login_req.client_id.clone(),
+                        login_req.user_id.clone(),
+                        real_ip_from_req(&req)?.to_string(),
+                        &data.tx_events,
+                    )
+                    .await
+                    {
+                        // the auth code must not be usable with another passkey afterward
+                        res.delete().await?;
+                        return Err(err);
+                    }
+                }
+                if let Some(version) = &login_req.tos_accept {
+                    TosAcceptance::accept(&req, login_req.user_id.clone(), version.clone()).await?;
+                }
+                if let Some(values) = &login_req.profile_values {
+                    UserAttrValueEntity::save_at_login(&login_req.user_id, values.clone()).await?;
+                }
+                if let Some(scopes) = &login_req.consent_accept {
+                    UserConsent::grant(
+                        login_req.user_id.clone(),
+                        login_req.client_id.clone(),
+                        scopes.clone(),
+                    )
+                    .await?;
+                }
+                if login_req.remember_me {
+                    if let Ok(session) = principal.get_session() {
+                        let user = User::find(login_req.user_id.clone()).await?;
+                        let mut session = session.clone();
+                        session.set_remember_me(user.user_expires).await?;
+                        remember_me_session = Some(session);
+                    }
+                }
+                if login_req.trust_device {
+                    trust_device_user = Some(login_req.user_id.clone());
+                }
+                LoginHistory::record(
+                    &req,
+                    login_req.user_id.clone(),
+                    Some(login_req.client_id.clone()),
+                    true,
+                    true,
+                )
+                .await;
+            }
+
+            let mut resp = res.into_response();
+            if let Some(session) = remember_me_session {
+                add_session_cookie(&mut resp, &session);
+            }
+            if let Some(user_id) = trust_device_user {
+                add_trusted_device_cookie(&mut resp, user_id, &req).await;
+            }
+            Ok(resp)
+        }
+        Err(err) => {
+            // An unknown or expired code is not a failed MFA ceremony.
+            if err.error == ErrorResponseType::Unauthorized
+                || err.error == ErrorResponseType::Forbidden
+            {
+                let email = match User::find(id.clone()).await {
+                    Ok(user) => {
+                        LoginHistory::record(&req, user.id, None, true, false).await;
+                        user.email
+                    }
+                    Err(_) => id.clone(),
+                };
+                Event::mfa_failed(email, real_ip_from_req(&req)?.to_string())
+                    .with_user_id(id)
+                    .send(&data.tx_events)
+                    .await?;
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Deletes the WebAuthn Device for this user in the given slot
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/webauthn/delete/{name}",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/webauthn/delete/{name}")]
+pub async fn delete_webauthn(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
+    // Note: Currently, this is not allowed with an ApiKey on purpose
+    let is_admin = match principal.validate_admin_session() {
+        Ok(()) => true,
+        Err(_) => {
+            principal.validate_session_auth()?;
+            false
+        }
+    };
+
+    let (id, name) = path.into_inner();
+
+    // validate that Principal matches the user or is an admin
+    if !is_admin {
+        principal.is_user(&id)?;
+        warn!("Passkey delete for user {} for key {}", id, name);
+    } else {
+        warn!("Passkey delete from admin for user {} for key {}", id, name);
+    }
+
+    PasskeyEntity::delete(id.clone(), name.clone()).await?;
+    MfaRecoveryCode::delete_if_mfa_disabled(&id).await?;
+    if is_admin && principal.is_user(&id).is_err() {
+        AuditLog::action(
+            &principal,
+            &req,
+            AuditAction::Delete,
+            "passkey",
+            &format!("{}/{}", id, name),
+        )
+        .await;
+    }
+
+    let email = User::find(id.clone())
+        .await
+        .map(|user| user.email)
+        .unwrap_or_else(|_| id.clone());
+    Event::mfa_removed(email, real_ip_from_req(&req)?.to_string())
+        .with_user_id(id)
+        .send(&data.tx_events)
+        .await?;
+
+    // // if we delete a passkey, we must check if this is the last existing one for the user
+    // let pks = PasskeyEntity::find_for_user(&data, &id).await?;
+    //
+    // let mut txn = DB::txn().await?;
+    //
+    // PasskeyEntity::delete_by_id_name(&id, &name, &mut txn).await?;
+    // if pks.len() < 2 {
+    //     let mut user = User::find(&data, id).await?;
+    //     user.webauthn_user_id = None;
+    //
+    //     // in this case, we need to check against the current password policy, if the password
+    //     // should expire again
+    //     let policy = PasswordPolicy::find(&data).await?;
+    //     if let Some(valid_days) = policy.valid_days {
+    //         if user.password.is_some() {
+    //             user.password_expires = Some(
+    //                 clock::now_utc()
+    //                     .add(time::Duration::days(valid_days as i64))
+    //                     .unix_timestamp(),
+    //             );
+    //         } else {
+    //             user.password_expires = None;
+    //         }
+    //     }
+    //
+    //     user.save_txn(&mut txn).await?;
+    //     txn.commit().await?;
+    // } else {
+    //     txn.commit().await?;
+    // }
+    // PasskeyEntity::clear_caches_by_id_name(&id, &name).await?;
+
+    // make sure to delete any existing MFA cookie when a key is deleted
+    let cookie = ApiCookie::build(COOKIE_MFA, "", 0);
+    let mut resp = HttpResponse::Ok().finish();
+    if let Err(err) = resp.add_cookie(&cookie) {
+        error!("Error deleting MFA cookie in post_webauthn_delete: {}", err);
+    }
+    Ok(resp)
+}
+
+/// Renames a WebAuthn Device for this user
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    put,
+    path = "/users/{id}/webauthn/rename/{name}",
+    tag = "mfa",
+    request_body = WebauthnRenameRequest,
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[put("/users/{id}/webauthn/rename/{name}")]
+pub async fn put_webauthn_rename(
+    path: web::Path<(String, String)>,
+    principal: ReqPrincipal,
+    payload: Json<WebauthnRenameRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+    principal.validate_session_auth()?;
+
+    let (id, name) = path.into_inner();
+    principal.is_user(&id)?;
+
+    PasskeyEntity::rename(&id, &name, payload.passkey_name.trim()).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns if TOTP is enabled for this user
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+/// - authenticated and logged in admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/totp",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Ok", body = TotpStatusResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/totp")]
+pub async fn get_user_totp(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let id = id.into_inner();
+
+    if principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)
+        .is_err()
+    {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    let resp = match UserTotp::find(&id).await? {
+        Some(totp) if totp.confirmed => TotpStatusResponse {
+            enabled: true,
+            created: Some(totp.created),
+        },
+        _ => TotpStatusResponse {
+            enabled: false,
+            created: None,
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Starts the TOTP setup for this user
+///
+/// The returned secret will only be used for logins after it has been confirmed with a valid
+/// code. Starting over replaces a not yet confirmed secret.
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    post,
+    path = "/users/{id}/totp",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Ok", body = TotpEnrollResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/totp")]
+pub async fn post_user_totp(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+    principal.validate_session_auth()?;
+    let id = id.into_inner();
+    principal.is_user(&id)?;
+
+    let user = User::find(id.clone()).await?;
+    let secret = UserTotp::enroll(id).await?;
+    let uri = UserTotp::provisioning_uri(&secret, &data.public_url, &user.email);
+    let qr_svg = UserTotp::provisioning_qr_svg(&uri)?;
+
+    Ok(HttpResponse::Ok().json(TotpEnrollResponse {
+        secret,
+        uri,
+        qr_svg,
+    }))
+}
+
+/// Confirms the TOTP setup with the current code from the authenticator app
+///
+/// Returns a new set of recovery codes, if the user does not have any left.
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    post,
+    path = "/users/{id}/totp/confirm",
+    tag = "mfa",
+    request_body = TotpConfirmRequest,
+    responses(
+        (status = 200, description = "Ok", body = MfaRecoveryCodesResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/totp/confirm")]
+pub async fn post_user_totp_confirm(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    payload: Json<TotpConfirmRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+    principal.validate_session_auth()?;
+    let id = id.into_inner();
+    principal.is_user(&id)?;
+
+    UserTotp::confirm(&id, &payload.code).await?;
+    let codes = MfaRecoveryCode::generate_if_missing(&id)
+        .await?
+        .unwrap_or_default();
+
+    let user = User::find(id).await?;
+    Event::mfa_enrolled(user.email, real_ip_from_req(&req)?.to_string())
+        .with_user_id(user.id)
+        .send(&data.tx_events)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(MfaRecoveryCodesResponse { codes }))
+}
+
+/// Removes TOTP for this user
+///
+/// Admins can use this to reset TOTP for a user, who lost access to the authenticator app.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/totp",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/totp")]
+pub async fn delete_user_totp(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
+    let is_admin = match principal.validate_admin_session() {
+        Ok(()) => true,
+        Err(_) => {
+            principal.validate_session_auth()?;
+            false
+        }
+    };
+
+    let id = id.into_inner();
+    if !is_admin {
+        principal.is_user(&id)?;
+        warn!("TOTP delete for user {}", id);
+    } else {
+        warn!("TOTP delete from admin for user {}", id);
+    }
+
+    UserTotp::delete(&id).await?;
+    MfaRecoveryCode::delete_if_mfa_disabled(&id).await?;
+    if is_admin && principal.is_user(&id).is_err() {
+        AuditLog::action(&principal, &req, AuditAction::Delete, "totp", &id).await;
+    }
+
+    let email = User::find(id.clone())
+        .await
+        .map(|user| user.email)
+        .unwrap_or_else(|_| id.clone());
+    Event::mfa_removed(email, real_ip_from_req(&req)?.to_string())
+        .with_user_id(id)
+        .send(&data.tx_events)
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns if SMS OTP is available and enabled for this user
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+/// - authenticated and logged in admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/sms_otp",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Ok", body = SmsOtpStatusResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/sms_otp")]
+pub async fn get_user_sms_otp(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let id = id.into_inner();
+
+    if principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)
+        .is_err()
+    {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    let available = Sms::is_enabled();
+    let resp = match UserSmsOtp::find(&id).await? {
+        Some(otp) if otp.verified => SmsOtpStatusResponse {
+            available,
+            enabled: available,
+            phone: Some(otp.phone_masked()),
+            created: Some(otp.created),
+        },
+        _ => SmsOtpStatusResponse {
+            available,
+            enabled: false,
+            phone: None,
+            created: None,
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Starts the SMS OTP setup for this user by sending a code to the given phone number
+///
+/// The number will only be used for logins after it has been verified with this code.
+/// Starting over replaces a not yet verified number.
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    post,
+    path = "/users/{id}/sms_otp",
+    tag = "mfa",
+    request_body = SmsOtpEnrollRequest,
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 429, description = "TooManyRequests", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/sms_otp")]
+pub async fn post_user_sms_otp(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    payload: Json<SmsOtpEnrollRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+    principal.validate_session_auth()?;
+    let id = id.into_inner();
+    principal.is_user(&id)?;
+
+    let user = User::find(id.clone()).await?;
+    if user.has_webauthn_enabled() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "SMS OTP is not available for accounts with a passkey",
+        ));
+    }
+
+    UserSmsOtp::enroll(id, payload.into_inner().phone).await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Verifies the phone number with the code from the SMS
+///
+/// Returns a new set of recovery codes, if the user does not have any left.
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    post,
+    path = "/users/{id}/sms_otp/confirm",
+    tag = "mfa",
+    request_body = SmsOtpConfirmRequest,
+    responses(
+        (status = 200, description = "Ok", body = MfaRecoveryCodesResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/sms_otp/confirm")]
+pub async fn post_user_sms_otp_confirm(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    payload: Json<SmsOtpConfirmRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+    principal.validate_session_auth()?;
+    let id = id.into_inner();
+    principal.is_user(&id)?;
+
+    UserSmsOtp::confirm(&id, &payload.code).await?;
+    let codes = MfaRecoveryCode::generate_if_missing(&id)
+        .await?
+        .unwrap_or_default();
+
+    let user = User::find(id).await?;
+    Event::mfa_enrolled(user.email, real_ip_from_req(&req)?.to_string())
+        .with_user_id(user.id)
+        .send(&data.tx_events)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(MfaRecoveryCodesResponse { codes }))
+}
+
+/// Removes SMS OTP for this user
+///
+/// Admins can use this to reset SMS OTP for a user, who lost access to the phone number.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/sms_otp",
+    tag = "mfa",
     responses(
-        (status = 200, description = "Ok", body = PasswordResetResponse),
+        (status = 200, description = "Ok"),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
-#[get("/users/{id}/reset/{reset_id}")]
-pub async fn get_user_password_reset(
-    path: web::Path<(String, String)>,
+#[delete("/users/{id}/sms_otp")]
+pub async fn delete_user_sms_otp(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
     req: HttpRequest,
-) -> HttpResponse {
-    let (user_id, reset_id) = path.into_inner();
-    let lang = Language::try_from(&req).unwrap_or_default();
-    let accept = req
-        .headers()
-        .get(ACCEPT)
-        .map(|v| v.to_str().unwrap_or("text/html"))
-        .unwrap_or("text/html");
-    let no_html = accept == "application/json";
-
-    match password_reset::handle_get_pwd_reset(req, user_id, reset_id, no_html).await {
-        Ok((content, cookie)) => {
-            if no_html {
-                let password_policy = match PasswordPolicy::find().await {
-                    Ok(policy) => PasswordPolicyResponse::from(policy),
-                    Err(err) => {
-                        let colors = ColorEntity::find_rauthy().await.unwrap_or_default();
-                        let status = err.status_code();
-                        let body = Error3Html::build(&colors, &lang, status, Some(err.message));
-                        return ErrorHtml::response(body, status);
-                    }
-                };
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
 
-                HttpResponse::Ok()
-                    .cookie(cookie)
-                    .insert_header(HEADER_JSON)
-                    .json(PasswordResetResponse {
-                        csrf_token: content,
-                        password_policy,
-                    })
-            } else {
-                HttpResponse::Ok()
-                    .cookie(cookie)
-                    .insert_header(HEADER_HTML)
-                    .body(content)
-            }
-        }
-        Err(err) => {
-            let colors = ColorEntity::find_rauthy().await.unwrap_or_default();
-            let status = err.status_code();
-            let body = Error3Html::build(&colors, &lang, status, Some(err.message));
-            ErrorHtml::response(body, status)
+    let is_admin = match principal.validate_admin_session() {
+        Ok(()) => true,
+        Err(_) => {
+            principal.validate_session_auth()?;
+            false
         }
+    };
+
+    let id = id.into_inner();
+    if !is_admin {
+        principal.is_user(&id)?;
+        warn!("SMS OTP delete for user {}", id);
+    } else {
+        warn!("SMS OTP delete from admin for user {}", id);
+    }
+
+    UserSmsOtp::delete(&id).await?;
+    MfaRecoveryCode::delete_if_mfa_disabled(&id).await?;
+    if is_admin && principal.is_user(&id).is_err() {
+        AuditLog::action(&principal, &req, AuditAction::Delete, "sms_otp", &id).await;
     }
+
+    let email = User::find(id.clone())
+        .await
+        .map(|user| user.email)
+        .unwrap_or_else(|_| id.clone());
+    Event::mfa_removed(email, real_ip_from_req(&req)?.to_string())
+        .with_user_id(id)
+        .send(&data.tx_events)
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
 }
 
-/// Endpoint for resetting passwords
+/// Returns if Yubico OTP is available and enabled for this user
 ///
-/// On this endpoint, a password reset can be posted. This only works with a valid
-/// `PWD_RESET_COOKIE` + CSRF token.
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+/// - authenticated and logged in admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/yubico_otp",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Ok", body = YubicoOtpStatusResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[get("/users/{id}/yubico_otp")]
+pub async fn get_user_yubico_otp(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+) -> Result<HttpResponse, ErrorResponse> {
+    let id = id.into_inner();
+
+    if principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)
+        .is_err()
+    {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
+
+    let available = UserYubicoOtp::is_available();
+    let resp = match UserYubicoOtp::find(&id).await? {
+        Some(otp) => YubicoOtpStatusResponse {
+            available,
+            enabled: available,
+            public_id: Some(otp.public_id),
+            created: Some(otp.created),
+            last_used: Some(otp.last_used),
+        },
+        None => YubicoOtpStatusResponse {
+            available,
+            enabled: false,
+            public_id: None,
+            created: None,
+            last_used: None,
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+/// Registers a Yubico key for OTP logins for this user
 ///
-/// Expects the CSRF token to be provided with an HTTP Header called `PWD_CSRF_HEADER`
+/// The key is identified by the public ID of the given OTP, which must be valid.
+/// Returns a new set of recovery codes, if the user does not have any left.
 ///
 /// **Permissions**
-/// - pre-authenticated with pwd-reset cookie from `GET /auth/v1/users/{id}/reset/{reset_id}`
+/// - authenticated and logged in user for this very {id}
 #[utoipa::path(
-    put,
-    path = "/users/{id}/reset",
-    tag = "users",
-    request_body = PasswordResetRequest,
+    post,
+    path = "/users/{id}/yubico_otp",
+    tag = "mfa",
+    request_body = YubicoOtpRequest,
     responses(
-        (status = 202, description = "Accepted"),
+        (status = 200, description = "Ok", body = MfaRecoveryCodesResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
-#[put("/users/{id}/reset")]
-pub async fn put_user_password_reset(
+#[post("/users/{id}/yubico_otp")]
+pub async fn post_user_yubico_otp(
     data: web::Data<AppState>,
-    path: web::Path<String>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
     req: HttpRequest,
-    req_data: Json<PasswordResetRequest>,
+    payload: Json<YubicoOtpRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    password_reset::handle_put_user_password_reset(
-        &data,
-        req,
-        path.into_inner(),
-        req_data.into_inner(),
-    )
-    .await
-    .map(|(cookie, location)| {
-        if let Some(loc) = location {
-            HttpResponse::Ok()
-                .insert_header((LOCATION, loc))
-                .cookie(cookie)
-                .status(StatusCode::ACCEPTED)
-                .finish()
-        } else {
-            HttpResponse::Ok()
-                .cookie(cookie)
-                .status(StatusCode::ACCEPTED)
-                .finish()
+    principal.validate_not_impersonated()?;
+    principal.validate_session_auth()?;
+    let id = id.into_inner();
+    principal.is_user(&id)?;
+
+    let user = User::find(id.clone()).await?;
+    if user.has_webauthn_enabled() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "Yubico OTP is not available for accounts with a passkey",
+        ));
+    }
+
+    UserYubicoOtp::enroll(id.clone(), &payload.otp).await?;
+    let codes = MfaRecoveryCode::generate_if_missing(&id)
+        .await?
+        .unwrap_or_default();
+
+    Event::mfa_enrolled(user.email, real_ip_from_req(&req)?.to_string())
+        .with_user_id(user.id)
+        .send(&data.tx_events)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(MfaRecoveryCodesResponse { codes }))
+}
+
+/// Removes the Yubico OTP key for this user
+///
+/// Admins can use this to reset Yubico OTP for a user, who lost the key.
+///
+/// **Permissions**
+/// - rauthy_admin
+/// - authenticated and logged in user for this very {id}
+#[utoipa::path(
+    delete,
+    path = "/users/{id}/yubico_otp",
+    tag = "mfa",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[delete("/users/{id}/yubico_otp")]
+pub async fn delete_user_yubico_otp(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
+    let is_admin = match principal.validate_admin_session() {
+        Ok(()) => true,
+        Err(_) => {
+            principal.validate_session_auth()?;
+            false
         }
-    })
+    };
+
+    let id = id.into_inner();
+    if !is_admin {
+        principal.is_user(&id)?;
+        warn!("Yubico OTP delete for user {}", id);
+    } else {
+        warn!("Yubico OTP delete from admin for user {}", id);
+    }
+
+    UserYubicoOtp::delete(&id).await?;
+    MfaRecoveryCode::delete_if_mfa_disabled(&id).await?;
+    if is_admin && principal.is_user(&id).is_err() {
+        AuditLog::action(&principal, &req, AuditAction::Delete, "yubico_otp", &id).await;
+    }
+
+    let email = User::find(id.clone())
+        .await
+        .map(|user| user.email)
+        .unwrap_or_else(|_| id.clone());
+    Event::mfa_removed(email, real_ip_from_req(&req)?.to_string())
+        .with_user_id(id)
+        .send(&data.tx_events)
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
 }
 
-/// Get all registered Webauthn Passkeys for a user
+/// Returns the E-Mail OTP setting for this user
 ///
 /// **Permissions**
 /// - authenticated and logged in user for this very {id}
 /// - authenticated and logged in admin
 #[utoipa::path(
     get,
-    path = "/users/{id}/webauthn",
-    tag = "users",
+    path = "/users/{id}/email_otp",
+    tag = "mfa",
     responses(
-        (status = 200, description = "Ok", body = [PasskeyResponse]),
+        (status = 200, description = "Ok", body = EmailOtpStatusResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
-#[get("/users/{id}/webauthn")]
-pub async fn get_user_webauthn_passkeys(
+#[get("/users/{id}/email_otp")]
+pub async fn get_user_email_otp(
     id: web::Path<String>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
@@ -715,205 +2590,199 @@ pub async fn get_user_webauthn_passkeys(
         .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)
         .is_err()
     {
-        // make sure a non-admin can only access its own information
         principal.validate_session_auth()?;
         principal.is_user(&id)?;
     }
 
-    let pks = PasskeyEntity::find_for_user(&id)
+    let mode = match *MFA_EMAIL_OTP {
+        EmailOtpMode::Disabled => "disabled",
+        EmailOtpMode::OptIn => "opt_in",
+        EmailOtpMode::All => "all",
+    };
+    let enabled = UserEmailOtp::find(&id)
         .await?
-        .into_iter()
-        .map(PasskeyResponse::from)
-        .collect::<Vec<PasskeyResponse>>();
+        .map(|otp| otp.enabled)
+        .unwrap_or(false);
 
-    Ok(HttpResponse::Ok().json(pks))
+    Ok(HttpResponse::Ok().json(EmailOtpStatusResponse {
+        mode: mode.to_string(),
+        enabled,
+    }))
 }
 
-/// Starts the authentication process for a WebAuthn Device for this user
+/// Enables or disables E-Mail OTP for this user
+///
+/// Only possible with `MFA_EMAIL_OTP=opt_in`. It will only be used for logins, as long as the user
+/// has neither a passkey nor TOTP.
 ///
 /// **Permissions**
+/// - rauthy_admin
 /// - authenticated and logged in user for this very {id}
 #[utoipa::path(
-    post,
-    path = "/users/{id}/webauthn/auth/start",
+    put,
+    path = "/users/{id}/email_otp",
+    tag = "mfa",
+    request_body = EmailOtpRequest,
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+    ),
+)]
+#[put("/users/{id}/email_otp")]
+pub async fn put_user_email_otp(
+    id: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+    payload: Json<EmailOtpRequest>,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
+    let is_admin = match principal.validate_admin_session() {
+        Ok(()) => true,
+        Err(_) => {
+            principal.validate_session_auth()?;
+            false
+        }
+    };
+
+    let id = id.into_inner();
+    if !is_admin {
+        principal.is_user(&id)?;
+    }
+
+    UserEmailOtp::set_enabled(id.clone(), payload.enabled).await?;
+    if is_admin && principal.is_user(&id).is_err() {
+        AuditLog::action(&principal, &req, AuditAction::Update, "email_otp", &id).await;
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Returns the amount of remaining MFA recovery codes for this user
+///
+/// **Permissions**
+/// - authenticated and logged in user for this very {id}
+/// - authenticated and logged in admin
+#[utoipa::path(
+    get,
+    path = "/users/{id}/mfa/recovery_codes",
     tag = "mfa",
-    request_body = WebauthnAuthStartRequest,
     responses(
-        (status = 200, description = "Ok", body = WebauthnAuthStartResponse),
+        (status = 200, description = "Ok", body = MfaRecoveryCodesStatusResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
-#[post("/users/{id}/webauthn/auth/start")]
-pub async fn post_webauthn_auth_start(
-    data: web::Data<AppState>,
+#[get("/users/{id}/mfa/recovery_codes")]
+pub async fn get_user_recovery_codes(
     id: web::Path<String>,
-    // The principal here must be optional to make cases like user password reset in a
-    // fully new / different browser which does not have any lefter data or cookies
     principal: ReqPrincipal,
-    req: HttpRequest,
-    req_data: Json<WebauthnAuthStartRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
-    let purpose = req_data.into_inner().purpose;
-    let id = match purpose {
-        // only for a Login purpose, this can be accessed without authentication (yet)
-        MfaPurpose::Login(_) => {
-            // During Login, the session is allowed to be in init only state
-            principal.validate_session_auth_or_init()?;
-            id.into_inner()
-        }
-
-        MfaPurpose::PasswordReset => {
-            // A password reset webauthn req can be opened without any session at all.
-            // This is mandatory to make password reset flows fully work, even with an old
-            // account with linked Passkeys.
-            match ApiCookie::from_req(&req, PWD_RESET_COOKIE) {
-                None => {
-                    return Err(ErrorResponse::new(
-                        ErrorResponseType::BadRequest,
-                        "You are not allowed to do this operation without an active binding cookie"
-                            .to_string(),
-                    ));
-                }
-                Some(c) => {
-                    if c.len() != 48 {
-                        return Err(ErrorResponse::new(
-                            ErrorResponseType::BadRequest,
-                            "Malformed magic link binding cookie".to_string(),
-                        ));
-                    };
-                }
-            };
-
-            id.into_inner()
-        }
+    let id = id.into_inner();
 
-        _ => {
-            // for all other purposes, we need an authenticated session
-            principal.validate_session_auth()?;
+    if principal
+        .validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Read)
+        .is_err()
+    {
+        principal.validate_session_auth()?;
+        principal.is_user(&id)?;
+    }
 
-            // make sure the principal is this very user
-            let id = id.into_inner();
-            principal.is_user(&id)?;
-            id
-        }
-    };
+    let (remaining, created) = MfaRecoveryCode::status(&id).await?;
 
-    webauthn::auth_start(&data, id, purpose)
-        .await
-        .map(|res| HttpResponse::Ok().json(res))
+    Ok(HttpResponse::Ok().json(MfaRecoveryCodesStatusResponse { remaining, created }))
 }
 
-/// Finishes the authentication process for a WebAuthn Device for this user
+/// Generates a new set of MFA recovery codes for this user
+///
+/// All existing codes will be invalidated. The new codes are only returned once.
 ///
 /// **Permissions**
 /// - authenticated and logged in user for this very {id}
 #[utoipa::path(
     post,
-    path = "/users/{id}/webauthn/auth/finish",
+    path = "/users/{id}/mfa/recovery_codes",
     tag = "mfa",
-    request_body = WebauthnAuthFinishRequest,
     responses(
-        (status = 200, description = "Ok", body = WebauthnAdditionalData),
+        (status = 200, description = "Ok", body = MfaRecoveryCodesResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
 )]
-#[post("/users/{id}/webauthn/auth/finish")]
-pub async fn post_webauthn_auth_finish(
-    data: web::Data<AppState>,
+#[post("/users/{id}/mfa/recovery_codes")]
+pub async fn post_user_recovery_codes(
     id: web::Path<String>,
-    req_data: Json<WebauthnAuthFinishRequest>,
+    principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+    principal.validate_session_auth()?;
     let id = id.into_inner();
+    principal.is_user(&id)?;
 
-    // We do not need to further validate the principal here.
-    // All of this is done at the /start endpoint.
-    // This here will simply fail, if the secret code from the /start does not exist.
+    // recovery codes can only replace the 2nd factor of a password login
+    let user = User::find(id).await?;
+    let has_mfa = user.has_webauthn_enabled()
+        || UserTotp::is_enabled(&user.id).await?
+        || UserYubicoOtp::is_enabled(&user.id).await?
+        || UserSmsOtp::is_enabled(&user.id).await?;
+    if user.password.is_none() || !has_mfa {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "Recovery codes are only available for password accounts with MFA enabled",
+        ));
+    }
+
+    let codes = MfaRecoveryCode::generate(&user.id).await?;
 
-    let res = webauthn::auth_finish(&data, id, req_data.into_inner()).await?;
-    Ok(res.into_response())
+    Ok(HttpResponse::Ok().json(MfaRecoveryCodesResponse { codes }))
 }
 
-/// Deletes the WebAuthn Device for this user in the given slot
+/// Resets MFA for this user
+///
+/// Removes all passkeys, TOTP, Yubico OTP, SMS OTP, opt-in E-Mail OTP, recovery codes and
+/// trusted devices, invalidates all sessions and refresh tokens, and sends a notification E-Mail
+/// to the user. The account will be flagged to require the enrollment of a new 2nd factor. Until
+/// then, only a login to the account dashboard is possible.
 ///
 /// **Permissions**
 /// - rauthy_admin
-/// - authenticated and logged in user for this very {id}
 #[utoipa::path(
-    delete,
-    path = "/users/{id}/webauthn/delete/{name}",
+    post,
+    path = "/users/{id}/mfa/reset",
     tag = "mfa",
     responses(
-        (status = 200, description = "Ok"),
+        (status = 200, description = "Ok", body = UserResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
     ),
 )]
-#[delete("/users/{id}/webauthn/delete/{name}")]
-pub async fn delete_webauthn(
-    path: web::Path<(String, String)>,
+#[post("/users/{id}/mfa/reset")]
+pub async fn post_user_mfa_reset(
+    data: web::Data<AppState>,
+    id: web::Path<String>,
     principal: ReqPrincipal,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
     // Note: Currently, this is not allowed with an ApiKey on purpose
-    let is_admin = match principal.validate_admin_session() {
-        Ok(()) => true,
-        Err(_) => {
-            principal.validate_session_auth()?;
-            false
-        }
-    };
+    principal.validate_admin_session()?;
 
-    let (id, name) = path.into_inner();
+    let id = id.into_inner();
+    warn!("MFA reset from admin for user {}", id);
 
-    // validate that Principal matches the user or is an admin
-    if !is_admin {
-        principal.is_user(&id)?;
-        warn!("Passkey delete for user {} for key {}", id, name);
-    } else {
-        warn!("Passkey delete from admin for user {} for key {}", id, name);
-    }
+    let user = User::reset_mfa(&data, id.clone()).await?;
+    AuditLog::action(&principal, &req, AuditAction::Update, "mfa_reset", &id).await;
 
-    PasskeyEntity::delete(id, name).await?;
-    // // if we delete a passkey, we must check if this is the last existing one for the user
-    // let pks = PasskeyEntity::find_for_user(&data, &id).await?;
-    //
-    // let mut txn = DB::txn().await?;
-    //
-    // PasskeyEntity::delete_by_id_name(&id, &name, &mut txn).await?;
-    // if pks.len() < 2 {
-    //     let mut user = User::find(&data, id).await?;
-    //     user.webauthn_user_id = None;
-    //
-    //     // in this case, we need to check against the current password policy, if the password
-    //     // should expire again
-    //     let policy = PasswordPolicy::find(&data).await?;
-    //     if let Some(valid_days) = policy.valid_days {
-    //         if user.password.is_some() {
-    //             user.password_expires = Some(
-    //                 OffsetDateTime::now_utc()
-    //                     .add(time::Duration::days(valid_days as i64))
-    //                     .unix_timestamp(),
-    //             );
-    //         } else {
-    //             user.password_expires = None;
-    //         }
-    //     }
-    //
-    //     user.save_txn(&mut txn).await?;
-    //     txn.commit().await?;
-    // } else {
-    //     txn.commit().await?;
-    // }
-    // PasskeyEntity::clear_caches_by_id_name(&id, &name).await?;
+    Event::mfa_removed(user.email.clone(), real_ip_from_req(&req)?.to_string())
+        .with_user_id(id)
+        .send(&data.tx_events)
+        .await?;
 
-    // make sure to delete any existing MFA cookie when a key is deleted
-    let cookie = ApiCookie::build(COOKIE_MFA, "", 0);
-    let mut resp = HttpResponse::Ok().finish();
-    if let Err(err) = resp.add_cookie(&cookie) {
-        error!("Error deleting MFA cookie in post_webauthn_delete: {}", err);
-    }
-    Ok(resp)
+    let values = UserValues::find(&user.id).await?;
+    Ok(HttpResponse::Ok().json(user.into_response(values)))
 }
 
 /// Starts the registration process for a new WebAuthn Device for this user
@@ -939,6 +2808,8 @@ pub async fn post_webauthn_reg_start(
     req: HttpRequest,
     req_data: Json<WebauthnRegStartRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
     // If we have a magic link ID in the payload, we do not validate the active session / principal.
     // This is mandatory to make registering a passkey for a completely new account work.
     if req_data.magic_link_id.is_some() && req.headers().get(PWD_CSRF_HEADER).is_some() {
@@ -958,7 +2829,7 @@ pub async fn post_webauthn_reg_start(
         let id = id.into_inner();
         principal.is_user(&id)?;
 
-        webauthn::reg_start(&data, id, req_data.into_inner())
+        webauthn::reg_start(data.webauthn.for_req(&req), id, req_data.into_inner())
             .await
             .map(|ccr| HttpResponse::Ok().json(ccr))
     }
@@ -966,6 +2837,9 @@ pub async fn post_webauthn_reg_start(
 
 /// Finishes the registration process for a new WebAuthn Device for this user
 ///
+/// For logged-in password accounts, a new set of recovery codes is returned, if the user does
+/// not have any left.
+///
 /// **Permissions**
 /// - authenticated and logged-in user for this very {id}
 /// - valid MagicLink code during password reset
@@ -975,7 +2849,7 @@ pub async fn post_webauthn_reg_start(
     tag = "mfa",
     request_body = WebauthnRegFinishRequest,
     responses(
-        (status = 201, description = "Created"),
+        (status = 201, description = "Created", body = MfaRecoveryCodesResponse),
         (status = 401, description = "Unauthorized", body = ErrorResponse),
         (status = 403, description = "Forbidden", body = ErrorResponse),
     ),
@@ -988,6 +2862,8 @@ pub async fn post_webauthn_reg_finish(
     req: HttpRequest,
     req_data: Json<WebauthnRegFinishRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
     // If we have a magic link ID in the payload, we do not validate the active session / principal.
     // This is mandatory to make registering a passkey for a completely new account work.
     if req_data.magic_link_id.is_some() {
@@ -1007,8 +2883,30 @@ pub async fn post_webauthn_reg_finish(
         let id = id.into_inner();
         principal.is_user(&id)?;
 
-        webauthn::reg_finish(&data, id, req_data.into_inner()).await?;
-        Ok(HttpResponse::Created().finish())
+        webauthn::reg_finish(
+            data.webauthn.for_req(&req),
+            id.clone(),
+            req_data.into_inner(),
+        )
+        .await?;
+
+        let user = User::find(id.clone()).await.ok();
+        // recovery codes can only be used for password logins
+        let codes = if user.as_ref().is_some_and(|u| u.password.is_some()) {
+            MfaRecoveryCode::generate_if_missing(&id)
+                .await?
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        let email = user.map(|user| user.email).unwrap_or_else(|| id.clone());
+        Event::mfa_enrolled(email, real_ip_from_req(&req)?.to_string())
+            .with_user_id(id)
+            .send(&data.tx_events)
+            .await?;
+
+        Ok(HttpResponse::Created().json(MfaRecoveryCodesResponse { codes }))
     }
 }
 
@@ -1109,6 +3007,8 @@ pub async fn put_user_webid_data(
     principal: ReqPrincipal,
     payload: Json<WebIdRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
+
     // check if webid's are enabled globally
     if !*ENABLE_WEB_ID {
         return Ok(HttpResponse::MethodNotAllowed().finish());
@@ -1228,20 +3128,26 @@ pub async fn put_user_by_id(
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)?;
 
+    let id = id.into_inner();
+    let before = User::find(id.clone())
+        .await?
+        .into_response(UserValues::find(&id).await?);
     let (user, user_values, is_new_admin) =
-        User::update(&data, id.into_inner(), user.into_inner(), None).await?;
+        User::update(&data, id, user.into_inner(), None).await?;
+    let resp = user.clone().into_response(user_values);
+    AuditLog::updated(&principal, &req, "user", &user.id, &before, &resp).await;
 
     if is_new_admin {
         data.tx_events
-            .send_async(Event::new_rauthy_admin(
-                user.email.clone(),
-                real_ip_from_req(&req)?.to_string(),
-            ))
+            .send_async(
+                Event::new_rauthy_admin(user.email.clone(), real_ip_from_req(&req)?.to_string())
+                    .with_user_id(user.id.clone()),
+            )
             .await
             .unwrap();
     }
 
-    Ok(HttpResponse::Ok().json(user.into_response(user_values)))
+    Ok(HttpResponse::Ok().json(resp))
 }
 
 /// Allows modification of specific user values from the user himself
@@ -1266,6 +3172,7 @@ pub async fn put_user_self(
     principal: ReqPrincipal,
     user: Json<UpdateUserSelfRequest>,
 ) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
     principal.validate_session_auth()?;
 
     // make sure the logged in user can only update itself
@@ -1300,6 +3207,7 @@ pub async fn post_user_self_convert_passkey(
     id: web::Path<String>,
     principal: ReqPrincipal,
 ) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_not_impersonated()?;
     principal.validate_session_auth()?;
 
     // make sure the logged in user can only update itself
@@ -1328,10 +3236,177 @@ pub async fn post_user_self_convert_passkey(
 pub async fn delete_user_by_id(
     path: web::Path<String>,
     principal: ReqPrincipal,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ErrorResponse> {
     principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Delete)?;
 
     let user = User::find(path.into_inner()).await?;
     user.delete().await?;
+    let before = user.clone().into_response(None);
+    AuditLog::deleted(&principal, &req, "user", &user.id, &before).await;
+
     Ok(HttpResponse::NoContent().finish())
 }
+
+/// Restores a soft-deleted user
+///
+/// Deleted users are only tombstoned and can be restored until they are purged after
+/// `USER_DELETE_GRACE_DAYS`. Sessions and refresh tokens are not restored.
+/// The E-Mail is freed up on delete and the restore fails, if it has been taken in the meantime.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/{id}/restore",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserResponse),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/restore")]
+pub async fn post_user_restore(
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)?;
+
+    let user = User::restore(path.into_inner()).await?;
+    AuditLog::action(
+        &principal,
+        &req,
+        AuditAction::Update,
+        "user_restore",
+        &user.id,
+    )
+    .await;
+    let values = UserValues::find(&user.id).await?;
+
+    Ok(HttpResponse::Ok().json(user.into_response(values)))
+}
+
+/// Unlocks a user
+///
+/// Resets the failed login counter, which lifts a lockout after too many failed password logins
+/// immediately. Without this, a locked account unlocks automatically after the lockout duration.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/{id}/unlock",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok", body = UserResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/unlock")]
+pub async fn post_user_unlock(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_api_key_or_admin_session(AccessGroup::Users, AccessRights::Update)?;
+
+    let mut user = User::find(path.into_inner()).await?;
+    user.unlock(&data, &req).await?;
+    AuditLog::action(
+        &principal,
+        &req,
+        AuditAction::Update,
+        "user_unlock",
+        &user.id,
+    )
+    .await;
+    let values = UserValues::find(&user.id).await?;
+
+    Ok(HttpResponse::Ok().json(user.into_response(values)))
+}
+
+/// Starts an impersonated session for a user
+///
+/// The returned session cookie replaces the current session in the browser. All tokens issued
+/// for this session contain an `act` claim with the ID of the admin, so downstream applications
+/// can tell them apart. Refresh tokens are never issued for impersonated sessions.
+/// The session can only be used to look at the account. All account modifications, like
+/// changing the email, the password or any MFA device, are rejected with a `403`.
+///
+/// Other admins cannot be impersonated.
+///
+/// **Permissions**
+/// - rauthy_admin
+#[utoipa::path(
+    post,
+    path = "/users/{id}/impersonate",
+    tag = "users",
+    responses(
+        (status = 200, description = "Ok"),
+        (status = 400, description = "BadRequest", body = ErrorResponse),
+        (status = 401, description = "Unauthorized", body = ErrorResponse),
+        (status = 403, description = "Forbidden", body = ErrorResponse),
+        (status = 404, description = "NotFound", body = ErrorResponse),
+    ),
+)]
+#[post("/users/{id}/impersonate")]
+pub async fn post_user_impersonate(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    principal: ReqPrincipal,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    principal.validate_admin_session()?;
+    let admin_id = principal.user_id()?.to_string();
+
+    let user = User::find(path.into_inner()).await?;
+    if user.id == admin_id {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "You cannot impersonate yourself",
+        ));
+    }
+    if user.is_admin() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "Rauthy admins cannot be impersonated",
+        ));
+    }
+    user.check_enabled()?;
+    user.check_expired()?;
+
+    let ip = real_ip_from_req(&req)?;
+    let mut session = Session::try_new(&user, *SESSION_LIFETIME, Some(ip.to_string()))?;
+    session.state = SessionState::Auth.as_str().to_string();
+    session.impersonator_id = Some(admin_id.clone());
+    session.save().await?;
+
+    AuditLog::action(
+        &principal,
+        &req,
+        AuditAction::Create,
+        "user_impersonation",
+        &user.id,
+    )
+    .await;
+    Event::user_impersonated(
+        format!("Admin {} impersonates user {}", admin_id, user.email),
+        ip.to_string(),
+    )
+    .with_user_id(user.id)
+    .send(&data.tx_events)
+    .await?;
+
+    Ok(HttpResponse::Ok()
+        .cookie(session.client_cookie())
+        .cookie(session.client_cookie_session_state())
+        .insert_header(Session::get_csrf_header(&session.csrf_token))
+        .finish())
+}
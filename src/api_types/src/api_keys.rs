@@ -1,4 +1,4 @@
-use rauthy_common::constants::RE_API_KEY;
+use rauthy_common::constants::{RE_API_KEY, RE_MFA_CODE};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use validator::Validate;
@@ -46,6 +46,29 @@ pub struct ApiKeyRequest {
     pub access: Vec<ApiKeyAccess>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ApiKeyExchangeRequest {
+    /// The `code` returned from a successful WebAuthn authentication with the purpose
+    /// `ApiKeyExchange`
+    ///
+    /// Validation: `^[a-zA-Z0-9]{48}$`
+    #[validate(regex(path = "*RE_MFA_CODE", code = "^[a-zA-Z0-9]{48}$"))]
+    pub mfa_code: String,
+    /// Lifetime of the new API Key in seconds. Capped at `API_KEY_EXCHANGE_TTL`.
+    #[validate(range(min = 60))]
+    pub ttl: Option<u32>,
+    pub access: Vec<ApiKeyAccess>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyExchangeResponse {
+    pub name: String,
+    /// The full API Key in the format `<name>$<secret>`
+    pub secret: String,
+    /// Unix timestamp in seconds
+    pub expires: i64,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct ApiKeysResponse {
     pub keys: Vec<ApiKeyResponse>,
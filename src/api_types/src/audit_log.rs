@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct AuditLogParams {
+    /// Validation: `1 <= limit <= 1000`
+    #[validate(range(min = 1, max = 1000))]
+    pub limit: Option<u16>,
+    /// If given, only entries with a lower `seq` will be returned, which can be used for
+    /// pagination.
+    pub before_seq: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditLogEntryResponse {
+    pub seq: i64,
+    /// Unix timestamp in milliseconds
+    pub timestamp: i64,
+    /// Either the `user_id` of the admin or `api_key$<name>`
+    pub actor: String,
+    /// `create` | `update` | `delete`
+    pub action: String,
+    pub entity: String,
+    pub entity_id: String,
+    /// All changed values in the form of `{ "key": { "before": _, "after": _ } }`
+    #[schema(value_type = Object)]
+    pub diff: serde_json::Value,
+    pub request_id: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditLogVerifyResponse {
+    pub valid: bool,
+    /// The amount of entries, that have been validated successfully
+    pub entries: u64,
+    /// The `seq` of the latest entry, if the chain is valid
+    pub head_seq: Option<i64>,
+    /// The `hash` of the latest entry, if the chain is valid. It can be stored externally to
+    /// detect a truncation of the log later on.
+    pub head_hash: Option<String>,
+    /// The `seq` of the first entry that broke the chain
+    pub invalid_seq: Option<i64>,
+    pub error: Option<String>,
+}
@@ -1,7 +1,7 @@
 use crate::cust_validation::validate_vec_scopes;
 use rauthy_common::constants::{
-    RE_ALNUM, RE_CLIENT_ID_EPHEMERAL, RE_CLIENT_NAME, RE_CODE_CHALLENGE, RE_PEM, RE_SCOPE_SPACE,
-    RE_URI,
+    RE_ALNUM, RE_CLIENT_ID_EPHEMERAL, RE_CLIENT_NAME, RE_CODE_CHALLENGE, RE_PEM, RE_RESPONSE_MODE,
+    RE_SCOPE_SPACE, RE_URI,
 };
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -111,6 +111,21 @@ pub struct ProviderLoginRequest {
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub state: Option<String>,
+    /// Validation: `^[a-z_.]{3,16}$`
+    #[validate(regex(path = "*RE_RESPONSE_MODE", code = "^[a-z_.]{3,16}$"))]
+    pub response_mode: Option<String>,
+    /// The target resource (RFC 8707) and audience of the access token
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub resource: Option<String>,
+    /// The OIDC `claims` request parameter as JSON
+    /// Validation: max length is 4096
+    #[validate(length(max = 4096))]
+    pub claims: Option<String>,
+    /// Space separated list of requested Authentication Context Class References
+    /// Validation: `[a-z0-9-_/:\s*]{0,512}`
+    #[validate(regex(path = "*RE_SCOPE_SPACE", code = "[a-z0-9-_/:\\s*]{0,512}"))]
+    pub acr_values: Option<String>,
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub nonce: Option<String>,
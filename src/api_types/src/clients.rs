@@ -2,15 +2,29 @@ use crate::cust_validation::*;
 use crate::oidc::JwkKeyPairAlg;
 use css_color::Srgb;
 use rauthy_common::constants::{
-    RE_CLIENT_ID_EPHEMERAL, RE_CLIENT_NAME, RE_LOWERCASE, RE_SCOPE_SPACE,
+    RE_CLIENT_AUTH_METHOD, RE_CLIENT_ID_EPHEMERAL, RE_CLIENT_NAME, RE_JWE_ALG, RE_JWE_ENC,
+    RE_LOWERCASE, RE_SCOPE_SPACE, RE_SUBJECT_TYPE, RE_TLS_CLIENT_AUTH,
     RE_TOKEN_ENDPOINT_AUTH_METHOD, RE_URI,
 };
 use rauthy_error::ErrorResponse;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::str::FromStr;
 use utoipa::ToSchema;
 use validator::Validate;
 
+/// Restricts which users may log in to a client. A user is allowed, if it is listed in `users`,
+/// or if it is an effective member of any of the `groups`. Empty lists remove the restriction.
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ClientAccessPolicyRequest {
+    /// User IDs
+    #[validate(length(max = 256))]
+    pub users: Vec<String>,
+    /// Group IDs
+    #[validate(length(max = 128))]
+    pub groups: Vec<String>,
+}
+
 #[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct ColorsRequest {
     #[validate(length(min = 2, max = 32))]
@@ -78,7 +92,7 @@ pub struct DynamicClientRequest {
     /// Validation: `Vec<^[a-zA-Z0-9\+.@/]{0,48}$>`
     #[validate(custom(function = "validate_vec_contact"))]
     pub contacts: Option<Vec<String>>,
-    /// Validation: `^(RS256|RS384|RS512|EdDSA)$`
+    /// Validation: `^(RS256|RS384|RS512|ES384|EdDSA)$`
     pub id_token_signed_response_alg: Option<JwkKeyPairAlg>,
     /// Validation: `^(client_secret_post|client_secret_basic|none)$`
     #[validate(regex(
@@ -86,8 +100,10 @@ pub struct DynamicClientRequest {
         code = "client_secret_post|client_secret_basic|none"
     ))]
     pub token_endpoint_auth_method: Option<String>,
-    /// Validation: `^(RS256|RS384|RS512|EdDSA)$`
+    /// Validation: `^(RS256|RS384|RS512|ES384|EdDSA)$`
     pub token_endpoint_auth_signing_alg: Option<JwkKeyPairAlg>,
+    /// Validation: `^(RS256|RS384|RS512|ES384|EdDSA)$`
+    pub userinfo_signed_response_alg: Option<JwkKeyPairAlg>,
     // Rauthy will only accept the following defaults
     // `response_type=code`
     // `subject_type=public`
@@ -108,7 +124,6 @@ pub struct DynamicClientRequest {
     // - sector_identifier_uri
     // - id_token_encrypted_response_alg
     // - id_token_encrypted_response_enc
-    // - userinfo_signed_response_alg
     // - userinfo_encrypted_response_alg
     // - userinfo_encrypted_response_enc
     // - request_object_signing_alg
@@ -158,9 +173,9 @@ pub struct EphemeralClientRequest {
     pub scope: Option<String>,
     pub require_auth_time: Option<bool>,
 
-    /// Validation: `^(RS256|RS384|RS512|EdDSA)$`
+    /// Validation: `^(RS256|RS384|RS512|ES384|EdDSA)$`
     pub access_token_signed_response_alg: Option<JwkKeyPairAlg>,
-    /// Validation: `^(RS256|RS384|RS512|EdDSA)$`
+    /// Validation: `^(RS256|RS384|RS512|ES384|EdDSA)$`
     pub id_token_signed_response_alg: Option<JwkKeyPairAlg>,
 }
 
@@ -210,9 +225,9 @@ pub struct UpdateClientRequest {
     /// Validation: `Vec<^(authorization_code|client_credentials|password|refresh_token)$>`
     #[validate(custom(function = "validate_vec_grant_types"))]
     pub flows_enabled: Vec<String>,
-    /// Validation: `^(RS256|RS384|RS512|EdDSA)$`
+    /// Validation: `^(RS256|RS384|RS512|ES384|EdDSA)$`
     pub access_token_alg: JwkKeyPairAlg,
-    /// Validation: `^(RS256|RS384|RS512|EdDSA)$`
+    /// Validation: `^(RS256|RS384|RS512|ES384|EdDSA)$`
     pub id_token_alg: JwkKeyPairAlg,
     /// Validation: `10 <= auth_code_lifetime <= 300`
     #[validate(range(min = 10, max = 300))]
@@ -236,6 +251,163 @@ pub struct UpdateClientRequest {
     /// Validation: `Vec<^[a-zA-Z0-9\+.@/]{0,48}$>`
     #[validate(custom(function = "validate_vec_contact"))]
     pub contacts: Option<Vec<String>>,
+    /// Allows JWT secured authorization responses (JARM) signed with this algorithm.
+    /// Validation: `^(RS256|RS384|RS512|ES384|EdDSA)$`
+    pub auth_response_alg: Option<JwkKeyPairAlg>,
+    /// A JSON Web Key Set with the clients public keys, which will be used to validate signed
+    /// request objects.
+    /// Validation: `length(max = 16384)`
+    #[validate(length(max = 16384))]
+    pub jwks: Option<String>,
+    /// Allowed `request_uri`s to fetch request objects from.
+    /// Validation: `Vec<^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]+$>`
+    #[validate(custom(function = "validate_vec_uri"))]
+    pub request_uris: Option<Vec<String>>,
+    /// Will be loaded in a hidden iframe with `iss` and `sid` during logout.
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub frontchannel_logout_uri: Option<String>,
+    /// Receives a signed `logout_token` via POST when a session ends.
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub backchannel_logout_uri: Option<String>,
+    /// The only accepted client authentication at the token endpoint. If not set, only the
+    /// `client_secret` via `client_secret_basic` or `client_secret_post` is accepted.
+    /// Validation: `^(client_secret_basic|client_secret_post|client_secret_jwt|private_key_jwt)$`
+    #[validate(regex(
+        path = "*RE_CLIENT_AUTH_METHOD",
+        code = "^(client_secret_basic|client_secret_post|client_secret_jwt|private_key_jwt)$"
+    ))]
+    pub token_endpoint_auth_method: Option<String>,
+    /// mTLS client authentication (RFC 8705), which replaces the `client_secret`.
+    /// Validation: `^(tls_client_auth|self_signed_tls_client_auth)$`
+    #[validate(regex(
+        path = "*RE_TLS_CLIENT_AUTH",
+        code = "^(tls_client_auth|self_signed_tls_client_auth)$"
+    ))]
+    pub tls_client_auth: Option<String>,
+    /// The expected subject DN of the client certificate with `tls_client_auth`
+    /// Validation: max length is 512
+    #[validate(length(max = 512))]
+    pub tls_client_auth_subject_dn: Option<String>,
+    /// Resources (RFC 8707), which may be requested as the audience of access tokens.
+    /// Validation: `Vec<^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]+$>`
+    #[validate(custom(function = "validate_vec_uri"))]
+    pub allowed_resources: Option<Vec<String>>,
+    /// Will be fetched for `private_key_jwt` client authentication, if no `jwks` is registered.
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub jwks_uri: Option<String>,
+    /// Validation: `^(public|pairwise)$`
+    #[validate(regex(path = "*RE_SUBJECT_TYPE", code = "^(public|pairwise)$"))]
+    pub subject_type: Option<String>,
+    /// Groups clients with redirect URIs on different hosts into a single sector for pairwise
+    /// subjects. It must return a JSON array containing all `redirect_uris` of the client.
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub sector_identifier_uri: Option<String>,
+    /// Issues opaque access tokens, which can only be resolved via the introspection endpoint,
+    /// instead of JWTs.
+    #[serde(default)]
+    pub opaque_access_tokens: bool,
+    /// Encrypts ID tokens with a key from the clients `jwks` or `jwks_uri`. Must be given
+    /// together with `id_token_encrypted_response_enc`.
+    /// Validation: `^(RSA-OAEP|RSA-OAEP-256)$`
+    #[validate(regex(path = "*RE_JWE_ALG", code = "^(RSA-OAEP|RSA-OAEP-256)$"))]
+    pub id_token_encrypted_response_alg: Option<String>,
+    /// Validation: `^(A128GCM|A256GCM)$`
+    #[validate(regex(path = "*RE_JWE_ENC", code = "^(A128GCM|A256GCM)$"))]
+    pub id_token_encrypted_response_enc: Option<String>,
+    /// Returns the userinfo as a signed and encrypted JWT. Must be given together with
+    /// `userinfo_encrypted_response_enc`.
+    /// Validation: `^(RSA-OAEP|RSA-OAEP-256)$`
+    #[validate(regex(path = "*RE_JWE_ALG", code = "^(RSA-OAEP|RSA-OAEP-256)$"))]
+    pub userinfo_encrypted_response_alg: Option<String>,
+    /// Validation: `^(A128GCM|A256GCM)$`
+    #[validate(regex(path = "*RE_JWE_ENC", code = "^(A128GCM|A256GCM)$"))]
+    pub userinfo_encrypted_response_enc: Option<String>,
+    /// Returns the userinfo as a signed JWT instead of plain JSON.
+    /// Validation: `^(RS256|RS384|RS512|ES384|EdDSA)$`
+    pub userinfo_signed_response_alg: Option<JwkKeyPairAlg>,
+    /// If `false`, the same refresh token will be returned on each use until it expires.
+    #[serde(default = "default_true")]
+    pub refresh_token_rotation: bool,
+    /// Overwrites the global `REFRESH_TOKEN_GRACE_TIME` in seconds, during which a rotated
+    /// refresh token can still be used.
+    /// Validation: `0 <= refresh_token_grace_time <= 300`
+    #[validate(range(min = 0, max = 300))]
+    pub refresh_token_grace_time: Option<i32>,
+    /// Revokes the whole refresh token family and creates a `Critical` event, if a rotated refresh
+    /// token is used after the grace time.
+    #[serde(default)]
+    pub refresh_token_reuse_detection: bool,
+    /// Users must grant the requested scopes on a consent screen during the login. Granted
+    /// scopes are remembered and the screen is skipped as long as no new scopes are requested.
+    #[serde(default)]
+    pub consent_required: bool,
+    /// Overwrites the `access_token_lifetime` for ID tokens in seconds.
+    /// Validation: `10 <= id_token_lifetime <= 86400`
+    #[validate(range(min = 10, max = 86400))]
+    pub id_token_lifetime: Option<i32>,
+    /// Overwrites the global `REFRESH_TOKEN_LIFETIME` and `DEVICE_GRANT_REFRESH_TOKEN_LIFETIME`
+    /// in seconds.
+    /// Validation: `60 <= refresh_token_lifetime <= 31536000`
+    #[validate(range(min = 60, max = 31536000))]
+    pub refresh_token_lifetime: Option<i32>,
+    #[validate(custom(function = "validate_claim_template"))]
+    pub claim_template: Option<ClaimTemplate>,
+    /// Max age of a session in seconds to be accepted for this client. Can only be stricter than
+    /// the global `SESSION_LIFETIME`.
+    /// Validation: `60 <= session_lifetime <= 2592000`
+    #[validate(range(min = 60, max = 2592000))]
+    pub session_lifetime: Option<i32>,
+    /// Max idle time of a session in seconds to be accepted for this client. Can only be stricter
+    /// than the global `SESSION_TIMEOUT`.
+    /// Validation: `60 <= session_timeout <= 2592000`
+    #[validate(range(min = 60, max = 2592000))]
+    pub session_timeout: Option<i32>,
+    /// Rejects passkey logins from another device via hybrid transport (caBLE), like scanning a
+    /// QR code with a phone, and creates a `PasskeyHybridDenied` event instead.
+    #[serde(default)]
+    pub deny_hybrid_passkeys: bool,
+    /// Requires PKCE with `S256` at the authorize and token endpoints and overwrites the
+    /// `challenges`.
+    #[serde(default)]
+    pub require_pkce_s256: bool,
+    /// The `response_type`s this client may use at the authorize endpoint.
+    /// Validation: `Vec<^(code)$>`
+    #[serde(default = "default_response_types")]
+    #[validate(custom(function = "validate_vec_response_types"))]
+    pub response_types: Vec<String>,
+    /// Only accepts authorization requests, which are passed inside a signed request object
+    /// with `request` or `request_uri`. Needs a registered `jwks`.
+    #[serde(default)]
+    pub require_signed_request_object: bool,
+}
+
+/// Modifies the access and ID token claims of a client at issuance time. Claims are dropped
+/// first, then renamed, and the static claims are added last. Claims Rauthy needs to validate its
+/// own tokens, like `sub`, `typ` or `azp`, cannot be modified.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize, Validate, ToSchema)]
+pub struct ClaimTemplate {
+    /// Static claims added to each token with a max serialized size of 4 kB.
+    #[serde(default, rename = "static")]
+    pub static_claims: HashMap<String, serde_json::Value>,
+    /// `current claim name` -> `new claim name`
+    #[serde(default)]
+    pub rename: HashMap<String, String>,
+    #[serde(default)]
+    pub drop: Vec<String>,
+}
+
+/// Without a policy, `restricted` is `false` and every active user may log in to the client.
+/// A restricted policy with empty `users` and `groups` denies every login. This happens, when all
+/// of its users and groups have been deleted in the meantime.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ClientAccessPolicyResponse {
+    pub restricted: bool,
+    pub users: Vec<String>,
+    pub groups: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -262,6 +434,37 @@ pub struct ClientResponse {
     pub force_mfa: bool,
     pub client_uri: Option<String>,
     pub contacts: Option<Vec<String>>,
+    pub auth_response_alg: Option<JwkKeyPairAlg>,
+    pub jwks: Option<String>,
+    pub request_uris: Option<Vec<String>>,
+    pub frontchannel_logout_uri: Option<String>,
+    pub backchannel_logout_uri: Option<String>,
+    pub token_endpoint_auth_method: Option<String>,
+    pub tls_client_auth: Option<String>,
+    pub tls_client_auth_subject_dn: Option<String>,
+    pub allowed_resources: Option<Vec<String>>,
+    pub jwks_uri: Option<String>,
+    pub subject_type: Option<String>,
+    pub sector_identifier_uri: Option<String>,
+    pub opaque_access_tokens: bool,
+    pub id_token_encrypted_response_alg: Option<String>,
+    pub id_token_encrypted_response_enc: Option<String>,
+    pub userinfo_encrypted_response_alg: Option<String>,
+    pub userinfo_encrypted_response_enc: Option<String>,
+    pub userinfo_signed_response_alg: Option<JwkKeyPairAlg>,
+    pub refresh_token_rotation: bool,
+    pub refresh_token_grace_time: Option<i32>,
+    pub refresh_token_reuse_detection: bool,
+    pub consent_required: bool,
+    pub id_token_lifetime: Option<i32>,
+    pub refresh_token_lifetime: Option<i32>,
+    pub claim_template: Option<ClaimTemplate>,
+    pub session_lifetime: Option<i32>,
+    pub session_timeout: Option<i32>,
+    pub deny_hybrid_passkeys: bool,
+    pub require_pkce_s256: bool,
+    pub response_types: Vec<String>,
+    pub require_signed_request_object: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -271,6 +474,14 @@ pub struct ClientSecretResponse {
     pub secret: Option<String>,
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ClientRevokeAllResponse {
+    pub id: String,
+    /// Count of users, who had active refresh tokens or sessions for this client and whose
+    /// sessions have been invalidated
+    pub users: usize,
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct DynamicClientResponse {
     pub client_id: String,
@@ -303,10 +514,20 @@ pub struct DynamicClientResponse {
 
     pub grant_types: Vec<String>,
     pub id_token_signed_response_alg: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub userinfo_signed_response_alg: Option<String>,
     pub token_endpoint_auth_method: String,
     pub token_endpoint_auth_signing_alg: String,
 }
 
+fn default_true() -> bool {
+    true
+}
+
+fn default_response_types() -> Vec<String> {
+    vec!["code".to_string()]
+}
+
 #[cfg(test)]
 mod tests {
     use crate::clients::ColorsRequest;
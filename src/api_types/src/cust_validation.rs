@@ -1,8 +1,41 @@
+use crate::clients::ClaimTemplate;
 use rauthy_common::constants::{
-    RE_ATTR, RE_CHALLENGE, RE_CONTACT, RE_GRANT_TYPES, RE_GROUPS, RE_ORIGIN, RE_URI,
+    CLAIMS_PROTECTED, RESPONSE_TYPES_SUPPORTED, RE_ATTR, RE_CHALLENGE, RE_CONTACT, RE_GRANT_TYPES,
+    RE_GROUPS, RE_ORIGIN, RE_URI,
 };
 use validator::ValidationError;
 
+pub fn validate_claim_template(value: &ClaimTemplate) -> Result<(), ValidationError> {
+    let names = value
+        .static_claims
+        .keys()
+        .chain(value.rename.keys())
+        .chain(value.rename.values())
+        .chain(value.drop.iter());
+
+    for name in names {
+        if !RE_ATTR.is_match(name) {
+            return Err(ValidationError::new("^[a-z0-9-_/]{2,128}$"));
+        }
+        if CLAIMS_PROTECTED.contains(&name.as_str()) {
+            return Err(ValidationError::new(
+                "protected claims cannot be modified by a claim template",
+            ));
+        }
+    }
+
+    let size = serde_json::to_vec(&value.static_claims)
+        .map(|b| b.len())
+        .unwrap_or(usize::MAX);
+    if size > 4096 {
+        return Err(ValidationError::new(
+            "static claims must have a max serialized size of 4 kB",
+        ));
+    }
+
+    Ok(())
+}
+
 pub fn validate_vec_attr(value: &[String]) -> Result<(), ValidationError> {
     let mut err = None;
 
@@ -41,6 +74,16 @@ pub fn validate_vec_challenge(value: &[String]) -> Result<(), ValidationError> {
     Ok(())
 }
 
+pub fn validate_vec_response_types(value: &[String]) -> Result<(), ValidationError> {
+    if value
+        .iter()
+        .any(|v| !RESPONSE_TYPES_SUPPORTED.contains(&v.as_str()))
+    {
+        return Err(ValidationError::new("^(code)$"));
+    }
+    Ok(())
+}
+
 pub fn validate_vec_contact(value: &[String]) -> Result<(), ValidationError> {
     let mut err = None;
     value.iter().for_each(|v| {
@@ -1,3 +1,4 @@
+use rauthy_common::constants::{RE_ALNUM, RE_GROUPS};
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
@@ -28,6 +29,22 @@ pub enum EventType {
     UserEmailChange,
     UserPasswordReset,
     Test,
+    MfaFailed,
+    MfaEnrolled,
+    MfaRemoved,
+    ClientCreated,
+    ClientDeleted,
+    ClientSecretRotated,
+    SessionRevoked,
+    UserImpersonated,
+    UserExpired,
+    UserLocked,
+    UserUnlocked,
+    RefreshTokenReused,
+    SessionLimitReached,
+    SessionAnomaly,
+    PasskeyHybridLogin,
+    PasskeyHybridDenied,
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
@@ -48,4 +65,22 @@ pub struct EventsRequest {
     pub until: Option<i64>,
     pub level: EventLevel,
     pub typ: Option<EventType>,
+    /// If given, only events about users, which are currently inside this group, will be
+    /// returned. Mandatory for delegated admins, which may only query groups they manage.
+    ///
+    /// Validation: `^[a-z0-9-_/,:*]{2,64}$`
+    #[validate(regex(path = "*RE_GROUPS", code = "^[a-z0-9-_/,:*]{2,64}$"))]
+    pub group: Option<String>,
+    /// If given, the result will be paginated with a stable ascending `(timestamp, id)` ordering.
+    /// The token for the next page will be returned in the `x-continuation-token` header.
+    ///
+    /// Validation: `1 <= limit <= 1000`
+    #[validate(range(min = 1, max = 1000))]
+    pub limit: Option<u16>,
+    /// The `x-continuation-token` from a previous paginated request. Only events after this
+    /// position will be returned. If no `limit` is given, it defaults to `100`.
+    ///
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub continuation_token: Option<String>,
 }
@@ -8,4 +8,27 @@ pub struct NewGroupRequest {
     /// Validation: `^[a-z0-9-_/,:*]{2,64}$`
     #[validate(regex(path = "*RE_GROUPS", code = "^[a-z0-9-_/,:*]{2,64}$"))]
     pub group: String,
+    /// Members of this group must have a 2nd factor enrolled before they can log in to any
+    /// client. Will not be changed on updates, if not given.
+    pub force_mfa: Option<bool>,
+}
+
+/// Members of all `children` groups are effectively members of this group as well and all
+/// effective members get the `roles` in their tokens.
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct GroupInheritanceRequest {
+    /// Group IDs
+    #[validate(length(max = 128))]
+    pub children: Vec<String>,
+    /// Role IDs
+    #[validate(length(max = 128))]
+    pub roles: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GroupInheritanceResponse {
+    /// Group IDs
+    pub children: Vec<String>,
+    /// Role IDs
+    pub roles: Vec<String>,
 }
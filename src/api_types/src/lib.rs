@@ -1,4 +1,5 @@
 pub mod api_keys;
+pub mod audit_log;
 pub mod auth_providers;
 pub mod blacklist;
 pub mod clients;
@@ -7,8 +8,10 @@ pub mod events;
 pub mod fed_cm;
 pub mod generic;
 pub mod groups;
+pub mod login_stats;
 pub mod oidc;
 pub mod roles;
+pub mod scim;
 pub mod scopes;
 pub mod sessions;
 pub mod users;
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct LoginStatsParams {
+    /// Unix timestamp in seconds, defaults to 24 hours ago
+    #[validate(range(min = 1719784800))]
+    pub from: Option<i64>,
+    /// Unix timestamp in seconds, defaults to now
+    #[validate(range(min = 1719784800))]
+    pub until: Option<i64>,
+    /// The maximum amount of top IP prefixes and users.
+    ///
+    /// Validation: `1 <= limit <= 100`
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<u16>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LoginStatsResponse {
+    /// Totals for each hour inside the range, ordered ascending
+    pub hours: Vec<LoginStatsHourResponse>,
+    /// The most active IP prefixes (`/24` for IPv4, `/48` for IPv6) inside the range
+    pub ip_prefixes: Vec<LoginStatsTopResponse>,
+    /// The most targeted users inside the range, identified by the given login name
+    pub users: Vec<LoginStatsTopResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LoginStatsHourResponse {
+    /// Unix timestamp in seconds for the start of the hour
+    pub hour: i64,
+    pub failed: i64,
+    pub blacklisted: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LoginStatsTopResponse {
+    pub subject: String,
+    pub failed: i64,
+    pub blacklisted: i64,
+}
@@ -1,13 +1,14 @@
 use crate::cust_validation::validate_vec_scopes;
 use crate::generic::PasswordPolicyResponse;
 use crate::sessions::SessionState;
+use crate::users::{UserAttrValueRequest, WebauthnAuthFinishRequest};
 use actix_web::http::header;
 use actix_web::HttpRequest;
 use rauthy_common::constants::{
-    RE_ALNUM, RE_CLIENT_ID_EPHEMERAL, RE_CODE_VERIFIER, RE_GRANT_TYPES, RE_LOWERCASE,
-    RE_SCOPE_SPACE, RE_URI,
+    RE_ALNUM, RE_CLIENT_ASSERTION_TYPE, RE_CLIENT_ID_EPHEMERAL, RE_CODE_VERIFIER, RE_GRANT_TYPES,
+    RE_KID, RE_LOWERCASE, RE_RESPONSE_MODE, RE_SCOPE_SPACE, RE_URI, RE_YUBICO_OTP,
 };
-use rauthy_common::utils::base64_decode;
+use rauthy_common::utils::{base64_decode, base64_url_no_pad_decode};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
@@ -38,12 +39,16 @@ pub struct AuthRequest {
         code = "^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,256}$"
     ))]
     pub client_id: String,
+    /// Required, unless the params are passed with `request` or `request_uri`
+    ///
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
-    pub redirect_uri: String,
+    pub redirect_uri: Option<String>,
+    /// Required, unless the params are passed with `request` or `request_uri`
+    ///
     /// Validation: `[a-z0-9-_/]{2,128}`
     #[validate(regex(path = "*RE_LOWERCASE", code = "[a-z0-9-_/]{2,128}"))]
-    pub response_type: String,
+    pub response_type: Option<String>,
     /// Validation: `[a-z0-9-_/:\s*]{0,512}`
     #[validate(regex(path = "*RE_SCOPE_SPACE", code = "[a-z0-9-_/:\\s*]{0,512}"))]
     #[serde(default = "default_scope")]
@@ -51,6 +56,21 @@ pub struct AuthRequest {
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub state: Option<String>,
+    /// Validation: `^[a-z_.]{3,16}$`
+    #[validate(regex(path = "*RE_RESPONSE_MODE", code = "^[a-z_.]{3,16}$"))]
+    pub response_mode: Option<String>,
+    /// The target resource (RFC 8707) and audience of the access token
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub resource: Option<String>,
+    /// The OIDC `claims` request parameter as JSON
+    /// Validation: max length is 4096
+    #[validate(length(max = 4096))]
+    pub claims: Option<String>,
+    /// Space separated list of requested Authentication Context Class References
+    /// Validation: `[a-z0-9-_/:\s*]{0,512}`
+    #[validate(regex(path = "*RE_SCOPE_SPACE", code = "[a-z0-9-_/:\\s*]{0,512}"))]
+    pub acr_values: Option<String>,
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub code_challenge: Option<String>,
@@ -62,6 +82,14 @@ pub struct AuthRequest {
     /// Validation: `[a-zA-Z0-9]`
     #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
     pub prompt: Option<String>,
+    /// A signed request object (JAR) containing the authorization request params
+    ///
+    /// Validation: `length(max = 16384)`
+    #[validate(length(max = 16384))]
+    pub request: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub request_uri: Option<String>,
 }
 
 fn default_scope() -> String {
@@ -88,6 +116,13 @@ pub struct AuthCodeRequest {
     pub redirect_uri: Option<String>,
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LogoutResponse {
+    /// The `frontchannel_logout_uri`s of all clients inside the session, which should be loaded
+    /// in hidden iframes to log the user out of these clients as well.
+    pub frontchannel_logout_uris: Vec<String>,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct PasswordResetResponse {
     pub csrf_token: String,
@@ -125,6 +160,21 @@ pub struct LoginRequest {
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub state: Option<String>,
+    /// Validation: `^[a-z_.]{3,16}$`
+    #[validate(regex(path = "*RE_RESPONSE_MODE", code = "^[a-z_.]{3,16}$"))]
+    pub response_mode: Option<String>,
+    /// The target resource (RFC 8707) and audience of the access token
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub resource: Option<String>,
+    /// The OIDC `claims` request parameter as JSON
+    /// Validation: max length is 4096
+    #[validate(length(max = 4096))]
+    pub claims: Option<String>,
+    /// Space separated list of requested Authentication Context Class References
+    /// Validation: `[a-z0-9-_/:\s*]{0,512}`
+    #[validate(regex(path = "*RE_SCOPE_SPACE", code = "[a-z0-9-_/:\\s*]{0,512}"))]
+    pub acr_values: Option<String>,
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub nonce: Option<String>,
@@ -134,6 +184,50 @@ pub struct LoginRequest {
     /// Validation: `[a-zA-Z0-9]`
     #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
     pub code_challenge_method: Option<String>,
+    /// The terms of service version, which the user accepted during this login
+    ///
+    /// Validation: `length(max = 64)`
+    #[validate(length(max = 64))]
+    pub tos_accept: Option<String>,
+    /// Values for custom attributes, which are required at login and have been requested with
+    /// a `428` before
+    #[validate(nested)]
+    pub profile_values: Option<Vec<UserAttrValueRequest>>,
+    /// The scopes, which the user granted on the consent screen during this login
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_scopes"))]
+    pub consent_accept: Option<Vec<String>>,
+    /// Opt-in for a long-lived session, which only has an effect with `SESSION_REMEMBER_ME_DAYS`
+    pub remember_me: Option<bool>,
+    /// The current code from the authenticator app, if the user has TOTP enabled
+    ///
+    /// Validation: `length(equal = 6)`
+    #[validate(length(equal = 6))]
+    pub totp: Option<String>,
+    /// A single-use recovery code, which replaces the 2nd factor, if it is not available
+    ///
+    /// Validation: `length(max = 16)`
+    #[validate(length(max = 16))]
+    pub recovery_code: Option<String>,
+    /// The OTP from a Yubico key, if the user has Yubico OTP enabled
+    ///
+    /// Validation: `^[cbdefghijklnrtuv]{32,48}$`
+    #[validate(regex(path = "*RE_YUBICO_OTP", code = "^[cbdefghijklnrtuv]{32,48}$"))]
+    pub yubico_otp: Option<String>,
+    /// The one-time code sent via SMS, if the user has SMS OTP enabled
+    ///
+    /// Validation: `length(equal = 6)`
+    #[validate(length(equal = 6))]
+    pub sms_otp: Option<String>,
+    /// The one-time code sent via E-Mail, if E-Mail OTP is enabled for the user
+    ///
+    /// Validation: `length(equal = 6)`
+    #[validate(length(equal = 6))]
+    pub email_otp: Option<String>,
+    /// Opt-in to skip the 2nd factor on this device in the future, which only has an effect
+    /// with `MFA_TRUSTED_DEVICE_DAYS`
+    pub trust_device: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema)]
@@ -153,6 +247,74 @@ pub struct LoginRefreshRequest {
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub state: Option<String>,
+    /// Validation: `^[a-z_.]{3,16}$`
+    #[validate(regex(path = "*RE_RESPONSE_MODE", code = "^[a-z_.]{3,16}$"))]
+    pub response_mode: Option<String>,
+    /// The target resource (RFC 8707) and audience of the access token
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub resource: Option<String>,
+    /// The OIDC `claims` request parameter as JSON
+    /// Validation: max length is 4096
+    #[validate(length(max = 4096))]
+    pub claims: Option<String>,
+    /// Space separated list of requested Authentication Context Class References
+    /// Validation: `[a-z0-9-_/:\s*]{0,512}`
+    #[validate(regex(path = "*RE_SCOPE_SPACE", code = "[a-z0-9-_/:\\s*]{0,512}"))]
+    pub acr_values: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub nonce: Option<String>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub code_challenge: Option<String>,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub code_challenge_method: Option<String>,
+    /// The scopes, which the user granted on the consent screen
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_scopes"))]
+    pub consent_accept: Option<Vec<String>>,
+}
+
+/// A login with a discoverable passkey, where the user is identified by the passkey itself
+/// instead of an E-Mail, like with the passkey autofill on the login page.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct LoginPasskeyRequest {
+    /// The `code` from the `/oidc/authorize/passkey/start` and the signed challenge
+    #[validate(nested)]
+    pub passkey: WebauthnAuthFinishRequest,
+    /// Validation: `^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,128}$`
+    #[validate(regex(
+        path = "*RE_CLIENT_ID_EPHEMERAL",
+        code = "^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,128}$"
+    ))]
+    pub client_id: String,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub redirect_uri: String,
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_scopes"))]
+    pub scopes: Option<Vec<String>>,
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub state: Option<String>,
+    /// Validation: `^[a-z_.]{3,16}$`
+    #[validate(regex(path = "*RE_RESPONSE_MODE", code = "^[a-z_.]{3,16}$"))]
+    pub response_mode: Option<String>,
+    /// The target resource (RFC 8707) and audience of the access token
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub resource: Option<String>,
+    /// The OIDC `claims` request parameter as JSON
+    /// Validation: max length is 4096
+    #[validate(length(max = 4096))]
+    pub claims: Option<String>,
+    /// Space separated list of requested Authentication Context Class References
+    /// Validation: `[a-z0-9-_/:\s*]{0,512}`
+    #[validate(regex(path = "*RE_SCOPE_SPACE", code = "[a-z0-9-_/:\\s*]{0,512}"))]
+    pub acr_values: Option<String>,
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub nonce: Option<String>,
@@ -162,6 +324,54 @@ pub struct LoginRefreshRequest {
     /// Validation: `[a-zA-Z0-9]`
     #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
     pub code_challenge_method: Option<String>,
+    /// The terms of service version, which the user accepted during this login
+    ///
+    /// Validation: `length(max = 64)`
+    #[validate(length(max = 64))]
+    pub tos_accept: Option<String>,
+    /// Values for custom attributes, which are required at login and have been requested with
+    /// a `428` before
+    #[validate(nested)]
+    pub profile_values: Option<Vec<UserAttrValueRequest>>,
+    /// The scopes, which the user granted on the consent screen during this login
+    ///
+    /// Validation: `Vec<^[a-z0-9-_/,:*]{2,64}$>`
+    #[validate(custom(function = "validate_vec_scopes"))]
+    pub consent_accept: Option<Vec<String>>,
+    /// Opt-in for a long-lived session, which only has an effect with `SESSION_REMEMBER_ME_DAYS`
+    pub remember_me: Option<bool>,
+}
+
+impl LoginPasskeyRequest {
+    /// Converts this request into a regular `LoginRequest` without a password for the user,
+    /// who has been identified by the passkey.
+    pub fn into_login_request(self, email: String) -> LoginRequest {
+        LoginRequest {
+            email,
+            password: None,
+            client_id: self.client_id,
+            redirect_uri: self.redirect_uri,
+            scopes: self.scopes,
+            state: self.state,
+            response_mode: self.response_mode,
+            resource: self.resource,
+            claims: self.claims,
+            acr_values: self.acr_values,
+            nonce: self.nonce,
+            code_challenge: self.code_challenge,
+            code_challenge_method: self.code_challenge_method,
+            tos_accept: self.tos_accept,
+            profile_values: self.profile_values,
+            consent_accept: self.consent_accept,
+            remember_me: self.remember_me,
+            totp: None,
+            recovery_code: None,
+            yubico_otp: None,
+            sms_otp: None,
+            email_otp: None,
+            trust_device: None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
@@ -242,48 +452,194 @@ pub struct TokenRequest {
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub refresh_token: Option<String>,
+    /// The target resource (RFC 8707) and audience of the access token
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub resource: Option<String>,
+    /// A signed JWT for `private_key_jwt` or `client_secret_jwt` client authentication
+    /// Validation: max length is 8192
+    #[validate(length(max = 8192))]
+    pub client_assertion: Option<String>,
+    /// Validation: `^urn:ietf:params:oauth:client-assertion-type:jwt-bearer$`
+    #[validate(regex(
+        path = "*RE_CLIENT_ASSERTION_TYPE",
+        code = "^urn:ietf:params:oauth:client-assertion-type:jwt-bearer$"
+    ))]
+    pub client_assertion_type: Option<String>,
 }
 
 impl TokenRequest {
+    /// Returns the `client_assertion`, if one has been given with the correct type.
+    pub fn client_assertion(&self) -> Result<Option<&str>, ErrorResponse> {
+        client_assertion(
+            self.client_assertion.as_deref(),
+            self.client_assertion_type.as_deref(),
+        )
+    }
+
     // by RFC, the client auth can be either sent inside the POST body, or as an Authorization header
     pub fn try_get_client_id_secret(
         &self,
         req: &HttpRequest,
     ) -> Result<(String, Option<String>), ErrorResponse> {
-        let auth_header = req.headers().get(header::AUTHORIZATION).map(|h| {
-            let (_, b64) = h
-                .to_str()
-                .unwrap_or_default()
-                .split_once(' ')
-                .unwrap_or(("", ""));
-            b64
-        });
-
-        if let Some(header) = auth_header {
-            let decoded = String::from_utf8(base64_decode(header)?)?;
-            match decoded.split_once(':') {
-                None => Err(ErrorResponse::new(
-                    ErrorResponseType::BadRequest,
-                    "Bad Authorization header",
-                )),
-                Some((client_id, client_secret)) => {
-                    Ok((client_id.to_string(), Some(client_secret.to_string())))
-                }
+        client_id_secret(
+            req,
+            self.client_id.as_deref(),
+            self.client_secret.as_deref(),
+            self.client_assertion()?,
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct TokenRevocationRequest {
+    /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
+    #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
+    pub token: String,
+    /// `access_token` or `refresh_token` - unknown hints are ignored
+    /// Validation: max length is 32
+    #[validate(length(max = 32))]
+    pub token_type_hint: Option<String>,
+    /// Validation: `^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,128}$`
+    #[validate(regex(
+        path = "*RE_CLIENT_ID_EPHEMERAL",
+        code = "^[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,128}$"
+    ))]
+    pub client_id: Option<String>,
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub client_secret: Option<String>,
+    /// A signed JWT for `private_key_jwt` or `client_secret_jwt` client authentication
+    /// Validation: max length is 8192
+    #[validate(length(max = 8192))]
+    pub client_assertion: Option<String>,
+    /// Validation: `^urn:ietf:params:oauth:client-assertion-type:jwt-bearer$`
+    #[validate(regex(
+        path = "*RE_CLIENT_ASSERTION_TYPE",
+        code = "^urn:ietf:params:oauth:client-assertion-type:jwt-bearer$"
+    ))]
+    pub client_assertion_type: Option<String>,
+}
+
+impl TokenRevocationRequest {
+    /// Returns the `client_assertion`, if one has been given with the correct type.
+    pub fn client_assertion(&self) -> Result<Option<&str>, ErrorResponse> {
+        client_assertion(
+            self.client_assertion.as_deref(),
+            self.client_assertion_type.as_deref(),
+        )
+    }
+
+    pub fn try_get_client_id_secret(
+        &self,
+        req: &HttpRequest,
+    ) -> Result<(String, Option<String>), ErrorResponse> {
+        client_id_secret(
+            req,
+            self.client_id.as_deref(),
+            self.client_secret.as_deref(),
+            self.client_assertion()?,
+        )
+    }
+}
+
+fn client_id_secret(
+    req: &HttpRequest,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+    client_assertion: Option<&str>,
+) -> Result<(String, Option<String>), ErrorResponse> {
+    let auth_header = req.headers().get(header::AUTHORIZATION).map(|h| {
+        let (_, b64) = h
+            .to_str()
+            .unwrap_or_default()
+            .split_once(' ')
+            .unwrap_or(("", ""));
+        b64
+    });
+
+    if let Some(header) = auth_header {
+        let decoded = String::from_utf8(base64_decode(header)?)?;
+        match decoded.split_once(':') {
+            None => Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Bad Authorization header",
+            )),
+            Some((client_id, client_secret)) => {
+                Ok((client_id.to_string(), Some(client_secret.to_string())))
             }
-        } else {
-            Ok((
-                self.client_id.clone().unwrap_or_default(),
-                self.client_secret.clone(),
-            ))
         }
+    } else {
+        // with a `client_assertion`, the `client_id` is optional and taken from the `sub`
+        let client_id = match client_id {
+            Some(id) => id.to_string(),
+            None => client_assertion
+                .map(client_id_from_assertion)
+                .transpose()?
+                .unwrap_or_default(),
+        };
+        Ok((client_id, client_secret.map(String::from)))
     }
 }
 
+fn client_assertion<'a>(
+    assertion: Option<&'a str>,
+    assertion_type: Option<&str>,
+) -> Result<Option<&'a str>, ErrorResponse> {
+    match (assertion, assertion_type) {
+        (None, None) => Ok(None),
+        (Some(assertion), Some(_)) => Ok(Some(assertion)),
+        _ => Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "'client_assertion' and 'client_assertion_type' must be given together",
+        )),
+    }
+}
+
+/// Extracts the `sub` from a not yet validated `client_assertion`.
+pub fn client_id_from_assertion(assertion: &str) -> Result<String, ErrorResponse> {
+    let claims = assertion
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| ErrorResponse::new(ErrorResponseType::BadRequest, "Malformed JWT"))?;
+    let claims = serde_json::from_slice::<serde_json::Value>(&base64_url_no_pad_decode(claims)?)?;
+    claims
+        .get("sub")
+        .and_then(|sub| sub.as_str())
+        .map(String::from)
+        .ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The 'client_assertion' has no 'sub'",
+            )
+        })
+}
+
 #[derive(Serialize, Deserialize, Validate, ToSchema)]
 pub struct TokenValidationRequest {
     /// Validation: `[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$`
     #[validate(regex(path = "*RE_URI", code = "[a-zA-Z0-9,.:/_-&?=~#!$'()*+%]+$"))]
     pub token: String,
+    /// A signed JWT for `private_key_jwt` or `client_secret_jwt` client authentication
+    /// Validation: max length is 8192
+    #[validate(length(max = 8192))]
+    pub client_assertion: Option<String>,
+    /// Validation: `^urn:ietf:params:oauth:client-assertion-type:jwt-bearer$`
+    #[validate(regex(
+        path = "*RE_CLIENT_ASSERTION_TYPE",
+        code = "^urn:ietf:params:oauth:client-assertion-type:jwt-bearer$"
+    ))]
+    pub client_assertion_type: Option<String>,
+}
+
+impl TokenValidationRequest {
+    /// Returns the `client_assertion`, if one has been given with the correct type.
+    pub fn client_assertion(&self) -> Result<Option<&str>, ErrorResponse> {
+        client_assertion(
+            self.client_assertion.as_deref(),
+            self.client_assertion_type.as_deref(),
+        )
+    }
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -305,8 +661,13 @@ pub struct DeviceVerifyResponse {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct JktClaim {
-    pub jkt: String,
+pub struct CnfClaim {
+    /// The JWK thumbprint of a DPoP bound token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jkt: Option<String>,
+    /// The certificate thumbprint of an mTLS bound token (RFC 8705)
+    #[serde(rename = "x5t#S256", skip_serializing_if = "Option::is_none")]
+    pub x5t_s256: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -315,6 +676,7 @@ pub enum JwkKeyPairAlg {
     RS384,
     RS512,
     EdDSA,
+    ES384,
 }
 
 impl Default for JwkKeyPairAlg {
@@ -329,6 +691,7 @@ impl Display for JwkKeyPairAlg {
             JwkKeyPairAlg::RS256 => "RS256",
             JwkKeyPairAlg::RS384 => "RS384",
             JwkKeyPairAlg::RS512 => "RS512",
+            JwkKeyPairAlg::ES384 => "ES384",
             JwkKeyPairAlg::EdDSA => "EdDSA",
         };
         write!(f, "{}", s)
@@ -339,6 +702,7 @@ impl Display for JwkKeyPairAlg {
 pub enum JwkKeyPairType {
     RSA,
     OKP,
+    EC,
 }
 
 impl Default for JwkKeyPairType {
@@ -352,7 +716,7 @@ pub struct JWKSPublicKeyCerts {
     pub kty: JwkKeyPairType,
     pub alg: JwkKeyPairAlg,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub crv: Option<String>, // Ed25519
+    pub crv: Option<String>, // Ed25519 / P-384
     #[serde(skip_serializing_if = "Option::is_none")]
     pub kid: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -360,7 +724,9 @@ pub struct JWKSPublicKeyCerts {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub e: Option<String>, // RSA
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub x: Option<String>, // OCT
+    pub x: Option<String>, // OCT / EC
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>, // EC
 }
 
 #[derive(Debug, Default, Serialize, ToSchema)]
@@ -368,6 +734,47 @@ pub struct JWKSCerts {
     pub keys: Vec<JWKSPublicKeyCerts>,
 }
 
+/// Imports an externally generated private key into the JWKS. Exactly one of `pem` or `jwk` must
+/// be given.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct JwkImportRequest {
+    /// Validation: `^[a-zA-Z0-9-_.:]{2,128}$`
+    #[validate(regex(path = "*RE_KID", code = "^[a-zA-Z0-9-_.:]{2,128}$"))]
+    pub kid: String,
+    pub alg: JwkKeyPairAlg,
+    /// A PKCS#8 private key in PEM format. For RSA, PKCS#1 is accepted as well.
+    /// Validation: max length is 16384
+    #[validate(length(max = 16384))]
+    pub pem: Option<String>,
+    #[validate(nested)]
+    pub jwk: Option<JwkImportPrivateKey>,
+}
+
+/// The private parts of a JWK. All values are base64 url-safe encoded without padding.
+/// Any other JWK members are ignored.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct JwkImportPrivateKey {
+    /// Validation: max length is 2048
+    #[validate(length(max = 2048))]
+    pub d: String,
+    /// RSA only
+    /// Validation: max length is 2048
+    #[validate(length(max = 2048))]
+    pub n: Option<String>,
+    /// RSA only
+    /// Validation: max length is 16
+    #[validate(length(max = 16))]
+    pub e: Option<String>,
+    /// RSA only
+    /// Validation: max length is 2048
+    #[validate(length(max = 2048))]
+    pub p: Option<String>,
+    /// RSA only
+    /// Validation: max length is 2048
+    #[validate(length(max = 2048))]
+    pub q: Option<String>,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct OAuth2ErrorResponse<'a> {
     pub error: OAuth2ErrorTypeResponse,
@@ -433,5 +840,5 @@ pub struct TokenInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exp: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub cnf: Option<JktClaim>,
+    pub cnf: Option<CnfClaim>,
 }
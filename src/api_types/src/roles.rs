@@ -1,4 +1,4 @@
-use rauthy_common::constants::RE_GROUPS;
+use rauthy_common::constants::{RE_CLIENT_NAME, RE_GROUPS};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use validator::Validate;
@@ -9,3 +9,77 @@ pub struct NewRoleRequest {
     #[validate(regex(path = "*RE_GROUPS", code = "^[a-z0-9-_/,:*]{2,64}$"))]
     pub role: String,
 }
+
+/// The value a `RoleMappingRule` is matched against
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoleMappingSource {
+    /// The domain part of the users E-Mail, compared case-insensitive
+    #[default]
+    EmailDomain,
+    /// A JSON path into the ID token claims of an upstream auth provider. Only available during
+    /// upstream logins.
+    ProviderClaim,
+    /// The value of a custom user attribute
+    UserAttr,
+}
+
+impl RoleMappingSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::EmailDomain => "email_domain",
+            Self::ProviderClaim => "provider_claim",
+            Self::UserAttr => "user_attr",
+        }
+    }
+}
+
+impl From<&str> for RoleMappingSource {
+    fn from(value: &str) -> Self {
+        match value {
+            "provider_claim" => Self::ProviderClaim,
+            "user_attr" => Self::UserAttr,
+            _ => Self::EmailDomain,
+        }
+    }
+}
+
+/// If the `source` matches the `value`, the `roles` and `groups` will be added to the user.
+/// Rules never remove any existing assignments.
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct RoleMappingRuleRequest {
+    /// Validation: `[a-zA-Z0-9À-ÿ-\\s]{2,128}`
+    #[validate(regex(path = "*RE_CLIENT_NAME", code = "[a-zA-Z0-9À-ÿ-\\s]{2,128}"))]
+    pub name: String,
+    pub enabled: bool,
+    pub source: RoleMappingSource,
+    /// The JSON path for `provider_claim` or the attribute name for `user_attr`. Ignored for
+    /// `email_domain`.
+    ///
+    /// Validation: `length(max = 256)`
+    #[validate(length(max = 256))]
+    pub key: Option<String>,
+    /// Validation: `length(min = 1, max = 256)`
+    #[validate(length(min = 1, max = 256))]
+    pub value: String,
+    /// Role IDs
+    #[validate(length(max = 128))]
+    pub roles: Vec<String>,
+    /// Group IDs
+    #[validate(length(max = 128))]
+    pub groups: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RoleMappingRuleResponse {
+    pub id: String,
+    pub name: String,
+    pub enabled: bool,
+    pub source: RoleMappingSource,
+    pub key: Option<String>,
+    pub value: String,
+    /// Role IDs
+    pub roles: Vec<String>,
+    /// Group IDs
+    pub groups: Vec<String>,
+}
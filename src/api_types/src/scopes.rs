@@ -1,5 +1,5 @@
 use crate::cust_validation::validate_vec_attr;
-use rauthy_common::constants::RE_GROUPS;
+use rauthy_common::constants::{RE_ATTR, RE_GROUPS};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use validator::Validate;
@@ -15,6 +15,50 @@ pub struct ScopeRequest {
     /// Validation: `^[a-zA-Z0-9-_/]{2,128}$`
     #[validate(custom(function = "validate_vec_attr"))]
     pub attr_include_id: Option<Vec<String>>,
+    #[validate(nested)]
+    pub claim_mappings: Option<Vec<ScopeClaimMapping>>,
+}
+
+/// Maps a value from the user to a claim inside the `custom` object of the tokens and the
+/// userinfo, as soon as the scope has been granted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ScopeClaimMapping {
+    /// Validation: `^[a-zA-Z0-9-_/]{2,32}$`
+    #[validate(regex(path = "*RE_ATTR", code = "^[a-zA-Z0-9-_/]{2,32}$"))]
+    pub claim: String,
+    pub source: ScopeClaimSource,
+    /// The name of the user field, custom attribute or group. A group ending with `*` maps all
+    /// matching groups as an array, otherwise the claim will be a boolean for the membership.
+    /// Validation: `length(min = 2, max = 64)`
+    #[validate(length(min = 2, max = 64))]
+    pub value: String,
+    #[serde(default)]
+    pub include_access: bool,
+    /// The ID token mappings apply to the userinfo endpoint as well
+    #[serde(default)]
+    pub include_id: bool,
+    /// Converts the value into this type. If the conversion is not possible, the claim will be
+    /// skipped. The value will be mapped as it is, if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub typ: Option<ScopeClaimType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ScopeClaimSource {
+    UserField,
+    Attribute,
+    Group,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ScopeClaimType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Array,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -25,4 +69,6 @@ pub struct ScopeResponse {
     pub attr_include_access: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub attr_include_id: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_mappings: Option<Vec<ScopeClaimMapping>>,
 }
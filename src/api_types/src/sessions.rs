@@ -1,5 +1,8 @@
+use rauthy_common::constants::{RE_ALNUM, RE_CLIENT_ID_EPHEMERAL};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use std::net::IpAddr;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum SessionState {
@@ -22,4 +25,68 @@ pub struct SessionResponse<'a> {
     /// Unix timestamp in seconds
     pub last_seen: i64,
     pub remote_ip: Option<&'a str>,
+    /// The ID of the admin, if this is an impersonated session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impersonator_id: Option<&'a str>,
+    /// `true` for long-lived sessions the user opted in for at login
+    pub remember_me: bool,
+    /// The client, which initiated this session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<&'a str>,
+    /// The method of the last login: `password`, `passkey` or `provider`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_method: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionPingResponse {
+    /// Unix timestamp in seconds for the absolute session lifetime
+    pub exp: i64,
+    /// Unix timestamp in seconds when the session will time out without any further activity
+    pub timeout: i64,
+    /// Remaining seconds until the session ends, whichever of `exp` or `timeout` comes first
+    pub remaining: i64,
+}
+
+/// If any of these is given, `GET /sessions` will return all matching sessions without
+/// pagination. All filters are combined with `AND`.
+#[derive(Debug, Default, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct SessionFilterParams {
+    /// Validation: `[a-zA-Z0-9]`
+    #[validate(regex(path = "*RE_ALNUM", code = "[a-zA-Z0-9]"))]
+    pub user_id: Option<String>,
+    /// Only sessions with a still existing refresh token for this client
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]{2,256}`
+    #[validate(regex(
+        path = "*RE_CLIENT_ID_EPHEMERAL",
+        code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]{2,256}"
+    ))]
+    pub client_id: Option<String>,
+    #[param(value_type = Option<String>)]
+    #[schema(value_type = Option<String>)]
+    pub remote_ip: Option<IpAddr>,
+    /// Only sessions without any activity for at least this amount of seconds
+    pub min_age: Option<u32>,
+    /// Only sessions with any activity during the last amount of seconds
+    pub max_age: Option<u32>,
+    /// Only "remember me" sessions with `true`, or only normal sessions with `false`
+    pub remember_me: Option<bool>,
+}
+
+impl SessionFilterParams {
+    pub fn is_filter(&self) -> bool {
+        self.user_id.is_some()
+            || self.client_id.is_some()
+            || self.remote_ip.is_some()
+            || self.min_age.is_some()
+            || self.max_age.is_some()
+            || self.remember_me.is_some()
+    }
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct SessionDeleteParams {
+    /// If `true`, only "remember me" sessions will be invalidated
+    pub remember_me: Option<bool>,
 }
@@ -1,14 +1,26 @@
 use crate::cust_validation::{validate_vec_groups, validate_vec_roles};
+use crate::events::EventType;
 use crate::generic::Language;
 use crate::oidc::AddressClaim;
+use crate::sessions::SessionResponse;
 use rauthy_common::constants::{
-    RE_ALNUM_48, RE_ALNUM_64, RE_APP_ID, RE_ATTR, RE_ATTR_DESC, RE_CITY, RE_CLIENT_NAME,
-    RE_DATE_STR, RE_MFA_CODE, RE_PHONE, RE_STREET, RE_URI, RE_USER_NAME,
+    RE_ALNUM_48, RE_ALNUM_64, RE_APP_ID, RE_ATTR, RE_ATTR_DESC, RE_AUTHENTICATOR_ATTACHMENT,
+    RE_CITY, RE_CLIENT_NAME, RE_CODE_VERIFIER, RE_DATE_STR, RE_EXTERNAL_ID, RE_GROUPS, RE_MFA_CODE,
+    RE_PHONE, RE_PHONE_E164, RE_SEARCH, RE_STREET, RE_TZ, RE_URI, RE_USER_NAME, RE_YUBICO_OTP,
 };
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
+/// App specific values for a user, which will only be added to tokens and the userinfo for the
+/// given client.
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ClientUserMetadataRequest {
+    /// Any JSON object with a serialized size of max 4 kB
+    pub data: HashMap<String, serde_json::Value>,
+}
+
 #[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct DeviceRequest {
     /// Validation: `[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%]+$`
@@ -29,14 +41,49 @@ pub struct MfaAwaitRequest {
     pub req_id: String,
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub enum MfaPurpose {
     Login(String),
     PasswordNew,
     PasswordReset,
+    /// Exchange the WebAuthn assertion for a short-lived admin API Key
+    ApiKeyExchange,
     Test,
 }
 
+/// The type of custom user attribute values
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UserAttrType {
+    #[default]
+    String,
+    Integer,
+    Number,
+    Boolean,
+}
+
+impl UserAttrType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Number => "number",
+            Self::Boolean => "boolean",
+        }
+    }
+}
+
+impl From<&str> for UserAttrType {
+    fn from(value: &str) -> Self {
+        match value {
+            "integer" => Self::Integer,
+            "number" => Self::Number,
+            "boolean" => Self::Boolean,
+            _ => Self::String,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
 pub struct NewUserRequest {
     /// Validation: `email`
@@ -60,6 +107,12 @@ pub struct NewUserRequest {
     /// Unix timestamp in seconds
     #[validate(range(min = 1719784800))]
     pub user_expires: Option<i64>,
+    /// Immutable ID of this user in an external system. Can only be set during creation.
+    ///
+    /// Validation: `[a-zA-Z0-9-_.:@|/]{1,128}`
+    #[validate(regex(path = "*RE_EXTERNAL_ID", code = "[a-zA-Z0-9-_.:@|/]{1,128}"))]
+    #[serde(default)]
+    pub external_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
@@ -140,6 +193,8 @@ pub struct UpdateUserRequest {
     pub user_expires: Option<i64>,
     #[validate(nested)]
     pub user_values: Option<UserValuesRequest>,
+    /// Forces the user to change the password after the next successful login
+    pub must_change_password: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
@@ -181,6 +236,11 @@ pub struct UserValuesRequest {
     /// Validation: `[a-zA-Z0-9À-ÿ-]{0,48}`
     #[validate(regex(path = "*RE_CITY", code = "[a-zA-Z0-9À-ÿ-]{0,48}"))]
     pub country: Option<String>,
+    /// IANA timezone like `Europe/Berlin`, used for timestamps in E-Mails
+    ///
+    /// Validation: `[a-zA-Z0-9-_/+]{1,48}`
+    #[validate(regex(path = "*RE_TZ", code = "[a-zA-Z0-9-_/+]{1,48}"))]
+    pub tz: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
@@ -191,9 +251,27 @@ pub struct UserAttrConfigRequest {
     /// Validation: `^[a-zA-Z0-9-_/]{0,128}$`
     #[validate(regex(path = "*RE_ATTR_DESC", code = "[a-zA-Z0-9À-ÿ-\\s]{2,128}"))]
     pub desc: Option<String>,
+    /// The type all values for this attribute must have. Defaults to `string` for new
+    /// attributes and will not be changed on updates, if not given.
+    pub typ: Option<UserAttrType>,
+    /// If `true`, a value for this attribute must be set with each update of a user's values,
+    /// and it cannot be deleted anymore. Will not be changed on updates, if not given.
+    pub required: Option<bool>,
+    /// If `true`, users without a value for this attribute must provide it themselves during
+    /// their next login, before any code is issued. Will not be changed on updates, if not given.
+    pub required_at_login: Option<bool>,
+    /// If `false`, the attribute cannot be mapped into tokens or the userinfo by any scope.
+    /// Defaults to `true` for new attributes and will not be changed on updates, if not given.
+    pub claim_eligible: Option<bool>,
+    /// A regex all values must match. Only allowed for the type `string`. Will not be changed
+    /// on updates, if not given. An empty string removes an existing regex.
+    ///
+    /// Validation: `max length 256`
+    #[validate(length(max = 256))]
+    pub regex: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, ToSchema)]
 pub struct UserAttrValueRequest {
     /// Validation: `^[a-zA-Z0-9-_/]{2,32}$`
     #[validate(regex(path = "*RE_ATTR", code = "^[a-z0-9-_/]{2,32}$"))]
@@ -220,6 +298,21 @@ pub struct WebauthnAuthFinishRequest {
     /// Note: `ToSchema` does currently not exist for `webauthn_rs::prelude::PublicKeyCredential`
     #[schema(value_type = str)]
     pub data: webauthn_rs::prelude::PublicKeyCredential,
+    /// The `authenticatorAttachment` of the credential, used to detect cross-device logins
+    ///
+    /// Validation: `^(platform|cross-platform)$`
+    #[validate(regex(
+        path = "*RE_AUTHENTICATOR_ATTACHMENT",
+        code = "^(platform|cross-platform)$"
+    ))]
+    pub authenticator_attachment: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
+pub struct WebauthnRenameRequest {
+    /// Validation: `[a-zA-Z0-9À-ÿ-\\s]{1,32}`
+    #[validate(regex(path = "*RE_USER_NAME", code = "[a-zA-Z0-9À-ſ-\\s]{1,32}"))]
+    pub passkey_name: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Validate, ToSchema)]
@@ -251,6 +344,109 @@ pub struct WebIdRequest {
     pub expose_email: bool,
 }
 
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct UserActivityParams {
+    /// Validation: `1 <= limit <= 100`
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<u16>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSortKey {
+    #[default]
+    CreatedAt,
+    Email,
+    GivenName,
+    FamilyName,
+    LastLogin,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct UserExportParams {
+    /// If `true`, the export will be sent to the users E-Mail address in addition
+    pub email: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct LoginHistoryParams {
+    /// Validation: `1 <= limit <= 100`, default: 20
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<u16>,
+}
+
+/// If any of these is given, `GET /users` will switch to the filtered search mode. All filters
+/// are combined with `AND`.
+#[derive(Debug, Deserialize, Validate, ToSchema, IntoParams)]
+pub struct UsersSearchParams {
+    /// Free-text search in the E-Mail, given name and family name
+    ///
+    /// Validation: `[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%@]+`
+    #[validate(regex(path = "*RE_SEARCH", code = "[a-zA-Z0-9,.:/_\\-&?=~#!$'()*+%@]+"))]
+    pub q: Option<String>,
+    pub enabled: Option<bool>,
+    /// Validation: `[a-z0-9-_/,:*]{2,64}`
+    #[validate(regex(path = "*RE_GROUPS", code = "[a-z0-9-_/,:*]{2,64}"))]
+    pub role: Option<String>,
+    /// Validation: `[a-z0-9-_/,:*]{2,64}`
+    #[validate(regex(path = "*RE_GROUPS", code = "[a-z0-9-_/,:*]{2,64}"))]
+    pub group: Option<String>,
+    /// `true` for only users linked to an upstream auth provider, `false` for only local users
+    pub federated: Option<bool>,
+    pub sort: Option<UserSortKey>,
+    pub order: Option<SortOrder>,
+    /// Validation: `1 <= limit <= 500`, default: 50
+    #[validate(range(min = 1, max = 500))]
+    pub limit: Option<u16>,
+    /// The value of the `x-next-cursor` header from the last response
+    ///
+    /// Validation: `[a-zA-Z0-9-\._~+/=]+`
+    #[validate(regex(path = "*RE_CODE_VERIFIER", code = "[a-zA-Z0-9-\\._~+/=]+"))]
+    pub cursor: Option<String>,
+}
+
+impl UsersSearchParams {
+    pub fn is_search(&self) -> bool {
+        self.q.is_some()
+            || self.enabled.is_some()
+            || self.role.is_some()
+            || self.group.is_some()
+            || self.federated.is_some()
+            || self.sort.is_some()
+            || self.order.is_some()
+            || self.limit.is_some()
+            || self.cursor.is_some()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ClientUserMetadataResponse {
+    pub client_id: String,
+    pub data: HashMap<String, serde_json::Value>,
+    /// Unix timestamp in seconds
+    pub updated: i64,
+}
+
+/// The scopes a user has granted to a client on the consent screen
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserConsentResponse {
+    pub client_id: String,
+    pub client_name: Option<String>,
+    pub scopes: Vec<String>,
+    /// Unix timestamp in seconds
+    pub created_at: i64,
+    /// Unix timestamp in seconds
+    pub last_updated: i64,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct DeviceResponse {
     pub id: String,
@@ -268,6 +464,149 @@ pub struct DeviceResponse {
     pub name: String,
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionDeviceResponse {
+    pub session_id: String,
+    /// A friendly name parsed from the `User-Agent`
+    pub name: String,
+    pub user_agent: Option<String>,
+    /// The IP of the last login on this device
+    pub ip: Option<String>,
+    /// Approximate location, if provided by the reverse proxy
+    pub location: Option<String>,
+    /// Unix timestamp in seconds
+    pub first_seen: i64,
+    /// Unix timestamp in seconds
+    pub last_seen: i64,
+    /// `true` for the session the request has been made with
+    pub current: bool,
+    /// The client, which initiated the session
+    pub client_id: Option<String>,
+    /// The method of the last login: `password`, `passkey` or `provider`
+    pub auth_method: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrustedDeviceResponse {
+    pub id: String,
+    /// A friendly name parsed from the `User-Agent`
+    pub name: String,
+    pub user_agent: Option<String>,
+    /// Unix timestamp in seconds
+    pub created: i64,
+    /// Unix timestamp in seconds
+    pub expires: i64,
+    /// Unix timestamp in seconds
+    pub last_used: i64,
+    /// `true` for the device the request has been made with
+    pub current: bool,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct TotpConfirmRequest {
+    /// The current code from the authenticator app
+    ///
+    /// Validation: `length(equal = 6)`
+    #[validate(length(equal = 6))]
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TotpEnrollResponse {
+    /// The base32 encoded secret for a manual setup
+    pub secret: String,
+    /// The `otpauth://` provisioning URI
+    pub uri: String,
+    /// The provisioning URI as a QR code in SVG format
+    pub qr_svg: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct EmailOtpRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmailOtpStatusResponse {
+    /// The global `MFA_EMAIL_OTP` setting: `disabled`, `opt_in` or `all`
+    pub mode: String,
+    /// The opt-in of the user, only used with `opt_in`
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct SmsOtpEnrollRequest {
+    /// The phone number in E.164 format
+    ///
+    /// Validation: `^\+[1-9][0-9]{6,14}$`
+    #[validate(regex(path = "*RE_PHONE_E164", code = "^\\+[1-9][0-9]{6,14}$"))]
+    pub phone: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct SmsOtpConfirmRequest {
+    /// The code from the SMS
+    ///
+    /// Validation: `length(equal = 6)`
+    #[validate(length(equal = 6))]
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SmsOtpStatusResponse {
+    /// `true`, if an `SMS_PROVIDER` has been configured
+    pub available: bool,
+    pub enabled: bool,
+    /// The verified number with all but the last 3 digits masked
+    pub phone: Option<String>,
+    /// Unix timestamp in seconds, when the number has been verified
+    pub created: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct YubicoOtpRequest {
+    /// An OTP generated by the Yubico key
+    ///
+    /// Validation: `^[cbdefghijklnrtuv]{32,48}$`
+    #[validate(regex(path = "*RE_YUBICO_OTP", code = "^[cbdefghijklnrtuv]{32,48}$"))]
+    pub otp: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct YubicoOtpStatusResponse {
+    /// `true`, if a `YUBICO_OTP_CLIENT_ID` has been configured
+    pub available: bool,
+    pub enabled: bool,
+    /// The public ID of the registered key
+    pub public_id: Option<String>,
+    /// Unix timestamp in seconds, when the key has been registered
+    pub created: Option<i64>,
+    /// Unix timestamp in seconds, when the key has been used for the last time
+    pub last_used: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MfaRecoveryCodesResponse {
+    /// The plain recovery codes. These are only returned once and can never be retrieved again.
+    /// Empty, if the user already had codes left, which are still valid.
+    pub codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MfaRecoveryCodesStatusResponse {
+    /// The amount of not yet used recovery codes
+    pub remaining: usize,
+    /// Unix timestamp in seconds, when the codes have been generated
+    pub created: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TotpStatusResponse {
+    pub enabled: bool,
+    /// Unix timestamp in seconds, when the TOTP has been set up
+    pub created: Option<i64>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct PasskeyResponse {
     pub name: String,
@@ -276,12 +615,30 @@ pub struct PasskeyResponse {
     /// Unix timestamp in seconds
     pub last_used: i64,
     pub user_verified: Option<bool>,
+    /// Only known, if an attestation has been requested during the registration
+    pub aaguid: Option<String>,
+    /// The authenticator model name from the FIDO MDS, if it is enabled
+    pub model: Option<String>,
+    /// Transport hints reported by the authenticator, like `usb`, `nfc`, `internal` or `hybrid`
+    pub transports: Vec<String>,
+    /// BE flag: the credential may be synced to other devices
+    pub backup_eligible: bool,
+    /// BS flag: the credential is currently backed up / synced
+    pub backup_state: bool,
+    /// Unix timestamp in seconds of the last cross-device login via hybrid transport
+    pub last_used_hybrid: Option<i64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct UserAttrConfigValueResponse {
     pub name: String,
     pub desc: Option<String>,
+    pub typ: UserAttrType,
+    pub required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    pub required_at_login: bool,
+    pub claim_eligible: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -300,6 +657,56 @@ pub struct UserAttrValuesResponse {
     pub values: Vec<UserAttrValueResponse>,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserActivityResponse {
+    pub id: String,
+    /// Unix timestamp in milliseconds
+    pub timestamp: i64,
+    pub typ: EventType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// A single user for the bulk import
+///
+/// Inside a CSV, the header must contain the names of these fields. `groups` and `roles` are
+/// separated by `;` in this case.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UserImportRow {
+    pub email: String,
+    pub given_name: String,
+    pub family_name: Option<String>,
+    pub language: Option<Language>,
+    pub groups: Option<Vec<String>>,
+    pub roles: Option<Vec<String>>,
+    /// Unix timestamp in seconds
+    pub user_expires: Option<i64>,
+    pub external_id: Option<String>,
+    /// Send out the new user E-Mail with a magic link to set the password (default: `true`)
+    pub send_magic_link: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserImportResponse {
+    pub created: usize,
+    pub failed: usize,
+    pub rows: Vec<UserImportRowResult>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserImportRowResult {
+    /// 1-based index of the row, without the CSV header
+    pub row: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct Userinfo {
     pub id: String,
@@ -333,6 +740,8 @@ pub struct Userinfo {
     pub birthdate: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub locale: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub picture: Option<String>,
 
     // scope: phone
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -341,6 +750,14 @@ pub struct Userinfo {
     // scope: webid
     #[serde(skip_serializing_if = "Option::is_none")]
     pub webid: Option<String>,
+
+    // the client specific user metadata
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_metadata: Option<HashMap<String, serde_json::Value>>,
+
+    // custom scope claim mappings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom: Option<HashMap<String, serde_json::Value>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -354,6 +771,44 @@ pub enum UserAccountTypeResponse {
     FederatedPassword,
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LoginHistoryResponse {
+    pub id: String,
+    /// Unix timestamp in seconds
+    pub timestamp: i64,
+    pub ip: String,
+    pub user_agent: Option<String>,
+    pub client_id: Option<String>,
+    /// `true` if a passkey has been used during this login
+    pub mfa: bool,
+    pub success: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TosAcceptanceResponse {
+    pub version: String,
+    /// Unix timestamp in seconds
+    pub accepted_at: i64,
+    pub ip: String,
+}
+
+/// All data Rauthy holds about a single user
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserExportResponse<'a> {
+    /// Unix timestamp in seconds
+    pub exported_at: i64,
+    pub user: UserResponse,
+    pub attributes: Vec<UserAttrValueResponse>,
+    pub sessions: Vec<SessionResponse<'a>>,
+    pub devices: Vec<DeviceResponse>,
+    pub passkeys: Vec<PasskeyResponse>,
+    pub login_history: Vec<LoginHistoryResponse>,
+    pub tos_acceptances: Vec<TosAcceptanceResponse>,
+    pub client_metadata: Vec<ClientUserMetadataResponse>,
+    pub consents: Vec<UserConsentResponse>,
+    pub events: Vec<UserActivityResponse>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct UserResponse {
     pub id: String,
@@ -366,6 +821,9 @@ pub struct UserResponse {
     pub groups: Option<Vec<String>>,
     pub enabled: bool,
     pub email_verified: bool,
+    /// Unix timestamp in seconds of the last successful verification of the E-Mail address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified_at: Option<i64>,
     /// Unix timestamp in seconds
     #[serde(skip_serializing_if = "Option::is_none")]
     pub password_expires: Option<i64>,
@@ -378,6 +836,10 @@ pub struct UserResponse {
     pub last_failed_login: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub failed_login_attempts: Option<i64>,
+    /// Unix timestamp in seconds of the automatic unlock, if the account is currently locked
+    /// after too many failed logins
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locked_until: Option<i64>,
     /// Unix timestamp in seconds
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user_expires: Option<i64>,
@@ -386,6 +848,11 @@ pub struct UserResponse {
     pub user_values: UserValuesResponse,
     pub auth_provider_id: Option<String>,
     pub federation_uid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    pub must_change_password: bool,
+    /// `true` after an MFA reset by an admin, until the user has enrolled a new 2nd factor
+    pub mfa_reenroll: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
@@ -404,6 +871,7 @@ pub struct UserValuesResponse {
     pub zip: Option<i32>,
     pub city: Option<String>,
     pub country: Option<String>,
+    pub tz: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -432,6 +900,16 @@ pub struct WebauthnAuthStartResponse {
     pub exp: u64,
 }
 
+/// The challenge for a passkey login without an E-Mail, which can be used with conditional
+/// mediation (`mediation: 'conditional'`) for the passkey autofill.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebauthnDiscoverableStartResponse {
+    pub code: String,
+    #[schema(value_type = str)]
+    pub rcr: webauthn_rs::prelude::RequestChallengeResponse,
+    pub exp: u64,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct WebauthnLoginFinishResponse {
     pub loc: String,
@@ -442,4 +920,32 @@ pub struct WebauthnLoginResponse {
     pub code: String,
     pub user_id: String,
     pub exp: u64,
+    /// If `true`, the login can be sent again with the password and a `totp` code instead
+    pub has_totp: bool,
+}
+
+/// Returned from the login with status `428`, when the user is missing values for custom
+/// attributes, which are required at login. The login must be repeated with `profile_values`
+/// containing a value for each of the `attrs`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProfileRequiredResponse {
+    pub attrs: Vec<UserAttrConfigValueResponse>,
+}
+
+/// Returned from the login with status `428`, when the client requires consent and the user has
+/// not granted all requested `scopes` yet. The login must be repeated with `consent_accept`
+/// containing these scopes.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ConsentRequiredResponse {
+    pub client_id: String,
+    pub client_name: Option<String>,
+    pub scopes: Vec<String>,
+}
+
+/// Returned from the login with status `428`, when the current terms of service version has not
+/// been accepted yet. The login must be repeated with `tos_accept` set to the `version`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TosRequiredResponse {
+    pub version: String,
+    pub url: Option<String>,
 }
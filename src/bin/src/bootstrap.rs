@@ -0,0 +1,270 @@
+use crate::routes;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::{web, App};
+use actix_web_prom::PrometheusMetrics;
+use cryptr::EncKeys;
+use hiqlite::params;
+use rauthy_common::constants::{COOKIE_DOMAIN, HEADER_CSP_OVERRIDES};
+use rauthy_common::utils::UseDummyAddress;
+use rauthy_common::{is_hiqlite, is_sqlite, password_hasher};
+use rauthy_middlewares::csrf_protection::CsrfProtectionMiddleware;
+use rauthy_middlewares::ip_blacklist::RauthyIpBlacklistMiddleware;
+use rauthy_middlewares::logging::RauthyLoggingMiddleware;
+use rauthy_middlewares::principal::RauthyPrincipalMiddleware;
+use rauthy_middlewares::request_id::RequestIdMiddleware;
+use rauthy_middlewares::security_headers::SecurityHeadersMiddleware;
+use rauthy_models::app_state::AppState;
+use rauthy_models::database::DB;
+use rauthy_models::email::EMail;
+use rauthy_models::entity::password::PasswordPolicy;
+use rauthy_models::events::health_watch::watch_health;
+use rauthy_models::events::listener::EventListener;
+use rauthy_models::events::notifier::EventNotifier;
+use rauthy_models::events::stream::EventStream;
+use rauthy_models::events::syslog::EventExporterSyslog;
+use rauthy_models::events::{init_event_vars, ip_blacklist_handler};
+use rauthy_models::geo_ip::GeoIp;
+use rauthy_models::kms::Kms;
+use rauthy_models::sms::Sms;
+use rauthy_models::{email, ListenScheme};
+use spow::pow::Pow;
+use std::env;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+/// Where outgoing E-Mails end up.
+pub enum EmailSink {
+    /// Sends E-Mails via the configured SMTP server. In `test_mode`, they are only logged.
+    Smtp { test_mode: bool },
+    /// Never sends anything out and collects all E-Mails, so tests can assert on them.
+    Capture(Arc<Mutex<Vec<EMail>>>),
+}
+
+/// Initializes all global state, the persistence layer and every background task in the
+/// exact same way for the Rauthy binary and the `test-support` harness.
+///
+/// The config must be loaded into the environment before.
+pub async fn init(email_sink: EmailSink) -> Result<web::Data<AppState>, Box<dyn Error>> {
+    // init encryption keys and pow secrets
+    match EncKeys::from_env() {
+        Ok(keys) => {
+            // for the PoWs, we just use our active keys as b64
+            Pow::init_bytes(keys.get_key(&keys.enc_key_active).unwrap());
+            keys.init().unwrap()
+        }
+        Err(err) => {
+            error!(
+                r#"The `ENC_KEYS`are not correctly set up. Please take a look at the documentation:
+https://sebadob.github.io/rauthy/getting_started/main.html"#
+            );
+            panic!("{}", err);
+        }
+    }
+
+    // Invalid combinations would otherwise only panic with the first request setting a cookie.
+    debug!("Validating the cookie config");
+    let _ = &*COOKIE_DOMAIN;
+
+    // Invalid overrides would otherwise only panic with the first request.
+    debug!("Validating the security headers config");
+    let _ = &*HEADER_CSP_OVERRIDES;
+
+    debug!("Starting the persistence layer");
+    // TODO Keep this check in place until v0.28.0 as info for migrations from older versions.
+    if is_sqlite() {
+        // Hiqlite migration has been finished.
+        panic!(
+            r#"
+
+A direct SQLite connection is not supported anymore. The `DATABASE_URL` is only used for
+Postgres connections. You can migrate your SQLite database to either Postgres or Hiqlite.
+Hiqlite uses SQLite under the hood, but provides a Raft layer on top to make it highly available,
+faster, and more resilient.
+
+You can migrate your existing SQLite database using the `MIGRATE_DB_FROM` config variable:
+
+    # If specified, the currently configured Database will be DELETED and OVERWRITTEN with a
+    # migration from the given database with this variable. Can be used to migrate between
+    # different databases.
+    # !!! USE WITH CARE !!!
+    #MIGRATE_DB_FROM=sqlite:data/rauthy.db
+
+To migrate to Postgres, simply set the `DATABASE_URL` to a Postgres database.
+To migrate to Hiqlite, there are a few new config variables you can set. The most important is
+to set
+
+        HIQLITE=true
+
+Take a look at the release notes for more detailed information:
+https://github.com/sebadob/rauthy/releases/tag/v0.27.0
+"#
+        );
+    }
+    DB::init()
+        .await
+        .expect("Error starting the database / cache layer");
+
+    // must be set up before the migrations, which register the KMS keys
+    Kms::init().map_err(|err| format!("Error setting up the KMS: {}", err.message))?;
+
+    // email sending
+    debug!("Starting E-Mail handler");
+    let (tx_email, mut rx_email) = mpsc::channel::<EMail>(16);
+    match email_sink {
+        EmailSink::Smtp { test_mode } => {
+            tokio::spawn(email::sender(rx_email, test_mode));
+        }
+        EmailSink::Capture(emails) => {
+            tokio::spawn(async move {
+                while let Some(email) = rx_email.recv().await {
+                    emails.lock().unwrap().push(email);
+                }
+            });
+        }
+    }
+
+    let (tx_events, rx_events) = flume::unbounded();
+    let (tx_events_router, rx_events_router) = flume::unbounded();
+    let (tx_ip_blacklist, rx_ip_blacklist) = flume::unbounded();
+
+    debug!("Initializing AppState");
+    let app_state = web::Data::new(
+        AppState::new(
+            tx_email.clone(),
+            tx_events.clone(),
+            tx_events_router.clone(),
+            tx_ip_blacklist.clone(),
+        )
+        .await?,
+    );
+
+    debug!("Applying database migrations");
+    DB::migrate(&app_state)
+        .await
+        .expect("Database migration error");
+
+    // events listener
+    debug!("Starting Events handler");
+    init_event_vars().unwrap();
+    EventNotifier::init_notifiers(tx_email).await.unwrap();
+    EventExporterSyslog::init().unwrap();
+    EventStream::init().await.unwrap();
+    GeoIp::init().unwrap();
+    Sms::init().unwrap();
+    tokio::spawn(EventListener::listen(
+        tx_ip_blacklist.clone(),
+        tx_events_router,
+        rx_events_router,
+        rx_events,
+    ));
+
+    // spawn password hash limiter
+    debug!("Starting Password Hasher");
+    tokio::spawn(password_hasher::run());
+
+    // spawn ip blacklist handler
+    debug!("Starting Blacklist handler");
+    tokio::spawn(ip_blacklist_handler::run(tx_ip_blacklist, rx_ip_blacklist));
+
+    // spawn health watcher
+    debug!("Starting health watch");
+    tokio::spawn(watch_health(app_state.tx_events.clone()));
+
+    // schedulers
+    match env::var("SCHED_DISABLE")
+        .unwrap_or_else(|_| String::from("false"))
+        .as_str()
+    {
+        "true" => {
+            info!("Schedulers are disabled");
+        }
+        _ => {
+            debug!("Starting Schedulers");
+            tokio::spawn(rauthy_schedulers::spawn(app_state.clone()));
+        }
+    };
+
+    // TODO remove this block check with the next minor version.
+    // 0.27.0 had a bug that could have inserted NULL for password policy on update.
+    if is_hiqlite() {
+        let mut row = DB::client()
+            .query_raw_one(
+                "SELECT data FROM config WHERE id = 'password_policy'",
+                params!(),
+            )
+            .await?;
+        if let Err(err) = row.try_get::<Vec<u8>>("data") {
+            warn!(
+                r#"
+
+Error looking up PasswordPolicy - this is most probably a known 0.27.0 bug.
+Inserting default Policy to fix it.
+You should visit the Admin UI -> Config -> Password Policy and adjust it to your needs.
+
+Error: {}
+"#,
+                err
+            );
+            PasswordPolicy {
+                length_min: 14,
+                length_max: 128,
+                include_lower_case: Some(1),
+                include_upper_case: Some(1),
+                include_digits: Some(1),
+                include_special: Some(1),
+                valid_days: Some(180),
+                not_recently_used: Some(3),
+            }
+            .save()
+            .await
+            .expect("Cannot fix default PasswordPolicy issue");
+        }
+    }
+
+    Ok(app_state)
+}
+
+/// Builds the Rauthy `App` with all middlewares and routes. Services, which only exist in
+/// some setups, like the Swagger UI, can be added on top.
+pub fn app(
+    app_state: web::Data<AppState>,
+    metrics: PrometheusMetrics,
+) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    let use_dummy_address = matches!(
+        app_state.listen_scheme,
+        ListenScheme::UnixHttp | ListenScheme::UnixHttps
+    );
+
+    // Note: all .wrap's are executed in reverse order -> the last .wrap is executed as the first
+    // one for any new request
+    let mut app = App::new()
+        // .data shares application state for all workers
+        .app_data(app_state)
+        .wrap(RauthyPrincipalMiddleware)
+        .wrap(CsrfProtectionMiddleware)
+        .wrap(RauthyLoggingMiddleware)
+        .wrap(RequestIdMiddleware)
+        .wrap(SecurityHeadersMiddleware)
+        .wrap(metrics)
+        // Important: Do not move this middleware do need the least amount of computing
+        // for blacklisted IPs -> middlewares are executed in reverse order -> this one first
+        .wrap(RauthyIpBlacklistMiddleware)
+        .configure(routes::configure);
+
+    if use_dummy_address {
+        app = app.app_data(UseDummyAddress);
+    }
+
+    app
+}
@@ -0,0 +1,9 @@
+// Copyright 2024 Sebastian Dobe <sebastiandobe@mailbox.org>
+
+#![forbid(unsafe_code)]
+
+pub mod bootstrap;
+pub mod routes;
+
+#[cfg(feature = "test-support")]
+pub mod test_support;
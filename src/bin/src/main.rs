@@ -4,45 +4,27 @@
 
 use crate::logging::setup_logging;
 use actix_web::rt::System;
-use actix_web::{middleware, web, App, HttpServer};
+use actix_web::{web, App, HttpServer};
 use actix_web_prom::PrometheusMetricsBuilder;
-use cryptr::EncKeys;
-use hiqlite::params;
 use prometheus::Registry;
+use rauthy::bootstrap::{self, EmailSink};
 use rauthy_common::constants::{
     APP_START, RAUTHY_VERSION, SWAGGER_UI_EXTERNAL, SWAGGER_UI_INTERNAL,
 };
-use rauthy_common::utils::UseDummyAddress;
-use rauthy_common::{is_hiqlite, is_sqlite, password_hasher};
-use rauthy_handlers::openapi::ApiDoc;
-use rauthy_handlers::{
-    api_keys, auth_providers, blacklist, clients, events, fed_cm, generic, groups, oidc, roles,
-    scopes, sessions, users,
-};
-use rauthy_middlewares::csrf_protection::CsrfProtectionMiddleware;
-use rauthy_middlewares::ip_blacklist::RauthyIpBlacklistMiddleware;
-use rauthy_middlewares::logging::RauthyLoggingMiddleware;
-use rauthy_middlewares::principal::RauthyPrincipalMiddleware;
+use rauthy_handlers::openapi::{ApiDoc, SelfServiceApiDoc};
 use rauthy_models::app_state::AppState;
 use rauthy_models::database::DB;
-use rauthy_models::email::EMail;
-use rauthy_models::entity::password::PasswordPolicy;
 use rauthy_models::events::event::Event;
-use rauthy_models::events::health_watch::watch_health;
-use rauthy_models::events::listener::EventListener;
-use rauthy_models::events::notifier::EventNotifier;
-use rauthy_models::events::{init_event_vars, ip_blacklist_handler};
-use rauthy_models::{email, ListenScheme};
-use spow::pow::Pow;
+use rauthy_models::events::metrics::EventMetrics;
+use rauthy_models::ListenScheme;
 use std::error::Error;
 use std::net::Ipv4Addr;
 use std::str::FromStr;
 use std::time::Duration;
 use std::{env, thread};
-use tokio::sync::mpsc;
 use tokio::time;
-use tracing::{debug, error, info, warn};
-use utoipa_swagger_ui::SwaggerUi;
+use tracing::{debug, error, info};
+use utoipa_swagger_ui::{SwaggerUi, Url};
 
 mod dummy_data;
 mod logging;
@@ -87,155 +69,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
         info!("Application started in Integration Test Mode");
     }
 
-    // init encryption keys and pow secrets
-    match EncKeys::from_env() {
-        Ok(keys) => {
-            // for the PoWs, we just use our active keys as b64
-            Pow::init_bytes(keys.get_key(&keys.enc_key_active).unwrap());
-            keys.init().unwrap()
-        }
-        Err(err) => {
-            error!(
-                r#"The `ENC_KEYS`are not correctly set up. Please take a look at the documentation:
-https://sebadob.github.io/rauthy/getting_started/main.html"#
-            );
-            panic!("{}", err);
-        }
-    }
-
-    debug!("Starting the persistence layer");
-    // TODO Keep this check in place until v0.28.0 as info for migrations from older versions.
-    if is_sqlite() {
-        // Hiqlite migration has been finished.
-        panic!(
-            r#"
-
-A direct SQLite connection is not supported anymore. The `DATABASE_URL` is only used for
-Postgres connections. You can migrate your SQLite database to either Postgres or Hiqlite.
-Hiqlite uses SQLite under the hood, but provides a Raft layer on top to make it highly available,
-faster, and more resilient.
-
-You can migrate your existing SQLite database using the `MIGRATE_DB_FROM` config variable:
-
-    # If specified, the currently configured Database will be DELETED and OVERWRITTEN with a
-    # migration from the given database with this variable. Can be used to migrate between
-    # different databases.
-    # !!! USE WITH CARE !!!
-    #MIGRATE_DB_FROM=sqlite:data/rauthy.db
-
-To migrate to Postgres, simply set the `DATABASE_URL` to a Postgres database.
-To migrate to Hiqlite, there are a few new config variables you can set. The most important is
-to set
-
-        HIQLITE=true
-
-Take a look at the release notes for more detailed information:
-https://github.com/sebadob/rauthy/releases/tag/v0.27.0
-"#
-        );
-    }
-    DB::init()
-        .await
-        .expect("Error starting the database / cache layer");
-
-    // email sending
-    debug!("Starting E-Mail handler");
-    let (tx_email, rx_email) = mpsc::channel::<EMail>(16);
-    tokio::spawn(email::sender(rx_email, test_mode));
-
-    let (tx_events, rx_events) = flume::unbounded();
-    let (tx_events_router, rx_events_router) = flume::unbounded();
-    let (tx_ip_blacklist, rx_ip_blacklist) = flume::unbounded();
-
-    debug!("Initializing AppState");
-    let app_state = web::Data::new(
-        AppState::new(
-            tx_email.clone(),
-            tx_events.clone(),
-            tx_events_router.clone(),
-            tx_ip_blacklist.clone(),
-        )
-        .await?,
-    );
-
-    debug!("Applying database migrations");
-    DB::migrate(&app_state)
-        .await
-        .expect("Database migration error");
-
-    // events listener
-    debug!("Starting Events handler");
-    init_event_vars().unwrap();
-    EventNotifier::init_notifiers(tx_email).await.unwrap();
-    tokio::spawn(EventListener::listen(
-        tx_ip_blacklist.clone(),
-        tx_events_router,
-        rx_events_router,
-        rx_events,
-    ));
-
-    // spawn password hash limiter
-    debug!("Starting Password Hasher");
-    tokio::spawn(password_hasher::run());
-
-    // spawn ip blacklist handler
-    debug!("Starting Blacklist handler");
-    tokio::spawn(ip_blacklist_handler::run(tx_ip_blacklist, rx_ip_blacklist));
-
-    // spawn health watcher
-    debug!("Starting health watch");
-    tokio::spawn(watch_health(app_state.tx_events.clone()));
-
-    // schedulers
-    match env::var("SCHED_DISABLE")
-        .unwrap_or_else(|_| String::from("false"))
-        .as_str()
-    {
-        "true" => {
-            info!("Schedulers are disabled");
-        }
-        _ => {
-            debug!("Starting Schedulers");
-            tokio::spawn(rauthy_schedulers::spawn(app_state.clone()));
-        }
-    };
-
-    // TODO remove this block check with the next minor version.
-    // 0.27.0 had a bug that could have inserted NULL for password policy on update.
-    if is_hiqlite() {
-        let mut row = DB::client()
-            .query_raw_one(
-                "SELECT data FROM config WHERE id = 'password_policy'",
-                params!(),
-            )
-            .await?;
-        if let Err(err) = row.try_get::<Vec<u8>>("data") {
-            warn!(
-                r#"
-
-Error looking up PasswordPolicy - this is most probably a known 0.27.0 bug.
-Inserting default Policy to fix it.
-You should visit the Admin UI -> Config -> Password Policy and adjust it to your needs.
-
-Error: {}
-"#,
-                err
-            );
-            PasswordPolicy {
-                length_min: 14,
-                length_max: 128,
-                include_lower_case: Some(1),
-                include_upper_case: Some(1),
-                include_digits: Some(1),
-                include_special: Some(1),
-                valid_days: Some(180),
-                not_recently_used: Some(3),
-            }
-            .save()
-            .await
-            .expect("Cannot fix default PasswordPolicy issue");
-        }
-    }
+    let app_state = bootstrap::init(EmailSink::Smtp { test_mode }).await?;
 
     // actix web
     let state = app_state.clone();
@@ -287,11 +121,18 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
     // OpenAPI / Swagger
     let swagger = SwaggerUi::new("/docs/v1/swagger-ui/{_:.*}")
         .url("/docs/v1/api-doc/openapi.json", ApiDoc::build(&app_state))
+        .url(
+            "/docs/v1/api-doc/openapi_self_service.json",
+            SelfServiceApiDoc::build(&app_state),
+        )
         .config(
-            utoipa_swagger_ui::Config::from("../api-doc/openapi.json")
-                .try_it_out_enabled(false)
-                .supported_submit_methods(["get"])
-                .filter(true),
+            utoipa_swagger_ui::Config::new([
+                Url::new("Full", "../api-doc/openapi.json"),
+                Url::new("Self-Service", "../api-doc/openapi_self_service.json"),
+            ])
+            .try_it_out_enabled(false)
+            .supported_submit_methods(["get"])
+            .filter(true),
         );
 
     // Prometheus metrics
@@ -301,6 +142,7 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
         .expect("Cannot parse METRICS_ENABLE to bool");
     let pub_metrics = if metrics_enable {
         let shared_registry = Registry::new();
+        EventMetrics::register(&shared_registry);
         let metrics = PrometheusMetricsBuilder::new("api")
             .registry(shared_registry.clone())
             .endpoint("/metrics")
@@ -367,210 +209,11 @@ async fn actix_main(app_state: web::Data<AppState>) -> std::io::Result<()> {
         .await
         .unwrap();
 
-    // Note: all .wrap's are executed in reverse order -> the last .wrap is executed as the first
-    // one for any new request
     let server = HttpServer::new(move || {
-        let mut app = App::new()
-            // .data shares application state for all workers
-            .app_data(app_state.clone())
-            .wrap(RauthyPrincipalMiddleware)
-            .wrap(CsrfProtectionMiddleware)
-            .wrap(RauthyLoggingMiddleware)
-            .wrap(
-                middleware::DefaultHeaders::new()
-                    .add(("x-frame-options", "SAMEORIGIN"))
-                    .add(("x-content-type-options", "nosniff"))
-                    .add((
-                        "strict-transport-security",
-                        "max-age=31536000;includeSubDomains",
-                    ))
-                    .add(("referrer-policy", "no-referrer"))
-                    .add(("x-robots-tag", "noindex, nofollow"))
-                    .add((
-                        "content-security-policy",
-                        "frame-ancestors 'none'; object-src 'none';",
-                    ))
-                    .add(("cache-control", "no-store")),
-            )
-            .wrap(pub_metrics.clone())
-            .service(oidc::get_well_known)
-            .service(fed_cm::get_fed_cm_well_known)
-            .service(generic::catch_all)
-            // Important: Do not move this middleware do need the least amount of computing
-            // for blacklisted IPs -> middlewares are executed in reverse order -> this one first
-            .wrap(RauthyIpBlacklistMiddleware)
-            .service(
-                web::scope("/auth")
-                    .service(generic::redirect_v1)
-                    .service(users::get_user_webid)
-                    .service(
-                        web::scope("/v1")
-                            .service(api_keys::get_api_keys)
-                            .service(api_keys::post_api_key)
-                            .service(api_keys::put_api_key)
-                            .service(api_keys::delete_api_key)
-                            .service(api_keys::get_api_key_test)
-                            .service(api_keys::put_api_key_secret)
-                            .service(auth_providers::post_providers)
-                            .service(auth_providers::get_providers_minimal)
-                            .service(auth_providers::post_provider)
-                            .service(auth_providers::post_provider_login)
-                            .service(auth_providers::get_provider_delete_safe)
-                            .service(auth_providers::post_provider_lookup)
-                            .service(auth_providers::get_provider_callback_html)
-                            .service(auth_providers::post_provider_callback)
-                            .service(auth_providers::delete_provider_link)
-                            .service(auth_providers::put_provider)
-                            .service(auth_providers::delete_provider)
-                            .service(auth_providers::get_provider_img)
-                            .service(auth_providers::put_provider_img)
-                            .service(auth_providers::post_provider_link)
-                            .service(blacklist::get_blacklist)
-                            .service(blacklist::post_blacklist)
-                            .service(blacklist::delete_blacklist)
-                            .service(events::post_events)
-                            .service(events::sse_events)
-                            .service(events::post_event_test)
-                            .service(generic::get_index)
-                            .service(generic::get_account_html)
-                            .service(generic::get_admin_html)
-                            .service(generic::get_admin_api_keys_html)
-                            .service(generic::get_admin_attr_html)
-                            .service(generic::get_admin_blacklist_html)
-                            .service(generic::get_admin_clients_html)
-                            .service(generic::get_admin_config_html)
-                            .service(generic::get_admin_docs_html)
-                            .service(generic::get_admin_events_html)
-                            .service(generic::get_admin_groups_html)
-                            .service(generic::get_admin_roles_html)
-                            .service(generic::get_admin_scopes_html)
-                            .service(generic::get_admin_providers_html)
-                            .service(generic::get_admin_sessions_html)
-                            .service(generic::get_admin_users_html)
-                            .service(generic::get_device_html)
-                            .service(generic::get_fed_cm_html)
-                            .service(generic::get_auth_check)
-                            .service(generic::get_auth_check_admin)
-                            .service(generic::post_i18n)
-                            .service(generic::post_update_language)
-                            .service(generic::get_version)
-                            .service(generic::get_whoami)
-                            .service(oidc::get_authorize)
-                            .service(oidc::post_authorize)
-                            .service(oidc::post_authorize_refresh)
-                            .service(oidc::post_device_auth)
-                            .service(oidc::post_device_verify)
-                            .service(oidc::get_callback_html)
-                            .service(oidc::get_certs)
-                            .service(oidc::get_cert_by_kid)
-                            .service(oidc::get_logout)
-                            .service(oidc::post_logout)
-                            .service(oidc::rotate_jwk)
-                            .service(oidc::post_session)
-                            .service(oidc::get_session_info)
-                            .service(oidc::get_session_xsrf)
-                            .service(clients::get_clients)
-                            .service(clients::get_client_by_id)
-                            .service(clients::get_client_colors)
-                            .service(clients::put_client_colors)
-                            .service(clients::delete_client_colors)
-                            .service(clients::get_client_logo)
-                            .service(clients::put_client_logo)
-                            .service(clients::delete_client_logo)
-                            .service(clients::get_client_secret)
-                            .service(clients::post_clients)
-                            .service(clients::put_clients)
-                            .service(clients::put_generate_client_secret)
-                            .service(clients::delete_client)
-                            .service(clients::post_clients_dyn)
-                            .service(clients::get_clients_dyn)
-                            .service(clients::put_clients_dyn)
-                            .service(generic::get_login_time)
-                            .service(fed_cm::get_fed_cm_accounts)
-                            .service(fed_cm::get_fed_cm_config)
-                            .service(fed_cm::get_fed_cm_client_meta)
-                            .service(fed_cm::get_fed_cm_well_known)
-                            .service(fed_cm::post_fed_cm_token)
-                            .service(fed_cm::get_fed_client_config)
-                            .service(fed_cm::get_fed_cm_status)
-                            .service(users::get_users)
-                            .service(users::get_users_register)
-                            .service(users::post_users_register)
-                            .service(users::get_cust_attr)
-                            .service(users::post_cust_attr)
-                            .service(users::put_cust_attr)
-                            .service(users::delete_cust_attr)
-                            .service(users::get_user_by_id)
-                            .service(users::get_user_attr)
-                            .service(users::put_user_attr)
-                            .service(users::get_user_devices)
-                            .service(users::put_user_device_name)
-                            .service(users::delete_user_device)
-                            .service(users::get_user_webid_data)
-                            .service(users::put_user_webid_data)
-                            .service(users::get_user_email_confirm)
-                            .service(users::post_user_self_convert_passkey)
-                            .service(generic::post_password_hash_times)
-                            .service(sessions::get_sessions)
-                            .service(sessions::delete_sessions)
-                            .service(sessions::delete_sessions_for_user)
-                            .service(users::get_user_password_reset)
-                            .service(users::put_user_password_reset)
-                            .service(users::get_user_by_email)
-                            .service(users::post_users)
-                            .service(users::put_user_by_id)
-                            .service(users::put_user_self)
-                            .service(users::delete_user_by_id)
-                            .service(users::post_user_password_request_reset)
-                            .service(users::get_user_webauthn_passkeys)
-                            .service(users::post_webauthn_reg_start)
-                            .service(users::post_webauthn_reg_finish)
-                            .service(users::post_webauthn_auth_start)
-                            .service(users::post_webauthn_auth_finish)
-                            .service(users::delete_webauthn)
-                            .service(generic::get_password_policy)
-                            .service(generic::put_password_policy)
-                            .service(generic::post_pow)
-                            .service(generic::get_search)
-                            .service(groups::get_groups)
-                            .service(groups::post_group)
-                            .service(groups::put_group)
-                            .service(groups::delete_group)
-                            .service(roles::get_roles)
-                            .service(roles::post_role)
-                            .service(roles::put_role)
-                            .service(roles::delete_role)
-                            .service(scopes::get_scopes)
-                            .service(scopes::post_scope)
-                            .service(scopes::put_scope)
-                            .service(scopes::delete_scope)
-                            .service(oidc::post_token)
-                            .service(oidc::post_token_introspect)
-                            .service(oidc::get_userinfo)
-                            .service(oidc::post_userinfo)
-                            .service(oidc::get_forward_auth)
-                            .service(generic::get_enc_keys)
-                            .service(generic::post_migrate_enc_key)
-                            .service(generic::ping)
-                            .service(oidc::post_validate_token)
-                            .service(oidc::get_well_known)
-                            .service(generic::get_health)
-                            .service(generic::get_ready)
-                            .service(generic::get_static_assets),
-                    ),
-            );
-
+        let mut app = bootstrap::app(app_state.clone(), pub_metrics.clone());
         if *SWAGGER_UI_EXTERNAL {
             app = app.service(swagger.clone());
         }
-
-        if matches!(
-            app_state.listen_scheme,
-            ListenScheme::UnixHttp | ListenScheme::UnixHttps
-        ) {
-            app = app.app_data(UseDummyAddress);
-        }
-
         app
     })
     // overwrites the number of worker threads -> default == available cpu cores
@@ -0,0 +1,247 @@
+use actix_web::web;
+use rauthy_handlers::{
+    api_keys, audit_log, auth_providers, blacklist, clients, events, fed_cm, generic, groups,
+    login_stats, oidc, roles, scim, scopes, sessions, users,
+};
+
+/// Registers all Rauthy routes. The middlewares are not included and must be wrapped around
+/// the `App` separately.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(oidc::get_well_known)
+        .service(oidc::get_openid_federation)
+        .service(fed_cm::get_fed_cm_well_known)
+        .service(generic::catch_all)
+        .service(
+            web::scope("/auth")
+                .service(generic::redirect_v1)
+                .service(users::get_user_webid)
+                .service(
+                    web::scope("/v1")
+                        .service(api_keys::get_api_keys)
+                        .service(api_keys::post_api_key_exchange)
+                        .service(api_keys::post_api_key)
+                        .service(api_keys::put_api_key)
+                        .service(api_keys::delete_api_key)
+                        .service(api_keys::get_api_key_test)
+                        .service(api_keys::put_api_key_secret)
+                        .service(audit_log::get_audit_log)
+                        .service(audit_log::get_audit_log_verify)
+                        .service(auth_providers::post_providers)
+                        .service(auth_providers::get_providers_minimal)
+                        .service(auth_providers::post_provider)
+                        .service(auth_providers::post_provider_login)
+                        .service(auth_providers::get_provider_delete_safe)
+                        .service(auth_providers::post_provider_lookup)
+                        .service(auth_providers::get_provider_callback_html)
+                        .service(auth_providers::post_provider_callback)
+                        .service(auth_providers::delete_provider_link)
+                        .service(auth_providers::put_provider)
+                        .service(auth_providers::delete_provider)
+                        .service(auth_providers::get_provider_img)
+                        .service(auth_providers::put_provider_img)
+                        .service(auth_providers::post_provider_link)
+                        .service(blacklist::get_blacklist)
+                        .service(blacklist::post_blacklist)
+                        .service(blacklist::delete_blacklist)
+                        .service(events::post_events)
+                        .service(events::sse_events)
+                        .service(events::post_event_test)
+                        .service(generic::get_index)
+                        .service(generic::get_account_html)
+                        .service(generic::get_admin_html)
+                        .service(generic::get_admin_api_keys_html)
+                        .service(generic::get_admin_attr_html)
+                        .service(generic::get_admin_blacklist_html)
+                        .service(generic::get_admin_clients_html)
+                        .service(generic::get_admin_config_html)
+                        .service(generic::get_admin_docs_html)
+                        .service(generic::get_admin_events_html)
+                        .service(generic::get_admin_groups_html)
+                        .service(generic::get_admin_roles_html)
+                        .service(generic::get_admin_scopes_html)
+                        .service(generic::get_admin_providers_html)
+                        .service(generic::get_admin_sessions_html)
+                        .service(generic::get_admin_users_html)
+                        .service(generic::get_device_html)
+                        .service(generic::get_fed_cm_html)
+                        .service(generic::get_auth_check)
+                        .service(generic::get_auth_check_admin)
+                        .service(generic::post_i18n)
+                        .service(generic::post_update_language)
+                        .service(generic::get_version)
+                        .service(generic::get_whoami)
+                        .service(oidc::get_authorize)
+                        .service(oidc::post_authorize)
+                        .service(oidc::post_authorize_refresh)
+                        .service(oidc::post_authorize_passkey_start)
+                        .service(oidc::post_authorize_passkey)
+                        .service(oidc::post_device_auth)
+                        .service(oidc::post_device_verify)
+                        .service(oidc::get_callback_html)
+                        .service(oidc::get_certs)
+                        .service(oidc::get_cert_by_kid)
+                        .service(oidc::get_check_session_iframe)
+                        .service(oidc::get_logout)
+                        .service(oidc::post_logout)
+                        .service(oidc::rotate_jwk)
+                        .service(oidc::post_import_jwk)
+                        .service(oidc::post_session)
+                        .service(oidc::get_session_info)
+                        .service(oidc::get_session_xsrf)
+                        .service(clients::get_clients)
+                        .service(clients::get_client_by_id)
+                        .service(clients::get_client_access_policy)
+                        .service(clients::put_client_access_policy)
+                        .service(clients::get_client_colors)
+                        .service(clients::put_client_colors)
+                        .service(clients::delete_client_colors)
+                        .service(clients::get_client_logo)
+                        .service(clients::put_client_logo)
+                        .service(clients::delete_client_logo)
+                        .service(clients::get_client_secret)
+                        .service(clients::post_clients)
+                        .service(clients::put_clients)
+                        .service(clients::put_generate_client_secret)
+                        .service(clients::post_client_revoke_all)
+                        .service(clients::delete_client)
+                        .service(clients::post_clients_dyn)
+                        .service(clients::get_clients_dyn)
+                        .service(clients::put_clients_dyn)
+                        .service(generic::get_login_time)
+                        .service(fed_cm::get_fed_cm_accounts)
+                        .service(fed_cm::get_fed_cm_config)
+                        .service(fed_cm::get_fed_cm_client_meta)
+                        .service(fed_cm::get_fed_cm_well_known)
+                        .service(fed_cm::post_fed_cm_token)
+                        .service(fed_cm::get_fed_client_config)
+                        .service(fed_cm::get_fed_cm_status)
+                        .service(users::get_users)
+                        .service(users::get_users_register)
+                        .service(users::post_users_register)
+                        .service(users::get_cust_attr)
+                        .service(users::post_cust_attr)
+                        .service(users::put_cust_attr)
+                        .service(users::delete_cust_attr)
+                        .service(users::get_user_by_id)
+                        .service(users::get_user_activity)
+                        .service(users::get_user_export)
+                        .service(users::get_user_login_history)
+                        .service(users::get_user_password_policy)
+                        .service(users::get_user_picture)
+                        .service(users::put_user_picture)
+                        .service(users::delete_user_picture)
+                        .service(users::get_user_attr)
+                        .service(users::put_user_attr)
+                        .service(users::get_user_client_metadata)
+                        .service(users::put_user_client_metadata)
+                        .service(users::delete_user_client_metadata)
+                        .service(users::get_user_consents)
+                        .service(users::delete_user_consent)
+                        .service(users::get_user_devices)
+                        .service(users::put_user_device_name)
+                        .service(users::delete_user_device)
+                        .service(users::get_user_session_devices)
+                        .service(users::delete_user_session_device)
+                        .service(users::get_user_trusted_devices)
+                        .service(users::delete_user_trusted_devices)
+                        .service(users::delete_user_trusted_device)
+                        .service(users::get_user_webid_data)
+                        .service(users::put_user_webid_data)
+                        .service(users::get_user_email_confirm)
+                        .service(users::post_user_email_verification)
+                        .service(users::post_user_self_convert_passkey)
+                        .service(generic::post_password_hash_times)
+                        .service(sessions::get_sessions)
+                        .service(sessions::delete_sessions)
+                        .service(sessions::delete_sessions_for_user)
+                        .service(sessions::delete_session)
+                        .service(sessions::post_session_ping)
+                        .service(users::get_user_password_reset)
+                        .service(users::put_user_password_reset)
+                        .service(users::get_user_by_email)
+                        .service(users::post_users_import)
+                        .service(users::post_users)
+                        .service(users::put_user_by_id)
+                        .service(users::put_user_self)
+                        .service(users::delete_user_by_id)
+                        .service(users::post_user_restore)
+                        .service(users::post_user_unlock)
+                        .service(users::post_user_impersonate)
+                        .service(users::post_user_password_request_reset)
+                        .service(users::get_user_webauthn_passkeys)
+                        .service(users::post_webauthn_reg_start)
+                        .service(users::post_webauthn_reg_finish)
+                        .service(users::post_webauthn_auth_start)
+                        .service(users::post_webauthn_auth_finish)
+                        .service(users::delete_webauthn)
+                        .service(users::put_webauthn_rename)
+                        .service(users::get_user_totp)
+                        .service(users::post_user_totp)
+                        .service(users::post_user_totp_confirm)
+                        .service(users::delete_user_totp)
+                        .service(users::get_user_email_otp)
+                        .service(users::put_user_email_otp)
+                        .service(users::get_user_sms_otp)
+                        .service(users::post_user_sms_otp)
+                        .service(users::post_user_sms_otp_confirm)
+                        .service(users::delete_user_sms_otp)
+                        .service(users::get_user_yubico_otp)
+                        .service(users::post_user_yubico_otp)
+                        .service(users::delete_user_yubico_otp)
+                        .service(users::get_user_recovery_codes)
+                        .service(users::post_user_recovery_codes)
+                        .service(users::post_user_mfa_reset)
+                        .service(generic::get_password_policy)
+                        .service(generic::put_password_policy)
+                        .service(generic::post_pow)
+                        .service(generic::get_search)
+                        .service(groups::get_groups)
+                        .service(groups::post_group)
+                        .service(groups::put_group)
+                        .service(groups::delete_group)
+                        .service(groups::get_group_password_policy)
+                        .service(groups::put_group_password_policy)
+                        .service(groups::delete_group_password_policy)
+                        .service(groups::get_group_inheritance)
+                        .service(groups::put_group_inheritance)
+                        .service(login_stats::get_login_stats)
+                        .service(roles::get_role_mapping_rules)
+                        .service(roles::post_role_mapping_rule)
+                        .service(roles::put_role_mapping_rule)
+                        .service(roles::delete_role_mapping_rule)
+                        .service(roles::get_roles)
+                        .service(roles::post_role)
+                        .service(roles::put_role)
+                        .service(roles::delete_role)
+                        .service(scim::get_scim_users)
+                        .service(scim::post_scim_user)
+                        .service(scim::get_scim_user)
+                        .service(scim::patch_scim_user)
+                        .service(scim::delete_scim_user)
+                        .service(scim::get_scim_groups)
+                        .service(scim::post_scim_group)
+                        .service(scim::get_scim_group)
+                        .service(scim::patch_scim_group)
+                        .service(scim::delete_scim_group)
+                        .service(scopes::get_scopes)
+                        .service(scopes::post_scope)
+                        .service(scopes::put_scope)
+                        .service(scopes::delete_scope)
+                        .service(oidc::post_token)
+                        .service(oidc::post_token_introspect)
+                        .service(oidc::post_token_revoke)
+                        .service(oidc::get_userinfo)
+                        .service(oidc::post_userinfo)
+                        .service(oidc::get_forward_auth)
+                        .service(generic::get_enc_keys)
+                        .service(generic::post_migrate_enc_key)
+                        .service(generic::ping)
+                        .service(oidc::post_validate_token)
+                        .service(oidc::get_well_known)
+                        .service(oidc::get_openid_federation)
+                        .service(generic::get_health)
+                        .service(generic::get_ready)
+                        .service(generic::get_static_assets),
+                ),
+        );
+}
@@ -0,0 +1,218 @@
+//! In-process Rauthy for integration tests in downstream applications.
+//!
+//! ```rust,ignore
+//! use rauthy::test_support::TestRauthy;
+//!
+//! #[tokio::test]
+//! async fn test_login() {
+//!     let rauthy = TestRauthy::start();
+//!     let issuer = rauthy.issuer();
+//!     // ... run your own login flows against the issuer
+//!
+//!     let email = rauthy
+//!         .wait_for_email("user@localhost.de", Duration::from_secs(5))
+//!         .await
+//!         .expect("no E-Mail received");
+//! }
+//! ```
+//!
+//! A single instance is started per process and shared between all tests, because the
+//! persistence layer and the config are global. It runs with Hiqlite inside a temporary
+//! directory and the same `DEV_MODE` test data the Rauthy integration tests use, which
+//! means the `ADMIN_*` and `CLIENT_*` constants can be used right away.
+//!
+//! All config values are read from the environment and can be overwritten before calling
+//! `TestRauthy::start()`, except for the ports, the `PUB_URL` and the data dir.
+//!
+//! All timestamps are taken from `rauthy_common::clock`, which can be frozen or advanced with
+//! `freeze_time()` and `advance_time()` to test expiries deterministically. Since the instance
+//! is shared, tests changing the time should call `reset_time()` afterward and must not run
+//! in parallel with tests relying on the real time. Cache TTLs inside Hiqlite keep following
+//! the system clock.
+
+use crate::bootstrap::{self, EmailSink};
+use actix_web::{web, HttpServer};
+use actix_web_prom::PrometheusMetricsBuilder;
+use chrono::{DateTime, TimeDelta, Utc};
+use rauthy_common::clock;
+use rauthy_common::utils::get_rand;
+use rauthy_models::app_state::AppState;
+use rauthy_models::email::EMail;
+use std::net::TcpListener;
+use std::sync::{mpsc as std_mpsc, Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use std::{env, thread};
+use tokio::time;
+
+pub const ADMIN_EMAIL: &str = "init_admin@localhost.de";
+pub const ADMIN_PASSWORD: &str = "123SuperSafe";
+pub const CLIENT_ID: &str = "init_client";
+pub const CLIENT_SECRET: &str = "LjERi0WSEz1E9OY9KFJaMjlwV1Uf3nuIuOUnJnoJQNm2i7YMjTDMy4PbAKnYRgFy";
+
+static TEST_RAUTHY: OnceLock<TestRauthy> = OnceLock::new();
+
+pub struct TestRauthy {
+    port: u16,
+    emails: Arc<Mutex<Vec<EMail>>>,
+}
+
+impl TestRauthy {
+    /// Starts Rauthy on a random local port, if it is not already running, and blocks until it
+    /// accepts requests.
+    ///
+    /// Must be called before any other Rauthy code, because the config is read lazily from the
+    /// environment.
+    pub fn start() -> &'static Self {
+        TEST_RAUTHY.get_or_init(Self::init)
+    }
+
+    /// The port the HTTP server is listening on
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The base URL for all API requests, which is the issuer at the same time
+    pub fn issuer(&self) -> String {
+        format!("http://127.0.0.1:{}/auth/v1", self.port)
+    }
+
+    /// All E-Mails, which have been sent out so far and have not been taken yet
+    pub fn emails(&self) -> Vec<EMail> {
+        self.emails.lock().unwrap().clone()
+    }
+
+    /// Removes and returns the oldest E-Mail for the given address. Waits until the `timeout`,
+    /// if there is none yet.
+    pub async fn wait_for_email(&self, address: &str, timeout: Duration) -> Option<EMail> {
+        let start = Instant::now();
+        loop {
+            {
+                let mut emails = self.emails.lock().unwrap();
+                if let Some(idx) = emails.iter().position(|e| e.address == address) {
+                    return Some(emails.remove(idx));
+                }
+            }
+
+            if start.elapsed() >= timeout {
+                return None;
+            }
+            time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Stops the Rauthy clock at the given time.
+    pub fn freeze_time(&self, at: DateTime<Utc>) {
+        clock::freeze(at);
+    }
+
+    /// Moves the Rauthy clock forward, no matter if it is frozen or not.
+    pub fn advance_time(&self, by: TimeDelta) {
+        clock::advance(by);
+    }
+
+    /// Lets the Rauthy clock follow the system clock again.
+    pub fn reset_time(&self) {
+        clock::reset();
+    }
+
+    fn init() -> Self {
+        let listener =
+            TcpListener::bind("127.0.0.1:0").expect("Cannot bind the TestRauthy listener");
+        let port = listener.local_addr().unwrap().port();
+        init_env(port);
+
+        let emails = Arc::new(Mutex::new(Vec::new()));
+        let emails_capture = emails.clone();
+        let (tx_ready, rx_ready) = std_mpsc::channel();
+
+        // The runtime must outlive single tests, because the persistence layer and all
+        // background tasks are bound to it.
+        thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .expect("Cannot build the TestRauthy runtime");
+
+            rt.block_on(async move {
+                // E-Mails are never sent out but captured for assertions
+                let app_state = bootstrap::init(EmailSink::Capture(emails_capture))
+                    .await
+                    .expect("Cannot bootstrap TestRauthy");
+
+                thread::spawn(move || {
+                    actix_web::rt::System::new()
+                        .block_on(serve(app_state, listener))
+                        .expect("TestRauthy HTTP server error");
+                });
+
+                tx_ready.send(()).unwrap();
+                std::future::pending::<()>().await;
+            });
+        });
+
+        rx_ready
+            .recv()
+            .expect("TestRauthy failed to start - check the logs");
+
+        Self { port, emails }
+    }
+}
+
+fn init_env(port: u16) {
+    let free_port = || {
+        TcpListener::bind("127.0.0.1:0")
+            .and_then(|l| l.local_addr())
+            .map(|addr| addr.port())
+            .expect("Cannot find a free port")
+    };
+    let data_dir = env::temp_dir().join(format!("rauthy-test-{}", get_rand(12)));
+
+    env::set_var("HIQLITE", "true");
+    env::set_var("HQL_DATA_DIR", data_dir.to_string_lossy().as_ref());
+    env::set_var("HQL_NODE_ID", "1");
+    env::set_var(
+        "HQL_NODES",
+        format!("1 127.0.0.1:{} 127.0.0.1:{}", free_port(), free_port()),
+    );
+    env::set_var("LISTEN_ADDRESS", "127.0.0.1");
+    env::set_var("LISTEN_SCHEME", "http");
+    env::set_var("LISTEN_PORT_HTTP", port.to_string());
+    env::set_var("PUB_URL", format!("127.0.0.1:{}", port));
+
+    let defaults = [
+        ("DEV_MODE", "true"),
+        ("HQL_SECRET_RAFT", "SuperSecureSecret1337"),
+        ("HQL_SECRET_API", "SuperSecureSecret1337"),
+        // the pre-computed DEV_MODE JWKs are encrypted with these keys
+        (
+            "ENC_KEYS",
+            "q6u26onRvXVG4427/M0NFQzhSSldCY01rckJNa1JYZ3g2NUFtSnNOVGdoU0E=\n\
+            bVCyTsGaggVy5yqQ/UzluN29DZW41M3hTSkx6Y3NtZmRuQkR2TnJxUTYzcjQ=",
+        ),
+        ("ENC_KEY_ACTIVE", "bVCyTsGaggVy5yqQ"),
+        ("MAX_HASH_THREADS", "1"),
+        ("ARGON2_M_COST", "32768"),
+        ("ARGON2_T_COST", "1"),
+        ("ARGON2_P_COST", "2"),
+        ("ADMIN_FORCE_MFA", "false"),
+    ];
+    for (key, value) in defaults {
+        if env::var(key).is_err() {
+            env::set_var(key, value);
+        }
+    }
+}
+
+async fn serve(app_state: web::Data<AppState>, listener: TcpListener) -> std::io::Result<()> {
+    // metrics are only collected for the Prometheus endpoint, which is not exposed here
+    let metrics = PrometheusMetricsBuilder::new("rauthy")
+        .exclude_regex(".*")
+        .build()
+        .unwrap();
+
+    HttpServer::new(move || bootstrap::app(app_state.clone(), metrics.clone()))
+        .workers(1)
+        .listen(listener)?
+        .run()
+        .await
+}
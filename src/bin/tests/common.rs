@@ -105,9 +105,23 @@ pub async fn session_headers() -> (HeaderMap, TokenSet) {
         redirect_uri: redirect_uri.to_owned(),
         scopes: None,
         state: None,
+        response_mode: None,
+        resource: None,
+        claims: None,
+        acr_values: None,
         nonce: Some("MySuperNonce".to_string()),
         code_challenge: Some(challenge_s256),
         code_challenge_method: Some("S256".to_string()),
+        tos_accept: None,
+        profile_values: None,
+        consent_accept: None,
+        remember_me: None,
+        totp: None,
+        recovery_code: None,
+        email_otp: None,
+        trust_device: None,
+        yubico_otp: None,
+        sms_otp: None,
     };
 
     let res = client
@@ -131,6 +145,9 @@ pub async fn session_headers() -> (HeaderMap, TokenSet) {
         username: None,
         password: None,
         refresh_token: None,
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
     };
 
     let url_token = format!("{}/oidc/token", backend_url);
@@ -198,16 +215,17 @@ pub fn code_state_from_headers(res: Response) -> Result<(String, Option<String>)
         .unwrap();
     println!("Location Header: {}", loc_header);
 
-    let code: String;
+    let mut code = String::default();
     let mut state = None;
-    let (_, code_str) = loc_header.split_once("code=").unwrap();
-    if let Some((c, state_str)) = code_str.split_once('&') {
-        code = c.to_string();
-        let (_, s) = state_str.split_once("state=").unwrap();
-        state = Some(s.to_string());
-    } else {
-        code = code_str.to_string();
+    let (_, query) = loc_header.split_once('?').unwrap();
+    for (key, value) in query.split('&').filter_map(|kv| kv.split_once('=')) {
+        match key {
+            "code" => code = value.to_string(),
+            "state" => state = Some(value.to_string()),
+            _ => {}
+        }
     }
+    assert!(!code.is_empty());
 
     Ok((code, state))
 }
@@ -51,6 +51,23 @@ async fn test_api_keys() -> Result<(), Box<dyn Error>> {
         .await?;
     assert_eq!(res.status(), StatusCode::BAD_REQUEST);
 
+    // the prefix for exchanged keys is reserved -> they would be cleaned up after expiry
+    let reserved = ApiKeyRequest {
+        name: "passkey-test123".to_string(),
+        exp: None,
+        access: vec![ApiKeyAccess {
+            group: AccessGroup::Groups,
+            access_rights: vec![AccessRights::Read],
+        }],
+    };
+    let res = client
+        .post(&url)
+        .headers(auth_headers.clone())
+        .json(&reserved)
+        .send()
+        .await?;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
     // we should be able to read groups now
     let url_groups = format!("{}/groups", get_backend_url());
     let res = client
@@ -65,6 +82,7 @@ async fn test_api_keys() -> Result<(), Box<dyn Error>> {
     // we should NOT be able to create a new group
     let new_group = NewGroupRequest {
         group: "api_key_test_group".to_string(),
+        force_mfa: None,
     };
     let res = client
         .post(&url_groups)
@@ -7,12 +7,13 @@ use chrono::Utc;
 use ed25519_compact::Noise;
 use josekit::jwk;
 use pretty_assertions::assert_eq;
-use rauthy_api_types::clients::UpdateClientRequest;
+use rauthy_api_types::clients::{ClientSecretResponse, NewClientRequest, UpdateClientRequest};
 use rauthy_api_types::oidc::{
-    JwkKeyPairAlg, LoginRequest, TokenInfo, TokenRequest, TokenValidationRequest,
+    JwkKeyPairAlg, LoginRequest, TokenInfo, TokenRequest, TokenRevocationRequest,
+    TokenValidationRequest,
 };
 use rauthy_common::constants::{
-    APPLICATION_JSON, DPOP_TOKEN_ENDPOINT, HEADER_DPOP_NONCE, TOKEN_DPOP,
+    APPLICATION_JSON, COOKIE_SESSION_STATE, DPOP_TOKEN_ENDPOINT, HEADER_DPOP_NONCE, TOKEN_DPOP,
 };
 use rauthy_common::utils::{
     base64_encode, base64_url_encode, base64_url_no_pad_decode, base64_url_no_pad_encode, get_rand,
@@ -22,7 +23,9 @@ use rauthy_models::entity::dpop_proof::{DPoPClaims, DPoPHeader};
 use rauthy_models::entity::jwk::{JWKSPublicKey, JwkKeyPairType, JWKS};
 use rauthy_models::JwtTokenType;
 use rauthy_service::token_set::TokenSet;
-use reqwest::header::AUTHORIZATION;
+use reqwest::header::{
+    AUTHORIZATION, CONTENT_SECURITY_POLICY, LOCATION, SET_COOKIE, X_FRAME_OPTIONS,
+};
 use ring::digest;
 use std::error::Error;
 use std::fmt::Write;
@@ -102,9 +105,23 @@ async fn test_authorization_code_flow() -> Result<(), Box<dyn Error>> {
         redirect_uri: redirect_uri.to_owned(),
         scopes: None,
         state: None,
+        response_mode: None,
+        resource: None,
+        claims: None,
+        acr_values: None,
         nonce: Some(nonce.to_owned()),
         code_challenge: Some(challenge_plain.to_owned()),
         code_challenge_method: Some("plain".to_string()),
+        tos_accept: None,
+        profile_values: None,
+        consent_accept: None,
+        remember_me: None,
+        totp: None,
+        recovery_code: None,
+        email_otp: None,
+        trust_device: None,
+        yubico_otp: None,
+        sms_otp: None,
     };
     let res = reqwest::Client::new()
         .post(&url_auth)
@@ -125,6 +142,8 @@ async fn test_authorization_code_flow() -> Result<(), Box<dyn Error>> {
     res = check_status(res, 202).await?;
 
     // Step 3: extract values from callback location header
+    let loc = res.headers().get(LOCATION).unwrap().to_str()?;
+    assert!(loc.contains("&session_state="));
     let (code, _) = code_state_from_headers(res)?;
     println!("Extracted code: {:?}", code);
 
@@ -140,6 +159,9 @@ async fn test_authorization_code_flow() -> Result<(), Box<dyn Error>> {
         username: None,
         password: None,
         refresh_token: None,
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
     };
     let url_token = format!("{}/oidc/token", backend_url);
     let res = reqwest::Client::new()
@@ -285,6 +307,37 @@ async fn test_authorization_code_flow() -> Result<(), Box<dyn Error>> {
         force_mfa: false,
         client_uri: None,
         contacts: None,
+        auth_response_alg: None,
+        jwks: None,
+        request_uris: None,
+        frontchannel_logout_uri: None,
+        tls_client_auth: None,
+        tls_client_auth_subject_dn: None,
+        allowed_resources: None,
+        jwks_uri: None,
+        subject_type: None,
+        sector_identifier_uri: None,
+        opaque_access_tokens: false,
+        id_token_encrypted_response_alg: None,
+        id_token_encrypted_response_enc: None,
+        userinfo_encrypted_response_alg: None,
+        userinfo_encrypted_response_enc: None,
+        userinfo_signed_response_alg: None,
+        refresh_token_rotation: true,
+        refresh_token_grace_time: None,
+        refresh_token_reuse_detection: false,
+        consent_required: false,
+        id_token_lifetime: None,
+        refresh_token_lifetime: None,
+        claim_template: None,
+        session_lifetime: None,
+        session_timeout: None,
+        deny_hybrid_passkeys: false,
+        require_pkce_s256: false,
+        response_types: vec!["code".to_string()],
+        require_signed_request_object: false,
+        backchannel_logout_uri: None,
+        token_endpoint_auth_method: None,
     };
     let url_client = format!("{}/clients/{}", backend_url, CLIENT_ID);
     let auth_headers = get_auth_headers().await?;
@@ -331,7 +384,127 @@ async fn test_authorization_code_flow() -> Result<(), Box<dyn Error>> {
     assert!(ts.refresh_token.is_some());
     assert_eq!(ts.expires_in, 60);
 
+    // ############################################################################
+    // ########################## flow with consent ###############################
+    // ############################################################################
+    println!("\nStarting authorization code flow with required consent");
+
+    update_client.consent_required = true;
+    let res = reqwest::Client::new()
+        .put(&url_client)
+        .headers(auth_headers.clone())
+        .json(&update_client)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    // 428 -> the default scopes must be granted first
+    let mut res = reqwest::get(&url_auth).await?;
+    res = check_status(res, 200).await?;
+    let headers = cookie_csrf_headers_from_res(res).await?;
+    let mut res = reqwest::Client::new()
+        .post(&url_auth)
+        .headers(headers.clone())
+        .json(&req_login)
+        .send()
+        .await?;
+    res = check_status(res, 428).await?;
+    let body = res.json::<serde_json::Value>().await?;
+    let scopes = body["scopes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s.as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(scopes, vec!["openid".to_string(), "email".to_string()]);
+
+    req_login.consent_accept = Some(scopes);
+    let res = reqwest::Client::new()
+        .post(&url_auth)
+        .headers(headers)
+        .json(&req_login)
+        .send()
+        .await?;
+    check_status(res, 202).await?;
+
+    // the consent has been persisted -> no screen for the next login
+    req_login.consent_accept = None;
+    let mut res = reqwest::get(&url_auth).await?;
+    res = check_status(res, 200).await?;
+    let headers = cookie_csrf_headers_from_res(res).await?;
+    let mut res = reqwest::Client::new()
+        .post(&url_auth)
+        .headers(headers)
+        .json(&req_login)
+        .send()
+        .await?;
+    res = check_status(res, 202).await?;
+    let (code, _) = code_state_from_headers(res)?;
+    req_token.code = Some(code);
+    let mut res = reqwest::Client::new()
+        .post(&url_token)
+        .form(&req_token)
+        .send()
+        .await?;
+    res = check_status(res, 200).await?;
+    let ts = res.json::<TokenSet>().await?;
+
+    let url_consents = format!("{}/users/m4PJ3TnyP32LA8hzY23deme3/consents", backend_url);
+    let mut res = reqwest::Client::new()
+        .get(&url_consents)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    res = check_status(res, 200).await?;
+    let consents = res.json::<serde_json::Value>().await?;
+    assert_eq!(consents[0]["client_id"], CLIENT_ID);
+    assert_eq!(consents[0]["client_name"], "Init Client");
+
+    // revoking the consent revokes the refresh tokens for the client as well
+    let res = reqwest::Client::new()
+        .delete(format!("{}/{}", url_consents, CLIENT_ID))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    let req_refresh = TokenRequest {
+        grant_type: "refresh_token".to_string(),
+        code: None,
+        redirect_uri: None,
+        client_id: Some(CLIENT_ID.to_string()),
+        client_secret: Some(CLIENT_SECRET.to_string()),
+        code_verifier: None,
+        device_code: None,
+        username: None,
+        password: None,
+        refresh_token: ts.refresh_token,
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
+    };
+    let res = reqwest::Client::new()
+        .post(&url_token)
+        .form(&req_refresh)
+        .send()
+        .await?;
+    assert!(!res.status().is_success());
+
+    // the consent screen must show up again
+
+    let mut res = reqwest::get(&url_auth).await?;
+    res = check_status(res, 200).await?;
+    let headers = cookie_csrf_headers_from_res(res).await?;
+    let res = reqwest::Client::new()
+        .post(&url_auth)
+        .headers(headers)
+        .json(&req_login)
+        .send()
+        .await?;
+    check_status(res, 428).await?;
+
     // now clean up and change back the pkce for the client
+    update_client.consent_required = false;
     update_client.challenges = Some(vec!["S256".to_string(), "plain".to_string()]);
     let res = reqwest::Client::new()
         .put(&url_client)
@@ -359,6 +532,9 @@ async fn test_client_credentials_flow() -> Result<(), Box<dyn Error>> {
         username: None,
         password: None,
         refresh_token: None,
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
     };
     let url = format!("{}/oidc/token", backend_url);
     let client = reqwest::Client::new();
@@ -380,12 +556,157 @@ async fn test_client_credentials_flow() -> Result<(), Box<dyn Error>> {
 
     let payload = TokenValidationRequest {
         token: ts.access_token.clone(),
+        client_assertion: None,
+        client_assertion_type: None,
     };
     validate_token(&ts.access_token, payload).await?;
 
     Ok(())
 }
 
+#[tokio::test]
+async fn test_es384_signed_tokens() -> Result<(), Box<dyn Error>> {
+    let backend_url = get_backend_url();
+    let auth_headers = get_auth_headers().await?;
+    let client = reqwest::Client::new();
+
+    // a dedicated client to not interfere with the other tests using the init client
+    let client_id = "es384_client";
+    let new_client = NewClientRequest {
+        id: client_id.to_string(),
+        secret: None,
+        name: Some("ES384 Client".to_string()),
+        confidential: true,
+        redirect_uris: vec!["http://localhost:3000/oidc/callback".to_string()],
+        post_logout_redirect_uris: None,
+    };
+    let res = client
+        .post(format!("{}/clients", backend_url))
+        .headers(auth_headers.clone())
+        .json(&new_client)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    let update_client = UpdateClientRequest {
+        id: client_id.to_string(),
+        name: Some("ES384 Client".to_string()),
+        confidential: true,
+        redirect_uris: vec!["http://localhost:3000/oidc/callback".to_string()],
+        post_logout_redirect_uris: None,
+        allowed_origins: None,
+        enabled: true,
+        flows_enabled: vec!["client_credentials".to_string()],
+        access_token_alg: JwkKeyPairAlg::ES384,
+        id_token_alg: JwkKeyPairAlg::ES384,
+        auth_code_lifetime: 60,
+        access_token_lifetime: 60,
+        scopes: vec!["openid".to_string()],
+        default_scopes: vec!["openid".to_string()],
+        challenges: None,
+        force_mfa: false,
+        client_uri: None,
+        contacts: None,
+        auth_response_alg: None,
+        jwks: None,
+        request_uris: None,
+        frontchannel_logout_uri: None,
+        tls_client_auth: None,
+        tls_client_auth_subject_dn: None,
+        allowed_resources: None,
+        jwks_uri: None,
+        subject_type: None,
+        sector_identifier_uri: None,
+        opaque_access_tokens: false,
+        id_token_encrypted_response_alg: None,
+        id_token_encrypted_response_enc: None,
+        userinfo_encrypted_response_alg: None,
+        userinfo_encrypted_response_enc: None,
+        userinfo_signed_response_alg: None,
+        refresh_token_rotation: true,
+        refresh_token_grace_time: None,
+        refresh_token_reuse_detection: false,
+        consent_required: false,
+        id_token_lifetime: None,
+        refresh_token_lifetime: None,
+        claim_template: None,
+        session_lifetime: None,
+        session_timeout: None,
+        deny_hybrid_passkeys: false,
+        require_pkce_s256: false,
+        response_types: vec!["code".to_string()],
+        require_signed_request_object: false,
+        backchannel_logout_uri: None,
+        token_endpoint_auth_method: None,
+    };
+    let url_client = format!("{}/clients/{}", backend_url, client_id);
+    let res = client
+        .put(&url_client)
+        .headers(auth_headers.clone())
+        .json(&update_client)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    let res = client
+        .post(format!("{}/secret", url_client))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    let secret = check_status(res, 200)
+        .await?
+        .json::<ClientSecretResponse>()
+        .await?
+        .secret;
+
+    let body = TokenRequest {
+        grant_type: "client_credentials".to_string(),
+        code: None,
+        redirect_uri: None,
+        client_id: Some(client_id.to_string()),
+        client_secret: secret,
+        code_verifier: None,
+        device_code: None,
+        username: None,
+        password: None,
+        refresh_token: None,
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
+    };
+    let res = client
+        .post(format!("{}/oidc/token", backend_url))
+        .form(&body)
+        .send()
+        .await?;
+    let ts = check_status(res, 200).await?.json::<TokenSet>().await?;
+
+    // the token must be signed with the ES384 key from the JWKS
+    let header = josekit::jwt::decode_header(&ts.access_token)?;
+    assert_eq!(header.claim("alg").and_then(|v| v.as_str()), Some("ES384"));
+    let kid = header.claim("kid").and_then(|v| v.as_str()).unwrap();
+
+    let res = reqwest::get(format!("{}/oidc/certs/{}", backend_url, kid)).await?;
+    let jwk = check_status(res, 200).await?.json::<jwk::Jwk>().await?;
+    assert_eq!(jwk.curve(), Some("P-384"));
+
+    let verifier = josekit::jws::ES384.verifier_from_jwk(&jwk)?;
+    let (payload, _) = josekit::jwt::decode_with_verifier(&ts.access_token, &verifier)?;
+    assert_eq!(
+        payload.claim("azp").and_then(|v| v.as_str()),
+        Some(client_id)
+    );
+
+    let res = client
+        .delete(&url_client)
+        .headers(auth_headers)
+        .send()
+        .await?;
+    check_status(res, 200).await?;
+
+    Ok(())
+}
+
 // This test is a bit messy currently with some code reception and so one - WIP
 #[tokio::test]
 #[ignore]
@@ -421,9 +742,23 @@ async fn test_concurrent_logins() -> Result<(), Box<dyn Error>> {
         redirect_uri: redirect_uri.to_owned(),
         scopes: None,
         state: None,
+        response_mode: None,
+        resource: None,
+        claims: None,
+        acr_values: None,
         nonce: None,
         code_challenge: Some(challenge_plain.to_owned()),
         code_challenge_method: None,
+        tos_accept: None,
+        profile_values: None,
+        consent_accept: None,
+        remember_me: None,
+        totp: None,
+        recovery_code: None,
+        email_otp: None,
+        trust_device: None,
+        yubico_otp: None,
+        sms_otp: None,
     };
 
     let start = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
@@ -491,6 +826,9 @@ async fn test_password_flow() -> Result<(), Box<dyn Error>> {
         username: Some(USERNAME.to_string()),
         password: None,
         refresh_token: None,
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
     };
     let client = reqwest::Client::new();
     let res = client.post(&url).form(&body).send().await?;
@@ -529,6 +867,8 @@ async fn test_password_flow() -> Result<(), Box<dyn Error>> {
     // validate against the backend
     let payload = TokenValidationRequest {
         token: ts.access_token.to_owned(),
+        client_assertion: None,
+        client_assertion_type: None,
     };
     validate_token(&ts.access_token, payload).await?;
 
@@ -553,6 +893,9 @@ async fn test_password_flow() -> Result<(), Box<dyn Error>> {
         username: None,
         password: None,
         refresh_token: Some(ts.refresh_token.clone().unwrap()),
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
     };
     let url = format!("{}/oidc/token", get_backend_url());
     let res = reqwest::Client::new().post(&url).form(&req).send().await?;
@@ -579,6 +922,164 @@ async fn test_password_flow() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_check_session_iframe() -> Result<(), Box<dyn Error>> {
+    let backend_url = get_backend_url();
+
+    let url = format!("{}/oidc/session/check", backend_url);
+    let res = reqwest::get(&url).await?;
+    assert_eq!(res.status(), 200);
+    let csp = res
+        .headers()
+        .get(CONTENT_SECURITY_POLICY)
+        .unwrap()
+        .to_str()?;
+    assert!(csp.contains("frame-ancestors *"));
+    assert!(res.headers().get(X_FRAME_OPTIONS).is_none());
+
+    // the JS readable browser state must be set together with the session cookie
+    let url_auth = format!(
+        "{}/oidc/authorize?client_id=init_client&redirect_uri=http://localhost:3000/oidc/callback&response_type=code&code_challenge=oDXug9zfYqfz8ejcqMpALRPXfW8QhbKV2AVuScAt8xrLKDAmaRYQ4yRi2uqcH9ys",
+        backend_url
+    );
+    let res = reqwest::get(&url_auth).await?;
+    assert_eq!(res.status(), 200);
+    let cookie = res
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find(|v| v.contains(&format!("{}=", COOKIE_SESSION_STATE)))
+        .unwrap();
+    assert!(!cookie.contains("HttpOnly"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_authorize_error_redirect() -> Result<(), Box<dyn Error>> {
+    let backend_url = get_backend_url();
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let challenge = "oDXug9zfYqfz8ejcqMpALRPXfW8QhbKV2AVuScAt8xrLKDAmaRYQ4yRi2uqcH9ys";
+
+    // an unsupported response_type must be sent back to a valid redirect_uri
+    let url_auth = format!(
+        "{}/oidc/authorize?client_id=init_client&redirect_uri=http://localhost:3000/oidc/callback&response_type=token&state=abc&code_challenge={}",
+        backend_url, challenge
+    );
+    let res = client.get(&url_auth).send().await?;
+    assert_eq!(res.status(), 302);
+    let loc = res.headers().get(LOCATION).unwrap().to_str()?;
+    assert!(loc.starts_with(
+        "http://localhost:3000/oidc/callback?error=unsupported_response_type&error_description="
+    ));
+    assert!(loc.ends_with("&state=abc"));
+
+    let url_auth = format!(
+        "{}/oidc/authorize?client_id=init_client&redirect_uri=http://localhost:3000/oidc/callback&response_type=code&resource=https%3A%2F%2Fapi.test&code_challenge={}",
+        backend_url, challenge
+    );
+    let res = client.get(&url_auth).send().await?;
+    assert_eq!(res.status(), 302);
+    let loc = res.headers().get(LOCATION).unwrap().to_str()?;
+    assert!(loc.contains("?error=invalid_target&"));
+    assert!(!loc.contains("state="));
+
+    // without an open registration, `prompt=create` is an `invalid_request`
+    let url_auth = format!(
+        "{}/oidc/authorize?client_id=init_client&redirect_uri=http://localhost:3000/oidc/callback&response_type=code&prompt=create&state=abc&code_challenge={}",
+        backend_url, challenge
+    );
+    let res = client.get(&url_auth).send().await?;
+    assert_eq!(res.status(), 302);
+    let loc = res.headers().get(LOCATION).unwrap().to_str()?;
+    assert!(loc.contains("?error=invalid_request&"));
+    assert!(loc.ends_with("&state=abc"));
+
+    // an invalid request object is sent back to the valid redirect_uri of the outer request
+    let url_auth = format!(
+        "{}/oidc/authorize?client_id=init_client&redirect_uri=http://localhost:3000/oidc/callback&response_type=code&state=abc&request=eyJhbGciOiJFUzI1NiJ9.e30.c2ln",
+        backend_url
+    );
+    let res = client.get(&url_auth).send().await?;
+    assert_eq!(res.status(), 302);
+    let loc = res.headers().get(LOCATION).unwrap().to_str()?;
+    assert!(loc.contains("?error=invalid_request_object&"));
+    assert!(loc.ends_with("&state=abc"));
+
+    // an invalid redirect_uri must never be redirected to
+    let url_auth = format!(
+        "{}/oidc/authorize?client_id=init_client&redirect_uri=http://localhost:3000/invalid&response_type=token",
+        backend_url
+    );
+    let res = client.get(&url_auth).send().await?;
+    assert_eq!(res.status(), 400);
+    assert!(res.headers().get(LOCATION).is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_revocation() -> Result<(), Box<dyn Error>> {
+    let url = format!("{}/oidc/token", get_backend_url());
+    let mut body = TokenRequest {
+        grant_type: "password".to_string(),
+        code: None,
+        redirect_uri: None,
+        client_id: Some(CLIENT_ID.to_string()),
+        client_secret: Some(CLIENT_SECRET.to_string()),
+        code_verifier: None,
+        device_code: None,
+        username: Some(USERNAME.to_string()),
+        password: Some(PASSWORD.to_string()),
+        refresh_token: None,
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
+    };
+    let client = reqwest::Client::new();
+    let res = client.post(&url).form(&body).send().await?;
+    let ts = check_status(res, 200).await?.json::<TokenSet>().await?;
+
+    // rotate the refresh token once
+    body.grant_type = "refresh_token".to_string();
+    body.username = None;
+    body.password = None;
+    body.refresh_token = ts.refresh_token.clone();
+    let res = client.post(&url).form(&body).send().await?;
+    let new_ts = check_status(res, 200).await?.json::<TokenSet>().await?;
+
+    // revoking the original token must revoke the rotated one as well
+    let url_revoke = format!("{}/oidc/revoke", get_backend_url());
+    let mut revoke = TokenRevocationRequest {
+        token: ts.refresh_token.clone().unwrap(),
+        token_type_hint: Some("refresh_token".to_string()),
+        client_id: Some(CLIENT_ID.to_string()),
+        client_secret: Some("NoNoNoSecret1337".to_string()),
+        client_assertion: None,
+        client_assertion_type: None,
+    };
+    let res = client.post(&url_revoke).form(&revoke).send().await?;
+    check_status(res, 401).await?;
+
+    revoke.client_secret = Some(CLIENT_SECRET.to_string());
+    let res = client.post(&url_revoke).form(&revoke).send().await?;
+    check_status(res, 200).await?;
+
+    body.refresh_token = new_ts.refresh_token.clone();
+    let res = client.post(&url).form(&body).send().await?;
+    assert!(!res.status().is_success());
+
+    // unknown tokens must not be an error
+    revoke.token = get_rand(64);
+    let res = client.post(&url_revoke).form(&revoke).send().await?;
+    check_status(res, 200).await?;
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_dpop() -> Result<(), Box<dyn Error>> {
     let client = reqwest::Client::new();
@@ -597,6 +1098,9 @@ async fn test_dpop() -> Result<(), Box<dyn Error>> {
         username: Some(USERNAME.to_string()),
         password: Some(PASSWORD.to_string()),
         refresh_token: None,
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
     };
 
     // dpop header
@@ -614,6 +1118,7 @@ async fn test_dpop() -> Result<(), Box<dyn Error>> {
             n: None,
             e: None,
             x: Some(base64_url_encode(kp.pk.as_slice())),
+            y: None,
         },
         kid: None,
     };
@@ -688,10 +1193,12 @@ async fn test_dpop() -> Result<(), Box<dyn Error>> {
     assert_eq!(ts.token_type, JwtTokenType::DPoP);
     let payload = TokenValidationRequest {
         token: ts.access_token.to_owned(),
+        client_assertion: None,
+        client_assertion_type: None,
     };
     let token_info = validate_token(&ts.access_token, payload).await?;
     assert!(token_info.cnf.is_some());
-    assert_eq!(token_info.cnf.unwrap().jkt, fingerprint);
+    assert_eq!(token_info.cnf.unwrap().jkt, Some(fingerprint.clone()));
 
     // refresh it
     time::sleep(Duration::from_secs(1)).await;
@@ -706,6 +1213,9 @@ async fn test_dpop() -> Result<(), Box<dyn Error>> {
         username: None,
         password: None,
         refresh_token: Some(ts.refresh_token.clone().unwrap()),
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
     };
 
     // without DPoP header, it should fail
@@ -729,10 +1239,12 @@ async fn test_dpop() -> Result<(), Box<dyn Error>> {
     assert_eq!(ts.token_type, JwtTokenType::DPoP);
     let payload = TokenValidationRequest {
         token: ts.access_token.to_owned(),
+        client_assertion: None,
+        client_assertion_type: None,
     };
     let token_info = validate_token(&ts.access_token, payload).await?;
     assert!(token_info.cnf.is_some());
-    assert_eq!(token_info.cnf.unwrap().jkt, fingerprint);
+    assert_eq!(token_info.cnf.unwrap().jkt, Some(fingerprint.clone()));
 
     Ok(())
 }
@@ -780,9 +1292,23 @@ async fn test_authorization_code_flow_ephemeral_client() -> Result<(), Box<dyn E
         redirect_uri: redirect_uri.to_owned(),
         scopes: None,
         state: None,
+        response_mode: None,
+        resource: None,
+        claims: None,
+        acr_values: None,
         nonce: Some(nonce.to_owned()),
         code_challenge: Some(challenge_s256),
         code_challenge_method: Some("S256".to_string()),
+        tos_accept: None,
+        profile_values: None,
+        consent_accept: None,
+        remember_me: None,
+        totp: None,
+        recovery_code: None,
+        email_otp: None,
+        trust_device: None,
+        yubico_otp: None,
+        sms_otp: None,
     };
     let res = client
         .post(&url_auth)
@@ -807,6 +1333,9 @@ async fn test_authorization_code_flow_ephemeral_client() -> Result<(), Box<dyn E
         username: None,
         password: None,
         refresh_token: None,
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
     };
 
     let url_token = format!("{}/oidc/token", backend_url);
@@ -832,6 +1361,9 @@ async fn test_authorization_code_flow_ephemeral_client() -> Result<(), Box<dyn E
         username: None,
         password: None,
         refresh_token: Some(ts.refresh_token.clone().unwrap()),
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
     };
     let res = client.post(&url_token).form(&req).send().await?;
     assert!(res.status().is_success());
@@ -906,6 +1438,9 @@ async fn test_auth_headers() -> Result<(), Box<dyn Error>> {
         username: Some(USERNAME.to_string()),
         password: Some(PASSWORD.to_string()),
         refresh_token: None,
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
     };
     let res = client.post(&url_token).form(&body).send().await?;
     assert!(res.status().is_success());
@@ -996,6 +1531,9 @@ async fn test_token_introspection() -> Result<(), Box<dyn Error>> {
         username: Some(USERNAME.to_string()),
         password: Some(PASSWORD.to_string()),
         refresh_token: None,
+        resource: None,
+        client_assertion: None,
+        client_assertion_type: None,
     };
     let res = client.post(&url_token).form(&body).send().await?;
     assert!(res.status().is_success());
@@ -1004,6 +1542,8 @@ async fn test_token_introspection() -> Result<(), Box<dyn Error>> {
     // make sure introspection is fine
     let mut payload = TokenValidationRequest {
         token: ts.access_token.clone(),
+        client_assertion: None,
+        client_assertion_type: None,
     };
     let url = format!("{}/oidc/introspect", backend_url);
 
@@ -24,6 +24,7 @@ async fn test_groups() -> Result<(), Box<dyn Error>> {
     // add a group
     let new_group = NewGroupRequest {
         group: "group123".to_string(),
+        force_mfa: None,
     };
     let res = reqwest::Client::new()
         .post(&url)
@@ -34,10 +35,12 @@ async fn test_groups() -> Result<(), Box<dyn Error>> {
     assert_eq!(res.status(), 200);
     let group = res.json::<Group>().await?;
     assert_eq!(group.name, "group123");
+    assert!(!group.force_mfa);
 
     // modify the group
     let upd_group = NewGroupRequest {
         group: "group456".to_string(),
+        force_mfa: Some(true),
     };
     let url_name = format!("{}/{}", url, group.id);
     let res = reqwest::Client::new()
@@ -49,6 +52,7 @@ async fn test_groups() -> Result<(), Box<dyn Error>> {
     assert_eq!(res.status(), 200);
     let upd_grp = res.json::<Group>().await?;
     assert_eq!(upd_group.group, upd_grp.name);
+    assert!(upd_grp.force_mfa);
 
     // delete the group
     let url_del = format!("{}/{}", url, upd_grp.id);
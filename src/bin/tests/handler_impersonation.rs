@@ -0,0 +1,152 @@
+use crate::common::{get_auth_headers, get_backend_url};
+use pretty_assertions::assert_eq;
+use rauthy_api_types::generic::Language;
+use rauthy_api_types::users::{
+    NewUserRequest, UpdateUserSelfRequest, UserResponse, WebauthnRegStartRequest,
+};
+use rauthy_common::constants::CSRF_HEADER;
+use reqwest::header::{HeaderMap, HeaderValue, COOKIE, SET_COOKIE};
+use std::error::Error;
+
+mod common;
+
+#[tokio::test]
+async fn test_impersonated_session_is_read_only() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let client = reqwest::Client::new();
+
+    // admins cannot be impersonated -> we need a new user
+    let new_user = NewUserRequest {
+        given_name: "Dick".to_string(),
+        family_name: Some("Grayson".to_string()),
+        email: "dick@batcave.io".to_string(),
+        language: Language::En,
+        roles: vec!["user".to_string()],
+        groups: None,
+        user_expires: None,
+        external_id: None,
+    };
+    let res = client
+        .post(format!("{}/users", backend_url))
+        .headers(auth_headers.clone())
+        .json(&new_user)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let user = res.json::<UserResponse>().await?;
+
+    let res = client
+        .post(format!("{}/users/{}/impersonate", backend_url, user.id))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let cookies = res
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|c| c.to_str().ok()?.split_once(';').map(|(c, _)| c.to_string()))
+        .collect::<Vec<_>>()
+        .join("; ");
+    let csrf = res.headers().get(CSRF_HEADER).unwrap().clone();
+    let mut headers = HeaderMap::new();
+    headers.append(COOKIE, HeaderValue::from_str(&cookies)?);
+    headers.append(CSRF_HEADER, csrf);
+
+    // looking at the account is fine
+    let res = client
+        .get(format!("{}/users/{}", backend_url, user.id))
+        .headers(headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // but it must not be modified
+    let payload = UpdateUserSelfRequest {
+        email: Some("nightwing@batcave.io".to_string()),
+        given_name: None,
+        family_name: None,
+        language: None,
+        password_current: None,
+        mfa_code: None,
+        password_new: None,
+        user_values: None,
+    };
+    let res = client
+        .put(format!("{}/users/{}/self", backend_url, user.id))
+        .headers(headers.clone())
+        .json(&payload)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+
+    let res = client
+        .post(format!("{}/users/{}/totp", backend_url, user.id))
+        .headers(headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+
+    let res = client
+        .post(format!(
+            "{}/users/{}/mfa/recovery_codes",
+            backend_url, user.id
+        ))
+        .headers(headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+
+    let payload = WebauthnRegStartRequest {
+        passkey_name: "Batarang".to_string(),
+        magic_link_id: None,
+    };
+    let res = client
+        .post(format!(
+            "{}/users/{}/webauthn/register/start",
+            backend_url, user.id
+        ))
+        .headers(headers.clone())
+        .json(&payload)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+
+    let res = client
+        .delete(format!(
+            "{}/users/{}/webauthn/delete/Batarang",
+            backend_url, user.id
+        ))
+        .headers(headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+
+    let res = client
+        .delete(format!("{}/users/{}/devices/trusted", backend_url, user.id))
+        .headers(headers)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 403);
+
+    // the user is still unchanged
+    let res = client
+        .get(format!("{}/users/{}", backend_url, user.id))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let unchanged = res.json::<UserResponse>().await?;
+    assert_eq!(unchanged.email, "dick@batcave.io");
+
+    let res = client
+        .delete(format!("{}/users/{}", backend_url, user.id))
+        .headers(auth_headers)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 204);
+
+    Ok(())
+}
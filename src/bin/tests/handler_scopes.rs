@@ -29,6 +29,7 @@ async fn test_scopes() -> Result<(), Box<dyn Error>> {
         scope: "scope123".to_string(),
         attr_include_access: None,
         attr_include_id: None,
+        claim_mappings: None,
     };
     let res = client
         .post(&url)
@@ -77,6 +78,37 @@ async fn test_scopes() -> Result<(), Box<dyn Error>> {
         force_mfa: init_client.force_mfa,
         client_uri: init_client.client_uri,
         contacts: init_client.contacts,
+        auth_response_alg: init_client.auth_response_alg,
+        jwks: init_client.jwks,
+        request_uris: init_client.request_uris,
+        frontchannel_logout_uri: init_client.frontchannel_logout_uri,
+        tls_client_auth: init_client.tls_client_auth,
+        tls_client_auth_subject_dn: init_client.tls_client_auth_subject_dn,
+        allowed_resources: init_client.allowed_resources,
+        jwks_uri: init_client.jwks_uri,
+        subject_type: init_client.subject_type,
+        sector_identifier_uri: init_client.sector_identifier_uri,
+        opaque_access_tokens: init_client.opaque_access_tokens,
+        id_token_encrypted_response_alg: init_client.id_token_encrypted_response_alg,
+        id_token_encrypted_response_enc: init_client.id_token_encrypted_response_enc,
+        userinfo_encrypted_response_alg: init_client.userinfo_encrypted_response_alg,
+        userinfo_encrypted_response_enc: init_client.userinfo_encrypted_response_enc,
+        userinfo_signed_response_alg: init_client.userinfo_signed_response_alg,
+        refresh_token_rotation: init_client.refresh_token_rotation,
+        refresh_token_grace_time: init_client.refresh_token_grace_time,
+        refresh_token_reuse_detection: init_client.refresh_token_reuse_detection,
+        consent_required: init_client.consent_required,
+        id_token_lifetime: init_client.id_token_lifetime,
+        refresh_token_lifetime: init_client.refresh_token_lifetime,
+        claim_template: init_client.claim_template,
+        session_lifetime: init_client.session_lifetime,
+        session_timeout: init_client.session_timeout,
+        deny_hybrid_passkeys: init_client.deny_hybrid_passkeys,
+        require_pkce_s256: init_client.require_pkce_s256,
+        response_types: init_client.response_types,
+        require_signed_request_object: init_client.require_signed_request_object,
+        backchannel_logout_uri: init_client.backchannel_logout_uri,
+        token_endpoint_auth_method: init_client.token_endpoint_auth_method,
     };
     let res = client
         .put(&url_client)
@@ -94,6 +126,7 @@ async fn test_scopes() -> Result<(), Box<dyn Error>> {
         scope: "scope456".to_string(),
         attr_include_access: None,
         attr_include_id: None,
+        claim_mappings: None,
     };
     let url_name = format!("{}/{}", url, scope.id);
     let res = client
@@ -44,6 +44,7 @@ async fn test_users() -> Result<(), Box<dyn Error>> {
             "non_existent".to_string(),
         ]),
         user_expires: None,
+        external_id: None,
     };
     let res = reqwest::Client::new()
         .post(&url)
@@ -103,6 +104,51 @@ async fn test_users() -> Result<(), Box<dyn Error>> {
         .await?;
     assert_eq!(res.status(), 204);
 
+    // the E-Mail of a soft-deleted user can be registered again right away
+    let res = reqwest::Client::new()
+        .post(&url)
+        .headers(auth_headers.clone())
+        .json(&new_user)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let alfred_new = res.json::<UserResponse>().await?;
+    assert_ne!(alfred_new.id, alfred.id);
+    assert_eq!(alfred_new.email, "alfred@batcave.io");
+
+    // which means the old one cannot be restored as long as the E-Mail is in use
+    let url_restore = format!("{}/restore", url_id);
+    let res = reqwest::Client::new()
+        .post(&url_restore)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 400);
+
+    let res = reqwest::Client::new()
+        .delete(format!("{}/users/{}", get_backend_url(), alfred_new.id))
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 204);
+
+    let res = reqwest::Client::new()
+        .post(&url_restore)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let restored = res.json::<UserResponse>().await?;
+    assert_eq!(restored.id, alfred.id);
+    assert_eq!(restored.email, "alfred@batcave.io");
+
+    let res = reqwest::Client::new()
+        .delete(&url_id)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 204);
+
     // get all users again and check that we are back to only 1
     let res = reqwest::Client::new()
         .get(&url)
@@ -0,0 +1,1381 @@
+#![cfg(feature = "test-support")]
+
+use chrono::{DateTime, TimeDelta, Utc};
+use pretty_assertions::assert_eq;
+use rauthy::test_support::{TestRauthy, ADMIN_EMAIL, ADMIN_PASSWORD, CLIENT_ID, CLIENT_SECRET};
+use rauthy_api_types::clients::ClientAccessPolicyRequest;
+use rauthy_api_types::users::MfaPurpose;
+use rauthy_common::clock;
+use rauthy_common::utils::{base64_url_encode, base64_url_no_pad_decode, get_rand};
+use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::clients_access::ClientAccessPolicy;
+use rauthy_models::entity::email_otp::UserEmailOtp;
+use rauthy_models::entity::totp::UserTotp;
+use rauthy_models::entity::trusted_devices::TrustedDevice;
+use rauthy_models::entity::user_expiry_notifications::UserExpiryNotification;
+use rauthy_models::entity::users::User;
+use rauthy_models::entity::webauthn::{WebauthnDiscoverableData, WebauthnServiceReq};
+use rauthy_models::events::event::{Event, EventType};
+use ring::{digest, hmac};
+use serde_json::json;
+use std::env;
+use std::error::Error;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// The `TestRauthy` and its clock are shared -> everything runs inside a single test.
+#[tokio::test]
+async fn test_harness() -> Result<(), Box<dyn Error>> {
+    // trusted devices, the account lockout and E-Mail OTP are disabled by default
+    env::set_var("MFA_TRUSTED_DEVICE_DAYS", "30");
+    env::set_var("ACCOUNT_LOCKOUT_THRESHOLD", "3");
+    env::set_var("MFA_EMAIL_OTP", "opt_in");
+    let rauthy = TestRauthy::start();
+    let issuer = rauthy.issuer();
+    let client = reqwest::Client::new();
+
+    // time can be frozen and is used for issued tokens
+    let frozen = DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap();
+    rauthy.freeze_time(frozen);
+
+    let res = client
+        .post(format!("{}/oidc/token", issuer))
+        .form(&[
+            ("grant_type", "client_credentials"),
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+        ])
+        .send()
+        .await?;
+    let status = res.status();
+    let ts = res.json::<serde_json::Value>().await?;
+    assert_eq!(status, 200, "{}", ts);
+    let access_token = ts["access_token"].as_str().unwrap().to_string();
+
+    let payload = access_token.split('.').nth(1).unwrap();
+    let claims =
+        serde_json::from_slice::<serde_json::Value>(&base64_url_no_pad_decode(payload).unwrap())?;
+    assert_eq!(claims["iat"].as_i64(), Some(frozen.timestamp()));
+
+    let validate = || async {
+        client
+            .post(format!("{}/oidc/token/validate", issuer))
+            .json(&serde_json::json!({ "token": access_token }))
+            .send()
+            .await
+            .unwrap()
+            .status()
+    };
+    assert_eq!(validate().await, 202);
+
+    // the `init_client` issues tokens with a lifetime of 60 seconds
+    rauthy.advance_time(TimeDelta::minutes(10));
+    assert_eq!(validate().await, 401);
+
+    rauthy.reset_time();
+
+    // E-Mails are captured
+    let res = client
+        .post(format!("{}/oidc/session", issuer))
+        .send()
+        .await?;
+    assert!(res.status().is_success());
+    let cookie = res
+        .headers()
+        .get("set-cookie")
+        .unwrap()
+        .to_str()?
+        .split_once(';')
+        .unwrap()
+        .0
+        .to_string();
+    let session = res.json::<serde_json::Value>().await?;
+    let csrf = session["csrf_token"].as_str().unwrap();
+
+    let res = client
+        .post(format!("{}/users/request_reset", issuer))
+        .header("cookie", cookie)
+        .header("csrf-token", csrf)
+        .json(&serde_json::json!({ "email": ADMIN_EMAIL }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let email = rauthy
+        .wait_for_email(ADMIN_EMAIL, Duration::from_secs(10))
+        .await
+        .expect("no password reset E-Mail captured");
+    assert_eq!(email.address, ADMIN_EMAIL);
+    assert!(rauthy.emails().iter().all(|e| e.address != ADMIN_EMAIL));
+
+    // terminating a session sends a back-channel logout to the clients inside of it
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let mut rauthy_client = Client::find("rauthy".to_string()).await.unwrap();
+    rauthy_client.backchannel_logout_uri =
+        Some(format!("http://{}/logout", listener.local_addr()?));
+    rauthy_client.save().await.unwrap();
+
+    let session = login(&client, &issuer).await?;
+    let id_claims = token_claims(&session.id_token)?;
+    let sub = id_claims["sub"].as_str().unwrap();
+    let sid = id_claims["sid"].as_str().unwrap();
+
+    let res = client
+        .delete(format!("{}/sessions/{}/{}", issuer, sub, sid))
+        .header("cookie", &session.cookie)
+        .header("csrf-token", &session.csrf)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    let body = tokio::time::timeout(Duration::from_secs(10), receive_form(&listener))
+        .await
+        .expect("no back-channel logout received")?;
+    let logout_token = body.strip_prefix("logout_token=").unwrap();
+    let header = serde_json::from_slice::<serde_json::Value>(
+        &base64_url_no_pad_decode(logout_token.split('.').next().unwrap()).unwrap(),
+    )?;
+    assert_eq!(header["typ"], "logout+jwt");
+    let claims = token_claims(logout_token)?;
+    assert_eq!(claims["iss"], issuer.as_str());
+    assert_eq!(claims["aud"], "rauthy");
+    assert_eq!(claims["sub"], sub);
+    assert_eq!(claims["sid"], sid);
+    assert!(claims["events"]
+        .get("http://schemas.openid.net/event/backchannel-logout")
+        .is_some());
+
+    rauthy_client.backchannel_logout_uri = None;
+    rauthy_client.save().await.unwrap();
+
+    // each user expiry is only notified once, a new expiry will be notified again
+    let admin = User::find_by_email(ADMIN_EMAIL.to_string()).await.unwrap();
+    let expires = Utc::now().timestamp() - 60;
+    assert!(UserExpiryNotification::insert(admin.id.clone(), expires)
+        .await
+        .unwrap());
+    assert!(!UserExpiryNotification::insert(admin.id.clone(), expires)
+        .await
+        .unwrap());
+    assert!(UserExpiryNotification::insert(admin.id, expires + 3600)
+        .await
+        .unwrap());
+
+    // a Passkey assertion can only be exchanged for an API Key, if it was made by the same user
+    // with the purpose `ApiKeyExchange`
+    let Login {
+        cookie,
+        csrf,
+        id_token,
+        ..
+    } = login(&client, &issuer).await?;
+    let admin_id = token_claims(&id_token)?["sub"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let svc_req = WebauthnServiceReq::new(admin_id.clone(), MfaPurpose::PasswordNew);
+    svc_req.save().await.unwrap();
+    let res = exchange_api_key(&client, &issuer, &cookie, &csrf, &svc_req.code).await?;
+    assert_eq!(res.status(), 403);
+
+    let svc_req = WebauthnServiceReq::new(
+        "za9UxpH7XVxqrtpEbThoqvn2".to_string(),
+        MfaPurpose::ApiKeyExchange,
+    );
+    svc_req.save().await.unwrap();
+    let res = exchange_api_key(&client, &issuer, &cookie, &csrf, &svc_req.code).await?;
+    assert_eq!(res.status(), 403);
+
+    let svc_req = WebauthnServiceReq::new(admin_id, MfaPurpose::ApiKeyExchange);
+    svc_req.save().await.unwrap();
+    let res = exchange_api_key(&client, &issuer, &cookie, &csrf, &svc_req.code).await?;
+    assert_eq!(res.status(), 200);
+    let exchanged = res.json::<serde_json::Value>().await?;
+    let name = exchanged["name"].as_str().unwrap();
+    assert!(name.starts_with("passkey-"));
+    let secret = exchanged["secret"].as_str().unwrap();
+    assert!(secret.starts_with(&format!("{}$", name)));
+    assert!(exchanged["expires"].as_i64().unwrap() > Utc::now().timestamp());
+
+    let res = client
+        .get(format!("{}/groups", issuer))
+        .header("authorization", format!("API-Key {}", secret))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    // each code can only be used once
+    let res = exchange_api_key(&client, &issuer, &cookie, &csrf, &svc_req.code).await?;
+    assert_eq!(res.status(), 404);
+
+    test_jti_denylist(&client, &issuer).await?;
+    test_group_force_mfa(&client, &issuer).await?;
+    test_refresh_token_reuse(rauthy, &client, &issuer).await?;
+    test_trusted_devices(rauthy, &client, &issuer).await?;
+    test_mfa_reset(rauthy, &client, &issuer).await?;
+    test_account_lockout(rauthy, &client, &issuer).await?;
+    test_client_access_policy(&client, &issuer).await?;
+    test_resource_indicators(&client, &issuer).await?;
+    test_acr_values(&client, &issuer).await?;
+    test_signed_userinfo(&client, &issuer).await?;
+    test_email_otp(rauthy, &client, &issuer).await?;
+    test_passkey_rename(&client, &issuer).await?;
+    test_discoverable_passkey(&client, &issuer).await?;
+
+    Ok(())
+}
+
+/// Revoking a session or a user denies exactly the access tokens, which have been issued for it
+/// before, while tokens from a new login right afterward stay valid.
+async fn test_jti_denylist(client: &reqwest::Client, issuer: &str) -> Result<(), Box<dyn Error>> {
+    let validate = |token: &str| {
+        let req = client
+            .post(format!("{}/oidc/token/validate", issuer))
+            .json(&serde_json::json!({ "token": token }));
+        async move { req.send().await.unwrap().status() }
+    };
+
+    let first = login(client, issuer).await?;
+    let second = login(client, issuer).await?;
+    assert_eq!(validate(&first.access_token).await, 202);
+    assert_eq!(validate(&second.access_token).await, 202);
+
+    let id_claims = token_claims(&first.id_token)?;
+    let sub = id_claims["sub"].as_str().unwrap();
+    let sid = id_claims["sid"].as_str().unwrap();
+    let res = client
+        .delete(format!("{}/sessions/{}/{}", issuer, sub, sid))
+        .header("cookie", &first.cookie)
+        .header("csrf-token", &first.csrf)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    assert_eq!(validate(&first.access_token).await, 401);
+    assert_eq!(validate(&second.access_token).await, 202);
+
+    // issued within the same second as the revocation, but afterward
+    let third = login(client, issuer).await?;
+    assert_eq!(validate(&third.access_token).await, 202);
+
+    let res = client
+        .delete(format!("{}/sessions/{}", issuer, sub))
+        .header("cookie", &third.cookie)
+        .header("csrf-token", &third.csrf)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    assert_eq!(validate(&second.access_token).await, 401);
+    assert_eq!(validate(&third.access_token).await, 401);
+
+    let fourth = login(client, issuer).await?;
+    assert_eq!(validate(&fourth.access_token).await, 202);
+
+    Ok(())
+}
+
+/// A `force_mfa` group is inherited by the members of all nested groups. Without a 2nd factor,
+/// they cannot use the password flow, while the `rauthy` client still allows the enrollment.
+async fn test_group_force_mfa(
+    client: &reqwest::Client,
+    issuer: &str,
+) -> Result<(), Box<dyn Error>> {
+    let Login { cookie, csrf, .. } = login(client, issuer).await?;
+    let password_grant = || {
+        let req = client.post(format!("{}/oidc/token", issuer)).form(&[
+            ("grant_type", "password"),
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+            ("username", ADMIN_EMAIL),
+            ("password", ADMIN_PASSWORD),
+        ]);
+        async move { req.send().await.unwrap().status() }
+    };
+    assert_eq!(password_grant().await, 200);
+
+    let groups = client
+        .get(format!("{}/groups", issuer))
+        .header("cookie", &cookie)
+        .header("csrf-token", &csrf)
+        .send()
+        .await?
+        .json::<Vec<serde_json::Value>>()
+        .await?;
+    let admin_group_id = groups.iter().find(|g| g["name"] == "admin").unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let res = client
+        .post(format!("{}/groups", issuer))
+        .header("cookie", &cookie)
+        .header("csrf-token", &csrf)
+        .json(&serde_json::json!({ "group": "mfa_parent", "force_mfa": true }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let parent_id = res.json::<serde_json::Value>().await?["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let set_children = |children: Vec<String>| {
+        let req = client
+            .put(format!("{}/groups/{}/inheritance", issuer, parent_id))
+            .header("cookie", &cookie)
+            .header("csrf-token", &csrf)
+            .json(&serde_json::json!({ "children": children, "roles": [] }));
+        async move { req.send().await.unwrap().status() }
+    };
+    assert_eq!(set_children(vec![admin_group_id]).await, 200);
+
+    // the admin is in the nested `admin` group and has no 2nd factor -> MfaRequired
+    assert_eq!(password_grant().await, 406);
+    // the `rauthy` client login is still possible to enroll one
+    login(client, issuer).await?;
+
+    assert_eq!(set_children(Vec::new()).await, 200);
+    assert_eq!(password_grant().await, 200);
+
+    let res = client
+        .delete(format!("{}/groups/{}", issuer, parent_id))
+        .header("cookie", &cookie)
+        .header("csrf-token", &csrf)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+
+    Ok(())
+}
+
+/// With reuse detection, a rotated refresh token can be replayed within the grace time. A replay
+/// afterward revokes the whole token family and creates a `RefreshTokenReused` event.
+async fn test_refresh_token_reuse(
+    rauthy: &TestRauthy,
+    client: &reqwest::Client,
+    issuer: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut init_client = Client::find(CLIENT_ID.to_string()).await.unwrap();
+    let orig = init_client.clone();
+    init_client.refresh_token_rotation = true;
+    init_client.refresh_token_reuse_detection = true;
+    init_client.refresh_token_grace_time = Some(10);
+    init_client.save().await.unwrap();
+
+    let refresh = |refresh_token: String| {
+        let req = client.post(format!("{}/oidc/token", issuer)).form(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+            ("refresh_token", &refresh_token),
+        ]);
+        async move { req.send().await.unwrap() }
+    };
+    let refresh_token = |ts: serde_json::Value| ts["refresh_token"].as_str().unwrap().to_string();
+
+    let frozen = DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap();
+    rauthy.freeze_time(frozen);
+
+    let res = client
+        .post(format!("{}/oidc/token", issuer))
+        .form(&[
+            ("grant_type", "password"),
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+            ("username", ADMIN_EMAIL),
+            ("password", ADMIN_PASSWORD),
+        ])
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let rt_0 = refresh_token(res.json().await?);
+
+    let res = refresh(rt_0.clone()).await;
+    assert_eq!(res.status(), 200);
+    let rt_1 = refresh_token(res.json().await?);
+
+    // a replay within the grace time is fine, e.g. for concurrent requests
+    rauthy.advance_time(TimeDelta::seconds(5));
+    let res = refresh(rt_0.clone()).await;
+    assert_eq!(res.status(), 200);
+    let rt_2 = refresh_token(res.json().await?);
+
+    // afterward, it revokes the whole family
+    rauthy.advance_time(TimeDelta::seconds(10));
+    assert_eq!(refresh(rt_0).await.status(), 404);
+    assert_eq!(refresh(rt_1).await.status(), 404);
+    assert_eq!(refresh(rt_2).await.status(), 404);
+
+    let admin = User::find_by_email(ADMIN_EMAIL.to_string()).await.unwrap();
+    let mut reused = false;
+    for _ in 0..100 {
+        let events = Event::find_for_user(&admin.id).await.unwrap();
+        if events
+            .iter()
+            .any(|e| e.typ == EventType::RefreshTokenReused)
+        {
+            reused = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(reused, "no RefreshTokenReused event");
+
+    // without rotation, the same token is kept and can be used again
+    init_client.refresh_token_rotation = false;
+    init_client.save().await.unwrap();
+
+    let res = client
+        .post(format!("{}/oidc/token", issuer))
+        .form(&[
+            ("grant_type", "password"),
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+            ("username", ADMIN_EMAIL),
+            ("password", ADMIN_PASSWORD),
+        ])
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let rt = refresh_token(res.json().await?);
+
+    let res = refresh(rt.clone()).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(refresh_token(res.json().await?), rt);
+    rauthy.advance_time(TimeDelta::seconds(30));
+    assert_eq!(refresh(rt).await.status(), 200);
+
+    rauthy.reset_time();
+    orig.save().await.unwrap();
+
+    Ok(())
+}
+
+/// A trusted device skips the 2nd factor only for the user it has been created for, as long as
+/// it exists and is not expired, and never when the client explicitly requests MFA.
+async fn test_trusted_devices(
+    rauthy: &TestRauthy,
+    client: &reqwest::Client,
+    issuer: &str,
+) -> Result<(), Box<dyn Error>> {
+    let now = Utc::now().timestamp();
+    rauthy.freeze_time(DateTime::from_timestamp(now - now % 30, 0).unwrap());
+
+    let (user, secret) = create_totp_user("trusted_device@localhost.de").await;
+    let (other, _) = create_totp_user("trusted_device_other@localhost.de").await;
+    // each TOTP code can only be used once
+    rauthy.advance_time(TimeDelta::seconds(30));
+
+    let res = login_user(client, issuer, "rauthy", &user.email, json!({}), None).await?;
+    assert_eq!(res.status, 401);
+    assert_eq!(res.error_code.as_deref(), Some("totp_required"));
+
+    let res = login_user(
+        client,
+        issuer,
+        "rauthy",
+        &user.email,
+        json!({ "totp": totp_code(&secret), "trust_device": true }),
+        None,
+    )
+    .await?;
+    assert_eq!(res.status, 202);
+    let device_cookie = res.device_cookie.expect("no trusted device cookie");
+
+    let res = login_user(
+        client,
+        issuer,
+        "rauthy",
+        &user.email,
+        json!({}),
+        Some(&device_cookie),
+    )
+    .await?;
+    assert_eq!(res.status, 202);
+
+    // `acr_values=mfa` always requires the 2nd factor
+    let res = login_user(
+        client,
+        issuer,
+        "rauthy",
+        &user.email,
+        json!({ "acr_values": "urn:rauthy:acr:mfa" }),
+        Some(&device_cookie),
+    )
+    .await?;
+    assert_eq!(res.status, 401);
+    assert_eq!(res.error_code.as_deref(), Some("totp_required"));
+
+    // the cookie is bound to the user
+    let res = login_user(
+        client,
+        issuer,
+        "rauthy",
+        &other.email,
+        json!({}),
+        Some(&device_cookie),
+    )
+    .await?;
+    assert_eq!(res.status, 401);
+    assert_eq!(res.error_code.as_deref(), Some("totp_required"));
+
+    // a revoked device
+    for device in TrustedDevice::find_for_user(&user.id).await.unwrap() {
+        TrustedDevice::delete(&user.id, &device.id).await.unwrap();
+    }
+    let res = login_user(
+        client,
+        issuer,
+        "rauthy",
+        &user.email,
+        json!({}),
+        Some(&device_cookie),
+    )
+    .await?;
+    assert_eq!(res.status, 401);
+    assert_eq!(res.error_code.as_deref(), Some("totp_required"));
+
+    // an expired device
+    rauthy.advance_time(TimeDelta::seconds(30));
+    let res = login_user(
+        client,
+        issuer,
+        "rauthy",
+        &user.email,
+        json!({ "totp": totp_code(&secret), "trust_device": true }),
+        None,
+    )
+    .await?;
+    assert_eq!(res.status, 202);
+    let device_cookie = res.device_cookie.expect("no trusted device cookie");
+
+    rauthy.advance_time(TimeDelta::days(31));
+    let res = login_user(
+        client,
+        issuer,
+        "rauthy",
+        &user.email,
+        json!({}),
+        Some(&device_cookie),
+    )
+    .await?;
+    assert_eq!(res.status, 401);
+    assert_eq!(res.error_code.as_deref(), Some("totp_required"));
+
+    rauthy.reset_time();
+    user.delete().await.unwrap();
+    other.delete().await.unwrap();
+
+    Ok(())
+}
+
+/// An MFA reset by an admin removes all 2nd factors, signs the user out everywhere and only
+/// allows the login to the account until a new 2nd factor has been enrolled.
+async fn test_mfa_reset(
+    rauthy: &TestRauthy,
+    client: &reqwest::Client,
+    issuer: &str,
+) -> Result<(), Box<dyn Error>> {
+    let now = Utc::now().timestamp();
+    rauthy.freeze_time(DateTime::from_timestamp(now - now % 30, 0).unwrap());
+
+    let (user, secret) = create_totp_user("mfa_reset@localhost.de").await;
+    rauthy.advance_time(TimeDelta::seconds(30));
+
+    let res = login_user(
+        client,
+        issuer,
+        "rauthy",
+        &user.email,
+        json!({ "totp": totp_code(&secret) }),
+        None,
+    )
+    .await?;
+    assert_eq!(res.status, 202);
+    let session_info = |cookie: String| {
+        let req = client
+            .get(format!("{}/oidc/sessioninfo", issuer))
+            .header("cookie", cookie);
+        async move { req.send().await.unwrap().status() }
+    };
+    assert_eq!(session_info(res.cookie.clone()).await, 200);
+
+    let admin = login(client, issuer).await?;
+    let res_reset = client
+        .post(format!("{}/users/{}/mfa/reset", issuer, user.id))
+        .header("cookie", &admin.cookie)
+        .header("csrf-token", &admin.csrf)
+        .send()
+        .await?;
+    assert_eq!(res_reset.status(), 200);
+
+    assert!(!UserTotp::is_enabled(&user.id).await.unwrap());
+    assert!(User::find(user.id.clone()).await.unwrap().mfa_reenroll);
+    assert_eq!(session_info(res.cookie).await, 401);
+    assert!(rauthy
+        .wait_for_email(&user.email, Duration::from_secs(10))
+        .await
+        .is_some());
+
+    // without a 2nd factor, only the account dashboard is allowed
+    let res = login_user(client, issuer, CLIENT_ID, &user.email, json!({}), None).await?;
+    assert_eq!(res.status, 406);
+    let res = login_user(client, issuer, "rauthy", &user.email, json!({}), None).await?;
+    assert_eq!(res.status, 202);
+
+    // a new 2nd factor lifts the restriction
+    let secret = UserTotp::enroll(user.id.clone()).await.unwrap();
+    UserTotp::confirm(&user.id, &totp_code(&secret))
+        .await
+        .unwrap();
+    rauthy.advance_time(TimeDelta::seconds(30));
+    let res = login_user(
+        client,
+        issuer,
+        CLIENT_ID,
+        &user.email,
+        json!({ "totp": totp_code(&secret) }),
+        None,
+    )
+    .await?;
+    assert_eq!(res.status, 202);
+    assert!(!User::find(user.id.clone()).await.unwrap().mfa_reenroll);
+
+    rauthy.reset_time();
+    user.delete().await.unwrap();
+
+    Ok(())
+}
+
+/// Too many failed password logins lock the account, even for the correct password, until the
+/// lockout duration is over or an admin unlocks it.
+async fn test_account_lockout(
+    rauthy: &TestRauthy,
+    client: &reqwest::Client,
+    issuer: &str,
+) -> Result<(), Box<dyn Error>> {
+    let user = create_user("lockout@localhost.de").await;
+    rauthy.freeze_time(DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap());
+
+    let password_grant = |password: &'static str| {
+        let req = client.post(format!("{}/oidc/token", issuer)).form(&[
+            ("grant_type", "password"),
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+            ("username", user.email.as_str()),
+            ("password", password),
+        ]);
+        async move {
+            let res = req.send().await.unwrap();
+            let status = res.status().as_u16();
+            let body = res.json::<serde_json::Value>().await.unwrap();
+            (status, body["code"].as_str().map(String::from))
+        }
+    };
+    let lock = || async {
+        for _ in 0..3 {
+            let (status, code) = password_grant("WrongPassword1337").await;
+            assert_eq!(status, 401);
+            assert_eq!(code.as_deref(), Some("invalid_credentials"));
+        }
+        let (status, code) = password_grant(ADMIN_PASSWORD).await;
+        assert_eq!(status, 401);
+        assert_eq!(code.as_deref(), Some("account_locked"));
+    };
+
+    // unlocks automatically after `ACCOUNT_LOCKOUT_DURATION`
+    lock().await;
+    rauthy.advance_time(TimeDelta::seconds(299));
+    assert_eq!(
+        password_grant(ADMIN_PASSWORD).await.1.as_deref(),
+        Some("account_locked")
+    );
+    rauthy.advance_time(TimeDelta::seconds(2));
+    assert_eq!(password_grant(ADMIN_PASSWORD).await.0, 200);
+
+    // or immediately by an admin
+    lock().await;
+    let admin = login(client, issuer).await?;
+    let res = client
+        .post(format!("{}/users/{}/unlock", issuer, user.id))
+        .header("cookie", &admin.cookie)
+        .header("csrf-token", &admin.csrf)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    assert_eq!(password_grant(ADMIN_PASSWORD).await.0, 200);
+
+    rauthy.reset_time();
+    user.delete().await.unwrap();
+
+    Ok(())
+}
+
+/// With an access policy, only the listed users can log in to the client, no matter the flow.
+async fn test_client_access_policy(
+    client: &reqwest::Client,
+    issuer: &str,
+) -> Result<(), Box<dyn Error>> {
+    let admin = User::find_by_email(ADMIN_EMAIL.to_string()).await.unwrap();
+    let user = create_user("access_policy@localhost.de").await;
+    ClientAccessPolicy::upsert(
+        CLIENT_ID.to_string(),
+        ClientAccessPolicyRequest {
+            users: vec![admin.id.clone()],
+            groups: Vec::default(),
+        },
+    )
+    .await
+    .unwrap();
+
+    let password_grant = |email: &str| {
+        let req = client.post(format!("{}/oidc/token", issuer)).form(&[
+            ("grant_type", "password"),
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+            ("username", email),
+            ("password", ADMIN_PASSWORD),
+        ]);
+        async move {
+            let res = req.send().await.unwrap();
+            let status = res.status().as_u16();
+            (status, res.json::<serde_json::Value>().await.unwrap())
+        }
+    };
+
+    let (status, body) = password_grant(&user.email).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["code"], "access_denied");
+    let res = login_user(client, issuer, CLIENT_ID, &user.email, json!({}), None).await?;
+    assert_eq!(res.status, 403);
+    assert_eq!(res.error_code.as_deref(), Some("access_denied"));
+
+    let (status, body) = password_grant(ADMIN_EMAIL).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // the `rauthy` client itself is never restricted
+    let res = login_user(client, issuer, "rauthy", &user.email, json!({}), None).await?;
+    assert_eq!(res.status, 202);
+
+    ClientAccessPolicy::upsert(
+        CLIENT_ID.to_string(),
+        ClientAccessPolicyRequest {
+            users: Vec::default(),
+            groups: Vec::default(),
+        },
+    )
+    .await
+    .unwrap();
+    let (status, _) = password_grant(&user.email).await;
+    assert_eq!(status, 200);
+
+    user.delete().await.unwrap();
+
+    Ok(())
+}
+
+/// A requested `resource` becomes the only `aud` of the access token, as long as it is allowed
+/// for the client.
+async fn test_resource_indicators(
+    client: &reqwest::Client,
+    issuer: &str,
+) -> Result<(), Box<dyn Error>> {
+    let client_credentials = |resource: Option<&'static str>| {
+        let mut form = vec![
+            ("grant_type", "client_credentials"),
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+        ];
+        if let Some(resource) = resource {
+            form.push(("resource", resource));
+        }
+        let req = client.post(format!("{}/oidc/token", issuer)).form(&form);
+        async move {
+            let res = req.send().await.unwrap();
+            let status = res.status().as_u16();
+            (status, res.json::<serde_json::Value>().await.unwrap())
+        }
+    };
+
+    let (status, body) = client_credentials(Some("https://api.example.com")).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["code"], "invalid_target");
+
+    let mut init_client = Client::find(CLIENT_ID.to_string()).await.unwrap();
+    let allowed_resources = init_client.allowed_resources.clone();
+    init_client.allowed_resources = Some("https://api.example.com".to_string());
+    init_client.save().await.unwrap();
+
+    let (status, body) = client_credentials(Some("https://api.example.com")).await;
+    assert_eq!(status, 200, "{}", body);
+    let claims = token_claims(body["access_token"].as_str().unwrap())?;
+    assert_eq!(claims["aud"], "https://api.example.com");
+
+    let (status, body) = client_credentials(Some("https://other.example.com")).await;
+    assert_eq!(status, 400);
+    assert_eq!(body["code"], "invalid_target");
+
+    // without a resource, the client itself stays the audience
+    let (status, body) = client_credentials(None).await;
+    assert_eq!(status, 200, "{}", body);
+    let claims = token_claims(body["access_token"].as_str().unwrap())?;
+    assert_eq!(claims["aud"], CLIENT_ID);
+
+    init_client.allowed_resources = allowed_resources;
+    init_client.save().await.unwrap();
+
+    Ok(())
+}
+
+/// A user without any MFA cannot satisfy the `acr_values` for MFA, while a lower requested level
+/// is fine.
+async fn test_acr_values(client: &reqwest::Client, issuer: &str) -> Result<(), Box<dyn Error>> {
+    let user = create_user("acr@localhost.de").await;
+
+    let res = login_user(
+        client,
+        issuer,
+        "rauthy",
+        &user.email,
+        json!({ "acr_values": "urn:rauthy:acr:mfa" }),
+        None,
+    )
+    .await?;
+    assert_eq!(res.status, 406);
+    assert_eq!(res.error_code.as_deref(), Some("mfa_required"));
+
+    let res = login_user(
+        client,
+        issuer,
+        "rauthy",
+        &user.email,
+        json!({ "acr_values": "urn:rauthy:acr:mfa urn:rauthy:acr:pwd" }),
+        None,
+    )
+    .await?;
+    assert_eq!(res.status, 202);
+
+    user.delete().await.unwrap();
+
+    Ok(())
+}
+
+/// With a `userinfo_signed_response_alg`, the userinfo is returned as a JWT for the client.
+async fn test_signed_userinfo(
+    client: &reqwest::Client,
+    issuer: &str,
+) -> Result<(), Box<dyn Error>> {
+    let userinfo = |access_token: String| {
+        let req = client
+            .get(format!("{}/oidc/userinfo", issuer))
+            .bearer_auth(access_token);
+        async move { req.send().await.unwrap() }
+    };
+
+    let session = login(client, issuer).await?;
+    let sub = token_claims(&session.id_token)?["sub"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let res = userinfo(session.access_token).await;
+    assert_eq!(res.status(), 200);
+    let plain = res.json::<serde_json::Value>().await?;
+    assert_eq!(plain["sub"], sub.as_str());
+
+    let mut rauthy_client = Client::find("rauthy".to_string()).await.unwrap();
+    rauthy_client.userinfo_signed_response_alg = Some("EdDSA".to_string());
+    rauthy_client.save().await.unwrap();
+
+    let session = login(client, issuer).await?;
+    let res = userinfo(session.access_token).await;
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["content-type"], "application/jwt");
+    let token = res.text().await?;
+    let header = serde_json::from_slice::<serde_json::Value>(
+        &base64_url_no_pad_decode(token.split('.').next().unwrap()).unwrap(),
+    )?;
+    assert_eq!(header["alg"], "EdDSA");
+    let claims = token_claims(&token)?;
+    assert_eq!(claims["iss"], issuer);
+    assert_eq!(claims["aud"], "rauthy");
+    assert_eq!(claims["sub"], sub.as_str());
+    assert_eq!(claims["email"], plain["email"]);
+
+    rauthy_client.userinfo_signed_response_alg = None;
+    rauthy_client.save().await.unwrap();
+
+    Ok(())
+}
+
+/// With an opt-in for E-Mail OTP, a login needs the code, which has been sent to the user.
+async fn test_email_otp(
+    rauthy: &TestRauthy,
+    client: &reqwest::Client,
+    issuer: &str,
+) -> Result<(), Box<dyn Error>> {
+    let user = create_user("email_otp@localhost.de").await;
+    UserEmailOtp::set_enabled(user.id.clone(), true)
+        .await
+        .unwrap();
+
+    let login = |email_otp: Option<String>| {
+        let extra = match email_otp {
+            Some(code) => json!({ "email_otp": code }),
+            None => json!({}),
+        };
+        login_user(client, issuer, "rauthy", &user.email, extra, None)
+    };
+
+    let res = login(None).await?;
+    assert_eq!(res.status, 401);
+    assert_eq!(res.error_code.as_deref(), Some("email_otp_required"));
+    let email = rauthy
+        .wait_for_email(&user.email, Duration::from_secs(10))
+        .await
+        .expect("no E-Mail OTP captured");
+    let code = email
+        .text
+        .split_once("Your login code is: ")
+        .unwrap()
+        .1
+        .get(..6)
+        .unwrap()
+        .to_string();
+
+    // a new login within the resend interval does not send another code
+    let res = login(None).await?;
+    assert_eq!(res.error_code.as_deref(), Some("email_otp_required"));
+    assert!(rauthy
+        .wait_for_email(&user.email, Duration::from_secs(1))
+        .await
+        .is_none());
+
+    let wrong = if code == "000000" { "000001" } else { "000000" };
+    let res = login(Some(wrong.to_string())).await?;
+    assert_eq!(res.status, 401);
+    assert_eq!(res.error_code.as_deref(), Some("invalid_credentials"));
+
+    let res = login(Some(code.clone())).await?;
+    assert_eq!(res.status, 202);
+
+    // each code can only be used once
+    let res = login(Some(code)).await?;
+    assert_eq!(res.status, 401);
+    assert_eq!(res.error_code.as_deref(), Some("invalid_credentials"));
+
+    user.delete().await.unwrap();
+
+    Ok(())
+}
+
+/// Passkeys can only be renamed by the user itself and only if they exist.
+async fn test_passkey_rename(client: &reqwest::Client, issuer: &str) -> Result<(), Box<dyn Error>> {
+    let admin = login(client, issuer).await?;
+    let admin_id = token_claims(&admin.id_token)?["sub"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let rename = |user_id: &str, name: &str, new_name: &str| {
+        let req = client
+            .put(format!(
+                "{}/users/{}/webauthn/rename/{}",
+                issuer, user_id, name
+            ))
+            .header("cookie", &admin.cookie)
+            .header("csrf-token", &admin.csrf)
+            .json(&json!({ "passkey_name": new_name }));
+        async move { req.send().await.unwrap().status() }
+    };
+
+    assert_eq!(rename(&admin_id, "Unknown", "New Name").await, 404);
+    assert_eq!(rename(&admin_id, "Unknown", "<script>").await, 400);
+
+    // even admins cannot rename passkeys of other users
+    let user = create_user("passkey_rename@localhost.de").await;
+    assert_eq!(rename(&user.id, "Unknown", "New Name").await, 403);
+    user.delete().await.unwrap();
+
+    Ok(())
+}
+
+/// The discoverable passkey login needs a session, and the challenge can only be used once and
+/// only with a known passkey.
+async fn test_discoverable_passkey(
+    client: &reqwest::Client,
+    issuer: &str,
+) -> Result<(), Box<dyn Error>> {
+    let url_start = format!("{}/oidc/authorize/passkey/start", issuer);
+    let res = client.post(&url_start).send().await?;
+    assert_eq!(res.status(), 401);
+
+    let res = client
+        .post(format!("{}/oidc/session", issuer))
+        .send()
+        .await?;
+    let cookie = res
+        .headers()
+        .get("set-cookie")
+        .unwrap()
+        .to_str()?
+        .split_once(';')
+        .unwrap()
+        .0
+        .to_string();
+    let session = res.json::<serde_json::Value>().await?;
+    let csrf = session["csrf_token"].as_str().unwrap().to_string();
+
+    let res = client
+        .post(&url_start)
+        .header("cookie", &cookie)
+        .header("csrf-token", &csrf)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let start = res.json::<serde_json::Value>().await?;
+    let code = start["code"].as_str().unwrap().to_string();
+    assert_eq!(code.len(), 48);
+    let public_key = &start["rcr"]["publicKey"];
+    assert!(!public_key["challenge"].as_str().unwrap().is_empty());
+    // the user is not known yet -> no credentials can be given
+    assert!(public_key["allowCredentials"]
+        .as_array()
+        .map(|creds| creds.is_empty())
+        .unwrap_or(true));
+
+    let finish = || {
+        let client_data = json!({
+            "type": "webauthn.get",
+            "challenge": public_key["challenge"],
+            "origin": format!("http://{}", issuer.split('/').nth(2).unwrap()),
+        });
+        let req = client
+            .post(format!("{}/oidc/authorize/passkey", issuer))
+            .header("cookie", &cookie)
+            .header("csrf-token", &csrf)
+            .json(&json!({
+                "passkey": {
+                    "code": code,
+                    "data": {
+                        "id": base64_url_encode(b"unknown-credential"),
+                        "rawId": base64_url_encode(b"unknown-credential"),
+                        "response": {
+                            "authenticatorData": base64_url_encode(&[0u8; 37]),
+                            "clientDataJSON": base64_url_encode(client_data.to_string().as_bytes()),
+                            "signature": base64_url_encode(&[0u8; 64]),
+                            "userHandle": base64_url_encode(&[7u8; 16]),
+                        },
+                        "type": "public-key",
+                    },
+                },
+                "client_id": "rauthy",
+                "redirect_uri": format!("{}/oidc/callback", issuer),
+            }));
+        async move {
+            let res = req.send().await.unwrap();
+            let status = res.status().as_u16();
+            (status, res.json::<serde_json::Value>().await.unwrap())
+        }
+    };
+
+    let (status, body) = finish().await;
+    assert_eq!(status, 401, "{}", body);
+    assert_eq!(body["code"], "invalid_credentials");
+
+    // the challenge has been deleted with the first try
+    assert!(WebauthnDiscoverableData::find(code.clone()).await.is_err());
+    let (status, _) = finish().await;
+    assert_eq!(status, 401);
+
+    Ok(())
+}
+
+async fn exchange_api_key(
+    client: &reqwest::Client,
+    issuer: &str,
+    cookie: &str,
+    csrf: &str,
+    mfa_code: &str,
+) -> Result<reqwest::Response, Box<dyn Error>> {
+    Ok(client
+        .post(format!("{}/api_keys/exchange", issuer))
+        .header("cookie", cookie)
+        .header("csrf-token", csrf)
+        .json(&serde_json::json!({
+            "mfa_code": mfa_code,
+            "access": [{ "group": "Groups", "access_rights": ["read"] }],
+        }))
+        .send()
+        .await?)
+}
+
+struct Login {
+    cookie: String,
+    csrf: String,
+    access_token: String,
+    id_token: String,
+}
+
+/// Logs in the admin for the `rauthy` client.
+async fn login(client: &reqwest::Client, issuer: &str) -> Result<Login, Box<dyn Error>> {
+    let res = client
+        .post(format!("{}/oidc/session", issuer))
+        .send()
+        .await?;
+    let cookie = res
+        .headers()
+        .get("set-cookie")
+        .unwrap()
+        .to_str()?
+        .split_once(';')
+        .unwrap()
+        .0
+        .to_string();
+    let session = res.json::<serde_json::Value>().await?;
+    let csrf = session["csrf_token"].as_str().unwrap().to_string();
+
+    let verifier = "oDXug9zfYqfz8ejcqMpALRPXfW8QhbKV2AVuScAt8xrLKDAmaRYQ4yRi2uqcH9ys";
+    let challenge =
+        base64_url_encode(digest::digest(&digest::SHA256, verifier.as_bytes()).as_ref());
+    let redirect_uri = format!("{}/oidc/callback", issuer);
+    let res = client
+        .post(format!(
+            "{}/oidc/authorize?client_id=rauthy&redirect_uri={}&response_type=code",
+            issuer, redirect_uri
+        ))
+        .header("cookie", &cookie)
+        .header("csrf-token", &csrf)
+        .json(&serde_json::json!({
+            "email": ADMIN_EMAIL,
+            "password": ADMIN_PASSWORD,
+            "client_id": "rauthy",
+            "redirect_uri": redirect_uri,
+            "code_challenge": challenge,
+            "code_challenge_method": "S256",
+        }))
+        .send()
+        .await?;
+    assert_eq!(res.status(), 202);
+    let location = res.headers().get("location").unwrap().to_str()?;
+    let code = location
+        .split_once("code=")
+        .unwrap()
+        .1
+        .split('&')
+        .next()
+        .unwrap();
+
+    let res = client
+        .post(format!("{}/oidc/token", issuer))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("client_id", "rauthy"),
+            ("code_verifier", verifier),
+        ])
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let ts = res.json::<serde_json::Value>().await?;
+    Ok(Login {
+        cookie,
+        csrf,
+        access_token: ts["access_token"].as_str().unwrap().to_string(),
+        id_token: ts["id_token"].as_str().unwrap().to_string(),
+    })
+}
+
+struct UserLogin {
+    status: u16,
+    error_code: Option<String>,
+    cookie: String,
+    device_cookie: Option<String>,
+}
+
+/// Logs in the given user with the `ADMIN_PASSWORD` inside a new session. `extra` is merged into
+/// the login request.
+async fn login_user(
+    client: &reqwest::Client,
+    issuer: &str,
+    client_id: &str,
+    email: &str,
+    extra: serde_json::Value,
+    device_cookie: Option<&str>,
+) -> Result<UserLogin, Box<dyn Error>> {
+    let res = client
+        .post(format!("{}/oidc/session", issuer))
+        .send()
+        .await?;
+    let cookie = res
+        .headers()
+        .get("set-cookie")
+        .unwrap()
+        .to_str()?
+        .split_once(';')
+        .unwrap()
+        .0
+        .to_string();
+    let session = res.json::<serde_json::Value>().await?;
+    let csrf = session["csrf_token"].as_str().unwrap().to_string();
+
+    let redirect_uri = if client_id == "rauthy" {
+        format!("{}/oidc/callback", issuer)
+    } else {
+        "http://localhost:3000/oidc/callback".to_string()
+    };
+    let challenge =
+        base64_url_encode(digest::digest(&digest::SHA256, get_rand(48).as_bytes()).as_ref());
+    let mut body = json!({
+        "email": email,
+        "password": ADMIN_PASSWORD,
+        "client_id": client_id,
+        "redirect_uri": redirect_uri,
+        "code_challenge": challenge,
+        "code_challenge_method": "S256",
+    });
+    for (key, value) in extra.as_object().unwrap() {
+        body[key] = value.clone();
+    }
+
+    let cookies = match device_cookie {
+        Some(device_cookie) => format!("{}; {}", cookie, device_cookie),
+        None => cookie.clone(),
+    };
+    let res = client
+        .post(format!(
+            "{}/oidc/authorize?client_id={}&redirect_uri={}&response_type=code",
+            issuer, client_id, redirect_uri
+        ))
+        .header("cookie", cookies)
+        .header("csrf-token", &csrf)
+        .json(&body)
+        .send()
+        .await?;
+
+    let status = res.status().as_u16();
+    let device_cookie = res
+        .headers()
+        .get_all("set-cookie")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find(|value| value.contains("RauthyTrustedDevice="))
+        .map(|value| value.split_once(';').unwrap().0.to_string());
+    let error_code = if status >= 400 {
+        res.json::<serde_json::Value>().await?["code"]
+            .as_str()
+            .map(String::from)
+    } else {
+        None
+    };
+
+    Ok(UserLogin {
+        status,
+        error_code,
+        cookie,
+        device_cookie,
+    })
+}
+
+/// Creates a user without any roles and groups and with the `ADMIN_PASSWORD`.
+async fn create_user(email: &str) -> User {
+    let admin = User::find_by_email(ADMIN_EMAIL.to_string()).await.unwrap();
+    User::insert(User {
+        id: get_rand(24),
+        email: email.to_string(),
+        roles: String::new(),
+        groups: None,
+        last_login: None,
+        last_failed_login: None,
+        failed_login_attempts: None,
+        webauthn_user_id: None,
+        mfa_reenroll: false,
+        ..admin
+    })
+    .await
+    .unwrap()
+}
+
+/// Creates a user with the `ADMIN_PASSWORD` and a confirmed TOTP secret, which is returned
+/// base32 encoded.
+async fn create_totp_user(email: &str) -> (User, String) {
+    let user = create_user(email).await;
+    let secret = UserTotp::enroll(user.id.clone()).await.unwrap();
+    UserTotp::confirm(&user.id, &totp_code(&secret))
+        .await
+        .unwrap();
+    (user, secret)
+}
+
+/// The current TOTP code for the base32 encoded secret at the Rauthy clock
+fn totp_code(secret: &str) -> String {
+    let mut bytes = Vec::with_capacity(secret.len() * 5 / 8);
+    let mut buf = 0u32;
+    let mut bits = 0;
+    for c in secret.bytes() {
+        let value = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567"
+            .iter()
+            .position(|b| *b == c)
+            .unwrap() as u32;
+        buf = (buf << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buf >> bits) as u8);
+        }
+    }
+
+    let counter = (clock::now().timestamp() / 30) as u64;
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &bytes);
+    let hash = hmac::sign(&key, &counter.to_be_bytes());
+    let hash = hash.as_ref();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let bin = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    format!("{:06}", bin % 1_000_000)
+}
+
+fn token_claims(token: &str) -> Result<serde_json::Value, Box<dyn Error>> {
+    let payload = token.split('.').nth(1).unwrap();
+    Ok(serde_json::from_slice(
+        &base64_url_no_pad_decode(payload).unwrap(),
+    )?)
+}
+
+/// Accepts a single form POST and returns its body.
+async fn receive_form(listener: &TcpListener) -> Result<String, Box<dyn Error>> {
+    let (mut stream, _) = listener.accept().await?;
+    let mut req = Vec::new();
+    let mut buf = [0u8; 1024];
+    let body = loop {
+        let n = stream.read(&mut buf).await?;
+        req.extend_from_slice(&buf[..n]);
+        let s = String::from_utf8_lossy(&req);
+        if let Some((head, body)) = s.split_once("\r\n\r\n") {
+            let len = head
+                .lines()
+                .find_map(|l| {
+                    l.to_lowercase()
+                        .strip_prefix("content-length: ")?
+                        .parse()
+                        .ok()
+                })
+                .unwrap_or(0);
+            if body.len() >= len {
+                break body.to_string();
+            }
+        }
+        if n == 0 {
+            return Err("connection closed before the body was received".into());
+        }
+    };
+    stream
+        .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+        .await?;
+    Ok(body)
+}
@@ -36,6 +36,11 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
     let cust_attr = UserAttrConfigRequest {
         name: "cust1".to_string(),
         desc: Some("some description".to_string()),
+        typ: None,
+        required: None,
+        required_at_login: None,
+        claim_eligible: None,
+        regex: None,
     };
     let res = client
         .post(&url_attrs)
@@ -53,6 +58,7 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
         scope: "cust_scope".to_string(),
         attr_include_access: None,
         attr_include_id: None,
+        claim_mappings: None,
     };
     let url_scopes = format!("{}/scopes", backend_url);
     let res = client
@@ -72,6 +78,7 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
         scope: "cust_scope".to_string(),
         attr_include_access: Some(vec!["cust1".to_string()]),
         attr_include_id: Some(vec!["cust1".to_string()]),
+        claim_mappings: None,
     };
     let url_scope = format!("{}/{}", url_scopes, scope.id);
     let res = client
@@ -127,6 +134,37 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
         force_mfa: c.force_mfa,
         client_uri: None,
         contacts: None,
+        auth_response_alg: None,
+        jwks: None,
+        request_uris: None,
+        frontchannel_logout_uri: None,
+        tls_client_auth: None,
+        tls_client_auth_subject_dn: None,
+        allowed_resources: None,
+        jwks_uri: None,
+        subject_type: None,
+        sector_identifier_uri: None,
+        opaque_access_tokens: false,
+        id_token_encrypted_response_alg: None,
+        id_token_encrypted_response_enc: None,
+        userinfo_encrypted_response_alg: None,
+        userinfo_encrypted_response_enc: None,
+        userinfo_signed_response_alg: None,
+        refresh_token_rotation: true,
+        refresh_token_grace_time: None,
+        refresh_token_reuse_detection: false,
+        consent_required: false,
+        id_token_lifetime: None,
+        refresh_token_lifetime: None,
+        claim_template: None,
+        session_lifetime: None,
+        session_timeout: None,
+        deny_hybrid_passkeys: false,
+        require_pkce_s256: false,
+        response_types: vec!["code".to_string()],
+        require_signed_request_object: false,
+        backchannel_logout_uri: None,
+        token_endpoint_auth_method: None,
     };
     let res = client
         .put(&url_client)
@@ -177,6 +215,11 @@ async fn test_cust_attrs() -> Result<(), Box<dyn Error>> {
     let cust_attr_mod = UserAttrConfigRequest {
         name: "cust2".to_string(),
         desc: Some("some description 2".to_string()),
+        typ: None,
+        required: None,
+        required_at_login: None,
+        claim_eligible: None,
+        regex: None,
     };
     let url_attr_mod = format!("{}/users/attr/{}", backend_url, cust_attr.name);
     let res = client
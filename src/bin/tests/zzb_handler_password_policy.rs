@@ -88,6 +88,7 @@ async fn test_password_policy() -> Result<(), Box<dyn Error>> {
         roles: vec!["user".to_string()],
         groups: None,
         user_expires: None,
+        external_id: None,
     };
     let mut res = reqwest::Client::new()
         .post(&url)
@@ -111,6 +112,7 @@ async fn test_password_policy() -> Result<(), Box<dyn Error>> {
         email_verified: false,
         user_expires: None,
         user_values: None,
+        must_change_password: None,
     };
     let user_url = format!("{}/{}", url, user.id);
     let mut res = reqwest::Client::new()
@@ -1,7 +1,8 @@
 use crate::common::{get_auth_headers, get_backend_url, CLIENT_SECRET};
 use pretty_assertions::{assert_eq, assert_ne};
 use rauthy_api_types::clients::{
-    ClientResponse, ClientSecretResponse, NewClientRequest, UpdateClientRequest,
+    ClientAccessPolicyRequest, ClientAccessPolicyResponse, ClientResponse, ClientSecretResponse,
+    NewClientRequest, UpdateClientRequest,
 };
 use rauthy_api_types::oidc::JwkKeyPairAlg;
 use std::error::Error;
@@ -118,6 +119,37 @@ async fn test_clients() -> Result<(), Box<dyn Error>> {
             "batman@localhost.de".to_string(),
             "@alfred:matrix.org".to_string(),
         ]),
+        auth_response_alg: None,
+        jwks: None,
+        request_uris: None,
+        frontchannel_logout_uri: None,
+        tls_client_auth: None,
+        tls_client_auth_subject_dn: None,
+        allowed_resources: None,
+        jwks_uri: None,
+        subject_type: None,
+        sector_identifier_uri: None,
+        opaque_access_tokens: false,
+        id_token_encrypted_response_alg: None,
+        id_token_encrypted_response_enc: None,
+        userinfo_encrypted_response_alg: None,
+        userinfo_encrypted_response_enc: None,
+        userinfo_signed_response_alg: None,
+        refresh_token_rotation: true,
+        refresh_token_grace_time: None,
+        refresh_token_reuse_detection: false,
+        consent_required: false,
+        id_token_lifetime: None,
+        refresh_token_lifetime: None,
+        claim_template: None,
+        session_lifetime: None,
+        session_timeout: None,
+        deny_hybrid_passkeys: false,
+        require_pkce_s256: false,
+        response_types: vec!["code".to_string()],
+        require_signed_request_object: false,
+        backchannel_logout_uri: None,
+        token_endpoint_auth_method: None,
     };
 
     let url_id = format!("{}/clients/{}", backend_url, client.id);
@@ -239,3 +271,76 @@ async fn test_client_secret() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_client_access_policy() -> Result<(), Box<dyn Error>> {
+    let auth_headers = get_auth_headers().await?;
+    let backend_url = get_backend_url();
+    let client = reqwest::Client::new();
+    let url = format!("{}/clients/init_client/access_policy", backend_url);
+
+    // without a policy, every user is allowed
+    let res = client
+        .get(&url)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let policy = res.json::<ClientAccessPolicyResponse>().await?;
+    assert!(!policy.restricted);
+
+    // unknown users must be rejected
+    let payload = ClientAccessPolicyRequest {
+        users: vec!["doesNotExist".to_string()],
+        groups: Vec::default(),
+    };
+    let res = client
+        .put(&url)
+        .headers(auth_headers.clone())
+        .json(&payload)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 404);
+
+    let payload = ClientAccessPolicyRequest {
+        users: vec!["za9UxpH7XVxqrtpEbThoqvn2".to_string()],
+        groups: Vec::default(),
+    };
+    let res = client
+        .put(&url)
+        .headers(auth_headers.clone())
+        .json(&payload)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let policy = res.json::<ClientAccessPolicyResponse>().await?;
+    assert!(policy.restricted);
+    assert_eq!(policy.users, vec!["za9UxpH7XVxqrtpEbThoqvn2".to_string()]);
+
+    let res = client
+        .get(&url)
+        .headers(auth_headers.clone())
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let policy = res.json::<ClientAccessPolicyResponse>().await?;
+    assert!(policy.restricted);
+    assert_eq!(policy.users, vec!["za9UxpH7XVxqrtpEbThoqvn2".to_string()]);
+
+    // empty lists remove the restriction again
+    let payload = ClientAccessPolicyRequest {
+        users: Vec::default(),
+        groups: Vec::default(),
+    };
+    let res = client
+        .put(&url)
+        .headers(auth_headers.clone())
+        .json(&payload)
+        .send()
+        .await?;
+    assert_eq!(res.status(), 200);
+    let policy = res.json::<ClientAccessPolicyResponse>().await?;
+    assert!(!policy.restricted);
+
+    Ok(())
+}
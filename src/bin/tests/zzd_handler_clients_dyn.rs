@@ -21,6 +21,7 @@ async fn test_dynamic_client() -> Result<(), Box<dyn Error>> {
         id_token_signed_response_alg: None,
         token_endpoint_auth_method: Some("none".to_string()),
         token_endpoint_auth_signing_alg: None,
+        userinfo_signed_response_alg: None,
         post_logout_redirect_uri: None,
     };
     let res = client.post(&url).json(&payload).send().await?;
@@ -0,0 +1,93 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use std::sync::atomic::{AtomicI64, Ordering};
+use time::OffsetDateTime;
+
+const NOT_FROZEN: i64 = i64::MIN;
+
+static OFFSET_MILLIS: AtomicI64 = AtomicI64::new(0);
+static FROZEN_MILLIS: AtomicI64 = AtomicI64::new(NOT_FROZEN);
+
+/// The current time all timestamps must be taken from.
+///
+/// It follows the system clock, unless it has been frozen or advanced, which is only possible
+/// in tests and with the `test-support` feature.
+#[inline]
+pub fn now() -> DateTime<Utc> {
+    let frozen = FROZEN_MILLIS.load(Ordering::Relaxed);
+    if frozen != NOT_FROZEN {
+        return DateTime::from_timestamp_millis(frozen).unwrap_or_default();
+    }
+
+    let offset = OFFSET_MILLIS.load(Ordering::Relaxed);
+    if offset == 0 {
+        Utc::now()
+    } else {
+        Utc::now() + TimeDelta::milliseconds(offset)
+    }
+}
+
+/// Same as `now()`, for everything working with the `time` crate.
+#[inline]
+pub fn now_utc() -> OffsetDateTime {
+    let now = now();
+    OffsetDateTime::from_unix_timestamp_nanos(now.timestamp_nanos_opt().unwrap_or_default() as i128)
+        .unwrap_or_else(|_| OffsetDateTime::now_utc())
+}
+
+/// Stops the clock at the given time.
+#[cfg(any(test, feature = "test-support"))]
+pub fn freeze(at: DateTime<Utc>) {
+    FROZEN_MILLIS.store(at.timestamp_millis(), Ordering::Relaxed);
+}
+
+/// Moves the clock forward, no matter if it is frozen or not.
+#[cfg(any(test, feature = "test-support"))]
+pub fn advance(by: TimeDelta) {
+    let millis = by.num_milliseconds();
+    let is_frozen = FROZEN_MILLIS
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |frozen| {
+            (frozen != NOT_FROZEN).then_some(frozen + millis)
+        })
+        .is_ok();
+    if !is_frozen {
+        OFFSET_MILLIS.fetch_add(millis, Ordering::Relaxed);
+    }
+}
+
+/// Follows the system clock again.
+#[cfg(any(test, feature = "test-support"))]
+pub fn reset() {
+    FROZEN_MILLIS.store(NOT_FROZEN, Ordering::Relaxed);
+    OFFSET_MILLIS.store(0, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    // the clock is global -> a single test avoids races between parallel tests
+    #[test]
+    fn test_clock() {
+        let sys = Utc::now();
+        assert!((now() - sys).num_seconds().abs() < 2);
+
+        let at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        freeze(at);
+        assert_eq!(now(), at);
+        assert_eq!(now_utc().unix_timestamp(), 1_700_000_000);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert_eq!(now(), at);
+
+        advance(TimeDelta::hours(1));
+        assert_eq!(now().timestamp(), 1_700_003_600);
+
+        reset();
+        advance(TimeDelta::days(1));
+        let diff = now() - Utc::now();
+        assert!((diff - TimeDelta::days(1)).num_seconds().abs() < 2);
+
+        reset();
+        assert!((now() - Utc::now()).num_seconds().abs() < 2);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::utils::build_trusted_proxies;
 use crate::DbType;
+use actix_web::cookie::SameSite;
 use actix_web::http::Uri;
 use chrono::{DateTime, Utc};
 use lazy_static::lazy_static;
@@ -13,9 +14,44 @@ use std::string::ToString;
 pub enum CookieMode {
     Host,
     Secure,
+    SecureNoPrefix,
     DangerInsecure,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum EmailOtpMode {
+    Disabled,
+    /// Users can enable E-Mail OTP for their account themselves
+    OptIn,
+    /// E-Mail OTP is used for all password accounts without another 2nd factor
+    All,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionAnomalyAction {
+    /// Only create an event
+    Warn,
+    /// Create an event and require a new login for this session
+    Reauth,
+    /// Create an event and invalidate the session together with its refresh tokens
+    Invalidate,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionLimitBehavior {
+    /// Evict the oldest active sessions to make room for the new one
+    Evict,
+    /// Reject the new login
+    Reject,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UserIdFormat {
+    /// Alphanumeric random ID with the given length
+    Rand(usize),
+    UuidV7,
+}
+
 pub const RAUTHY_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const CONTENT_TYPE_WEBP: &str = "image/webp";
 pub const HEADER_DPOP_NONCE: &str = "DPoP-Nonce";
@@ -25,6 +61,8 @@ pub const HEADER_JSON: (&str, &str) = ("content-type", "application/json");
 pub const HEADER_RETRY_NOT_BEFORE: &str = "x-retry-not-before";
 pub const APPLICATION_JSON: &str = "application/json";
 pub const TEXT_TURTLE: &str = "text/turtle";
+/// The base path of all SCIM 2.0 endpoints
+pub const SCIM_BASE_PATH: &str = "/auth/v1/scim/v2/";
 
 pub const TOKEN_API_KEY: &str = "API-Key";
 pub const TOKEN_BEARER: &str = "Bearer";
@@ -32,7 +70,9 @@ pub const TOKEN_DPOP: &str = "DPoP";
 pub const TOKEN_DPOP_NONCE: &str = "DPoP-nonce";
 pub const COOKIE_SESSION: &str = "RauthySession";
 pub const COOKIE_SESSION_FED_CM: &str = "RauthySessionFedCM";
+pub const COOKIE_SESSION_STATE: &str = "RauthySessionState";
 pub const COOKIE_MFA: &str = "RauthyMfa";
+pub const COOKIE_TRUSTED_DEVICE: &str = "RauthyTrustedDevice";
 pub const COOKIE_LOCALE: &str = "locale";
 pub const COOKIE_UPSTREAM_CALLBACK: &str = "UpstreamAuthCallback";
 pub const PROVIDER_LINK_COOKIE: &str = "rauthy-provider-link";
@@ -44,14 +84,40 @@ pub const PWD_CSRF_HEADER: &str = "pwd-csrf-token";
 pub const ARGON2ID_M_COST_MIN: u32 = 32768;
 pub const ARGON2ID_T_COST_MIN: u32 = 1;
 pub const API_KEY_LENGTH: usize = 64;
+pub const API_KEY_EXCHANGE_PREFIX: &str = "passkey-";
 pub const DEVICE_KEY_LENGTH: u8 = 64;
 pub const EVENTS_LATEST_LIMIT: u16 = 100;
+// Rauthy needs these to validate its own tokens -> cannot be modified by a client claim template
+pub const CLAIMS_PROTECTED: [&str; 19] = [
+    "iss",
+    "sub",
+    "aud",
+    "exp",
+    "nbf",
+    "iat",
+    "jti",
+    "nonce",
+    "typ",
+    "azp",
+    "scope",
+    "amr",
+    "acr",
+    "auth_time",
+    "at_hash",
+    "sid",
+    "cnf",
+    "did",
+    "act",
+];
 pub const GRANT_TYPE_DEVICE_CODE: &str = "urn:ietf:params:oauth:grant-type:device_code";
 pub const UPSTREAM_AUTH_CALLBACK_TIMEOUT_SECS: u16 = 300;
+/// The `response_type`s, which can be allowed for a client.
+pub const RESPONSE_TYPES_SUPPORTED: [&str; 1] = ["code"];
 
 pub const CACHE_TTL_APP: Option<i64> = Some(43200);
 pub const CACHE_TTL_AUTH_PROVIDER_CALLBACK: Option<i64> =
     Some(UPSTREAM_AUTH_CALLBACK_TIMEOUT_SECS as i64);
+pub const CACHE_TTL_CLIENT_JWKS: Option<i64> = Some(3600);
 pub const CACHE_TTL_SESSION: Option<i64> = Some(14400);
 // TODO maybe add a size limit to hiqlite to fix users cache, or simply always append?
 // No size limit means the cache could grow infinitely in theory, at least until every user from
@@ -66,9 +132,13 @@ pub const IDX_APP_VERSION: &str = "rauthy_app_version";
 pub const IDX_AUTH_PROVIDER: &str = "auth_provider_";
 pub const IDX_AUTH_PROVIDER_LOGO: &str = "auth_provider_logo_";
 pub const IDX_AUTH_PROVIDER_TEMPLATE: &str = "provider_json_tpl";
+pub const IDX_CLIENT_ACCESS_POLICY: &str = "client_access_policy_";
 pub const IDX_CLIENTS: &str = "clients_";
 pub const IDX_CLIENT_LOGO: &str = "client_logo_";
+pub const IDX_CLIENT_USER_METADATA: &str = "client_user_metadata_";
+pub const IDX_FIDO_MDS: &str = "fido_mds";
 pub const IDX_GROUPS: &str = "groups_";
+pub const IDX_GROUPS_INHERITANCE: &str = "groups_inheritance_";
 pub const IDX_JWK_KID: &str = "jwk_kid_";
 pub const IDX_JWK_LATEST: &str = "jwk_latest_";
 pub const IDX_JWKS: &str = "jkws_";
@@ -76,6 +146,8 @@ pub const IDX_LOGIN_TIME: &str = "login_time_";
 pub const IDX_MFA_APP: &str = "mfa_app_";
 pub const IDX_MFA_LOGIN_REQ: &str = "mfa_login_req_";
 pub const IDX_PASSWORD_RULES: &str = "password_rules_";
+pub const IDX_PASSWORD_RULES_GROUPS: &str = "password_rules_groups_";
+pub const IDX_ROLE_MAPPING_RULES: &str = "role_mapping_rules_";
 pub const IDX_ROLES: &str = "roles_";
 pub const IDX_SCOPES: &str = "scopes_";
 pub const IDX_SESSIONS: &str = "sessions";
@@ -83,6 +155,7 @@ pub const IDX_USERS: &str = "users_";
 pub const IDX_USER_COUNT: &str = "users_count_total";
 pub const IDX_USERS_VALUES: &str = "users_values_";
 pub const IDX_USER_ATTR_CONFIG: &str = "user_attrs_";
+pub const IDX_USER_PICTURE: &str = "user_picture_";
 pub const IDX_WEBAUTHN: &str = "webauthn_";
 
 // TODO drop `lazy_static` and use rust 1.80 built-in features
@@ -134,11 +207,13 @@ lazy_static! {
     pub static ref RE_CODE_VERIFIER: Regex = Regex::new(r"^[a-zA-Z0-9-\._~+/=]+$").unwrap();
     pub static ref RE_CONTACT: Regex = Regex::new(r"^[a-zA-Z0-9\+.@/:]{0,48}$").unwrap();
     pub static ref RE_DATE_STR: Regex = Regex::new(r"^[0-9]{4}-[0-9]{2}-[0-9]{2}$").unwrap();
+    pub static ref RE_EXTERNAL_ID: Regex = Regex::new(r"^[a-zA-Z0-9-_.:@|/]{1,128}$").unwrap();
     pub static ref RE_GRANT_TYPES: Regex = Regex::new(r"^(authorization_code|client_credentials|urn:ietf:params:oauth:grant-type:device_code|password|refresh_token)$").unwrap();
     pub static ref RE_GRANT_TYPES_EPHEMERAL: Regex = Regex::new(r"^(authorization_code|client_credentials|password|refresh_token)$").unwrap();
     pub static ref RE_GROUPS: Regex = Regex::new(r"^[a-z0-9-_/,:*]{2,64}$").unwrap();
     pub static ref RE_LOWERCASE: Regex = Regex::new(r"^[a-z0-9-_/]{2,128}$").unwrap();
     pub static ref RE_LOWERCASE_SPACE: Regex = Regex::new(r"^[a-z0-9-_/\s]{2,128}$").unwrap();
+    pub static ref RE_RESPONSE_MODE: Regex = Regex::new(r"^[a-z_.]{3,16}$").unwrap();
     pub static ref RE_MFA_CODE: Regex = Regex::new(r"^[a-zA-Z0-9]{48}$").unwrap();
     pub static ref RE_ORIGIN: Regex = {
         let additional_schemes = ADDITIONAL_ALLOWED_ORIGIN_SCHEMES.join("|");
@@ -151,6 +226,7 @@ lazy_static! {
     };
     pub static ref RE_PEM: Regex = Regex::new(r"^(-----BEGIN CERTIFICATE-----)[a-zA-Z0-9+/=\n]+(-----END CERTIFICATE-----)$").unwrap();
     pub static ref RE_PHONE: Regex = Regex::new(r"^\+[0-9]{0,32}$").unwrap();
+    pub static ref RE_PHONE_E164: Regex = Regex::new(r"^\+[1-9][0-9]{6,14}$").unwrap();
     // we have a pretty high upper limit for characters here just to be sure that even if
     // multiple values like 'urn:ietf:params:oauth:grant-type:device_code' would not fail
     pub static ref RE_SCOPE_SPACE: Regex = Regex::new(r"^[a-z0-9-_/:\s*]{0,512}$").unwrap();
@@ -158,8 +234,19 @@ lazy_static! {
     pub static ref RE_STREET: Regex = Regex::new(r"^[a-zA-Z0-9À-ÿ-.\s]{0,48}$").unwrap();
     pub static ref RE_URI: Regex = Regex::new(r"^[a-zA-Z0-9,.:/_\-&?=~#!$'()*+%]+$").unwrap();
     pub static ref RE_USER_NAME: Regex = Regex::new(r"^[a-zA-Z0-9À-ſ-\s\x{3041}-\x{3096}\x{30A0}-\x{30FF}\x{3400}-\x{4DB5}\x{4E00}-\x{9FCB}\x{F900}-\x{FA6A}\x{2E80}-\x{2FD5}\x{FF66}-\x{FF9F}\x{FFA1}-\x{FFDC}\x{31F0}-\x{31FF}]{1,32}$").unwrap();
+    // ModHex encoded Yubico OTP: up to 16 chars public ID + 32 chars OTP
+    pub static ref RE_YUBICO_OTP: Regex = Regex::new(r"^[cbdefghijklnrtuv]{32,48}$").unwrap();
+    pub static ref RE_AUTHENTICATOR_ATTACHMENT: Regex = Regex::new(r"^(platform|cross-platform)$").unwrap();
+    pub static ref RE_TZ: Regex = Regex::new(r"^[a-zA-Z0-9-_/+]{1,48}$").unwrap();
     pub static ref RE_TOKEN_68: Regex = Regex::new(r"^[a-zA-Z0-9-._~+/]+=*$").unwrap();
     pub static ref RE_TOKEN_ENDPOINT_AUTH_METHOD: Regex = Regex::new(r"^(client_secret_post|client_secret_basic|none)$").unwrap();
+    pub static ref RE_CLIENT_AUTH_METHOD: Regex = Regex::new(r"^(client_secret_basic|client_secret_post|client_secret_jwt|private_key_jwt)$").unwrap();
+    pub static ref RE_TLS_CLIENT_AUTH: Regex = Regex::new(r"^(tls_client_auth|self_signed_tls_client_auth)$").unwrap();
+    pub static ref RE_SUBJECT_TYPE: Regex = Regex::new(r"^(public|pairwise)$").unwrap();
+    pub static ref RE_JWE_ALG: Regex = Regex::new(r"^(RSA-OAEP|RSA-OAEP-256)$").unwrap();
+    pub static ref RE_JWE_ENC: Regex = Regex::new(r"^(A128GCM|A256GCM)$").unwrap();
+    pub static ref RE_KID: Regex = Regex::new(r"^[a-zA-Z0-9-_.:]{2,128}$").unwrap();
+    pub static ref RE_CLIENT_ASSERTION_TYPE: Regex = Regex::new(r"^urn:ietf:params:oauth:client-assertion-type:jwt-bearer$").unwrap();
 
     pub static ref USERINFO_STRICT: bool = env::var("USERINFO_STRICT")
         .unwrap_or_else(|_| String::from("true"))
@@ -211,10 +298,57 @@ lazy_static! {
         match var.as_str() {
             "host" => CookieMode::Host,
             "secure" => CookieMode::Secure,
+            "secure-no-prefix" => CookieMode::SecureNoPrefix,
             "danger-insecure" => CookieMode::DangerInsecure,
-            _ => panic!("COOKIE_MODE must be one of: host, secure, danger-insecure")
+            _ => panic!("COOKIE_MODE must be one of: host, secure, secure-no-prefix, danger-insecure")
         }
     };
+    pub static ref COOKIE_SAME_SITE: SameSite = {
+        let var = env::var("COOKIE_SAME_SITE").unwrap_or_else(|_| "lax".to_string());
+        let same_site = match var.as_str() {
+            "lax" => SameSite::Lax,
+            "strict" => SameSite::Strict,
+            "none" => SameSite::None,
+            _ => panic!("COOKIE_SAME_SITE must be one of: lax, strict, none")
+        };
+        if same_site == SameSite::None && *COOKIE_MODE == CookieMode::DangerInsecure {
+            panic!("COOKIE_SAME_SITE=none cannot be used with COOKIE_MODE=danger-insecure");
+        }
+        same_site
+    };
+    pub static ref COOKIE_DOMAIN: Option<String> = env::var("COOKIE_DOMAIN")
+        .ok()
+        .map(|domain| domain.trim().to_string())
+        .filter(|domain| !domain.is_empty())
+        .inspect(|_| {
+            if *COOKIE_MODE == CookieMode::Host {
+                panic!("COOKIE_DOMAIN cannot be used with COOKIE_MODE=host");
+            }
+        });
+    pub static ref HEADER_CSP: String = env::var("HEADER_CSP")
+        .unwrap_or_else(|_| "frame-ancestors 'none'; object-src 'none';".to_string())
+        .trim()
+        .to_string();
+    /// Per-path overrides for the CSP as `(path prefix, policy)`, sorted by the longest prefix
+    pub static ref HEADER_CSP_OVERRIDES: Vec<(String, String)> =
+        parse_csp_overrides(&env::var("HEADER_CSP_OVERRIDES").unwrap_or_default());
+    pub static ref HEADER_HSTS: String = env::var("HEADER_HSTS")
+        .unwrap_or_else(|_| "max-age=31536000;includeSubDomains".to_string())
+        .trim()
+        .to_string();
+    pub static ref HEADER_REFERRER_POLICY: String = env::var("HEADER_REFERRER_POLICY")
+        .unwrap_or_else(|_| "no-referrer".to_string())
+        .trim()
+        .to_string();
+    pub static ref HEADER_PERMISSIONS_POLICY: String = env::var("HEADER_PERMISSIONS_POLICY")
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    pub static ref HEADER_FRAME_OPTIONS: String = env::var("HEADER_FRAME_OPTIONS")
+        .unwrap_or_else(|_| "SAMEORIGIN".to_string())
+        .trim()
+        .to_string();
+
     pub static ref COOKIE_SET_PATH: bool = env::var("COOKIE_SET_PATH")
         .unwrap_or_else(|_| String::from("true"))
         .parse::<bool>()
@@ -358,6 +492,26 @@ lazy_static! {
             .collect::<Vec<String>>()
             .join(",");
 
+    pub static ref FEDERATION_ENABLE: bool = env::var("FEDERATION_ENABLE")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("FEDERATION_ENABLE cannot be parsed to bool - bad format");
+    pub static ref FEDERATION_AUTHORITY_HINTS: Vec<String> = env::var("FEDERATION_AUTHORITY_HINTS")
+        .unwrap_or_else(|_| String::from(""))
+        .split(' ')
+        .filter_map(|hint| hint.is_empty().not().then_some(hint.to_string()))
+        .collect();
+    pub static ref FEDERATION_TRUST_ANCHORS: Vec<String> = env::var("FEDERATION_TRUST_ANCHORS")
+        .unwrap_or_else(|_| String::from(""))
+        .split(' ')
+        .filter_map(|anchor| anchor.is_empty().not().then_some(anchor.to_string()))
+        .collect();
+    pub static ref FEDERATION_ENTITY_STATEMENT_LIFETIME: u64 = env::var("FEDERATION_ENTITY_STATEMENT_LIFETIME")
+        .unwrap_or_else(|_| String::from("86400"))
+        .parse::<u64>()
+        .expect("FEDERATION_ENTITY_STATEMENT_LIFETIME cannot be parsed to u64 - bad format");
+    pub static ref FEDERATION_ORGANIZATION_NAME: Option<String> = env::var("FEDERATION_ORGANIZATION_NAME").ok();
+
     pub static ref EXPERIMENTAL_FED_CM_ENABLE: bool = env::var("EXPERIMENTAL_FED_CM_ENABLE")
         .unwrap_or_else(|_| String::from("false"))
         .parse::<bool>()
@@ -398,7 +552,67 @@ lazy_static! {
         .parse::<bool>()
         .expect("Cannot parse USER_REG_OPEN_REDIRECT to bool");
 
+    pub static ref USER_ID_FORMAT: UserIdFormat = {
+        let var = env::var("USER_ID_FORMAT").unwrap_or_else(|_| "nanoid".to_string());
+        match var.as_str() {
+            "nanoid" => {
+                let len = env::var("USER_ID_LENGTH")
+                    .unwrap_or_else(|_| "24".to_string())
+                    .parse::<usize>()
+                    .expect("USER_ID_LENGTH cannot be parsed to usize - bad format");
+                if !(16..=64).contains(&len) {
+                    panic!("USER_ID_LENGTH must be in the range 16..=64");
+                }
+                UserIdFormat::Rand(len)
+            }
+            "uuidv7" => UserIdFormat::UuidV7,
+            _ => panic!("USER_ID_FORMAT must be one of: nanoid, uuidv7"),
+        }
+    };
+    pub static ref USER_DELETE_GRACE_DAYS: u16 = env::var("USER_DELETE_GRACE_DAYS")
+        .unwrap_or_else(|_| "30".to_string())
+        .parse::<u16>()
+        .expect("USER_DELETE_GRACE_DAYS cannot be parsed to u16 - bad format");
+    pub static ref LOGIN_HISTORY_RETENTION_DAYS: u16 = env::var("LOGIN_HISTORY_RETENTION_DAYS")
+        .unwrap_or_else(|_| "90".to_string())
+        .parse::<u16>()
+        .expect("LOGIN_HISTORY_RETENTION_DAYS cannot be parsed to u16 - bad format");
+
+    pub static ref TOS_VERSION: Option<String> = env::var("TOS_VERSION")
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    pub static ref TOS_URL: Option<String> = env::var("TOS_URL")
+        .ok()
+        .filter(|url| !url.is_empty());
+
+    pub static ref ACCOUNT_LOCKOUT_THRESHOLD: u32 = env::var("ACCOUNT_LOCKOUT_THRESHOLD")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse::<u32>()
+        .expect("ACCOUNT_LOCKOUT_THRESHOLD cannot be parsed to u32 - bad format");
+    pub static ref ACCOUNT_LOCKOUT_DURATION: u32 = env::var("ACCOUNT_LOCKOUT_DURATION")
+        .unwrap_or_else(|_| "300".to_string())
+        .parse::<u32>()
+        .expect("ACCOUNT_LOCKOUT_DURATION cannot be parsed to u32 - bad format");
+    pub static ref ACCOUNT_LOCKOUT_DURATION_MAX: u32 = env::var("ACCOUNT_LOCKOUT_DURATION_MAX")
+        .unwrap_or_else(|_| "86400".to_string())
+        .parse::<u32>()
+        .expect("ACCOUNT_LOCKOUT_DURATION_MAX cannot be parsed to u32 - bad format");
+
     pub static ref PEER_IP_HEADER_NAME: Option<String> = env::var("PEER_IP_HEADER_NAME").ok();
+    pub static ref MTLS_CLIENT_CERT_HEADER: Option<String> = env::var("MTLS_CLIENT_CERT_HEADER").ok();
+    pub static ref PAIRWISE_SUBJECT_SALT: String = env::var("PAIRWISE_SUBJECT_SALT").unwrap_or_default();
+
+    pub static ref JWK_AUTOROTATE_CRON: String = env::var("JWK_AUTOROTATE_CRON")
+        .unwrap_or_else(|_| String::from("0 30 3 1 * * *"));
+    pub static ref JWK_RETIREMENT_DAYS: u16 = env::var("JWK_RETIREMENT_DAYS")
+        .unwrap_or_else(|_| String::from("90"))
+        .parse::<u16>()
+        .expect("JWK_RETIREMENT_DAYS cannot be parsed to u16 - bad format");
+    pub static ref JWT_CLOCK_SKEW_SECS: u16 = env::var("JWT_CLOCK_SKEW_SECS")
+        .unwrap_or_else(|_| String::from("60"))
+        .parse::<u16>()
+        .expect("JWT_CLOCK_SKEW_SECS cannot be parsed to u16 - bad format");
 
     pub static ref POW_IT: u64 = env::var("POW_IT")
         .unwrap_or_else(|_| String::from("1000000"))
@@ -418,6 +632,45 @@ lazy_static! {
         .parse::<bool>()
         .expect("ADMIN_FORCE_MFA cannot be parsed to bool - bad format");
 
+    pub static ref MFA_EMAIL_OTP: EmailOtpMode = {
+        let var = env::var("MFA_EMAIL_OTP").unwrap_or_else(|_| "disabled".to_string());
+        match var.as_str() {
+            "disabled" => EmailOtpMode::Disabled,
+            "opt_in" => EmailOtpMode::OptIn,
+            "all" => EmailOtpMode::All,
+            _ => panic!("MFA_EMAIL_OTP must be one of: disabled, opt_in, all"),
+        }
+    };
+    pub static ref MFA_EMAIL_OTP_LIFETIME: u16 = env::var("MFA_EMAIL_OTP_LIFETIME")
+        .unwrap_or_else(|_| String::from("300"))
+        .parse::<u16>()
+        .expect("MFA_EMAIL_OTP_LIFETIME cannot be parsed to u16 - bad format");
+    pub static ref MFA_EMAIL_OTP_RESEND_SECS: u16 = env::var("MFA_EMAIL_OTP_RESEND_SECS")
+        .unwrap_or_else(|_| String::from("60"))
+        .parse::<u16>()
+        .expect("MFA_EMAIL_OTP_RESEND_SECS cannot be parsed to u16 - bad format");
+    pub static ref SMS_OTP_LIFETIME: u16 = env::var("SMS_OTP_LIFETIME")
+        .unwrap_or_else(|_| String::from("300"))
+        .parse::<u16>()
+        .expect("SMS_OTP_LIFETIME cannot be parsed to u16 - bad format");
+    pub static ref SMS_OTP_RESEND_SECS: u16 = env::var("SMS_OTP_RESEND_SECS")
+        .unwrap_or_else(|_| String::from("60"))
+        .parse::<u16>()
+        .expect("SMS_OTP_RESEND_SECS cannot be parsed to u16 - bad format");
+    pub static ref YUBICO_OTP_CLIENT_ID: Option<String> = env::var("YUBICO_OTP_CLIENT_ID").ok();
+    pub static ref YUBICO_OTP_SECRET_KEY: Option<String> = env::var("YUBICO_OTP_SECRET_KEY").ok();
+    pub static ref YUBICO_OTP_URL: String = env::var("YUBICO_OTP_URL")
+        .unwrap_or_else(|_| String::from("https://api.yubico.com/wsapi/2.0/verify"));
+    pub static ref MFA_TRUSTED_DEVICE_DAYS: u16 = env::var("MFA_TRUSTED_DEVICE_DAYS")
+        .unwrap_or_else(|_| String::from("0"))
+        .parse::<u16>()
+        .expect("MFA_TRUSTED_DEVICE_DAYS cannot be parsed to u16 - bad format");
+
+    pub static ref API_KEY_EXCHANGE_TTL: u32 = env::var("API_KEY_EXCHANGE_TTL")
+        .unwrap_or_else(|_| String::from("3600"))
+        .parse::<u32>()
+        .expect("API_KEY_EXCHANGE_TTL cannot be parsed to u32 - bad format");
+
     pub static ref DPOP_NONCE_EXP: u32 = env::var("DPOP_NONCE_EXP")
         .unwrap_or_else(|_| String::from("900"))
         .parse::<u32>()
@@ -436,6 +689,40 @@ lazy_static! {
         .unwrap_or_else(|_| String::from("14400"))
         .parse::<u32>()
         .expect("SESSION_LIFETIME cannot be parsed to u32 - bad format");
+    pub static ref SESSION_ANOMALY_ACTION: SessionAnomalyAction = {
+        let var = env::var("SESSION_ANOMALY_ACTION").unwrap_or_else(|_| "warn".to_string());
+        match var.as_str() {
+            "warn" => SessionAnomalyAction::Warn,
+            "reauth" => SessionAnomalyAction::Reauth,
+            "invalidate" => SessionAnomalyAction::Invalidate,
+            _ => panic!("SESSION_ANOMALY_ACTION must be one of: warn, reauth, invalidate"),
+        }
+    };
+    pub static ref SESSION_ANOMALY_ASN: bool = env::var("SESSION_ANOMALY_ASN")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("SESSION_ANOMALY_ASN cannot be parsed to bool - bad format");
+    pub static ref SESSION_ANOMALY_MAX_SPEED: u32 = env::var("SESSION_ANOMALY_MAX_SPEED")
+        .unwrap_or_else(|_| String::from("1000"))
+        .parse::<u32>()
+        .expect("SESSION_ANOMALY_MAX_SPEED cannot be parsed to u32 - bad format");
+    pub static ref SESSION_LIMIT_PER_USER: u16 = env::var("SESSION_LIMIT_PER_USER")
+        .unwrap_or_else(|_| String::from("0"))
+        .parse::<u16>()
+        .expect("SESSION_LIMIT_PER_USER cannot be parsed to u16 - bad format");
+    pub static ref SESSION_LIMIT_BEHAVIOR: SessionLimitBehavior = {
+        let var = env::var("SESSION_LIMIT_BEHAVIOR").unwrap_or_else(|_| "evict".to_string());
+        match var.as_str() {
+            "evict" => SessionLimitBehavior::Evict,
+            "reject" => SessionLimitBehavior::Reject,
+            _ => panic!("SESSION_LIMIT_BEHAVIOR must be one of: evict, reject"),
+        }
+    };
+    pub static ref SESSION_REMEMBER_ME_DAYS: u16 = env::var("SESSION_REMEMBER_ME_DAYS")
+        .unwrap_or_else(|_| String::from("0"))
+        .parse::<u16>()
+        .expect("SESSION_REMEMBER_ME_DAYS cannot be parsed to u16 - bad format");
+    pub static ref SESSION_LOCATION_HEADER: Option<String> = env::var("SESSION_LOCATION_HEADER").ok();
     pub static ref SESSION_RENEW_MFA: bool = env::var("SESSION_RENEW_MFA")
         .unwrap_or_else(|_| String::from("false"))
         .parse::<bool>()
@@ -453,6 +740,11 @@ lazy_static! {
         .unwrap_or_else(|_| String::from("30"))
         .parse::<u16>()
         .expect("SSE_KEEP_ALIVE cannot be parsed to u16 - bad format");
+    pub static ref EVENTS_GROUP_ADMIN_ROLE_PREFIX: Option<String> =
+        env::var("EVENTS_GROUP_ADMIN_ROLE_PREFIX")
+            .ok()
+            .map(|prefix| prefix.trim().to_string())
+            .filter(|prefix| !prefix.is_empty());
 
     pub static ref RAUTHY_ADMIN_EMAIL: Option<String> = env::var("RAUTHY_ADMIN_EMAIL").ok();
     pub static ref EMAIL_SUB_PREFIX: String = env::var("EMAIL_SUB_PREFIX")
@@ -472,6 +764,11 @@ lazy_static! {
         .unwrap_or_else(|_| "Rauthy <rauthy@localhost.de>".to_string())
         .trim()
         .to_string();
+    pub static ref EMAIL_DEFAULT_TZ: chrono_tz::Tz = env::var("EMAIL_DEFAULT_TZ")
+        .unwrap_or_else(|_| "UTC".to_string())
+        .trim()
+        .parse::<chrono_tz::Tz>()
+        .expect("EMAIL_DEFAULT_TZ cannot be parsed to a valid IANA timezone - bad format");
 
     pub static ref SWAGGER_UI_INTERNAL: bool = env::var("SESSION_VALIDATE_IP")
         .unwrap_or_else(|_| String::from("true"))
@@ -491,6 +788,43 @@ lazy_static! {
         .unwrap_or_else(|_| String::from("false"))
         .parse::<bool>()
         .expect("PASSWORD_RESET_COOKIE_BINDING cannot be parsed to bool - bad format");
+    pub static ref PASSWORD_EXPIRY_REMIND_DAYS: Vec<i64> = {
+        let mut days = env::var("PASSWORD_EXPIRY_REMIND_DAYS")
+            .unwrap_or_else(|_| String::from("10"))
+            .split(' ')
+            .filter(|d| !d.is_empty())
+            .map(|d| {
+                d.trim()
+                    .parse::<i64>()
+                    .expect("PASSWORD_EXPIRY_REMIND_DAYS cannot be parsed to i64 - bad format")
+            })
+            .collect::<Vec<i64>>();
+        days.sort();
+        days.dedup();
+        days
+    };
+    pub static ref PASSWORD_EXPIRY_QUIET_HOURS: Option<(u32, u32)> =
+        env::var("PASSWORD_EXPIRY_QUIET_HOURS").ok().and_then(|hours| {
+            let hours = hours.trim();
+            if hours.is_empty() {
+                return None;
+            }
+            let (start, end) = hours
+                .split_once('-')
+                .expect("PASSWORD_EXPIRY_QUIET_HOURS must be in the format 'start-end'");
+            let start = start
+                .trim()
+                .parse::<u32>()
+                .expect("PASSWORD_EXPIRY_QUIET_HOURS cannot be parsed to u32 - bad format");
+            let end = end
+                .trim()
+                .parse::<u32>()
+                .expect("PASSWORD_EXPIRY_QUIET_HOURS cannot be parsed to u32 - bad format");
+            if start > 23 || end > 23 {
+                panic!("PASSWORD_EXPIRY_QUIET_HOURS must be between 0 and 23");
+            }
+            Some((start, end))
+        });
 
     pub static ref WEBAUTHN_REQ_EXP: u64 = env::var("WEBAUTHN_REQ_EXP")
         .unwrap_or_else(|_| String::from("60"))
@@ -512,4 +846,96 @@ lazy_static! {
         .unwrap_or_else(|_| String::from("true"))
         .parse::<bool>()
         .expect("WEBAUTHN_NO_PASSWORD_EXPIRY cannot be parsed to bool - bad format");
+    pub static ref WEBAUTHN_FORCE_RESIDENT_KEY: bool = env::var("WEBAUTHN_FORCE_RESIDENT_KEY")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("WEBAUTHN_FORCE_RESIDENT_KEY cannot be parsed to bool - bad format");
+    pub static ref WEBAUTHN_AAGUID_ALLOW: Vec<String> = parse_aaguids("WEBAUTHN_AAGUID_ALLOW");
+    pub static ref WEBAUTHN_AAGUID_DENY: Vec<String> = parse_aaguids("WEBAUTHN_AAGUID_DENY");
+    pub static ref WEBAUTHN_MDS_ENABLE: bool = env::var("WEBAUTHN_MDS_ENABLE")
+        .unwrap_or_else(|_| String::from("false"))
+        .parse::<bool>()
+        .expect("WEBAUTHN_MDS_ENABLE cannot be parsed to bool - bad format");
+    pub static ref WEBAUTHN_MDS_URL: String = env::var("WEBAUTHN_MDS_URL")
+        .unwrap_or_else(|_| String::from("https://mds3.fidoalliance.org/"));
+}
+
+fn parse_aaguids(var: &str) -> Vec<String> {
+    env::var(var)
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|id| {
+            let id = id.to_lowercase();
+            if id.len() != 36 || !id.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+                panic!("{} contains an invalid AAGUID: {}", var, id);
+            }
+            id
+        })
+        .collect()
+}
+
+fn parse_csp_overrides(value: &str) -> Vec<(String, String)> {
+    let mut overrides = value
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let Some((path, policy)) = line.split_once(' ') else {
+                panic!(
+                    "HEADER_CSP_OVERRIDES must be in the format '<path prefix> <policy>': {}",
+                    line
+                );
+            };
+            if !path.starts_with('/') {
+                panic!(
+                    "HEADER_CSP_OVERRIDES contains a path prefix not starting with '/': {}",
+                    path
+                );
+            }
+            (path.to_string(), policy.trim().to_string())
+        })
+        .collect::<Vec<(String, String)>>();
+    overrides.sort_by_key(|(path, _)| std::cmp::Reverse(path.len()));
+    overrides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csp_overrides() {
+        let overrides = parse_csp_overrides(
+            r#"
+            /auth/v1/account frame-ancestors https://app.example.com;
+            /auth/v1/account/x frame-ancestors 'none';
+            "#,
+        );
+        assert_eq!(
+            overrides,
+            vec![
+                (
+                    "/auth/v1/account/x".to_string(),
+                    "frame-ancestors 'none';".to_string()
+                ),
+                (
+                    "/auth/v1/account".to_string(),
+                    "frame-ancestors https://app.example.com;".to_string()
+                ),
+            ]
+        );
+        assert!(parse_csp_overrides("").is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "not starting with '/'")]
+    fn test_parse_csp_overrides_path() {
+        parse_csp_overrides("auth/v1/account frame-ancestors 'none';");
+    }
+
+    #[test]
+    #[should_panic(expected = "must be in the format")]
+    fn test_parse_csp_overrides_format() {
+        parse_csp_overrides("/auth/v1/account");
+    }
 }
@@ -1,4 +1,7 @@
-use crate::constants::{PEER_IP_HEADER_NAME, PROXY_MODE, TRUSTED_PROXIES};
+use crate::constants::{
+    UserIdFormat, JWT_CLOCK_SKEW_SECS, PEER_IP_HEADER_NAME, PROXY_MODE, TRUSTED_PROXIES,
+    USER_ID_FORMAT,
+};
 use actix_web::dev::ServiceRequest;
 use actix_web::http::header::HeaderMap;
 use actix_web::HttpRequest;
@@ -16,6 +19,19 @@ const B64_URL_SAFE: engine::GeneralPurpose = general_purpose::URL_SAFE;
 const B64_URL_SAFE_NO_PAD: engine::GeneralPurpose = general_purpose::URL_SAFE_NO_PAD;
 const B64_STD: engine::GeneralPurpose = general_purpose::STANDARD;
 
+/// Returns `true` if a JWT with this `exp` has expired, with respect to the `JWT_CLOCK_SKEW_SECS`.
+#[inline]
+pub fn jwt_expired(exp: i64, now: i64) -> bool {
+    exp + (*JWT_CLOCK_SKEW_SECS as i64) < now
+}
+
+/// Returns `true` if a JWT with this `nbf` or `iat` is not valid yet, with respect to the
+/// `JWT_CLOCK_SKEW_SECS`.
+#[inline]
+pub fn jwt_not_yet_valid(nbf: i64, now: i64) -> bool {
+    nbf - (*JWT_CLOCK_SKEW_SECS as i64) > now
+}
+
 pub fn get_local_hostname() -> String {
     let hostname_os = gethostname();
     hostname_os
@@ -25,6 +41,19 @@ pub fn get_local_hostname() -> String {
 }
 
 // Returns an alphanumeric random String with the requested length
+tokio::task_local! {
+    /// The CSP nonce for the current request, if the CSP contains a `{nonce}` placeholder.
+    pub static CSP_NONCE: String;
+}
+
+/// Returns the CSP nonce for the current request, or an empty `String` outside of a request
+/// scope.
+pub fn csp_nonce() -> String {
+    CSP_NONCE
+        .try_with(|nonce| nonce.clone())
+        .unwrap_or_default()
+}
+
 pub fn get_rand(count: usize) -> String {
     rand::thread_rng()
         .sample_iter(&Alphanumeric)
@@ -82,6 +111,54 @@ pub fn new_store_id() -> String {
     get_rand(24)
 }
 
+/// Generates a new user ID in the format configured via `USER_ID_FORMAT`.
+pub fn new_user_id() -> String {
+    match *USER_ID_FORMAT {
+        UserIdFormat::Rand(len) => get_rand(len),
+        UserIdFormat::UuidV7 => new_uuid_v7(),
+    }
+}
+
+/// RFC 9562 UUIDv7: 48 bit unix timestamp in ms, followed by random bits, which keeps the IDs
+/// sortable by creation time.
+fn new_uuid_v7() -> String {
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+
+    let mut bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes[..6].copy_from_slice(&ts.to_be_bytes()[2..]);
+    bytes[6] = (bytes[6] & 0x0f) | 0x70;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Compares both values in constant time to not leak any information about secrets through
+/// timing differences. Only the length of the values may be leaked.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a
+        .iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
+
 // 192.0.0.8 is the IPv4 dummy address, according to RFC 7600.
 // On the Internet, according to IANA registry, this address cannot be
 // a destination address and is never global-reachable.
@@ -123,6 +200,16 @@ pub fn real_ip_from_svc_req(req: &ServiceRequest) -> Result<IpAddr, ErrorRespons
     }
 }
 
+/// Returns `true` if the direct peer of this request is one of the `TRUSTED_PROXIES`.
+/// Headers, which have been set by a reverse proxy, must only be trusted in this case.
+pub fn is_trusted_proxy_req(req: &HttpRequest) -> bool {
+    let use_dummy_addr = req.app_data::<UseDummyAddress>().is_some();
+    match parse_peer_addr(req.connection_info().peer_addr(), use_dummy_addr) {
+        Ok(peer_ip) => check_trusted_proxy(&peer_ip, use_dummy_addr).is_ok(),
+        Err(_) => false,
+    }
+}
+
 #[inline(always)]
 fn parse_peer_addr(peer_addr: Option<&str>, use_dummy_addr: bool) -> Result<IpAddr, ErrorResponse> {
     match peer_addr {
@@ -225,6 +312,41 @@ mod tests {
         assert_eq!(rnd.len(), 1024);
     }
 
+    #[test]
+    fn test_jwt_time_leeway() {
+        let skew = *JWT_CLOCK_SKEW_SECS as i64;
+        let now = 1_700_000_000;
+
+        assert!(!jwt_expired(now, now));
+        assert!(!jwt_expired(now - skew, now));
+        assert!(jwt_expired(now - skew - 1, now));
+
+        assert!(!jwt_not_yet_valid(now, now));
+        assert!(!jwt_not_yet_valid(now + skew, now));
+        assert!(jwt_not_yet_valid(now + skew + 1, now));
+    }
+
+    #[test]
+    fn test_new_uuid_v7() {
+        let id = new_uuid_v7();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "7");
+        assert!(["8", "9", "a", "b"].contains(&&id[19..20]));
+
+        // must be sortable by creation time
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert!(new_uuid_v7() > id);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"", b""));
+        assert!(constant_time_eq(b"abc123", b"abc123"));
+        assert!(!constant_time_eq(b"abc123", b"abc124"));
+        assert!(!constant_time_eq(b"abc123", b"abc12"));
+        assert!(!constant_time_eq(b"abc", b""));
+    }
+
     #[test]
     fn test_trusted_proxy_check() {
         env::set_var(
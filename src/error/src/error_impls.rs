@@ -1,4 +1,4 @@
-use crate::{ErrorResponse, ErrorResponseType};
+use crate::{request_id, ErrorCode, ErrorResponse, ErrorResponseType};
 use actix_multipart::MultipartError;
 use actix_web::error::BlockingError;
 use actix_web::http::header::{
@@ -32,11 +32,20 @@ impl ErrorResponse {
     {
         Self {
             timestamp: OffsetDateTime::now_utc().unix_timestamp(),
+            code: ErrorCode::from(&error),
             error,
             message: message.into(),
+            request_id: request_id::current(),
         }
     }
 
+    /// Overwrites the default `ErrorCode`, which is derived from the `ErrorResponseType`, with
+    /// a more specific one.
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = code;
+        self
+    }
+
     pub fn error_response_html(&self, body: String) -> HttpResponse {
         HttpResponseBuilder::new(self.status_code())
             .append_header(HEADER_HTML)
@@ -198,7 +207,8 @@ impl From<sqlx::Error> for ErrorResponse {
                 let s = e.to_string();
                 if s.contains("duplicate key") || s.contains("UNIQUE") {
                     // basically returns http 400 on duplicate id column errors -> no distinct err type
-                    (ErrorResponseType::BadRequest, s)
+                    return ErrorResponse::new(ErrorResponseType::BadRequest, s)
+                        .with_code(ErrorCode::AlreadyExists);
                 } else {
                     (ErrorResponseType::Database, s)
                 }
@@ -254,7 +264,13 @@ impl From<hiqlite::Error> for ErrorResponse {
             hiqlite::Error::Connect(err) => (ErrorResponseType::Connection, err.to_string().into()),
             // hiqlite::Error::Cryptr(err) => todo!(),
             hiqlite::Error::ConstraintViolation(err) => {
-                (ErrorResponseType::BadRequest, err.to_string().into())
+                let code = if err.contains("UNIQUE") {
+                    ErrorCode::AlreadyExists
+                } else {
+                    ErrorCode::BadRequest
+                };
+                return ErrorResponse::new(ErrorResponseType::BadRequest, err.to_string())
+                    .with_code(code);
             }
             // hiqlite::Error::Error(err) => todo!(),
             // hiqlite::Error::InitializeError(err) => todo!(),
@@ -343,6 +359,7 @@ impl From<validator::ValidationErrors> for ErrorResponse {
             ErrorResponseType::BadRequest,
             format!("Payload validation error: {:?}", value),
         )
+        .with_code(ErrorCode::ValidationFailed)
     }
 }
 
@@ -5,6 +5,7 @@ use std::fmt::Formatter;
 use utoipa::ToSchema;
 
 pub mod error_impls;
+pub mod request_id;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub enum ErrorResponseType {
@@ -40,6 +41,164 @@ impl Display for ErrorResponseType {
     }
 }
 
+/// Stable, machine-readable error codes.
+///
+/// In contrast to the `message`, these codes will never change for the same kind of error and
+/// can be used by clients to branch on, or to show localized error messages.
+/// New codes may be added in the future, so clients should always have a fallback.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The user is not allowed to access the requested client
+    AccessDenied,
+    /// The account is temporarily locked after too many failed logins
+    AccountLocked,
+    /// The entity already exists
+    AlreadyExists,
+    /// Generic bad request
+    BadRequest,
+    /// A connection to another service / node failed
+    Connection,
+    /// The CSRF token is missing or invalid
+    CsrfTokenInvalid,
+    /// Generic database error
+    Database,
+    /// The user or client is disabled
+    Disabled,
+    /// The given DPoP proof is invalid
+    DpopInvalid,
+    /// A DPoP nonce must be used
+    DpopNonceRequired,
+    /// The user needs to provide the one-time code, which has been sent via E-Mail
+    EmailOtpRequired,
+    /// Error during en- or decryption
+    Encryption,
+    /// Generic forbidden
+    Forbidden,
+    /// Unspecified internal error
+    #[default]
+    Internal,
+    /// The given user credentials are invalid
+    InvalidCredentials,
+    /// The request object (JAR) is invalid or cannot be validated
+    InvalidRequestObject,
+    /// The `request_uri` is not registered or does not return a valid request object
+    InvalidRequestUri,
+    /// The requested target resource (RFC 8707) is not allowed
+    InvalidTarget,
+    /// The given token is invalid or cannot be validated
+    InvalidToken,
+    /// MFA is required for this action
+    MfaRequired,
+    /// No valid session exists
+    NoSession,
+    /// The requested entity does not exist
+    NotFound,
+    /// The password has expired and must be reset
+    PasswordExpired,
+    /// The new password does not match the password policy
+    PasswordPolicy,
+    /// The password has expired and a reset E-Mail has been sent
+    PasswordRefresh,
+    /// The session has reached its max lifetime
+    SessionExpired,
+    /// The session has timed out because of inactivity
+    SessionTimeout,
+    /// The user has SMS OTP enabled and needs to provide the code, which has been sent via SMS
+    SmsOtpRequired,
+    /// Rate limit reached, retry later
+    TooManyRequests,
+    /// The user has TOTP enabled and needs to provide the current code
+    TotpRequired,
+    /// Generic unauthorized
+    Unauthorized,
+    /// The client is not allowed to use the requested `response_type`
+    UnauthorizedClient,
+    /// The requested `response_type` is not supported at all
+    UnsupportedResponseType,
+    /// The payload did not pass validation
+    ValidationFailed,
+    /// The user has a Yubico OTP key registered and needs to provide an OTP
+    YubicoOtpRequired,
+}
+
+impl From<&ErrorResponseType> for ErrorCode {
+    fn from(value: &ErrorResponseType) -> Self {
+        match value {
+            ErrorResponseType::BadRequest => Self::BadRequest,
+            ErrorResponseType::Connection => Self::Connection,
+            ErrorResponseType::CSRFTokenError => Self::CsrfTokenInvalid,
+            ErrorResponseType::Database | ErrorResponseType::DatabaseIo => Self::Database,
+            ErrorResponseType::Disabled => Self::Disabled,
+            ErrorResponseType::DPoP(_) => Self::DpopInvalid,
+            ErrorResponseType::Encryption => Self::Encryption,
+            ErrorResponseType::UseDpopNonce(_) => Self::DpopNonceRequired,
+            ErrorResponseType::Forbidden => Self::Forbidden,
+            ErrorResponseType::Internal => Self::Internal,
+            ErrorResponseType::JoseError => Self::InvalidToken,
+            ErrorResponseType::MfaRequired => Self::MfaRequired,
+            ErrorResponseType::NoSession => Self::NoSession,
+            ErrorResponseType::NotFound => Self::NotFound,
+            ErrorResponseType::PasswordExpired => Self::PasswordExpired,
+            ErrorResponseType::PasswordRefresh => Self::PasswordRefresh,
+            ErrorResponseType::SessionExpired => Self::SessionExpired,
+            ErrorResponseType::SessionTimeout => Self::SessionTimeout,
+            ErrorResponseType::TooManyRequests(_) => Self::TooManyRequests,
+            ErrorResponseType::Unauthorized | ErrorResponseType::WWWAuthenticate(_) => {
+                Self::Unauthorized
+            }
+        }
+    }
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AccessDenied => "access_denied",
+            Self::AccountLocked => "account_locked",
+            Self::AlreadyExists => "already_exists",
+            Self::BadRequest => "bad_request",
+            Self::Connection => "connection",
+            Self::CsrfTokenInvalid => "csrf_token_invalid",
+            Self::Database => "database",
+            Self::Disabled => "disabled",
+            Self::DpopInvalid => "dpop_invalid",
+            Self::DpopNonceRequired => "dpop_nonce_required",
+            Self::EmailOtpRequired => "email_otp_required",
+            Self::Encryption => "encryption",
+            Self::Forbidden => "forbidden",
+            Self::Internal => "internal",
+            Self::InvalidCredentials => "invalid_credentials",
+            Self::InvalidRequestObject => "invalid_request_object",
+            Self::InvalidRequestUri => "invalid_request_uri",
+            Self::InvalidTarget => "invalid_target",
+            Self::InvalidToken => "invalid_token",
+            Self::MfaRequired => "mfa_required",
+            Self::NoSession => "no_session",
+            Self::NotFound => "not_found",
+            Self::PasswordExpired => "password_expired",
+            Self::PasswordPolicy => "password_policy",
+            Self::PasswordRefresh => "password_refresh",
+            Self::SessionExpired => "session_expired",
+            Self::SessionTimeout => "session_timeout",
+            Self::SmsOtpRequired => "sms_otp_required",
+            Self::TooManyRequests => "too_many_requests",
+            Self::TotpRequired => "totp_required",
+            Self::Unauthorized => "unauthorized",
+            Self::UnauthorizedClient => "unauthorized_client",
+            Self::UnsupportedResponseType => "unsupported_response_type",
+            Self::ValidationFailed => "validation_failed",
+            Self::YubicoOtpRequired => "yubico_otp_required",
+        }
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 // This is the default `ErrorResponse` that could be the answer on almost every API endpoint in
 // case something is wrong.<br>
 // Except for input validations, every error will have this format and every possible error in the
@@ -49,5 +208,11 @@ impl Display for ErrorResponseType {
 pub struct ErrorResponse {
     pub timestamp: i64,
     pub error: ErrorResponseType,
+    /// Stable, machine-readable error code
+    #[serde(default)]
+    pub code: ErrorCode,
     pub message: Cow<'static, str>,
+    /// The id of the request, which caused this error, to correlate it with access logs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
@@ -0,0 +1,22 @@
+use std::future::Future;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Executes the given future with the given request id in scope. Each `ErrorResponse` and each
+/// `Event` created inside it will automatically be linked to this request.
+pub async fn scope<F>(request_id: String, f: F) -> F::Output
+where
+    F: Future,
+{
+    REQUEST_ID.scope(request_id, f).await
+}
+
+/// Returns the id of the request the current task is handling, if any.
+///
+/// Keep in mind that the id is only available on the task which handles the request, and not
+/// inside any tasks spawned from it.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
@@ -2,8 +2,8 @@ use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
     web, Error,
 };
-use chrono::Utc;
 use futures::future::LocalBoxFuture;
+use rauthy_common::clock;
 use rauthy_common::utils::real_ip_from_svc_req;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
@@ -75,7 +75,7 @@ where
             match rx.await {
                 Ok(exp) => {
                     if let Some(exp) = exp {
-                        if exp > Utc::now() {
+                        if exp > clock::now() {
                             let ts = exp.timestamp();
                             return Err(Error::from(ErrorResponse::new(
                                 ErrorResponseType::TooManyRequests(ts),
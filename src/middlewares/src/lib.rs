@@ -2,3 +2,5 @@ pub mod csrf_protection;
 pub mod ip_blacklist;
 pub mod logging;
 pub mod principal;
+pub mod request_id;
+pub mod security_headers;
@@ -7,7 +7,7 @@ use actix_web::{
 use futures::future::LocalBoxFuture;
 use lazy_static::lazy_static;
 use rauthy_common::utils::real_ip_from_svc_req;
-use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_error::{request_id, ErrorResponse, ErrorResponseType};
 use std::env;
 use std::future::{ready, Ready};
 use std::rc::Rc;
@@ -100,6 +100,7 @@ where
 async fn log_access(req: &ServiceRequest) -> Result<(), ErrorResponse> {
     let path = req.uri().path();
     let ip = real_ip_from_svc_req(req)?;
+    let request_id = request_id::current().unwrap_or_default();
 
     match *LOG_LEVEL_ACCESS {
         LogLevelAccess::Debug => {
@@ -107,8 +108,9 @@ async fn log_access(req: &ServiceRequest) -> Result<(), ErrorResponse> {
         }
         LogLevelAccess::Verbose => {
             info!(
-                "{} {:?} {} {} {:?}",
+                "{} {} {:?} {} {} {:?}",
                 ip,
+                request_id,
                 req.version(),
                 req.method(),
                 path,
@@ -126,8 +128,9 @@ async fn log_access(req: &ServiceRequest) -> Result<(), ErrorResponse> {
                 return Ok(());
             }
             info!(
-                "{} {:?} {} {} {:?}",
+                "{} {} {:?} {} {} {:?}",
                 ip,
+                request_id,
                 req.version(),
                 req.method(),
                 path,
@@ -144,8 +147,9 @@ async fn log_access(req: &ServiceRequest) -> Result<(), ErrorResponse> {
                 return Ok(());
             }
             info!(
-                "{} {:?} {} {} {:?}",
+                "{} {} {:?} {} {} {:?}",
                 ip,
+                request_id,
                 req.version(),
                 req.method(),
                 path,
@@ -3,7 +3,10 @@ use actix_web::{
     http, web, Error, HttpMessage,
 };
 use futures::future::LocalBoxFuture;
-use rauthy_common::constants::{COOKIE_SESSION, SESSION_VALIDATE_IP, TOKEN_API_KEY};
+use rauthy_common::clock;
+use rauthy_common::constants::{
+    COOKIE_SESSION, SCIM_BASE_PATH, SESSION_VALIDATE_IP, TOKEN_API_KEY,
+};
 use rauthy_common::utils::real_ip_from_svc_req;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::api_cookie::ApiCookie;
@@ -13,7 +16,6 @@ use rauthy_models::entity::principal::Principal;
 use rauthy_models::entity::sessions::Session;
 use std::future::{ready, Ready};
 use std::rc::Rc;
-use time::OffsetDateTime;
 use tracing::debug;
 
 pub struct RauthyPrincipalMiddleware;
@@ -104,7 +106,9 @@ async fn get_api_key_from_headers(req: &ServiceRequest) -> Result<Option<ApiKey>
             "Malformed 'Authorization' header".to_string(),
         ));
     };
-    let api_key_value = if k.ne(TOKEN_API_KEY) || k.is_empty() {
+    // SCIM clients like Okta or Azure AD can only send static Bearer tokens
+    let is_scim_bearer = k == "Bearer" && req.path().starts_with(SCIM_BASE_PATH);
+    let api_key_value = if (k.ne(TOKEN_API_KEY) && !is_scim_bearer) || k.is_empty() {
         None
     } else {
         Some(v)
@@ -139,9 +143,17 @@ async fn get_session_from_cookie(
                 None
             };
             if session.is_valid(data.session_timeout, remote_ip) {
-                let now = OffsetDateTime::now_utc().unix_timestamp();
-                // only update the last_seen, if it is older than 10 seconds
-                if session.last_seen < now - 10 {
+                if let Ok(ip) = real_ip_from_svc_req(req) {
+                    if !session.validate_geo_ip(ip, &data.tx_events).await? {
+                        return Ok(None);
+                    }
+                }
+
+                let now = clock::now_utc().unix_timestamp();
+                // Only update the last_seen, if it is older than 10 seconds.
+                // The authorize endpoint does this on its own, because it needs to validate a
+                // possibly stricter idle timeout for the requesting client first.
+                if session.last_seen < now - 10 && !is_path_authorize(req) {
                     session.last_seen = now;
                     session.save().await?;
                 }
@@ -171,6 +183,11 @@ async fn get_session_from_cookie(
     }
 }
 
+#[inline(always)]
+fn is_path_authorize(req: &ServiceRequest) -> bool {
+    req.method() == http::Method::GET && req.path().ends_with("/oidc/authorize")
+}
+
 // !!! CAUTION !!!
 // CSRF MUST BE CHECKED FOR THESE EXCEPTIONS MANUALLY !
 #[inline(always)]
@@ -0,0 +1,155 @@
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::error::InternalError;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use futures::future::LocalBoxFuture;
+use lazy_static::lazy_static;
+use rauthy_common::utils::get_rand;
+use rauthy_error::request_id;
+use std::env;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+lazy_static! {
+    static ref REQUEST_ID_HEADER: HeaderName = env::var("REQUEST_ID_HEADER")
+        .unwrap_or_else(|_| "x-request-id".to_string())
+        .trim()
+        .to_lowercase()
+        .parse::<HeaderName>()
+        .expect("Cannot parse REQUEST_ID_HEADER to a valid header name");
+}
+
+/// Accepts a request id from the `REQUEST_ID_HEADER` or generates a new one otherwise.
+///
+/// The id is kept in scope while the request is being handled, so it will be attached to each
+/// `ErrorResponse` and `Event` created on the way. It will be returned in the same header with
+/// each response.
+pub struct RequestIdMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Transform = RequestIdMiddlewareInner<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdMiddlewareInner {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RequestIdMiddlewareInner<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdMiddlewareInner<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let id = req
+                .headers()
+                .get(&*REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .filter(|id| is_valid_id(id))
+                .map(String::from)
+                .unwrap_or_else(|| get_rand(16));
+
+            let res = request_id::scope(id.clone(), service.call(req)).await;
+            let value = HeaderValue::from_str(&id).ok();
+            match res {
+                Ok(res) => {
+                    let mut res = res.map_into_boxed_body();
+                    if let Some(value) = value {
+                        res.headers_mut().insert(REQUEST_ID_HEADER.clone(), value);
+                    }
+                    Ok(res)
+                }
+                Err(err) => {
+                    // errors from inner middlewares would be converted after this one -> do it
+                    // here to be able to add the header to them as well
+                    let mut res = err.error_response();
+                    if let Some(value) = value {
+                        res.headers_mut().insert(REQUEST_ID_HEADER.clone(), value);
+                    }
+                    Err(InternalError::from_response(err, res).into())
+                }
+            }
+        })
+    }
+}
+
+/// Only accepts ids from clients, which are safe to be logged and used in headers.
+#[inline]
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    #[test]
+    fn test_is_valid_id() {
+        assert!(is_valid_id("abc-123_XYZ.1"));
+        assert!(is_valid_id("0af7651916cd43dd8448eb211c80319c"));
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id("with space"));
+        assert!(!is_valid_id("new\nline"));
+        assert!(!is_valid_id(&"a".repeat(65)));
+    }
+
+    #[actix_web::test]
+    async fn test_request_id_header() {
+        let app = init_service(App::new().wrap(RequestIdMiddleware).route(
+            "/{id}",
+            web::get().to(|id: web::Path<String>| async move {
+                HttpResponse::Ok().body(id.into_inner())
+            }),
+        ))
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/123")
+            .insert_header(("x-request-id", "abc-123"))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers().get("x-request-id").unwrap(), "abc-123");
+
+        let req = TestRequest::get()
+            .uri("/123")
+            .insert_header(("x-request-id", "in valid"))
+            .to_request();
+        let res = call_service(&app, req).await;
+        let id = res.headers().get("x-request-id").unwrap().to_str().unwrap();
+        assert_eq!(id.len(), 16);
+    }
+}
@@ -0,0 +1,184 @@
+use actix_web::body::MessageBody;
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_SECURITY_POLICY};
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error,
+};
+use futures::future::LocalBoxFuture;
+use rauthy_common::constants::{
+    HEADER_CSP, HEADER_CSP_OVERRIDES, HEADER_FRAME_OPTIONS, HEADER_HSTS, HEADER_PERMISSIONS_POLICY,
+    HEADER_REFERRER_POLICY,
+};
+use rauthy_common::utils::{get_rand, CSP_NONCE};
+use std::future::{ready, Ready};
+use std::rc::Rc;
+
+/// Placeholder inside the CSP, which will be replaced with a new random nonce for each request.
+const NONCE_PLACEHOLDER: &str = "{nonce}";
+
+/// Adds the configured security headers to each response. Headers, which have been set by a
+/// handler already, will not be overwritten.
+///
+/// If the CSP contains a `{nonce}` placeholder, a new nonce will be generated for each request.
+/// It is available to the handler via `rauthy_common::utils::csp_nonce()` and only ends up on
+/// the `<script>` and `<style>` tags, which are part of the rendered templates. The response body
+/// is never touched.
+pub struct SecurityHeadersMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for SecurityHeadersMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = SecurityHeadersMiddlewareInner<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SecurityHeadersMiddlewareInner {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct SecurityHeadersMiddlewareInner<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for SecurityHeadersMiddlewareInner<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let csp = csp_for_path(req.path());
+            let (csp, mut res) = if csp.contains(NONCE_PLACEHOLDER) {
+                let nonce = get_rand(24);
+                let res = CSP_NONCE.scope(nonce.clone(), service.call(req)).await?;
+                (csp.replace(NONCE_PLACEHOLDER, &nonce), res)
+            } else {
+                (csp.to_string(), service.call(req).await?)
+            };
+
+            // `frame-ancestors` obsoletes `x-frame-options`, which would otherwise block pages
+            // a handler explicitly allowed to be framed
+            let handler_allows_framing = allows_framing(res.headers());
+            let headers = res.headers_mut();
+            for (name, value) in [
+                ("content-security-policy", csp.as_str()),
+                ("strict-transport-security", HEADER_HSTS.as_str()),
+                ("referrer-policy", HEADER_REFERRER_POLICY.as_str()),
+                ("permissions-policy", HEADER_PERMISSIONS_POLICY.as_str()),
+                ("x-frame-options", HEADER_FRAME_OPTIONS.as_str()),
+                ("x-content-type-options", "nosniff"),
+                ("x-robots-tag", "noindex, nofollow"),
+                ("cache-control", "no-store"),
+            ] {
+                // an empty value disables the header
+                if value.is_empty() {
+                    continue;
+                }
+                let name = HeaderName::from_static(name);
+                if name == "x-frame-options" && handler_allows_framing {
+                    continue;
+                }
+                if !headers.contains_key(&name) {
+                    if let Ok(value) = HeaderValue::from_str(value) {
+                        headers.insert(name, value);
+                    }
+                }
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+#[inline]
+fn csp_for_path(path: &str) -> &'static str {
+    HEADER_CSP_OVERRIDES
+        .iter()
+        .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+        .map(|(_, policy)| policy.as_str())
+        .unwrap_or(HEADER_CSP.as_str())
+}
+
+#[inline]
+fn allows_framing(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_SECURITY_POLICY)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains("frame-ancestors") && !v.contains("frame-ancestors 'none'"))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+    use pretty_assertions::assert_eq;
+    use rauthy_common::utils::csp_nonce;
+
+    #[actix_web::test]
+    async fn test_csp_nonce() {
+        std::env::set_var("HEADER_CSP", "script-src 'self' 'nonce-{nonce}';");
+
+        let app = test::init_service(App::new().wrap(SecurityHeadersMiddleware).route(
+            "/",
+            web::get().to(|| async {
+                HttpResponse::Ok().content_type("text/html").body(format!(
+                    r#"<script nonce="{}"></script><script>injected</script>"#,
+                    csp_nonce()
+                ))
+            }),
+        ))
+        .await;
+
+        let mut nonces = Vec::with_capacity(2);
+        for _ in 0..2 {
+            let res =
+                test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+            let csp = res
+                .headers()
+                .get(CONTENT_SECURITY_POLICY)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string();
+            let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+
+            let nonce = csp
+                .strip_prefix("script-src 'self' 'nonce-")
+                .and_then(|s| s.strip_suffix("';"))
+                .unwrap()
+                .to_string();
+            assert_eq!(nonce.len(), 24);
+            // only the tag owned by the handler must carry the nonce
+            assert_eq!(
+                body,
+                format!(
+                    r#"<script nonce="{}"></script><script>injected</script>"#,
+                    nonce
+                )
+            );
+            nonces.push(nonce);
+        }
+        assert_ne!(nonces[0], nonces[1]);
+
+        // no nonce outside of a request scope
+        assert!(csp_nonce().is_empty());
+    }
+}
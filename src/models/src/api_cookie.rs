@@ -2,7 +2,9 @@ use actix_web::cookie::{Cookie, SameSite};
 use actix_web::dev::ServiceRequest;
 use actix_web::{cookie, HttpRequest};
 use cryptr::EncValue;
-use rauthy_common::constants::{CookieMode, COOKIE_MODE, COOKIE_SET_PATH};
+use rauthy_common::constants::{
+    CookieMode, COOKIE_DOMAIN, COOKIE_MODE, COOKIE_SAME_SITE, COOKIE_SET_PATH,
+};
 use rauthy_common::utils::{base64_decode, base64_encode};
 use std::borrow::Cow;
 use std::fmt::Display;
@@ -16,7 +18,7 @@ impl ApiCookie {
         N: Into<Cow<'c, str>> + Display,
         V: Into<Cow<'b, str>> + Display,
     {
-        Self::build_with_same_site(name, value, max_age, SameSite::Lax)
+        Self::build_with_same_site(name, value, max_age, *COOKIE_SAME_SITE)
     }
 
     pub fn build_with_same_site<'c, 'b, N, V>(
@@ -30,14 +32,15 @@ impl ApiCookie {
         V: Into<Cow<'b, str>> + Display,
     {
         let path = if *COOKIE_SET_PATH { "/auth" } else { "/" };
-        let (name, secure, path) = match *COOKIE_MODE {
-            CookieMode::Host => (format!("__Host-{}", name), true, "/"),
-            CookieMode::Secure => (format!("__Secure-{}", name), true, path),
+        let (secure, path) = match *COOKIE_MODE {
+            CookieMode::Host => (true, "/"),
+            CookieMode::Secure | CookieMode::SecureNoPrefix => (true, path),
             CookieMode::DangerInsecure => {
                 warn!("Building INSECURE cookie - you MUST NEVER use this in production");
-                (name.to_string(), false, path)
+                (false, path)
             }
         };
+        let name = Self::prefixed_name(name);
         let max_age = if max_age < 1 {
             cookie::time::Duration::ZERO
         } else {
@@ -49,24 +52,59 @@ impl ApiCookie {
             EncValue::encrypt(value.into().as_bytes()).expect("ENC_VALUES not set up correctly");
         let value_b64 = base64_encode(enc.into_bytes().as_ref());
 
-        Cookie::build(name, value_b64)
+        let mut builder = Cookie::build(name, value_b64)
             .secure(secure)
             .http_only(true)
             .same_site(same_site)
             .max_age(max_age)
-            .path(path)
-            .finish()
+            .path(path);
+        // a `__Host-` cookie must never have a domain -> validated at startup
+        if let Some(domain) = COOKIE_DOMAIN.as_deref() {
+            builder = builder.domain(domain);
+        }
+        builder.finish()
     }
 
-    pub fn from_req<'c, N>(req: &HttpRequest, cookie_name: N) -> Option<String>
+    /// Builds a cookie with a plain text value, which is readable from JS. This must only ever be
+    /// used for values which are public by design, like the OIDC Session Management
+    /// `browser_state`. It is sent with `SameSite=None`, because it must be readable from inside
+    /// the `check_session_iframe` embedded into the client's origin.
+    pub fn build_plain<'c, N, V>(name: N, value: V, max_age: i64) -> Cookie<'c>
     where
         N: Into<Cow<'c, str>> + Display,
+        V: Into<Cow<'c, str>>,
     {
-        let name = match *COOKIE_MODE {
-            CookieMode::Host => format!("__Host-{}", cookie_name),
-            CookieMode::Secure => format!("__Secure-{}", cookie_name),
-            CookieMode::DangerInsecure => cookie_name.to_string(),
+        let path = if *COOKIE_SET_PATH { "/auth" } else { "/" };
+        let (secure, path, same_site) = match *COOKIE_MODE {
+            CookieMode::Host => (true, "/", SameSite::None),
+            CookieMode::Secure | CookieMode::SecureNoPrefix => (true, path, SameSite::None),
+            // `SameSite=None` is rejected by browsers without `Secure`
+            CookieMode::DangerInsecure => (false, path, SameSite::Lax),
+        };
+        let name = Self::prefixed_name(name);
+        let max_age = if max_age < 1 {
+            cookie::time::Duration::ZERO
+        } else {
+            cookie::time::Duration::seconds(max_age)
         };
+
+        let mut builder = Cookie::build(name, value)
+            .secure(secure)
+            .http_only(false)
+            .same_site(same_site)
+            .max_age(max_age)
+            .path(path);
+        if let Some(domain) = COOKIE_DOMAIN.as_deref() {
+            builder = builder.domain(domain);
+        }
+        builder.finish()
+    }
+
+    pub fn from_req<'c, N>(req: &HttpRequest, cookie_name: N) -> Option<String>
+    where
+        N: Into<Cow<'c, str>> + Display,
+    {
+        let name = Self::prefixed_name(cookie_name);
         // req.cookie(&name)
         Self::cookie_into_value(req.cookie(&name))
     }
@@ -75,14 +113,20 @@ impl ApiCookie {
     where
         N: Into<Cow<'c, str>> + Display,
     {
-        let name = match *COOKIE_MODE {
-            CookieMode::Host => format!("__Host-{}", cookie_name),
-            CookieMode::Secure => format!("__Secure-{}", cookie_name),
-            CookieMode::DangerInsecure => cookie_name.to_string(),
-        };
+        let name = Self::prefixed_name(cookie_name);
         Self::cookie_into_value(req.cookie(&name))
     }
 
+    /// Returns the cookie name with the `__Host-` / `__Secure-` prefix depending on the
+    /// `COOKIE_MODE`.
+    pub fn prefixed_name<N: Display>(name: N) -> String {
+        match *COOKIE_MODE {
+            CookieMode::Host => format!("__Host-{}", name),
+            CookieMode::Secure => format!("__Secure-{}", name),
+            CookieMode::SecureNoPrefix | CookieMode::DangerInsecure => name.to_string(),
+        }
+    }
+
     pub fn cookie_into_value(cookie: Option<Cookie>) -> Option<String> {
         match cookie {
             None => None,
@@ -3,6 +3,8 @@ use crate::events::event::Event;
 use crate::events::ip_blacklist_handler::IpBlacklistReq;
 use crate::events::listener::EventRouterMsg;
 use crate::ListenScheme;
+use actix_web::http::header::ORIGIN;
+use actix_web::HttpRequest;
 use rauthy_common::constants::PROXY_MODE;
 use std::env;
 use std::sync::Arc;
@@ -30,7 +32,108 @@ pub struct AppState {
     pub tx_events: flume::Sender<Event>,
     pub tx_events_router: flume::Sender<EventRouterMsg>,
     pub tx_ip_blacklist: flume::Sender<IpBlacklistReq>,
-    pub webauthn: Arc<Webauthn>,
+    pub webauthn: Arc<WebauthnRps>,
+}
+
+/// A WebAuthn Relying Party together with all origins it accepts ceremonies from.
+#[derive(Debug)]
+pub struct WebauthnRp {
+    pub rp_id: String,
+    origins: Vec<Url>,
+    pub webauthn: Webauthn,
+}
+
+impl WebauthnRp {
+    fn new(rp_id: String, origins: Vec<Url>, rp_name: &str) -> Self {
+        let mut builder = webauthn_rs::WebauthnBuilder::new(&rp_id, &origins[0])
+            .expect("Invalid Webauthn configuration")
+            // Set a "nice" relying party name. Has no security properties - may be changed in the future.
+            .rp_name(rp_name);
+        for origin in origins.iter().skip(1) {
+            builder = builder.append_allowed_origin(origin);
+        }
+        let webauthn = builder.build().expect("Invalid Webauthn configuration");
+
+        Self {
+            rp_id,
+            origins,
+            webauthn,
+        }
+    }
+
+    #[inline]
+    fn accepts_origin(&self, origin: &Url) -> bool {
+        self.origins.iter().any(|o| o.origin() == origin.origin())
+    }
+}
+
+/// All configured WebAuthn Relying Parties. The first one is always the default from `RP_ID`
+/// and `RP_ORIGIN`.
+#[derive(Debug)]
+pub struct WebauthnRps(Vec<WebauthnRp>);
+
+impl WebauthnRps {
+    fn from_env() -> Self {
+        let rp_name = env::var("RP_NAME").unwrap_or_else(|_| String::from("Rauthy Webauthn"));
+
+        let rp_id = env::var("RP_ID").unwrap_or_else(|_| String::from("localhost"));
+        let rp_origin =
+            env::var("RP_ORIGIN").unwrap_or_else(|_| String::from("http://localhost:8080"));
+        let mut origins = vec![Url::parse(&rp_origin).expect("Cannot parse RP_ORIGIN to URL")];
+        for origin in env::var("RP_ORIGINS_ADDITIONAL")
+            .unwrap_or_default()
+            .split(' ')
+            .filter(|o| !o.is_empty())
+        {
+            let url = Url::parse(origin)
+                .unwrap_or_else(|_| panic!("Cannot parse RP_ORIGINS_ADDITIONAL '{}'", origin));
+            origins.push(url);
+        }
+        let mut rps = vec![WebauthnRp::new(rp_id, origins, &rp_name)];
+
+        // format: "rp_id=origin[,origin] rp_id=origin"
+        for entry in env::var("RP_IDS_ADDITIONAL")
+            .unwrap_or_default()
+            .split(' ')
+            .filter(|e| !e.is_empty())
+        {
+            let (rp_id, origins) = entry.split_once('=').unwrap_or_else(|| {
+                panic!(
+                    "Invalid RP_IDS_ADDITIONAL entry '{}' - expected 'rp_id=origin'",
+                    entry
+                )
+            });
+            let origins = origins
+                .split(',')
+                .map(|o| {
+                    Url::parse(o.trim())
+                        .unwrap_or_else(|_| panic!("Cannot parse RP_IDS_ADDITIONAL origin '{}'", o))
+                })
+                .collect::<Vec<_>>();
+            if rps.iter().any(|rp| rp.rp_id == rp_id) {
+                panic!("Duplicate RP ID '{}' in RP_IDS_ADDITIONAL", rp_id);
+            }
+            rps.push(WebauthnRp::new(rp_id.to_string(), origins, &rp_name));
+        }
+
+        for rp in &rps {
+            debug!("Webauthn RP ID '{}' for origins {:?}", rp.rp_id, rp.origins);
+        }
+
+        Self(rps)
+    }
+
+    /// Returns the Relying Party, which accepts the `Origin` of the given request. Falls back to
+    /// the default one, if the header is missing or unknown. In the latter case, the ceremony
+    /// will fail later on anyway.
+    pub fn for_req(&self, req: &HttpRequest) -> &WebauthnRp {
+        req.headers()
+            .get(ORIGIN)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|origin| Url::parse(origin).ok())
+            .and_then(|origin| self.0.iter().find(|rp| rp.accepts_origin(&origin)))
+            .unwrap_or(&self.0[0])
+    }
 }
 
 impl AppState {
@@ -143,16 +246,7 @@ impl AppState {
             .parse::<u32>()
             .expect("ML_LT_PWD_RESET cannot be parsed to u32 - bad format");
 
-        let rp_id = env::var("RP_ID").unwrap_or_else(|_| String::from("localhost"));
-        let rp_origin_str =
-            env::var("RP_ORIGIN").unwrap_or_else(|_| String::from("http://localhost:8080"));
-        let rp_origin = Url::parse(&rp_origin_str).expect("Cannot parse RP_ORIGIN to URL");
-        let rp_name = env::var("RP_NAME").unwrap_or_else(|_| String::from("Rauthy Webauthn"));
-        let builder = webauthn_rs::WebauthnBuilder::new(&rp_id, &rp_origin)
-            .expect("Invalid configuration")
-            // Set a "nice" relying party name. Has no security properties - may be changed in the future.
-            .rp_name(&rp_name);
-        let webauthn = Arc::new(builder.build().expect("Invalid configuration"));
+        let webauthn = Arc::new(WebauthnRps::from_env());
 
         Ok(Self {
             public_url,
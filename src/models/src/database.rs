@@ -1,5 +1,6 @@
 use crate::app_state::AppState;
 use crate::entity::db_version::DbVersion;
+use crate::entity::jwk::JWKS;
 use crate::migration::db_migrate_dev::migrate_dev_data;
 use crate::migration::{anti_lockout, db_migrate, init_prod};
 use actix_web::web;
@@ -32,10 +33,12 @@ pub enum Cache {
     AuthCode,
     DeviceCode,
     AuthProviderCallback,
+    ClientAssertion,
     ClientDynamic,
     ClientEphemeral,
     DPoPNonce,
     IPRateLimit,
+    JtiDenylist,
     Session,
     PoW,
     User,
@@ -222,6 +225,10 @@ impl DB {
             migrate_dev_data().await.expect("Migrating DEV DATA");
         }
 
+        // must run after the DEV data, which is only inserted into an empty `jwks` table
+        init_prod::migrate_jwk_es384().await?;
+        JWKS::register_kms_keys().await?;
+
         if let Err(err) = anti_lockout::anti_lockout(&app_state.issuer).await {
             error!("Error when applying anti-lockout check: {:?}", err);
         }
@@ -1,20 +1,24 @@
 use crate::app_state::AppState;
 use crate::entity::magic_links::MagicLink;
 use crate::entity::users::User;
+use crate::entity::users_values::UserValues;
 use crate::i18n::email_change_info_new::I18nEmailChangeInfoNew;
 use crate::i18n::email_confirm_change::I18nEmailConfirmChange;
 use crate::i18n::email_password_new::I18nEmailPasswordNew;
 use crate::i18n::email_reset::I18nEmailReset;
 use crate::i18n::email_reset_info::I18nEmailResetInfo;
 use crate::i18n::SsrJson;
+use crate::language::Language;
 use actix_web::web;
 use askama_actix::Template;
 use chrono::DateTime;
+use chrono_tz::Tz;
 use lettre::message::{MultiPart, SinglePart};
 use lettre::transport::smtp::authentication;
 use lettre::{message, AsyncSmtpTransport, AsyncTransport};
 use rauthy_common::constants::{
-    EMAIL_SUB_PREFIX, SMTP_FROM, SMTP_PASSWORD, SMTP_URL, SMTP_USERNAME,
+    EMAIL_DEFAULT_TZ, EMAIL_SUB_PREFIX, MFA_EMAIL_OTP_LIFETIME, SMTP_FROM, SMTP_PASSWORD, SMTP_URL,
+    SMTP_USERNAME,
 };
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_notify::Notification;
@@ -24,7 +28,7 @@ use tokio::sync::mpsc;
 use tokio::sync::mpsc::Receiver;
 use tracing::{debug, error, info, warn};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EMail {
     pub recipient_name: String,
     pub address: String,
@@ -192,19 +196,54 @@ pub async fn send_email_change_info_new(
     magic_link: &MagicLink,
     user: &User,
     new_email: String,
+) {
+    let i18n = I18nEmailChangeInfoNew::build(&user.language);
+    send_email_confirm_link(
+        data,
+        magic_link,
+        user,
+        new_email,
+        i18n.subject,
+        i18n.header,
+        &i18n,
+    )
+    .await;
+}
+
+pub async fn send_email_verify(data: &web::Data<AppState>, magic_link: &MagicLink, user: &User) {
+    let i18n = I18nEmailChangeInfoNew::build(&user.language);
+    send_email_confirm_link(
+        data,
+        magic_link,
+        user,
+        user.email.clone(),
+        i18n.subject_verify,
+        i18n.header_verify,
+        &i18n,
+    )
+    .await;
+}
+
+async fn send_email_confirm_link(
+    data: &web::Data<AppState>,
+    magic_link: &MagicLink,
+    user: &User,
+    address: String,
+    subject: &str,
+    header: &str,
+    i18n: &I18nEmailChangeInfoNew<'_>,
 ) {
     let link = format!(
         "{}/users/{}/email_confirm/{}",
         data.issuer, magic_link.user_id, &magic_link.id,
     );
-    let exp = email_ts_prettify(magic_link.exp);
+    let exp = email_ts_prettify(magic_link.exp, user).await;
 
-    let i18n = I18nEmailChangeInfoNew::build(&user.language);
     let text = EMailChangeInfoNewTxt {
         email_sub_prefix: &EMAIL_SUB_PREFIX,
         link: &link,
         exp: &exp,
-        header: i18n.header,
+        header,
         click_link: i18n.click_link,
         validity: i18n.validity,
         expires: i18n.expires,
@@ -214,7 +253,7 @@ pub async fn send_email_change_info_new(
         email_sub_prefix: &EMAIL_SUB_PREFIX,
         link: &link,
         exp: &exp,
-        header: i18n.header,
+        header,
         click_link: i18n.click_link,
         validity: i18n.validity,
         expires: i18n.expires,
@@ -223,8 +262,8 @@ pub async fn send_email_change_info_new(
 
     let req = EMail {
         recipient_name: user.email_recipient_name(),
-        address: new_email.clone(),
-        subject: format!("{} - {}", *EMAIL_SUB_PREFIX, i18n.subject),
+        address: address.clone(),
+        subject: format!("{} - {}", *EMAIL_SUB_PREFIX, subject),
         text: text
             .render()
             .expect("Template rendering: EMailChangeInfoNewTxt"),
@@ -241,7 +280,7 @@ pub async fn send_email_change_info_new(
         Err(ref e) => {
             error!(
                 "Error sending magic link email request for user '{}': {:?}",
-                new_email, e
+                address, e
             );
         }
     }
@@ -306,7 +345,7 @@ pub async fn send_pwd_reset(data: &web::Data<AppState>, magic_link: &MagicLink,
         "{}/users/{}/reset/{}?type={}",
         data.issuer, magic_link.user_id, &magic_link.id, magic_link.usage,
     );
-    let exp = email_ts_prettify(magic_link.exp);
+    let exp = email_ts_prettify(magic_link.exp, user).await;
 
     let (subject, text, html) = if user.password.is_none() {
         let i18n = I18nEmailPasswordNew::build(&user.language);
@@ -388,7 +427,7 @@ pub async fn send_pwd_reset(data: &web::Data<AppState>, magic_link: &MagicLink,
 }
 
 pub async fn send_pwd_reset_info(data: &web::Data<AppState>, user: &User) {
-    let exp = email_ts_prettify(user.password_expires.unwrap());
+    let exp = email_ts_prettify(user.password_expires.unwrap(), user).await;
     let link = format!("{}/auth/v1/account", data.public_url);
 
     let i18n = I18nEmailResetInfo::build(&user.language);
@@ -437,6 +476,89 @@ pub async fn send_pwd_reset_info(data: &web::Data<AppState>, user: &User) {
     }
 }
 
+/// Sends a one-time login code as a plain text E-Mail.
+pub async fn send_email_otp(data: &web::Data<AppState>, user: &User, code: &str) {
+    let req = EMail {
+        recipient_name: user.email_recipient_name(),
+        address: user.email.to_string(),
+        subject: format!("{} - Login Code", *EMAIL_SUB_PREFIX),
+        text: format!(
+            "Your login code is: {}\n\nIt expires in {} minutes. If you did not try to log in, \
+            someone else knows your password and you should change it.",
+            code,
+            (*MFA_EMAIL_OTP_LIFETIME).div_ceil(60)
+        ),
+        html: None,
+    };
+
+    let tx = &data.tx_email;
+    let res = tx.send_timeout(req, Duration::from_secs(10)).await;
+    match res {
+        Ok(_) => {}
+        Err(ref e) => {
+            error!(
+                "Error sending login code email request for user '{}': {:?}",
+                user.email, e
+            );
+        }
+    }
+}
+
+/// Informs the user as a plain text E-Mail, that an admin has reset all 2nd factors.
+pub async fn send_mfa_reset_info(data: &web::Data<AppState>, user: &User) {
+    let link = format!("{}/auth/v1/account", data.public_url);
+    let req = EMail {
+        recipient_name: user.email_recipient_name(),
+        address: user.email.to_string(),
+        subject: format!("{} - MFA Reset", *EMAIL_SUB_PREFIX),
+        text: format!(
+            "An administrator has removed all MFA devices from your account and signed out all \
+            of your sessions.\n\nYou need to register a new MFA device at your next login:\n{}\n\n\
+            If you did not request this, please contact your administrator.",
+            link
+        ),
+        html: None,
+    };
+
+    let tx = &data.tx_email;
+    let res = tx.send_timeout(req, Duration::from_secs(10)).await;
+    match res {
+        Ok(_) => {}
+        Err(ref e) => {
+            error!(
+                "Error sending MFA reset email request for user '{}': {:?}",
+                user.email, e
+            );
+        }
+    }
+}
+
+/// Sends the JSON export of all data about the user as a plain text E-Mail.
+pub async fn send_user_export(data: &web::Data<AppState>, user: &User, export: String) {
+    let req = EMail {
+        recipient_name: user.email_recipient_name(),
+        address: user.email.to_string(),
+        subject: format!("{} - Data Export", *EMAIL_SUB_PREFIX),
+        text: format!(
+            "This is a machine-readable export of all data stored about your account.\n\n{}",
+            export
+        ),
+        html: None,
+    };
+
+    let tx = &data.tx_email;
+    let res = tx.send_timeout(req, Duration::from_secs(10)).await;
+    match res {
+        Ok(_) => {}
+        Err(ref e) => {
+            error!(
+                "Error sending data export email request for user '{}': {:?}",
+                user.email, e
+            );
+        }
+    }
+}
+
 pub async fn sender(mut rx: Receiver<EMail>, test_mode: bool) {
     debug!("E-Mail sender started");
 
@@ -632,9 +754,53 @@ async fn conn_test_smtp_insecure(
 }
 
 /// Prettifies unix timestamps for E-Mails in a better readable format for end users
-#[inline]
-fn email_ts_prettify(ts: i64) -> String {
-    let dt = DateTime::from_timestamp(ts, 0).unwrap_or_default();
-    let fmt = dt.format("%d/%m/%Y %H:%M:%S");
-    format!("{} UTC", fmt)
+async fn email_ts_prettify(ts: i64, user: &User) -> String {
+    let tz = match UserValues::find(&user.id).await {
+        Ok(Some(values)) => values.tz(),
+        Ok(None) => *EMAIL_DEFAULT_TZ,
+        Err(err) => {
+            error!("Error looking up UserValues for E-Mail timezone: {:?}", err);
+            *EMAIL_DEFAULT_TZ
+        }
+    };
+    email_ts_fmt(ts, &user.language, tz)
+}
+
+/// Formats the given unix timestamp in the users timezone with a format that matches the
+/// users language.
+fn email_ts_fmt(ts: i64, lang: &Language, tz: Tz) -> String {
+    let dt = DateTime::from_timestamp(ts, 0)
+        .unwrap_or_default()
+        .with_timezone(&tz);
+    let fmt = match lang {
+        Language::En => "%Y/%m/%d %H:%M:%S %Z",
+        Language::De => "%d.%m.%Y %H:%M:%S %Z",
+        Language::ZhHans => "%Y年%m月%d日 %H:%M:%S %Z",
+        Language::Ko => "%Y년 %m월 %d일 %H:%M:%S %Z",
+    };
+    dt.format(fmt).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_email_ts_fmt() {
+        // 2024-07-01 12:00:00 UTC
+        let ts = 1719835200;
+
+        let s = email_ts_fmt(ts, &Language::En, Tz::UTC);
+        assert_eq!(s, "2024/07/01 12:00:00 UTC");
+
+        let s = email_ts_fmt(ts, &Language::De, Tz::Europe__Berlin);
+        assert_eq!(s, "01.07.2024 14:00:00 CEST");
+
+        let s = email_ts_fmt(ts, &Language::ZhHans, Tz::Asia__Shanghai);
+        assert_eq!(s, "2024年07月01日 20:00:00 CST");
+
+        let s = email_ts_fmt(ts, &Language::Ko, Tz::Asia__Seoul);
+        assert_eq!(s, "2024년 07월 01일 21:00:00 KST");
+    }
 }
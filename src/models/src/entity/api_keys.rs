@@ -1,9 +1,9 @@
 use crate::database::{Cache, DB};
-use chrono::Utc;
 use cryptr::{EncKeys, EncValue};
 use hiqlite::{params, Param};
 use rauthy_api_types::api_keys::ApiKeyResponse;
-use rauthy_common::constants::{API_KEY_LENGTH, CACHE_TTL_APP};
+use rauthy_common::clock;
+use rauthy_common::constants::{API_KEY_EXCHANGE_PREFIX, API_KEY_LENGTH, CACHE_TTL_APP};
 use rauthy_common::is_hiqlite;
 use rauthy_common::utils::get_rand;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -27,7 +27,7 @@ impl ApiKeyEntity {
         expires: Option<i64>,
         access: Vec<ApiKeyAccess>,
     ) -> Result<String, ErrorResponse> {
-        let created = Utc::now().timestamp();
+        let created = clock::now().timestamp();
         let secret_plain = get_rand(API_KEY_LENGTH);
         let secret_hash = digest::digest(&digest::SHA256, secret_plain.as_bytes());
         let secret_enc = EncValue::encrypt(secret_hash.as_ref())?
@@ -92,6 +92,31 @@ VALUES ($1, $2, $3, $4, $5, $6)"#,
         Ok(())
     }
 
+    /// Deletes all API Keys, which have been created via a Passkey exchange and are expired.
+    pub async fn delete_expired_exchanged(now: i64) -> Result<u64, ErrorResponse> {
+        let prefix = format!("{}%", API_KEY_EXCHANGE_PREFIX);
+
+        let rows_affected = if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM api_keys WHERE name LIKE $1 AND expires < $2",
+                    params!(prefix, now),
+                )
+                .await? as u64
+        } else {
+            query!(
+                "DELETE FROM api_keys WHERE name LIKE $1 AND expires < $2",
+                prefix,
+                now,
+            )
+            .execute(DB::conn())
+            .await?
+            .rows_affected()
+        };
+
+        Ok(rows_affected)
+    }
+
     pub async fn find(name: &str) -> Result<Self, ErrorResponse> {
         let res = if is_hiqlite() {
             DB::client()
@@ -376,7 +401,7 @@ impl ApiKey {
     #[inline(always)]
     pub fn validate_secret(&self, secret: &str) -> Result<(), ErrorResponse> {
         if let Some(exp) = self.expires {
-            if Utc::now().timestamp() > exp {
+            if clock::now().timestamp() > exp {
                 return Err(ErrorResponse::new(
                     ErrorResponseType::Unauthorized,
                     "API Key has expired",
@@ -1,6 +1,6 @@
 use crate::database::{Cache, DB};
-use chrono::Utc;
 use hiqlite::{params, Param};
+use rauthy_common::clock;
 use rauthy_common::constants::{CACHE_TTL_APP, IDX_APP_VERSION, RAUTHY_VERSION};
 use rauthy_common::is_hiqlite;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -80,7 +80,7 @@ impl LatestAppVersion {
         release_url: String,
     ) -> Result<(), ErrorResponse> {
         let slf = Self {
-            timestamp: Utc::now().timestamp(),
+            timestamp: clock::now().timestamp(),
             latest_version,
             release_url,
         };
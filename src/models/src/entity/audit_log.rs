@@ -0,0 +1,533 @@
+use crate::database::DB;
+use crate::entity::principal::Principal;
+use actix_web::HttpRequest;
+use hiqlite::{params, Param};
+use rauthy_api_types::audit_log::{AuditLogEntryResponse, AuditLogVerifyResponse};
+use rauthy_common::clock;
+use rauthy_common::is_hiqlite;
+use rauthy_error::ErrorResponse;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{query, query_as, FromRow};
+use std::fmt::{Display, Formatter};
+use tokio::sync::Mutex;
+use tracing::{error, warn};
+
+/// The `prev_hash` of the very first entry in the chain
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+const VERIFY_BATCH_SIZE: i64 = 1000;
+
+/// Serializes local appends. Other nodes in an HA deployment may still append at the same time,
+/// which is handled by retrying on a `seq` conflict.
+static APPEND_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+impl Display for AuditAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Tamper-evident log of all admin mutations.
+///
+/// Each entry contains the hash of the previous one and its own hash is built over all of its
+/// values including `prev_hash`. Modifying or removing any entry in between breaks the chain,
+/// which will be detected by `AuditLog::verify()`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AuditLog {
+    pub seq: i64,
+    pub timestamp: i64,
+    pub actor: String,
+    pub action: String,
+    pub entity: String,
+    pub entity_id: String,
+    pub diff: String,
+    pub request_id: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+// CRUD
+impl AuditLog {
+    async fn insert(&self) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO audit_log
+(seq, timestamp, actor, action, entity, entity_id, diff, request_id, prev_hash, hash)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
+                    params!(
+                        self.seq,
+                        self.timestamp,
+                        self.actor.clone(),
+                        self.action.clone(),
+                        self.entity.clone(),
+                        self.entity_id.clone(),
+                        self.diff.clone(),
+                        self.request_id.clone(),
+                        self.prev_hash.clone(),
+                        self.hash.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO audit_log
+(seq, timestamp, actor, action, entity, entity_id, diff, request_id, prev_hash, hash)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
+                self.seq,
+                self.timestamp,
+                self.actor,
+                self.action,
+                self.entity,
+                self.entity_id,
+                self.diff,
+                self.request_id,
+                self.prev_hash,
+                self.hash,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn find_head() -> Result<Option<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            let mut rows: Vec<Self> = DB::client()
+                .query_as(
+                    "SELECT * FROM audit_log ORDER BY seq DESC LIMIT 1",
+                    params!(),
+                )
+                .await?;
+            rows.pop()
+        } else {
+            query_as!(Self, "SELECT * FROM audit_log ORDER BY seq DESC LIMIT 1")
+                .fetch_optional(DB::conn())
+                .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Returns the latest entries ordered by `seq` descending.
+    pub async fn find_latest(
+        limit: i64,
+        before_seq: Option<i64>,
+    ) -> Result<Vec<Self>, ErrorResponse> {
+        let before_seq = before_seq.unwrap_or(i64::MAX);
+
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM audit_log WHERE seq < $1 ORDER BY seq DESC LIMIT $2",
+                    params!(before_seq, limit),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM audit_log WHERE seq < $1 ORDER BY seq DESC LIMIT $2",
+                before_seq,
+                limit,
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    async fn find_batch_after(seq: i64) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM audit_log WHERE seq > $1 ORDER BY seq ASC LIMIT $2",
+                    params!(seq, VERIFY_BATCH_SIZE),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM audit_log WHERE seq > $1 ORDER BY seq ASC LIMIT $2",
+                seq,
+                VERIFY_BATCH_SIZE,
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+}
+
+impl AuditLog {
+    pub async fn created<T: Serialize>(
+        principal: &Principal,
+        req: &HttpRequest,
+        entity: &str,
+        entity_id: &str,
+        after: &T,
+    ) {
+        Self::record(
+            principal,
+            req,
+            AuditAction::Create,
+            entity,
+            entity_id,
+            Value::Null,
+            Self::value(after),
+        )
+        .await
+    }
+
+    pub async fn updated<T: Serialize>(
+        principal: &Principal,
+        req: &HttpRequest,
+        entity: &str,
+        entity_id: &str,
+        before: &T,
+        after: &T,
+    ) {
+        Self::record(
+            principal,
+            req,
+            AuditAction::Update,
+            entity,
+            entity_id,
+            Self::value(before),
+            Self::value(after),
+        )
+        .await
+    }
+
+    pub async fn deleted<T: Serialize>(
+        principal: &Principal,
+        req: &HttpRequest,
+        entity: &str,
+        entity_id: &str,
+        before: &T,
+    ) {
+        Self::record(
+            principal,
+            req,
+            AuditAction::Delete,
+            entity,
+            entity_id,
+            Self::value(before),
+            Value::Null,
+        )
+        .await
+    }
+
+    /// For mutations without any meaningful before / after values like a secret rotation.
+    pub async fn action(
+        principal: &Principal,
+        req: &HttpRequest,
+        action: AuditAction,
+        entity: &str,
+        entity_id: &str,
+    ) {
+        Self::record(
+            principal,
+            req,
+            action,
+            entity,
+            entity_id,
+            Value::Null,
+            Value::Null,
+        )
+        .await
+    }
+
+    /// Appends a new entry to the audit log.
+    ///
+    /// This is called after the mutation has been applied already. A failure will therefore only
+    /// be logged instead of returning an error for an otherwise successful request.
+    ///
+    /// Make sure to never pass any secrets as `before` or `after`.
+    pub async fn record(
+        principal: &Principal,
+        req: &HttpRequest,
+        action: AuditAction,
+        entity: &str,
+        entity_id: &str,
+        before: Value,
+        after: Value,
+    ) {
+        let request_id = req
+            .headers()
+            .get("x-request-id")
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let diff = Self::diff(before, after).to_string();
+
+        if let Err(err) = Self::append(
+            principal.actor(),
+            action,
+            entity.to_string(),
+            entity_id.to_string(),
+            diff,
+            request_id,
+        )
+        .await
+        {
+            error!(
+                "Appending to the audit log for {} {} {}: {}",
+                action, entity, entity_id, err.message
+            );
+        }
+    }
+
+    async fn append(
+        actor: String,
+        action: AuditAction,
+        entity: String,
+        entity_id: String,
+        diff: String,
+        request_id: Option<String>,
+    ) -> Result<(), ErrorResponse> {
+        let _lock = APPEND_LOCK.lock().await;
+
+        let mut entry = Self {
+            seq: 0,
+            timestamp: clock::now().timestamp_millis(),
+            actor,
+            action: action.as_str().to_string(),
+            entity,
+            entity_id,
+            diff,
+            request_id,
+            prev_hash: String::default(),
+            hash: String::default(),
+        };
+
+        // The primary key on `seq` makes sure, that the chain can never fork, even if another
+        // node appended in the meantime. We simply retry with the new head in that case.
+        let mut res = Ok(());
+        for _ in 0..5 {
+            match Self::find_head().await? {
+                Some(head) => {
+                    entry.seq = head.seq + 1;
+                    entry.prev_hash = head.hash;
+                }
+                None => {
+                    entry.seq = 1;
+                    entry.prev_hash = GENESIS_HASH.to_string();
+                }
+            }
+            entry.hash = entry.compute_hash();
+
+            res = entry.insert().await;
+            match &res {
+                Ok(_) => return Ok(()),
+                Err(err) => warn!(
+                    "Inserting audit log entry with seq {}: {}",
+                    entry.seq, err.message
+                ),
+            }
+        }
+
+        res
+    }
+
+    /// Walks the whole chain and validates the links and hashes of all entries.
+    pub async fn verify() -> Result<AuditLogVerifyResponse, ErrorResponse> {
+        let mut res = AuditLogVerifyResponse {
+            valid: true,
+            entries: 0,
+            head_seq: None,
+            head_hash: None,
+            invalid_seq: None,
+            error: None,
+        };
+        let mut prev_seq = 0;
+        let mut prev_hash = GENESIS_HASH.to_string();
+
+        loop {
+            let batch = Self::find_batch_after(prev_seq).await?;
+            if batch.is_empty() {
+                break;
+            }
+
+            for entry in batch {
+                let error = if entry.seq != prev_seq + 1 {
+                    Some(format!(
+                        "Expected seq {}, found {}",
+                        prev_seq + 1,
+                        entry.seq
+                    ))
+                } else if entry.prev_hash != prev_hash {
+                    Some("The prev_hash does not match the previous entry".to_string())
+                } else if entry.hash != entry.compute_hash() {
+                    Some("The hash does not match the content".to_string())
+                } else {
+                    None
+                };
+
+                if error.is_some() {
+                    res.valid = false;
+                    res.invalid_seq = Some(entry.seq);
+                    res.error = error;
+                    return Ok(res);
+                }
+
+                res.entries += 1;
+                prev_seq = entry.seq;
+                prev_hash = entry.hash;
+            }
+        }
+
+        if prev_seq > 0 {
+            res.head_seq = Some(prev_seq);
+            res.head_hash = Some(prev_hash);
+        }
+        Ok(res)
+    }
+
+    /// The hash is built over the JSON serialized tuple of all values, which makes the input
+    /// unambiguous regardless of the content.
+    fn compute_hash(&self) -> String {
+        let input = serde_json::to_string(&(
+            self.seq,
+            self.timestamp,
+            &self.actor,
+            &self.action,
+            &self.entity,
+            &self.entity_id,
+            &self.diff,
+            &self.request_id,
+            &self.prev_hash,
+        ))
+        .unwrap();
+        hex::encode(digest::digest(&digest::SHA256, input.as_bytes()).as_ref())
+    }
+
+    #[inline]
+    fn value<T: Serialize>(value: &T) -> Value {
+        serde_json::to_value(value).unwrap_or_default()
+    }
+
+    /// Builds a `{ "key": { "before": _, "after": _ } }` object for all changed top level values.
+    fn diff(before: Value, after: Value) -> Value {
+        let (before, after) = match (before, after) {
+            (Value::Object(b), Value::Object(a)) => (b, a),
+            (Value::Object(b), Value::Null) => (b, Map::new()),
+            (Value::Null, Value::Object(a)) => (Map::new(), a),
+            (b, a) => {
+                let mut map = Map::with_capacity(1);
+                if b != a {
+                    map.insert(
+                        "value".to_string(),
+                        serde_json::json!({ "before": b, "after": a }),
+                    );
+                }
+                return Value::Object(map);
+            }
+        };
+
+        let mut diff = Map::new();
+        for (key, b) in before.iter() {
+            let a = after.get(key).unwrap_or(&Value::Null);
+            if b != a {
+                diff.insert(key.clone(), serde_json::json!({ "before": b, "after": a }));
+            }
+        }
+        for (key, a) in after.iter() {
+            if !before.contains_key(key) && !a.is_null() {
+                diff.insert(
+                    key.clone(),
+                    serde_json::json!({ "before": null, "after": a }),
+                );
+            }
+        }
+
+        Value::Object(diff)
+    }
+}
+
+impl From<AuditLog> for AuditLogEntryResponse {
+    fn from(value: AuditLog) -> Self {
+        Self {
+            seq: value.seq,
+            timestamp: value.timestamp,
+            actor: value.actor,
+            action: value.action,
+            entity: value.entity,
+            entity_id: value.entity_id,
+            diff: serde_json::from_str(&value.diff).unwrap_or_default(),
+            request_id: value.request_id,
+            prev_hash: value.prev_hash,
+            hash: value.hash,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_audit_log_diff() {
+        let before = json!({ "id": "abc", "name": "old", "enabled": true, "removed": 1 });
+        let after = json!({ "id": "abc", "name": "new", "enabled": true, "added": [1] });
+        assert_eq!(
+            AuditLog::diff(before, after),
+            json!({
+                "name": { "before": "old", "after": "new" },
+                "removed": { "before": 1, "after": null },
+                "added": { "before": null, "after": [1] },
+            })
+        );
+
+        let created = AuditLog::diff(Value::Null, json!({ "id": "abc" }));
+        assert_eq!(created, json!({ "id": { "before": null, "after": "abc" } }));
+
+        let deleted = AuditLog::diff(json!({ "id": "abc" }), Value::Null);
+        assert_eq!(deleted, json!({ "id": { "before": "abc", "after": null } }));
+    }
+
+    #[test]
+    fn test_audit_log_hash() {
+        let mut entry = AuditLog {
+            seq: 1,
+            timestamp: 1720000000123,
+            actor: "za9UxpH7XVxqrtpEbThoqvn2".to_string(),
+            action: AuditAction::Update.to_string(),
+            entity: "client".to_string(),
+            entity_id: "rauthy".to_string(),
+            diff: r#"{"enabled":{"after":false,"before":true}}"#.to_string(),
+            request_id: None,
+            prev_hash: GENESIS_HASH.to_string(),
+            hash: String::default(),
+        };
+        entry.hash = entry.compute_hash();
+        assert_eq!(entry.hash.len(), 64);
+        assert_eq!(entry.hash, entry.compute_hash());
+
+        let hash = entry.hash.clone();
+        entry.diff = r#"{"enabled":{"after":true,"before":true}}"#.to_string();
+        assert_ne!(hash, entry.compute_hash());
+    }
+}
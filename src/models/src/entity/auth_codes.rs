@@ -1,10 +1,22 @@
+use crate::app_state::AppState;
 use crate::database::{Cache, DB};
+use crate::entity::clients::Client;
+use crate::entity::jwk::{JwkKeyPair, JwkKeyPairAlg};
+use crate::entity::sessions::Session;
+use crate::jwt_claims;
+use crate::sign_jwt;
+use actix_web::web;
+use jwt_simple::algorithms::{ECDSAP384KeyPairLike, EdDSAKeyPairLike, RSAKeyPairLike};
+use jwt_simple::prelude::coarsetime;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rauthy_common::clock;
 use rauthy_common::constants::CACHE_TTL_AUTH_CODE;
 use rauthy_common::utils::get_rand;
-use rauthy_error::ErrorResponse;
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
+use std::fmt::Write;
 use std::ops::Add;
-use time::OffsetDateTime;
+use std::str::FromStr;
 
 // Struct for the codes from the 'authorization_code' flow
 #[derive(Debug, Deserialize, Serialize)]
@@ -18,6 +30,10 @@ pub struct AuthCode {
     pub challenge_method: Option<String>,
     pub nonce: Option<String>,
     pub scopes: Vec<String>,
+    // the requested target resource (RFC 8707)
+    pub resource: Option<String>,
+    // the time of the user authentication this code has been issued for
+    pub auth_time: i64,
 }
 
 // CRUD
@@ -54,10 +70,12 @@ impl AuthCode {
         challenge_method: Option<String>,
         nonce: Option<String>,
         scopes: Vec<String>,
+        resource: Option<String>,
+        auth_time: i64,
         lifetime_secs: i32,
     ) -> Self {
         let id = get_rand(64);
-        let exp = OffsetDateTime::now_utc()
+        let exp = clock::now_utc()
             .add(time::Duration::seconds(lifetime_secs as i64))
             .unix_timestamp();
         Self {
@@ -70,6 +88,196 @@ impl AuthCode {
             challenge_method,
             nonce,
             scopes,
+            resource,
+            auth_time,
         }
     }
+
+    /// Builds the `Location` for the redirect back to the client, which contains this code.
+    pub async fn response_location(
+        &self,
+        data: &web::Data<AppState>,
+        client: &Client,
+        redirect_uri: &str,
+        state: Option<&str>,
+        response_mode: AuthResponseMode,
+    ) -> Result<String, ErrorResponse> {
+        // OIDC Session Management
+        let session_state = self
+            .session_id
+            .as_deref()
+            .map(|sid| Session::session_state(&client.id, redirect_uri, sid));
+
+        if response_mode == AuthResponseMode::Query {
+            let mut loc = match state {
+                None => format!("{}?code={}", redirect_uri, self.id),
+                Some(state) => format!("{}?code={}&state={}", redirect_uri, self.id, state),
+            };
+            if let Some(session_state) = session_state {
+                write!(loc, "&session_state={}", session_state)?;
+            }
+            return Ok(loc);
+        }
+
+        // the alg has been checked when the `response_mode` has been parsed
+        let alg = client.auth_response_alg.as_deref().unwrap_or_default();
+        let custom_claims = JwtAuthResponseClaims {
+            code: self.id.clone(),
+            state: state.map(String::from),
+            session_state,
+        };
+        // the response must not outlive the code itself
+        let lifetime = self.exp - clock::now_utc().unix_timestamp();
+        let claims = jwt_claims(
+            custom_claims,
+            coarsetime::Duration::from_secs(lifetime.max(1) as u64),
+        )
+        .with_issuer(data.issuer.clone())
+        .with_audience(client.id.clone());
+
+        let kp = JwkKeyPair::find_latest(JwkKeyPairAlg::from_str(alg)?).await?;
+        let response = sign_jwt!(kp, claims)?;
+
+        if response_mode == AuthResponseMode::FragmentJwt {
+            Ok(format!("{}#response={}", redirect_uri, response))
+        } else {
+            Ok(format!("{}?response={}", redirect_uri, response))
+        }
+    }
+
+    /// Builds the `Location` for an error response (RFC 6749 4.1.2.1) back to the client.
+    /// Must only be used after the `redirect_uri` has been validated, because an invalid one
+    /// must never be redirected to.
+    pub fn error_location(redirect_uri: &str, state: Option<&str>, err: &ErrorResponse) -> String {
+        let error = match err.code {
+            ErrorCode::AccessDenied
+            | ErrorCode::InvalidRequestObject
+            | ErrorCode::InvalidRequestUri
+            | ErrorCode::InvalidTarget
+            | ErrorCode::UnauthorizedClient
+            | ErrorCode::UnsupportedResponseType => err.code.as_str(),
+            _ => "invalid_request",
+        };
+        let separator = if redirect_uri.contains('?') { '&' } else { '?' };
+
+        let mut loc = format!(
+            "{}{}error={}&error_description={}",
+            redirect_uri,
+            separator,
+            error,
+            utf8_percent_encode(&err.message, NON_ALPHANUMERIC),
+        );
+        if let Some(state) = state {
+            write!(
+                loc,
+                "&state={}",
+                utf8_percent_encode(state, NON_ALPHANUMERIC)
+            )
+            .expect("writing to a String to never fail");
+        }
+        loc
+    }
+}
+
+/// The `response_mode` of an authorization request. JWT secured authorization responses (JARM)
+/// must be enabled for a client by setting an `auth_response_alg`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuthResponseMode {
+    #[default]
+    Query,
+    QueryJwt,
+    FragmentJwt,
+}
+
+impl AuthResponseMode {
+    pub fn parse(response_mode: Option<&str>, client: &Client) -> Result<Self, ErrorResponse> {
+        let mode = match response_mode {
+            None | Some("query") => return Ok(Self::Query),
+            // `jwt` means the default mode for the `code` response type
+            Some("jwt") | Some("query.jwt") => Self::QueryJwt,
+            Some("fragment.jwt") => Self::FragmentJwt,
+            Some(mode) => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("unsupported response_mode: {}", mode),
+                ))
+            }
+        };
+
+        if client.auth_response_alg.is_none() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "JWT secured authorization responses are not enabled for this client",
+            ));
+        }
+        Ok(mode)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JwtAuthResponseClaims {
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_state: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_response_mode() {
+        let mut client = Client::default();
+        assert_eq!(
+            AuthResponseMode::parse(None, &client).unwrap(),
+            AuthResponseMode::Query
+        );
+        assert_eq!(
+            AuthResponseMode::parse(Some("query"), &client).unwrap(),
+            AuthResponseMode::Query
+        );
+        assert!(AuthResponseMode::parse(Some("jwt"), &client).is_err());
+
+        client.auth_response_alg = Some("EdDSA".to_string());
+        assert_eq!(
+            AuthResponseMode::parse(Some("jwt"), &client).unwrap(),
+            AuthResponseMode::QueryJwt
+        );
+        assert_eq!(
+            AuthResponseMode::parse(Some("query.jwt"), &client).unwrap(),
+            AuthResponseMode::QueryJwt
+        );
+        assert_eq!(
+            AuthResponseMode::parse(Some("fragment.jwt"), &client).unwrap(),
+            AuthResponseMode::FragmentJwt
+        );
+        assert!(AuthResponseMode::parse(Some("form_post.jwt"), &client).is_err());
+        assert!(AuthResponseMode::parse(Some("fragment"), &client).is_err());
+    }
+
+    #[test]
+    fn test_error_location() {
+        let err = ErrorResponse::new(ErrorResponseType::BadRequest, "'typ' is not supported")
+            .with_code(ErrorCode::UnsupportedResponseType);
+        assert_eq!(
+            AuthCode::error_location("https://client.test/cb", Some("a b"), &err),
+            "https://client.test/cb?error=unsupported_response_type&error_description=%27typ%27%20is%20not%20supported&state=a%20b"
+        );
+
+        // internal codes must never leak as OAuth errors
+        let err = ErrorResponse::new(ErrorResponseType::BadRequest, "bad");
+        assert_eq!(
+            AuthCode::error_location("https://client.test/cb?x=1", None, &err),
+            "https://client.test/cb?x=1&error=invalid_request&error_description=bad"
+        );
+
+        let err = ErrorResponse::new(ErrorResponseType::BadRequest, "expired")
+            .with_code(ErrorCode::InvalidRequestObject);
+        assert_eq!(
+            AuthCode::error_location("https://client.test/cb", None, &err),
+            "https://client.test/cb?error=invalid_request_object&error_description=expired"
+        );
+    }
 }
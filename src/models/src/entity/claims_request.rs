@@ -0,0 +1,182 @@
+use crate::entity::clients::Client;
+use crate::entity::scopes::Scope;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::debug;
+
+// The max amount of claims, which can be requested for each target
+const MAX_CLAIMS: usize = 64;
+
+/// The OIDC `claims` request parameter, which requests specific claims for the ID token and
+/// the userinfo endpoint.
+///
+/// Claims are always released by scope. A requested claim will add the scope it belongs to,
+/// but only if this scope is allowed for the client. Anything else will be ignored.
+#[derive(Debug, Default, Deserialize)]
+pub struct ClaimsRequest {
+    #[serde(default)]
+    pub id_token: HashMap<String, Option<ClaimRequest>>,
+    #[serde(default)]
+    pub userinfo: HashMap<String, Option<ClaimRequest>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ClaimRequest {
+    #[serde(default)]
+    pub essential: bool,
+    // `value` and `values` are accepted, but the real user values will always be returned
+    pub value: Option<Value>,
+    pub values: Option<Vec<Value>>,
+}
+
+impl ClaimsRequest {
+    pub fn parse(value: &str) -> Result<Self, ErrorResponse> {
+        let slf = serde_json::from_str::<Self>(value).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Invalid 'claims' parameter: {}", err),
+            )
+        })?;
+
+        if slf.id_token.len() > MAX_CLAIMS || slf.userinfo.len() > MAX_CLAIMS {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Too many claims requested",
+            ));
+        }
+
+        Ok(slf)
+    }
+
+    /// Extends the sanitized login scopes with all scopes, which are needed for the requested
+    /// claims and allowed for the client.
+    pub async fn extend_scopes(
+        claims: Option<&str>,
+        client: &Client,
+        scopes: &mut Vec<String>,
+    ) -> Result<(), ErrorResponse> {
+        let Some(claims) = claims else {
+            return Ok(());
+        };
+        let slf = Self::parse(claims)?;
+        let client_scopes = client.get_scopes();
+
+        let mut custom_scopes = None;
+        for (claim, essential) in slf.requested() {
+            let scope = match Self::scope_for_claim(claim) {
+                Some(scope) => Some(scope.to_string()),
+                None => {
+                    // custom user attributes are mapped via custom scopes
+                    if custom_scopes.is_none() {
+                        custom_scopes = Some(Scope::find_all().await?);
+                    }
+                    Self::custom_scope_for_claim(custom_scopes.as_deref().unwrap(), claim)
+                }
+            };
+
+            let Some(scope) = scope.filter(|s| client_scopes.contains(s)) else {
+                if essential {
+                    debug!(
+                        "Essential claim '{}' is not allowed for client '{}'",
+                        claim, client.id
+                    );
+                }
+                continue;
+            };
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ClaimsRequest {
+    /// Returns all requested claim names with their `essential` flag.
+    fn requested(&self) -> impl Iterator<Item = (&str, bool)> {
+        self.id_token
+            .iter()
+            .chain(self.userinfo.iter())
+            .map(|(claim, req)| {
+                let essential = req.as_ref().map(|r| r.essential).unwrap_or(false);
+                (claim.as_str(), essential)
+            })
+    }
+
+    fn scope_for_claim(claim: &str) -> Option<&'static str> {
+        match claim {
+            "email" | "email_verified" => Some("email"),
+            "name" | "given_name" | "family_name" | "preferred_username" | "locale"
+            | "birthdate" | "picture" => Some("profile"),
+            "address" => Some("address"),
+            "phone" | "phone_number" => Some("phone"),
+            "groups" => Some("groups"),
+            "webid" => Some("webid"),
+            _ => None,
+        }
+    }
+
+    fn custom_scope_for_claim(scopes: &[Scope], claim: &str) -> Option<String> {
+        scopes
+            .iter()
+            .find(|s| {
+                [&s.attr_include_id, &s.attr_include_access]
+                    .into_iter()
+                    .flatten()
+                    .any(|csv| csv.split(',').any(|attr| attr.trim() == claim))
+            })
+            .map(|s| s.name.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_claims_request() {
+        let req = ClaimsRequest::parse(
+            r#"{"id_token":{"email":{"essential":true},"given_name":null},"userinfo":{"phone_number":null}}"#,
+        )
+        .unwrap();
+        assert_eq!(req.id_token.len(), 2);
+        assert_eq!(req.userinfo.len(), 1);
+
+        let mut requested = req.requested().collect::<Vec<_>>();
+        requested.sort();
+        assert_eq!(
+            requested,
+            vec![
+                ("email", true),
+                ("given_name", false),
+                ("phone_number", false)
+            ]
+        );
+
+        assert!(ClaimsRequest::parse("{}").is_ok());
+        assert!(ClaimsRequest::parse("email").is_err());
+        assert!(ClaimsRequest::parse(r#"{"id_token":["email"]}"#).is_err());
+    }
+
+    #[test]
+    fn test_custom_scope_for_claim() {
+        let scopes = vec![Scope {
+            id: "1".to_string(),
+            name: "custom".to_string(),
+            attr_include_access: None,
+            attr_include_id: Some("age,city".to_string()),
+            claim_mappings: None,
+        }];
+        assert_eq!(
+            ClaimsRequest::custom_scope_for_claim(&scopes, "city"),
+            Some("custom".to_string())
+        );
+        assert_eq!(
+            ClaimsRequest::custom_scope_for_claim(&scopes, "other"),
+            None
+        );
+    }
+}
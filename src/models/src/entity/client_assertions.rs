@@ -0,0 +1,431 @@
+use crate::database::{Cache, DB};
+use crate::entity::clients::Client;
+use crate::entity::jwk::{JWKSPublicKey, JWKS};
+use jwt_simple::token::Token;
+use rauthy_common::clock;
+use rauthy_common::constants::{CACHE_TTL_CLIENT_JWKS, RAUTHY_VERSION};
+use rauthy_common::utils::{base64_url_no_pad_decode, jwt_expired, jwt_not_yet_valid};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use reqwest::header::ACCEPT;
+use reqwest::tls;
+use ring::hmac;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{error, warn};
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+// The max size of a JWKS fetched from a clients `jwks_uri`
+const MAX_JWKS_SIZE: usize = 64 * 1024;
+
+/// A JWT client assertion (RFC 7523), which is used for `private_key_jwt` and
+/// `client_secret_jwt` client authentication.
+#[derive(Debug, Deserialize)]
+pub struct ClientAssertion {
+    pub iss: String,
+    pub sub: String,
+    pub aud: Value,
+    pub exp: i64,
+    pub jti: String,
+    pub nbf: Option<i64>,
+}
+
+impl ClientAssertion {
+    /// Validates the assertion for the given client. HMAC signed assertions will be validated
+    /// with the `client_secret`, all others with the clients `jwks` or `jwks_uri`.
+    /// Each `jti` will only be accepted once.
+    pub async fn validate(issuer: &str, client: &Client, token: &str) -> Result<(), ErrorResponse> {
+        let metadata = Token::decode_metadata(token).map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Malformed client assertion header",
+            )
+        })?;
+        let alg = metadata.algorithm();
+        client.validate_auth_method(if alg.starts_with("HS") {
+            "client_secret_jwt"
+        } else {
+            "private_key_jwt"
+        })?;
+
+        if alg.starts_with("HS") {
+            Self::validate_hmac(client, token, alg)?;
+        } else {
+            Self::validate_signature(client, token, alg, metadata.key_id()).await?;
+        }
+
+        let claims = token.split('.').nth(1).ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Malformed client assertion",
+            )
+        })?;
+        let slf = serde_json::from_slice::<Self>(&base64_url_no_pad_decode(claims)?)?;
+        slf.validate_claims(issuer, &client.id)?;
+        slf.check_replay(&client.id).await
+    }
+}
+
+impl ClientAssertion {
+    fn validate_hmac(client: &Client, token: &str, alg: &str) -> Result<(), ErrorResponse> {
+        let alg = match alg {
+            "HS256" => hmac::HMAC_SHA256,
+            "HS384" => hmac::HMAC_SHA384,
+            "HS512" => hmac::HMAC_SHA512,
+            _ => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Unauthorized,
+                    "Unsupported client assertion algorithm",
+                ));
+            }
+        };
+        let secret = client.get_secret_cleartext()?.ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "'client_secret_jwt' needs a client with a secret",
+            )
+        })?;
+
+        let (message, sig) = token.rsplit_once('.').ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Malformed client assertion",
+            )
+        })?;
+        let sig = base64_url_no_pad_decode(sig)?;
+        let key = hmac::Key::new(alg, secret.as_bytes());
+        hmac::verify(&key, message.as_bytes(), &sig).map_err(|_| {
+            warn!(
+                "Invalid client assertion signature for client '{}'",
+                client.id
+            );
+            ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Invalid client assertion signature",
+            )
+        })
+    }
+
+    async fn validate_signature(
+        client: &Client,
+        token: &str,
+        alg: &str,
+        kid: Option<&str>,
+    ) -> Result<(), ErrorResponse> {
+        let key = if let Some(jwks) = client.get_jwks()? {
+            Self::find_key(jwks, alg, kid)
+        } else if let Some(uri) = &client.jwks_uri {
+            // the client may have rotated its keys since the JWKS has been cached
+            match Self::find_key(Self::jwks_from_uri(&client.id, uri, false).await?, alg, kid) {
+                Some(key) => Some(key),
+                None => Self::find_key(Self::jwks_from_uri(&client.id, uri, true).await?, alg, kid),
+            }
+        } else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "'private_key_jwt' needs a client with a 'jwks' or 'jwks_uri'",
+            ));
+        };
+
+        let key = key.ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "No matching key found in the clients JWKS",
+            )
+        })?;
+        key.validate_token_signature(token).map_err(|_| {
+            warn!(
+                "Invalid client assertion signature for client '{}'",
+                client.id
+            );
+            ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Invalid client assertion signature",
+            )
+        })
+    }
+
+    fn find_key(jwks: JWKS, alg: &str, kid: Option<&str>) -> Option<JWKSPublicKey> {
+        // the `alg` must always match, which also makes sure that `none` will never be accepted
+        jwks.keys.into_iter().find(|key| {
+            key.alg.as_ref().map(|a| a.as_str()) == Some(alg)
+                && (kid.is_none() || key.kid.as_deref() == kid)
+        })
+    }
+
+    async fn jwks_from_uri(
+        client_id: &str,
+        uri: &str,
+        force_refresh: bool,
+    ) -> Result<JWKS, ErrorResponse> {
+        let jwks = Self::fetch_jwks(client_id, uri, force_refresh).await?;
+        Client::parse_jwks(&jwks)
+    }
+
+    /// Returns the raw JWKS from the clients `jwks_uri`, which will be cached for
+    /// `CACHE_TTL_CLIENT_JWKS`.
+    pub(crate) async fn fetch_jwks(
+        client_id: &str,
+        uri: &str,
+        force_refresh: bool,
+    ) -> Result<String, ErrorResponse> {
+        let idx = format!("client_jwks_{}", client_id);
+        if !force_refresh {
+            let jwks: Option<String> = DB::client().get(Cache::App, &idx).await?;
+            if let Some(jwks) = jwks {
+                return Ok(jwks);
+            }
+        }
+
+        let http_client = HTTP_CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!("Rauthy v{} Client JWKS Resolver", RAUTHY_VERSION))
+                .min_tls_version(tls::Version::TLS_1_2)
+                .pool_idle_timeout(Duration::from_secs(600))
+                .build()
+                .unwrap()
+        });
+
+        let res = http_client
+            .get(uri)
+            .header(ACCEPT, "application/json")
+            .send()
+            .await
+            .map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::Connection,
+                    format!("Cannot fetch client JWKS from {}: {:?}", uri, err),
+                )
+            })?;
+
+        if !res.status().is_success() {
+            let msg = format!("Cannot fetch client JWKS from {}", uri);
+            error!("{}", msg);
+            return Err(ErrorResponse::new(ErrorResponseType::Connection, msg));
+        }
+
+        let body = Self::read_jwks(uri, res).await?;
+        // never cache an invalid JWKS
+        Client::parse_jwks(&body)?;
+
+        DB::client()
+            .put(Cache::App, idx, &body, CACHE_TTL_CLIENT_JWKS)
+            .await?;
+
+        Ok(body)
+    }
+
+    /// Reads the JWKS in chunks to never buffer more than `MAX_JWKS_SIZE`, no matter what the
+    /// remote server sends or claims in its `Content-Length`.
+    async fn read_jwks(uri: &str, mut res: reqwest::Response) -> Result<String, ErrorResponse> {
+        let too_large = || {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The client JWKS is too large",
+            )
+        };
+        if res
+            .content_length()
+            .is_some_and(|len| len > MAX_JWKS_SIZE as u64)
+        {
+            return Err(too_large());
+        }
+
+        let mut body = Vec::with_capacity(1024);
+        while let Some(chunk) = res.chunk().await.map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Connection,
+                format!("Cannot read client JWKS from {}: {}", uri, err),
+            )
+        })? {
+            if body.len() + chunk.len() > MAX_JWKS_SIZE {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        String::from_utf8(body).map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The client JWKS is not valid UTF-8",
+            )
+        })
+    }
+
+    fn validate_claims(&self, issuer: &str, client_id: &str) -> Result<(), ErrorResponse> {
+        if self.iss != client_id || self.sub != client_id {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "The 'iss' and 'sub' of the client assertion must be the 'client_id'",
+            ));
+        }
+
+        // RFC 7523 allows the issuer or the URL of the endpoint the assertion is sent to
+        let audiences = [
+            issuer.to_string(),
+            format!("{}/oidc/token", issuer),
+            format!("{}/oidc/introspect", issuer),
+        ];
+        let aud_matches = match &self.aud {
+            Value::String(aud) => audiences.contains(aud),
+            Value::Array(aud) => aud
+                .iter()
+                .filter_map(|a| a.as_str())
+                .any(|a| audiences.iter().any(|allowed| allowed == a)),
+            _ => false,
+        };
+        if !aud_matches {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "The 'aud' of the client assertion must contain the issuer",
+            ));
+        }
+
+        let now = clock::now().timestamp();
+        if jwt_expired(self.exp, now) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "The client assertion has expired",
+            ));
+        }
+        if self
+            .nbf
+            .map(|nbf| jwt_not_yet_valid(nbf, now))
+            .unwrap_or(false)
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "The client assertion is not valid yet",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Makes sure that each `jti` can only be used once until the assertion expires.
+    /// The check and the insert happen inside a distributed lock, so concurrent requests
+    /// with the same assertion cannot both pass.
+    async fn check_replay(&self, client_id: &str) -> Result<(), ErrorResponse> {
+        let idx = format!("{}_{}", client_id, self.jti);
+        let client = DB::client();
+        let _lock = client.lock(format!("client_assertion_{}", idx)).await?;
+
+        let used: Option<i64> = client.get(Cache::ClientAssertion, &idx).await?;
+        if used.is_some() {
+            warn!(
+                "Client assertion replay detected for client '{}'",
+                client_id
+            );
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "The client assertion has been used already",
+            ));
+        }
+
+        let ttl = (self.exp - clock::now().timestamp()).max(1);
+        client
+            .put(Cache::ClientAssertion, idx, &self.exp, Some(ttl))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_validate_claims() {
+        let issuer = "http://localhost:8080/auth/v1";
+        let now = clock::now().timestamp();
+        let mut assertion = serde_json::from_value::<ClientAssertion>(json!({
+            "iss": "my_client",
+            "sub": "my_client",
+            "aud": format!("{}/oidc/token", issuer),
+            "exp": now + 60,
+            "jti": "123",
+        }))
+        .unwrap();
+        assert!(assertion.validate_claims(issuer, "my_client").is_ok());
+        assert!(assertion.validate_claims(issuer, "other_client").is_err());
+
+        assertion.aud = json!(["something", issuer]);
+        assert!(assertion.validate_claims(issuer, "my_client").is_ok());
+        assertion.aud = json!("something");
+        assert!(assertion.validate_claims(issuer, "my_client").is_err());
+        assertion.aud = json!(issuer);
+
+        assertion.sub = "other_client".to_string();
+        assert!(assertion.validate_claims(issuer, "my_client").is_err());
+        assertion.sub = "my_client".to_string();
+
+        // the default clock skew of 60 seconds is accepted
+        assertion.exp = now - 30;
+        assert!(assertion.validate_claims(issuer, "my_client").is_ok());
+        assertion.exp = now - 61;
+        assert!(assertion.validate_claims(issuer, "my_client").is_err());
+        assertion.exp = now + 60;
+
+        assertion.nbf = Some(now + 30);
+        assert!(assertion.validate_claims(issuer, "my_client").is_ok());
+        assertion.nbf = Some(now + 90);
+        assert!(assertion.validate_claims(issuer, "my_client").is_err());
+    }
+
+    /// Serves a single response with the given head and body and closes the connection.
+    async fn serve_once(head: &'static str, body: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("http://{}/jwks", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf).await.unwrap();
+            let _ = stream.write_all(head.as_bytes()).await;
+            let _ = stream.write_all(&body).await;
+        });
+
+        uri
+    }
+
+    async fn read_from(head: &'static str, body: Vec<u8>) -> Result<String, ErrorResponse> {
+        let uri = serve_once(head, body).await;
+        let res = reqwest::get(&uri).await.unwrap();
+        ClientAssertion::read_jwks(&uri, res).await
+    }
+
+    #[tokio::test]
+    async fn test_read_jwks() {
+        let jwks = r#"{"keys":[]}"#;
+        let body = read_from(
+            "HTTP/1.1 200 OK\r\ncontent-length: 11\r\n\r\n",
+            jwks.as_bytes().to_vec(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body, jwks);
+
+        // rejected by its `Content-Length` before the body is read
+        let res = read_from(
+            "HTTP/1.1 200 OK\r\ncontent-length: 1000000\r\n\r\n",
+            vec![b'a'; 1024],
+        )
+        .await;
+        assert!(res.is_err());
+
+        // without a `Content-Length`, the read stops once the limit is exceeded
+        let res = read_from(
+            "HTTP/1.1 200 OK\r\nconnection: close\r\n\r\n",
+            vec![b'a'; MAX_JWKS_SIZE + 1],
+        )
+        .await;
+        assert_eq!(res.unwrap_err().message, "The client JWKS is too large");
+    }
+}
@@ -1,29 +1,37 @@
 use crate::app_state::{AppState, DbTxn};
 use crate::database::{Cache, DB};
+use crate::entity::client_assertions::ClientAssertion;
+use crate::entity::clients_access::ClientAccessPolicy;
 use crate::entity::clients_dyn::ClientDyn;
-use crate::entity::jwk::JwkKeyPairAlg;
+use crate::entity::federation::EntityStatement;
+use crate::entity::jwe::JweEncrypter;
+use crate::entity::jwk::{JwkKeyPairAlg, JWKS};
+use crate::entity::mtls::ClientCert;
+use crate::entity::pairwise_subjects::PairwiseSubject;
 use crate::entity::scopes::Scope;
+use crate::entity::sessions::Session;
 use crate::entity::users::User;
 use crate::ListenScheme;
 use actix_web::http::header;
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest};
-use chrono::Utc;
 use cryptr::{utils, EncKeys, EncValue};
 use hiqlite::{params, Param, Params};
 use rauthy_api_types::clients::{
-    ClientResponse, DynamicClientRequest, DynamicClientResponse, EphemeralClientRequest,
-    NewClientRequest,
+    ClaimTemplate, ClientResponse, DynamicClientRequest, DynamicClientResponse,
+    EphemeralClientRequest, NewClientRequest,
 };
+use rauthy_common::clock;
 use rauthy_common::constants::{
     ADDITIONAL_ALLOWED_ORIGIN_SCHEMES, ADMIN_FORCE_MFA, APPLICATION_JSON, CACHE_TTL_APP,
-    CACHE_TTL_DYN_CLIENT, CACHE_TTL_EPHEMERAL_CLIENT, DYN_CLIENT_DEFAULT_TOKEN_LIFETIME,
-    DYN_CLIENT_SECRET_AUTO_ROTATE, ENABLE_EPHEMERAL_CLIENTS, EPHEMERAL_CLIENTS_ALLOWED_FLOWS,
-    EPHEMERAL_CLIENTS_ALLOWED_SCOPES, EPHEMERAL_CLIENTS_FORCE_MFA, PROXY_MODE, RAUTHY_VERSION,
+    CACHE_TTL_DYN_CLIENT, CACHE_TTL_EPHEMERAL_CLIENT, CLAIMS_PROTECTED,
+    DYN_CLIENT_DEFAULT_TOKEN_LIFETIME, DYN_CLIENT_SECRET_AUTO_ROTATE, ENABLE_EPHEMERAL_CLIENTS,
+    EPHEMERAL_CLIENTS_ALLOWED_FLOWS, EPHEMERAL_CLIENTS_ALLOWED_SCOPES, EPHEMERAL_CLIENTS_FORCE_MFA,
+    FEDERATION_ENABLE, PROXY_MODE, RAUTHY_VERSION, RESPONSE_TYPES_SUPPORTED,
 };
 use rauthy_common::is_hiqlite;
 use rauthy_common::utils::{get_rand, real_ip_from_req};
-use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
 use reqwest::header::CONTENT_TYPE;
 use reqwest::{tls, Url};
 use serde::{Deserialize, Serialize};
@@ -58,9 +66,9 @@ pub struct Client {
     pub post_logout_redirect_uris: Option<String>,
     pub allowed_origins: Option<String>,
     pub flows_enabled: String,
-    // Currently supported Algorithms: RS 256, 384, 512 and EdDSA
+    // Currently supported Algorithms: RS 256, 384, 512, ES384 and EdDSA
     pub access_token_alg: String,
-    // Currently supported Algorithms: RS 256, 384, 512 and EdDSA
+    // Currently supported Algorithms: RS 256, 384, 512, ES384 and EdDSA
     pub id_token_alg: String,
     pub auth_code_lifetime: i32,
     pub access_token_lifetime: i32,
@@ -70,6 +78,62 @@ pub struct Client {
     pub force_mfa: bool,
     pub client_uri: Option<String>,
     pub contacts: Option<String>,
+    // JWT secured authorization responses (JARM) are only allowed with an algorithm
+    pub auth_response_alg: Option<String>,
+    // the public keys for signed request objects as JSON
+    pub jwks: Option<String>,
+    pub request_uris: Option<String>,
+    // rendered as hidden iframe during logout to log the user out of this client as well
+    pub frontchannel_logout_uri: Option<String>,
+    // `tls_client_auth` | `self_signed_tls_client_auth` -> replaces the `client_secret` if set
+    pub tls_client_auth: Option<String>,
+    pub tls_client_auth_subject_dn: Option<String>,
+    // comma separated list of resources (RFC 8707), which may be requested as token audience
+    pub allowed_resources: Option<String>,
+    // fetched JWKS for `private_key_jwt` client authentication, if no `jwks` is registered
+    pub jwks_uri: Option<String>,
+    // `public` | `pairwise` -> `None` means public
+    pub subject_type: Option<String>,
+    pub sector_identifier_uri: Option<String>,
+    // issues opaque references instead of JWT access tokens
+    pub opaque_access_tokens: bool,
+    // JWE encryption of ID tokens and userinfo responses with a key from the clients JWKS
+    pub id_token_encrypted_response_alg: Option<String>,
+    pub id_token_encrypted_response_enc: Option<String>,
+    pub userinfo_encrypted_response_alg: Option<String>,
+    pub userinfo_encrypted_response_enc: Option<String>,
+    // returns the userinfo as a signed JWT instead of plain JSON
+    pub userinfo_signed_response_alg: Option<String>,
+    // issues a new refresh token on each use and invalidates the old one after the grace time
+    pub refresh_token_rotation: bool,
+    // overwrites the global `REFRESH_TOKEN_GRACE_TIME`
+    pub refresh_token_grace_time: Option<i32>,
+    // revokes the whole token family, if a rotated refresh token is used after the grace time
+    pub refresh_token_reuse_detection: bool,
+    // users must grant the requested scopes on a consent screen before a code is issued
+    pub consent_required: bool,
+    // overwrites the `access_token_lifetime` for ID tokens
+    pub id_token_lifetime: Option<i32>,
+    // overwrites the global refresh token lifetimes
+    pub refresh_token_lifetime: Option<i32>,
+    // the `ClaimTemplate` as JSON
+    pub claim_template: Option<String>,
+    // stricter session max age / idle timeout for this client
+    pub session_lifetime: Option<i32>,
+    pub session_timeout: Option<i32>,
+    // rejects passkey logins from another device via hybrid transport
+    pub deny_hybrid_passkeys: bool,
+    // requires PKCE with `S256` at the authorize and token endpoints
+    pub require_pkce_s256: bool,
+    // comma separated list of the allowed `response_type`s
+    pub response_types: String,
+    // only accepts authorization requests passed inside a signed request object (JAR)
+    pub require_signed_request_object: bool,
+    // receives a signed `logout_token` via POST when a session of this client ends
+    pub backchannel_logout_uri: Option<String>,
+    // the only accepted client authentication at the token endpoint, if set -
+    // without it, only the `client_secret` via `client_secret_basic` / `_post` is accepted
+    pub token_endpoint_auth_method: Option<String>,
 }
 
 // CRUD
@@ -98,9 +162,14 @@ impl Client {
 INSERT INTO clients (id, name, enabled, confidential, secret, secret_kid, redirect_uris,
 post_logout_redirect_uris, allowed_origins, flows_enabled, access_token_alg, id_token_alg,
 auth_code_lifetime, access_token_lifetime, scopes, default_scopes, challenge, force_mfa,
-client_uri, contacts)
+client_uri, contacts, auth_response_alg, jwks, request_uris, frontchannel_logout_uri,
+tls_client_auth, tls_client_auth_subject_dn, allowed_resources, jwks_uri,
+subject_type, sector_identifier_uri,
+opaque_access_tokens, id_token_encrypted_response_alg, id_token_encrypted_response_enc,
+userinfo_encrypted_response_alg, userinfo_encrypted_response_enc, userinfo_signed_response_alg, refresh_token_rotation, refresh_token_grace_time, refresh_token_reuse_detection, consent_required, id_token_lifetime, refresh_token_lifetime, claim_template, session_lifetime, session_timeout, deny_hybrid_passkeys, require_pkce_s256, response_types, require_signed_request_object, backchannel_logout_uri, token_endpoint_auth_method)
 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
-$18, $19, $20)"#,
+$18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33,
+$34, $35, $36, $37, $38, $39, $40, $41, $42, $43, $44, $45, $46, $47, $48, $49, $50, $51)"#,
                     params!(
                         &client.id,
                         &client.name,
@@ -121,7 +190,38 @@ $18, $19, $20)"#,
                         &client.challenge,
                         client.force_mfa,
                         &client.client_uri,
-                        &client.contacts
+                        &client.contacts,
+                        &client.auth_response_alg,
+                        &client.jwks,
+                        &client.request_uris,
+                        &client.frontchannel_logout_uri,
+                        &client.tls_client_auth,
+                        &client.tls_client_auth_subject_dn,
+                        &client.allowed_resources,
+                        &client.jwks_uri,
+                        &client.subject_type,
+                        &client.sector_identifier_uri,
+                        client.opaque_access_tokens,
+                        &client.id_token_encrypted_response_alg,
+                        &client.id_token_encrypted_response_enc,
+                        &client.userinfo_encrypted_response_alg,
+                        &client.userinfo_encrypted_response_enc,
+                        &client.userinfo_signed_response_alg,
+                        client.refresh_token_rotation,
+                        client.refresh_token_grace_time,
+                        client.refresh_token_reuse_detection,
+                        client.consent_required,
+                        client.id_token_lifetime,
+                        client.refresh_token_lifetime,
+                        &client.claim_template,
+                        client.session_lifetime,
+                        client.session_timeout,
+                        client.deny_hybrid_passkeys,
+                        client.require_pkce_s256,
+                        &client.response_types,
+                        client.require_signed_request_object,
+                        &client.backchannel_logout_uri,
+                        &client.token_endpoint_auth_method
                     ),
                 )
                 .await?;
@@ -131,9 +231,14 @@ $18, $19, $20)"#,
     INSERT INTO clients (id, name, enabled, confidential, secret, secret_kid, redirect_uris,
     post_logout_redirect_uris, allowed_origins, flows_enabled, access_token_alg, id_token_alg,
     auth_code_lifetime, access_token_lifetime, scopes, default_scopes, challenge, force_mfa,
-    client_uri, contacts)
+    client_uri, contacts, auth_response_alg, jwks, request_uris, frontchannel_logout_uri,
+tls_client_auth, tls_client_auth_subject_dn, allowed_resources, jwks_uri,
+subject_type, sector_identifier_uri,
+opaque_access_tokens, id_token_encrypted_response_alg, id_token_encrypted_response_enc,
+userinfo_encrypted_response_alg, userinfo_encrypted_response_enc, userinfo_signed_response_alg, refresh_token_rotation, refresh_token_grace_time, refresh_token_reuse_detection, consent_required, id_token_lifetime, refresh_token_lifetime, claim_template, session_lifetime, session_timeout, deny_hybrid_passkeys, require_pkce_s256, response_types, require_signed_request_object, backchannel_logout_uri, token_endpoint_auth_method)
     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
-    $18, $19, $20)"#,
+    $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33,
+$34, $35, $36, $37, $38, $39, $40, $41, $42, $43, $44, $45, $46, $47, $48, $49, $50, $51)"#,
                 client.id,
                 client.name,
                 client.enabled,
@@ -154,6 +259,37 @@ $18, $19, $20)"#,
                 client.force_mfa,
                 client.client_uri,
                 client.contacts,
+                client.auth_response_alg,
+                client.jwks,
+                client.request_uris,
+                client.frontchannel_logout_uri,
+                client.tls_client_auth,
+                client.tls_client_auth_subject_dn,
+                client.allowed_resources,
+                client.jwks_uri,
+                client.subject_type,
+                client.sector_identifier_uri,
+                client.opaque_access_tokens,
+                client.id_token_encrypted_response_alg,
+                client.id_token_encrypted_response_enc,
+                client.userinfo_encrypted_response_alg,
+                client.userinfo_encrypted_response_enc,
+                client.userinfo_signed_response_alg,
+                client.refresh_token_rotation,
+                client.refresh_token_grace_time,
+                client.refresh_token_reuse_detection,
+                client.consent_required,
+                client.id_token_lifetime,
+                client.refresh_token_lifetime,
+                client.claim_template,
+                client.session_lifetime,
+                client.session_timeout,
+                client.deny_hybrid_passkeys,
+                client.require_pkce_s256,
+                client.response_types,
+                client.require_signed_request_object,
+                client.backchannel_logout_uri,
+                client.token_endpoint_auth_method,
             )
             .execute(DB::conn())
             .await?;
@@ -173,7 +309,7 @@ $18, $19, $20)"#,
 
         let client = Self::try_from_dyn_reg(client_req)?;
 
-        let created = Utc::now().timestamp();
+        let created = clock::now().timestamp();
         let (_secret_plain, registration_token) = Self::generate_new_secret()?;
 
         if is_hiqlite() {
@@ -182,8 +318,13 @@ $18, $19, $20)"#,
 INSERT INTO clients (id, name, enabled, confidential, secret, secret_kid, redirect_uris,
 post_logout_redirect_uris, allowed_origins, flows_enabled, access_token_alg, id_token_alg,
 auth_code_lifetime, access_token_lifetime, scopes, default_scopes, challenge, force_mfa,
-client_uri, contacts)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)"#,
+client_uri, contacts, auth_response_alg, jwks, request_uris, frontchannel_logout_uri,
+tls_client_auth, tls_client_auth_subject_dn, allowed_resources, jwks_uri,
+subject_type, sector_identifier_uri,
+opaque_access_tokens, id_token_encrypted_response_alg, id_token_encrypted_response_enc,
+userinfo_encrypted_response_alg, userinfo_encrypted_response_enc, userinfo_signed_response_alg, refresh_token_rotation, refresh_token_grace_time, refresh_token_reuse_detection, consent_required, id_token_lifetime, refresh_token_lifetime, claim_template, session_lifetime, session_timeout, deny_hybrid_passkeys, require_pkce_s256, response_types, require_signed_request_object, backchannel_logout_uri, token_endpoint_auth_method)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33,
+$34, $35, $36, $37, $38, $39, $40, $41, $42, $43, $44, $45, $46, $47, $48, $49, $50, $51)"#,
                 params!(
                     &client.id,
                     &client.name,
@@ -204,7 +345,38 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
                     &client.challenge,
                     client.force_mfa,
                     &client.client_uri,
-                    &client.contacts
+                    &client.contacts,
+                    &client.auth_response_alg,
+                    &client.jwks,
+                    &client.request_uris,
+                    &client.frontchannel_logout_uri,
+                    &client.tls_client_auth,
+                    &client.tls_client_auth_subject_dn,
+                    &client.allowed_resources,
+                        &client.jwks_uri,
+                        &client.subject_type,
+                        &client.sector_identifier_uri,
+                        client.opaque_access_tokens,
+                        &client.id_token_encrypted_response_alg,
+                        &client.id_token_encrypted_response_enc,
+                        &client.userinfo_encrypted_response_alg,
+                        &client.userinfo_encrypted_response_enc,
+                        &client.userinfo_signed_response_alg,
+                        client.refresh_token_rotation,
+                        client.refresh_token_grace_time,
+                        client.refresh_token_reuse_detection,
+                        client.consent_required,
+                        client.id_token_lifetime,
+                        client.refresh_token_lifetime,
+                        &client.claim_template,
+                        client.session_lifetime,
+                        client.session_timeout,
+                        client.deny_hybrid_passkeys,
+                        client.require_pkce_s256,
+                        &client.response_types,
+                        client.require_signed_request_object,
+                        &client.backchannel_logout_uri,
+                        &client.token_endpoint_auth_method
                 )),
                 (r#"
 INSERT INTO
@@ -225,8 +397,13 @@ VALUES ($1, $2, $3, $4)"#,
 INSERT INTO clients (id, name, enabled, confidential, secret, secret_kid, redirect_uris,
 post_logout_redirect_uris, allowed_origins, flows_enabled, access_token_alg, id_token_alg,
 auth_code_lifetime, access_token_lifetime, scopes, default_scopes, challenge, force_mfa,
-client_uri, contacts)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)"#,
+client_uri, contacts, auth_response_alg, jwks, request_uris, frontchannel_logout_uri,
+tls_client_auth, tls_client_auth_subject_dn, allowed_resources, jwks_uri,
+subject_type, sector_identifier_uri,
+opaque_access_tokens, id_token_encrypted_response_alg, id_token_encrypted_response_enc,
+userinfo_encrypted_response_alg, userinfo_encrypted_response_enc, userinfo_signed_response_alg, refresh_token_rotation, refresh_token_grace_time, refresh_token_reuse_detection, consent_required, id_token_lifetime, refresh_token_lifetime, claim_template, session_lifetime, session_timeout, deny_hybrid_passkeys, require_pkce_s256, response_types, require_signed_request_object, backchannel_logout_uri, token_endpoint_auth_method)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33,
+$34, $35, $36, $37, $38, $39, $40, $41, $42, $43, $44, $45, $46, $47, $48, $49, $50, $51)"#,
                 client.id,
                 client.name,
                 client.enabled,
@@ -247,6 +424,37 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
                 client.force_mfa,
                 client.client_uri,
                 client.contacts,
+                client.auth_response_alg,
+                client.jwks,
+                client.request_uris,
+                client.frontchannel_logout_uri,
+                client.tls_client_auth,
+                client.tls_client_auth_subject_dn,
+                client.allowed_resources,
+                client.jwks_uri,
+                client.subject_type,
+                client.sector_identifier_uri,
+                client.opaque_access_tokens,
+                client.id_token_encrypted_response_alg,
+                client.id_token_encrypted_response_enc,
+                client.userinfo_encrypted_response_alg,
+                client.userinfo_encrypted_response_enc,
+                client.userinfo_signed_response_alg,
+                client.refresh_token_rotation,
+                client.refresh_token_grace_time,
+                client.refresh_token_reuse_detection,
+                client.consent_required,
+                client.id_token_lifetime,
+                client.refresh_token_lifetime,
+                client.claim_template,
+                client.session_lifetime,
+                client.session_timeout,
+                client.deny_hybrid_passkeys,
+                client.require_pkce_s256,
+                client.response_types,
+                client.require_signed_request_object,
+                client.backchannel_logout_uri,
+                client.token_endpoint_auth_method,
             )
             .execute(&mut *txn)
             .await?;
@@ -296,6 +504,7 @@ VALUES ($1, $2, $3, $4)"#,
         if self.is_dynamic() {
             ClientDyn::delete_from_cache(&self.id).await?;
         }
+        ClientAccessPolicy::invalidate_cache().await?;
 
         Ok(())
     }
@@ -376,12 +585,42 @@ VALUES ($1, $2, $3, $4)"#,
         Ok(uris)
     }
 
+    /// Returns `true`, if the given URI is an authorization request on this instance, which
+    /// leads back to a registered `redirect_uri` of an existing client. These are built for
+    /// registrations started via `prompt=create`.
+    pub async fn is_authorize_uri(issuer: &str, uri: &str) -> bool {
+        if !uri.starts_with(&format!("{}/oidc/authorize?", issuer)) {
+            return false;
+        }
+        let Ok(url) = Url::parse(uri) else {
+            return false;
+        };
+
+        let mut client_id = None;
+        let mut redirect_uri = None;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "client_id" => client_id = Some(value.to_string()),
+                "redirect_uri" => redirect_uri = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        let (Some(client_id), Some(redirect_uri)) = (client_id, redirect_uri) else {
+            return false;
+        };
+
+        match Self::find(client_id).await {
+            Ok(client) => client.validate_redirect_uri(&redirect_uri).is_ok(),
+            Err(_) => false,
+        }
+    }
+
     /// Accepts either a pre-registered client_id or a URL as such.
     /// If allowed, it will dynamically build an ephemeral client and cache it, it the client_id
     /// is a URL. Otherwise, it will do a classic fetch from the database.
     /// This function should be used in places where we would possibly accept an ephemeral client.
     pub async fn find_maybe_ephemeral(id: String) -> Result<Self, ErrorResponse> {
-        if !*ENABLE_EPHEMERAL_CLIENTS || Url::from_str(&id).is_err() {
+        if (!*ENABLE_EPHEMERAL_CLIENTS && !*FEDERATION_ENABLE) || Url::from_str(&id).is_err() {
             return Self::find(id).await;
         }
 
@@ -390,7 +629,18 @@ VALUES ($1, $2, $3, $4)"#,
             return Ok(slf);
         }
 
-        let slf = Self::ephemeral_from_url(&id).await?;
+        let slf = if *FEDERATION_ENABLE {
+            match Self::federated_from_url(&id).await {
+                Ok(slf) => slf,
+                Err(err) if *ENABLE_EPHEMERAL_CLIENTS => {
+                    debug!("No federation entity at {}: {}", id, err.message);
+                    Self::ephemeral_from_url(&id).await?
+                }
+                Err(err) => return Err(err),
+            }
+        } else {
+            Self::ephemeral_from_url(&id).await?
+        };
 
         client
             .put(
@@ -432,8 +682,13 @@ UPDATE clients
 SET name = $1, enabled = $2, confidential = $3, secret = $4, secret_kid = $5, redirect_uris = $6,
 post_logout_redirect_uris = $7, allowed_origins = $8, flows_enabled = $9, access_token_alg = $10,
 id_token_alg = $11, auth_code_lifetime = $12, access_token_lifetime = $13, scopes = $14,
-default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19
-WHERE id = $20"#,
+default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19,
+auth_response_alg = $20, jwks = $21, request_uris = $22,
+frontchannel_logout_uri = $23, tls_client_auth = $24, tls_client_auth_subject_dn = $25,
+allowed_resources = $26, jwks_uri = $27, subject_type = $28, sector_identifier_uri = $29, opaque_access_tokens = $30,
+id_token_encrypted_response_alg = $31, id_token_encrypted_response_enc = $32,
+userinfo_encrypted_response_alg = $33, userinfo_encrypted_response_enc = $34, userinfo_signed_response_alg = $35, refresh_token_rotation = $36, refresh_token_grace_time = $37, refresh_token_reuse_detection = $38, consent_required = $39, id_token_lifetime = $40, refresh_token_lifetime = $41, claim_template = $42, session_lifetime = $43, session_timeout = $44, deny_hybrid_passkeys = $45, require_pkce_s256 = $46, response_types = $47, require_signed_request_object = $48, backchannel_logout_uri = $49, token_endpoint_auth_method = $50
+WHERE id = $51"#,
             params!(
                 &self.name,
                 self.enabled,
@@ -454,6 +709,37 @@ WHERE id = $20"#,
                 self.force_mfa,
                 &self.client_uri,
                 &self.contacts,
+                &self.auth_response_alg,
+                &self.jwks,
+                &self.request_uris,
+                &self.frontchannel_logout_uri,
+                &self.tls_client_auth,
+                &self.tls_client_auth_subject_dn,
+                &self.allowed_resources,
+                &self.jwks_uri,
+                &self.subject_type,
+                &self.sector_identifier_uri,
+                self.opaque_access_tokens,
+                &self.id_token_encrypted_response_alg,
+                &self.id_token_encrypted_response_enc,
+                &self.userinfo_encrypted_response_alg,
+                &self.userinfo_encrypted_response_enc,
+                &self.userinfo_signed_response_alg,
+                self.refresh_token_rotation,
+                self.refresh_token_grace_time,
+                self.refresh_token_reuse_detection,
+                self.consent_required,
+                self.id_token_lifetime,
+                self.refresh_token_lifetime,
+                &self.claim_template,
+                self.session_lifetime,
+                self.session_timeout,
+                self.deny_hybrid_passkeys,
+                self.require_pkce_s256,
+                &self.response_types,
+                self.require_signed_request_object,
+                &self.backchannel_logout_uri,
+                &self.token_endpoint_auth_method,
                 &self.id
             ),
         ));
@@ -466,8 +752,13 @@ UPDATE clients
 SET name = $1, enabled = $2, confidential = $3, secret = $4, secret_kid = $5, redirect_uris = $6,
 post_logout_redirect_uris = $7, allowed_origins = $8, flows_enabled = $9, access_token_alg = $10,
 id_token_alg = $11, auth_code_lifetime = $12, access_token_lifetime = $13, scopes = $14,
-default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19
-WHERE id = $20"#,
+default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19,
+auth_response_alg = $20, jwks = $21, request_uris = $22,
+frontchannel_logout_uri = $23, tls_client_auth = $24, tls_client_auth_subject_dn = $25,
+allowed_resources = $26, jwks_uri = $27, subject_type = $28, sector_identifier_uri = $29, opaque_access_tokens = $30,
+id_token_encrypted_response_alg = $31, id_token_encrypted_response_enc = $32,
+userinfo_encrypted_response_alg = $33, userinfo_encrypted_response_enc = $34, userinfo_signed_response_alg = $35, refresh_token_rotation = $36, refresh_token_grace_time = $37, refresh_token_reuse_detection = $38, consent_required = $39, id_token_lifetime = $40, refresh_token_lifetime = $41, claim_template = $42, session_lifetime = $43, session_timeout = $44, deny_hybrid_passkeys = $45, require_pkce_s256 = $46, response_types = $47, require_signed_request_object = $48, backchannel_logout_uri = $49, token_endpoint_auth_method = $50
+WHERE id = $51"#,
             self.name,
             self.enabled,
             self.confidential,
@@ -487,6 +778,37 @@ WHERE id = $20"#,
             self.force_mfa,
             self.client_uri,
             self.contacts,
+            self.auth_response_alg,
+            self.jwks,
+            self.request_uris,
+            self.frontchannel_logout_uri,
+            self.tls_client_auth,
+            self.tls_client_auth_subject_dn,
+            self.allowed_resources,
+            self.jwks_uri,
+            self.subject_type,
+            self.sector_identifier_uri,
+            self.opaque_access_tokens,
+            self.id_token_encrypted_response_alg,
+            self.id_token_encrypted_response_enc,
+            self.userinfo_encrypted_response_alg,
+            self.userinfo_encrypted_response_enc,
+            self.userinfo_signed_response_alg,
+            self.refresh_token_rotation,
+            self.refresh_token_grace_time,
+            self.refresh_token_reuse_detection,
+            self.consent_required,
+            self.id_token_lifetime,
+            self.refresh_token_lifetime,
+            self.claim_template,
+            self.session_lifetime,
+            self.session_timeout,
+            self.deny_hybrid_passkeys,
+            self.require_pkce_s256,
+            self.response_types,
+            self.require_signed_request_object,
+            self.backchannel_logout_uri,
+            self.token_endpoint_auth_method,
             self.id,
         )
         .execute(&mut **txn)
@@ -511,8 +833,13 @@ UPDATE clients
 SET name = $1, enabled = $2, confidential = $3, secret = $4, secret_kid = $5, redirect_uris = $6,
 post_logout_redirect_uris = $7, allowed_origins = $8, flows_enabled = $9, access_token_alg = $10,
 id_token_alg = $11, auth_code_lifetime = $12, access_token_lifetime = $13, scopes = $14,
-default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19
-WHERE id = $20"#,
+default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19,
+auth_response_alg = $20, jwks = $21, request_uris = $22,
+frontchannel_logout_uri = $23, tls_client_auth = $24, tls_client_auth_subject_dn = $25,
+allowed_resources = $26, jwks_uri = $27, subject_type = $28, sector_identifier_uri = $29, opaque_access_tokens = $30,
+id_token_encrypted_response_alg = $31, id_token_encrypted_response_enc = $32,
+userinfo_encrypted_response_alg = $33, userinfo_encrypted_response_enc = $34, userinfo_signed_response_alg = $35, refresh_token_rotation = $36, refresh_token_grace_time = $37, refresh_token_reuse_detection = $38, consent_required = $39, id_token_lifetime = $40, refresh_token_lifetime = $41, claim_template = $42, session_lifetime = $43, session_timeout = $44, deny_hybrid_passkeys = $45, require_pkce_s256 = $46, response_types = $47, require_signed_request_object = $48, backchannel_logout_uri = $49, token_endpoint_auth_method = $50
+WHERE id = $51"#,
                     params!(
                         self.name.clone(),
                         self.enabled,
@@ -533,6 +860,37 @@ WHERE id = $20"#,
                         self.force_mfa,
                         self.client_uri.clone(),
                         self.contacts.clone(),
+                        self.auth_response_alg.clone(),
+                        self.jwks.clone(),
+                        self.request_uris.clone(),
+                        self.frontchannel_logout_uri.clone(),
+                        self.tls_client_auth.clone(),
+                        self.tls_client_auth_subject_dn.clone(),
+                        self.allowed_resources.clone(),
+                        self.jwks_uri.clone(),
+                        self.subject_type.clone(),
+                        self.sector_identifier_uri.clone(),
+                        self.opaque_access_tokens,
+                        self.id_token_encrypted_response_alg.clone(),
+                        self.id_token_encrypted_response_enc.clone(),
+                        self.userinfo_encrypted_response_alg.clone(),
+                        self.userinfo_encrypted_response_enc.clone(),
+                        self.userinfo_signed_response_alg.clone(),
+                        self.refresh_token_rotation,
+                        self.refresh_token_grace_time,
+                        self.refresh_token_reuse_detection,
+                        self.consent_required,
+                        self.id_token_lifetime,
+                        self.refresh_token_lifetime,
+                        self.claim_template.clone(),
+                        self.session_lifetime,
+                        self.session_timeout,
+                        self.deny_hybrid_passkeys,
+                        self.require_pkce_s256,
+                        &self.response_types,
+                        self.require_signed_request_object,
+                        self.backchannel_logout_uri.clone(),
+                        self.token_endpoint_auth_method.clone(),
                         self.id.clone()
                     ),
                 )
@@ -544,8 +902,13 @@ UPDATE clients
 SET name = $1, enabled = $2, confidential = $3, secret = $4, secret_kid = $5, redirect_uris = $6,
 post_logout_redirect_uris = $7, allowed_origins = $8, flows_enabled = $9, access_token_alg = $10,
 id_token_alg = $11, auth_code_lifetime = $12, access_token_lifetime = $13, scopes = $14,
-default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19
-WHERE id = $20"#,
+default_scopes = $15, challenge = $16, force_mfa= $17, client_uri = $18, contacts = $19,
+auth_response_alg = $20, jwks = $21, request_uris = $22,
+frontchannel_logout_uri = $23, tls_client_auth = $24, tls_client_auth_subject_dn = $25,
+allowed_resources = $26, jwks_uri = $27, subject_type = $28, sector_identifier_uri = $29, opaque_access_tokens = $30,
+id_token_encrypted_response_alg = $31, id_token_encrypted_response_enc = $32,
+userinfo_encrypted_response_alg = $33, userinfo_encrypted_response_enc = $34, userinfo_signed_response_alg = $35, refresh_token_rotation = $36, refresh_token_grace_time = $37, refresh_token_reuse_detection = $38, consent_required = $39, id_token_lifetime = $40, refresh_token_lifetime = $41, claim_template = $42, session_lifetime = $43, session_timeout = $44, deny_hybrid_passkeys = $45, require_pkce_s256 = $46, response_types = $47, require_signed_request_object = $48, backchannel_logout_uri = $49, token_endpoint_auth_method = $50
+WHERE id = $51"#,
                 self.name,
                 self.enabled,
                 self.confidential,
@@ -565,6 +928,37 @@ WHERE id = $20"#,
                 self.force_mfa,
                 self.client_uri,
                 self.contacts,
+                self.auth_response_alg,
+                self.jwks,
+                self.request_uris,
+                self.frontchannel_logout_uri,
+                self.tls_client_auth,
+                self.tls_client_auth_subject_dn,
+                self.allowed_resources,
+                self.jwks_uri,
+                self.subject_type,
+                self.sector_identifier_uri,
+                self.opaque_access_tokens,
+                self.id_token_encrypted_response_alg,
+                self.id_token_encrypted_response_enc,
+                self.userinfo_encrypted_response_alg,
+                self.userinfo_encrypted_response_enc,
+                self.userinfo_signed_response_alg,
+                self.refresh_token_rotation,
+                self.refresh_token_grace_time,
+                self.refresh_token_reuse_detection,
+                self.consent_required,
+                self.id_token_lifetime,
+                self.refresh_token_lifetime,
+                self.claim_template,
+                self.session_lifetime,
+                self.session_timeout,
+                self.deny_hybrid_passkeys,
+                self.require_pkce_s256,
+                self.response_types,
+                self.require_signed_request_object,
+                self.backchannel_logout_uri,
+                self.token_endpoint_auth_method,
                 self.id,
             )
             .execute(DB::conn())
@@ -604,7 +998,7 @@ WHERE id = $20"#,
         new_client.default_scopes = current.default_scopes;
 
         client_dyn.token_endpoint_auth_method = token_endpoint_auth_method;
-        client_dyn.last_used = Some(Utc::now().timestamp());
+        client_dyn.last_used = Some(clock::now().timestamp());
 
         if *DYN_CLIENT_SECRET_AUTO_ROTATE {
             let (_secret_plain, registration_token) = Client::generate_new_secret()?;
@@ -727,7 +1121,51 @@ impl Client {
         Ok((rnd, enc))
     }
 
+    /// Applies the `claim_template` on the given token claims. Protected claims are never
+    /// modified, even if the template has been changed directly inside the database.
     #[inline(always)]
+    pub fn claims_with_template<T: Serialize>(
+        &self,
+        claims: T,
+    ) -> Result<serde_json::Map<String, serde_json::Value>, ErrorResponse> {
+        let mut map = match serde_json::to_value(claims)? {
+            serde_json::Value::Object(map) => map,
+            _ => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    "Token claims must be a JSON object",
+                ))
+            }
+        };
+
+        let tpl = match &self.claim_template {
+            None => return Ok(map),
+            Some(json) => serde_json::from_str::<ClaimTemplate>(json)?,
+        };
+        let is_protected = |name: &str| CLAIMS_PROTECTED.contains(&name);
+
+        for name in &tpl.drop {
+            if !is_protected(name) {
+                map.remove(name);
+            }
+        }
+        for (from, to) in tpl.rename {
+            if is_protected(&from) || is_protected(&to) {
+                continue;
+            }
+            if let Some(value) = map.remove(&from) {
+                map.insert(to, value);
+            }
+        }
+        for (name, value) in tpl.static_claims {
+            if !is_protected(&name) {
+                map.insert(name, value);
+            }
+        }
+
+        Ok(map)
+    }
+
     pub fn get_access_token_alg(&self) -> Result<JwkKeyPairAlg, ErrorResponse> {
         JwkKeyPairAlg::from_str(self.access_token_alg.as_str())
     }
@@ -767,6 +1205,63 @@ impl Client {
         }
     }
 
+    /// Returns the registered public keys for signed request objects.
+    pub fn get_jwks(&self) -> Result<Option<JWKS>, ErrorResponse> {
+        match &self.jwks {
+            None => Ok(None),
+            Some(jwks) => Ok(Some(Self::parse_jwks(jwks)?)),
+        }
+    }
+
+    /// Parses a JSON Web Key Set and validates each key against Rauthy's supported values.
+    /// Encryption keys are skipped and only handled by the `JweEncrypter`.
+    pub fn parse_jwks(jwks: &str) -> Result<JWKS, ErrorResponse> {
+        let invalid = |err: serde_json::Error| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Invalid JWKS: {}", err),
+            )
+        };
+        let mut value = serde_json::from_str::<serde_json::Value>(jwks).map_err(invalid)?;
+        let mut has_enc_keys = false;
+        if let Some(keys) = value.get_mut("keys").and_then(|k| k.as_array_mut()) {
+            let len = keys.len();
+            keys.retain(|key| !JweEncrypter::is_enc_key(key));
+            has_enc_keys = keys.len() < len;
+        }
+        let jwks = serde_json::from_value::<JWKS>(value).map_err(invalid)?;
+        if jwks.keys.is_empty() && !has_enc_keys {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The JWKS does not contain any keys",
+            ));
+        }
+        for key in &jwks.keys {
+            key.validate_self()?;
+        }
+        Ok(jwks)
+    }
+
+    pub fn get_request_uris(&self) -> Option<Vec<String>> {
+        self.request_uris
+            .as_ref()
+            .map(|uris| uris.split(',').map(|i| i.trim().to_string()).collect())
+    }
+
+    pub fn get_response_types(&self) -> Vec<&str> {
+        self.response_types
+            .split(',')
+            .map(|typ| typ.trim())
+            .filter(|typ| !typ.is_empty())
+            .collect()
+    }
+
+    pub fn get_allowed_resources(&self) -> Option<Vec<String>> {
+        self.allowed_resources
+            .as_ref()
+            .map(|res| res.split(',').map(|i| i.trim().to_string()).collect())
+    }
+
     /// Decrypts the client secret (if it exists) and then returns it as clear text.
     pub fn get_secret_cleartext(&self) -> Result<Option<String>, ErrorResponse> {
         if let Some(secret) = self.secret.as_ref() {
@@ -838,6 +1333,19 @@ impl Client {
         Url::from_str(&self.id).is_ok()
     }
 
+    /// Returns the `sub` this client will receive for the given user. Clients with pairwise
+    /// subjects receive a value unique to their sector, all others the user id.
+    ///
+    /// Fails for pairwise clients without a valid sector, because the user id must never be
+    /// leaked to them instead.
+    pub fn subject_for(&self, user_id: &str) -> Result<String, ErrorResponse> {
+        if self.subject_type.as_deref() == Some("pairwise") {
+            let sector = PairwiseSubject::sector(self)?;
+            return Ok(PairwiseSubject::calculate(&sector, user_id));
+        }
+        Ok(user_id.to_string())
+    }
+
     /// Sanitizes the current scopes and deletes everything, which does not exist in the `scopes`
     /// table in the database
     pub async fn sanitize_scopes(scps: Vec<String>) -> Result<String, ErrorResponse> {
@@ -902,8 +1410,10 @@ impl Client {
     /// The "rauthy" client is the exception for this check to makes logging into the account
     /// possible without MFA. The force MFA for the Rauthy admin UI is done in
     /// Principal::validate_admin_session() depending on the `ADMIN_FORCE_MFA` config variable.
-    pub fn validate_mfa(&self, user: &User) -> Result<(), ErrorResponse> {
-        if &self.id != "rauthy" && self.force_mfa && !user.has_webauthn_enabled() {
+    ///
+    /// `has_otp` must be set, if the user has TOTP, Yubico OTP or SMS OTP enabled.
+    pub fn validate_mfa(&self, user: &User, has_otp: bool) -> Result<(), ErrorResponse> {
+        if &self.id != "rauthy" && self.force_mfa && !user.has_webauthn_enabled() && !has_otp {
             trace!("MFA required for this client but the user has none");
             Err(ErrorResponse::new(
                 ErrorResponseType::MfaRequired,
@@ -914,6 +1424,45 @@ impl Client {
         }
     }
 
+    /// Validates the session against the `session_lifetime` and `session_timeout` of this client.
+    /// The global `SESSION_LIFETIME` and `SESSION_TIMEOUT` are validated for each request anyway,
+    /// which means the client values can only ever be stricter.
+    pub fn validate_session(&self, session: &Session, now: i64) -> Result<(), ErrorResponse> {
+        if let Some(timeout) = self.session_timeout {
+            if session.last_seen < now - timeout as i64 {
+                trace!("Session idle timeout for client {} exceeded", self.id);
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::SessionTimeout,
+                    "The session has timed out for this client",
+                ));
+            }
+        }
+
+        if let Some(lifetime) = self.session_lifetime {
+            if session.created() < now - lifetime as i64 {
+                trace!("Session lifetime for client {} exceeded", self.id);
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::SessionTimeout,
+                    "The session has expired for this client",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rejects a cross-device passkey login via hybrid transport, if this client denies them.
+    pub fn validate_passkey_hybrid(&self, hybrid: bool) -> Result<(), ErrorResponse> {
+        if hybrid && self.deny_hybrid_passkeys {
+            trace!("Cross-device passkey login denied for client {}", self.id);
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "Passkey logins from another device are not allowed for this client",
+            ));
+        }
+        Ok(())
+    }
+
     // Validates the `Origin` HTTP Header from an incoming request and compares it to the
     // `allowed_origins`. If the Origin is an external one and allowed by the config, it returns
     // the correct `ACCESS_CONTROL_ALLOW_ORIGIN` header which can then be inserted into the
@@ -995,6 +1544,14 @@ impl Client {
         code_challenge: &Option<String>,
         code_challenge_method: &Option<String>,
     ) -> Result<(), ErrorResponse> {
+        if self.require_pkce_s256 && code_challenge_method.as_deref() != Some("S256") {
+            trace!("'S256' PKCE is required");
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "PKCE with code_challenge_method 'S256' is required for this client",
+            ));
+        }
+
         if self.challenge.is_some() {
             if code_challenge.is_none() {
                 trace!("'code_challenge' is missing");
@@ -1037,6 +1594,12 @@ impl Client {
         &self,
         code_challenge_method: &str,
     ) -> Result<(), ErrorResponse> {
+        if self.require_pkce_s256 && code_challenge_method != "S256" {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "PKCE with code_challenge_method 'S256' is required for this client",
+            ));
+        }
         if self.challenge.is_none() {
             return Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
@@ -1061,6 +1624,30 @@ impl Client {
         Ok(())
     }
 
+    /// Validates a requested `resource` (RFC 8707) against the clients allowed resources.
+    pub fn validate_resource(&self, resource: Option<&str>) -> Result<(), ErrorResponse> {
+        let Some(resource) = resource else {
+            return Ok(());
+        };
+
+        let is_allowed = self
+            .get_allowed_resources()
+            .unwrap_or_default()
+            .iter()
+            .any(|r| r == resource);
+        if !is_allowed {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "invalid_target: '{}' is not allowed for this client",
+                    resource
+                ),
+            )
+            .with_code(ErrorCode::InvalidTarget));
+        }
+        Ok(())
+    }
+
     pub fn validate_flow(&self, flow: &str) -> Result<(), ErrorResponse> {
         if flow.is_empty() || !self.flows_enabled.contains(flow) {
             return Err(ErrorResponse::new(
@@ -1071,6 +1658,50 @@ impl Client {
         Ok(())
     }
 
+    /// Validates the `response_type` of an authorization request against the supported types
+    /// and the ones allowed for this client. A `code` is only allowed for clients with the
+    /// `authorization_code` flow enabled.
+    pub fn validate_response_type(&self, response_type: Option<&str>) -> Result<(), ErrorResponse> {
+        let Some(typ) = response_type else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "'response_type' is missing",
+            ));
+        };
+
+        if !RESPONSE_TYPES_SUPPORTED.contains(&typ) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("response_type '{}' is not supported", typ),
+            )
+            .with_code(ErrorCode::UnsupportedResponseType));
+        }
+
+        let is_allowed = self.get_response_types().contains(&typ)
+            && (typ != "code" || self.flows_enabled.contains("authorization_code"));
+        if !is_allowed {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("response_type '{}' is not allowed for this client", typ),
+            )
+            .with_code(ErrorCode::UnauthorizedClient));
+        }
+        Ok(())
+    }
+
+    /// Validates that the params of an authorization request have been passed inside a signed
+    /// request object, if this client requires it.
+    pub fn validate_request_object(&self, has_request_object: bool) -> Result<(), ErrorResponse> {
+        if self.require_signed_request_object && !has_request_object {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "This client only accepts authorization requests inside a signed request object",
+            )
+            .with_code(ErrorCode::InvalidRequestObject));
+        }
+        Ok(())
+    }
+
     pub fn validate_secret(&self, secret: &str, req: &HttpRequest) -> Result<(), ErrorResponse> {
         if !self.confidential {
             error!("Cannot validate 'client_secret' for public client");
@@ -1080,6 +1711,17 @@ impl Client {
             ));
         }
 
+        let is_basic = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("Basic "));
+        self.validate_auth_method(if is_basic {
+            "client_secret_basic"
+        } else {
+            "client_secret_post"
+        })?;
+
         let secret_enc = self.secret.as_ref().ok_or_else(|| {
             ErrorResponse::new(
                 ErrorResponseType::Internal,
@@ -1103,6 +1745,69 @@ impl Client {
         }
         Ok(())
     }
+
+    /// Validates that the client authenticates with its registered `token_endpoint_auth_method`.
+    /// Without one, only the `client_secret` via `client_secret_basic` or `client_secret_post`
+    /// is accepted, which means client assertions must be enabled explicitly.
+    pub fn validate_auth_method(&self, method: &str) -> Result<(), ErrorResponse> {
+        let allowed = match self.token_endpoint_auth_method.as_deref() {
+            Some(registered) => registered == method,
+            None => method == "client_secret_basic" || method == "client_secret_post",
+        };
+        if !allowed {
+            warn!(
+                "Client '{}' tried to authenticate with '{}'",
+                self.id, method
+            );
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                format!("'{}' is not allowed for this client", method),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validates the authentication of a confidential client. With mTLS client authentication
+    /// enabled, the client certificate replaces the `client_secret` and will be returned, so
+    /// tokens can be bound to it. A `client_assertion` (`private_key_jwt` / `client_secret_jwt`)
+    /// replaces the `client_secret` as well.
+    pub async fn validate_client_auth(
+        &self,
+        issuer: &str,
+        secret: Option<String>,
+        assertion: Option<&str>,
+        req: &HttpRequest,
+    ) -> Result<Option<ClientCert>, ErrorResponse> {
+        if self.tls_client_auth.is_some() {
+            let cert = ClientCert::opt_from_req(req)?.ok_or_else(|| {
+                warn!("Client certificate is missing for client '{}'", self.id);
+                ErrorResponse::new(
+                    ErrorResponseType::Unauthorized,
+                    "The client certificate is missing",
+                )
+            })?;
+            cert.validate_for_client(self)?;
+            return Ok(Some(cert));
+        }
+
+        if let Some(assertion) = assertion {
+            if !self.confidential {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "Client assertions are only possible for confidential clients",
+                ));
+            }
+            ClientAssertion::validate(issuer, self, assertion).await?;
+            return Ok(None);
+        }
+
+        let secret = secret.ok_or_else(|| {
+            warn!("'client_secret' is missing");
+            ErrorResponse::new(ErrorResponseType::BadRequest, "'client_secret' is missing")
+        })?;
+        self.validate_secret(&secret, req)?;
+        Ok(None)
+    }
 }
 
 impl Client {
@@ -1168,6 +1873,52 @@ impl Client {
 
         Ok(slf)
     }
+
+    /// Automatic registration for OpenID Federation 1.0. The client is built from the
+    /// `openid_relying_party` metadata of the entity after its trust chain has been resolved.
+    async fn federated_from_url(entity_id: &str) -> Result<Self, ErrorResponse> {
+        let mut metadata = EntityStatement::resolve_trust_chain(entity_id)
+            .await?
+            .rp_metadata()
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("{} has no 'openid_relying_party' metadata", entity_id),
+                )
+            })?;
+        metadata["client_id"] = serde_json::Value::from(entity_id);
+
+        let req =
+            serde_json::from_value::<EphemeralClientRequest>(metadata.clone()).map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Invalid relying party metadata from {}: {}", entity_id, err),
+                )
+            })?;
+        req.validate()?;
+        let mut slf = Self::from(req);
+
+        // federated clients can only authenticate with their own keys
+        if metadata
+            .get("token_endpoint_auth_method")
+            .and_then(|m| m.as_str())
+            == Some("private_key_jwt")
+        {
+            if let Some(jwks) = metadata.get("jwks") {
+                let jwks = jwks.to_string();
+                Self::parse_jwks(&jwks)?;
+                slf.jwks = Some(jwks);
+            }
+            slf.jwks_uri = metadata
+                .get("jwks_uri")
+                .and_then(|uri| uri.as_str())
+                .map(String::from);
+            slf.confidential = slf.jwks.is_some() || slf.jwks_uri.is_some();
+            slf.token_endpoint_auth_method = Some("private_key_jwt".to_string());
+        }
+
+        Ok(slf)
+    }
 }
 
 impl From<Client> for ClientResponse {
@@ -1180,6 +1931,8 @@ impl From<Client> for ClientResponse {
         let default_scopes = client.get_default_scopes();
         let challenges = client.get_challenges();
         let contacts = client.get_contacts();
+        let request_uris = client.get_request_uris();
+        let allowed_resources = client.get_allowed_resources();
 
         let access_token_alg = JwkKeyPairAlg::from_str(&client.access_token_alg)
             .expect("internal JwkKeyPairAlg conversion to always succeed")
@@ -1187,6 +1940,22 @@ impl From<Client> for ClientResponse {
         let id_token_alg = JwkKeyPairAlg::from_str(&client.id_token_alg)
             .expect("internal JwkKeyPairAlg conversion to always succeed")
             .into();
+        let auth_response_alg = client.auth_response_alg.as_deref().map(|alg| {
+            JwkKeyPairAlg::from_str(alg)
+                .expect("internal JwkKeyPairAlg conversion to always succeed")
+                .into()
+        });
+        let userinfo_signed_response_alg =
+            client.userinfo_signed_response_alg.as_deref().map(|alg| {
+                JwkKeyPairAlg::from_str(alg)
+                    .expect("internal JwkKeyPairAlg conversion to always succeed")
+                    .into()
+            });
+        let response_types = client
+            .get_response_types()
+            .into_iter()
+            .map(String::from)
+            .collect();
 
         Self {
             id: client.id,
@@ -1207,6 +1976,39 @@ impl From<Client> for ClientResponse {
             force_mfa: client.force_mfa,
             client_uri: client.client_uri,
             contacts,
+            auth_response_alg,
+            jwks: client.jwks,
+            request_uris,
+            frontchannel_logout_uri: client.frontchannel_logout_uri,
+            tls_client_auth: client.tls_client_auth,
+            tls_client_auth_subject_dn: client.tls_client_auth_subject_dn,
+            allowed_resources,
+            jwks_uri: client.jwks_uri,
+            subject_type: client.subject_type,
+            sector_identifier_uri: client.sector_identifier_uri,
+            opaque_access_tokens: client.opaque_access_tokens,
+            id_token_encrypted_response_alg: client.id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc: client.id_token_encrypted_response_enc,
+            userinfo_encrypted_response_alg: client.userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc: client.userinfo_encrypted_response_enc,
+            userinfo_signed_response_alg,
+            refresh_token_rotation: client.refresh_token_rotation,
+            refresh_token_grace_time: client.refresh_token_grace_time,
+            refresh_token_reuse_detection: client.refresh_token_reuse_detection,
+            consent_required: client.consent_required,
+            id_token_lifetime: client.id_token_lifetime,
+            refresh_token_lifetime: client.refresh_token_lifetime,
+            claim_template: client
+                .claim_template
+                .and_then(|json| serde_json::from_str(&json).ok()),
+            session_lifetime: client.session_lifetime,
+            session_timeout: client.session_timeout,
+            deny_hybrid_passkeys: client.deny_hybrid_passkeys,
+            require_pkce_s256: client.require_pkce_s256,
+            response_types,
+            require_signed_request_object: client.require_signed_request_object,
+            backchannel_logout_uri: client.backchannel_logout_uri,
+            token_endpoint_auth_method: client.token_endpoint_auth_method,
         }
     }
 }
@@ -1242,6 +2044,37 @@ impl From<EphemeralClientRequest> for Client {
             force_mfa: *EPHEMERAL_CLIENTS_FORCE_MFA,
             client_uri: value.client_uri,
             contacts: value.contacts.map(|c| c.join(",")),
+            auth_response_alg: None,
+            jwks: None,
+            request_uris: None,
+            frontchannel_logout_uri: None,
+            tls_client_auth: None,
+            tls_client_auth_subject_dn: None,
+            allowed_resources: None,
+            jwks_uri: None,
+            subject_type: None,
+            sector_identifier_uri: None,
+            opaque_access_tokens: false,
+            id_token_encrypted_response_alg: None,
+            id_token_encrypted_response_enc: None,
+            userinfo_encrypted_response_alg: None,
+            userinfo_encrypted_response_enc: None,
+            userinfo_signed_response_alg: None,
+            refresh_token_rotation: true,
+            refresh_token_grace_time: None,
+            refresh_token_reuse_detection: false,
+            consent_required: false,
+            id_token_lifetime: None,
+            refresh_token_lifetime: None,
+            claim_template: None,
+            session_lifetime: None,
+            session_timeout: None,
+            deny_hybrid_passkeys: false,
+            require_pkce_s256: false,
+            response_types: "code".to_string(),
+            require_signed_request_object: false,
+            backchannel_logout_uri: None,
+            token_endpoint_auth_method: None,
         }
     }
 }
@@ -1279,6 +2112,37 @@ impl Default for Client {
             force_mfa: false,
             client_uri: None,
             contacts: None,
+            auth_response_alg: None,
+            jwks: None,
+            request_uris: None,
+            frontchannel_logout_uri: None,
+            tls_client_auth: None,
+            tls_client_auth_subject_dn: None,
+            allowed_resources: None,
+            jwks_uri: None,
+            subject_type: None,
+            sector_identifier_uri: None,
+            opaque_access_tokens: false,
+            id_token_encrypted_response_alg: None,
+            id_token_encrypted_response_enc: None,
+            userinfo_encrypted_response_alg: None,
+            userinfo_encrypted_response_enc: None,
+            userinfo_signed_response_alg: None,
+            refresh_token_rotation: true,
+            refresh_token_grace_time: None,
+            refresh_token_reuse_detection: false,
+            consent_required: false,
+            id_token_lifetime: None,
+            refresh_token_lifetime: None,
+            claim_template: None,
+            session_lifetime: None,
+            session_timeout: None,
+            deny_hybrid_passkeys: false,
+            require_pkce_s256: false,
+            response_types: "code".to_string(),
+            require_signed_request_object: false,
+            backchannel_logout_uri: None,
+            token_endpoint_auth_method: None,
         }
     }
 }
@@ -1343,6 +2207,7 @@ impl Client {
             force_mfa: false,
             client_uri: req.client_uri,
             contacts: req.contacts.map(|c| c.join(",")),
+            userinfo_signed_response_alg: req.userinfo_signed_response_alg.map(|a| a.to_string()),
             ..Default::default()
         })
     }
@@ -1383,6 +2248,7 @@ impl Client {
             registration_client_uri,
             grant_types,
             id_token_signed_response_alg: self.id_token_alg,
+            userinfo_signed_response_alg: self.userinfo_signed_response_alg,
             token_endpoint_auth_method: client_dyn.token_endpoint_auth_method,
             token_endpoint_auth_signing_alg: self.access_token_alg,
         })
@@ -1453,7 +2319,7 @@ mod tests {
     use actix_web::test::TestRequest;
     use actix_web::{App, HttpResponse, HttpServer};
     use pretty_assertions::assert_eq;
-    use rauthy_common::constants::APPLICATION_JSON;
+    use rauthy_common::constants::{APPLICATION_JSON, SESSION_LIFETIME};
     use validator::Validate;
 
     use super::*;
@@ -1483,6 +2349,37 @@ mod tests {
             force_mfa: false,
             client_uri: Some("http://localhost:1337".to_string()),
             contacts: Some("batman@localhost.de,@alfred:matrix.org".to_string()),
+            auth_response_alg: None,
+            jwks: None,
+            request_uris: None,
+            frontchannel_logout_uri: None,
+            tls_client_auth: None,
+            tls_client_auth_subject_dn: None,
+            allowed_resources: None,
+            jwks_uri: None,
+            subject_type: None,
+            sector_identifier_uri: None,
+            opaque_access_tokens: false,
+            id_token_encrypted_response_alg: None,
+            id_token_encrypted_response_enc: None,
+            userinfo_encrypted_response_alg: None,
+            userinfo_encrypted_response_enc: None,
+            userinfo_signed_response_alg: None,
+            refresh_token_rotation: true,
+            refresh_token_grace_time: None,
+            refresh_token_reuse_detection: false,
+            consent_required: false,
+            id_token_lifetime: None,
+            refresh_token_lifetime: None,
+            claim_template: None,
+            session_lifetime: None,
+            session_timeout: None,
+            deny_hybrid_passkeys: false,
+            require_pkce_s256: false,
+            response_types: "code".to_string(),
+            require_signed_request_object: false,
+            backchannel_logout_uri: None,
+            token_endpoint_auth_method: None,
         };
 
         assert_eq!(client.get_access_token_alg().unwrap(), JwkKeyPairAlg::EdDSA);
@@ -1565,6 +2462,43 @@ mod tests {
         assert!(client.validate_flow("blabla").is_err());
         assert!(client.validate_flow("").is_err());
 
+        assert_eq!(client.validate_response_type(Some("code")), Ok(()));
+        assert_eq!(
+            client
+                .validate_response_type(Some("token"))
+                .unwrap_err()
+                .code,
+            ErrorCode::UnsupportedResponseType
+        );
+        assert!(client.validate_response_type(None).is_err());
+        client.response_types = String::default();
+        assert_eq!(
+            client
+                .validate_response_type(Some("code"))
+                .unwrap_err()
+                .code,
+            ErrorCode::UnauthorizedClient
+        );
+        client.response_types = "code".to_string();
+
+        client.require_pkce_s256 = true;
+        assert_eq!(client.validate_challenge_method("S256"), Ok(()));
+        assert!(client.validate_challenge_method("plain").is_err());
+        assert!(client
+            .validate_code_challenge(&Some("challenge".to_string()), &Some("plain".to_string()))
+            .is_err());
+        assert!(client.validate_code_challenge(&None, &None).is_err());
+        client.require_pkce_s256 = false;
+
+        assert_eq!(client.validate_request_object(false), Ok(()));
+        client.require_signed_request_object = true;
+        assert_eq!(client.validate_request_object(true), Ok(()));
+        assert_eq!(
+            client.validate_request_object(false).unwrap_err().code,
+            ErrorCode::InvalidRequestObject
+        );
+        client.require_signed_request_object = false;
+
         // contacts
         assert_eq!(
             client.get_contacts().expect("contacts to be set"),
@@ -1806,4 +2740,161 @@ mod tests {
         assert_eq!(&client.scopes, "openid");
         assert_eq!(&client.default_scopes, "openid");
     }
+
+    #[test]
+    fn test_validate_session() {
+        let session = Session::new(*SESSION_LIFETIME, None);
+        let now = session.last_seen;
+
+        let mut client = Client::default();
+        assert!(client.validate_session(&session, now + 3600).is_ok());
+
+        client.session_timeout = Some(600);
+        assert!(client.validate_session(&session, now + 300).is_ok());
+        assert!(client.validate_session(&session, now + 900).is_err());
+
+        client.session_timeout = None;
+        client.session_lifetime = Some(1800);
+        assert!(client.validate_session(&session, now + 900).is_ok());
+        assert!(client.validate_session(&session, now + 3600).is_err());
+    }
+
+    #[test]
+    fn test_subject_for() {
+        let mut client = Client {
+            redirect_uris: "https://app.example.com/callback,https://app.example.com/other"
+                .to_string(),
+            ..Default::default()
+        };
+        assert_eq!(client.subject_for("user_id").unwrap(), "user_id");
+
+        client.subject_type = Some("pairwise".to_string());
+        let sub = client.subject_for("user_id").unwrap();
+        assert_eq!(
+            sub,
+            PairwiseSubject::calculate("app.example.com", "user_id")
+        );
+
+        // the sector cannot be determined without a `sector_identifier_uri`
+        client.redirect_uris =
+            "https://app.example.com/callback,https://other.example.com/callback".to_string();
+        assert!(client.subject_for("user_id").is_err());
+
+        client.sector_identifier_uri = Some("https://sector.example.com/uris.json".to_string());
+        assert_eq!(
+            client.subject_for("user_id").unwrap(),
+            PairwiseSubject::calculate("sector.example.com", "user_id")
+        );
+    }
+
+    #[test]
+    fn test_validate_passkey_hybrid() {
+        let mut client = Client::default();
+        assert!(client.validate_passkey_hybrid(false).is_ok());
+        assert!(client.validate_passkey_hybrid(true).is_ok());
+
+        client.deny_hybrid_passkeys = true;
+        assert!(client.validate_passkey_hybrid(false).is_ok());
+        assert!(client.validate_passkey_hybrid(true).is_err());
+    }
+
+    #[test]
+    fn test_validate_resource() {
+        let mut client = Client::default();
+        assert!(client.validate_resource(None).is_ok());
+        assert!(client
+            .validate_resource(Some("https://api.example.com"))
+            .is_err());
+
+        client.allowed_resources =
+            Some("https://api.example.com, https://other.example.com".to_string());
+        assert!(client.validate_resource(None).is_ok());
+        assert!(client
+            .validate_resource(Some("https://api.example.com"))
+            .is_ok());
+        assert!(client
+            .validate_resource(Some("https://other.example.com"))
+            .is_ok());
+        // only exact matches are allowed
+        assert!(client
+            .validate_resource(Some("https://api.example.com/v1"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_auth_method() {
+        let mut client = Client::default();
+        assert!(client.validate_auth_method("client_secret_basic").is_ok());
+        assert!(client.validate_auth_method("client_secret_post").is_ok());
+        assert!(client.validate_auth_method("client_secret_jwt").is_err());
+        assert!(client.validate_auth_method("private_key_jwt").is_err());
+
+        client.token_endpoint_auth_method = Some("private_key_jwt".to_string());
+        assert!(client.validate_auth_method("private_key_jwt").is_ok());
+        assert!(client.validate_auth_method("client_secret_jwt").is_err());
+        assert!(client.validate_auth_method("client_secret_basic").is_err());
+        assert!(client.validate_auth_method("client_secret_post").is_err());
+
+        client.token_endpoint_auth_method = Some("client_secret_basic".to_string());
+        assert!(client.validate_auth_method("client_secret_basic").is_ok());
+        assert!(client.validate_auth_method("client_secret_post").is_err());
+    }
+
+    #[test]
+    fn test_claims_with_template() {
+        let claims = serde_json::json!({
+            "typ": "Bearer",
+            "azp": "client_1",
+            "email": "admin@localhost.de",
+            "groups": ["admin"],
+            "roles": ["rauthy_admin"],
+        });
+
+        let mut client = Client::default();
+        let map = client.claims_with_template(&claims).unwrap();
+        assert_eq!(serde_json::Value::Object(map), claims);
+
+        client.claim_template = Some(
+            serde_json::json!({
+                "static": {"tenant": "acme", "typ": "Id"},
+                "rename": {"email": "mail", "azp": "client"},
+                "drop": ["groups", "typ"],
+            })
+            .to_string(),
+        );
+        let map = client.claims_with_template(&claims).unwrap();
+        assert_eq!(map.get("tenant").unwrap(), "acme");
+        assert_eq!(map.get("mail").unwrap(), "admin@localhost.de");
+        assert!(map.get("email").is_none());
+        assert!(map.get("groups").is_none());
+        assert!(map.get("roles").is_some());
+        // protected claims are never modified
+        assert_eq!(map.get("typ").unwrap(), "Bearer");
+        assert_eq!(map.get("azp").unwrap(), "client_1");
+        assert!(map.get("client").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_is_authorize_uri() {
+        let issuer = "http://localhost:8080/auth/v1";
+
+        // none of these must reach the client lookup
+        assert!(
+            !Client::is_authorize_uri(issuer, "https://evil.test/oidc/authorize?client_id=a").await
+        );
+        assert!(
+            !Client::is_authorize_uri(
+                issuer,
+                "http://localhost:8080/auth/v1/oidc/authorize/../../evil?client_id=a"
+            )
+            .await
+        );
+        assert!(
+            !Client::is_authorize_uri(
+                issuer,
+                "http://localhost:8080/auth/v1/oidc/authorize?client_id=a&response_type=code"
+            )
+            .await
+        );
+    }
 }
@@ -0,0 +1,383 @@
+use crate::database::{Cache, DB};
+use crate::entity::groups::Group;
+use crate::entity::users::User;
+use hiqlite::{params, Param, Params};
+use rauthy_api_types::clients::{ClientAccessPolicyRequest, ClientAccessPolicyResponse};
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_CLIENT_ACCESS_POLICY};
+use rauthy_common::is_hiqlite;
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+use tracing::trace;
+
+/// Restricts which users may log in to a client. Without a policy, every active user is allowed.
+///
+/// The allowed users and groups live in their own tables with `ON DELETE CASCADE`, so they are
+/// cleaned up automatically when a user is purged or a group is deleted. A policy, which has
+/// lost all of its entries this way, denies every login until it is updated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientAccessPolicy {
+    pub client_id: String,
+    pub users: Vec<String>,
+    pub groups: Vec<String>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ClientAccessPolicyEntity {
+    pub client_id: String,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ClientAccessPolicyUser {
+    pub client_id: String,
+    pub user_id: String,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ClientAccessPolicyGroup {
+    pub client_id: String,
+    pub group_id: String,
+}
+
+impl From<ClientAccessPolicy> for ClientAccessPolicyResponse {
+    fn from(policy: ClientAccessPolicy) -> Self {
+        Self {
+            restricted: true,
+            users: policy.users,
+            groups: policy.groups,
+        }
+    }
+}
+
+// CRUD
+impl ClientAccessPolicy {
+    pub async fn delete(client_id: &str) -> Result<(), ErrorResponse> {
+        // the users and groups are removed via `ON DELETE CASCADE`
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM client_access_policies WHERE client_id = $1",
+                    params!(client_id),
+                )
+                .await?;
+        } else {
+            query!(
+                "DELETE FROM client_access_policies WHERE client_id = $1",
+                client_id
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Self::invalidate_cache().await
+    }
+
+    /// Must be called after users have been purged or groups have been deleted, because their
+    /// policy entries are removed via `ON DELETE CASCADE`.
+    pub async fn invalidate_cache() -> Result<(), ErrorResponse> {
+        DB::client()
+            .delete(Cache::App, IDX_CLIENT_ACCESS_POLICY)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn find(client_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        Ok(Self::find_all()
+            .await?
+            .into_iter()
+            .find(|p| p.client_id == client_id))
+    }
+
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let client = DB::client();
+        if let Some(slf) = client.get(Cache::App, IDX_CLIENT_ACCESS_POLICY).await? {
+            return Ok(slf);
+        }
+
+        let (policies, users, groups) = if is_hiqlite() {
+            let policies: Vec<ClientAccessPolicyEntity> = client
+                .query_as("SELECT * FROM client_access_policies", params!())
+                .await?;
+            let users: Vec<ClientAccessPolicyUser> = client
+                .query_as("SELECT * FROM client_access_policy_users", params!())
+                .await?;
+            let groups: Vec<ClientAccessPolicyGroup> = client
+                .query_as("SELECT * FROM client_access_policy_groups", params!())
+                .await?;
+            (policies, users, groups)
+        } else {
+            let policies = query_as!(
+                ClientAccessPolicyEntity,
+                "SELECT * FROM client_access_policies"
+            )
+            .fetch_all(DB::conn())
+            .await?;
+            let users = query_as!(
+                ClientAccessPolicyUser,
+                "SELECT * FROM client_access_policy_users"
+            )
+            .fetch_all(DB::conn())
+            .await?;
+            let groups = query_as!(
+                ClientAccessPolicyGroup,
+                "SELECT * FROM client_access_policy_groups"
+            )
+            .fetch_all(DB::conn())
+            .await?;
+            (policies, users, groups)
+        };
+
+        let res = policies
+            .into_iter()
+            .map(|p| Self {
+                users: users
+                    .iter()
+                    .filter(|u| u.client_id == p.client_id)
+                    .map(|u| u.user_id.clone())
+                    .collect(),
+                groups: groups
+                    .iter()
+                    .filter(|g| g.client_id == p.client_id)
+                    .map(|g| g.group_id.clone())
+                    .collect(),
+                client_id: p.client_id,
+            })
+            .collect::<Vec<_>>();
+
+        client
+            .put(Cache::App, IDX_CLIENT_ACCESS_POLICY, &res, CACHE_TTL_APP)
+            .await?;
+
+        Ok(res)
+    }
+
+    /// Replaces the policy for the given client. Empty `users` and `groups` remove the
+    /// restriction completely.
+    pub async fn upsert(
+        client_id: String,
+        req: ClientAccessPolicyRequest,
+    ) -> Result<Option<Self>, ErrorResponse> {
+        if client_id == "rauthy" {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The access to the 'rauthy' client cannot be restricted",
+            ));
+        }
+
+        if req.users.is_empty() && req.groups.is_empty() {
+            Self::delete(&client_id).await?;
+            return Ok(None);
+        }
+
+        for user_id in &req.users {
+            User::find(user_id.clone()).await?;
+        }
+        let all_groups = Group::find_all().await?;
+        for group_id in &req.groups {
+            if !all_groups.iter().any(|g| &g.id == group_id) {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::NotFound,
+                    format!("Group '{}' does not exist", group_id),
+                ));
+            }
+        }
+
+        let mut users = req.users;
+        users.sort();
+        users.dedup();
+        let mut groups = req.groups;
+        groups.sort();
+        groups.dedup();
+        let slf = Self {
+            client_id,
+            users,
+            groups,
+        };
+
+        if is_hiqlite() {
+            let mut txn: Vec<(&str, Params)> =
+                Vec::with_capacity(3 + slf.users.len() + slf.groups.len());
+            txn.push((
+                r#"
+INSERT INTO client_access_policies (client_id)
+VALUES ($1)
+ON CONFLICT(client_id) DO NOTHING"#,
+                params!(slf.client_id.clone()),
+            ));
+            txn.push((
+                "DELETE FROM client_access_policy_users WHERE client_id = $1",
+                params!(slf.client_id.clone()),
+            ));
+            txn.push((
+                "DELETE FROM client_access_policy_groups WHERE client_id = $1",
+                params!(slf.client_id.clone()),
+            ));
+            for user_id in &slf.users {
+                txn.push((
+                    r#"
+INSERT INTO client_access_policy_users (client_id, user_id)
+VALUES ($1, $2)"#,
+                    params!(slf.client_id.clone(), user_id.clone()),
+                ));
+            }
+            for group_id in &slf.groups {
+                txn.push((
+                    r#"
+INSERT INTO client_access_policy_groups (client_id, group_id)
+VALUES ($1, $2)"#,
+                    params!(slf.client_id.clone(), group_id.clone()),
+                ));
+            }
+
+            for res in DB::client().txn(txn).await? {
+                res?;
+            }
+        } else {
+            let mut txn = DB::txn().await?;
+
+            query!(
+                r#"
+INSERT INTO client_access_policies (client_id)
+VALUES ($1)
+ON CONFLICT(client_id) DO NOTHING"#,
+                slf.client_id,
+            )
+            .execute(&mut *txn)
+            .await?;
+            query!(
+                "DELETE FROM client_access_policy_users WHERE client_id = $1",
+                slf.client_id,
+            )
+            .execute(&mut *txn)
+            .await?;
+            query!(
+                "DELETE FROM client_access_policy_groups WHERE client_id = $1",
+                slf.client_id,
+            )
+            .execute(&mut *txn)
+            .await?;
+            for user_id in &slf.users {
+                query!(
+                    r#"
+INSERT INTO client_access_policy_users (client_id, user_id)
+VALUES ($1, $2)"#,
+                    slf.client_id,
+                    user_id,
+                )
+                .execute(&mut *txn)
+                .await?;
+            }
+            for group_id in &slf.groups {
+                query!(
+                    r#"
+INSERT INTO client_access_policy_groups (client_id, group_id)
+VALUES ($1, $2)"#,
+                    slf.client_id,
+                    group_id,
+                )
+                .execute(&mut *txn)
+                .await?;
+            }
+
+            txn.commit().await?;
+        }
+
+        Self::invalidate_cache().await?;
+
+        Ok(Some(slf))
+    }
+}
+
+impl ClientAccessPolicy {
+    /// Validates that the user may log in to the given client. The user is allowed, if no policy
+    /// exists, if it is listed directly, or if it is an effective member of any allowed group,
+    /// including groups inherited via nesting.
+    pub async fn validate_user(client_id: &str, user: &User) -> Result<(), ErrorResponse> {
+        let Some(slf) = Self::find(client_id).await? else {
+            return Ok(());
+        };
+
+        if slf.is_allowed(user).await? {
+            return Ok(());
+        }
+
+        trace!(
+            "User {} is not allowed to access client {}",
+            user.id,
+            client_id
+        );
+        Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "You are not allowed to access this client",
+        )
+        .with_code(ErrorCode::AccessDenied))
+    }
+
+    async fn is_allowed(&self, user: &User) -> Result<bool, ErrorResponse> {
+        if self.users.iter().any(|id| id == &user.id) {
+            return Ok(true);
+        }
+
+        if self.groups.is_empty() || user.groups.is_none() {
+            return Ok(false);
+        }
+
+        let (user_groups, _) = user.get_groups_roles_effective().await?;
+        let is_member = Group::find_all()
+            .await?
+            .iter()
+            .any(|g| self.groups.contains(&g.id) && user_groups.contains(&g.name));
+        Ok(is_member)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_is_allowed_users() {
+        let user = User {
+            id: "user1".to_string(),
+            groups: None,
+            ..Default::default()
+        };
+
+        let policy = ClientAccessPolicy {
+            client_id: "client1".to_string(),
+            users: vec!["user0".to_string(), "user1".to_string()],
+            groups: Vec::default(),
+        };
+        assert!(policy.is_allowed(&user).await.unwrap());
+
+        let policy = ClientAccessPolicy {
+            client_id: "client1".to_string(),
+            users: vec!["user0".to_string()],
+            groups: vec!["group0".to_string()],
+        };
+        assert!(!policy.is_allowed(&user).await.unwrap());
+
+        // a policy, which has lost all of its users and groups, must deny everyone
+        let policy = ClientAccessPolicy {
+            client_id: "client1".to_string(),
+            users: Vec::default(),
+            groups: Vec::default(),
+        };
+        assert!(!policy.is_allowed(&user).await.unwrap());
+    }
+
+    #[test]
+    fn test_response_restricted() {
+        let policy = ClientAccessPolicy {
+            client_id: "client1".to_string(),
+            users: Vec::default(),
+            groups: Vec::default(),
+        };
+        let resp = ClientAccessPolicyResponse::from(policy);
+        assert!(resp.restricted);
+        assert!(resp.users.is_empty());
+
+        let resp = ClientAccessPolicyResponse::default();
+        assert!(!resp.restricted);
+    }
+}
@@ -1,9 +1,9 @@
 use crate::app_state::AppState;
 use crate::database::{Cache, DB};
 use actix_web::web;
-use chrono::Utc;
 use cryptr::EncValue;
 use hiqlite::{params, Param};
+use rauthy_common::clock;
 use rauthy_common::constants::{CACHE_TTL_DYN_CLIENT, CACHE_TTL_IP_RATE_LIMIT};
 use rauthy_common::is_hiqlite;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -63,7 +63,7 @@ impl ClientDyn {
     }
 
     pub async fn update_used(id: &str) -> Result<(), ErrorResponse> {
-        let now = Utc::now().timestamp();
+        let now = clock::now().timestamp();
 
         if is_hiqlite() {
             DB::client()
@@ -106,7 +106,7 @@ impl ClientDyn {
                 ));
             }
             None => {
-                let now = Utc::now().timestamp();
+                let now = clock::now().timestamp();
                 client
                     .put(
                         Cache::IPRateLimit,
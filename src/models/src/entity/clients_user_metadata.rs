@@ -0,0 +1,238 @@
+use crate::database::{Cache, DB};
+use hiqlite::{params, Param};
+use rauthy_api_types::users::ClientUserMetadataResponse;
+use rauthy_common::clock;
+use rauthy_common::constants::{CACHE_TTL_USER, IDX_CLIENT_USER_METADATA};
+use rauthy_common::is_hiqlite;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{query, query_as, FromRow};
+use std::collections::HashMap;
+
+// The max size of the serialized metadata. It ends up in each token for the client, so it should
+// be kept small.
+const MAX_DATA_SIZE: usize = 4 * 1024;
+
+/// App specific metadata for a user, which only shows up in tokens and the userinfo for the
+/// `client_id` it belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ClientUserMetadata {
+    pub client_id: String,
+    pub user_id: String,
+    /// A JSON object
+    pub data: Vec<u8>,
+    pub updated: i64,
+}
+
+impl From<ClientUserMetadata> for ClientUserMetadataResponse {
+    fn from(value: ClientUserMetadata) -> Self {
+        Self {
+            data: value.data_map(),
+            client_id: value.client_id,
+            updated: value.updated,
+        }
+    }
+}
+
+// CRUD
+impl ClientUserMetadata {
+    pub async fn delete(client_id: &str, user_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM client_user_metadata WHERE client_id = $1 AND user_id = $2",
+                    params!(client_id, user_id),
+                )
+                .await?;
+        } else {
+            query!(
+                "DELETE FROM client_user_metadata WHERE client_id = $1 AND user_id = $2",
+                client_id,
+                user_id,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        DB::client()
+            .delete(Cache::User, Self::cache_idx(client_id, user_id))
+            .await?;
+
+        Ok(())
+    }
+
+    /// This is called during each token creation, which is why the result is cached.
+    pub async fn find(client_id: &str, user_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        let client = DB::client();
+        let idx = Self::cache_idx(client_id, user_id);
+        if let Some(slf) = client.get(Cache::User, &idx).await? {
+            return Ok(slf);
+        }
+
+        let slf = if is_hiqlite() {
+            client
+                .query_as(
+                    "SELECT * FROM client_user_metadata WHERE client_id = $1 AND user_id = $2",
+                    params!(client_id, user_id),
+                )
+                .await?
+                .into_iter()
+                .next()
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM client_user_metadata WHERE client_id = $1 AND user_id = $2",
+                client_id,
+                user_id,
+            )
+            .fetch_optional(DB::conn())
+            .await?
+        };
+
+        client.put(Cache::User, idx, &slf, CACHE_TTL_USER).await?;
+
+        Ok(slf)
+    }
+
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM client_user_metadata WHERE user_id = $1 ORDER BY client_id",
+                    params!(user_id),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM client_user_metadata WHERE user_id = $1 ORDER BY client_id",
+                user_id
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Replaces the metadata for the given client and user. An empty `data` removes it.
+    pub async fn upsert(
+        client_id: String,
+        user_id: String,
+        data: HashMap<String, Value>,
+    ) -> Result<Option<Self>, ErrorResponse> {
+        if data.is_empty() {
+            Self::delete(&client_id, &user_id).await?;
+            return Ok(None);
+        }
+
+        let slf = Self {
+            client_id,
+            user_id,
+            data: Self::serialize_data(&data)?,
+            updated: clock::now().timestamp(),
+        };
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO client_user_metadata (client_id, user_id, data, updated)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(client_id, user_id) DO UPDATE SET data = $3, updated = $4"#,
+                    params!(
+                        slf.client_id.clone(),
+                        slf.user_id.clone(),
+                        slf.data.clone(),
+                        slf.updated
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO client_user_metadata (client_id, user_id, data, updated)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(client_id, user_id) DO UPDATE
+SET data = $3, updated = $4"#,
+                slf.client_id,
+                slf.user_id,
+                slf.data,
+                slf.updated,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        DB::client()
+            .put(
+                Cache::User,
+                Self::cache_idx(&slf.client_id, &slf.user_id),
+                &Some(slf.clone()),
+                CACHE_TTL_USER,
+            )
+            .await?;
+
+        Ok(Some(slf))
+    }
+}
+
+impl ClientUserMetadata {
+    #[inline]
+    fn cache_idx(client_id: &str, user_id: &str) -> String {
+        format!("{}{}_{}", IDX_CLIENT_USER_METADATA, client_id, user_id)
+    }
+
+    /// Returns the value for the `client_metadata` claim, if any metadata exists.
+    pub async fn claim(
+        client_id: &str,
+        user_id: &str,
+    ) -> Result<Option<HashMap<String, Value>>, ErrorResponse> {
+        Ok(Self::find(client_id, user_id)
+            .await?
+            .map(|slf| slf.data_map()))
+    }
+
+    pub fn data_map(&self) -> HashMap<String, Value> {
+        serde_json::from_slice(&self.data).unwrap_or_default()
+    }
+
+    fn serialize_data(data: &HashMap<String, Value>) -> Result<Vec<u8>, ErrorResponse> {
+        let bytes = serde_json::to_vec(data)?;
+        if bytes.len() > MAX_DATA_SIZE {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "The client metadata must not exceed {} bytes",
+                    MAX_DATA_SIZE
+                ),
+            ));
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_serialize_data() {
+        let mut data = HashMap::new();
+        data.insert("app_user_id".to_string(), json!(1337));
+        data.insert("theme".to_string(), json!("dark"));
+        let bytes = ClientUserMetadata::serialize_data(&data).unwrap();
+        let slf = ClientUserMetadata {
+            client_id: "app".to_string(),
+            user_id: "user".to_string(),
+            data: bytes,
+            updated: 0,
+        };
+        assert_eq!(slf.data_map(), data);
+
+        data.insert("large".to_string(), json!("a".repeat(MAX_DATA_SIZE)));
+        assert!(ClientUserMetadata::serialize_data(&data).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::database::DB;
+use crate::entity::clients::Client;
+use hiqlite::{params, Param};
+use rauthy_api_types::users::UserConsentResponse;
+use rauthy_common::clock;
+use rauthy_common::is_hiqlite;
+use rauthy_error::ErrorResponse;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+/// The scopes a user has granted to a client with `consent_required` on the consent screen.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserConsent {
+    pub user_id: String,
+    pub client_id: String,
+    /// comma separated list of granted scopes
+    pub scopes: String,
+    pub created_at: i64,
+    pub last_updated: i64,
+}
+
+// CRUD
+impl UserConsent {
+    pub async fn delete(user_id: &str, client_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM user_consents WHERE user_id = $1 AND client_id = $2",
+                    params!(user_id, client_id),
+                )
+                .await?;
+        } else {
+            query!(
+                "DELETE FROM user_consents WHERE user_id = $1 AND client_id = $2",
+                user_id,
+                client_id,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_for_client(client_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM user_consents WHERE client_id = $1",
+                    params!(client_id),
+                )
+                .await?;
+        } else {
+            query!("DELETE FROM user_consents WHERE client_id = $1", client_id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find(user_id: &str, client_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        let slf = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM user_consents WHERE user_id = $1 AND client_id = $2",
+                    params!(user_id, client_id),
+                )
+                .await?
+                .into_iter()
+                .next()
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM user_consents WHERE user_id = $1 AND client_id = $2",
+                user_id,
+                client_id,
+            )
+            .fetch_optional(DB::conn())
+            .await?
+        };
+
+        Ok(slf)
+    }
+
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM user_consents WHERE user_id = $1 ORDER BY client_id",
+                    params!(user_id),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM user_consents WHERE user_id = $1 ORDER BY client_id",
+                user_id
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Adds the given scopes to the already granted ones for this user and client.
+    pub async fn grant(
+        user_id: String,
+        client_id: String,
+        scopes: Vec<String>,
+    ) -> Result<(), ErrorResponse> {
+        let now = clock::now().timestamp();
+        let slf = match Self::find(&user_id, &client_id).await? {
+            Some(mut slf) => {
+                let mut granted = slf.scopes_vec();
+                for scope in scopes {
+                    if !granted.contains(&scope) {
+                        granted.push(scope);
+                    }
+                }
+                slf.scopes = granted.join(",");
+                slf.last_updated = now;
+                slf
+            }
+            None => Self {
+                user_id,
+                client_id,
+                scopes: scopes.join(","),
+                created_at: now,
+                last_updated: now,
+            },
+        };
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_consents (user_id, client_id, scopes, created_at, last_updated)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT(user_id, client_id) DO UPDATE SET scopes = $3, last_updated = $5"#,
+                    params!(
+                        slf.user_id,
+                        slf.client_id,
+                        slf.scopes,
+                        slf.created_at,
+                        slf.last_updated
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO user_consents (user_id, client_id, scopes, created_at, last_updated)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT(user_id, client_id) DO UPDATE
+SET scopes = $3, last_updated = $5"#,
+                slf.user_id,
+                slf.client_id,
+                slf.scopes,
+                slf.created_at,
+                slf.last_updated,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UserConsent {
+    /// Returns all `requested` scopes, which have not been granted by the user so far.
+    pub async fn find_missing(
+        user_id: &str,
+        client_id: &str,
+        requested: &[String],
+    ) -> Result<Vec<String>, ErrorResponse> {
+        let granted = Self::find(user_id, client_id)
+            .await?
+            .map(|slf| slf.scopes_vec())
+            .unwrap_or_default();
+        Ok(Self::missing_scopes(&granted, requested))
+    }
+
+    fn missing_scopes(granted: &[String], requested: &[String]) -> Vec<String> {
+        let mut missing = Vec::with_capacity(requested.len());
+        for scope in requested {
+            if !granted.contains(scope) && !missing.contains(scope) {
+                missing.push(scope.clone());
+            }
+        }
+        missing
+    }
+
+    /// The client name is resolved from the cache, so users can recognize the application.
+    pub async fn into_response(self) -> UserConsentResponse {
+        let client_name = Client::find(self.client_id.clone())
+            .await
+            .ok()
+            .and_then(|client| client.name);
+
+        UserConsentResponse {
+            scopes: self.scopes_vec(),
+            client_id: self.client_id,
+            client_name,
+            created_at: self.created_at,
+            last_updated: self.last_updated,
+        }
+    }
+
+    pub fn scopes_vec(&self) -> Vec<String> {
+        self.scopes
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_scopes() {
+        let granted = vec!["openid".to_string(), "email".to_string()];
+
+        let requested = vec!["openid".to_string(), "email".to_string()];
+        assert!(UserConsent::missing_scopes(&granted, &requested).is_empty());
+
+        let requested = vec![
+            "openid".to_string(),
+            "profile".to_string(),
+            "groups".to_string(),
+            "profile".to_string(),
+        ];
+        assert_eq!(
+            UserConsent::missing_scopes(&granted, &requested),
+            vec!["profile".to_string(), "groups".to_string()]
+        );
+
+        assert_eq!(
+            UserConsent::missing_scopes(&[], &requested).len(),
+            3,
+            "nothing granted yet"
+        );
+    }
+}
@@ -20,25 +20,7 @@ impl TryFrom<&str> for ContinuationToken {
     type Error = ErrorResponse;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        if value.len() < 16 {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::BadRequest,
-                "Invalid continuation_token",
-            ));
-        }
-
-        let (ts, id) = value.split_at(10);
-        let ts = i64::from_str(ts).map_err(|err| {
-            ErrorResponse::new(
-                ErrorResponseType::BadRequest,
-                format!("timestamp str cannot be parsed into i64: {:?}", err),
-            )
-        })?;
-
-        Ok(Self {
-            id: id.to_string(),
-            ts,
-        })
+        Self::parse(value, 10)
     }
 }
 
@@ -47,6 +29,31 @@ impl ContinuationToken {
         Self { id, ts }
     }
 
+    /// Parses a token which has been built from a millisecond timestamp, like for `Event`s.
+    pub fn try_from_millis(value: &str) -> Result<Self, ErrorResponse> {
+        Self::parse(value, 13)
+    }
+
+    /// The token is the timestamp with a fixed amount of digits, directly followed by the id.
+    fn parse(value: &str, ts_len: usize) -> Result<Self, ErrorResponse> {
+        let err =
+            || ErrorResponse::new(ErrorResponseType::BadRequest, "Invalid continuation_token");
+
+        if !value.is_ascii() || value.len() <= ts_len {
+            return Err(err());
+        }
+        let (ts, id) = value.split_at(ts_len);
+        if !ts.bytes().all(|b| b.is_ascii_digit()) || !id.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(err());
+        }
+        let ts = i64::from_str(ts).map_err(|_| err())?;
+
+        Ok(Self {
+            id: id.to_string(),
+            ts,
+        })
+    }
+
     pub fn into_header_pair(self) -> (HeaderName, HeaderValue) {
         // these header values will always be valid
         let name = HeaderName::from_str("x-continuation-token").unwrap();
@@ -54,3 +61,36 @@ impl ContinuationToken {
         (name, value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_from_millis() {
+        let token = ContinuationToken::new("aB3dE5gH".to_string(), 1728000000123);
+        let parsed = ContinuationToken::try_from_millis(&token.to_string()).unwrap();
+        assert_eq!(parsed.ts, 1728000000123);
+        assert_eq!(parsed.id, "aB3dE5gH");
+
+        // missing id
+        assert!(ContinuationToken::try_from_millis("1728000000123").is_err());
+        // seconds instead of millis
+        assert!(ContinuationToken::try_from_millis("1728000000aB3dE5gH").is_err());
+        // signs and non-ascii input must not be accepted or panic
+        assert!(ContinuationToken::try_from_millis("+728000000123aB3dE5gH").is_err());
+        assert!(ContinuationToken::try_from_millis("172800000012ÄaB3dE5gH").is_err());
+        assert!(ContinuationToken::try_from_millis("1728000000123aB3d 5gH").is_err());
+    }
+
+    #[test]
+    fn test_try_from_secs() {
+        let token = ContinuationToken::new("aB3dE5gH".to_string(), 1728000000);
+        let parsed = ContinuationToken::try_from(token.to_string().as_str()).unwrap();
+        assert_eq!(parsed.ts, 1728000000);
+        assert_eq!(parsed.id, "aB3dE5gH");
+
+        assert!(ContinuationToken::try_from("1728000000").is_err());
+        assert!(ContinuationToken::try_from("-728000000aB3dE5gH").is_err());
+    }
+}
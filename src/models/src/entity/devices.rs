@@ -3,6 +3,7 @@ use crate::entity::refresh_tokens_devices::RefreshTokenDevice;
 use chrono::{DateTime, Utc};
 use hiqlite::{params, Param};
 use rauthy_api_types::users::DeviceResponse;
+use rauthy_common::clock;
 use rauthy_common::constants::{
     CACHE_TTL_DEVICE_CODE, DEVICE_GRANT_CODE_LIFETIME, DEVICE_GRANT_USER_CODE_LENGTH,
     DEVICE_KEY_LENGTH, PUB_URL_WITH_SCHEME,
@@ -98,7 +99,7 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
 
     /// Deletes all devices where access and refresh token expirations are in the past
     pub async fn delete_expired() -> Result<(), ErrorResponse> {
-        let exp = Utc::now()
+        let exp = clock::now()
             .sub(chrono::Duration::try_hours(1).unwrap())
             .timestamp();
 
@@ -213,6 +214,9 @@ pub struct DeviceAuthCode {
     pub device_code: String,
     /// Will be Some(user_id) once a user has been validated the auth request
     pub verified_by: Option<String>,
+    /// Set when the user declined the request. The next poll will get an `access_denied`.
+    #[serde(default)]
+    pub denied: bool,
     /// We need the additional `exp` here because a verification from a
     /// user will reset the lifetime, which means without the additional
     /// check here, it could be possible that a code lives longer than
@@ -235,7 +239,7 @@ impl DeviceAuthCode {
         client_id: String,
         client_secret: Option<String>,
     ) -> Result<Self, ErrorResponse> {
-        let now = Utc::now();
+        let now = clock::now();
         let exp = now.add(chrono::Duration::seconds(
             *DEVICE_GRANT_CODE_LIFETIME as i64,
         ));
@@ -243,6 +247,7 @@ impl DeviceAuthCode {
             client_id,
             device_code: get_rand(DEVICE_KEY_LENGTH as usize),
             verified_by: None,
+            denied: false,
             exp,
             last_poll: now,
             scopes,
@@ -272,7 +277,7 @@ impl DeviceAuthCode {
         match slf {
             None => Ok(None),
             Some(slf) => {
-                if slf.exp < Utc::now() {
+                if slf.exp < clock::now() {
                     slf.delete().await?;
                     Ok(None)
                 } else {
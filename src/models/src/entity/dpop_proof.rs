@@ -3,11 +3,12 @@ use crate::entity::jwk::{JWKSPublicKey, JwkKeyPairAlg};
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{http, HttpRequest};
 use chrono::{DateTime, Utc};
+use rauthy_common::clock;
 use rauthy_common::constants::{
     CACHE_TTL_DPOP_NONCE, DPOP_FORCE_NONCE, DPOP_NONCE_EXP, DPOP_TOKEN_ENDPOINT, RE_TOKEN_68,
     TOKEN_DPOP,
 };
-use rauthy_common::utils::{base64_url_no_pad_decode, get_rand};
+use rauthy_common::utils::{base64_url_no_pad_decode, get_rand, jwt_not_yet_valid};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 use std::ops::{Add, Sub};
@@ -23,7 +24,7 @@ pub struct DPoPNonce {
 impl DPoPNonce {
     /// Creates a new DPoP nonce, inserts it into the cache and returns its value.
     pub async fn new_value() -> Result<String, ErrorResponse> {
-        let exp = Utc::now().add(chrono::Duration::seconds(*DPOP_NONCE_EXP as i64));
+        let exp = clock::now().add(chrono::Duration::seconds(*DPOP_NONCE_EXP as i64));
         let slf = Self {
             exp,
             value: get_rand(32),
@@ -62,7 +63,7 @@ impl DPoPNonce {
         match slf {
             None => Self::new_value().await,
             Some(slf) => {
-                let now_minus_15 = Utc::now().sub(chrono::Duration::seconds(15));
+                let now_minus_15 = clock::now().sub(chrono::Duration::seconds(15));
                 if slf.exp < now_minus_15 {
                     Self::new_value().await
                 } else {
@@ -301,10 +302,11 @@ impl DPoPProof {
         // claim or a server managed timestamp via the nonce claim, is within an
         // acceptable window (see Section 11.1).
         //
-        // We will accept an 'iat' of 1 minute old and it must not be in the future
-        let now = Utc::now().timestamp();
+        // We will accept an 'iat' of 1 minute old and it must not be in the future, apart from
+        // the configured clock skew.
+        let now = clock::now().timestamp();
         let now_minus_1 = now - 60;
-        if self.claims.iat < now_minus_1 || self.claims.iat > now {
+        if self.claims.iat < now_minus_1 || jwt_not_yet_valid(self.claims.iat, now) {
             return Err("DPoP 'iat' claim is out of range".to_string());
         }
 
@@ -343,8 +345,8 @@ mod tests {
     use crate::entity::dpop_proof::{DPoPClaims, DPoPHeader, DPoPProof};
     use crate::entity::jwk::{JWKSPublicKey, JwkKeyPairAlg, JwkKeyPairType};
     use actix_web::http;
-    use chrono::Utc;
     use ed25519_compact::Noise;
+    use rauthy_common::clock;
     use rauthy_common::constants::DPOP_TOKEN_ENDPOINT;
     use rauthy_common::utils::{base64_url_encode, base64_url_no_pad_encode};
     use rsa::sha2::Sha256;
@@ -371,6 +373,7 @@ mod tests {
                 n: None,
                 e: None,
                 x: Some(base64_url_encode(kp.pk.as_slice())),
+                y: None,
             },
             kid: None,
         };
@@ -379,7 +382,7 @@ mod tests {
             jti: "-BwC3ESc6acc2lTc".to_string(),
             htm: http::Method::POST.to_string(),
             htu: DPOP_TOKEN_ENDPOINT.clone().to_string(),
-            iat: Utc::now().timestamp(),
+            iat: clock::now().timestamp(),
             nonce: None,
         };
 
@@ -424,6 +427,7 @@ mod tests {
                 n: Some(base64_url_encode(&n)),
                 e: Some(base64_url_encode(&e)),
                 x: None,
+                y: None,
             },
             kid: None,
         };
@@ -432,7 +436,7 @@ mod tests {
             jti: "-BwC3ESc6acc2lTc".to_string(),
             htm: http::Method::POST.to_string(),
             htu: DPOP_TOKEN_ENDPOINT.clone().to_string(),
-            iat: Utc::now().timestamp(),
+            iat: clock::now().timestamp(),
             nonce: None,
         };
 
@@ -0,0 +1,207 @@
+use crate::app_state::AppState;
+use crate::database::DB;
+use crate::email::send_email_otp;
+use crate::entity::users::User;
+use actix_web::web;
+use hiqlite::{params, Param};
+use rand::Rng;
+use rauthy_common::clock;
+use rauthy_common::constants::{
+    EmailOtpMode, MFA_EMAIL_OTP, MFA_EMAIL_OTP_LIFETIME, MFA_EMAIL_OTP_RESEND_SECS,
+};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::{base64_url_no_pad_encode, constant_time_eq};
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+use tracing::debug;
+
+/// The amount of wrong guesses, after which a code cannot be used anymore
+const EMAIL_OTP_MAX_ATTEMPTS: i64 = 5;
+
+/// One-time codes sent via E-Mail as a fallback 2nd factor for password accounts without a
+/// passkey or TOTP.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserEmailOtp {
+    pub user_id: String,
+    /// The user opt-in, which is only used with `MFA_EMAIL_OTP=opt_in`
+    pub enabled: bool,
+    pub code_hash: Option<String>,
+    pub expires: Option<i64>,
+    /// Failed attempts for the current code
+    pub attempts: i64,
+    pub last_sent: Option<i64>,
+}
+
+// CRUD
+impl UserEmailOtp {
+    pub async fn find(user_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        let slf = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM user_email_otp WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?
+                .into_iter()
+                .next()
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM user_email_otp WHERE user_id = $1",
+                user_id
+            )
+            .fetch_optional(DB::conn())
+            .await?
+        };
+
+        Ok(slf)
+    }
+
+    async fn save(&self) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_email_otp (user_id, enabled, code_hash, expires, attempts, last_sent)
+VALUES ($1, $2, $3, $4, $5, $6)
+ON CONFLICT(user_id) DO UPDATE
+SET enabled = $2, code_hash = $3, expires = $4, attempts = $5, last_sent = $6"#,
+                    params!(
+                        self.user_id.clone(),
+                        self.enabled,
+                        self.code_hash.clone(),
+                        self.expires,
+                        self.attempts,
+                        self.last_sent
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO user_email_otp (user_id, enabled, code_hash, expires, attempts, last_sent)
+VALUES ($1, $2, $3, $4, $5, $6)
+ON CONFLICT(user_id) DO UPDATE
+SET enabled = $2, code_hash = $3, expires = $4, attempts = $5, last_sent = $6"#,
+                self.user_id,
+                self.enabled,
+                self.code_hash,
+                self.expires,
+                self.attempts,
+                self.last_sent,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UserEmailOtp {
+    fn new(user_id: String) -> Self {
+        Self {
+            user_id,
+            enabled: false,
+            code_hash: None,
+            expires: None,
+            attempts: 0,
+            last_sent: None,
+        }
+    }
+
+    /// Returns `true`, if E-Mail OTP must be used for this user, depending on `MFA_EMAIL_OTP`.
+    /// The caller must check first, that the user has no other 2nd factor.
+    pub async fn is_enabled(user_id: &str) -> Result<bool, ErrorResponse> {
+        match *MFA_EMAIL_OTP {
+            EmailOtpMode::Disabled => Ok(false),
+            EmailOtpMode::All => Ok(true),
+            EmailOtpMode::OptIn => Ok(Self::find(user_id)
+                .await?
+                .map(|slf| slf.enabled)
+                .unwrap_or(false)),
+        }
+    }
+
+    pub async fn set_enabled(user_id: String, enabled: bool) -> Result<(), ErrorResponse> {
+        if *MFA_EMAIL_OTP != EmailOtpMode::OptIn {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "E-Mail OTP cannot be changed per user with the current configuration",
+            ));
+        }
+
+        let mut slf = match Self::find(&user_id).await? {
+            Some(slf) => slf,
+            None => Self::new(user_id),
+        };
+        slf.enabled = enabled;
+        slf.save().await
+    }
+
+    /// Sends a new code to the user. Within `MFA_EMAIL_OTP_RESEND_SECS`, no new E-Mail will be
+    /// sent and the last code stays valid.
+    pub async fn send_code(data: &web::Data<AppState>, user: &User) -> Result<(), ErrorResponse> {
+        let now = clock::now().timestamp();
+        let mut slf = match Self::find(&user.id).await? {
+            Some(slf) => slf,
+            None => Self::new(user.id.clone()),
+        };
+
+        let code_valid = slf.code_hash.is_some() && slf.expires.unwrap_or(0) > now;
+        if code_valid && slf.last_sent.unwrap_or(0) + *MFA_EMAIL_OTP_RESEND_SECS as i64 > now {
+            debug!("Skipping E-Mail OTP for user {} - rate limited", user.id);
+            return Ok(());
+        }
+
+        let code = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+        slf.code_hash = Some(Self::hash(&code));
+        slf.expires = Some(now + *MFA_EMAIL_OTP_LIFETIME as i64);
+        slf.attempts = 0;
+        slf.last_sent = Some(now);
+        slf.save().await?;
+
+        send_email_otp(data, user, &code).await;
+
+        Ok(())
+    }
+
+    /// Validates the code and invalidates it on success. Each code can only be guessed
+    /// `EMAIL_OTP_MAX_ATTEMPTS` times.
+    pub async fn verify(user_id: &str, code: &str) -> Result<(), ErrorResponse> {
+        let Some(mut slf) = Self::find(user_id).await? else {
+            return Err(Self::err_invalid());
+        };
+        let Some(code_hash) = slf.code_hash.as_deref() else {
+            return Err(Self::err_invalid());
+        };
+        if slf.expires.unwrap_or(0) <= clock::now().timestamp()
+            || slf.attempts >= EMAIL_OTP_MAX_ATTEMPTS
+        {
+            return Err(Self::err_invalid());
+        }
+
+        if constant_time_eq(code_hash.as_bytes(), Self::hash(code.trim()).as_bytes()) {
+            slf.code_hash = None;
+            slf.expires = None;
+            slf.attempts = 0;
+            slf.save().await
+        } else {
+            slf.attempts += 1;
+            slf.save().await?;
+            Err(Self::err_invalid())
+        }
+    }
+
+    fn hash(code: &str) -> String {
+        let hash = digest::digest(&digest::SHA256, code.as_bytes());
+        base64_url_no_pad_encode(hash.as_ref())
+    }
+
+    fn err_invalid() -> ErrorResponse {
+        ErrorResponse::new(ErrorResponseType::Unauthorized, "Invalid E-Mail code")
+            .with_code(ErrorCode::InvalidCredentials)
+    }
+}
@@ -34,7 +34,7 @@ pub struct FedCMAccount {
 }
 
 impl FedCMAccount {
-    pub fn build(user: User) -> Self {
+    pub fn build(user: User, picture: Option<String>) -> Self {
         let name = user.email_recipient_name();
         let login_hint = format!("login_hint={}", user.email);
 
@@ -43,8 +43,7 @@ impl FedCMAccount {
             name,
             email: user.email,
             given_name: Some(user.given_name),
-            // Rauthy does not store user pictures
-            picture: None,
+            picture,
             // TODO how should we decide which clients to return here? How to make this dynamic?
             // simply all of them? Or introduce a new flow to allow fedCm and filter?
             // approved_clients: clients,
@@ -0,0 +1,367 @@
+use crate::app_state::AppState;
+use crate::entity::jwk::{JWKSPublicKey, JwkKeyPair, JwkKeyPairAlg, JWKS};
+use crate::entity::well_known::WellKnown;
+use crate::jwt_claims;
+use actix_web::web;
+use jwt_simple::prelude::coarsetime;
+use jwt_simple::token::Token;
+use rauthy_api_types::oidc::JWKSCerts;
+use rauthy_common::clock;
+use rauthy_common::constants::{
+    FEDERATION_AUTHORITY_HINTS, FEDERATION_ENTITY_STATEMENT_LIFETIME, FEDERATION_ORGANIZATION_NAME,
+    FEDERATION_TRUST_ANCHORS, RAUTHY_VERSION,
+};
+use rauthy_common::utils::base64_url_no_pad_decode;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use reqwest::header::ACCEPT;
+use reqwest::{tls, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{debug, error, info};
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+pub const ENTITY_STATEMENT_TYP: &str = "entity-statement+jwt";
+pub const CONTENT_TYPE_ENTITY_STATEMENT: &str = "application/entity-statement+jwt";
+
+// The max amount of superiors between a leaf entity and a trust anchor
+const MAX_CHAIN_LENGTH: usize = 5;
+// The max size of a fetched entity statement
+const MAX_STATEMENT_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EntityConfigurationClaims {
+    jwks: Value,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authority_hints: Vec<String>,
+    metadata: Value,
+}
+
+/// An OpenID Federation 1.0 entity statement. This is either the self-signed entity
+/// configuration of an entity, or a subordinate statement issued by one of its superiors.
+#[derive(Debug, Deserialize)]
+pub struct EntityStatement {
+    pub iss: String,
+    pub sub: String,
+    pub exp: i64,
+    pub jwks: Value,
+    #[serde(default)]
+    pub authority_hints: Vec<String>,
+    pub metadata: Option<Value>,
+}
+
+impl EntityStatement {
+    /// Builds and signs the entity configuration of this Rauthy instance, which is published
+    /// at `/.well-known/openid-federation`.
+    pub async fn own_configuration(data: &web::Data<AppState>) -> Result<String, ErrorResponse> {
+        let mut openid_provider = serde_json::from_str::<Value>(&WellKnown::json(data).await?)?;
+        openid_provider["client_registration_types_supported"] = json!(["automatic"]);
+
+        let mut federation_entity = json!({});
+        if let Some(name) = FEDERATION_ORGANIZATION_NAME.as_deref() {
+            federation_entity["organization_name"] = json!(name);
+        }
+
+        let custom_claims = EntityConfigurationClaims {
+            jwks: serde_json::to_value(JWKSCerts::from(JWKS::find_pk().await?))?,
+            authority_hints: FEDERATION_AUTHORITY_HINTS.clone(),
+            metadata: json!({
+                "openid_provider": openid_provider,
+                "federation_entity": federation_entity,
+            }),
+        };
+        let claims = jwt_claims(
+            custom_claims,
+            coarsetime::Duration::from_secs(*FEDERATION_ENTITY_STATEMENT_LIFETIME),
+        )
+        .with_issuer(data.issuer.clone())
+        .with_subject(data.issuer.clone());
+
+        let kp = JwkKeyPair::find_latest(JwkKeyPairAlg::default()).await?;
+        kp.sign_with_typ(&claims, ENTITY_STATEMENT_TYP).await
+    }
+
+    /// Resolves a trust chain from the given leaf entity up to one of the configured
+    /// `FEDERATION_TRUST_ANCHORS` and returns the verified entity configuration of the leaf.
+    ///
+    /// Each step verifies the subordinate statement with the keys of the superior and the
+    /// entity configuration of the subordinate with the keys from this statement.
+    pub async fn resolve_trust_chain(entity_id: &str) -> Result<Self, ErrorResponse> {
+        if FEDERATION_TRUST_ANCHORS.is_empty() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "No federation trust anchors have been configured",
+            ));
+        }
+
+        let (leaf, leaf_token) = Self::fetch_configuration(entity_id).await?;
+
+        // (entity ID, entity configuration, authority hints, chain length)
+        let mut stack = vec![(
+            leaf.sub.clone(),
+            leaf_token,
+            leaf.authority_hints.clone(),
+            0,
+        )];
+        while let Some((subject, token, hints, len)) = stack.pop() {
+            for hint in hints {
+                let (superior, superior_token) = match Self::fetch_configuration(&hint).await {
+                    Ok(res) => res,
+                    Err(err) => {
+                        debug!("Skipping authority hint {}: {}", hint, err.message);
+                        continue;
+                    }
+                };
+                let statement = match Self::fetch_subordinate(&superior, &subject).await {
+                    Ok(stmt) => stmt,
+                    Err(err) => {
+                        debug!("Skipping authority hint {}: {}", hint, err.message);
+                        continue;
+                    }
+                };
+                // the superior vouches for the keys of its subordinate
+                if Self::verify_signature(&token, &statement.jwks).is_err() {
+                    debug!(
+                        "Entity configuration of {} does not match the statement from {}",
+                        subject, hint
+                    );
+                    continue;
+                }
+
+                if FEDERATION_TRUST_ANCHORS.contains(&hint) {
+                    info!("Resolved trust chain for {} via {}", entity_id, hint);
+                    return Ok(leaf);
+                }
+                if len + 1 < MAX_CHAIN_LENGTH {
+                    stack.push((
+                        superior.sub.clone(),
+                        superior_token,
+                        superior.authority_hints,
+                        len + 1,
+                    ));
+                }
+            }
+        }
+
+        Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            format!("Cannot resolve a trust chain for {}", entity_id),
+        ))
+    }
+
+    /// Returns the `openid_relying_party` metadata of this entity.
+    pub fn rp_metadata(self) -> Option<Value> {
+        self.metadata
+            .and_then(|mut m| m.get_mut("openid_relying_party").map(Value::take))
+            .filter(Value::is_object)
+    }
+}
+
+impl EntityStatement {
+    /// Fetches the self-signed entity configuration and validates it with its own keys.
+    async fn fetch_configuration(entity_id: &str) -> Result<(Self, String), ErrorResponse> {
+        let url = format!(
+            "{}/.well-known/openid-federation",
+            entity_id.trim_end_matches('/')
+        );
+        let token = Self::fetch(&url).await?;
+        let slf = Self::decode(&token)?;
+
+        if slf.iss != entity_id || slf.sub != entity_id {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Entity configuration is not issued by {}", entity_id),
+            ));
+        }
+        Self::verify_signature(&token, &slf.jwks)?;
+
+        Ok((slf, token))
+    }
+
+    /// Fetches the statement about `sub` from the fetch endpoint of the superior.
+    async fn fetch_subordinate(superior: &Self, sub: &str) -> Result<Self, ErrorResponse> {
+        let endpoint = superior
+            .metadata
+            .as_ref()
+            .and_then(|m| m.get("federation_entity"))
+            .and_then(|m| m.get("federation_fetch_endpoint"))
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("{} has no 'federation_fetch_endpoint'", superior.sub),
+                )
+            })?;
+        let mut url = Url::from_str(endpoint).map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Invalid 'federation_fetch_endpoint'",
+            )
+        })?;
+        url.query_pairs_mut().append_pair("sub", sub);
+
+        let token = Self::fetch(url.as_str()).await?;
+        let slf = Self::decode(&token)?;
+        if slf.iss != superior.sub || slf.sub != sub {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Invalid subordinate statement from {}", superior.sub),
+            ));
+        }
+        Self::verify_signature(&token, &superior.jwks)?;
+
+        Ok(slf)
+    }
+
+    async fn fetch(url: &str) -> Result<String, ErrorResponse> {
+        let client = HTTP_CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!("Rauthy v{} Federation Resolver", RAUTHY_VERSION))
+                .min_tls_version(tls::Version::TLS_1_2)
+                .pool_idle_timeout(Duration::from_secs(600))
+                .build()
+                .unwrap()
+        });
+
+        let res = client
+            .get(url)
+            .header(ACCEPT, CONTENT_TYPE_ENTITY_STATEMENT)
+            .send()
+            .await
+            .map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::Connection,
+                    format!("Cannot fetch entity statement from {}: {:?}", url, err),
+                )
+            })?;
+
+        if !res.status().is_success() {
+            let msg = format!("Cannot fetch entity statement from {}", url);
+            error!("{}", msg);
+            return Err(ErrorResponse::new(ErrorResponseType::Connection, msg));
+        }
+
+        let body = res.text().await?;
+        if body.len() > MAX_STATEMENT_SIZE {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The entity statement is too large",
+            ));
+        }
+        Ok(body)
+    }
+
+    /// Decodes the statement without validating the signature, which must be done separately.
+    fn decode(token: &str) -> Result<Self, ErrorResponse> {
+        let malformed =
+            || ErrorResponse::new(ErrorResponseType::BadRequest, "Malformed entity statement");
+
+        let mut parts = token.trim().split('.');
+        let (Some(header), Some(claims), Some(_), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(malformed());
+        };
+
+        let header = serde_json::from_slice::<Value>(&base64_url_no_pad_decode(header)?)
+            .map_err(|_| malformed())?;
+        if header.get("typ").and_then(Value::as_str) != Some(ENTITY_STATEMENT_TYP) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Invalid 'typ' for an entity statement",
+            ));
+        }
+
+        let slf = serde_json::from_slice::<Self>(&base64_url_no_pad_decode(claims)?)
+            .map_err(|_| malformed())?;
+        if slf.exp < clock::now().timestamp() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The entity statement has expired",
+            ));
+        }
+
+        Ok(slf)
+    }
+
+    /// Validates the signature of the token with the matching key from the given JWKS.
+    fn verify_signature(token: &str, jwks: &Value) -> Result<(), ErrorResponse> {
+        let metadata = Token::decode_metadata(token).map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Malformed entity statement header",
+            )
+        })?;
+        // the `alg` must be one we support, which also makes sure that `none` is never accepted
+        let alg = JwkKeyPairAlg::from_str(metadata.algorithm())?;
+        let kid = metadata.key_id();
+
+        let keys = jwks
+            .get("keys")
+            .and_then(Value::as_array)
+            .map(|keys| keys.as_slice())
+            .unwrap_or_default();
+        for key in keys {
+            let Ok(mut key) = serde_json::from_value::<JWKSPublicKey>(key.clone()) else {
+                continue;
+            };
+            if kid.is_some() && key.kid.as_deref() != kid {
+                continue;
+            }
+            match &key.alg {
+                Some(key_alg) if key_alg != &alg => continue,
+                Some(_) => {}
+                None => key.alg = Some(alg.clone()),
+            }
+            if key.validate_token_signature(token).is_ok() {
+                return Ok(());
+            }
+        }
+
+        Err(ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            "Invalid entity statement signature",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rauthy_common::utils::base64_url_no_pad_encode;
+
+    #[test]
+    fn test_decode() {
+        let exp = clock::now().timestamp() + 60;
+        let claims = json!({
+            "iss": "https://rp.example.com",
+            "sub": "https://rp.example.com",
+            "exp": exp,
+            "jwks": {"keys": []},
+            "metadata": {"openid_relying_party": {"redirect_uris": ["https://rp.example.com/cb"]}},
+        });
+        let build = |typ: &str| {
+            format!(
+                "{}.{}.c2ln",
+                base64_url_no_pad_encode(
+                    json!({"alg": "EdDSA", "typ": typ}).to_string().as_bytes()
+                ),
+                base64_url_no_pad_encode(claims.to_string().as_bytes()),
+            )
+        };
+
+        let stmt = EntityStatement::decode(&build(ENTITY_STATEMENT_TYP)).unwrap();
+        assert_eq!(stmt.sub, "https://rp.example.com");
+        assert!(stmt.authority_hints.is_empty());
+        let rp = stmt.rp_metadata().unwrap();
+        assert_eq!(rp["redirect_uris"][0], "https://rp.example.com/cb");
+
+        assert!(EntityStatement::decode(&build("JWT")).is_err());
+        assert!(EntityStatement::decode("a.b").is_err());
+    }
+}
@@ -0,0 +1,313 @@
+use crate::database::{Cache, DB};
+use hiqlite::{params, Param};
+use rauthy_common::clock;
+use rauthy_common::constants::{
+    CACHE_TTL_APP, IDX_FIDO_MDS, RAUTHY_VERSION, WEBAUTHN_MDS_ENABLE, WEBAUTHN_MDS_URL,
+};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::{base64_decode, base64_url_no_pad_decode};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use reqwest::tls;
+use ring::signature;
+use serde::{Deserialize, Serialize};
+use sqlx::query;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{error, warn};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// Status reports, which mark an authenticator model as compromised.
+const STATUS_COMPROMISED: [&str; 5] = [
+    "REVOKED",
+    "USER_VERIFICATION_BYPASS",
+    "ATTESTATION_KEY_COMPROMISE",
+    "USER_KEY_REMOTE_COMPROMISE",
+    "USER_KEY_PHYSICAL_COMPROMISE",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FidoMdsEntry {
+    /// The authenticator model name
+    pub description: String,
+    /// `true` if any status report flags this model as compromised
+    pub compromised: bool,
+}
+
+/// The parts of the FIDO Metadata Service BLOB, which are used by Rauthy. Only FIDO2
+/// authenticators with an AAGUID are included.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FidoMds {
+    /// The serial number of the BLOB
+    pub no: i64,
+    pub fetched: i64,
+    /// Key: AAGUID
+    pub entries: HashMap<String, FidoMdsEntry>,
+}
+
+impl FidoMds {
+    /// Returns the latest fetched BLOB, if the MDS is enabled and it has been fetched already.
+    pub async fn find() -> Option<Self> {
+        if !*WEBAUTHN_MDS_ENABLE {
+            return None;
+        }
+
+        let client = DB::client();
+        if let Ok(Some(slf)) = client.get(Cache::App, IDX_FIDO_MDS).await {
+            return Some(slf);
+        }
+
+        let data: Option<Vec<u8>> = if is_hiqlite() {
+            client
+                .query_raw("SELECT data FROM config WHERE id = 'fido_mds'", params!())
+                .await
+                .ok()?
+                .into_iter()
+                .next()
+                .map(|mut row| row.get("data"))
+        } else {
+            query!("SELECT data FROM config WHERE id = 'fido_mds'")
+                .fetch_optional(DB::conn())
+                .await
+                .ok()?
+                .and_then(|r| r.data)
+        };
+
+        let slf = bincode::deserialize::<Self>(&data?).ok()?;
+        if let Err(err) = client
+            .put(Cache::App, IDX_FIDO_MDS, &slf, CACHE_TTL_APP)
+            .await
+        {
+            error!("Inserting FidoMds into cache: {:?}", err);
+        }
+
+        Some(slf)
+    }
+
+    pub async fn upsert(&self) -> Result<(), ErrorResponse> {
+        let data = bincode::serialize(self)?;
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO config (id, data) VALUES ('fido_mds', $1)
+ON CONFLICT(id) DO UPDATE SET data = $1"#,
+                    params!(data),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO config (id, data) VALUES ('fido_mds', $1)
+ON CONFLICT(id) DO UPDATE SET data = $1"#,
+                data
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        DB::client()
+            .put(Cache::App, IDX_FIDO_MDS, self, CACHE_TTL_APP)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Fetches and verifies the current BLOB from `WEBAUTHN_MDS_URL`.
+    pub async fn lookup() -> Result<Self, ErrorResponse> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(60))
+            .user_agent(format!("Rauthy v{} FIDO MDS", RAUTHY_VERSION))
+            .min_tls_version(tls::Version::TLS_1_2)
+            .build()?;
+
+        let resp = client.get(WEBAUTHN_MDS_URL.as_str()).send().await?;
+        if !resp.status().is_success() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Connection,
+                format!("Error fetching the FIDO MDS BLOB: {}", resp.status()),
+            ));
+        }
+        let token = resp.text().await?;
+
+        let payload = Self::verify_blob(&token)?;
+        Self::parse(&payload)
+    }
+
+    /// Returns an error, if the FIDO MDS flags the authenticator model as compromised.
+    pub async fn validate_not_compromised(aaguid: Option<&str>) -> Result<(), ErrorResponse> {
+        let Some(aaguid) = aaguid else {
+            return Ok(());
+        };
+        let Some(slf) = Self::find().await else {
+            return Ok(());
+        };
+
+        match slf.entries.get(aaguid) {
+            Some(entry) if entry.compromised => {
+                warn!(
+                    "Rejecting compromised authenticator '{}' ({})",
+                    entry.description, aaguid
+                );
+                Err(ErrorResponse::new(
+                    ErrorResponseType::Forbidden,
+                    "This security key has been reported as compromised",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Verifies the signature of the BLOB with the signing certificate from the `x5c` header
+    /// and returns the decoded payload. The trust in the signing certificate itself comes from
+    /// the TLS connection to the `WEBAUTHN_MDS_URL`.
+    fn verify_blob(token: &str) -> Result<Vec<u8>, ErrorResponse> {
+        let token = token.trim();
+        let err = |msg: &str| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Invalid FIDO MDS BLOB: {}", msg),
+            )
+        };
+
+        let (message, sig) = token.rsplit_once('.').ok_or_else(|| err("no signature"))?;
+        let (header, payload) = message.split_once('.').ok_or_else(|| err("no payload"))?;
+
+        let header = serde_json::from_slice::<MdsHeader>(&base64_url_no_pad_decode(header)?)?;
+        if header.alg != "RS256" {
+            return Err(err("unsupported alg"));
+        }
+        let cert_der = base64_decode(header.x5c.first().ok_or_else(|| err("empty x5c"))?)?;
+        let (_, cert) =
+            X509Certificate::from_der(&cert_der).map_err(|_| err("invalid certificate"))?;
+        if !cert.validity().is_valid() {
+            return Err(err("expired certificate"));
+        }
+
+        signature::UnparsedPublicKey::new(
+            &signature::RSA_PKCS1_2048_8192_SHA256,
+            cert.public_key().subject_public_key.data.as_ref(),
+        )
+        .verify(message.as_bytes(), &base64_url_no_pad_decode(sig)?)
+        .map_err(|_| err("bad signature"))?;
+
+        base64_url_no_pad_decode(payload)
+    }
+
+    fn parse(payload: &[u8]) -> Result<Self, ErrorResponse> {
+        let payload = serde_json::from_slice::<MdsPayload>(payload)?;
+
+        let entries = payload
+            .entries
+            .into_iter()
+            .filter_map(|entry| {
+                let aaguid = entry.aaguid?.to_lowercase();
+                let description = entry
+                    .metadata_statement
+                    .map(|s| s.description)
+                    .unwrap_or_default();
+                let compromised = entry
+                    .status_reports
+                    .iter()
+                    .any(|r| STATUS_COMPROMISED.contains(&r.status.as_str()));
+
+                Some((
+                    aaguid,
+                    FidoMdsEntry {
+                        description,
+                        compromised,
+                    },
+                ))
+            })
+            .collect();
+
+        Ok(Self {
+            no: payload.no,
+            fetched: clock::now().timestamp(),
+            entries,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MdsHeader {
+    alg: String,
+    #[serde(default)]
+    x5c: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MdsPayload {
+    no: i64,
+    entries: Vec<MdsPayloadEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MdsPayloadEntry {
+    aaguid: Option<String>,
+    metadata_statement: Option<MdsMetadataStatement>,
+    #[serde(default)]
+    status_reports: Vec<MdsStatusReport>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MdsMetadataStatement {
+    description: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MdsStatusReport {
+    status: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let payload = r#"{
+            "legalHeader": "",
+            "no": 42,
+            "nextUpdate": "2024-11-01",
+            "entries": [
+                {
+                    "aaguid": "CB69481E-8FF7-4039-93EC-0A2729A154A8",
+                    "metadataStatement": { "description": "YubiKey 5 Series" },
+                    "statusReports": [{ "status": "FIDO_CERTIFIED_L1" }]
+                },
+                {
+                    "aaguid": "ee882879-721c-4913-9775-3dfcce97072a",
+                    "metadataStatement": { "description": "Broken Key" },
+                    "statusReports": [
+                        { "status": "FIDO_CERTIFIED" },
+                        { "status": "ATTESTATION_KEY_COMPROMISE" }
+                    ]
+                },
+                {
+                    "attestationCertificateKeyIdentifiers": ["abc"],
+                    "statusReports": []
+                }
+            ]
+        }"#;
+
+        let mds = FidoMds::parse(payload.as_bytes()).unwrap();
+        assert_eq!(mds.no, 42);
+        assert_eq!(mds.entries.len(), 2);
+        assert_eq!(
+            mds.entries.get("cb69481e-8ff7-4039-93ec-0a2729a154a8"),
+            Some(&FidoMdsEntry {
+                description: "YubiKey 5 Series".to_string(),
+                compromised: false,
+            })
+        );
+        assert!(
+            mds.entries
+                .get("ee882879-721c-4913-9775-3dfcce97072a")
+                .unwrap()
+                .compromised
+        );
+    }
+}
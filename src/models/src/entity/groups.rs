@@ -1,19 +1,30 @@
 use crate::database::{Cache, DB};
+use crate::entity::clients_access::ClientAccessPolicy;
+use crate::entity::roles::Role;
 use crate::entity::users::User;
 use hiqlite::{params, Param, Params};
-use rauthy_api_types::groups::NewGroupRequest;
-use rauthy_common::constants::{CACHE_TTL_APP, IDX_GROUPS};
+use rauthy_api_types::groups::{
+    GroupInheritanceRequest, GroupInheritanceResponse, NewGroupRequest,
+};
+use rauthy_common::constants::{
+    CACHE_TTL_APP, IDX_GROUPS, IDX_GROUPS_INHERITANCE, IDX_PASSWORD_RULES_GROUPS,
+};
 use rauthy_common::is_hiqlite;
 use rauthy_common::utils::new_store_id;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::collections::HashSet;
+use tracing::trace;
 use utoipa::ToSchema;
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize, ToSchema)]
 pub struct Group {
     pub id: String,
     pub name: String,
+    /// Members of this group, including the members of all nested child groups, must have a 2nd
+    /// factor enrolled before they can log in to any client.
+    pub force_mfa: bool,
 }
 
 // CRUD
@@ -33,20 +44,26 @@ impl Group {
         let new_group = Group {
             id: new_store_id(),
             name: group_req.group,
+            force_mfa: group_req.force_mfa.unwrap_or(false),
         };
 
         if is_hiqlite() {
             DB::client()
                 .execute(
-                    "INSERT INTO groups (id, name) VALUES ($1, $2)",
-                    params!(new_group.id.clone(), new_group.name.clone()),
+                    "INSERT INTO groups (id, name, force_mfa) VALUES ($1, $2, $3)",
+                    params!(
+                        new_group.id.clone(),
+                        new_group.name.clone(),
+                        new_group.force_mfa
+                    ),
                 )
                 .await?;
         } else {
             sqlx::query!(
-                "INSERT INTO groups (id, name) VALUES ($1, $2)",
+                "INSERT INTO groups (id, name, force_mfa) VALUES ($1, $2, $3)",
                 new_group.id,
                 new_group.name,
+                new_group.force_mfa,
             )
             .execute(DB::conn())
             .await?;
@@ -109,6 +126,11 @@ impl Group {
         client
             .put(Cache::App, IDX_GROUPS, &groups, CACHE_TTL_APP)
             .await?;
+        // a possibly bound password policy, client access policy entries and the inheritance are
+        // removed via `ON DELETE CASCADE`
+        client.delete(Cache::App, IDX_PASSWORD_RULES_GROUPS).await?;
+        client.delete(Cache::App, IDX_GROUPS_INHERITANCE).await?;
+        ClientAccessPolicy::invalidate_cache().await?;
 
         Ok(())
     }
@@ -150,14 +172,19 @@ impl Group {
         Ok(res)
     }
 
-    // Updates a group
-    pub async fn update(id: String, new_name: String) -> Result<Self, ErrorResponse> {
+    // Updates a group. `force_mfa` will only be changed, if it is given.
+    pub async fn update(
+        id: String,
+        new_name: String,
+        force_mfa: Option<bool>,
+    ) -> Result<Self, ErrorResponse> {
         let group = Group::find(id).await?;
         let users = User::find_with_group(&group.name).await?;
 
         let new_group = Self {
             id: group.id.clone(),
             name: new_name,
+            force_mfa: force_mfa.unwrap_or(group.force_mfa),
         };
 
         if is_hiqlite() {
@@ -174,8 +201,12 @@ impl Group {
             }
 
             txn.push((
-                "UPDATE groups SET name = $1 WHERE id = $2",
-                params!(new_group.name.clone(), new_group.id.clone()),
+                "UPDATE groups SET name = $1, force_mfa = $2 WHERE id = $3",
+                params!(
+                    new_group.name.clone(),
+                    new_group.force_mfa,
+                    new_group.id.clone()
+                ),
             ));
 
             for res in DB::client().txn(txn).await? {
@@ -190,8 +221,9 @@ impl Group {
                 user.save_txn(&mut txn).await?;
             }
             sqlx::query!(
-                "UPDATE groups SET name = $1 WHERE id = $2",
+                "UPDATE groups SET name = $1, force_mfa = $2 WHERE id = $3",
                 new_group.name,
+                new_group.force_mfa,
                 new_group.id,
             )
             .execute(&mut *txn)
@@ -203,11 +235,12 @@ impl Group {
         let groups = Group::find_all()
             .await?
             .into_iter()
-            .map(|mut g| {
+            .map(|g| {
                 if g.id == group.id {
-                    g.name.clone_from(&new_group.name);
+                    new_group.clone()
+                } else {
+                    g
                 }
-                g
             })
             .collect::<Vec<Group>>();
 
@@ -222,6 +255,60 @@ impl Group {
 }
 
 impl Group {
+    /// Returns `true` if any of the effective groups of the user forces MFA.
+    pub async fn forces_mfa(user: &User) -> Result<bool, ErrorResponse> {
+        let groups = Group::find_all().await?;
+        if !groups.iter().any(|g| g.force_mfa) {
+            return Ok(false);
+        }
+
+        let inheritance = GroupInheritance::find().await?;
+        Ok(Self::any_forces_mfa(
+            &groups,
+            &inheritance.nested,
+            &user.get_groups(),
+        ))
+    }
+
+    /// Returns `true` if any of the given groups or their parents forces MFA.
+    fn any_forces_mfa(groups: &[Group], nested: &[GroupNested], direct_groups: &[String]) -> bool {
+        let group_ids = groups
+            .iter()
+            .filter(|g| direct_groups.contains(&g.name))
+            .map(|g| g.id.as_str())
+            .collect::<Vec<_>>();
+        let effective_ids = GroupInheritance::effective_group_ids(nested, group_ids);
+
+        groups
+            .iter()
+            .any(|g| g.force_mfa && effective_ids.contains(g.id.as_str()))
+    }
+
+    /// Validates the User's access to the client depending on the `force_mfa` setting of all
+    /// effective groups. Like for `Client::validate_mfa()`, the "rauthy" client is the exception
+    /// to make it possible to log into the account and enroll a 2nd factor.
+    ///
+    /// `has_otp` must be set, if the user has TOTP, Yubico OTP or SMS OTP enabled.
+    pub async fn validate_mfa(
+        client_id: &str,
+        user: &User,
+        has_otp: bool,
+    ) -> Result<(), ErrorResponse> {
+        if client_id != "rauthy"
+            && !user.has_webauthn_enabled()
+            && !has_otp
+            && Self::forces_mfa(user).await?
+        {
+            trace!("MFA required for the groups of this user but the user has none");
+            Err(ErrorResponse::new(
+                ErrorResponseType::MfaRequired,
+                "MFA is required for the groups of this user",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     // Sanitizes any bad data from an API request for adding / modifying groups and silently
     // dismissed all bad data.
     pub async fn sanitize(
@@ -248,3 +335,377 @@ impl Group {
         }
     }
 }
+
+/// The `parent_id` group contains the `child_id` group. Members of the child are effectively
+/// members of the parent as well.
+#[derive(Debug, Clone, PartialEq, FromRow, Serialize, Deserialize)]
+pub struct GroupNested {
+    pub parent_id: String,
+    pub child_id: String,
+}
+
+/// A role, which is granted to all effective members of the group.
+#[derive(Debug, Clone, PartialEq, FromRow, Serialize, Deserialize)]
+pub struct GroupRole {
+    pub group_id: String,
+    pub role_id: String,
+}
+
+/// The nesting of groups and their roles, which will be resolved transitively at token issuance
+/// time.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct GroupInheritance {
+    pub nested: Vec<GroupNested>,
+    pub roles: Vec<GroupRole>,
+}
+
+impl GroupInheritance {
+    pub async fn find() -> Result<Self, ErrorResponse> {
+        let client = DB::client();
+        if let Some(slf) = client.get(Cache::App, IDX_GROUPS_INHERITANCE).await? {
+            return Ok(slf);
+        }
+
+        let slf = if is_hiqlite() {
+            Self {
+                nested: client
+                    .query_as("SELECT * FROM groups_nested", params!())
+                    .await?,
+                roles: client
+                    .query_as("SELECT * FROM groups_roles", params!())
+                    .await?,
+            }
+        } else {
+            Self {
+                nested: sqlx::query_as!(GroupNested, "SELECT * FROM groups_nested")
+                    .fetch_all(DB::conn())
+                    .await?,
+                roles: sqlx::query_as!(GroupRole, "SELECT * FROM groups_roles")
+                    .fetch_all(DB::conn())
+                    .await?,
+            }
+        };
+
+        client
+            .put(Cache::App, IDX_GROUPS_INHERITANCE, &slf, CACHE_TTL_APP)
+            .await?;
+
+        Ok(slf)
+    }
+
+    pub async fn find_for_group(group_id: &str) -> Result<GroupInheritanceResponse, ErrorResponse> {
+        let slf = Self::find().await?;
+        Ok(GroupInheritanceResponse {
+            children: slf
+                .nested
+                .into_iter()
+                .filter(|n| n.parent_id == group_id)
+                .map(|n| n.child_id)
+                .collect(),
+            roles: slf
+                .roles
+                .into_iter()
+                .filter(|r| r.group_id == group_id)
+                .map(|r| r.role_id)
+                .collect(),
+        })
+    }
+
+    /// Replaces the child groups and roles of the given group.
+    pub async fn update(
+        group_id: String,
+        mut req: GroupInheritanceRequest,
+    ) -> Result<GroupInheritanceResponse, ErrorResponse> {
+        let group = Group::find(group_id).await?;
+        req.children.sort();
+        req.children.dedup();
+        req.roles.sort();
+        req.roles.dedup();
+
+        let group_ids = Group::find_all()
+            .await?
+            .into_iter()
+            .map(|g| g.id)
+            .collect::<HashSet<_>>();
+        if let Some(id) = req.children.iter().find(|id| !group_ids.contains(*id)) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                format!("Group '{}' does not exist", id),
+            ));
+        }
+
+        let role_ids = Role::find_all()
+            .await?
+            .into_iter()
+            .map(|r| r.id)
+            .collect::<HashSet<_>>();
+        if let Some(id) = req.roles.iter().find(|id| !role_ids.contains(*id)) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                format!("Role '{}' does not exist", id),
+            ));
+        }
+
+        let inheritance = Self::find().await?;
+        if Self::would_cycle(&inheritance.nested, &group.id, &req.children) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Nesting these groups would create a cycle",
+            ));
+        }
+
+        if is_hiqlite() {
+            let mut txn: Vec<(&str, Params)> =
+                Vec::with_capacity(req.children.len() + req.roles.len() + 2);
+
+            txn.push((
+                "DELETE FROM groups_nested WHERE parent_id = $1",
+                params!(group.id.clone()),
+            ));
+            for child_id in &req.children {
+                txn.push((
+                    "INSERT INTO groups_nested (parent_id, child_id) VALUES ($1, $2)",
+                    params!(group.id.clone(), child_id.clone()),
+                ));
+            }
+
+            txn.push((
+                "DELETE FROM groups_roles WHERE group_id = $1",
+                params!(group.id.clone()),
+            ));
+            for role_id in &req.roles {
+                txn.push((
+                    "INSERT INTO groups_roles (group_id, role_id) VALUES ($1, $2)",
+                    params!(group.id.clone(), role_id.clone()),
+                ));
+            }
+
+            for res in DB::client().txn(txn).await? {
+                res?;
+            }
+        } else {
+            let mut txn = DB::txn().await?;
+
+            sqlx::query!("DELETE FROM groups_nested WHERE parent_id = $1", group.id)
+                .execute(&mut *txn)
+                .await?;
+            for child_id in &req.children {
+                sqlx::query!(
+                    "INSERT INTO groups_nested (parent_id, child_id) VALUES ($1, $2)",
+                    group.id,
+                    child_id,
+                )
+                .execute(&mut *txn)
+                .await?;
+            }
+
+            sqlx::query!("DELETE FROM groups_roles WHERE group_id = $1", group.id)
+                .execute(&mut *txn)
+                .await?;
+            for role_id in &req.roles {
+                sqlx::query!(
+                    "INSERT INTO groups_roles (group_id, role_id) VALUES ($1, $2)",
+                    group.id,
+                    role_id,
+                )
+                .execute(&mut *txn)
+                .await?;
+            }
+
+            txn.commit().await?;
+        }
+
+        DB::client()
+            .delete(Cache::App, IDX_GROUPS_INHERITANCE)
+            .await?;
+
+        Ok(GroupInheritanceResponse {
+            children: req.children,
+            roles: req.roles,
+        })
+    }
+
+    /// Returns `true` if setting `children` for `parent_id` would create a cycle, which is the
+    /// case when the parent can be reached from any of the new children.
+    fn would_cycle(nested: &[GroupNested], parent_id: &str, children: &[String]) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = children.iter().map(String::as_str).collect::<Vec<_>>();
+
+        while let Some(id) = stack.pop() {
+            if id == parent_id {
+                return true;
+            }
+            if !visited.insert(id) {
+                continue;
+            }
+
+            // the existing children of `parent_id` are never visited, since they will be replaced
+            stack.extend(
+                nested
+                    .iter()
+                    .filter(|n| n.parent_id == id)
+                    .map(|n| n.child_id.as_str()),
+            );
+        }
+
+        false
+    }
+
+    /// Resolves the effective groups and roles for the given direct group and role names.
+    /// Groups inherit all parents transitively and roles are inherited from all effective groups.
+    pub async fn resolve(
+        direct_groups: Vec<String>,
+        direct_roles: Vec<String>,
+    ) -> Result<(Vec<String>, Vec<String>), ErrorResponse> {
+        if direct_groups.is_empty() {
+            return Ok((direct_groups, direct_roles));
+        }
+
+        let inheritance = Self::find().await?;
+        if inheritance.nested.is_empty() && inheritance.roles.is_empty() {
+            return Ok((direct_groups, direct_roles));
+        }
+
+        let groups = Group::find_all().await?;
+        let group_ids = groups
+            .iter()
+            .filter(|g| direct_groups.contains(&g.name))
+            .map(|g| g.id.as_str())
+            .collect::<Vec<_>>();
+        let effective_ids = Self::effective_group_ids(&inheritance.nested, group_ids);
+
+        let mut res_groups = direct_groups;
+        for group in &groups {
+            if effective_ids.contains(group.id.as_str()) && !res_groups.contains(&group.name) {
+                res_groups.push(group.name.clone());
+            }
+        }
+
+        let mut res_roles = direct_roles;
+        let role_ids = inheritance
+            .roles
+            .iter()
+            .filter(|r| effective_ids.contains(r.group_id.as_str()))
+            .map(|r| r.role_id.as_str())
+            .collect::<HashSet<_>>();
+        if !role_ids.is_empty() {
+            for role in Role::find_all().await? {
+                if role_ids.contains(role.id.as_str()) && !res_roles.contains(&role.name) {
+                    res_roles.push(role.name);
+                }
+            }
+        }
+
+        Ok((res_groups, res_roles))
+    }
+
+    /// Walks up the nesting from the given groups. Visited groups are skipped, which keeps this
+    /// safe even if a cycle ended up in the database somehow.
+    fn effective_group_ids<'a>(
+        nested: &'a [GroupNested],
+        group_ids: Vec<&'a str>,
+    ) -> HashSet<&'a str> {
+        let mut visited = HashSet::new();
+        let mut stack = group_ids;
+
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            stack.extend(
+                nested
+                    .iter()
+                    .filter(|n| n.child_id == id)
+                    .map(|n| n.parent_id.as_str()),
+            );
+        }
+
+        visited
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nest(parent_id: &str, child_id: &str) -> GroupNested {
+        GroupNested {
+            parent_id: parent_id.to_string(),
+            child_id: child_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_group_inheritance() {
+        // org -> eng -> backend
+        let nested = vec![nest("org", "eng"), nest("eng", "backend")];
+
+        let ids = GroupInheritance::effective_group_ids(&nested, vec!["backend"]);
+        assert_eq!(ids, HashSet::from(["backend", "eng", "org"]));
+        let ids = GroupInheritance::effective_group_ids(&nested, vec!["eng"]);
+        assert_eq!(ids, HashSet::from(["eng", "org"]));
+
+        assert!(GroupInheritance::would_cycle(
+            &nested,
+            "backend",
+            &["org".to_string()]
+        ));
+        assert!(GroupInheritance::would_cycle(
+            &nested,
+            "eng",
+            &["eng".to_string()]
+        ));
+        assert!(!GroupInheritance::would_cycle(
+            &nested,
+            "org",
+            &["backend".to_string()]
+        ));
+        // replacing the children of `eng` removes the existing path to `backend`
+        assert!(!GroupInheritance::would_cycle(
+            &nested,
+            "eng",
+            &["other".to_string()]
+        ));
+
+        // must terminate even with a cycle in the data
+        let nested = vec![nest("a", "b"), nest("b", "a")];
+        let ids = GroupInheritance::effective_group_ids(&nested, vec!["a"]);
+        assert_eq!(ids, HashSet::from(["a", "b"]));
+    }
+
+    #[test]
+    fn test_any_forces_mfa() {
+        let group = |id: &str, force_mfa: bool| Group {
+            id: id.to_string(),
+            name: format!("name_{}", id),
+            force_mfa,
+        };
+        let groups = vec![
+            group("org", true),
+            group("eng", false),
+            group("backend", false),
+            group("sales", false),
+        ];
+        // org -> eng -> backend
+        let nested = vec![nest("org", "eng"), nest("eng", "backend")];
+
+        // members of nested groups inherit the MFA requirement of all parents
+        let direct = vec!["name_backend".to_string()];
+        assert!(Group::any_forces_mfa(&groups, &nested, &direct));
+        let direct = vec!["name_eng".to_string()];
+        assert!(Group::any_forces_mfa(&groups, &nested, &direct));
+
+        // but not the other way around
+        let groups = vec![
+            group("org", false),
+            group("eng", false),
+            group("backend", true),
+            group("sales", false),
+        ];
+        assert!(!Group::any_forces_mfa(&groups, &nested, &direct));
+
+        let direct = vec!["name_sales".to_string()];
+        assert!(!Group::any_forces_mfa(&groups, &nested, &direct));
+        assert!(!Group::any_forces_mfa(&groups, &nested, &[]));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::database::{Cache, DB};
 use chrono::{DateTime, Utc};
+use rauthy_common::clock;
 use rauthy_common::constants::DEVICE_GRANT_RATE_LIMIT;
 use rauthy_error::ErrorResponse;
 use serde::{Deserialize, Serialize};
@@ -17,7 +18,7 @@ pub struct DeviceIpRateLimit {
 impl DeviceIpRateLimit {
     pub async fn insert(ip: String) -> Result<(), ErrorResponse> {
         let limit_secs = DEVICE_GRANT_RATE_LIMIT.unwrap_or(1) as i64;
-        let limit = Utc::now().add(chrono::Duration::seconds(limit_secs));
+        let limit = clock::now().add(chrono::Duration::seconds(limit_secs));
         DB::client()
             .put(Cache::IPRateLimit, ip, &limit, Some(limit_secs))
             .await?;
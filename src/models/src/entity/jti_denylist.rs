@@ -0,0 +1,204 @@
+use crate::database::{Cache, DB};
+use hiqlite::{params, Param};
+use rauthy_common::constants::JWT_CLOCK_SKEW_SECS;
+use rauthy_common::utils::base64_url_no_pad_decode;
+use rauthy_common::{clock, is_hiqlite};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use tracing::debug;
+
+/// The claims of a verified token, which are needed to check for a revocation.
+#[derive(Debug, Deserialize)]
+struct RevocationClaims {
+    jti: Option<String>,
+}
+
+/// An access token, which has been issued for a user or session. These are tracked until their
+/// expiry, so they can be denied by their `jti` when the user or session is revoked.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct IssuedAccessToken {
+    pub jti: String,
+    pub user_id: Option<String>,
+    pub session_id: Option<String>,
+    pub exp: i64,
+}
+
+impl IssuedAccessToken {
+    pub async fn insert(
+        jti: String,
+        user_id: Option<String>,
+        session_id: Option<String>,
+        exp: i64,
+    ) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO issued_access_tokens (jti, user_id, session_id, exp)
+VALUES ($1, $2, $3, $4)"#,
+                    params!(jti, user_id, session_id, exp),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                r#"
+INSERT INTO issued_access_tokens (jti, user_id, session_id, exp)
+VALUES ($1, $2, $3, $4)"#,
+                jti,
+                user_id,
+                session_id,
+                exp,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_expired() -> Result<(), ErrorResponse> {
+        // denied tokens are accepted until `exp` plus the clock skew
+        let exp = clock::now().timestamp() - *JWT_CLOCK_SKEW_SECS as i64;
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM issued_access_tokens WHERE exp < $1",
+                    params!(exp),
+                )
+                .await?;
+        } else {
+            sqlx::query!("DELETE FROM issued_access_tokens WHERE exp < $1", exp)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find_valid_for_session(session_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let now = clock::now().timestamp();
+
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM issued_access_tokens WHERE session_id = $1 AND exp >= $2",
+                    params!(session_id, now),
+                )
+                .await?
+        } else {
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM issued_access_tokens WHERE session_id = $1 AND exp >= $2",
+                session_id,
+                now,
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    pub async fn find_valid_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let now = clock::now().timestamp();
+
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM issued_access_tokens WHERE user_id = $1 AND exp >= $2",
+                    params!(user_id, now),
+                )
+                .await?
+        } else {
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM issued_access_tokens WHERE user_id = $1 AND exp >= $2",
+                user_id,
+                now,
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+}
+
+/// Denylist for revoked access tokens.
+///
+/// JWT access tokens are stateless and would stay valid until they expire. Revoked tokens are
+/// denied by their `jti`. Each access token issued for a user or session is tracked as an
+/// `IssuedAccessToken`, so that on logout, session revocation or when a user is disabled, exactly
+/// the still valid tokens for it can be denied, while new tokens issued afterward are not
+/// affected. Each denylist entry only lives until the token would have expired anyway.
+pub struct JtiDenylist;
+
+impl JtiDenylist {
+    /// Adds the given `jti` to the denylist until `exp`.
+    pub async fn deny(jti: String, exp: i64) -> Result<(), ErrorResponse> {
+        // the token validation accepts the `exp` with the clock skew as leeway
+        let ttl = exp + *JWT_CLOCK_SKEW_SECS as i64 - clock::now().timestamp();
+        if ttl < 1 {
+            return Ok(());
+        }
+
+        DB::client()
+            .put(Cache::JtiDenylist, Self::idx_jti(&jti), &exp, Some(ttl))
+            .await?;
+        Ok(())
+    }
+
+    /// Denies all still valid access tokens that have been issued for this user.
+    pub async fn deny_for_user(user_id: &str) -> Result<(), ErrorResponse> {
+        debug!("Denying all access tokens for user {}", user_id);
+        for token in IssuedAccessToken::find_valid_for_user(user_id).await? {
+            Self::deny(token.jti, token.exp).await?;
+        }
+        Ok(())
+    }
+
+    /// Denies all still valid access tokens that have been issued for this session.
+    pub async fn deny_for_session(sid: &str) -> Result<(), ErrorResponse> {
+        debug!("Denying all access tokens for session {}", sid);
+        for token in IssuedAccessToken::find_valid_for_session(sid).await? {
+            Self::deny(token.jti, token.exp).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns an error if the already verified JWT has been revoked before its expiry.
+    pub async fn validate_token(token: &str) -> Result<(), ErrorResponse> {
+        let payload = token
+            .split('.')
+            .nth(1)
+            .ok_or_else(|| ErrorResponse::new(ErrorResponseType::BadRequest, "Malformed JWT"))?;
+        Self::validate_claims(&base64_url_no_pad_decode(payload)?).await
+    }
+
+    /// Returns an error if the token with the given JSON claims has been denied by its `jti`.
+    pub async fn validate_claims(json: &[u8]) -> Result<(), ErrorResponse> {
+        let claims = serde_json::from_slice::<RevocationClaims>(json)?;
+        let Some(jti) = &claims.jti else {
+            return Ok(());
+        };
+
+        let exp: Option<i64> = DB::client()
+            .get(Cache::JtiDenylist, Self::idx_jti(jti))
+            .await?;
+        if exp.is_some() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "The access token has been revoked",
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn idx_jti(jti: &str) -> String {
+        format!("jti:{}", jti)
+    }
+}
@@ -0,0 +1,392 @@
+use crate::entity::client_assertions::ClientAssertion;
+use crate::entity::clients::Client;
+use rand::RngCore;
+use rauthy_common::utils::{base64_url_no_pad_decode, base64_url_no_pad_encode};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use ring::aead;
+use rsa::sha2::Sha256;
+use rsa::{BigUint, Oaep, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha1::Sha1;
+use std::str::FromStr;
+
+/// The key management algorithm, which encrypts the content encryption key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JweAlg {
+    RsaOaep,
+    RsaOaep256,
+}
+
+impl FromStr for JweAlg {
+    type Err = ErrorResponse;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "RSA-OAEP" => Ok(Self::RsaOaep),
+            "RSA-OAEP-256" => Ok(Self::RsaOaep256),
+            _ => Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Unsupported JWE 'alg': {}", s),
+            )),
+        }
+    }
+}
+
+impl JweAlg {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::RsaOaep => "RSA-OAEP",
+            Self::RsaOaep256 => "RSA-OAEP-256",
+        }
+    }
+}
+
+/// The content encryption algorithm for the payload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JweEnc {
+    A128GCM,
+    A256GCM,
+}
+
+impl FromStr for JweEnc {
+    type Err = ErrorResponse;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "A128GCM" => Ok(Self::A128GCM),
+            "A256GCM" => Ok(Self::A256GCM),
+            _ => Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Unsupported JWE 'enc': {}", s),
+            )),
+        }
+    }
+}
+
+impl JweEnc {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::A128GCM => "A128GCM",
+            Self::A256GCM => "A256GCM",
+        }
+    }
+
+    fn aead_alg(&self) -> &'static aead::Algorithm {
+        match self {
+            Self::A128GCM => &aead::AES_128_GCM,
+            Self::A256GCM => &aead::AES_256_GCM,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JweHeader<'a> {
+    alg: &'static str,
+    enc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kid: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cty: Option<&'a str>,
+}
+
+/// A public encryption key from a clients JWKS. Only RSA keys are supported.
+#[derive(Debug, Deserialize)]
+struct JweKey {
+    kty: String,
+    #[serde(rename = "use")]
+    key_use: Option<String>,
+    alg: Option<String>,
+    kid: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+impl JweKey {
+    fn matches(&self, alg: JweAlg) -> bool {
+        self.kty == "RSA"
+            && self.key_use.as_deref() != Some("sig")
+            && self
+                .alg
+                .as_deref()
+                .map(|a| a == alg.as_str())
+                .unwrap_or(true)
+    }
+
+    fn public_key(&self) -> Result<RsaPublicKey, ErrorResponse> {
+        let (Some(n), Some(e)) = (&self.n, &self.e) else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Incorrect format for RSA JWK: e / n missing",
+            ));
+        };
+        let n = BigUint::from_bytes_be(&base64_url_no_pad_decode(n)?);
+        let e = BigUint::from_bytes_be(&base64_url_no_pad_decode(e)?);
+        RsaPublicKey::new(n, e).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Invalid RSA encryption key: {}", err),
+            )
+        })
+    }
+}
+
+/// Encrypts ID tokens and userinfo responses as compact JWE (RFC 7516) with the public
+/// encryption key from the clients `jwks` or `jwks_uri`.
+#[derive(Debug)]
+pub struct JweEncrypter {
+    alg: JweAlg,
+    enc: JweEnc,
+    kid: Option<String>,
+    key: RsaPublicKey,
+}
+
+impl JweEncrypter {
+    /// Returns the encrypter for ID tokens, if the client has requested encryption.
+    pub async fn for_id_token(client: &Client) -> Result<Option<Self>, ErrorResponse> {
+        Self::build(
+            client,
+            client.id_token_encrypted_response_alg.as_deref(),
+            client.id_token_encrypted_response_enc.as_deref(),
+        )
+        .await
+    }
+
+    /// Returns the encrypter for userinfo responses, if the client has requested encryption.
+    pub async fn for_userinfo(client: &Client) -> Result<Option<Self>, ErrorResponse> {
+        Self::build(
+            client,
+            client.userinfo_encrypted_response_alg.as_deref(),
+            client.userinfo_encrypted_response_enc.as_deref(),
+        )
+        .await
+    }
+
+    /// Encrypts the payload with a fresh content encryption key. `cty` should be `JWT` for a
+    /// nested, signed JWT.
+    pub fn encrypt(&self, payload: &[u8], cty: Option<&str>) -> Result<String, ErrorResponse> {
+        let header = JweHeader {
+            alg: self.alg.as_str(),
+            enc: self.enc.as_str(),
+            kid: self.kid.as_deref(),
+            cty,
+        };
+        let header = base64_url_no_pad_encode(&serde_json::to_vec(&header)?);
+
+        let mut rng = rand::thread_rng();
+        let mut cek = vec![0u8; self.enc.aead_alg().key_len()];
+        rng.fill_bytes(&mut cek);
+        let mut iv = [0u8; aead::NONCE_LEN];
+        rng.fill_bytes(&mut iv);
+
+        let encrypted_key = match self.alg {
+            JweAlg::RsaOaep => self.key.encrypt(&mut rng, Oaep::new::<Sha1>(), &cek),
+            JweAlg::RsaOaep256 => self.key.encrypt(&mut rng, Oaep::new::<Sha256>(), &cek),
+        }
+        .map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("Cannot encrypt the JWE content encryption key: {}", err),
+            )
+        })?;
+
+        let key = aead::UnboundKey::new(self.enc.aead_alg(), &cek)
+            .map(aead::LessSafeKey::new)
+            .map_err(|_| {
+                ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    "Cannot build the JWE content encryption key",
+                )
+            })?;
+        let mut ciphertext = payload.to_vec();
+        // the encoded protected header is the additional authenticated data
+        let tag = key
+            .seal_in_place_separate_tag(
+                aead::Nonce::assume_unique_for_key(iv),
+                aead::Aad::from(header.as_bytes()),
+                &mut ciphertext,
+            )
+            .map_err(|_| {
+                ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    "Cannot encrypt the JWE payload",
+                )
+            })?;
+
+        Ok(format!(
+            "{}.{}.{}.{}.{}",
+            header,
+            base64_url_no_pad_encode(&encrypted_key),
+            base64_url_no_pad_encode(&iv),
+            base64_url_no_pad_encode(&ciphertext),
+            base64_url_no_pad_encode(tag.as_ref()),
+        ))
+    }
+
+    /// Encryption keys are not used for signature validation. They are skipped when parsing a
+    /// clients JWKS for signing keys.
+    pub fn is_enc_key(key: &Value) -> bool {
+        key.get("use").and_then(Value::as_str) == Some("enc")
+            || key
+                .get("alg")
+                .and_then(Value::as_str)
+                .map(|alg| JweAlg::from_str(alg).is_ok())
+                .unwrap_or(false)
+    }
+
+    /// Makes sure, that the configured algorithms are supported and used together.
+    pub fn validate_config(alg: Option<&str>, enc: Option<&str>) -> Result<(), ErrorResponse> {
+        match (alg, enc) {
+            (Some(alg), Some(enc)) => {
+                JweAlg::from_str(alg)?;
+                JweEnc::from_str(enc)?;
+                Ok(())
+            }
+            (None, None) => Ok(()),
+            _ => Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The encryption 'alg' and 'enc' must be given together",
+            )),
+        }
+    }
+}
+
+impl JweEncrypter {
+    async fn build(
+        client: &Client,
+        alg: Option<&str>,
+        enc: Option<&str>,
+    ) -> Result<Option<Self>, ErrorResponse> {
+        let (Some(alg), Some(enc)) = (alg, enc) else {
+            return Ok(None);
+        };
+        let alg = JweAlg::from_str(alg)?;
+        let enc = JweEnc::from_str(enc)?;
+
+        let key = if let Some(jwks) = &client.jwks {
+            Self::find_key(jwks, alg)?
+        } else if let Some(uri) = &client.jwks_uri {
+            // the client may have rotated its keys since the JWKS has been cached
+            let jwks = ClientAssertion::fetch_jwks(&client.id, uri, false).await?;
+            match Self::find_key(&jwks, alg)? {
+                Some(key) => Some(key),
+                None => {
+                    let jwks = ClientAssertion::fetch_jwks(&client.id, uri, true).await?;
+                    Self::find_key(&jwks, alg)?
+                }
+            }
+        } else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Encryption needs a client with a 'jwks' or 'jwks_uri'",
+            ));
+        };
+
+        let key = key.ok_or_else(|| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "No matching encryption key found in the clients JWKS",
+            )
+        })?;
+
+        Ok(Some(Self {
+            alg,
+            enc,
+            kid: key.kid.clone(),
+            key: key.public_key()?,
+        }))
+    }
+
+    fn find_key(jwks: &str, alg: JweAlg) -> Result<Option<JweKey>, ErrorResponse> {
+        #[derive(Deserialize)]
+        struct Keys {
+            keys: Vec<Value>,
+        }
+
+        let keys = serde_json::from_str::<Keys>(jwks).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Invalid JWKS: {}", err),
+            )
+        })?;
+        Ok(keys
+            .keys
+            .into_iter()
+            .filter_map(|key| serde_json::from_value::<JweKey>(key).ok())
+            .find(|key| key.matches(alg)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsa::traits::PublicKeyParts;
+    use rsa::RsaPrivateKey;
+
+    #[test]
+    fn test_encrypt() {
+        let mut rng = rand::thread_rng();
+        let sk = RsaPrivateKey::new(&mut rng, 2048).unwrap();
+        let pk = sk.to_public_key();
+        let jwks = serde_json::json!({
+            "keys": [{
+                "kty": "RSA",
+                "use": "enc",
+                "alg": "RSA-OAEP-256",
+                "kid": "enc1",
+                "n": base64_url_no_pad_encode(&pk.n().to_bytes_be()),
+                "e": base64_url_no_pad_encode(&pk.e().to_bytes_be()),
+            }]
+        })
+        .to_string();
+
+        assert!(JweEncrypter::find_key(&jwks, JweAlg::RsaOaep)
+            .unwrap()
+            .is_none());
+        let key = JweEncrypter::find_key(&jwks, JweAlg::RsaOaep256)
+            .unwrap()
+            .unwrap();
+        let encrypter = JweEncrypter {
+            alg: JweAlg::RsaOaep256,
+            enc: JweEnc::A256GCM,
+            kid: key.kid.clone(),
+            key: key.public_key().unwrap(),
+        };
+
+        let payload = b"header.claims.signature";
+        let jwe = encrypter.encrypt(payload, Some("JWT")).unwrap();
+        let parts = jwe.split('.').collect::<Vec<_>>();
+        assert_eq!(parts.len(), 5);
+
+        // decrypt it again to make sure it is valid
+        let cek = sk
+            .decrypt(
+                Oaep::new::<Sha256>(),
+                &base64_url_no_pad_decode(parts[1]).unwrap(),
+            )
+            .unwrap();
+        let key = aead::LessSafeKey::new(aead::UnboundKey::new(&aead::AES_256_GCM, &cek).unwrap());
+        let iv: [u8; aead::NONCE_LEN] = base64_url_no_pad_decode(parts[2])
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let mut in_out = base64_url_no_pad_decode(parts[3]).unwrap();
+        in_out.extend(base64_url_no_pad_decode(parts[4]).unwrap());
+        let plain = key
+            .open_in_place(
+                aead::Nonce::assume_unique_for_key(iv),
+                aead::Aad::from(parts[0].as_bytes()),
+                &mut in_out,
+            )
+            .unwrap();
+        assert_eq!(plain, payload);
+
+        assert!(JweEncrypter::is_enc_key(&serde_json::json!({"use": "enc"})));
+        assert!(JweEncrypter::is_enc_key(
+            &serde_json::json!({"alg": "RSA-OAEP"})
+        ));
+        assert!(!JweEncrypter::is_enc_key(
+            &serde_json::json!({"alg": "RS256"})
+        ));
+    }
+}
@@ -2,6 +2,7 @@
 // which cannot handle some stuff from the `rsa` crate at the time of writing
 
 use crate::entity::jwk::{JWKSPublicKey, JwkKeyPairAlg};
+use p384::ecdsa::signature::Verifier;
 use rauthy_common::utils::base64_url_no_pad_decode;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rsa::sha2::{Sha256, Sha384, Sha512};
@@ -62,6 +63,21 @@ impl JWKSPublicKey {
                 }
             }
 
+            JwkKeyPairAlg::ES384 => {
+                // JWS ECDSA signatures are the raw `r || s` and not DER encoded
+                let mut point = Vec::with_capacity(97);
+                point.push(0x04);
+                point.extend(self.x()?);
+                point.extend(self.y()?);
+                if let Ok(pubkey) = p384::ecdsa::VerifyingKey::from_sec1_bytes(&point) {
+                    if let Ok(signature) = p384::ecdsa::Signature::from_slice(&sig_bytes) {
+                        if pubkey.verify(message.as_bytes(), &signature).is_ok() {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+
             JwkKeyPairAlg::EdDSA => {
                 let x = self.x()?;
                 if let Ok(pubkey) = ed25519_compact::PublicKey::from_slice(x.as_slice()) {
@@ -0,0 +1,173 @@
+use crate::database::DB;
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use hiqlite::{params, Param};
+use rauthy_api_types::users::LoginHistoryResponse;
+use rauthy_common::clock;
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::{new_store_id, real_ip_from_req};
+use rauthy_error::ErrorResponse;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+use tracing::error;
+
+/// A single successful or failed login attempt for an existing user.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct LoginHistory {
+    pub id: String,
+    pub user_id: String,
+    pub timestamp: i64,
+    pub ip: String,
+    pub user_agent: Option<String>,
+    pub client_id: Option<String>,
+    pub mfa: bool,
+    pub success: bool,
+}
+
+impl From<LoginHistory> for LoginHistoryResponse {
+    fn from(value: LoginHistory) -> Self {
+        Self {
+            id: value.id,
+            timestamp: value.timestamp,
+            ip: value.ip,
+            user_agent: value.user_agent,
+            client_id: value.client_id,
+            mfa: value.mfa,
+            success: value.success,
+        }
+    }
+}
+
+impl LoginHistory {
+    pub async fn insert(&self) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO login_history (id, user_id, timestamp, ip, user_agent, client_id, mfa, success)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                    params!(
+                        self.id.clone(),
+                        self.user_id.clone(),
+                        self.timestamp,
+                        self.ip.clone(),
+                        self.user_agent.clone(),
+                        self.client_id.clone(),
+                        self.mfa,
+                        self.success
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO login_history (id, user_id, timestamp, ip, user_agent, client_id, mfa, success)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                self.id,
+                self.user_id,
+                self.timestamp,
+                self.ip,
+                self.user_agent,
+                self.client_id,
+                self.mfa,
+                self.success,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Records a login attempt for the given user. This must never let a login fail, which is
+    /// why errors are only logged.
+    pub async fn record(
+        req: &HttpRequest,
+        user_id: String,
+        client_id: Option<String>,
+        mfa: bool,
+        success: bool,
+    ) {
+        let ip = match real_ip_from_req(req) {
+            Ok(ip) => ip.to_string(),
+            Err(err) => {
+                error!(
+                    "Cannot extract the IP for the login history: {}",
+                    err.message
+                );
+                return;
+            }
+        };
+        // the header is user controlled -> cap the length
+        let user_agent = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.chars().take(256).collect::<String>());
+
+        let slf = Self {
+            id: new_store_id(),
+            user_id,
+            timestamp: clock::now().timestamp(),
+            ip,
+            user_agent,
+            client_id,
+            mfa,
+            success,
+        };
+        if let Err(err) = slf.insert().await {
+            error!("Error saving the login history: {}", err.message);
+        }
+    }
+
+    /// Returns the latest login attempts for the given user, newest first.
+    pub async fn find_for_user(user_id: &str, limit: u16) -> Result<Vec<Self>, ErrorResponse> {
+        let limit = limit as i64;
+
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    r#"
+SELECT * FROM login_history
+WHERE user_id = $1
+ORDER BY timestamp DESC
+LIMIT $2"#,
+                    params!(user_id, limit),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                r#"
+SELECT * FROM login_history
+WHERE user_id = $1
+ORDER BY timestamp DESC
+LIMIT $2"#,
+                user_id,
+                limit,
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    pub async fn delete_before(ts: i64) -> Result<u64, ErrorResponse> {
+        let rows_affected = if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM login_history WHERE timestamp < $1",
+                    params!(ts),
+                )
+                .await? as u64
+        } else {
+            query!("DELETE FROM login_history WHERE timestamp < $1", ts)
+                .execute(DB::conn())
+                .await?
+                .rows_affected()
+        };
+
+        Ok(rows_affected)
+    }
+}
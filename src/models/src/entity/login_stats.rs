@@ -0,0 +1,252 @@
+use crate::database::DB;
+use hiqlite::{params, Param};
+use rauthy_api_types::login_stats::{LoginStatsHourResponse, LoginStatsTopResponse};
+use rauthy_common::is_hiqlite;
+use rauthy_error::ErrorResponse;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+use std::net::IpAddr;
+
+/// Pre-aggregated failed login and blacklist statistics per hour, which makes it possible to
+/// render attack dashboards without scanning raw events.
+///
+/// Each hour has one row for the totals, one for each IP prefix and one for each targeted user.
+pub struct LoginStats;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoginStatsKind {
+    Total,
+    IpPrefix,
+    User,
+}
+
+impl LoginStatsKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Total => "total",
+            Self::IpPrefix => "ip_prefix",
+            Self::User => "user",
+        }
+    }
+}
+
+/// A raw row, only used for migrations between databases
+#[derive(Debug, FromRow)]
+pub struct LoginStatsRow {
+    pub bucket: i64,
+    pub kind: String,
+    pub subject: String,
+    pub failed: i64,
+    pub blacklisted: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginStatsHour {
+    pub bucket: i64,
+    pub failed: i64,
+    pub blacklisted: i64,
+}
+
+impl From<LoginStatsHour> for LoginStatsHourResponse {
+    fn from(value: LoginStatsHour) -> Self {
+        Self {
+            hour: value.bucket,
+            failed: value.failed,
+            blacklisted: value.blacklisted,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginStatsTop {
+    pub subject: String,
+    pub failed: i64,
+    pub blacklisted: i64,
+}
+
+impl From<LoginStatsTop> for LoginStatsTopResponse {
+    fn from(value: LoginStatsTop) -> Self {
+        Self {
+            subject: value.subject,
+            failed: value.failed,
+            blacklisted: value.blacklisted,
+        }
+    }
+}
+
+impl LoginStats {
+    /// Counts a failed login for the current hour. `target` is the login name, which has been
+    /// used for this attempt, if any.
+    pub async fn record_failed(
+        ip: &IpAddr,
+        target: Option<&str>,
+        blacklisted: bool,
+        now: i64,
+    ) -> Result<(), ErrorResponse> {
+        let bucket = now - now % 3600;
+        let blacklisted = if blacklisted { 1 } else { 0 };
+
+        let mut rows = vec![
+            (LoginStatsKind::Total, String::default()),
+            (LoginStatsKind::IpPrefix, ip_prefix(ip)),
+        ];
+        if let Some(target) = target {
+            rows.push((LoginStatsKind::User, target.to_string()));
+        }
+
+        let sql = r#"
+INSERT INTO login_stats (bucket, kind, subject, failed, blacklisted)
+VALUES ($1, $2, $3, 1, $4)
+ON CONFLICT (bucket, kind, subject) DO UPDATE
+SET failed = login_stats.failed + 1, blacklisted = login_stats.blacklisted + $4"#;
+
+        if is_hiqlite() {
+            let txn = rows
+                .into_iter()
+                .map(|(kind, subject)| (sql, params!(bucket, kind.as_str(), subject, blacklisted)))
+                .collect::<Vec<_>>();
+            DB::client().txn(txn).await?;
+        } else {
+            let mut txn = DB::txn().await?;
+            for (kind, subject) in rows {
+                query!(
+                    r#"
+INSERT INTO login_stats (bucket, kind, subject, failed, blacklisted)
+VALUES ($1, $2, $3, 1, $4)
+ON CONFLICT (bucket, kind, subject) DO UPDATE
+SET failed = login_stats.failed + 1, blacklisted = login_stats.blacklisted + $4"#,
+                    bucket,
+                    kind.as_str(),
+                    subject,
+                    blacklisted,
+                )
+                .execute(&mut *txn)
+                .await?;
+            }
+            txn.commit().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the totals for each hour between `from` and `until` (unix seconds).
+    pub async fn find_hourly(from: i64, until: i64) -> Result<Vec<LoginStatsHour>, ErrorResponse> {
+        let kind = LoginStatsKind::Total.as_str();
+
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    r#"
+SELECT bucket, failed, blacklisted FROM login_stats
+WHERE kind = $1 AND bucket >= $2 AND bucket <= $3
+ORDER BY bucket ASC"#,
+                    params!(kind, from, until),
+                )
+                .await?
+        } else {
+            query_as!(
+                LoginStatsHour,
+                r#"
+SELECT bucket, failed, blacklisted FROM login_stats
+WHERE kind = $1 AND bucket >= $2 AND bucket <= $3
+ORDER BY bucket ASC"#,
+                kind,
+                from,
+                until,
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Returns the subjects with the most failed logins between `from` and `until`.
+    pub async fn find_top(
+        kind: LoginStatsKind,
+        from: i64,
+        until: i64,
+        limit: i64,
+    ) -> Result<Vec<LoginStatsTop>, ErrorResponse> {
+        let kind = kind.as_str();
+
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    r#"
+SELECT subject, SUM(failed) AS failed, SUM(blacklisted) AS blacklisted
+FROM login_stats
+WHERE kind = $1 AND bucket >= $2 AND bucket <= $3
+GROUP BY subject
+ORDER BY 2 DESC
+LIMIT $4"#,
+                    params!(kind, from, until, limit),
+                )
+                .await?
+        } else {
+            query_as!(
+                LoginStatsTop,
+                r#"
+SELECT subject, SUM(failed)::bigint AS "failed!", SUM(blacklisted)::bigint AS "blacklisted!"
+FROM login_stats
+WHERE kind = $1 AND bucket >= $2 AND bucket <= $3
+GROUP BY subject
+ORDER BY 2 DESC
+LIMIT $4"#,
+                kind,
+                from,
+                until,
+                limit,
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Deletes all statistics for hours before the given unix timestamp in seconds.
+    pub async fn delete_before(ts: i64) -> Result<u64, ErrorResponse> {
+        let rows_affected = if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM login_stats WHERE bucket < $1", params!(ts))
+                .await? as u64
+        } else {
+            query!("DELETE FROM login_stats WHERE bucket < $1", ts)
+                .execute(DB::conn())
+                .await?
+                .rows_affected()
+        };
+
+        Ok(rows_affected)
+    }
+}
+
+/// Returns the `/24` network for IPv4 and the `/48` network for IPv6.
+fn ip_prefix(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{}.{}.{}.0/24", a, b, c)
+        }
+        IpAddr::V6(ip) => {
+            let s = ip.segments();
+            format!("{:x}:{:x}:{:x}::/48", s[0], s[1], s[2])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_ip_prefix() {
+        let ip = IpAddr::from_str("192.168.14.211").unwrap();
+        assert_eq!(ip_prefix(&ip), "192.168.14.0/24");
+
+        let ip = IpAddr::from_str("2001:db8:abcd:12::1").unwrap();
+        assert_eq!(ip_prefix(&ip), "2001:db8:abcd::/48");
+    }
+}
@@ -0,0 +1,318 @@
+use crate::entity::clients::Client;
+use crate::entity::jwk::{JwkKeyPair, JwkKeyPairAlg};
+use crate::entity::sessions_clients::SessionClient;
+use crate::jwt_claims;
+use jwt_simple::prelude::coarsetime;
+use rauthy_common::constants::RAUTHY_VERSION;
+use rauthy_common::utils::get_rand;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use reqwest::{redirect, tls};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{debug, error, info};
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+pub const LOGOUT_TOKEN_TYP: &str = "logout+jwt";
+pub const EVENT_BACKCHANNEL_LOGOUT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+// Logout tokens are delivered right away -> keep them short-lived
+const LOGOUT_TOKEN_LIFETIME: u64 = 120;
+
+/// The custom claims of a logout token as defined in OpenID Connect Back-Channel Logout 1.0.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogoutTokenClaims {
+    pub events: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+}
+
+/// A client, which needs to be notified about the end of a user's session via its
+/// `backchannel_logout_uri`. Without a `session_id`, the client should end all sessions for
+/// this user.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BackchannelLogout {
+    pub client_id: String,
+    pub user_id: String,
+    pub session_id: Option<String>,
+}
+
+impl BackchannelLogout {
+    /// Returns all clients the user has logged in to during the given session.
+    /// Must be called before the session is deleted.
+    pub async fn find_for_session(
+        session_id: &str,
+        user_id: &str,
+    ) -> Result<Vec<Self>, ErrorResponse> {
+        let res = SessionClient::find_client_ids(session_id)
+            .await?
+            .into_iter()
+            .map(|client_id| Self {
+                client_id,
+                user_id: user_id.to_string(),
+                session_id: Some(session_id.to_string()),
+            })
+            .collect();
+        Ok(res)
+    }
+
+    /// Returns all clients the user has logged in to during any of its sessions.
+    /// Must be called before the sessions are deleted.
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let res = SessionClient::find_for_user(user_id)
+            .await?
+            .into_iter()
+            .map(|sc| Self {
+                client_id: sc.client_id,
+                user_id: user_id.to_string(),
+                session_id: Some(sc.session_id),
+            })
+            .collect();
+        Ok(res)
+    }
+
+    /// Delivers the logout tokens in the background. The sessions have been ended already at
+    /// this point, which means a client that cannot be reached must not fail the request.
+    pub fn send_all(issuer: String, mut logouts: Vec<Self>) {
+        logouts.sort();
+        logouts.dedup();
+        if logouts.is_empty() {
+            return;
+        }
+
+        tokio::spawn(async move {
+            for logout in logouts {
+                if let Err(err) = logout.send(&issuer).await {
+                    error!(
+                        "Back-channel logout for client {} failed: {}",
+                        logout.client_id, err.message
+                    );
+                }
+            }
+        });
+    }
+
+    async fn send(&self, issuer: &str) -> Result<(), ErrorResponse> {
+        let client = match Client::find(self.client_id.clone()).await {
+            Ok(client) => client,
+            Err(err) => {
+                // the client may have been deleted in the meantime
+                debug!("Cannot find client for back-channel logout: {:?}", err);
+                return Ok(());
+            }
+        };
+        let Some(uri) = client.backchannel_logout_uri.as_deref() else {
+            return Ok(());
+        };
+
+        let kp = JwkKeyPair::find_latest(JwkKeyPairAlg::from_str(&client.id_token_alg)?).await?;
+        let token = self.build_token(issuer, &client, &kp).await?;
+        Self::post(uri, token).await?;
+
+        info!(
+            "Back-channel logout for user {} delivered to client {}",
+            self.user_id, client.id
+        );
+        Ok(())
+    }
+
+    /// Builds the signed `logout_token`. It contains the `sub` as the client knows it and the
+    /// `sid`, if a specific session has been ended.
+    async fn build_token(
+        &self,
+        issuer: &str,
+        client: &Client,
+        kp: &JwkKeyPair,
+    ) -> Result<String, ErrorResponse> {
+        let custom_claims = LogoutTokenClaims {
+            events: json!({ EVENT_BACKCHANNEL_LOGOUT: {} }),
+            sid: self.session_id.clone(),
+        };
+        let claims = jwt_claims(
+            custom_claims,
+            coarsetime::Duration::from_secs(LOGOUT_TOKEN_LIFETIME),
+        )
+        .with_issuer(issuer)
+        .with_audience(client.id.clone())
+        .with_subject(client.subject_for(&self.user_id)?)
+        .with_jwt_id(get_rand(32));
+
+        kp.sign_with_typ(&claims, LOGOUT_TOKEN_TYP).await
+    }
+
+    async fn post(uri: &str, token: String) -> Result<(), ErrorResponse> {
+        let client = HTTP_CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(5))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!("Rauthy v{} Back-Channel Logout", RAUTHY_VERSION))
+                .min_tls_version(tls::Version::TLS_1_2)
+                // the logout token must only ever be sent to the registered URI
+                .redirect(redirect::Policy::none())
+                .pool_idle_timeout(Duration::from_secs(600))
+                .build()
+                .unwrap()
+        });
+
+        let res = client
+            .post(uri)
+            .form(&[("logout_token", token)])
+            .send()
+            .await
+            .map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::Connection,
+                    format!("Cannot reach backchannel_logout_uri {}: {}", uri, err),
+                )
+            })?;
+
+        if !res.status().is_success() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Connection,
+                format!(
+                    "backchannel_logout_uri {} responded with {}",
+                    uri,
+                    res.status()
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jwt_simple::prelude::*;
+    use rauthy_common::utils::base64_url_no_pad_decode;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_build_token() {
+        let key = Ed25519KeyPair::generate();
+        let kp = JwkKeyPair {
+            kid: "kid1".to_string(),
+            typ: JwkKeyPairAlg::EdDSA,
+            bytes: key.to_der(),
+            kms_key_id: None,
+        };
+        let client = Client {
+            id: "client1".to_string(),
+            ..Default::default()
+        };
+        let logout = BackchannelLogout {
+            client_id: client.id.clone(),
+            user_id: "user1".to_string(),
+            session_id: Some("sid1".to_string()),
+        };
+
+        let token = logout
+            .build_token("https://iam.example.com/auth/v1", &client, &kp)
+            .await
+            .unwrap();
+
+        let header = token.split('.').next().unwrap();
+        let header =
+            serde_json::from_slice::<Value>(&base64_url_no_pad_decode(header).unwrap()).unwrap();
+        assert_eq!(header["typ"], LOGOUT_TOKEN_TYP);
+        assert_eq!(header["kid"], "kid1");
+
+        let claims = key
+            .public_key()
+            .verify_token::<LogoutTokenClaims>(&token, None)
+            .unwrap();
+        assert_eq!(
+            claims.issuer.as_deref(),
+            Some("https://iam.example.com/auth/v1")
+        );
+        assert_eq!(claims.audiences.unwrap().into_string().unwrap(), "client1");
+        assert_eq!(claims.subject.as_deref(), Some("user1"));
+        assert!(claims.jwt_id.is_some());
+        assert!(claims.nonce.is_none());
+        let exp = claims.expires_at.unwrap().as_secs() - claims.issued_at.unwrap().as_secs();
+        assert_eq!(exp, LOGOUT_TOKEN_LIFETIME);
+        assert_eq!(claims.custom.sid.as_deref(), Some("sid1"));
+        assert_eq!(
+            claims.custom.events,
+            json!({ EVENT_BACKCHANNEL_LOGOUT: {} })
+        );
+
+        // without a session, the client should log out the user completely
+        let logout = BackchannelLogout {
+            session_id: None,
+            ..logout
+        };
+        let token = logout
+            .build_token("https://iam.example.com/auth/v1", &client, &kp)
+            .await
+            .unwrap();
+        let claims = key
+            .public_key()
+            .verify_token::<Value>(&token, None)
+            .unwrap();
+        assert!(claims.custom.get("sid").is_none());
+    }
+
+    async fn serve_once(status: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("http://{}/logout", listener.local_addr().unwrap());
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut req = Vec::new();
+            let mut buf = [0u8; 1024];
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                req.extend_from_slice(&buf[..n]);
+                let s = String::from_utf8_lossy(&req);
+                if let Some((head, body)) = s.split_once("\r\n\r\n") {
+                    let len = head
+                        .lines()
+                        .find_map(|l| {
+                            l.to_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(|v| v.parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if body.len() >= len {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+            stream
+                .write_all(format!("HTTP/1.1 {}\r\ncontent-length: 0\r\n\r\n", status).as_bytes())
+                .await
+                .unwrap();
+            String::from_utf8(req).unwrap()
+        });
+
+        (uri, handle)
+    }
+
+    #[tokio::test]
+    async fn test_post() {
+        let (uri, handle) = serve_once("200 OK").await;
+        BackchannelLogout::post(&uri, "a.b.c".to_string())
+            .await
+            .unwrap();
+        let req = handle.await.unwrap();
+        assert!(req.starts_with("POST /logout HTTP/1.1"));
+        assert!(req
+            .to_lowercase()
+            .contains("content-type: application/x-www-form-urlencoded"));
+        assert!(req.ends_with("\r\n\r\nlogout_token=a.b.c"));
+
+        let (uri, handle) = serve_once("400 Bad Request").await;
+        assert!(BackchannelLogout::post(&uri, "a.b.c".to_string())
+            .await
+            .is_err());
+        handle.await.unwrap();
+    }
+}
@@ -1,21 +1,25 @@
 use crate::api_cookie::ApiCookie;
 use crate::database::DB;
 use actix_web::HttpRequest;
+use cryptr::EncValue;
 use hiqlite::{params, Param};
+use rauthy_common::clock;
 use rauthy_common::constants::{PASSWORD_RESET_COOKIE_BINDING, PWD_CSRF_HEADER, PWD_RESET_COOKIE};
 use rauthy_common::is_hiqlite;
-use rauthy_common::utils::{get_rand, real_ip_from_req};
+use rauthy_common::utils::{
+    base64_decode, base64_encode, constant_time_eq, get_rand, real_ip_from_req,
+};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use std::fmt::{Display, Formatter};
-use time::OffsetDateTime;
 use tracing::warn;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum MagicLinkUsage {
     EmailChange(String),
+    EmailVerify,
     PasswordReset(Option<String>),
     NewUser(Option<String>),
 }
@@ -35,6 +39,7 @@ impl TryFrom<&str> for MagicLinkUsage {
         let (ty, v) = value.split_once('$').unwrap_or((value, ""));
         let slf = match ty {
             "email_change" => MagicLinkUsage::EmailChange(v.to_string()),
+            "email_verify" => MagicLinkUsage::EmailVerify,
             "new_user" => {
                 if !v.is_empty() {
                     MagicLinkUsage::NewUser(Some(v.to_string()))
@@ -67,6 +72,7 @@ impl Display for MagicLinkUsage {
         // It also makes splitting of the value quite easy.
         match self {
             MagicLinkUsage::EmailChange(email) => write!(f, "email_change${}", email),
+            MagicLinkUsage::EmailVerify => write!(f, "email_verify"),
             MagicLinkUsage::NewUser(redirect_uri) => {
                 if let Some(uri) = redirect_uri {
                     write!(f, "new_user${}", uri)
@@ -85,6 +91,9 @@ impl Display for MagicLinkUsage {
     }
 }
 
+/// The `csrf_token` and the binding `cookie` are always stored encrypted with the current
+/// value encryption key inside the database. The values inside this struct are the plain ones
+/// after `find()`ing a link.
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct MagicLink {
     pub id: String,
@@ -104,7 +113,7 @@ impl MagicLink {
         usage: MagicLinkUsage,
     ) -> Result<Self, ErrorResponse> {
         let id = get_rand(64);
-        let exp = OffsetDateTime::now_utc().unix_timestamp() + lifetime_minutes * 60;
+        let exp = clock::now_utc().unix_timestamp() + lifetime_minutes * 60;
         let link = MagicLink {
             id,
             user_id,
@@ -114,6 +123,7 @@ impl MagicLink {
             used: false,
             usage: usage.to_string(),
         };
+        let csrf_token_enc = Self::encrypt_secret(&link.csrf_token)?;
 
         if is_hiqlite() {
             DB::client()
@@ -124,7 +134,7 @@ VALUES ($1, $2, $3, $4, $5, $6)"#,
                     params!(
                         link.id.clone(),
                         link.user_id.clone(),
-                        link.csrf_token.clone(),
+                        csrf_token_enc,
                         link.exp,
                         false,
                         link.usage.clone()
@@ -138,7 +148,7 @@ INSERT INTO magic_links (id, user_id, csrf_token, exp, used, usage)
 VALUES ($1, $2, $3, $4, $5, $6)"#,
                 link.id,
                 link.user_id,
-                link.csrf_token,
+                csrf_token_enc,
                 link.exp,
                 false,
                 link.usage,
@@ -151,7 +161,7 @@ VALUES ($1, $2, $3, $4, $5, $6)"#,
     }
 
     pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
-        let res = if is_hiqlite() {
+        let res: Self = if is_hiqlite() {
             DB::client()
                 .query_as_one("SELECT * FROM magic_links WHERE id = $1", params!(id))
                 .await?
@@ -161,11 +171,11 @@ VALUES ($1, $2, $3, $4, $5, $6)"#,
                 .await?
         };
 
-        Ok(res)
+        Ok(res.decrypt_secrets())
     }
 
     pub async fn find_by_user(user_id: String) -> Result<MagicLink, ErrorResponse> {
-        let res = if is_hiqlite() {
+        let res: Self = if is_hiqlite() {
             DB::client()
                 .query_as_one(
                     "SELECT * FROM magic_links WHERE user_id = $1",
@@ -182,7 +192,7 @@ VALUES ($1, $2, $3, $4, $5, $6)"#,
             .await?
         };
 
-        Ok(res)
+        Ok(res.decrypt_secrets())
     }
 
     pub async fn invalidate_all_email_change(user_id: &str) -> Result<(), ErrorResponse> {
@@ -205,18 +215,43 @@ VALUES ($1, $2, $3, $4, $5, $6)"#,
         Ok(())
     }
 
+    pub async fn invalidate_all_email_verify(user_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM magic_links WHERE user_id = $1 AND USAGE = 'email_verify'",
+                    params!(user_id),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                "DELETE FROM magic_links WHERE user_id = $1 AND USAGE = 'email_verify'",
+                user_id,
+            )
+            .execute(DB::conn())
+            .await?;
+        };
+
+        Ok(())
+    }
+
     pub async fn save(&self) -> Result<(), ErrorResponse> {
+        let cookie_enc = match &self.cookie {
+            None => None,
+            Some(cookie) => Some(Self::encrypt_secret(cookie)?),
+        };
+
         if is_hiqlite() {
             DB::client()
                 .execute(
                     "UPDATE magic_links SET cookie = $1, exp = $2, used = $3 WHERE id = $4",
-                    params!(self.cookie.clone(), self.exp, self.used, self.id.clone()),
+                    params!(cookie_enc, self.exp, self.used, self.id.clone()),
                 )
                 .await?;
         } else {
             sqlx::query!(
                 "UPDATE magic_links SET cookie = $1, exp = $2, used = $3 WHERE id = $4",
-                self.cookie,
+                cookie_enc,
                 self.exp,
                 self.used,
                 self.id,
@@ -230,8 +265,37 @@ VALUES ($1, $2, $3, $4, $5, $6)"#,
 }
 
 impl MagicLink {
+    fn encrypt_secret(value: &str) -> Result<String, ErrorResponse> {
+        let enc = EncValue::encrypt(value.as_bytes())?.into_bytes();
+        Ok(base64_encode(enc.as_ref()))
+    }
+
+    /// Links, which have been created before the encryption at rest was introduced, contain
+    /// plain values. These will be returned as they are and just expire naturally.
+    fn decrypt_secret(value: String) -> String {
+        let dec = base64_decode(&value)
+            .ok()
+            .and_then(|bytes| EncValue::try_from(bytes).ok())
+            .and_then(|enc| enc.decrypt().ok());
+        match dec {
+            Some(bytes) => String::from_utf8_lossy(bytes.as_ref()).to_string(),
+            None => value,
+        }
+    }
+
+    fn decrypt_secrets(mut self) -> Self {
+        self.csrf_token = Self::decrypt_secret(self.csrf_token);
+        self.cookie = self.cookie.map(Self::decrypt_secret);
+        self
+    }
+
+    /// Validates the given CSRF token against this link in constant time.
+    pub fn is_valid_csrf_token(&self, token: &str) -> bool {
+        constant_time_eq(self.csrf_token.as_bytes(), token.as_bytes())
+    }
+
     pub async fn invalidate(&mut self) -> Result<(), ErrorResponse> {
-        self.exp = OffsetDateTime::now_utc().unix_timestamp() - 10;
+        self.exp = clock::now_utc().unix_timestamp() - 10;
         self.save().await
     }
 
@@ -242,7 +306,7 @@ impl MagicLink {
         with_csrf: bool,
     ) -> Result<(), ErrorResponse> {
         // binding cookie
-        if self.cookie.is_some() {
+        if let Some(binding) = &self.cookie {
             let err = ErrorResponse::new(
                 ErrorResponseType::Forbidden,
                 "The requested password reset link is already tied to another session",
@@ -250,8 +314,7 @@ impl MagicLink {
 
             let cookie_opt = ApiCookie::from_req(req, PWD_RESET_COOKIE);
             if let Some(cookie) = cookie_opt {
-                // the extracted cookie from the request starts with 'rauthy-pwd-reset='
-                if !cookie.ends_with(self.cookie.as_ref().unwrap()) {
+                if !constant_time_eq(cookie.as_bytes(), binding.as_bytes()) {
                     if *PASSWORD_RESET_COOKIE_BINDING {
                         return Err(err);
                     } else {
@@ -277,7 +340,7 @@ impl MagicLink {
                     ));
                 }
                 Some(token) => {
-                    if self.csrf_token != token.to_str().unwrap_or("") {
+                    if !self.is_valid_csrf_token(token.to_str().unwrap_or("")) {
                         return Err(ErrorResponse::new(
                             ErrorResponseType::Unauthorized,
                             "Invalid CSRF Token",
@@ -294,7 +357,7 @@ impl MagicLink {
             ));
         }
 
-        if self.exp < OffsetDateTime::now_utc().unix_timestamp() {
+        if self.exp < clock::now_utc().unix_timestamp() {
             return Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
                 "This link has expired already",
@@ -357,5 +420,10 @@ mod tests {
         let s = ml.to_string();
         let ml_from = MagicLinkUsage::try_from(&s).unwrap();
         assert_eq!(ml, ml_from);
+
+        let ml = MagicLinkUsage::EmailVerify;
+        let s = ml.to_string();
+        let ml_from = MagicLinkUsage::try_from(&s).unwrap();
+        assert_eq!(ml, ml_from);
     }
 }
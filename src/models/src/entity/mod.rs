@@ -5,38 +5,68 @@ use sqlx::query;
 
 pub mod api_keys;
 pub mod app_version;
+pub mod audit_log;
 pub mod auth_codes;
 mod auth_provider_cust_impl;
 pub mod auth_providers;
+pub mod claims_request;
+pub mod client_assertions;
 pub mod clients;
+pub mod clients_access;
 pub mod clients_dyn;
+pub mod clients_user_metadata;
 pub mod colors;
 pub mod config;
+pub mod consents;
 pub mod continuation_token;
 pub mod db_version;
 pub mod devices;
 pub mod dpop_proof;
+pub mod email_otp;
 pub mod fed_cm;
+pub mod federation;
+pub mod fido_mds;
 pub mod groups;
 pub mod ip_rate_limit;
+pub mod jti_denylist;
+pub mod jwe;
 pub mod jwk;
 pub mod jwk_token_validation;
+pub mod login_history;
+pub mod login_stats;
 pub mod logos;
+pub mod logout_token;
 pub mod magic_links;
+pub mod mtls;
+pub mod opaque_tokens;
+pub mod pairwise_subjects;
 pub mod password;
+pub mod password_expiry_reminders;
 pub mod pow;
 pub mod principal;
+pub mod recovery_codes;
 pub mod refresh_tokens;
 pub mod refresh_tokens_devices;
+pub mod request_objects;
+pub mod role_mapping;
 pub mod roles;
 pub mod scopes;
+pub mod session_devices;
 pub mod sessions;
+pub mod sessions_clients;
+pub mod sms_otp;
+pub mod tos;
+pub mod totp;
+pub mod trusted_devices;
 pub mod user_attr;
+pub mod user_expiry_notifications;
+pub mod user_pictures;
 pub mod users;
 pub mod users_values;
 pub mod webauthn;
 pub mod webids;
 pub mod well_known;
+pub mod yubico_otp;
 
 pub async fn is_db_alive() -> bool {
     if is_hiqlite() {
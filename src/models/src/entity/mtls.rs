@@ -0,0 +1,197 @@
+use crate::entity::clients::Client;
+use actix_web::HttpRequest;
+use percent_encoding::percent_decode_str;
+use rauthy_common::constants::MTLS_CLIENT_CERT_HEADER;
+use rauthy_common::utils::{base64_decode, base64_url_no_pad_encode, is_trusted_proxy_req};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use ring::digest;
+use serde_json::Value;
+use std::io::BufReader;
+use tracing::warn;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// A TLS client certificate (RFC 8705), which has been forwarded by a trusted reverse proxy
+/// after a successful mTLS handshake.
+#[derive(Debug)]
+pub struct ClientCert {
+    der: Vec<u8>,
+}
+
+impl ClientCert {
+    /// Extracts the client certificate from the `MTLS_CLIENT_CERT_HEADER`, if it exists.
+    /// The header will only be accepted from one of the `TRUSTED_PROXIES`.
+    pub fn opt_from_req(req: &HttpRequest) -> Result<Option<Self>, ErrorResponse> {
+        let Some(header_name) = &*MTLS_CLIENT_CERT_HEADER else {
+            return Ok(None);
+        };
+        let Some(value) = req.headers().get(header_name) else {
+            return Ok(None);
+        };
+
+        if !is_trusted_proxy_req(req) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "The client certificate header is only accepted from trusted proxies",
+            ));
+        }
+
+        let value = value.to_str().map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Invalid client certificate header",
+            )
+        })?;
+        // some proxies always set the header, even without a client certificate
+        if value.is_empty() {
+            return Ok(None);
+        }
+
+        Self::parse(value).map(Some)
+    }
+
+    /// The base64 url encoded SHA-256 thumbprint of the certificate, which will be used as the
+    /// `x5t#S256` confirmation claim.
+    pub fn x5t_s256(&self) -> String {
+        let hash = digest::digest(&digest::SHA256, &self.der);
+        base64_url_no_pad_encode(hash.as_ref())
+    }
+
+    /// Validates the certificate against the mTLS client authentication of the given client.
+    pub fn validate_for_client(&self, client: &Client) -> Result<(), ErrorResponse> {
+        let (_, cert) = X509Certificate::from_der(&self.der).map_err(|_| {
+            ErrorResponse::new(ErrorResponseType::BadRequest, "Invalid client certificate")
+        })?;
+        if !cert.validity().is_valid() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "The client certificate is expired or not valid yet",
+            ));
+        }
+
+        let is_valid = match client.tls_client_auth.as_deref() {
+            // the chain has been validated by the reverse proxy during the handshake already
+            Some("tls_client_auth") => Self::dn_matches(
+                &cert.subject().to_string(),
+                client
+                    .tls_client_auth_subject_dn
+                    .as_deref()
+                    .unwrap_or_default(),
+            ),
+            Some("self_signed_tls_client_auth") => self.is_registered(client)?,
+            _ => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "mTLS client authentication is not enabled for this client",
+                ));
+            }
+        };
+
+        if !is_valid {
+            warn!("Invalid client certificate for client '{}'", client.id);
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "Invalid client certificate",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl ClientCert {
+    fn parse(value: &str) -> Result<Self, ErrorResponse> {
+        // nginx sends the URL encoded PEM, while others like traefik send the base64 DER
+        let decoded = percent_decode_str(value).decode_utf8_lossy();
+        let der = if decoded.contains("-----BEGIN") {
+            let mut reader = BufReader::new(decoded.as_bytes());
+            let cert = rustls_pemfile::certs(&mut reader)
+                .next()
+                .and_then(|cert| cert.ok())
+                .ok_or_else(|| {
+                    ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        "Invalid client certificate PEM",
+                    )
+                })?;
+            cert.to_vec()
+        } else {
+            base64_decode(decoded.trim())?
+        };
+
+        X509Certificate::from_der(&der).map_err(|_| {
+            ErrorResponse::new(ErrorResponseType::BadRequest, "Invalid client certificate")
+        })?;
+
+        Ok(Self { der })
+    }
+
+    /// Checks if the certificate matches the first `x5c` entry of any key in the clients JWKS.
+    fn is_registered(&self, client: &Client) -> Result<bool, ErrorResponse> {
+        let Some(jwks) = &client.jwks else {
+            return Ok(false);
+        };
+        let jwks = serde_json::from_str::<Value>(jwks)?;
+
+        let is_registered = jwks
+            .get("keys")
+            .and_then(|keys| keys.as_array())
+            .map(|keys| {
+                keys.iter().any(|key| {
+                    key.get("x5c")
+                        .and_then(|x5c| x5c.get(0))
+                        .and_then(|cert| cert.as_str())
+                        .and_then(|cert| base64_decode(cert).ok())
+                        .map(|der| der == self.der)
+                        .unwrap_or(false)
+                })
+            })
+            .unwrap_or(false);
+        Ok(is_registered)
+    }
+
+    /// Compares 2 distinguished names while ignoring the order of the RDNs, whitespace and the
+    /// case of the attribute types.
+    fn dn_matches(given: &str, expected: &str) -> bool {
+        let normalize = |dn: &str| {
+            let mut rdns = dn
+                .split(',')
+                .map(|rdn| {
+                    let (typ, value) = rdn.split_once('=').unwrap_or((rdn, ""));
+                    format!("{}={}", typ.trim().to_uppercase(), value.trim())
+                })
+                .collect::<Vec<_>>();
+            rdns.sort();
+            rdns
+        };
+
+        !expected.trim().is_empty() && normalize(given) == normalize(expected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dn_matches() {
+        let given = "C=DE, O=Rauthy, CN=my-client";
+        assert!(ClientCert::dn_matches(
+            given,
+            "C=DE, O=Rauthy, CN=my-client"
+        ));
+        assert!(ClientCert::dn_matches(given, "cn=my-client,o=Rauthy,c=DE"));
+        assert!(!ClientCert::dn_matches(given, "CN=my-client,O=Rauthy"));
+        assert!(!ClientCert::dn_matches(given, "CN=other,O=Rauthy,C=DE"));
+        assert!(!ClientCert::dn_matches(given, ""));
+    }
+
+    #[test]
+    fn test_x5t_s256() {
+        let cert = ClientCert {
+            der: b"test".to_vec(),
+        };
+        assert_eq!(
+            cert.x5t_s256(),
+            "n4bQgYhMfWWaL-qgxVrQFaO_TxsrC4Is0V1sFbDwCgg"
+        );
+    }
+}
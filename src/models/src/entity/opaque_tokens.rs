@@ -0,0 +1,274 @@
+use crate::database::DB;
+use crate::entity::jti_denylist::JtiDenylist;
+use hiqlite::{params, Param};
+use jwt_simple::claims::JWTClaims;
+use rauthy_common::clock;
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::{base64_url_no_pad_encode, get_rand};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+// The length of the opaque reference handed out to the client
+const OPAQUE_TOKEN_LEN: usize = 48;
+
+/// An opaque access token, which is only a random reference to the token claims stored on the
+/// server. Clients and resource servers can only resolve it via the introspection endpoint, and it
+/// can be revoked at any time by deleting it. No signed JWT exists for it, so a leaked database
+/// never contains usable tokens.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct OpaqueToken {
+    // the SHA-256 hash of the reference, the reference itself is never stored
+    pub id: String,
+    pub client_id: String,
+    pub user_id: Option<String>,
+    pub session_id: Option<String>,
+    pub exp: i64,
+    // the access token claims as JSON
+    pub claims: String,
+}
+
+// CRUD
+impl OpaqueToken {
+    /// Stores the given access token claims and returns the opaque reference for them.
+    pub async fn create(
+        client_id: String,
+        user_id: Option<String>,
+        session_id: Option<String>,
+        exp: i64,
+        claims: String,
+    ) -> Result<String, ErrorResponse> {
+        let reference = get_rand(OPAQUE_TOKEN_LEN);
+        let id = Self::hash(&reference);
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO opaque_tokens (id, client_id, user_id, session_id, exp, claims)
+VALUES ($1, $2, $3, $4, $5, $6)"#,
+                    params!(id, client_id, user_id, session_id, exp, claims),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                r#"
+INSERT INTO opaque_tokens (id, client_id, user_id, session_id, exp, claims)
+VALUES ($1, $2, $3, $4, $5, $6)"#,
+                id,
+                client_id,
+                user_id,
+                session_id,
+                exp,
+                claims,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(reference)
+    }
+
+    /// Deletes the token for the given opaque reference, if it exists.
+    pub async fn delete(reference: &str) -> Result<(), ErrorResponse> {
+        let id = Self::hash(reference);
+
+        if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM opaque_tokens WHERE id = $1", params!(id))
+                .await?;
+        } else {
+            sqlx::query!("DELETE FROM opaque_tokens WHERE id = $1", id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_expired() -> Result<(), ErrorResponse> {
+        let now = clock::now().timestamp();
+
+        if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM opaque_tokens WHERE exp < $1", params!(now))
+                .await?;
+        } else {
+            sqlx::query!("DELETE FROM opaque_tokens WHERE exp < $1", now)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as("SELECT * FROM opaque_tokens", params!())
+                .await?
+        } else {
+            sqlx::query_as!(Self, "SELECT * FROM opaque_tokens")
+                .fetch_all(DB::conn())
+                .await?
+        };
+        Ok(res)
+    }
+
+    pub async fn invalidate_all() -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM opaque_tokens", params!())
+                .await?;
+        } else {
+            sqlx::query!("DELETE FROM opaque_tokens")
+                .execute(DB::conn())
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn invalidate_for_client(client_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM opaque_tokens WHERE client_id = $1",
+                    params!(client_id),
+                )
+                .await?;
+        } else {
+            sqlx::query!("DELETE FROM opaque_tokens WHERE client_id = $1", client_id)
+                .execute(DB::conn())
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn invalidate_for_session(session_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM opaque_tokens WHERE session_id = $1",
+                    params!(session_id),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                "DELETE FROM opaque_tokens WHERE session_id = $1",
+                session_id
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn invalidate_for_user(user_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM opaque_tokens WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?;
+        } else {
+            sqlx::query!("DELETE FROM opaque_tokens WHERE user_id = $1", user_id)
+                .execute(DB::conn())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Resolves the opaque reference into the claims it has been issued for. The claims go
+    /// through the same revocation checks as a JWT access token.
+    pub async fn resolve<T>(reference: &str) -> Result<JWTClaims<T>, ErrorResponse>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let id = Self::hash(reference);
+        let now = clock::now().timestamp();
+
+        let slf = if is_hiqlite() {
+            DB::client()
+                .query_as_one::<Self, _>(
+                    "SELECT * FROM opaque_tokens WHERE id = $1 AND exp > $2",
+                    params!(id, now),
+                )
+                .await
+                .ok()
+        } else {
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM opaque_tokens WHERE id = $1 AND exp > $2",
+                id,
+                now
+            )
+            .fetch_optional(DB::conn())
+            .await?
+        };
+
+        let Some(slf) = slf else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                "The access token does not exist or has expired",
+            ));
+        };
+
+        JtiDenylist::validate_claims(slf.claims.as_bytes()).await?;
+        let claims = serde_json::from_str::<JWTClaims<T>>(&slf.claims)?;
+        Ok(claims)
+    }
+}
+
+impl OpaqueToken {
+    /// Opaque references never contain a `.`, while a JWT always does.
+    #[inline]
+    pub fn is_opaque(token: &str) -> bool {
+        !token.contains('.')
+    }
+
+    fn hash(reference: &str) -> String {
+        let hash = digest::digest(&digest::SHA256, reference.as_bytes());
+        base64_url_no_pad_encode(hash.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JwtCommonClaims, JwtTokenType};
+    use jwt_simple::prelude::{Claims, Duration};
+    use serde_json::json;
+
+    #[test]
+    fn test_is_opaque() {
+        assert!(OpaqueToken::is_opaque(&get_rand(OPAQUE_TOKEN_LEN)));
+        assert!(!OpaqueToken::is_opaque(
+            "eyJhbGciOiJFZERTQSJ9.eyJzdWIiOiIxIn0.c2ln"
+        ));
+        assert_ne!(OpaqueToken::hash("ref1"), OpaqueToken::hash("ref2"));
+    }
+
+    #[test]
+    fn test_claims_roundtrip() {
+        // the stored claims must resolve just like the ones from a validated JWT
+        let claims = Claims::with_custom_claims(
+            json!({ "typ": "Bearer", "azp": "client1", "sid": "sid1", "cnf": null }),
+            Duration::from_secs(60),
+        )
+        .with_jwt_id("jti1")
+        .with_subject("user1");
+        let json = serde_json::to_string(&claims).unwrap();
+
+        let res = serde_json::from_str::<JWTClaims<JwtCommonClaims>>(&json).unwrap();
+        assert_eq!(res.custom.typ, JwtTokenType::Bearer);
+        assert_eq!(res.custom.azp, "client1");
+        assert_eq!(res.subject.as_deref(), Some("user1"));
+        assert_eq!(res.jwt_id.as_deref(), Some("jti1"));
+        assert_eq!(
+            res.expires_at.map(|exp| exp.as_secs()),
+            claims.expires_at.map(|exp| exp.as_secs())
+        );
+    }
+}
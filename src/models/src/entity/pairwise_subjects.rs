@@ -0,0 +1,181 @@
+use crate::entity::clients::Client;
+use rauthy_common::constants::{PAIRWISE_SUBJECT_SALT, RAUTHY_VERSION};
+use rauthy_common::utils::base64_url_no_pad_encode;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use reqwest::header::ACCEPT;
+use reqwest::{tls, Url};
+use ring::hmac;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::error;
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+// The max size of a document fetched from a `sector_identifier_uri`
+const MAX_SECTOR_DOC_SIZE: usize = 16 * 1024;
+
+/// Pairwise subject identifiers (OIDC Core 8.1). Each client sector receives its own stable
+/// `sub` for the same user, which prevents the correlation of users across clients.
+pub struct PairwiseSubject;
+
+impl PairwiseSubject {
+    /// Calculates the pairwise `sub` for the given sector and user id.
+    pub fn calculate(sector: &str, user_id: &str) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, PAIRWISE_SUBJECT_SALT.as_bytes());
+        let mut ctx = hmac::Context::with_key(&key);
+        ctx.update(sector.as_bytes());
+        // the separator makes sure that different sector / user combinations can never collide
+        ctx.update(b"\n");
+        ctx.update(user_id.as_bytes());
+        base64_url_no_pad_encode(ctx.sign().as_ref())
+    }
+
+    /// Returns the sector of the client, which is the host of the `sector_identifier_uri`, or
+    /// the host of the `redirect_uris` if there is none.
+    pub fn sector(client: &Client) -> Result<String, ErrorResponse> {
+        if let Some(uri) = &client.sector_identifier_uri {
+            return Self::host(uri);
+        }
+
+        let mut hosts = client
+            .get_redirect_uris()
+            .iter()
+            .map(|uri| Self::host(uri))
+            .collect::<Result<Vec<_>, _>>()?;
+        hosts.sort();
+        hosts.dedup();
+
+        match hosts.pop() {
+            Some(host) if hosts.is_empty() => Ok(host),
+            _ => Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Pairwise subjects with 'redirect_uris' on multiple hosts need a \
+                'sector_identifier_uri'",
+            )),
+        }
+    }
+
+    /// Fetches the clients `sector_identifier_uri` and makes sure, that it contains all
+    /// `redirect_uris`. Without a `sector_identifier_uri`, only the `sector` will be validated.
+    pub async fn validate_client(client: &Client) -> Result<(), ErrorResponse> {
+        if client.subject_type.as_deref() != Some("pairwise") {
+            return Ok(());
+        }
+
+        let Some(uri) = &client.sector_identifier_uri else {
+            return Self::sector(client).map(|_| ());
+        };
+        if !uri.starts_with("https://") {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The 'sector_identifier_uri' must use https",
+            ));
+        }
+
+        let uris = Self::fetch(uri).await?;
+        if let Some(missing) = client
+            .get_redirect_uris()
+            .into_iter()
+            .find(|redirect_uri| !uris.contains(redirect_uri))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "The 'sector_identifier_uri' does not contain the redirect uri '{}'",
+                    missing
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl PairwiseSubject {
+    fn host(uri: &str) -> Result<String, ErrorResponse> {
+        Url::parse(uri)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Cannot extract the host from '{}'", uri),
+                )
+            })
+    }
+
+    async fn fetch(uri: &str) -> Result<Vec<String>, ErrorResponse> {
+        let http_client = HTTP_CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!(
+                    "Rauthy v{} Sector Identifier Resolver",
+                    RAUTHY_VERSION
+                ))
+                .min_tls_version(tls::Version::TLS_1_2)
+                .pool_idle_timeout(Duration::from_secs(600))
+                .build()
+                .unwrap()
+        });
+
+        let res = http_client
+            .get(uri)
+            .header(ACCEPT, "application/json")
+            .send()
+            .await
+            .map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Cannot fetch sector identifier from {}: {:?}", uri, err),
+                )
+            })?;
+
+        if !res.status().is_success() {
+            let msg = format!("Cannot fetch sector identifier from {}", uri);
+            error!("{}", msg);
+            return Err(ErrorResponse::new(ErrorResponseType::Connection, msg));
+        }
+
+        let body = res.text().await?;
+        if body.len() > MAX_SECTOR_DOC_SIZE {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The sector identifier document is too large",
+            ));
+        }
+
+        serde_json::from_str::<Vec<String>>(&body).map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The sector identifier document must be a JSON array of redirect uris",
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calculate() {
+        let sub = PairwiseSubject::calculate("client.example.com", "user_id");
+        assert_eq!(
+            sub,
+            PairwiseSubject::calculate("client.example.com", "user_id")
+        );
+        assert_ne!(
+            sub,
+            PairwiseSubject::calculate("other.example.com", "user_id")
+        );
+        assert_ne!(
+            sub,
+            PairwiseSubject::calculate("client.example.com", "other_id")
+        );
+        assert_ne!(
+            PairwiseSubject::calculate("a", "bc"),
+            PairwiseSubject::calculate("ab", "c")
+        );
+    }
+}
@@ -1,4 +1,6 @@
 use crate::database::{Cache, DB};
+use crate::entity::groups::Group;
+use crate::entity::users::User;
 use actix_web::web;
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, PasswordHasher, Version};
@@ -9,12 +11,14 @@ use rauthy_api_types::generic::{
 };
 use rauthy_common::constants::{
     ARGON2ID_M_COST_MIN, ARGON2ID_T_COST_MIN, CACHE_TTL_APP, IDX_PASSWORD_RULES,
+    IDX_PASSWORD_RULES_GROUPS,
 };
 use rauthy_common::is_hiqlite;
 use rauthy_error::ErrorResponse;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, Row};
-use std::cmp::max;
+use std::cmp::{max, min};
+use std::collections::HashSet;
 use tokio::time;
 use utoipa::ToSchema;
 
@@ -181,6 +185,63 @@ impl PasswordPolicy {
 }
 
 impl PasswordPolicy {
+    /// Resolves the effective policy for the given user. If the user is a member of any groups
+    /// with their own policy, the strictest combination of all of them will be used instead of
+    /// the global policy.
+    pub async fn find_for_user(user: &User) -> Result<Self, ErrorResponse> {
+        if user.groups.is_none() {
+            return Self::find().await;
+        }
+
+        let policies = PasswordPolicyGroup::find_all().await?;
+        if policies.is_empty() {
+            return Self::find().await;
+        }
+
+        let user_groups = user.get_groups();
+        let group_ids = Group::find_all()
+            .await?
+            .into_iter()
+            .filter(|g| user_groups.contains(&g.name))
+            .map(|g| g.id)
+            .collect::<HashSet<String>>();
+
+        let effective = policies
+            .into_iter()
+            .filter(|p| group_ids.contains(&p.group_id))
+            .map(Self::from)
+            .reduce(Self::strictest);
+
+        match effective {
+            Some(policy) => Ok(policy),
+            None => Self::find().await,
+        }
+    }
+
+    /// Combines both policies and always keeps the stricter value for each rule.
+    pub fn strictest(self, other: Self) -> Self {
+        let max_opt = |a: Option<i32>, b: Option<i32>| match (a, b) {
+            (Some(a), Some(b)) => Some(max(a, b)),
+            (a, b) => a.or(b),
+        };
+
+        let length_min = max(self.length_min, other.length_min);
+        Self {
+            length_min,
+            length_max: max(min(self.length_max, other.length_max), length_min),
+            include_lower_case: max_opt(self.include_lower_case, other.include_lower_case),
+            include_upper_case: max_opt(self.include_upper_case, other.include_upper_case),
+            include_digits: max_opt(self.include_digits, other.include_digits),
+            include_special: max_opt(self.include_special, other.include_special),
+            // a password, that never expires, is the less strict option here
+            valid_days: match (self.valid_days, other.valid_days) {
+                (Some(a), Some(b)) => Some(min(a, b)),
+                (a, b) => a.or(b),
+            },
+            not_recently_used: max_opt(self.not_recently_used, other.not_recently_used),
+        }
+    }
+
     pub fn apply_req(&mut self, req: PasswordPolicyRequest) {
         self.length_min = req.length_min;
         self.length_max = req.length_max;
@@ -208,6 +269,163 @@ impl From<PasswordPolicy> for PasswordPolicyResponse {
     }
 }
 
+/// A password policy bound to a group, which overrides the global policy for its members.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct PasswordPolicyGroup {
+    pub group_id: String,
+    pub length_min: i32,
+    pub length_max: i32,
+    pub include_lower_case: Option<i32>,
+    pub include_upper_case: Option<i32>,
+    pub include_digits: Option<i32>,
+    pub include_special: Option<i32>,
+    pub valid_days: Option<i32>,
+    pub not_recently_used: Option<i32>,
+}
+
+// CRUD
+impl PasswordPolicyGroup {
+    pub async fn find(group_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        Ok(Self::find_all()
+            .await?
+            .into_iter()
+            .find(|p| p.group_id == group_id))
+    }
+
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let client = DB::client();
+        if let Some(slf) = client.get(Cache::App, IDX_PASSWORD_RULES_GROUPS).await? {
+            return Ok(slf);
+        }
+
+        let res = if is_hiqlite() {
+            client
+                .query_as("SELECT * FROM password_policies_groups", params!())
+                .await?
+        } else {
+            sqlx::query_as!(Self, "SELECT * FROM password_policies_groups")
+                .fetch_all(DB::conn())
+                .await?
+        };
+
+        client
+            .put(Cache::App, IDX_PASSWORD_RULES_GROUPS, &res, CACHE_TTL_APP)
+            .await?;
+
+        Ok(res)
+    }
+
+    pub async fn upsert(
+        group_id: String,
+        req: PasswordPolicyRequest,
+    ) -> Result<Self, ErrorResponse> {
+        let slf = Self {
+            group_id,
+            length_min: req.length_min,
+            length_max: req.length_max,
+            include_lower_case: req.include_lower_case,
+            include_upper_case: req.include_upper_case,
+            include_digits: req.include_digits,
+            include_special: req.include_special,
+            valid_days: req.valid_days,
+            not_recently_used: req.not_recently_used,
+        };
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO password_policies_groups
+(group_id, length_min, length_max, include_lower_case, include_upper_case, include_digits,
+include_special, valid_days, not_recently_used)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+ON CONFLICT(group_id) DO UPDATE
+SET length_min = $2, length_max = $3, include_lower_case = $4, include_upper_case = $5,
+include_digits = $6, include_special = $7, valid_days = $8, not_recently_used = $9"#,
+                    params!(
+                        slf.group_id.clone(),
+                        slf.length_min,
+                        slf.length_max,
+                        slf.include_lower_case,
+                        slf.include_upper_case,
+                        slf.include_digits,
+                        slf.include_special,
+                        slf.valid_days,
+                        slf.not_recently_used
+                    ),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                r#"
+INSERT INTO password_policies_groups
+(group_id, length_min, length_max, include_lower_case, include_upper_case, include_digits,
+include_special, valid_days, not_recently_used)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+ON CONFLICT(group_id) DO UPDATE
+SET length_min = $2, length_max = $3, include_lower_case = $4, include_upper_case = $5,
+include_digits = $6, include_special = $7, valid_days = $8, not_recently_used = $9"#,
+                slf.group_id,
+                slf.length_min,
+                slf.length_max,
+                slf.include_lower_case,
+                slf.include_upper_case,
+                slf.include_digits,
+                slf.include_special,
+                slf.valid_days,
+                slf.not_recently_used,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        DB::client()
+            .delete(Cache::App, IDX_PASSWORD_RULES_GROUPS)
+            .await?;
+
+        Ok(slf)
+    }
+
+    pub async fn delete(group_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM password_policies_groups WHERE group_id = $1",
+                    params!(group_id),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                "DELETE FROM password_policies_groups WHERE group_id = $1",
+                group_id
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        DB::client()
+            .delete(Cache::App, IDX_PASSWORD_RULES_GROUPS)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl From<PasswordPolicyGroup> for PasswordPolicy {
+    fn from(value: PasswordPolicyGroup) -> Self {
+        Self {
+            length_min: value.length_min,
+            length_max: value.length_max,
+            include_lower_case: value.include_lower_case,
+            include_upper_case: value.include_upper_case,
+            include_digits: value.include_digits,
+            include_special: value.include_special,
+            valid_days: value.valid_days,
+            not_recently_used: value.not_recently_used,
+        }
+    }
+}
+
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct RecentPasswordsEntity {
     pub user_id: String,
@@ -277,3 +495,42 @@ impl RecentPasswordsEntity {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_policy_strictest() {
+        let global = PasswordPolicy {
+            length_min: 14,
+            length_max: 128,
+            include_lower_case: Some(1),
+            include_upper_case: Some(1),
+            include_digits: Some(1),
+            include_special: None,
+            valid_days: None,
+            not_recently_used: Some(3),
+        };
+        let admins = PasswordPolicy {
+            length_min: 24,
+            length_max: 64,
+            include_lower_case: None,
+            include_upper_case: Some(2),
+            include_digits: Some(1),
+            include_special: Some(2),
+            valid_days: Some(90),
+            not_recently_used: None,
+        };
+
+        let res = global.strictest(admins);
+        assert_eq!(res.length_min, 24);
+        assert_eq!(res.length_max, 64);
+        assert_eq!(res.include_lower_case, Some(1));
+        assert_eq!(res.include_upper_case, Some(2));
+        assert_eq!(res.include_digits, Some(1));
+        assert_eq!(res.include_special, Some(2));
+        assert_eq!(res.valid_days, Some(90));
+        assert_eq!(res.not_recently_used, Some(3));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::database::DB;
+use hiqlite::{params, Param};
+use rauthy_common::clock;
+use rauthy_common::is_hiqlite;
+use rauthy_error::ErrorResponse;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+/// Keeps track of already sent password expiry reminders. The `password_expires` is part of the
+/// primary key, which means that a new password automatically starts a fresh set of reminders.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct PasswordExpiryReminder {
+    pub user_id: String,
+    pub password_expires: i64,
+    pub remind_days: i64,
+    pub sent: i64,
+}
+
+impl PasswordExpiryReminder {
+    pub async fn insert(
+        user_id: String,
+        password_expires: i64,
+        remind_days: i64,
+    ) -> Result<(), ErrorResponse> {
+        let sent = clock::now().timestamp();
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO password_expiry_reminders (user_id, password_expires, remind_days, sent)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT DO NOTHING"#,
+                    params!(user_id, password_expires, remind_days, sent),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO password_expiry_reminders (user_id, password_expires, remind_days, sent)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT DO NOTHING"#,
+                user_id,
+                password_expires,
+                remind_days,
+                sent,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns all reminders for passwords, that have not expired yet.
+    pub async fn find_active() -> Result<Vec<Self>, ErrorResponse> {
+        let now = clock::now().timestamp();
+
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM password_expiry_reminders WHERE password_expires > $1",
+                    params!(now),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM password_expiry_reminders WHERE password_expires > $1",
+                now
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Cleans up all reminders for passwords, that have expired already.
+    pub async fn delete_expired() -> Result<(), ErrorResponse> {
+        let now = clock::now().timestamp();
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM password_expiry_reminders WHERE password_expires <= $1",
+                    params!(now),
+                )
+                .await?;
+        } else {
+            query!(
+                "DELETE FROM password_expiry_reminders WHERE password_expires <= $1",
+                now
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+}
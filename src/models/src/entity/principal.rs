@@ -1,7 +1,9 @@
 use crate::entity::api_keys::{AccessGroup, AccessRights, ApiKey};
 use crate::entity::sessions::{Session, SessionState};
 use actix_web::{web, HttpRequest};
-use rauthy_common::constants::{ADMIN_FORCE_MFA, RAUTHY_ADMIN_ROLE};
+use rauthy_common::constants::{
+    ADMIN_FORCE_MFA, EVENTS_GROUP_ADMIN_ROLE_PREFIX, RAUTHY_ADMIN_ROLE,
+};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use tracing::trace;
 
@@ -48,6 +50,20 @@ impl Principal {
         self.roles.contains(&*RAUTHY_ADMIN_ROLE)
     }
 
+    /// Returns all groups this principal may see events for as a delegated admin.
+    /// Each role `<EVENTS_GROUP_ADMIN_ROLE_PREFIX><group>` grants access to the events of all
+    /// users inside `<group>`.
+    pub fn event_admin_groups(&self) -> Vec<&str> {
+        let Some(prefix) = EVENTS_GROUP_ADMIN_ROLE_PREFIX.as_deref() else {
+            return Vec::default();
+        };
+        self.roles
+            .iter()
+            .filter_map(|role| role.strip_prefix(prefix))
+            .filter(|group| !group.is_empty())
+            .collect()
+    }
+
     #[inline(always)]
     pub fn is_user(&self, id: &str) -> Result<(), ErrorResponse> {
         if self.user_id() != Ok(id) {
@@ -75,6 +91,16 @@ impl Principal {
             })
     }
 
+    /// Returns an identifier for whoever is acting with this principal, which is either the
+    /// `user_id` of the session or the name of the API Key in the form of `api_key$<name>`.
+    pub fn actor(&self) -> String {
+        if let Some(api_key) = &self.api_key {
+            format!("api_key${}", api_key.name)
+        } else {
+            self.user_id().unwrap_or("unknown").to_string()
+        }
+    }
+
     #[inline(always)]
     pub fn validate_api_key(
         &self,
@@ -161,6 +187,26 @@ impl Principal {
         }
     }
 
+    /// Rejects account modifications from an impersonated session. An admin may look at the
+    /// account as the user, but everything that changes it must be done with the admin API,
+    /// so it can be attributed correctly.
+    #[inline(always)]
+    pub fn validate_not_impersonated(&self) -> Result<(), ErrorResponse> {
+        if self
+            .session
+            .as_ref()
+            .is_some_and(|s| s.impersonator_id.is_some())
+        {
+            trace!("Account modification rejected for an impersonated session");
+            Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "Not allowed in an impersonated session",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Validates the given user_id against this Principal.
     pub fn validate_user_session(&self, user_id: &str) -> Result<(), ErrorResponse> {
         let session = self.validate_session_auth()?;
@@ -0,0 +1,229 @@
+use crate::database::DB;
+use crate::entity::sms_otp::UserSmsOtp;
+use crate::entity::totp::UserTotp;
+use crate::entity::users::User;
+use crate::entity::yubico_otp::UserYubicoOtp;
+use cryptr::utils::secure_random_alnum;
+use hiqlite::{params, Param};
+use rauthy_common::clock;
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::base64_url_no_pad_encode;
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+const RECOVERY_CODES_COUNT: usize = 10;
+const RECOVERY_CODE_LEN: usize = 10;
+
+/// A single-use recovery code, which can be used during the login instead of the 2nd factor,
+/// if it is not available. Only the hash of the code is stored.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MfaRecoveryCode {
+    pub user_id: String,
+    pub code_hash: String,
+    pub created: i64,
+}
+
+// CRUD
+impl MfaRecoveryCode {
+    pub async fn delete_all_for_user(user_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM mfa_recovery_codes WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?;
+        } else {
+            query!("DELETE FROM mfa_recovery_codes WHERE user_id = $1", user_id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM mfa_recovery_codes WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM mfa_recovery_codes WHERE user_id = $1",
+                user_id
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+}
+
+impl MfaRecoveryCode {
+    /// Replaces all existing recovery codes of the user with a new set. The plain codes are
+    /// returned and can never be retrieved again afterward.
+    pub async fn generate(user_id: &str) -> Result<Vec<String>, ErrorResponse> {
+        let created = clock::now().timestamp();
+        let codes = (0..RECOVERY_CODES_COUNT)
+            .map(|_| Self::new_code())
+            .collect::<Vec<_>>();
+
+        if is_hiqlite() {
+            let mut txn = Vec::with_capacity(RECOVERY_CODES_COUNT + 1);
+            txn.push((
+                "DELETE FROM mfa_recovery_codes WHERE user_id = $1",
+                params!(user_id.to_string()),
+            ));
+            for code in &codes {
+                txn.push((
+                    r#"
+INSERT INTO mfa_recovery_codes (user_id, code_hash, created)
+VALUES ($1, $2, $3)"#,
+                    params!(user_id.to_string(), Self::hash(code), created),
+                ));
+            }
+            DB::client().txn(txn).await?;
+        } else {
+            let mut txn = DB::txn().await?;
+
+            query!("DELETE FROM mfa_recovery_codes WHERE user_id = $1", user_id)
+                .execute(&mut *txn)
+                .await?;
+            for code in &codes {
+                query!(
+                    r#"
+INSERT INTO mfa_recovery_codes (user_id, code_hash, created)
+VALUES ($1, $2, $3)"#,
+                    user_id,
+                    Self::hash(code),
+                    created,
+                )
+                .execute(&mut *txn)
+                .await?;
+            }
+
+            txn.commit().await?;
+        }
+
+        Ok(codes)
+    }
+
+    /// Generates a new set of codes, if the user does not have any left. This is used during
+    /// MFA enrollments, which should not invalidate codes the user has saved already.
+    pub async fn generate_if_missing(user_id: &str) -> Result<Option<Vec<String>>, ErrorResponse> {
+        if Self::find_for_user(user_id).await?.is_empty() {
+            Ok(Some(Self::generate(user_id).await?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Deletes all codes of the user, if no 2nd factor is left after a removal.
+    pub async fn delete_if_mfa_disabled(user_id: &str) -> Result<(), ErrorResponse> {
+        let has_webauthn = User::find(user_id.to_string())
+            .await
+            .map(|user| user.has_webauthn_enabled())
+            .unwrap_or(false);
+        if !has_webauthn
+            && !UserTotp::is_enabled(user_id).await?
+            && !UserYubicoOtp::is_enabled(user_id).await?
+            && !UserSmsOtp::is_enabled(user_id).await?
+        {
+            Self::delete_all_for_user(user_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the amount of remaining codes and when they have been generated.
+    pub async fn status(user_id: &str) -> Result<(usize, Option<i64>), ErrorResponse> {
+        let codes = Self::find_for_user(user_id).await?;
+        let created = codes.iter().map(|c| c.created).max();
+        Ok((codes.len(), created))
+    }
+
+    /// Consumes the given recovery code. The matching row is deleted in the same statement,
+    /// which makes sure a code can never be redeemed twice, even with concurrent requests.
+    pub async fn redeem(user_id: &str, code: &str) -> Result<(), ErrorResponse> {
+        let hash = Self::hash(code);
+
+        let rows_affected = if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM mfa_recovery_codes WHERE user_id = $1 AND code_hash = $2",
+                    params!(user_id, hash),
+                )
+                .await? as u64
+        } else {
+            query!(
+                "DELETE FROM mfa_recovery_codes WHERE user_id = $1 AND code_hash = $2",
+                user_id,
+                hash,
+            )
+            .execute(DB::conn())
+            .await?
+            .rows_affected()
+        };
+
+        if rows_affected == 0 {
+            Err(
+                ErrorResponse::new(ErrorResponseType::Unauthorized, "Invalid recovery code")
+                    .with_code(ErrorCode::InvalidCredentials),
+            )
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Creates a new code in the format `xxxxx-xxxxx` for better readability.
+    fn new_code() -> String {
+        let raw = secure_random_alnum(RECOVERY_CODE_LEN).to_lowercase();
+        let (a, b) = raw.split_at(RECOVERY_CODE_LEN / 2);
+        format!("{}-{}", a, b)
+    }
+
+    /// Removes separators and whitespace and ignores the case, because these codes will often
+    /// be typed in manually from a printout.
+    fn normalize(code: &str) -> String {
+        code.chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .collect::<String>()
+            .to_lowercase()
+    }
+
+    fn hash(code: &str) -> String {
+        let normalized = Self::normalize(code);
+        let hash = digest::digest(&digest::SHA256, normalized.as_bytes());
+        base64_url_no_pad_encode(hash.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_code() {
+        let code = MfaRecoveryCode::new_code();
+        assert_eq!(code.len(), RECOVERY_CODE_LEN + 1);
+        assert_eq!(code.chars().nth(RECOVERY_CODE_LEN / 2), Some('-'));
+        assert!(code
+            .chars()
+            .all(|c| c == '-' || c.is_ascii_digit() || c.is_ascii_lowercase()));
+        assert_ne!(code, MfaRecoveryCode::new_code());
+    }
+
+    #[test]
+    fn test_hash_normalized() {
+        let hash = MfaRecoveryCode::hash("abcde-12345");
+        assert_eq!(hash, MfaRecoveryCode::hash("ABCDE12345"));
+        assert_eq!(hash, MfaRecoveryCode::hash(" abcde 12345\n"));
+        assert_ne!(hash, MfaRecoveryCode::hash("abcde-12346"));
+    }
+}
@@ -1,10 +1,11 @@
 use crate::database::DB;
 use chrono::{DateTime, Utc};
 use hiqlite::{params, Param};
+use rauthy_common::clock;
 use rauthy_common::is_hiqlite;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, Row};
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct RefreshToken {
@@ -14,6 +15,12 @@ pub struct RefreshToken {
     pub exp: i64,
     pub scope: Option<String>,
     pub is_mfa: bool,
+    pub client_id: Option<String>,
+    // all tokens rotated from the same original refresh token share the same family
+    pub family_id: Option<String>,
+    pub session_id: Option<String>,
+    // set as soon as this token has been used and a new one has been issued in exchange
+    pub rotated_at: Option<i64>,
 }
 
 // CRUD
@@ -28,14 +35,20 @@ impl RefreshToken {
         // even if the original token has been issued with mfa, the refresh
         // token not really is, because it can be given without user interaction.
         is_mfa: bool,
+        client_id: String,
+        session_id: Option<String>,
     ) -> Result<Self, ErrorResponse> {
         let rt = Self {
+            family_id: Some(id.clone()),
             id,
             user_id,
             nbf: nbf.timestamp(),
             exp: exp.timestamp(),
             scope,
             is_mfa,
+            client_id: Some(client_id),
+            session_id,
+            rotated_at: None,
         };
 
         rt.save().await?;
@@ -69,7 +82,7 @@ impl RefreshToken {
     }
 
     pub async fn invalidate_all() -> Result<(), ErrorResponse> {
-        let now = Utc::now().timestamp();
+        let now = clock::now().timestamp();
 
         if is_hiqlite() {
             DB::client()
@@ -103,8 +116,129 @@ impl RefreshToken {
         Ok(())
     }
 
+    pub async fn invalidate_for_session(session_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM refresh_tokens WHERE session_id = $1",
+                    params!(session_id),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                "DELETE FROM refresh_tokens WHERE session_id = $1",
+                session_id
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn invalidate_for_user_client(
+        user_id: &str,
+        client_id: &str,
+    ) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM refresh_tokens WHERE user_id = $1 AND client_id = $2",
+                    params!(user_id, client_id),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                "DELETE FROM refresh_tokens WHERE user_id = $1 AND client_id = $2",
+                user_id,
+                client_id
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Deletes all refresh tokens, which have been issued for the given client, and returns the
+    /// IDs of all users who had an active token.
+    pub async fn invalidate_for_client(client_id: &str) -> Result<Vec<String>, ErrorResponse> {
+        let now = clock::now().timestamp();
+
+        let mut user_ids: Vec<String> = if is_hiqlite() {
+            let rows = DB::client()
+                .execute_returning(
+                    "DELETE FROM refresh_tokens WHERE client_id = $1 RETURNING user_id, exp",
+                    params!(client_id),
+                )
+                .await?;
+
+            let mut ids = Vec::with_capacity(rows.len());
+            for row in rows {
+                let mut row = row?;
+                if row.get::<i64>("exp") > now {
+                    ids.push(row.get("user_id"));
+                }
+            }
+            ids
+        } else {
+            let rows = sqlx::query(
+                "DELETE FROM refresh_tokens WHERE client_id = $1 RETURNING user_id, exp",
+            )
+            .bind(client_id)
+            .fetch_all(DB::conn())
+            .await?;
+
+            rows.into_iter()
+                .filter(|row| row.get::<i64, _>("exp") > now)
+                .map(|row| row.get("user_id"))
+                .collect()
+        };
+
+        user_ids.sort();
+        user_ids.dedup();
+        Ok(user_ids)
+    }
+
+    /// Returns the IDs of all sessions with a still valid refresh token for the given client.
+    pub async fn find_session_ids_for_client(
+        client_id: &str,
+    ) -> Result<Vec<String>, ErrorResponse> {
+        let now = clock::now().timestamp();
+
+        let sids = if is_hiqlite() {
+            let rows = DB::client()
+                .query_raw(
+                    r#"
+SELECT DISTINCT session_id FROM refresh_tokens
+WHERE client_id = $1 AND exp > $2 AND session_id IS NOT NULL"#,
+                    params!(client_id, now),
+                )
+                .await?;
+
+            let mut ids = Vec::with_capacity(rows.len());
+            for mut row in rows {
+                ids.push(row.get("session_id"));
+            }
+            ids
+        } else {
+            sqlx::query(
+                r#"
+SELECT DISTINCT session_id FROM refresh_tokens
+WHERE client_id = $1 AND exp > $2 AND session_id IS NOT NULL"#,
+            )
+            .bind(client_id)
+            .bind(now)
+            .fetch_all(DB::conn())
+            .await?
+            .into_iter()
+            .map(|row| row.get("session_id"))
+            .collect()
+        };
+
+        Ok(sids)
+    }
+
     pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
-        let now = Utc::now().timestamp();
+        let now = clock::now().timestamp();
 
         let slf = if is_hiqlite() {
             DB::client()
@@ -132,36 +266,86 @@ impl RefreshToken {
         Ok(slf)
     }
 
+    /// Deletes the whole rotation family of the given refresh token, no matter if the token
+    /// itself is still valid or not. Returns the session ID the family was linked to, if any.
+    pub async fn revoke_family(id: &str) -> Result<Option<String>, ErrorResponse> {
+        let slf = if is_hiqlite() {
+            DB::client()
+                .query_as_one::<Self, _>("SELECT * FROM refresh_tokens WHERE id = $1", params!(id))
+                .await
+                .ok()
+        } else {
+            sqlx::query_as!(Self, "SELECT * FROM refresh_tokens WHERE id = $1", id)
+                .fetch_optional(DB::conn())
+                .await?
+        };
+        let Some(slf) = slf else {
+            return Ok(None);
+        };
+
+        // tokens from before the family tracking only have their own ID
+        let family_id = slf.family_id.unwrap_or(slf.id);
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM refresh_tokens WHERE family_id = $1 OR id = $1",
+                    params!(family_id),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                "DELETE FROM refresh_tokens WHERE family_id = $1 OR id = $1",
+                family_id
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(slf.session_id)
+    }
+
     pub async fn save(&self) -> Result<(), ErrorResponse> {
         if is_hiqlite() {
             DB::client()
                 .execute(
                     r#"
-INSERT INTO refresh_tokens (id, user_id, nbf, exp, scope, is_mfa)
-VALUES ($1, $2, $3, $4, $5, $6)
-ON CONFLICT(id) DO UPDATE SET user_id = $2, nbf = $3, exp = $4, scope = $5"#,
+INSERT INTO refresh_tokens
+(id, user_id, nbf, exp, scope, is_mfa, client_id, family_id, session_id, rotated_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+ON CONFLICT(id) DO UPDATE SET user_id = $2, nbf = $3, exp = $4, scope = $5, client_id = $7,
+family_id = $8, session_id = $9, rotated_at = $10"#,
                     params!(
                         self.id.clone(),
                         self.user_id.clone(),
                         self.nbf,
                         self.exp,
                         self.scope.clone(),
-                        self.is_mfa
+                        self.is_mfa,
+                        self.client_id.clone(),
+                        self.family_id.clone(),
+                        self.session_id.clone(),
+                        self.rotated_at
                     ),
                 )
                 .await?;
         } else {
             sqlx::query!(
                 r#"
-INSERT INTO refresh_tokens (id, user_id, nbf, exp, scope, is_mfa)
-VALUES ($1, $2, $3, $4, $5, $6)
-ON CONFLICT(id) DO UPDATE SET user_id = $2, nbf = $3, exp = $4, scope = $5"#,
+INSERT INTO refresh_tokens
+(id, user_id, nbf, exp, scope, is_mfa, client_id, family_id, session_id, rotated_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+ON CONFLICT(id) DO UPDATE SET user_id = $2, nbf = $3, exp = $4, scope = $5, client_id = $7,
+family_id = $8, session_id = $9, rotated_at = $10"#,
                 self.id,
                 self.user_id,
                 self.nbf,
                 self.exp,
                 self.scope,
                 self.is_mfa,
+                self.client_id,
+                self.family_id,
+                self.session_id,
+                self.rotated_at,
             )
             .execute(DB::conn())
             .await?;
@@ -1,11 +1,11 @@
 use crate::database::DB;
 use chrono::{DateTime, Utc};
 use hiqlite::{params, Param};
+use rauthy_common::clock;
 use rauthy_common::is_hiqlite;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
-use time::OffsetDateTime;
+use sqlx::{FromRow, Row};
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct RefreshTokenDevice {
@@ -70,7 +70,7 @@ impl RefreshTokenDevice {
     }
 
     pub async fn invalidate_all() -> Result<(), ErrorResponse> {
-        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let now = clock::now_utc().unix_timestamp();
 
         if is_hiqlite() {
             DB::client()
@@ -92,7 +92,7 @@ impl RefreshTokenDevice {
     }
 
     pub async fn invalidate_for_user(user_id: &str) -> Result<(), ErrorResponse> {
-        let now = Utc::now().timestamp();
+        let now = clock::now().timestamp();
 
         if is_hiqlite() {
             DB::client()
@@ -115,7 +115,7 @@ impl RefreshTokenDevice {
     }
 
     pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
-        let now = Utc::now().timestamp();
+        let now = clock::now().timestamp();
 
         if is_hiqlite() {
             DB::client()
@@ -168,6 +168,52 @@ impl RefreshTokenDevice {
         Ok(())
     }
 
+    /// Deletes the refresh tokens of all devices, which are linked to the given client, and
+    /// returns the IDs of all users who had an active token.
+    pub async fn invalidate_for_client(client_id: &str) -> Result<Vec<String>, ErrorResponse> {
+        let now = clock::now().timestamp();
+
+        let mut user_ids: Vec<String> = if is_hiqlite() {
+            let rows = DB::client()
+                .execute_returning(
+                    r#"
+DELETE FROM refresh_tokens_devices
+WHERE device_id IN (SELECT id FROM devices WHERE client_id = $1)
+RETURNING user_id, exp"#,
+                    params!(client_id),
+                )
+                .await?;
+
+            let mut ids = Vec::with_capacity(rows.len());
+            for row in rows {
+                let mut row = row?;
+                if row.get::<i64>("exp") > now {
+                    ids.push(row.get("user_id"));
+                }
+            }
+            ids
+        } else {
+            let rows = sqlx::query(
+                r#"
+DELETE FROM refresh_tokens_devices
+WHERE device_id IN (SELECT id FROM devices WHERE client_id = $1)
+RETURNING user_id, exp"#,
+            )
+            .bind(client_id)
+            .fetch_all(DB::conn())
+            .await?;
+
+            rows.into_iter()
+                .filter(|row| row.get::<i64, _>("exp") > now)
+                .map(|row| row.get("user_id"))
+                .collect()
+        };
+
+        user_ids.sort();
+        user_ids.dedup();
+        Ok(user_ids)
+    }
+
     pub async fn invalidate_all_for_user(user_id: &str) -> Result<(), ErrorResponse> {
         if is_hiqlite() {
             DB::client()
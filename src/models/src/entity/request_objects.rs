@@ -0,0 +1,522 @@
+use crate::entity::clients::Client;
+use actix_web::web;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes128Gcm, Aes256Gcm, KeyInit, Nonce};
+use jwt_simple::token::Token;
+use rauthy_api_types::oidc::AuthRequest;
+use rauthy_common::clock;
+use rauthy_common::constants::RAUTHY_VERSION;
+use rauthy_common::utils::{base64_url_no_pad_decode, jwt_expired, jwt_not_yet_valid};
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
+use reqwest::header::ACCEPT;
+use reqwest::{tls, Url};
+use serde::Deserialize;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::error;
+use validator::Validate;
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+// The max size of a request object fetched from a `request_uri`
+const MAX_REQUEST_OBJECT_SIZE: usize = 16 * 1024;
+
+/// A signed request object (JAR, RFC 9101), which contains the params for the authorization
+/// request. It can be passed by value with `request` or by reference with `request_uri`.
+/// It may be encrypted with `dir` and `A128GCM` / `A256GCM`, where the key is derived from the
+/// SHA-256 of the client secret, like defined in OIDC Core 10.2.
+#[derive(Debug, Deserialize)]
+pub struct RequestObject {
+    pub iss: String,
+    pub aud: Value,
+    pub exp: i64,
+    pub nbf: Option<i64>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub response_type: Option<String>,
+    pub response_mode: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub max_age: Option<i64>,
+    pub prompt: Option<String>,
+    pub login_hint: Option<String>,
+    pub resource: Option<String>,
+    pub claims: Option<Value>,
+    pub acr_values: Option<String>,
+}
+
+/// The protected header of an encrypted request object
+#[derive(Debug, Deserialize)]
+struct JweHeader {
+    alg: String,
+    enc: String,
+}
+
+impl RequestObject {
+    /// Resolves the request object from either `request` or `request_uri` and validates it
+    /// against the clients registered JWKS.
+    pub async fn resolve(
+        issuer: &str,
+        client: &Client,
+        request: Option<&str>,
+        request_uri: Option<&str>,
+    ) -> Result<Self, ErrorResponse> {
+        let token = match (request, request_uri) {
+            (Some(request), None) => request.to_string(),
+            (None, Some(request_uri)) => Self::fetch(client, request_uri).await?,
+            (Some(_), Some(_)) => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "'request' and 'request_uri' must not be used together",
+                ))
+            }
+            (None, None) => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "No request object given",
+                ))
+            }
+        };
+
+        Self::validate(issuer, client, &token)
+    }
+
+    /// Converts the request object into an authorization request. The params from the request
+    /// object replace all params of the original request and must pass the same validation.
+    /// Returns the query string of the new request as well, which the login UI needs.
+    pub fn into_auth_request(
+        self,
+        client_id: &str,
+    ) -> Result<(AuthRequest, String), ErrorResponse> {
+        let query = self.query_string(client_id)?;
+
+        let req = web::Query::<AuthRequest>::from_query(&query)
+            .map_err(|err| Self::err(format!("Invalid authorization request params: {}", err)))?
+            .into_inner();
+        req.validate()
+            .map_err(|err| Self::err(format!("Invalid authorization request params: {}", err)))?;
+
+        Ok((req, query))
+    }
+
+    fn query_string(&self, client_id: &str) -> Result<String, ErrorResponse> {
+        let max_age = self.max_age.map(|age| age.to_string());
+        let claims = self.claims.as_ref().map(|claims| claims.to_string());
+        let params = [
+            ("client_id", Some(client_id)),
+            ("redirect_uri", self.redirect_uri.as_deref()),
+            ("response_type", self.response_type.as_deref()),
+            ("response_mode", self.response_mode.as_deref()),
+            ("scope", self.scope.as_deref()),
+            ("state", self.state.as_deref()),
+            ("nonce", self.nonce.as_deref()),
+            ("code_challenge", self.code_challenge.as_deref()),
+            (
+                "code_challenge_method",
+                self.code_challenge_method.as_deref(),
+            ),
+            ("max_age", max_age.as_deref()),
+            ("prompt", self.prompt.as_deref()),
+            ("login_hint", self.login_hint.as_deref()),
+            ("resource", self.resource.as_deref()),
+            ("claims", claims.as_deref()),
+            ("acr_values", self.acr_values.as_deref()),
+        ];
+
+        let url = Url::parse_with_params(
+            "http://localhost/",
+            params
+                .into_iter()
+                .filter_map(|(key, value)| value.map(|v| (key, v))),
+        )
+        .map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("Cannot build authorization request params: {}", err),
+            )
+        })?;
+
+        Ok(url.query().unwrap_or_default().to_string())
+    }
+}
+
+impl RequestObject {
+    async fn fetch(client: &Client, request_uri: &str) -> Result<String, ErrorResponse> {
+        // Only pre-registered URIs will ever be fetched. Anything else would make it possible
+        // to send requests to any location from inside the Rauthy network.
+        let is_registered = client
+            .get_request_uris()
+            .unwrap_or_default()
+            .iter()
+            .any(|uri| uri == request_uri);
+        if !is_registered {
+            return Err(Self::err_uri("The 'request_uri' is not registered"));
+        }
+
+        let http_client = HTTP_CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!(
+                    "Rauthy v{} Request Object Resolver",
+                    RAUTHY_VERSION
+                ))
+                .min_tls_version(tls::Version::TLS_1_2)
+                .pool_idle_timeout(Duration::from_secs(600))
+                .build()
+                .unwrap()
+        });
+
+        let mut res = http_client
+            .get(request_uri)
+            .header(ACCEPT, "application/oauth-authz-req+jwt")
+            .send()
+            .await
+            .map_err(|err| {
+                Self::err_uri(format!(
+                    "Cannot fetch request object from {}: {:?}",
+                    request_uri, err
+                ))
+            })?;
+
+        if !res.status().is_success() {
+            let msg = format!("Cannot fetch request object from {}", request_uri);
+            error!("{}", msg);
+            return Err(Self::err_uri(msg));
+        }
+
+        // The body is read in chunks to never buffer more than the max allowed size,
+        // no matter what the remote server sends or claims in its `Content-Length`.
+        let too_large = || Self::err_uri("The request object is too large");
+        if res
+            .content_length()
+            .is_some_and(|len| len > MAX_REQUEST_OBJECT_SIZE as u64)
+        {
+            return Err(too_large());
+        }
+        let mut body = Vec::with_capacity(1024);
+        while let Some(chunk) = res.chunk().await.map_err(|err| {
+            Self::err_uri(format!(
+                "Cannot read request object from {}: {}",
+                request_uri, err
+            ))
+        })? {
+            if body.len() + chunk.len() > MAX_REQUEST_OBJECT_SIZE {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        let body = String::from_utf8(body)
+            .map_err(|_| Self::err_uri("The request object is not valid UTF-8"))?;
+        Ok(body.trim().to_string())
+    }
+
+    fn validate(issuer: &str, client: &Client, token: &str) -> Result<Self, ErrorResponse> {
+        // a JWE has 5 parts instead of 3
+        let decrypted;
+        let token = if token.split('.').count() == 5 {
+            decrypted = Self::decrypt(client, token)?;
+            decrypted.as_str()
+        } else {
+            token
+        };
+
+        let Some(jwks) = client.get_jwks()? else {
+            return Err(Self::err(
+                "The client has no JWKS registered for signed request objects",
+            ));
+        };
+
+        let metadata = Token::decode_metadata(token)
+            .map_err(|_| Self::err("Malformed request object header"))?;
+        let alg = metadata.algorithm();
+        let kid = metadata.key_id();
+        // the `alg` must always match, which also makes sure that `none` will never be accepted
+        let key = jwks
+            .keys
+            .iter()
+            .find(|key| {
+                key.alg.as_ref().map(|a| a.as_str()) == Some(alg)
+                    && (kid.is_none() || key.kid.as_deref() == kid)
+            })
+            .ok_or_else(|| Self::err("No matching key found in the clients JWKS"))?;
+        key.validate_token_signature(token)
+            .map_err(|err| Self::err(err.message))?;
+
+        let claims = token
+            .split('.')
+            .nth(1)
+            .ok_or_else(|| Self::err("Malformed request object"))?;
+        let claims =
+            base64_url_no_pad_decode(claims).map_err(|_| Self::err("Malformed request object"))?;
+        let slf = serde_json::from_slice::<Self>(&claims)
+            .map_err(|err| Self::err(format!("Invalid request object claims: {}", err)))?;
+        slf.validate_claims(issuer, &client.id)?;
+
+        Ok(slf)
+    }
+
+    fn decrypt(client: &Client, token: &str) -> Result<String, ErrorResponse> {
+        let Some(secret) = client.get_secret_cleartext()? else {
+            return Err(Self::err(
+                "Encrypted request objects are only supported for confidential clients",
+            ));
+        };
+        Self::decrypt_with_secret(&secret, token)
+    }
+
+    /// Decrypts a request object, which has been encrypted with `dir`. The key is the SHA-256 of
+    /// the client secret, where `A128GCM` uses its left-most 128 bits.
+    fn decrypt_with_secret(secret: &str, token: &str) -> Result<String, ErrorResponse> {
+        let parts = token.split('.').collect::<Vec<_>>();
+        let [header_b64, encrypted_key, iv, ciphertext, tag] = parts.as_slice() else {
+            return Err(Self::err("Malformed encrypted request object"));
+        };
+        let decode = |b64: &str| {
+            base64_url_no_pad_decode(b64)
+                .map_err(|_| Self::err("Malformed encrypted request object"))
+        };
+
+        let header = serde_json::from_slice::<JweHeader>(&decode(header_b64)?)
+            .map_err(|_| Self::err("Malformed encrypted request object header"))?;
+        if header.alg != "dir" || !encrypted_key.is_empty() {
+            return Err(Self::err(
+                "Only 'dir' is supported for encrypted request objects",
+            ));
+        }
+        let iv = decode(iv)?;
+        if iv.len() != 12 {
+            return Err(Self::err("Invalid IV for the encrypted request object"));
+        }
+
+        let key = hmac_sha256::Hash::hash(secret.as_bytes());
+
+        let mut msg = decode(ciphertext)?;
+        msg.extend(decode(tag)?);
+        let payload = Payload {
+            msg: &msg,
+            aad: header_b64.as_bytes(),
+        };
+        let nonce = Nonce::from_slice(&iv);
+        let plain = match header.enc.as_str() {
+            "A128GCM" => Aes128Gcm::new_from_slice(&key[..16])
+                .expect("a 128 bit key to always be valid")
+                .decrypt(nonce, payload),
+            "A256GCM" => Aes256Gcm::new_from_slice(&key)
+                .expect("a 256 bit key to always be valid")
+                .decrypt(nonce, payload),
+            enc => {
+                return Err(Self::err(format!(
+                    "'{}' is not supported for encrypted request objects",
+                    enc
+                )))
+            }
+        }
+        .map_err(|_| Self::err("Cannot decrypt the request object"))?;
+
+        String::from_utf8(plain).map_err(|_| Self::err("Malformed encrypted request object"))
+    }
+
+    fn validate_claims(&self, issuer: &str, client_id: &str) -> Result<(), ErrorResponse> {
+        if self.iss != client_id {
+            return Err(Self::err(
+                "The 'iss' of the request object must be the 'client_id'",
+            ));
+        }
+        if let Some(id) = &self.client_id {
+            if id != client_id {
+                return Err(Self::err(
+                    "The 'client_id' of the request object does not match",
+                ));
+            }
+        }
+
+        let aud_matches = match &self.aud {
+            Value::String(aud) => aud == issuer,
+            Value::Array(aud) => aud.iter().any(|a| a.as_str() == Some(issuer)),
+            _ => false,
+        };
+        if !aud_matches {
+            return Err(Self::err(
+                "The 'aud' of the request object must contain the issuer",
+            ));
+        }
+
+        let now = clock::now().timestamp();
+        if jwt_expired(self.exp, now) {
+            return Err(Self::err("The request object has expired"));
+        }
+        if self
+            .nbf
+            .map(|nbf| jwt_not_yet_valid(nbf, now))
+            .unwrap_or(false)
+        {
+            return Err(Self::err("The request object is not valid yet"));
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn err<C: Into<Cow<'static, str>>>(msg: C) -> ErrorResponse {
+        ErrorResponse::new(ErrorResponseType::BadRequest, msg)
+            .with_code(ErrorCode::InvalidRequestObject)
+    }
+
+    #[inline]
+    fn err_uri<C: Into<Cow<'static, str>>>(msg: C) -> ErrorResponse {
+        ErrorResponse::new(ErrorResponseType::BadRequest, msg)
+            .with_code(ErrorCode::InvalidRequestUri)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rauthy_common::utils::base64_url_no_pad_encode;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_claims() {
+        let issuer = "http://localhost:8080/auth/v1";
+        let now = clock::now().timestamp();
+        let mut obj = serde_json::from_value::<RequestObject>(json!({
+            "iss": "my_client",
+            "aud": issuer,
+            "exp": now + 60,
+            "redirect_uri": "http://localhost:3000/callback",
+            "response_type": "code",
+        }))
+        .unwrap();
+        assert!(obj.validate_claims(issuer, "my_client").is_ok());
+        assert!(obj.validate_claims(issuer, "other_client").is_err());
+
+        obj.aud = json!(["something", issuer]);
+        assert!(obj.validate_claims(issuer, "my_client").is_ok());
+        obj.aud = json!("something");
+        assert!(obj.validate_claims(issuer, "my_client").is_err());
+        obj.aud = json!(issuer);
+
+        obj.exp = now - 30;
+        assert!(
+            obj.validate_claims(issuer, "my_client").is_ok(),
+            "within the clock skew"
+        );
+        obj.exp = now - 61;
+        assert!(obj.validate_claims(issuer, "my_client").is_err());
+        obj.exp = now + 60;
+
+        obj.client_id = Some("other_client".to_string());
+        assert!(obj.validate_claims(issuer, "my_client").is_err());
+    }
+
+    #[test]
+    fn test_exp_required() {
+        let res = serde_json::from_value::<RequestObject>(json!({
+            "iss": "my_client",
+            "aud": "http://localhost:8080/auth/v1",
+            "response_type": "code",
+        }));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_into_auth_request() {
+        let issuer = "http://localhost:8080/auth/v1";
+        let obj = serde_json::from_value::<RequestObject>(json!({
+            "iss": "my_client",
+            "aud": issuer,
+            "exp": clock::now().timestamp() + 60,
+            "redirect_uri": "http://localhost:3000/callback",
+            "response_type": "code",
+            "scope": "openid email",
+            "max_age": 300,
+        }))
+        .unwrap();
+
+        let (req, query) = obj.into_auth_request("my_client").unwrap();
+        assert_eq!(
+            query,
+            "client_id=my_client&redirect_uri=http%3A%2F%2Flocalhost%3A3000%2Fcallback&response_type=code&scope=openid+email&max_age=300"
+        );
+        assert_eq!(req.client_id, "my_client");
+        assert_eq!(
+            req.redirect_uri.as_deref(),
+            Some("http://localhost:3000/callback")
+        );
+        assert_eq!(req.scope, "openid email");
+        assert_eq!(req.max_age, Some(300));
+        assert!(req.request.is_none());
+
+        // the params inside the object must pass the same validation as plain ones
+        let obj = serde_json::from_value::<RequestObject>(json!({
+            "iss": "my_client",
+            "aud": issuer,
+            "exp": clock::now().timestamp() + 60,
+            "redirect_uri": "http://localhost:3000/callback",
+            "response_type": "code",
+            "max_age": -1,
+        }))
+        .unwrap();
+        let err = obj.into_auth_request("my_client").unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidRequestObject);
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let secret = "SuperSecretClientSecret1337";
+        let jws = "eyJhbGciOiJFUzI1NiJ9.eyJpc3MiOiJteV9jbGllbnQifQ.c2ln";
+        let key = hmac_sha256::Hash::hash(secret.as_bytes());
+        let iv = [7u8; 12];
+
+        let encrypt = |enc: &str| {
+            let header = base64_url_no_pad_encode(
+                format!(r#"{{"alg":"dir","enc":"{}","cty":"JWT"}}"#, enc).as_bytes(),
+            );
+            let payload = Payload {
+                msg: jws.as_bytes(),
+                aad: header.as_bytes(),
+            };
+            let nonce = Nonce::from_slice(&iv);
+            let mut ciphertext = match enc {
+                "A128GCM" => Aes128Gcm::new_from_slice(&key[..16])
+                    .unwrap()
+                    .encrypt(nonce, payload),
+                _ => Aes256Gcm::new_from_slice(&key)
+                    .unwrap()
+                    .encrypt(nonce, payload),
+            }
+            .unwrap();
+            let tag = ciphertext.split_off(ciphertext.len() - 16);
+            format!(
+                "{}..{}.{}.{}",
+                header,
+                base64_url_no_pad_encode(&iv),
+                base64_url_no_pad_encode(&ciphertext),
+                base64_url_no_pad_encode(&tag),
+            )
+        };
+
+        for enc in ["A128GCM", "A256GCM"] {
+            let jwe = encrypt(enc);
+            assert_eq!(
+                RequestObject::decrypt_with_secret(secret, &jwe).unwrap(),
+                jws
+            );
+
+            let err = RequestObject::decrypt_with_secret("wrong", &jwe).unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidRequestObject);
+        }
+
+        // only `dir` is allowed
+        let header = base64_url_no_pad_encode(br#"{"alg":"RSA-OAEP","enc":"A256GCM"}"#);
+        let jwe = format!("{}.a2V5.aXY.Y3Q.dGFn", header);
+        assert!(RequestObject::decrypt_with_secret(secret, &jwe).is_err());
+    }
+}
@@ -0,0 +1,473 @@
+use crate::database::{Cache, DB};
+use crate::entity::groups::Group;
+use crate::entity::roles::Role;
+use crate::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use crate::entity::users::User;
+use hiqlite::{params, Param};
+use rauthy_api_types::roles::{RoleMappingRuleRequest, RoleMappingRuleResponse, RoleMappingSource};
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_ROLE_MAPPING_RULES};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::new_store_id;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use serde_json::value;
+use serde_json_path::JsonPath;
+use sqlx::FromRow;
+use std::collections::HashSet;
+use tracing::{debug, error};
+
+/// Assigns roles and groups automatically, when a user is created or logs in.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct RoleMappingRule {
+    pub id: String,
+    pub name: String,
+    pub enabled: bool,
+    pub source: String,
+    pub key: Option<String>,
+    pub value: String,
+    /// Comma separated Role IDs
+    pub roles: String,
+    /// Comma separated Group IDs
+    pub groups: String,
+}
+
+impl From<RoleMappingRule> for RoleMappingRuleResponse {
+    fn from(value: RoleMappingRule) -> Self {
+        Self {
+            source: RoleMappingSource::from(value.source.as_str()),
+            roles: value.role_ids().map(String::from).collect(),
+            groups: value.group_ids().map(String::from).collect(),
+            id: value.id,
+            name: value.name,
+            enabled: value.enabled,
+            key: value.key,
+            value: value.value,
+        }
+    }
+}
+
+// CRUD
+impl RoleMappingRule {
+    pub async fn create(req: RoleMappingRuleRequest) -> Result<Self, ErrorResponse> {
+        let slf = Self::try_from_req(new_store_id(), req).await?;
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO role_mapping_rules (id, name, enabled, source, key, value, roles, groups)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                    params!(
+                        slf.id.clone(),
+                        slf.name.clone(),
+                        slf.enabled,
+                        slf.source.clone(),
+                        slf.key.clone(),
+                        slf.value.clone(),
+                        slf.roles.clone(),
+                        slf.groups.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                r#"
+INSERT INTO role_mapping_rules (id, name, enabled, source, key, value, roles, groups)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                slf.id,
+                slf.name,
+                slf.enabled,
+                slf.source,
+                slf.key,
+                slf.value,
+                slf.roles,
+                slf.groups,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Self::clear_cache().await?;
+        Ok(slf)
+    }
+
+    pub async fn delete(id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM role_mapping_rules WHERE id = $1", params!(id))
+                .await?;
+        } else {
+            sqlx::query!("DELETE FROM role_mapping_rules WHERE id = $1", id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        Self::clear_cache().await?;
+        Ok(())
+    }
+
+    pub async fn find(id: &str) -> Result<Self, ErrorResponse> {
+        Self::find_all()
+            .await?
+            .into_iter()
+            .find(|r| r.id == id)
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::NotFound,
+                    "Role mapping rule does not exist",
+                )
+            })
+    }
+
+    pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
+        let client = DB::client();
+        if let Some(slf) = client.get(Cache::App, IDX_ROLE_MAPPING_RULES).await? {
+            return Ok(slf);
+        }
+
+        let res = if is_hiqlite() {
+            client
+                .query_as("SELECT * FROM role_mapping_rules ORDER BY name", params!())
+                .await?
+        } else {
+            sqlx::query_as!(Self, "SELECT * FROM role_mapping_rules ORDER BY name")
+                .fetch_all(DB::conn())
+                .await?
+        };
+
+        client
+            .put(Cache::App, IDX_ROLE_MAPPING_RULES, &res, CACHE_TTL_APP)
+            .await?;
+        Ok(res)
+    }
+
+    pub async fn update(id: String, req: RoleMappingRuleRequest) -> Result<Self, ErrorResponse> {
+        // make sure it exists
+        Self::find(&id).await?;
+        let slf = Self::try_from_req(id, req).await?;
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+UPDATE role_mapping_rules
+SET name = $1, enabled = $2, source = $3, key = $4, value = $5, roles = $6, groups = $7
+WHERE id = $8"#,
+                    params!(
+                        slf.name.clone(),
+                        slf.enabled,
+                        slf.source.clone(),
+                        slf.key.clone(),
+                        slf.value.clone(),
+                        slf.roles.clone(),
+                        slf.groups.clone(),
+                        slf.id.clone()
+                    ),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                r#"
+UPDATE role_mapping_rules
+SET name = $1, enabled = $2, source = $3, key = $4, value = $5, roles = $6, groups = $7
+WHERE id = $8"#,
+                slf.name,
+                slf.enabled,
+                slf.source,
+                slf.key,
+                slf.value,
+                slf.roles,
+                slf.groups,
+                slf.id,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Self::clear_cache().await?;
+        Ok(slf)
+    }
+}
+
+impl RoleMappingRule {
+    /// Evaluates all enabled rules and adds the mapped roles and groups to the given user.
+    /// The user is only modified in memory. Returns `true` if anything has been added and the
+    /// user needs to be saved.
+    ///
+    /// `claims` are the ID token claims of an upstream provider, if this is a federated login.
+    pub async fn apply(
+        user: &mut User,
+        claims: Option<&value::Value>,
+    ) -> Result<bool, ErrorResponse> {
+        let rules = Self::find_all()
+            .await?
+            .into_iter()
+            .filter(|r| r.enabled)
+            .collect::<Vec<_>>();
+        if rules.is_empty() {
+            return Ok(false);
+        }
+
+        let attrs = if rules
+            .iter()
+            .any(|r| r.source == RoleMappingSource::UserAttr.as_str())
+        {
+            UserAttrValueEntity::find_for_user(&user.id).await?
+        } else {
+            Vec::new()
+        };
+
+        let mut role_ids = HashSet::new();
+        let mut group_ids = HashSet::new();
+        for rule in &rules {
+            if rule.matches(user, claims, &attrs) {
+                debug!("Role mapping rule '{}' matches user {}", rule.name, user.id);
+                role_ids.extend(rule.role_ids());
+                group_ids.extend(rule.group_ids());
+            }
+        }
+        if role_ids.is_empty() && group_ids.is_empty() {
+            return Ok(false);
+        }
+
+        let mut changed = false;
+
+        let mut roles = user.get_roles();
+        for role in Role::find_all().await? {
+            if role_ids.contains(role.id.as_str()) && !roles.contains(&role.name) {
+                roles.push(role.name);
+                changed = true;
+            }
+        }
+
+        let mut groups = user.get_groups();
+        for group in Group::find_all().await? {
+            if group_ids.contains(group.id.as_str()) && !groups.contains(&group.name) {
+                groups.push(group.name);
+                changed = true;
+            }
+        }
+
+        if changed {
+            user.roles = roles.join(",");
+            user.groups = if groups.is_empty() {
+                None
+            } else {
+                Some(groups.join(","))
+            };
+        }
+
+        Ok(changed)
+    }
+
+    async fn clear_cache() -> Result<(), ErrorResponse> {
+        DB::client()
+            .delete(Cache::App, IDX_ROLE_MAPPING_RULES)
+            .await?;
+        Ok(())
+    }
+
+    fn group_ids(&self) -> impl Iterator<Item = &str> {
+        self.groups.split(',').filter(|id| !id.is_empty())
+    }
+
+    fn role_ids(&self) -> impl Iterator<Item = &str> {
+        self.roles.split(',').filter(|id| !id.is_empty())
+    }
+
+    fn matches(
+        &self,
+        user: &User,
+        claims: Option<&value::Value>,
+        attrs: &[UserAttrValueEntity],
+    ) -> bool {
+        match RoleMappingSource::from(self.source.as_str()) {
+            // an unverified E-Mail could be chosen freely
+            RoleMappingSource::EmailDomain => {
+                user.email_verified
+                    && user
+                        .email
+                        .rsplit_once('@')
+                        .map(|(_, domain)| domain.eq_ignore_ascii_case(&self.value))
+                        .unwrap_or(false)
+            }
+            RoleMappingSource::ProviderClaim => {
+                let (Some(claims), Some(key)) = (claims, &self.key) else {
+                    return false;
+                };
+                match JsonPath::parse(key) {
+                    Ok(path) => path
+                        .query(claims)
+                        .all()
+                        .into_iter()
+                        .any(|v| Self::value_matches(v, &self.value)),
+                    Err(err) => {
+                        error!("Error parsing JsonPath from: '{}\nError: {}", key, err);
+                        false
+                    }
+                }
+            }
+            RoleMappingSource::UserAttr => {
+                let Some(key) = &self.key else {
+                    return false;
+                };
+                attrs.iter().filter(|a| &a.key == key).any(|a| {
+                    serde_json::from_slice::<value::Value>(&a.value)
+                        .map(|v| Self::value_matches(&v, &self.value))
+                        .unwrap_or(false)
+                })
+            }
+        }
+    }
+
+    /// Strings are compared directly, arrays match if any of their elements matches and all
+    /// other values are compared by their JSON representation, so that `true` or `42` can be
+    /// matched as well.
+    fn value_matches(v: &value::Value, expected: &str) -> bool {
+        match v {
+            value::Value::String(s) => s == expected,
+            value::Value::Array(arr) => arr.iter().any(|v| Self::value_matches(v, expected)),
+            v => serde_json::to_string(v).is_ok_and(|json| json == expected),
+        }
+    }
+
+    async fn try_from_req(id: String, req: RoleMappingRuleRequest) -> Result<Self, ErrorResponse> {
+        let key = match req.source {
+            RoleMappingSource::EmailDomain => None,
+            RoleMappingSource::ProviderClaim => {
+                let Some(key) = req.key else {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        "`key` must contain the claim JsonPath",
+                    ));
+                };
+                if let Err(err) = JsonPath::parse(&key) {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        format!("Invalid JsonPath for `key`: {}", err),
+                    ));
+                }
+                Some(key)
+            }
+            RoleMappingSource::UserAttr => {
+                let Some(key) = req.key else {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::BadRequest,
+                        "`key` must contain the user attribute name",
+                    ));
+                };
+                if !UserAttrConfigEntity::find_all()
+                    .await?
+                    .iter()
+                    .any(|a| a.name == key)
+                {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::NotFound,
+                        format!("User attribute '{}' does not exist", key),
+                    ));
+                }
+                Some(key)
+            }
+        };
+
+        let role_ids = Role::find_all()
+            .await?
+            .into_iter()
+            .map(|r| r.id)
+            .collect::<HashSet<_>>();
+        if let Some(id) = req.roles.iter().find(|id| !role_ids.contains(*id)) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                format!("Role '{}' does not exist", id),
+            ));
+        }
+
+        let group_ids = Group::find_all()
+            .await?
+            .into_iter()
+            .map(|g| g.id)
+            .collect::<HashSet<_>>();
+        if let Some(id) = req.groups.iter().find(|id| !group_ids.contains(*id)) {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                format!("Group '{}' does not exist", id),
+            ));
+        }
+
+        if req.roles.is_empty() && req.groups.is_empty() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "A rule must assign at least one role or group",
+            ));
+        }
+
+        Ok(Self {
+            id,
+            name: req.name,
+            enabled: req.enabled,
+            source: req.source.as_str().to_string(),
+            key,
+            value: req.value,
+            roles: req.roles.join(","),
+            groups: req.groups.join(","),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(source: RoleMappingSource, key: Option<&str>, value: &str) -> RoleMappingRule {
+        RoleMappingRule {
+            id: new_store_id(),
+            name: "test".to_string(),
+            enabled: true,
+            source: source.as_str().to_string(),
+            key: key.map(String::from),
+            value: value.to_string(),
+            roles: "r1".to_string(),
+            groups: String::default(),
+        }
+    }
+
+    fn user(email: &str, email_verified: bool) -> User {
+        User {
+            email: email.to_string(),
+            email_verified,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_role_mapping_matches() {
+        let r = rule(RoleMappingSource::EmailDomain, None, "example.com");
+        assert!(r.matches(&user("admin@Example.com", true), None, &[]));
+        assert!(!r.matches(&user("admin@example.com", false), None, &[]));
+        assert!(!r.matches(&user("admin@example.com.evil", true), None, &[]));
+
+        let u = user("a@b.c", true);
+
+        let claims = json!({ "groups": ["dev", "ops"], "org": { "verified": true } });
+        let r = rule(RoleMappingSource::ProviderClaim, Some("$.groups"), "ops");
+        assert!(r.matches(&u, Some(&claims), &[]));
+        assert!(!r.matches(&u, None, &[]));
+        let r = rule(
+            RoleMappingSource::ProviderClaim,
+            Some("$.org.verified"),
+            "true",
+        );
+        assert!(r.matches(&u, Some(&claims), &[]));
+
+        let attrs = vec![UserAttrValueEntity {
+            user_id: "u1".to_string(),
+            key: "department".to_string(),
+            value: serde_json::to_vec(&json!("sales")).unwrap(),
+        }];
+        let r = rule(RoleMappingSource::UserAttr, Some("department"), "sales");
+        assert!(r.matches(&u, None, &attrs));
+        let r = rule(RoleMappingSource::UserAttr, Some("department"), "it");
+        assert!(!r.matches(&u, None, &attrs));
+    }
+}
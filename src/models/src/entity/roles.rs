@@ -2,7 +2,7 @@ use crate::database::{Cache, DB};
 use crate::entity::users::User;
 use hiqlite::{params, Param, Params};
 use rauthy_api_types::roles::NewRoleRequest;
-use rauthy_common::constants::{CACHE_TTL_APP, IDX_ROLES};
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_GROUPS_INHERITANCE, IDX_ROLES};
 use rauthy_common::is_hiqlite;
 use rauthy_common::utils::new_store_id;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -115,6 +115,8 @@ impl Role {
         client
             .put(Cache::App, IDX_ROLES, &roles, CACHE_TTL_APP)
             .await?;
+        // roles bound to groups are removed via `ON DELETE CASCADE`
+        client.delete(Cache::App, IDX_GROUPS_INHERITANCE).await?;
 
         Ok(())
     }
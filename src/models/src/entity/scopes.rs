@@ -1,19 +1,24 @@
 use crate::app_state::{AppState, DbTxn};
 use crate::database::{Cache, DB};
 use crate::entity::clients::Client;
-use crate::entity::user_attr::UserAttrConfigEntity;
+use crate::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use crate::entity::users::User;
+use crate::entity::users_values::UserValues;
 use crate::entity::well_known::WellKnown;
 use actix_web::web;
 use hiqlite::{params, Param, Params};
-use rauthy_api_types::scopes::{ScopeRequest, ScopeResponse};
-use rauthy_common::constants::{CACHE_TTL_APP, IDX_CLIENTS, IDX_SCOPES};
+use rauthy_api_types::scopes::{
+    ScopeClaimMapping, ScopeClaimSource, ScopeClaimType, ScopeRequest, ScopeResponse,
+};
+use rauthy_common::constants::{CACHE_TTL_APP, IDX_CLIENTS, IDX_SCOPES, RE_GROUPS};
 use rauthy_common::is_hiqlite;
 use rauthy_common::utils::new_store_id;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sqlx::FromRow;
-use std::collections::HashSet;
-use tracing::debug;
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, error};
 use utoipa::ToSchema;
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize, ToSchema)]
@@ -24,8 +29,49 @@ pub struct Scope {
     pub attr_include_access: Option<String>,
     // Custom user attributes as CSV to include in the id token
     pub attr_include_id: Option<String>,
+    // Mappings from user values to claims as a JSON array of `ScopeClaimMapping`s
+    pub claim_mappings: Option<String>,
 }
 
+/// The target for the `claim_mappings` of a scope. The `Id` mappings apply to the userinfo
+/// endpoint as well.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScopeClaimTarget {
+    Access,
+    Id,
+}
+
+impl ScopeClaimTarget {
+    fn matches(&self, mapping: &ScopeClaimMapping) -> bool {
+        match self {
+            Self::Access => mapping.include_access,
+            Self::Id => mapping.include_id,
+        }
+    }
+}
+
+// The user fields, which can be mapped to claims
+const CLAIM_USER_FIELDS: [&str; 15] = [
+    "birthdate",
+    "city",
+    "country",
+    "created_at",
+    "email",
+    "email_verified",
+    "family_name",
+    "given_name",
+    "language",
+    "last_login",
+    "mfa_enabled",
+    "phone",
+    "roles",
+    "street",
+    "zip",
+];
+// The user fields, which need the `UserValues`
+const CLAIM_USER_VALUES_FIELDS: [&str; 6] =
+    ["birthdate", "city", "country", "phone", "street", "zip"];
+
 // CRUD
 impl Scope {
     pub async fn clear_cache() -> Result<(), ErrorResponse> {
@@ -49,7 +95,9 @@ impl Scope {
         }
 
         if !Scope::is_custom(&scope_req.scope)
-            && (scope_req.attr_include_access.is_some() || scope_req.attr_include_id.is_some())
+            && (scope_req.attr_include_access.is_some()
+                || scope_req.attr_include_id.is_some()
+                || scope_req.claim_mappings.is_some())
         {
             return Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
@@ -58,40 +106,45 @@ impl Scope {
         }
 
         // check configured custom attributes and clean them up
-        let attrs = UserAttrConfigEntity::find_all_as_set().await?;
+        // only claim eligible attributes can be mapped
+        let attrs = UserAttrConfigEntity::find_claim_eligible_as_set().await?;
         let attr_include_access = Self::clean_up_attrs(scope_req.attr_include_access, &attrs);
         let attr_include_id = Self::clean_up_attrs(scope_req.attr_include_id, &attrs);
+        let claim_mappings = Self::clean_up_claim_mappings(scope_req.claim_mappings, &attrs)?;
 
         let new_scope = Scope {
             id: new_store_id(),
             name: scope_req.scope,
             attr_include_access,
             attr_include_id,
+            claim_mappings,
         };
 
         if is_hiqlite() {
             DB::client()
                 .execute(
                     r#"
-INSERT INTO scopes (id, name, attr_include_access, attr_include_id)
-VALUES ($1, $2, $3, $4)"#,
+INSERT INTO scopes (id, name, attr_include_access, attr_include_id, claim_mappings)
+VALUES ($1, $2, $3, $4, $5)"#,
                     params!(
                         &new_scope.id,
                         &new_scope.name,
                         &new_scope.attr_include_access,
-                        &new_scope.attr_include_id
+                        &new_scope.attr_include_id,
+                        &new_scope.claim_mappings
                     ),
                 )
                 .await?;
         } else {
             sqlx::query!(
                 r#"
-    INSERT INTO scopes (id, name, attr_include_access, attr_include_id)
-    VALUES ($1, $2, $3, $4)"#,
+    INSERT INTO scopes (id, name, attr_include_access, attr_include_id, claim_mappings)
+    VALUES ($1, $2, $3, $4, $5)"#,
                 new_scope.id,
                 new_scope.name,
                 new_scope.attr_include_access,
                 new_scope.attr_include_id,
+                new_scope.claim_mappings,
             )
             .execute(DB::conn())
             .await?;
@@ -222,7 +275,9 @@ VALUES ($1, $2, $3, $4)"#,
         }
 
         if !Scope::is_custom(&scope_req.scope)
-            && (scope_req.attr_include_access.is_some() || scope_req.attr_include_id.is_some())
+            && (scope_req.attr_include_access.is_some()
+                || scope_req.attr_include_id.is_some()
+                || scope_req.claim_mappings.is_some())
         {
             return Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
@@ -251,17 +306,20 @@ VALUES ($1, $2, $3, $4)"#,
 
         debug!("scope_req: {:?}", scope_req);
         // check configured custom attributes and clean them up
-        let attrs = UserAttrConfigEntity::find_all_as_set().await?;
+        // only claim eligible attributes can be mapped
+        let attrs = UserAttrConfigEntity::find_claim_eligible_as_set().await?;
         let attr_include_access = Self::clean_up_attrs(scope_req.attr_include_access, &attrs);
         let attr_include_id = Self::clean_up_attrs(scope_req.attr_include_id, &attrs);
         debug!("attr_include_access: {:?}", attr_include_access);
         debug!("attr_include_id: {:?}", attr_include_id);
+        let claim_mappings = Self::clean_up_claim_mappings(scope_req.claim_mappings, &attrs)?;
 
         let new_scope = Scope {
             id: scope.id.clone(),
             name: scope_req.scope,
             attr_include_access,
             attr_include_id,
+            claim_mappings,
         };
 
         if is_hiqlite() {
@@ -276,12 +334,13 @@ VALUES ($1, $2, $3, $4)"#,
             txn.push((
                 r#"
 UPDATE scopes
-SET name = $1, attr_include_access = $2, attr_include_id = $3
-WHERE id = $4"#,
+SET name = $1, attr_include_access = $2, attr_include_id = $3, claim_mappings = $4
+WHERE id = $5"#,
                 params!(
                     &new_scope.name,
                     &new_scope.attr_include_access,
                     &new_scope.attr_include_id,
+                    &new_scope.claim_mappings,
                     &new_scope.id
                 ),
             ));
@@ -301,11 +360,12 @@ WHERE id = $4"#,
             sqlx::query!(
                 r#"
 UPDATE scopes
-SET name = $1, attr_include_access = $2, attr_include_id = $3
-WHERE id = $4"#,
+SET name = $1, attr_include_access = $2, attr_include_id = $3, claim_mappings = $4
+WHERE id = $5"#,
                 new_scope.name,
                 new_scope.attr_include_access,
                 new_scope.attr_include_id,
+                new_scope.claim_mappings,
                 new_scope.id,
             )
             .execute(&mut *txn)
@@ -399,6 +459,137 @@ impl Scope {
         Some(res)
     }
 
+    /// Validates the requested claim mappings and converts them into their JSON representation.
+    pub fn clean_up_claim_mappings(
+        mappings: Option<Vec<ScopeClaimMapping>>,
+        existing_attrs: &HashSet<String>,
+    ) -> Result<Option<String>, ErrorResponse> {
+        let Some(mappings) = mappings.filter(|m| !m.is_empty()) else {
+            return Ok(None);
+        };
+
+        let mut claims = HashSet::with_capacity(mappings.len());
+        for mapping in &mappings {
+            if !claims.insert(mapping.claim.as_str()) {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("The claim '{}' is mapped more than once", mapping.claim),
+                ));
+            }
+            if !mapping.include_access && !mapping.include_id {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!(
+                        "The claim '{}' must be included in at least one token",
+                        mapping.claim
+                    ),
+                ));
+            }
+
+            let is_valid = match mapping.source {
+                ScopeClaimSource::UserField => CLAIM_USER_FIELDS.contains(&mapping.value.as_str()),
+                ScopeClaimSource::Attribute => existing_attrs.contains(&mapping.value),
+                ScopeClaimSource::Group => RE_GROUPS.is_match(&mapping.value),
+            };
+            if !is_valid {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!(
+                        "Invalid value '{}' for the claim '{}'",
+                        mapping.value, mapping.claim
+                    ),
+                ));
+            }
+        }
+
+        Ok(Some(serde_json::to_string(&mappings)?))
+    }
+
+    /// Returns `true` if the custom attribute is included or mapped into any token.
+    pub fn maps_attr(&self, attr: &str) -> bool {
+        let in_csv = |csv: &Option<String>| {
+            csv.as_deref()
+                .map(|csv| csv.split(',').any(|a| a == attr))
+                .unwrap_or(false)
+        };
+
+        in_csv(&self.attr_include_access)
+            || in_csv(&self.attr_include_id)
+            || self
+                .get_claim_mappings()
+                .iter()
+                .any(|m| m.source == ScopeClaimSource::Attribute && m.value == attr)
+    }
+
+    pub fn get_claim_mappings(&self) -> Vec<ScopeClaimMapping> {
+        let Some(mappings) = &self.claim_mappings else {
+            return Vec::default();
+        };
+        serde_json::from_str(mappings).unwrap_or_else(|err| {
+            error!("Invalid claim mappings for scope '{}': {}", self.name, err);
+            Vec::default()
+        })
+    }
+
+    #[inline]
+    pub fn has_claim_mappings(&self, target: ScopeClaimTarget) -> bool {
+        self.get_claim_mappings()
+            .iter()
+            .any(|mapping| target.matches(mapping))
+    }
+
+    /// Resolves the claim mappings of all given scopes for the user. The custom user attributes
+    /// will only be fetched, if they are needed and have not been given already.
+    pub async fn resolve_claim_mappings(
+        scopes: &[&Scope],
+        target: ScopeClaimTarget,
+        user: &User,
+        user_attrs: Option<&HashMap<String, Vec<u8>>>,
+    ) -> Result<HashMap<String, Value>, ErrorResponse> {
+        let mappings = scopes
+            .iter()
+            .flat_map(|s| s.get_claim_mappings())
+            .filter(|mapping| target.matches(mapping))
+            .collect::<Vec<_>>();
+        if mappings.is_empty() {
+            return Ok(HashMap::default());
+        }
+
+        let needs_field = |fields: &[&str]| {
+            mappings.iter().any(|m| {
+                m.source == ScopeClaimSource::UserField && fields.contains(&m.value.as_str())
+            })
+        };
+        let needs_source = |source: ScopeClaimSource| mappings.iter().any(|m| m.source == source);
+
+        let mut sources = ClaimSources::default();
+        if needs_source(ScopeClaimSource::Group) || needs_field(&["roles"]) {
+            (sources.groups, sources.roles) = user.get_groups_roles_effective().await?;
+        }
+        if needs_field(&CLAIM_USER_VALUES_FIELDS) {
+            sources.values = UserValues::find(&user.id).await?;
+        }
+        match user_attrs {
+            Some(attrs) => sources.attrs.clone_from(attrs),
+            None if needs_source(ScopeClaimSource::Attribute) => {
+                sources.attrs = UserAttrValueEntity::find_for_user(&user.id)
+                    .await?
+                    .into_iter()
+                    .map(|a| (a.key, a.value))
+                    .collect();
+            }
+            None => {}
+        }
+
+        let mut res = HashMap::with_capacity(mappings.len());
+        for mapping in mappings {
+            if let Some(value) = sources.resolve(&mapping, user) {
+                res.insert(mapping.claim, value);
+            }
+        }
+        Ok(res)
+    }
+
     // Accepts a string of scopes seperated by \s and returns a `Vec<&str>` containing all
     // non-custom scopes.
     /// Note: `groups` is not a default scope, but it will be handled like one for performance
@@ -431,21 +622,266 @@ impl From<Scope> for ScopeResponse {
             .attr_include_id
             .map(|attr| attr.split(',').map(String::from).collect());
 
+        let claim_mappings = value
+            .claim_mappings
+            .as_deref()
+            .and_then(|mappings| serde_json::from_str::<Vec<ScopeClaimMapping>>(mappings).ok());
+
         Self {
             id: value.id,
             name: value.name,
             attr_include_access,
             attr_include_id,
+            claim_mappings,
+        }
+    }
+}
+
+/// All user values, which may be needed to resolve claim mappings.
+#[derive(Default)]
+struct ClaimSources {
+    groups: Vec<String>,
+    roles: Vec<String>,
+    values: Option<UserValues>,
+    attrs: HashMap<String, Vec<u8>>,
+}
+
+impl ClaimSources {
+    fn resolve(&self, mapping: &ScopeClaimMapping, user: &User) -> Option<Value> {
+        let value = self.resolve_source(mapping, user)?;
+        match &mapping.typ {
+            None => Some(value),
+            Some(typ) => {
+                let coerced = coerce_claim(value, typ);
+                if coerced.is_none() {
+                    debug!(
+                        "Cannot convert the value for claim '{}' into {:?}",
+                        mapping.claim, typ
+                    );
+                }
+                coerced
+            }
+        }
+    }
+
+    fn resolve_source(&self, mapping: &ScopeClaimMapping, user: &User) -> Option<Value> {
+        match mapping.source {
+            ScopeClaimSource::UserField => self.user_field(&mapping.value, user),
+            ScopeClaimSource::Attribute => self
+                .attrs
+                .get(&mapping.value)
+                .and_then(|value| serde_json::from_slice(value).ok()),
+            ScopeClaimSource::Group => Some(self.group(&mapping.value)),
+        }
+    }
+
+    /// A group ending with `*` returns all matching groups, otherwise the membership.
+    fn group(&self, group: &str) -> Value {
+        if let Some(prefix) = group.strip_suffix('*') {
+            let groups = self
+                .groups
+                .iter()
+                .filter(|g| g.starts_with(prefix))
+                .cloned()
+                .collect::<Vec<_>>();
+            Value::from(groups)
+        } else {
+            Value::Bool(self.groups.iter().any(|g| g == group))
+        }
+    }
+
+    fn user_field(&self, field: &str, user: &User) -> Option<Value> {
+        let values = self.values.as_ref();
+        match field {
+            "birthdate" => values?.birthdate.clone().map(Value::from),
+            "city" => values?.city.clone().map(Value::from),
+            "country" => values?.country.clone().map(Value::from),
+            "created_at" => Some(Value::from(user.created_at)),
+            "email" => Some(Value::from(user.email.clone())),
+            "email_verified" => Some(Value::from(user.email_verified)),
+            "family_name" => user.family_name.clone().map(Value::from),
+            "given_name" => Some(Value::from(user.given_name.clone())),
+            "language" => Some(Value::from(user.language.to_string())),
+            "last_login" => user.last_login.map(Value::from),
+            "mfa_enabled" => Some(Value::from(user.has_webauthn_enabled())),
+            "phone" => values?.phone.clone().map(Value::from),
+            "roles" => Some(Value::from(self.roles.clone())),
+            "street" => values?.street.clone().map(Value::from),
+            "zip" => values?.zip.map(Value::from),
+            _ => None,
+        }
+    }
+}
+
+/// Converts the value into the given type, if possible. `null` values are always skipped.
+fn coerce_claim(value: Value, typ: &ScopeClaimType) -> Option<Value> {
+    match (typ, value) {
+        (_, Value::Null) => None,
+
+        (ScopeClaimType::String, Value::String(s)) => Some(Value::String(s)),
+        (ScopeClaimType::String, Value::Number(n)) => Some(Value::String(n.to_string())),
+        (ScopeClaimType::String, Value::Bool(b)) => Some(Value::String(b.to_string())),
+        (ScopeClaimType::String, Value::Array(arr)) => {
+            let parts = arr
+                .into_iter()
+                .map(|v| match v {
+                    Value::String(s) => Some(s),
+                    Value::Number(n) => Some(n.to_string()),
+                    Value::Bool(b) => Some(b.to_string()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(Value::String(parts.join(",")))
         }
+
+        (ScopeClaimType::Integer, Value::Number(n)) => n
+            .as_i64()
+            .or_else(|| n.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i64))
+            .map(Value::from),
+        (ScopeClaimType::Integer, Value::String(s)) => {
+            s.trim().parse::<i64>().ok().map(Value::from)
+        }
+        (ScopeClaimType::Integer, Value::Bool(b)) => Some(Value::from(i64::from(b))),
+
+        (ScopeClaimType::Number, Value::Number(n)) => Some(Value::Number(n)),
+        (ScopeClaimType::Number, Value::String(s)) => s
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+            .map(Value::Number),
+        (ScopeClaimType::Number, Value::Bool(b)) => Some(Value::from(i64::from(b))),
+
+        (ScopeClaimType::Boolean, Value::Bool(b)) => Some(Value::Bool(b)),
+        (ScopeClaimType::Boolean, Value::String(s)) => match s.trim().to_lowercase().as_str() {
+            "true" | "1" | "yes" => Some(Value::Bool(true)),
+            "false" | "0" | "no" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        (ScopeClaimType::Boolean, Value::Number(n)) => n.as_f64().map(|f| Value::Bool(f != 0.0)),
+
+        (ScopeClaimType::Array, Value::Array(arr)) => Some(Value::Array(arr)),
+        (ScopeClaimType::Array, value) => Some(Value::Array(vec![value])),
+
+        (_, Value::Array(_) | Value::Object(_)) => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::{HashMap, HashSet};
 
     use pretty_assertions::assert_eq;
-    use serde_json::Value;
+    use rauthy_api_types::scopes::{ScopeClaimMapping, ScopeClaimSource, ScopeClaimType};
+    use serde_json::{json, Value};
+
+    use super::{coerce_claim, ClaimSources, Scope};
+
+    fn mapping(claim: &str, source: ScopeClaimSource, value: &str) -> ScopeClaimMapping {
+        ScopeClaimMapping {
+            claim: claim.to_string(),
+            source,
+            value: value.to_string(),
+            include_access: true,
+            include_id: false,
+            typ: None,
+        }
+    }
+
+    #[test]
+    fn test_clean_up_claim_mappings() {
+        let attrs = HashSet::from(["age".to_string()]);
+
+        assert_eq!(Scope::clean_up_claim_mappings(None, &attrs).unwrap(), None);
+        assert_eq!(
+            Scope::clean_up_claim_mappings(Some(vec![]), &attrs).unwrap(),
+            None
+        );
+
+        let mappings = vec![
+            mapping("mail", ScopeClaimSource::UserField, "email"),
+            mapping("age", ScopeClaimSource::Attribute, "age"),
+            mapping("admins", ScopeClaimSource::Group, "admin"),
+            mapping("teams", ScopeClaimSource::Group, "team:*"),
+        ];
+        let json = Scope::clean_up_claim_mappings(Some(mappings.clone()), &attrs)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<ScopeClaimMapping>>(&json).unwrap(),
+            mappings
+        );
+
+        // unknown field / attribute
+        let m = mapping("pw", ScopeClaimSource::UserField, "password");
+        assert!(Scope::clean_up_claim_mappings(Some(vec![m]), &attrs).is_err());
+        let m = mapping("size", ScopeClaimSource::Attribute, "size");
+        assert!(Scope::clean_up_claim_mappings(Some(vec![m]), &attrs).is_err());
+
+        // duplicate claim
+        let m = mapping("mail", ScopeClaimSource::UserField, "email");
+        assert!(Scope::clean_up_claim_mappings(Some(vec![m.clone(), m]), &attrs).is_err());
+
+        // no target
+        let mut m = mapping("mail", ScopeClaimSource::UserField, "email");
+        m.include_access = false;
+        assert!(Scope::clean_up_claim_mappings(Some(vec![m]), &attrs).is_err());
+    }
+
+    #[test]
+    fn test_group_claim() {
+        let sources = ClaimSources {
+            groups: vec![
+                "admin".to_string(),
+                "team:a".to_string(),
+                "team:b".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(sources.group("admin"), Value::Bool(true));
+        assert_eq!(sources.group("team"), Value::Bool(false));
+        assert_eq!(
+            sources.group("team:*"),
+            Value::from(vec!["team:a".to_string(), "team:b".to_string()])
+        );
+        assert_eq!(sources.group("other*"), Value::from(Vec::<String>::new()));
+    }
+
+    #[test]
+    fn test_coerce_claim() {
+        assert_eq!(
+            coerce_claim(json!(42), &ScopeClaimType::String),
+            Some(json!("42"))
+        );
+        assert_eq!(
+            coerce_claim(json!(["a", "b"]), &ScopeClaimType::String),
+            Some(json!("a,b"))
+        );
+        assert_eq!(
+            coerce_claim(json!(" 13 "), &ScopeClaimType::Integer),
+            Some(json!(13))
+        );
+        assert_eq!(coerce_claim(json!(1.5), &ScopeClaimType::Integer), None);
+        assert_eq!(
+            coerce_claim(json!("1.5"), &ScopeClaimType::Number),
+            Some(json!(1.5))
+        );
+        assert_eq!(
+            coerce_claim(json!("Yes"), &ScopeClaimType::Boolean),
+            Some(json!(true))
+        );
+        assert_eq!(coerce_claim(json!("maybe"), &ScopeClaimType::Boolean), None);
+        assert_eq!(
+            coerce_claim(json!("admin"), &ScopeClaimType::Array),
+            Some(json!(["admin"]))
+        );
+        assert_eq!(coerce_claim(Value::Null, &ScopeClaimType::String), None);
+        assert_eq!(
+            coerce_claim(json!({"a": 1}), &ScopeClaimType::Integer),
+            None
+        );
+    }
 
     #[tokio::test]
     async fn test_generic_json() {
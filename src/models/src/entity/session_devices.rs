@@ -0,0 +1,245 @@
+use crate::database::DB;
+use crate::entity::sessions::Session;
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use hiqlite::{params, Param};
+use rauthy_api_types::users::SessionDeviceResponse;
+use rauthy_common::clock;
+use rauthy_common::constants::SESSION_LOCATION_HEADER;
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::real_ip_from_req;
+use rauthy_error::ErrorResponse;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+/// Metadata about the device a user session has been created on. This makes it possible for users
+/// to review their active sessions and sign out single devices.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SessionDevice {
+    pub session_id: String,
+    pub user_id: String,
+    /// A friendly name parsed from the `User-Agent`
+    pub name: String,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    /// Only set, if the `SESSION_LOCATION_HEADER` is configured
+    pub location: Option<String>,
+    pub first_seen: i64,
+    pub last_seen: i64,
+}
+
+// CRUD
+impl SessionDevice {
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM session_devices WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM session_devices WHERE user_id = $1",
+                user_id
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Creates or updates the device for the given session from the current request.
+    pub async fn upsert(
+        session_id: &str,
+        user_id: &str,
+        req: &HttpRequest,
+    ) -> Result<(), ErrorResponse> {
+        let slf = Self::from_req(session_id, user_id, req);
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO session_devices
+(session_id, user_id, name, user_agent, ip, location, first_seen, last_seen)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+ON CONFLICT(session_id) DO UPDATE
+SET name = $3, user_agent = $4, ip = $5, location = $6, last_seen = $8"#,
+                    params!(
+                        slf.session_id,
+                        slf.user_id,
+                        slf.name,
+                        slf.user_agent,
+                        slf.ip,
+                        slf.location,
+                        slf.first_seen,
+                        slf.last_seen
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO session_devices
+(session_id, user_id, name, user_agent, ip, location, first_seen, last_seen)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+ON CONFLICT(session_id) DO UPDATE
+SET name = $3, user_agent = $4, ip = $5, location = $6, last_seen = $8"#,
+                slf.session_id,
+                slf.user_id,
+                slf.name,
+                slf.user_agent,
+                slf.ip,
+                slf.location,
+                slf.first_seen,
+                slf.last_seen,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SessionDevice {
+    fn from_req(session_id: &str, user_id: &str, req: &HttpRequest) -> Self {
+        let user_agent = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|ua| ua.chars().take(256).collect::<String>());
+        let location = SESSION_LOCATION_HEADER.as_ref().and_then(|name| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|loc| loc.chars().take(64).collect::<String>())
+        });
+        let now = clock::now().timestamp();
+
+        Self {
+            session_id: session_id.to_string(),
+            user_id: user_id.to_string(),
+            name: Self::name_from_user_agent(user_agent.as_deref()),
+            user_agent,
+            ip: real_ip_from_req(req).ok().map(|ip| ip.to_string()),
+            location,
+            first_seen: now,
+            last_seen: now,
+        }
+    }
+
+    /// Creates a friendly name like `Firefox on Linux` from the `User-Agent`.
+    pub(crate) fn name_from_user_agent(user_agent: Option<&str>) -> String {
+        let Some(ua) = user_agent else {
+            return "Unknown Device".to_string();
+        };
+
+        // the order matters, because most browsers include the tokens of others
+        let browser = if ua.contains("Edg/") || ua.contains("EdgiOS/") {
+            Some("Edge")
+        } else if ua.contains("OPR/") || ua.contains("Opera") {
+            Some("Opera")
+        } else if ua.contains("Firefox/") || ua.contains("FxiOS/") {
+            Some("Firefox")
+        } else if ua.contains("Chrome/") || ua.contains("CriOS/") {
+            Some("Chrome")
+        } else if ua.contains("Safari/") {
+            Some("Safari")
+        } else {
+            None
+        };
+
+        let os = if ua.contains("Android") {
+            Some("Android")
+        } else if ua.contains("iPhone") {
+            Some("iPhone")
+        } else if ua.contains("iPad") {
+            Some("iPad")
+        } else if ua.contains("Windows") {
+            Some("Windows")
+        } else if ua.contains("Mac OS X") || ua.contains("Macintosh") {
+            Some("macOS")
+        } else if ua.contains("CrOS") {
+            Some("ChromeOS")
+        } else if ua.contains("Linux") {
+            Some("Linux")
+        } else {
+            None
+        };
+
+        match (browser, os) {
+            (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+            (Some(browser), None) => browser.to_string(),
+            (None, Some(os)) => os.to_string(),
+            (None, None) => "Unknown Device".to_string(),
+        }
+    }
+
+    /// Returns the devices for all still valid sessions with the `last_seen` of the session.
+    pub fn into_responses(
+        devices: Vec<Self>,
+        sessions: &[Session],
+        current_session_id: Option<&str>,
+    ) -> Vec<SessionDeviceResponse> {
+        let now = clock::now().timestamp();
+
+        devices
+            .into_iter()
+            .filter_map(|device| {
+                let session = sessions
+                    .iter()
+                    .find(|s| s.id == device.session_id && s.exp > now)?;
+
+                Some(SessionDeviceResponse {
+                    current: current_session_id == Some(device.session_id.as_str()),
+                    last_seen: device.last_seen.max(session.last_seen),
+                    session_id: device.session_id,
+                    name: device.name,
+                    user_agent: device.user_agent,
+                    ip: device.ip,
+                    location: device.location,
+                    first_seen: device.first_seen,
+                    client_id: session.client_id.clone(),
+                    auth_method: session.auth_method.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_from_user_agent() {
+        let name = |ua: &str| SessionDevice::name_from_user_agent(Some(ua));
+
+        assert_eq!(
+            name("Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0"),
+            "Firefox on Linux"
+        );
+        assert_eq!(
+            name("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36 Edg/126.0.0.0"),
+            "Edge on Windows"
+        );
+        assert_eq!(
+            name("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Safari/605.1.15"),
+            "Safari on macOS"
+        );
+        assert_eq!(
+            name("Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Mobile Safari/537.36"),
+            "Chrome on Android"
+        );
+        assert_eq!(
+            name("Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) CriOS/126.0.0.0 Mobile/15E148 Safari/604.1"),
+            "Chrome on iPhone"
+        );
+        assert_eq!(name("curl/8.8.0"), "Unknown Device");
+        assert_eq!(SessionDevice::name_from_user_agent(None), "Unknown Device");
+    }
+}
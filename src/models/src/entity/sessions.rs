@@ -1,26 +1,35 @@
 use crate::api_cookie::ApiCookie;
 use crate::database::{Cache, DB};
 use crate::entity::continuation_token::ContinuationToken;
+use crate::entity::jti_denylist::JtiDenylist;
+use crate::entity::opaque_tokens::OpaqueToken;
+use crate::entity::refresh_tokens::RefreshToken;
 use crate::entity::users::User;
+use crate::events::event::Event;
+use crate::geo_ip::GeoIp;
 use actix_web::cookie::{time, SameSite};
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{cookie, web, HttpRequest};
-use chrono::Utc;
 use hiqlite::{params, Param};
 use rauthy_api_types::generic::SearchParamsIdx;
+use rauthy_api_types::sessions::SessionFilterParams;
+use rauthy_common::clock;
 use rauthy_common::constants::{
-    CACHE_TTL_SESSION, COOKIE_SESSION, COOKIE_SESSION_FED_CM, CSRF_HEADER, SESSION_LIFETIME_FED_CM,
+    SessionAnomalyAction, CACHE_TTL_SESSION, COOKIE_SESSION, COOKIE_SESSION_FED_CM,
+    COOKIE_SESSION_STATE, CSRF_HEADER, SESSION_ANOMALY_ACTION, SESSION_ANOMALY_ASN,
+    SESSION_ANOMALY_MAX_SPEED, SESSION_LIFETIME_FED_CM, SESSION_LIMIT_PER_USER,
+    SESSION_REMEMBER_ME_DAYS,
 };
 use rauthy_common::is_hiqlite;
-use rauthy_common::utils::get_rand;
+use rauthy_common::utils::{base64_url_no_pad_encode, get_rand};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
+use ring::digest;
 use serde::{Deserialize, Serialize};
 use sqlx::{query_as, FromRow, Row};
 use std::borrow::Cow;
 use std::net::IpAddr;
 use std::ops::Add;
 use std::str::FromStr;
-use time::OffsetDateTime;
 use tracing::{error, warn};
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
@@ -35,6 +44,18 @@ pub struct Session {
     pub exp: i64,
     pub last_seen: i64,
     pub remote_ip: Option<String>,
+    /// Set to the ID of the admin, if this is an impersonated session
+    pub impersonator_id: Option<String>,
+    /// Long-lived session the user opted in for at login, which has no idle timeout
+    pub remember_me: bool,
+    /// Unix timestamp of the last successful user authentication for this session
+    pub auth_time: Option<i64>,
+    /// The client, which initiated this session
+    pub client_id: Option<String>,
+    /// The method of the last user authentication, see `SessionAuthMethod`
+    pub auth_method: Option<String>,
+    /// Unix timestamp of the creation, which is the base for the absolute session lifetime
+    pub created_at: i64,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -97,6 +118,25 @@ impl SessionState {
     }
 }
 
+/// The method a user has authenticated with for a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionAuthMethod {
+    Password,
+    Passkey,
+    /// Login via an upstream auth provider
+    Provider,
+}
+
+impl SessionAuthMethod {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Password => "password",
+            Self::Passkey => "passkey",
+            Self::Provider => "provider",
+        }
+    }
+}
+
 // CRUD
 impl Session {
     pub async fn delete(self) -> Result<(), ErrorResponse> {
@@ -110,6 +150,7 @@ impl Session {
                 .await?;
         }
 
+        OpaqueToken::invalidate_for_session(&self.id).await?;
         DB::client().delete(Cache::Session, self.id).await?;
 
         Ok(())
@@ -198,6 +239,45 @@ impl Session {
         Ok(sessions)
     }
 
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let sessions = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM sessions WHERE user_id = $1 ORDER BY exp DESC",
+                    params!(user_id),
+                )
+                .await?
+        } else {
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM sessions WHERE user_id = $1 ORDER BY exp DESC",
+                user_id
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+        Ok(sessions)
+    }
+
+    /// Returns the active sessions for this user, which would exceed the `SESSION_LIMIT_PER_USER`
+    /// if another login would be added, oldest first. The `current_sid` is never included.
+    pub async fn find_over_limit(
+        user_id: &str,
+        current_sid: &str,
+    ) -> Result<Vec<Self>, ErrorResponse> {
+        if *SESSION_LIMIT_PER_USER == 0 {
+            return Ok(Vec::default());
+        }
+
+        let sessions = Self::find_for_user(user_id).await?;
+        Ok(Self::over_limit(
+            sessions,
+            current_sid,
+            *SESSION_LIMIT_PER_USER as usize,
+            clock::now().timestamp(),
+        ))
+    }
+
     pub async fn find_paginated(
         continuation_token: Option<ContinuationToken>,
         page_size: i64,
@@ -370,7 +450,7 @@ OFFSET $2"#,
 
     /// Invalidates all sessions by setting the expiry to `now()`
     pub async fn invalidate_all() -> Result<(), ErrorResponse> {
-        let now = Utc::now().timestamp() - 1;
+        let now = clock::now().timestamp() - 1;
 
         let rows_affected = if is_hiqlite() {
             DB::client()
@@ -390,6 +470,56 @@ OFFSET $2"#,
         Ok(())
     }
 
+    /// Invalidates all "remember me" sessions, while keeping all others intact.
+    pub async fn invalidate_remember_me() -> Result<(), ErrorResponse> {
+        let now = clock::now().timestamp() - 1;
+
+        let ids: Vec<String> = if is_hiqlite() {
+            let rows = DB::client()
+                .execute_returning(
+                    r#"
+UPDATE sessions SET exp = $1
+WHERE remember_me = true AND exp > $1
+RETURNING id"#,
+                    params!(now),
+                )
+                .await?;
+
+            let mut ids = Vec::with_capacity(rows.len());
+            for row in rows {
+                let mut row = row?;
+                ids.push(row.get("id"));
+            }
+            ids
+        } else {
+            let rows = sqlx::query(
+                r#"
+UPDATE sessions SET exp = $1
+WHERE remember_me = true AND exp > $1
+RETURNING id"#,
+            )
+            .bind(now)
+            .fetch_all(DB::conn())
+            .await?;
+
+            let mut ids = Vec::with_capacity(rows.len());
+            for row in rows {
+                ids.push(row.get("id"));
+            }
+            ids
+        };
+
+        let client = DB::client();
+        for sid in ids {
+            RefreshToken::invalidate_for_session(&sid).await?;
+            JtiDenylist::deny_for_session(&sid).await?;
+            OpaqueToken::invalidate_for_session(&sid).await?;
+            client.delete(Cache::Session, sid).await?;
+        }
+
+        Ok(())
+    }
+
     /// If any sessions have been deleted, `Vec<SessionId>` will be returned for cache invalidation.
     pub async fn invalidate_for_user(uid: &str) -> Result<(), ErrorResponse> {
         let sids: Vec<String> = if is_hiqlite() {
@@ -435,11 +565,13 @@ OFFSET $2"#,
                 .execute(
                     r#"
 INSERT INTO
-sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, remote_ip)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, remote_ip,
+impersonator_id, remember_me, auth_time, client_id, auth_method, created_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
 ON CONFLICT(id) DO UPDATE
 SET user_id = $3, roles = $4, groups = $5, is_mfa = $6, state = $7, exp = $8, last_seen = $9,
-remote_ip = $10"#,
+remote_ip = $10, impersonator_id = $11, remember_me = $12, auth_time = $13, client_id = $14,
+auth_method = $15"#,
                     params!(
                         &self.id,
                         &self.csrf_token,
@@ -450,7 +582,13 @@ remote_ip = $10"#,
                         state_str,
                         self.exp,
                         self.last_seen,
-                        &self.remote_ip
+                        &self.remote_ip,
+                        &self.impersonator_id,
+                        self.remember_me,
+                        self.auth_time,
+                        &self.client_id,
+                        &self.auth_method,
+                        self.created_at
                     ),
                 )
                 .await?;
@@ -458,11 +596,13 @@ remote_ip = $10"#,
             sqlx::query!(
                 r#"
 INSERT INTO
-sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, remote_ip)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, remote_ip,
+impersonator_id, remember_me, auth_time, client_id, auth_method, created_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
 ON CONFLICT(id) DO UPDATE
 SET user_id = $3, roles = $4, groups = $5, is_mfa = $6, state = $7, exp = $8, last_seen = $9,
-remote_ip = $10"#,
+remote_ip = $10, impersonator_id = $11, remember_me = $12, auth_time = $13, client_id = $14,
+auth_method = $15"#,
                 self.id,
                 self.csrf_token,
                 self.user_id,
@@ -473,6 +613,12 @@ remote_ip = $10"#,
                 self.exp,
                 self.last_seen,
                 self.remote_ip,
+                self.impersonator_id,
+                self.remember_me,
+                self.auth_time,
+                self.client_id,
+                self.auth_method,
+                self.created_at,
             )
             .execute(DB::conn())
             .await?;
@@ -557,6 +703,26 @@ remote_ip = $10"#,
 
         Ok(res)
     }
+
+    /// Returns all sessions matching the given filter. The `client_id` can only be resolved for
+    /// sessions, which still have a refresh token for this client.
+    pub async fn find_filtered(filter: &SessionFilterParams) -> Result<Vec<Self>, ErrorResponse> {
+        let mut sessions = if let Some(uid) = &filter.user_id {
+            Self::find_for_user(uid).await?
+        } else {
+            Self::find_all().await?
+        };
+
+        if let Some(client_id) = &filter.client_id {
+            let sids = RefreshToken::find_session_ids_for_client(client_id).await?;
+            sessions.retain(|s| sids.contains(&s.id));
+        }
+
+        let now = clock::now().timestamp();
+        sessions.retain(|s| s.matches_filter(filter, now));
+
+        Ok(sessions)
+    }
 }
 
 impl Session {
@@ -564,7 +730,7 @@ impl Session {
     pub fn new(exp_in: u32, remote_ip: Option<IpAddr>) -> Self {
         let id = get_rand(32);
         let csrf_token = get_rand(32);
-        let now = OffsetDateTime::now_utc();
+        let now = clock::now_utc();
 
         Self {
             id,
@@ -579,6 +745,12 @@ impl Session {
                 .unix_timestamp(),
             last_seen: now.unix_timestamp(),
             remote_ip: remote_ip.map(|ip| ip.to_string()),
+            impersonator_id: None,
+            remember_me: false,
+            auth_time: None,
+            client_id: None,
+            auth_method: None,
+            created_at: now.unix_timestamp(),
         }
     }
 
@@ -594,7 +766,7 @@ impl Session {
         let roles = Some(user.roles.clone());
         let groups = user.groups.clone();
 
-        let now = OffsetDateTime::now_utc();
+        let now = clock::now_utc();
 
         // make sure to check the max session lifetime for expiring users
         let exp = if let Some(ts) = user.user_expires {
@@ -629,11 +801,17 @@ impl Session {
             exp,
             last_seen: now.unix_timestamp(),
             remote_ip,
+            impersonator_id: None,
+            remember_me: false,
+            auth_time: None,
+            client_id: None,
+            auth_method: None,
+            created_at: now.unix_timestamp(),
         })
     }
 
     pub fn client_cookie(&self) -> cookie::Cookie {
-        let max_age = self.exp - Utc::now().timestamp();
+        let max_age = self.exp - clock::now().timestamp();
         ApiCookie::build(COOKIE_SESSION, Cow::from(&self.id), max_age)
     }
 
@@ -646,6 +824,54 @@ impl Session {
         )
     }
 
+    /// The JS readable cookie for the OIDC Session Management `check_session_iframe`.
+    pub fn client_cookie_session_state(&self) -> cookie::Cookie<'_> {
+        let max_age = self.exp - clock::now().timestamp();
+        ApiCookie::build_plain(COOKIE_SESSION_STATE, Self::browser_state(&self.id), max_age)
+    }
+
+    /// The `browser_state` for OIDC Session Management. It is derived from the session id without
+    /// leaking it, because it will be readable from JS.
+    pub fn browser_state(session_id: &str) -> String {
+        let hash = digest::digest(&digest::SHA256, session_id.as_bytes());
+        base64_url_no_pad_encode(hash.as_ref())
+    }
+
+    /// Calculates the `session_state` returned with the authorization response in the format
+    /// `<hash>.<salt>`, which the `check_session_iframe` can validate against the current
+    /// `browser_state` cookie.
+    pub fn session_state(client_id: &str, redirect_uri: &str, session_id: &str) -> String {
+        let salt = get_rand(8);
+        Self::session_state_with_salt(client_id, redirect_uri, session_id, &salt)
+    }
+
+    fn session_state_with_salt(
+        client_id: &str,
+        redirect_uri: &str,
+        session_id: &str,
+        salt: &str,
+    ) -> String {
+        let input = format!(
+            "{} {} {} {}",
+            client_id,
+            Self::origin(redirect_uri),
+            Self::browser_state(session_id),
+            salt
+        );
+        let hash = digest::digest(&digest::SHA256, input.as_bytes());
+        format!("{}.{}", base64_url_no_pad_encode(hash.as_ref()), salt)
+    }
+
+    /// Extracts the origin from the `redirect_uri`, which must match the `event.origin` of the
+    /// `postMessage()` sent by the client to the `check_session_iframe`.
+    fn origin(redirect_uri: &str) -> &str {
+        let start = redirect_uri.find("://").map(|i| i + 3).unwrap_or_default();
+        match redirect_uri[start..].find(['/', '?', '#']) {
+            None => redirect_uri,
+            Some(end) => &redirect_uri[..start + end],
+        }
+    }
+
     pub fn extract_from_req(
         session_req: web::ReqData<Option<Session>>,
     ) -> Result<Session, ErrorResponse> {
@@ -677,7 +903,7 @@ impl Session {
     }
 
     pub async fn invalidate(self) -> Result<(), ErrorResponse> {
-        let now = Utc::now().timestamp() - 1;
+        let now = clock::now().timestamp() - 1;
         let state = SessionState::LoggedOut.as_str().to_string();
 
         if is_hiqlite() {
@@ -698,11 +924,83 @@ impl Session {
             .await?;
         }
 
+        // access tokens issued for this session must not outlive it
+        JtiDenylist::deny_for_session(&self.id).await?;
+        OpaqueToken::invalidate_for_session(&self.id).await?;
         DB::client().delete(Cache::Session, self.id).await?;
 
         Ok(())
     }
 
+    fn matches_filter(&self, filter: &SessionFilterParams, now: i64) -> bool {
+        if let Some(remember_me) = filter.remember_me {
+            if self.remember_me != remember_me {
+                return false;
+            }
+        }
+
+        if let Some(ip) = &filter.remote_ip {
+            let matches = self
+                .remote_ip
+                .as_deref()
+                .and_then(|remote_ip| IpAddr::from_str(remote_ip).ok())
+                .map(|remote_ip| &remote_ip == ip)
+                .unwrap_or(false);
+            if !matches {
+                return false;
+            }
+        }
+
+        let idle = now - self.last_seen;
+        if let Some(min_age) = filter.min_age {
+            if idle < min_age as i64 {
+                return false;
+            }
+        }
+        if let Some(max_age) = filter.max_age {
+            if idle > max_age as i64 {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    fn over_limit(mut sessions: Vec<Self>, current_sid: &str, limit: usize, now: i64) -> Vec<Self> {
+        sessions.retain(|s| s.id != current_sid && s.exp > now);
+        if sessions.len() < limit {
+            return Vec::default();
+        }
+
+        sessions.sort_by_key(|s| s.created());
+        sessions.truncate(sessions.len() + 1 - limit);
+        sessions
+    }
+
+    /// Unix timestamp of the creation of this session.
+    #[inline(always)]
+    pub fn created(&self) -> i64 {
+        self.created_at
+    }
+
+    /// Unix timestamp of the last authentication. Falls back to the creation time for sessions
+    /// without a recorded `auth_time`.
+    #[inline(always)]
+    pub fn last_auth(&self) -> i64 {
+        self.auth_time.unwrap_or_else(|| self.created())
+    }
+
+    /// Unix timestamp when this session will time out without any further activity.
+    /// "Remember me" sessions have no idle timeout and are valid until they expire.
+    #[inline(always)]
+    pub fn timeout(&self, session_timeout: u32) -> i64 {
+        if self.remember_me {
+            self.exp
+        } else {
+            self.last_seen + session_timeout as i64
+        }
+    }
+
     #[inline(always)]
     pub fn state(&self) -> Result<SessionState, ErrorResponse> {
         SessionState::from_str(self.state.as_str())
@@ -711,11 +1009,11 @@ impl Session {
 
     /// Checks if the current session is valid: has not expired and has not timed out (last_seen)
     pub fn is_valid(&self, session_timeout: u32, remote_ip: Option<IpAddr>) -> bool {
-        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let now = clock::now_utc().unix_timestamp();
         if self.exp < now {
             return false;
         }
-        if self.last_seen < now - session_timeout as i64 {
+        if self.timeout(session_timeout) < now {
             return false;
         }
 
@@ -774,6 +1072,112 @@ impl Session {
             .collect())
     }
 
+    /// Checks if the session is used from another country or network than it has been seen from
+    /// before, or if the travel since the last request would have been impossible. If so, a
+    /// `SessionAnomaly` event will be created and the `SESSION_ANOMALY_ACTION` applied.
+    /// Returns `false`, if the session has been invalidated.
+    pub async fn validate_geo_ip(
+        &mut self,
+        ip: IpAddr,
+        tx_events: &flume::Sender<Event>,
+    ) -> Result<bool, ErrorResponse> {
+        if !GeoIp::is_enabled() {
+            return Ok(true);
+        }
+        let Some(uid) = self.user_id.clone() else {
+            return Ok(true);
+        };
+        let Some(prev_ip) = self
+            .remote_ip
+            .as_deref()
+            .and_then(|ip| IpAddr::from_str(ip).ok())
+        else {
+            return Ok(true);
+        };
+        if prev_ip == ip {
+            return Ok(true);
+        }
+
+        let (Some(from), Some(to)) = (GeoIp::lookup(&prev_ip), GeoIp::lookup(&ip)) else {
+            return Ok(true);
+        };
+        let elapsed_secs = clock::now().timestamp() - self.last_seen;
+        let Some(anomaly) = from.anomaly(
+            &to,
+            *SESSION_ANOMALY_ASN,
+            *SESSION_ANOMALY_MAX_SPEED,
+            elapsed_secs,
+        ) else {
+            // the next request will be compared to this location
+            self.remote_ip = Some(ip.to_string());
+            self.save().await?;
+            return Ok(true);
+        };
+
+        warn!("Anomaly for session {}: {}", self.id, anomaly);
+        Event::session_anomaly(
+            format!("Session {} for user {}: {}", self.id, uid, anomaly),
+            ip.to_string(),
+        )
+        .with_user_id(uid.clone())
+        .send(tx_events)
+        .await?;
+
+        match *SESSION_ANOMALY_ACTION {
+            SessionAnomalyAction::Warn => {
+                // only the next change should create another event
+                self.remote_ip = Some(ip.to_string());
+                self.save().await?;
+                Ok(true)
+            }
+            SessionAnomalyAction::Reauth => {
+                RefreshToken::invalidate_for_session(&self.id).await?;
+                JtiDenylist::deny_for_session(&self.id).await?;
+                OpaqueToken::invalidate_for_session(&self.id).await?;
+                self.remote_ip = Some(ip.to_string());
+                self.state = SessionState::Init.as_str().to_string();
+                self.save().await?;
+                Ok(true)
+            }
+            SessionAnomalyAction::Invalidate => {
+                RefreshToken::invalidate_for_session(&self.id).await?;
+                self.clone().invalidate().await?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Turns this session into a long-lived "remember me" session, which expires after
+    /// `SESSION_REMEMBER_ME_DAYS`, or when the user expires, whatever comes first.
+    pub async fn set_remember_me(
+        &mut self,
+        user_expires: Option<i64>,
+    ) -> Result<(), ErrorResponse> {
+        if *SESSION_REMEMBER_ME_DAYS == 0 {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "'Remember me' sessions are disabled",
+            ));
+        }
+
+        let exp = clock::now().timestamp() + *SESSION_REMEMBER_ME_DAYS as i64 * 24 * 3600;
+        self.exp = match user_expires {
+            Some(ts) if ts < exp => ts,
+            _ => exp,
+        };
+        self.remember_me = true;
+        self.save().await
+    }
+
+    /// Remembers the client, which initiated this session, and the method of the current login.
+    /// The client will not be overwritten with logins to other clients later on.
+    pub fn set_login_origin(&mut self, client_id: &str, method: SessionAuthMethod) {
+        if self.client_id.is_none() {
+            self.client_id = Some(client_id.to_string());
+        }
+        self.auth_method = Some(method.as_str().to_string());
+    }
+
     #[inline]
     pub async fn set_mfa(&mut self, value: bool) -> Result<(), ErrorResponse> {
         self.is_mfa = value;
@@ -800,7 +1204,7 @@ impl Session {
 
     pub fn validate_user_expiry(&mut self, user: &User) -> Result<(), ErrorResponse> {
         if let Some(ts) = user.user_expires {
-            if OffsetDateTime::now_utc().unix_timestamp() > ts {
+            if clock::now_utc().unix_timestamp() > ts {
                 return Err(ErrorResponse::new(
                     ErrorResponseType::Forbidden,
                     "User has expired",
@@ -826,3 +1230,173 @@ pub fn get_header_value<'a>(
     })?;
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_state() {
+        assert_eq!(
+            Session::origin("https://rp.example.com:8443/callback?x=1"),
+            "https://rp.example.com:8443"
+        );
+        assert_eq!(
+            Session::origin("http://localhost:5173"),
+            "http://localhost:5173"
+        );
+        assert_eq!(
+            Session::origin("https://rp.example.com#frag"),
+            "https://rp.example.com"
+        );
+
+        let state = Session::session_state_with_salt(
+            "client1",
+            "https://rp.example.com/callback",
+            "sid1",
+            "salt1",
+        );
+        let (_, salt) = state.split_once('.').unwrap();
+        assert_eq!(salt, "salt1");
+        // the same origin with another path must result in the same state
+        assert_eq!(
+            state,
+            Session::session_state_with_salt("client1", "https://rp.example.com/", "sid1", "salt1")
+        );
+        assert_ne!(
+            state,
+            Session::session_state_with_salt(
+                "client1",
+                "https://rp.example.com/callback",
+                "sid2",
+                "salt1"
+            )
+        );
+    }
+
+    #[test]
+    fn test_matches_filter() {
+        let mut session = Session::new(3600, Some(IpAddr::from_str("192.168.1.10").unwrap()));
+        let now = session.last_seen + 600;
+
+        assert!(session.matches_filter(&SessionFilterParams::default(), now));
+
+        let filter = SessionFilterParams {
+            remote_ip: Some(IpAddr::from_str("192.168.1.10").unwrap()),
+            ..Default::default()
+        };
+        assert!(session.matches_filter(&filter, now));
+        let filter = SessionFilterParams {
+            remote_ip: Some(IpAddr::from_str("192.168.1.11").unwrap()),
+            ..Default::default()
+        };
+        assert!(!session.matches_filter(&filter, now));
+
+        let filter = SessionFilterParams {
+            min_age: Some(300),
+            max_age: Some(900),
+            ..Default::default()
+        };
+        assert!(session.matches_filter(&filter, now));
+        let filter = SessionFilterParams {
+            min_age: Some(900),
+            ..Default::default()
+        };
+        assert!(!session.matches_filter(&filter, now));
+        let filter = SessionFilterParams {
+            max_age: Some(300),
+            ..Default::default()
+        };
+        assert!(!session.matches_filter(&filter, now));
+
+        let filter = SessionFilterParams {
+            remember_me: Some(true),
+            ..Default::default()
+        };
+        assert!(!session.matches_filter(&filter, now));
+        session.remember_me = true;
+        assert!(session.matches_filter(&filter, now));
+
+        session.remote_ip = None;
+        let filter = SessionFilterParams {
+            remote_ip: Some(IpAddr::from_str("192.168.1.10").unwrap()),
+            ..Default::default()
+        };
+        assert!(!session.matches_filter(&filter, now));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut session = Session::new(3600, None);
+        assert_eq!(session.timeout(600), session.last_seen + 600);
+
+        // "remember me" sessions have no idle timeout
+        session.remember_me = true;
+        assert_eq!(session.timeout(600), session.exp);
+    }
+
+    #[test]
+    fn test_set_login_origin() {
+        let mut session = Session::new(3600, None);
+        session.set_login_origin("app", SessionAuthMethod::Password);
+        assert_eq!(session.client_id.as_deref(), Some("app"));
+        assert_eq!(session.auth_method.as_deref(), Some("password"));
+
+        // a step-up for another client only updates the method
+        session.set_login_origin("admin", SessionAuthMethod::Passkey);
+        assert_eq!(session.client_id.as_deref(), Some("app"));
+        assert_eq!(session.auth_method.as_deref(), Some("passkey"));
+    }
+
+    #[test]
+    fn test_created() {
+        let now = clock::now().timestamp();
+        let mut session = Session::new(3600, None);
+        assert!((now..=now + 1).contains(&session.created()));
+
+        // neither a new expiry nor a "remember me" may move the creation
+        let created = session.created();
+        session.exp = now + 30 * 24 * 3600;
+        session.remember_me = true;
+        assert_eq!(session.created(), created);
+    }
+
+    #[test]
+    fn test_last_auth() {
+        let mut session = Session::new(3600, None);
+        assert_eq!(session.last_auth(), session.created());
+
+        session.auth_time = Some(session.created() + 120);
+        assert_eq!(session.last_auth(), session.created() + 120);
+    }
+
+    #[test]
+    fn test_over_limit() {
+        let now = clock::now().timestamp();
+        // the order depends on the creation only, not on the expiry
+        let session = |id: &str, created_at: i64, exp: i64| {
+            let mut s = Session::new(3600, None);
+            s.id = id.to_string();
+            s.created_at = created_at;
+            s.exp = exp;
+            s
+        };
+        let sessions = vec![
+            session("newest", now - 100, now + 100),
+            session("expired", now - 3600, now - 1),
+            session("oldest", now - 300, now + 300),
+            session("current", now - 50, now + 50),
+            session("middle", now - 200, now + 3600),
+        ];
+
+        assert!(Session::over_limit(sessions.clone(), "current", 4, now).is_empty());
+
+        let evict = Session::over_limit(sessions.clone(), "current", 3, now);
+        assert_eq!(evict.len(), 1);
+        assert_eq!(evict[0].id, "oldest");
+
+        let evict = Session::over_limit(sessions, "current", 1, now);
+        let ids = evict.iter().map(|s| s.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["oldest", "middle", "newest"]);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::database::DB;
+use crate::entity::clients::Client;
+use hiqlite::{params, Param};
+use rauthy_common::is_hiqlite;
+use rauthy_error::ErrorResponse;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, FromRow};
+use tracing::{debug, error};
+
+/// A client the user has logged in to during a session. This is needed to log the user out of
+/// all participating clients at the end of the session.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SessionClient {
+    pub session_id: String,
+    pub client_id: String,
+}
+
+impl SessionClient {
+    pub async fn insert(session_id: String, client_id: String) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO sessions_clients (session_id, client_id)
+VALUES ($1, $2)
+ON CONFLICT (session_id, client_id) DO NOTHING"#,
+                    params!(session_id, client_id),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO sessions_clients (session_id, client_id)
+VALUES ($1, $2)
+ON CONFLICT (session_id, client_id) DO NOTHING"#,
+                session_id,
+                client_id,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the IDs of all clients the user has logged in to during the given session.
+    pub async fn find_client_ids(session_id: &str) -> Result<Vec<String>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_raw(
+                    "SELECT client_id FROM sessions_clients WHERE session_id = $1",
+                    params!(session_id),
+                )
+                .await?
+                .into_iter()
+                .map(|mut row| row.get::<String>("client_id"))
+                .collect()
+        } else {
+            query!(
+                "SELECT client_id FROM sessions_clients WHERE session_id = $1",
+                session_id
+            )
+            .fetch_all(DB::conn())
+            .await?
+            .into_iter()
+            .map(|row| row.client_id)
+            .collect()
+        };
+
+        Ok(res)
+    }
+
+    /// Returns the IDs of all users with a session, in which they have logged in to the client.
+    pub async fn find_user_ids(client_id: &str) -> Result<Vec<String>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_raw(
+                    r#"
+SELECT DISTINCT s.user_id
+FROM sessions_clients sc
+JOIN sessions s ON s.id = sc.session_id
+WHERE sc.client_id = $1 AND s.user_id IS NOT NULL"#,
+                    params!(client_id),
+                )
+                .await?
+                .into_iter()
+                .map(|mut row| row.get::<String>("user_id"))
+                .collect()
+        } else {
+            query!(
+                r#"
+SELECT DISTINCT s.user_id
+FROM sessions_clients sc
+JOIN sessions s ON s.id = sc.session_id
+WHERE sc.client_id = $1 AND s.user_id IS NOT NULL"#,
+                client_id
+            )
+            .fetch_all(DB::conn())
+            .await?
+            .into_iter()
+            .filter_map(|row| row.user_id)
+            .collect()
+        };
+
+        Ok(res)
+    }
+
+    /// Returns the clients of all sessions of the given user.
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_raw(
+                    r#"
+SELECT sc.session_id, sc.client_id
+FROM sessions_clients sc
+JOIN sessions s ON s.id = sc.session_id
+WHERE s.user_id = $1"#,
+                    params!(user_id),
+                )
+                .await?
+                .into_iter()
+                .map(|mut row| Self {
+                    session_id: row.get("session_id"),
+                    client_id: row.get("client_id"),
+                })
+                .collect()
+        } else {
+            query!(
+                r#"
+SELECT sc.session_id, sc.client_id
+FROM sessions_clients sc
+JOIN sessions s ON s.id = sc.session_id
+WHERE s.user_id = $1"#,
+                user_id
+            )
+            .fetch_all(DB::conn())
+            .await?
+            .into_iter()
+            .map(|row| Self {
+                session_id: row.session_id,
+                client_id: row.client_id,
+            })
+            .collect()
+        };
+
+        Ok(res)
+    }
+
+    /// Returns the `frontchannel_logout_uri`s of all clients inside the given session with the
+    /// `iss` and `sid` query params appended.
+    pub async fn frontchannel_logout_uris(
+        issuer: &str,
+        session_id: &str,
+    ) -> Result<Vec<String>, ErrorResponse> {
+        let mut uris = Vec::new();
+        for client_id in Self::find_client_ids(session_id).await? {
+            let client = match Client::find(client_id).await {
+                Ok(client) => client,
+                Err(err) => {
+                    // the client may have been deleted in the meantime
+                    debug!("Cannot find client for front-channel logout: {:?}", err);
+                    continue;
+                }
+            };
+            let Some(uri) = client.frontchannel_logout_uri else {
+                continue;
+            };
+
+            match Url::parse_with_params(&uri, &[("iss", issuer), ("sid", session_id)]) {
+                Ok(url) => uris.push(url.to_string()),
+                Err(err) => {
+                    error!(
+                        "Invalid frontchannel_logout_uri for client {}: {}",
+                        client.id, err
+                    );
+                }
+            }
+        }
+
+        Ok(uris)
+    }
+}
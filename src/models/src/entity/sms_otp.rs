@@ -0,0 +1,294 @@
+use crate::database::DB;
+use crate::sms::Sms;
+use hiqlite::{params, Param};
+use rand::Rng;
+use rauthy_common::clock;
+use rauthy_common::constants::{SMS_OTP_LIFETIME, SMS_OTP_RESEND_SECS};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::{base64_url_no_pad_encode, constant_time_eq};
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+use tracing::debug;
+
+/// The amount of wrong guesses, after which a code cannot be used anymore
+const SMS_OTP_MAX_ATTEMPTS: i64 = 5;
+
+/// A verified phone number for one-time codes via SMS as a 2nd factor. Only available, if an
+/// `SMS_PROVIDER` has been configured.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserSmsOtp {
+    pub user_id: String,
+    /// E.164 format
+    pub phone: String,
+    /// The number will only be used for logins, after it has been verified with a valid code
+    pub verified: bool,
+    pub created: i64,
+    pub code_hash: Option<String>,
+    pub expires: Option<i64>,
+    /// Failed attempts for the current code
+    pub attempts: i64,
+    pub last_sent: Option<i64>,
+}
+
+// CRUD
+impl UserSmsOtp {
+    pub async fn delete(user_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM user_sms_otp WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?;
+        } else {
+            query!("DELETE FROM user_sms_otp WHERE user_id = $1", user_id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find(user_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        let slf = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM user_sms_otp WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?
+                .into_iter()
+                .next()
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM user_sms_otp WHERE user_id = $1",
+                user_id
+            )
+            .fetch_optional(DB::conn())
+            .await?
+        };
+
+        Ok(slf)
+    }
+
+    async fn save(&self) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_sms_otp
+(user_id, phone, verified, created, code_hash, expires, attempts, last_sent)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+ON CONFLICT(user_id) DO UPDATE
+SET phone = $2, verified = $3, created = $4, code_hash = $5, expires = $6, attempts = $7,
+last_sent = $8"#,
+                    params!(
+                        self.user_id.clone(),
+                        self.phone.clone(),
+                        self.verified,
+                        self.created,
+                        self.code_hash.clone(),
+                        self.expires,
+                        self.attempts,
+                        self.last_sent
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO user_sms_otp
+(user_id, phone, verified, created, code_hash, expires, attempts, last_sent)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+ON CONFLICT(user_id) DO UPDATE
+SET phone = $2, verified = $3, created = $4, code_hash = $5, expires = $6, attempts = $7,
+last_sent = $8"#,
+                self.user_id,
+                self.phone,
+                self.verified,
+                self.created,
+                self.code_hash,
+                self.expires,
+                self.attempts,
+                self.last_sent,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UserSmsOtp {
+    /// Starts the verification of a new phone number by sending a code to it. An existing, not
+    /// yet verified number will be replaced.
+    pub async fn enroll(user_id: String, phone: String) -> Result<(), ErrorResponse> {
+        if !Sms::is_enabled() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "SMS OTP is not enabled",
+            ));
+        }
+        if let Some(existing) = Self::find(&user_id).await? {
+            if existing.verified {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "SMS OTP is already enabled for this user",
+                ));
+            }
+            // changing the number must not bypass the resend rate limit
+            let not_before = existing.last_sent.unwrap_or(0) + *SMS_OTP_RESEND_SECS as i64;
+            if not_before > clock::now().timestamp() {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::TooManyRequests(not_before),
+                    "Please wait before requesting another code",
+                ));
+            }
+        }
+
+        let mut slf = Self {
+            user_id,
+            phone,
+            verified: false,
+            created: clock::now().timestamp(),
+            code_hash: None,
+            expires: None,
+            attempts: 0,
+            last_sent: None,
+        };
+        slf.send_code().await
+    }
+
+    /// Verifies the phone number of a pending enrollment.
+    pub async fn confirm(user_id: &str, code: &str) -> Result<(), ErrorResponse> {
+        let Some(mut slf) = Self::find(user_id).await? else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "No SMS OTP enrollment has been started",
+            ));
+        };
+        if slf.verified {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "SMS OTP is already enabled for this user",
+            ));
+        }
+
+        slf.verify(code).await?;
+        slf.verified = true;
+        slf.created = clock::now().timestamp();
+        slf.save().await
+    }
+
+    pub async fn is_enabled(user_id: &str) -> Result<bool, ErrorResponse> {
+        if !Sms::is_enabled() {
+            return Ok(false);
+        }
+        Ok(Self::find(user_id)
+            .await?
+            .map(|slf| slf.verified)
+            .unwrap_or(false))
+    }
+
+    /// Sends a new login code to the verified number of the user.
+    pub async fn send_for_user(user_id: &str) -> Result<(), ErrorResponse> {
+        match Self::find(user_id).await? {
+            Some(mut slf) if slf.verified => slf.send_code().await,
+            _ => Err(Self::err_invalid()),
+        }
+    }
+
+    /// Validates a login code against the verified number of the user.
+    pub async fn validate_for_user(user_id: &str, code: &str) -> Result<(), ErrorResponse> {
+        match Self::find(user_id).await? {
+            Some(mut slf) if slf.verified => slf.verify(code).await,
+            _ => Err(Self::err_invalid()),
+        }
+    }
+
+    /// Returns the number with all but the last 3 digits masked.
+    pub fn phone_masked(&self) -> String {
+        let masked = self.phone.len().saturating_sub(3);
+        format!(
+            "{}{}",
+            "*".repeat(masked),
+            self.phone.get(masked..).unwrap_or_default()
+        )
+    }
+
+    /// Within `SMS_OTP_RESEND_SECS`, no new SMS will be sent and the last code stays valid.
+    async fn send_code(&mut self) -> Result<(), ErrorResponse> {
+        let now = clock::now().timestamp();
+        let code_valid = self.code_hash.is_some() && self.expires.unwrap_or(0) > now;
+        if code_valid && self.last_sent.unwrap_or(0) + *SMS_OTP_RESEND_SECS as i64 > now {
+            debug!("Skipping SMS OTP for user {} - rate limited", self.user_id);
+            return Ok(());
+        }
+
+        let code = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+        self.code_hash = Some(Self::hash(&code));
+        self.expires = Some(now + *SMS_OTP_LIFETIME as i64);
+        self.attempts = 0;
+        self.last_sent = Some(now);
+
+        // send first, so a failed delivery does not trigger the resend rate limit
+        Sms::send(&self.phone, &format!("Your login code is: {}", code)).await?;
+        self.save().await
+    }
+
+    async fn verify(&mut self, code: &str) -> Result<(), ErrorResponse> {
+        let Some(code_hash) = self.code_hash.as_deref() else {
+            return Err(Self::err_invalid());
+        };
+        if self.expires.unwrap_or(0) <= clock::now().timestamp()
+            || self.attempts >= SMS_OTP_MAX_ATTEMPTS
+        {
+            return Err(Self::err_invalid());
+        }
+
+        if constant_time_eq(code_hash.as_bytes(), Self::hash(code.trim()).as_bytes()) {
+            self.code_hash = None;
+            self.expires = None;
+            self.attempts = 0;
+            self.save().await
+        } else {
+            self.attempts += 1;
+            self.save().await?;
+            Err(Self::err_invalid())
+        }
+    }
+
+    fn hash(code: &str) -> String {
+        let hash = digest::digest(&digest::SHA256, code.as_bytes());
+        base64_url_no_pad_encode(hash.as_ref())
+    }
+
+    fn err_invalid() -> ErrorResponse {
+        ErrorResponse::new(ErrorResponseType::Unauthorized, "Invalid SMS code")
+            .with_code(ErrorCode::InvalidCredentials)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phone_masked() {
+        let otp = UserSmsOtp {
+            user_id: "id".to_string(),
+            phone: "+4915112345678".to_string(),
+            verified: true,
+            created: 0,
+            code_hash: None,
+            expires: None,
+            attempts: 0,
+            last_sent: None,
+        };
+        assert_eq!(otp.phone_masked(), "***********678");
+    }
+}
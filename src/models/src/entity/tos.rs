@@ -0,0 +1,128 @@
+use crate::database::DB;
+use actix_web::HttpRequest;
+use hiqlite::{params, Param};
+use rauthy_api_types::users::TosAcceptanceResponse;
+use rauthy_common::clock;
+use rauthy_common::constants::TOS_VERSION;
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::real_ip_from_req;
+use rauthy_error::ErrorResponse;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+/// A users acceptance of a specific version of the terms of service.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TosAcceptance {
+    pub user_id: String,
+    pub version: String,
+    pub accepted_at: i64,
+    pub ip: String,
+}
+
+impl From<TosAcceptance> for TosAcceptanceResponse {
+    fn from(value: TosAcceptance) -> Self {
+        Self {
+            version: value.version,
+            accepted_at: value.accepted_at,
+            ip: value.ip,
+        }
+    }
+}
+
+impl TosAcceptance {
+    /// Persists the acceptance of the given version. Accepting the same version again is a no-op.
+    pub async fn accept(
+        req: &HttpRequest,
+        user_id: String,
+        version: String,
+    ) -> Result<(), ErrorResponse> {
+        let slf = Self {
+            user_id,
+            version,
+            accepted_at: clock::now().timestamp(),
+            ip: real_ip_from_req(req)?.to_string(),
+        };
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO tos_acceptances (user_id, version, accepted_at, ip)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT (user_id, version) DO NOTHING"#,
+                    params!(slf.user_id, slf.version, slf.accepted_at, slf.ip),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO tos_acceptances (user_id, version, accepted_at, ip)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT (user_id, version) DO NOTHING"#,
+                slf.user_id,
+                slf.version,
+                slf.accepted_at,
+                slf.ip,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns all accepted versions for the given user, oldest first.
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM tos_acceptances WHERE user_id = $1 ORDER BY accepted_at",
+                    params!(user_id),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM tos_acceptances WHERE user_id = $1 ORDER BY accepted_at",
+                user_id
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Returns the current `TOS_VERSION`, if it is configured and has not been accepted by the
+    /// given user yet.
+    pub async fn pending_version(user_id: &str) -> Result<Option<&'static str>, ErrorResponse> {
+        let Some(version) = TOS_VERSION.as_deref() else {
+            return Ok(None);
+        };
+
+        let accepted = if is_hiqlite() {
+            !DB::client()
+                .query_raw(
+                    "SELECT 1 FROM tos_acceptances WHERE user_id = $1 AND version = $2",
+                    params!(user_id, version),
+                )
+                .await?
+                .is_empty()
+        } else {
+            query!(
+                "SELECT 1 AS found FROM tos_acceptances WHERE user_id = $1 AND version = $2",
+                user_id,
+                version,
+            )
+            .fetch_optional(DB::conn())
+            .await?
+            .is_some()
+        };
+
+        if accepted {
+            Ok(None)
+        } else {
+            Ok(Some(version))
+        }
+    }
+}
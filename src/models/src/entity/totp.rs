@@ -0,0 +1,336 @@
+use crate::database::DB;
+use cryptr::EncValue;
+use hiqlite::{params, Param};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use rand::RngCore;
+use rauthy_common::clock;
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::constant_time_eq;
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+
+const TOTP_DIGITS: u32 = 6;
+const TOTP_PERIOD: i64 = 30;
+/// The amount of time steps a code may be off into either direction to allow for clock skew
+const TOTP_SKEW: i64 = 1;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// A TOTP (RFC 6238) secret as an additional 2nd factor for users without a passkey.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserTotp {
+    pub user_id: String,
+    /// The encrypted secret
+    pub secret: Vec<u8>,
+    /// The secret will only be used for logins, after it has been confirmed with a valid code
+    pub confirmed: bool,
+    pub created: i64,
+    /// The last accepted time step, which prevents a replay of the same code
+    pub last_step: i64,
+}
+
+// CRUD
+impl UserTotp {
+    pub async fn delete(user_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute("DELETE FROM user_totp WHERE user_id = $1", params!(user_id))
+                .await?;
+        } else {
+            query!("DELETE FROM user_totp WHERE user_id = $1", user_id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find(user_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        let slf = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM user_totp WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?
+                .into_iter()
+                .next()
+        } else {
+            query_as!(Self, "SELECT * FROM user_totp WHERE user_id = $1", user_id)
+                .fetch_optional(DB::conn())
+                .await?
+        };
+
+        Ok(slf)
+    }
+
+    async fn save(&self) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_totp (user_id, secret, confirmed, created, last_step)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT(user_id) DO UPDATE
+SET secret = $2, confirmed = $3, created = $4, last_step = $5"#,
+                    params!(
+                        self.user_id.clone(),
+                        self.secret.clone(),
+                        self.confirmed,
+                        self.created,
+                        self.last_step
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO user_totp (user_id, secret, confirmed, created, last_step)
+VALUES ($1, $2, $3, $4, $5)
+ON CONFLICT(user_id) DO UPDATE
+SET secret = $2, confirmed = $3, created = $4, last_step = $5"#,
+                self.user_id,
+                self.secret,
+                self.confirmed,
+                self.created,
+                self.last_step,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UserTotp {
+    /// Starts a new enrollment and returns the base32 encoded secret. An existing, not yet
+    /// confirmed secret will be replaced.
+    pub async fn enroll(user_id: String) -> Result<String, ErrorResponse> {
+        if Self::is_enabled(&user_id).await? {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "TOTP is already enabled for this user",
+            ));
+        }
+
+        let mut secret = [0u8; 20];
+        rand::thread_rng().fill_bytes(&mut secret);
+
+        Self {
+            user_id,
+            secret: EncValue::encrypt(&secret)?.into_bytes().to_vec(),
+            confirmed: false,
+            created: clock::now().timestamp(),
+            last_step: 0,
+        }
+        .save()
+        .await?;
+
+        Ok(Self::base32_encode(&secret))
+    }
+
+    /// Confirms a pending enrollment with a code from the authenticator app.
+    pub async fn confirm(user_id: &str, code: &str) -> Result<(), ErrorResponse> {
+        let Some(mut slf) = Self::find(user_id).await? else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "No TOTP enrollment has been started",
+            ));
+        };
+        if slf.confirmed {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "TOTP is already enabled for this user",
+            ));
+        }
+
+        slf.verify(code).await?;
+        slf.confirmed = true;
+        slf.save().await
+    }
+
+    pub async fn is_enabled(user_id: &str) -> Result<bool, ErrorResponse> {
+        Ok(Self::find(user_id)
+            .await?
+            .map(|slf| slf.confirmed)
+            .unwrap_or(false))
+    }
+
+    /// Validates a code against the confirmed secret of the user.
+    pub async fn validate_for_user(user_id: &str, code: &str) -> Result<(), ErrorResponse> {
+        match Self::find(user_id).await? {
+            Some(mut slf) if slf.confirmed => slf.verify(code).await,
+            _ => Err(Self::err_invalid()),
+        }
+    }
+
+    /// Returns the `otpauth://` URI, which can be added to an authenticator app directly or
+    /// rendered as a QR code.
+    pub fn provisioning_uri(secret: &str, issuer: &str, account: &str) -> String {
+        let issuer = utf8_percent_encode(issuer, NON_ALPHANUMERIC).to_string();
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            issuer,
+            utf8_percent_encode(account, NON_ALPHANUMERIC),
+            secret,
+            issuer,
+            TOTP_DIGITS,
+            TOTP_PERIOD,
+        )
+    }
+
+    /// Renders the provisioning URI as a QR code in SVG format.
+    pub fn provisioning_qr_svg(uri: &str) -> Result<String, ErrorResponse> {
+        let qr = QrCode::new(uri.as_bytes()).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("Cannot create the TOTP QR code: {}", err),
+            )
+        })?;
+        Ok(qr.render::<svg::Color>().min_dimensions(200, 200).build())
+    }
+
+    async fn verify(&mut self, code: &str) -> Result<(), ErrorResponse> {
+        let secret = EncValue::try_from(self.secret.clone())?.decrypt()?;
+        let step = Self::matching_step(&secret, code, clock::now().timestamp(), self.last_step)
+            .ok_or_else(Self::err_invalid)?;
+
+        self.last_step = step;
+        self.save().await
+    }
+
+    /// Returns the time step the code is valid for. Steps up to and including the `last_step`
+    /// are rejected, so a code can only ever be used once.
+    fn matching_step(secret: &[u8], code: &str, now: i64, last_step: i64) -> Option<i64> {
+        let code = code.trim();
+        if code.len() != TOTP_DIGITS as usize {
+            return None;
+        }
+
+        let current = now / TOTP_PERIOD;
+        (current - TOTP_SKEW..=current + TOTP_SKEW)
+            .filter(|step| *step > last_step)
+            .find(|step| {
+                let expected = format!(
+                    "{:0width$}",
+                    Self::hotp(secret, *step as u64),
+                    width = TOTP_DIGITS as usize
+                );
+                constant_time_eq(expected.as_bytes(), code.as_bytes())
+            })
+    }
+
+    /// HOTP value (RFC 4226) for the given counter
+    fn hotp(secret: &[u8], counter: u64) -> u32 {
+        let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+        let tag = hmac::sign(&key, &counter.to_be_bytes());
+        let hash = tag.as_ref();
+
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let bin = u32::from_be_bytes([
+            hash[offset] & 0x7f,
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ]);
+        bin % 10u32.pow(TOTP_DIGITS)
+    }
+
+    /// RFC 4648 base32 without padding, like expected by authenticator apps
+    fn base32_encode(bytes: &[u8]) -> String {
+        let mut res = String::with_capacity(bytes.len().div_ceil(5) * 8);
+        let mut buf = 0u32;
+        let mut bits = 0;
+
+        for byte in bytes {
+            buf = (buf << 8) | *byte as u32;
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                res.push(BASE32_ALPHABET[((buf >> bits) & 0x1f) as usize] as char);
+            }
+        }
+        if bits > 0 {
+            res.push(BASE32_ALPHABET[((buf << (5 - bits)) & 0x1f) as usize] as char);
+        }
+
+        res
+    }
+
+    fn err_invalid() -> ErrorResponse {
+        ErrorResponse::new(ErrorResponseType::Unauthorized, "Invalid TOTP code")
+            .with_code(ErrorCode::InvalidCredentials)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // test vectors from RFC 6238 Appendix B, truncated to 6 digits
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_hotp() {
+        assert_eq!(UserTotp::hotp(SECRET, 59 / 30), 287082);
+        assert_eq!(UserTotp::hotp(SECRET, 1111111109 / 30), 81804);
+        assert_eq!(UserTotp::hotp(SECRET, 1234567890 / 30), 5924);
+        assert_eq!(UserTotp::hotp(SECRET, 2000000000 / 30), 279037);
+    }
+
+    #[test]
+    fn test_matching_step() {
+        let now = 1111111109;
+        let step = now / TOTP_PERIOD;
+
+        assert_eq!(
+            UserTotp::matching_step(SECRET, "081804", now, 0),
+            Some(step)
+        );
+        // clock skew of one step
+        assert_eq!(
+            UserTotp::matching_step(SECRET, "081804", now + TOTP_PERIOD, 0),
+            Some(step)
+        );
+        assert_eq!(
+            UserTotp::matching_step(SECRET, "081804", now + 2 * TOTP_PERIOD, 0),
+            None
+        );
+        // replay
+        assert_eq!(UserTotp::matching_step(SECRET, "081804", now, step), None);
+        assert_eq!(UserTotp::matching_step(SECRET, "81804", now, 0), None);
+        assert_eq!(UserTotp::matching_step(SECRET, "000000", now, 0), None);
+    }
+
+    #[test]
+    fn test_base32_encode() {
+        assert_eq!(UserTotp::base32_encode(b""), "");
+        assert_eq!(UserTotp::base32_encode(b"f"), "MY");
+        assert_eq!(UserTotp::base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(
+            UserTotp::base32_encode(SECRET),
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"
+        );
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        let uri = UserTotp::provisioning_uri("MZXW6YTBOI", "Rauthy", "admin@localhost");
+        assert_eq!(
+            uri,
+            "otpauth://totp/Rauthy:admin%40localhost?secret=MZXW6YTBOI&issuer=Rauthy&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_provisioning_qr_svg() {
+        let uri = UserTotp::provisioning_uri("MZXW6YTBOI", "Rauthy", "admin@localhost");
+        let svg = UserTotp::provisioning_qr_svg(&uri).unwrap();
+        assert!(svg.contains("<svg"));
+        assert!(svg.ends_with("</svg>"));
+    }
+}
@@ -0,0 +1,311 @@
+use crate::api_cookie::ApiCookie;
+use crate::database::DB;
+use crate::entity::session_devices::SessionDevice;
+use actix_web::cookie::Cookie;
+use actix_web::http::header;
+use actix_web::HttpRequest;
+use hiqlite::{params, Param};
+use rauthy_api_types::users::TrustedDeviceResponse;
+use rauthy_common::clock;
+use rauthy_common::constants::{COOKIE_TRUSTED_DEVICE, MFA_TRUSTED_DEVICE_DAYS};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::get_rand;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+use tracing::{debug, error};
+
+/// A device, on which the user has opted in to skip the 2nd factor for password logins for
+/// `MFA_TRUSTED_DEVICE_DAYS`. The device is identified by an encrypted cookie, which contains
+/// both the user and the device id, and is only valid as long as this entry exists.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TrustedDevice {
+    pub id: String,
+    pub user_id: String,
+    /// A friendly name parsed from the `User-Agent`
+    pub name: String,
+    pub user_agent: Option<String>,
+    pub created: i64,
+    pub expires: i64,
+    pub last_used: i64,
+}
+
+// CRUD
+impl TrustedDevice {
+    pub async fn delete(user_id: &str, id: &str) -> Result<(), ErrorResponse> {
+        let rows_affected = if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM trusted_devices WHERE id = $1 AND user_id = $2",
+                    params!(id, user_id),
+                )
+                .await?
+        } else {
+            query!(
+                "DELETE FROM trusted_devices WHERE id = $1 AND user_id = $2",
+                id,
+                user_id
+            )
+            .execute(DB::conn())
+            .await?
+            .rows_affected() as usize
+        };
+
+        if rows_affected == 0 {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "Trusted device does not exist for this user",
+            ));
+        }
+        Ok(())
+    }
+
+    pub async fn delete_all_for_user(user_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM trusted_devices WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?;
+        } else {
+            query!("DELETE FROM trusted_devices WHERE user_id = $1", user_id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_expired() -> Result<(), ErrorResponse> {
+        let now = clock::now().timestamp();
+
+        let rows_affected = if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM trusted_devices WHERE expires < $1",
+                    params!(now),
+                )
+                .await?
+        } else {
+            query!("DELETE FROM trusted_devices WHERE expires < $1", now)
+                .execute(DB::conn())
+                .await?
+                .rows_affected() as usize
+        };
+        debug!("Cleaned up {} expired trusted devices", rows_affected);
+
+        Ok(())
+    }
+
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let now = clock::now().timestamp();
+
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM trusted_devices WHERE user_id = $1 AND expires > $2",
+                    params!(user_id, now),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM trusted_devices WHERE user_id = $1 AND expires > $2",
+                user_id,
+                now
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    async fn find_valid(id: &str, user_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        let now = clock::now().timestamp();
+
+        let slf = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    r#"
+SELECT * FROM trusted_devices
+WHERE id = $1 AND user_id = $2 AND expires > $3"#,
+                    params!(id, user_id, now),
+                )
+                .await?
+                .into_iter()
+                .next()
+        } else {
+            query_as!(
+                Self,
+                r#"
+SELECT * FROM trusted_devices
+WHERE id = $1 AND user_id = $2 AND expires > $3"#,
+                id,
+                user_id,
+                now
+            )
+            .fetch_optional(DB::conn())
+            .await?
+        };
+
+        Ok(slf)
+    }
+
+    async fn insert(&self) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO trusted_devices
+(id, user_id, name, user_agent, created, expires, last_used)
+VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+                    params!(
+                        self.id.clone(),
+                        self.user_id.clone(),
+                        self.name.clone(),
+                        self.user_agent.clone(),
+                        self.created,
+                        self.expires,
+                        self.last_used
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO trusted_devices
+(id, user_id, name, user_agent, created, expires, last_used)
+VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+                self.id,
+                self.user_id,
+                self.name,
+                self.user_agent,
+                self.created,
+                self.expires,
+                self.last_used,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn update_last_used(&self) -> Result<(), ErrorResponse> {
+        let now = clock::now().timestamp();
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "UPDATE trusted_devices SET last_used = $1 WHERE id = $2",
+                    params!(now, self.id.clone()),
+                )
+                .await?;
+        } else {
+            query!(
+                "UPDATE trusted_devices SET last_used = $1 WHERE id = $2",
+                now,
+                self.id
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TrustedDevice {
+    #[inline]
+    pub fn is_enabled() -> bool {
+        *MFA_TRUSTED_DEVICE_DAYS > 0
+    }
+
+    /// Trusts the device of the current request for the given user and returns the cookie,
+    /// which identifies it for future logins.
+    pub async fn create(
+        user_id: String,
+        req: &HttpRequest,
+    ) -> Result<Cookie<'static>, ErrorResponse> {
+        let user_agent = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|ua| ua.chars().take(256).collect::<String>());
+        let now = clock::now().timestamp();
+        let lifetime = *MFA_TRUSTED_DEVICE_DAYS as i64 * 24 * 3600;
+
+        let slf = Self {
+            id: get_rand(32),
+            user_id,
+            name: SessionDevice::name_from_user_agent(user_agent.as_deref()),
+            user_agent,
+            created: now,
+            expires: now + lifetime,
+            last_used: now,
+        };
+        slf.insert().await?;
+
+        Ok(ApiCookie::build(
+            COOKIE_TRUSTED_DEVICE,
+            slf.cookie_value(),
+            lifetime,
+        ))
+    }
+
+    /// Returns `true` if the request comes from a device, which has been trusted by this very
+    /// user. Any error is logged and treated as an untrusted device.
+    pub async fn is_trusted(req: &HttpRequest, user_id: &str) -> bool {
+        if !Self::is_enabled() {
+            return false;
+        }
+        let Some(id) = Self::id_from_req(req, user_id) else {
+            return false;
+        };
+
+        match Self::find_valid(&id, user_id).await {
+            Ok(Some(slf)) => {
+                if let Err(err) = slf.update_last_used().await {
+                    error!("Error updating trusted device: {:?}", err);
+                }
+                true
+            }
+            Ok(None) => false,
+            Err(err) => {
+                error!("Error looking up trusted device: {:?}", err);
+                false
+            }
+        }
+    }
+
+    /// Returns the device id from the cookie, if it has been issued for the given user.
+    fn id_from_req(req: &HttpRequest, user_id: &str) -> Option<String> {
+        let value = ApiCookie::from_req(req, COOKIE_TRUSTED_DEVICE)?;
+        let (cookie_user_id, id) = value.rsplit_once(':')?;
+        if cookie_user_id == user_id {
+            Some(id.to_string())
+        } else {
+            None
+        }
+    }
+
+    fn cookie_value(&self) -> String {
+        format!("{}:{}", self.user_id, self.id)
+    }
+
+    pub fn into_response(self, req: &HttpRequest) -> TrustedDeviceResponse {
+        let current = Self::id_from_req(req, &self.user_id).as_deref() == Some(self.id.as_str());
+
+        TrustedDeviceResponse {
+            id: self.id,
+            name: self.name,
+            user_agent: self.user_agent,
+            created: self.created,
+            expires: self.expires,
+            last_used: self.last_used,
+            current,
+        }
+    }
+}
@@ -4,16 +4,17 @@ use crate::entity::scopes::Scope;
 use crate::entity::users::User;
 use hiqlite::{params, Param, Params};
 use rauthy_api_types::users::{
-    UserAttrConfigRequest, UserAttrConfigValueResponse, UserAttrValueResponse,
-    UserAttrValuesUpdateRequest,
+    UserAttrConfigRequest, UserAttrConfigValueResponse, UserAttrType, UserAttrValueRequest,
+    UserAttrValueResponse, UserAttrValuesUpdateRequest,
 };
 use rauthy_common::constants::{CACHE_TTL_APP, CACHE_TTL_USER, IDX_USER_ATTR_CONFIG};
 use rauthy_common::is_hiqlite;
-use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::{FromRow, Row};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 // Additional custom attributes for users. These can be set for every user and then mapped to a
 // scope, to include them in JWT tokens.
@@ -21,6 +22,13 @@ use std::collections::HashSet;
 pub struct UserAttrConfigEntity {
     pub name: String,
     pub desc: Option<String>,
+    pub typ: String,
+    pub required: bool,
+    pub regex: Option<String>,
+    /// Users without a value must provide it during their next login
+    pub required_at_login: bool,
+    /// Only claim eligible attributes can be mapped into tokens and the userinfo
+    pub claim_eligible: bool,
 }
 
 // CRUD
@@ -45,28 +53,54 @@ impl UserAttrConfigEntity {
             ));
         }
 
+        let slf = Self {
+            name: new_attr.name,
+            desc: new_attr.desc,
+            typ: new_attr.typ.unwrap_or_default().as_str().to_string(),
+            required: new_attr.required.unwrap_or(false),
+            regex: new_attr.regex.filter(|re| !re.is_empty()),
+            required_at_login: new_attr.required_at_login.unwrap_or(false),
+            claim_eligible: new_attr.claim_eligible.unwrap_or(true),
+        };
+        slf.validate_schema()?;
+
         if is_hiqlite() {
             DB::client()
                 .execute(
-                    "INSERT INTO user_attr_config (name, \"desc\") VALUES ($1, $2)",
-                    params!(&new_attr.name, &new_attr.desc),
+                    r#"
+INSERT INTO user_attr_config
+(name, "desc", typ, required, regex, required_at_login, claim_eligible)
+VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+                    params!(
+                        &slf.name,
+                        &slf.desc,
+                        &slf.typ,
+                        slf.required,
+                        &slf.regex,
+                        slf.required_at_login,
+                        slf.claim_eligible
+                    ),
                 )
                 .await?;
         } else {
             sqlx::query!(
-                "INSERT INTO user_attr_config (name, \"desc\") VALUES ($1, $2)",
-                new_attr.name,
-                new_attr.desc,
+                r#"
+INSERT INTO user_attr_config
+(name, "desc", typ, required, regex, required_at_login, claim_eligible)
+VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+                slf.name,
+                slf.desc,
+                slf.typ,
+                slf.required,
+                slf.regex,
+                slf.required_at_login,
+                slf.claim_eligible,
             )
             .execute(DB::conn())
             .await?;
         };
 
         let mut attrs = UserAttrConfigEntity::find_all().await?;
-        let slf = Self {
-            name: new_attr.name.clone(),
-            desc: new_attr.desc.clone(),
-        };
         attrs.push(slf.clone());
         DB::client()
             .put(Cache::App, IDX_USER_ATTR_CONFIG, &attrs, CACHE_TTL_APP)
@@ -235,6 +269,25 @@ impl UserAttrConfigEntity {
 
         slf.name.clone_from(&req_data.name);
         slf.desc.clone_from(&req_data.desc);
+        if let Some(typ) = req_data.typ {
+            slf.typ = typ.as_str().to_string();
+        }
+        if let Some(required) = req_data.required {
+            slf.required = required;
+        }
+        if let Some(required_at_login) = req_data.required_at_login {
+            slf.required_at_login = required_at_login;
+        }
+        if let Some(claim_eligible) = req_data.claim_eligible {
+            if slf.claim_eligible && !claim_eligible {
+                Self::validate_not_mapped(&name).await?;
+            }
+            slf.claim_eligible = claim_eligible;
+        }
+        if let Some(regex) = req_data.regex {
+            slf.regex = if regex.is_empty() { None } else { Some(regex) };
+        }
+        slf.validate_schema()?;
 
         let client = DB::client();
         let mut scope_updates = Vec::new();
@@ -316,8 +369,21 @@ impl UserAttrConfigEntity {
             // need another user_attr_values update here
 
             txn.push((
-                "UPDATE user_attr_config SET name  = $1, \"desc\" = $2 WHERE name = $3",
-                params!(&slf.name, &slf.desc, name),
+                r#"
+UPDATE user_attr_config
+SET name  = $1, "desc" = $2, typ = $3, required = $4, regex = $5, required_at_login = $6,
+claim_eligible = $7
+WHERE name = $8"#,
+                params!(
+                    &slf.name,
+                    &slf.desc,
+                    &slf.typ,
+                    slf.required,
+                    &slf.regex,
+                    slf.required_at_login,
+                    slf.claim_eligible,
+                    name
+                ),
             ));
 
             client.txn(txn).await?;
@@ -333,9 +399,18 @@ impl UserAttrConfigEntity {
             // need another user_attr_values update here
 
             sqlx::query!(
-                "UPDATE user_attr_config SET name  = $1, \"desc\" = $2 WHERE name = $3",
+                r#"
+UPDATE user_attr_config
+SET name  = $1, "desc" = $2, typ = $3, required = $4, regex = $5, required_at_login = $6,
+claim_eligible = $7
+WHERE name = $8"#,
                 slf.name,
                 slf.desc,
+                slf.typ,
+                slf.required,
+                slf.regex,
+                slf.required_at_login,
+                slf.claim_eligible,
                 name,
             )
             .execute(&mut *txn)
@@ -356,6 +431,56 @@ impl UserAttrConfigEntity {
 }
 
 impl UserAttrConfigEntity {
+    /// Returns all attributes, which are required at login, without a value for the given user.
+    pub async fn find_missing_at_login(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let configs = Self::find_all()
+            .await?
+            .into_iter()
+            .filter(|c| c.required_at_login)
+            .collect::<Vec<_>>();
+        if configs.is_empty() {
+            return Ok(configs);
+        }
+
+        let keys = UserAttrValueEntity::find_for_user(user_id)
+            .await?
+            .into_iter()
+            .map(|v| v.key)
+            .collect::<HashSet<_>>();
+
+        Ok(configs
+            .into_iter()
+            .filter(|c| !keys.contains(&c.name))
+            .collect())
+    }
+
+    /// Returns the names of all attributes, which may be mapped into tokens.
+    pub async fn find_claim_eligible_as_set() -> Result<HashSet<String>, ErrorResponse> {
+        let set = Self::find_all()
+            .await?
+            .into_iter()
+            .filter(|a| a.claim_eligible)
+            .map(|a| a.name)
+            .collect();
+        Ok(set)
+    }
+
+    /// An attribute must not be mapped by any scope, before it can lose its claim eligibility.
+    async fn validate_not_mapped(name: &str) -> Result<(), ErrorResponse> {
+        for scope in Scope::find_all().await? {
+            if scope.maps_attr(name) {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!(
+                        "The attribute is still mapped by the scope '{}' and must be removed first",
+                        scope.name
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
     pub async fn find_all_as_set() -> Result<HashSet<String>, ErrorResponse> {
         let attrs = Self::find_all().await?;
 
@@ -367,6 +492,51 @@ impl UserAttrConfigEntity {
         Ok(set)
     }
 
+    /// Makes sure that the schema itself is valid, before it will be saved.
+    fn validate_schema(&self) -> Result<(), ErrorResponse> {
+        if let Some(re) = &self.regex {
+            if UserAttrType::from(self.typ.as_str()) != UserAttrType::String {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "A regex can only be set for attributes of type 'string'",
+                ));
+            }
+            if let Err(err) = Regex::new(re) {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Invalid regex: {}", err),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks if the given value matches the type and regex of this attribute.
+    pub fn validate_value(&self, value: &Value) -> Result<(), ErrorResponse> {
+        let is_valid = match UserAttrType::from(self.typ.as_str()) {
+            UserAttrType::String => match value.as_str() {
+                None => false,
+                Some(s) => match &self.regex {
+                    None => true,
+                    // the regex has been validated before saving -> an invalid one cannot exist
+                    Some(re) => Regex::new(re).map(|re| re.is_match(s)).unwrap_or(false),
+                },
+            },
+            UserAttrType::Integer => value.is_i64() || value.is_u64(),
+            UserAttrType::Number => value.is_number(),
+            UserAttrType::Boolean => value.is_boolean(),
+        };
+
+        if is_valid {
+            Ok(())
+        } else {
+            Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Invalid value for custom attribute '{}'", self.name),
+            ))
+        }
+    }
+
     pub fn names_hash_set(mut slf: Vec<Self>) -> HashSet<String> {
         let mut res = HashSet::with_capacity(slf.len());
         slf.drain(..).for_each(|s| {
@@ -379,8 +549,13 @@ impl UserAttrConfigEntity {
 impl From<UserAttrConfigEntity> for UserAttrConfigValueResponse {
     fn from(value: UserAttrConfigEntity) -> Self {
         Self {
+            typ: UserAttrType::from(value.typ.as_str()),
             name: value.name,
             desc: value.desc,
+            required: value.required,
+            regex: value.regex,
+            required_at_login: value.required_at_login,
+            claim_eligible: value.claim_eligible,
         }
     }
 }
@@ -479,22 +654,31 @@ impl UserAttrValueEntity {
         // Not necessary for the operation and correctness, but look up the user first and return
         // an error, if it does not exist at all, for a better user experience.
         User::exists(user_id.to_string()).await?;
+        Self::validate_update(user_id, &req_data).await?;
+        Self::upsert_values(user_id, req_data.values).await
+    }
 
-        let delete_value = |value: &Value| {
-            if let Some(s) = value.as_str() {
-                s.is_empty() || value == &Value::Null
-            } else {
-                value == &Value::Null
-            }
-        };
+    /// Saves the values a user provided during login. They must have been checked with
+    /// `validate_at_login()` before.
+    pub async fn save_at_login(
+        user_id: &str,
+        values: Vec<UserAttrValueRequest>,
+    ) -> Result<(), ErrorResponse> {
+        Self::upsert_values(user_id, values).await?;
+        Ok(())
+    }
 
+    async fn upsert_values(
+        user_id: &str,
+        values: Vec<UserAttrValueRequest>,
+    ) -> Result<Vec<Self>, ErrorResponse> {
         let client = DB::client();
 
         let res = if is_hiqlite() {
-            let mut txn = Vec::with_capacity(req_data.values.len());
+            let mut txn = Vec::with_capacity(values.len());
 
-            for value in req_data.values {
-                if delete_value(&value.value) {
+            for value in values {
+                if Self::is_delete_value(&value.value) {
                     txn.push((
                         "DELETE FROM user_attr_values WHERE user_id = $1 AND key = $2",
                         params!(user_id, value.key),
@@ -521,8 +705,8 @@ ON CONFLICT(user_id, key) DO UPDATE SET value = $3"#,
         } else {
             let mut txn = DB::txn().await?;
 
-            for value in req_data.values {
-                if delete_value(&value.value) {
+            for value in values {
+                if Self::is_delete_value(&value.value) {
                     sqlx::query!(
                         "DELETE FROM user_attr_values WHERE user_id = $1 AND key = $2",
                         user_id,
@@ -565,6 +749,101 @@ ON CONFLICT(user_id, key) DO UPDATE SET value = $3"#,
 }
 
 impl UserAttrValueEntity {
+    /// Validates all new values against their schema and makes sure, that no required attribute
+    /// would be missing afterward.
+    async fn validate_update(
+        user_id: &str,
+        req_data: &UserAttrValuesUpdateRequest,
+    ) -> Result<(), ErrorResponse> {
+        let configs = UserAttrConfigEntity::find_all()
+            .await?
+            .into_iter()
+            .map(|c| (c.name.clone(), c))
+            .collect::<HashMap<_, _>>();
+        let mut keys = Self::find_for_user(user_id)
+            .await?
+            .into_iter()
+            .map(|v| v.key)
+            .collect::<HashSet<_>>();
+
+        for value in &req_data.values {
+            let Some(config) = configs.get(&value.key) else {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Custom attribute '{}' does not exist", value.key),
+                ));
+            };
+
+            if Self::is_delete_value(&value.value) {
+                keys.remove(&value.key);
+            } else {
+                config.validate_value(&value.value)?;
+                keys.insert(value.key.clone());
+            }
+        }
+
+        if let Some(missing) = configs
+            .values()
+            .find(|c| c.required && !keys.contains(&c.name))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("Custom attribute '{}' is required", missing.name),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Validates the values a user provided during login for the `missing` attributes. Values for
+    /// any other attribute are rejected, because they must not be modified by users themselves.
+    pub fn validate_at_login(
+        missing: &[UserAttrConfigEntity],
+        values: &[UserAttrValueRequest],
+    ) -> Result<(), ErrorResponse> {
+        for config in missing {
+            let Some(value) = values
+                .iter()
+                .find(|v| v.key == config.name && !Self::is_delete_value(&v.value))
+            else {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Custom attribute '{}' is required", config.name),
+                )
+                .with_code(ErrorCode::ValidationFailed));
+            };
+            config
+                .validate_value(&value.value)
+                .map_err(|err| err.with_code(ErrorCode::ValidationFailed))?;
+        }
+
+        if let Some(value) = values
+            .iter()
+            .find(|v| !missing.iter().any(|c| c.name == v.key))
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!(
+                    "Custom attribute '{}' cannot be set during login",
+                    value.key
+                ),
+            )
+            .with_code(ErrorCode::ValidationFailed));
+        }
+
+        Ok(())
+    }
+
+    /// `null` and empty strings will delete an existing value.
+    #[inline]
+    fn is_delete_value(value: &Value) -> bool {
+        if let Some(s) = value.as_str() {
+            s.is_empty()
+        } else {
+            value == &Value::Null
+        }
+    }
+
     #[inline]
     fn cache_idx(user_id: &str) -> String {
         format!("{}{}", IDX_USER_ATTR_CONFIG, user_id)
@@ -580,3 +859,89 @@ impl From<UserAttrValueEntity> for UserAttrValueResponse {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn attr(typ: UserAttrType, regex: Option<&str>) -> UserAttrConfigEntity {
+        UserAttrConfigEntity {
+            name: "employee_id".to_string(),
+            desc: None,
+            typ: typ.as_str().to_string(),
+            required: false,
+            regex: regex.map(String::from),
+            required_at_login: false,
+            claim_eligible: true,
+        }
+    }
+
+    #[test]
+    fn test_validate_value() {
+        let a = attr(UserAttrType::String, Some("^E[0-9]{4}$"));
+        assert!(a.validate_schema().is_ok());
+        assert!(a.validate_value(&json!("E1234")).is_ok());
+        assert!(a.validate_value(&json!("E123")).is_err());
+        assert!(a.validate_value(&json!(1234)).is_err());
+
+        let a = attr(UserAttrType::Integer, None);
+        assert!(a.validate_value(&json!(-13)).is_ok());
+        assert!(a.validate_value(&json!(1.5)).is_err());
+        assert!(a.validate_value(&json!("13")).is_err());
+
+        let a = attr(UserAttrType::Number, None);
+        assert!(a.validate_value(&json!(1.5)).is_ok());
+        assert!(a.validate_value(&json!(true)).is_err());
+
+        let a = attr(UserAttrType::Boolean, None);
+        assert!(a.validate_value(&json!(false)).is_ok());
+        assert!(a.validate_value(&json!("false")).is_err());
+
+        // a regex is only allowed for strings
+        assert!(attr(UserAttrType::Integer, Some("^[0-9]+$"))
+            .validate_schema()
+            .is_err());
+        assert!(attr(UserAttrType::String, Some("(unclosed"))
+            .validate_schema()
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_at_login() {
+        let value = |key: &str, value: Value| UserAttrValueRequest {
+            key: key.to_string(),
+            value,
+        };
+        let missing = vec![attr(UserAttrType::String, Some("^E[0-9]{4}$"))];
+
+        assert!(UserAttrValueEntity::validate_at_login(
+            &missing,
+            &[value("employee_id", json!("E1234"))]
+        )
+        .is_ok());
+
+        // missing, empty or invalid
+        assert!(UserAttrValueEntity::validate_at_login(&missing, &[]).is_err());
+        assert!(UserAttrValueEntity::validate_at_login(
+            &missing,
+            &[value("employee_id", json!(""))]
+        )
+        .is_err());
+        assert!(UserAttrValueEntity::validate_at_login(
+            &missing,
+            &[value("employee_id", json!("E12"))]
+        )
+        .is_err());
+
+        // other attributes must not be modified during login
+        assert!(UserAttrValueEntity::validate_at_login(
+            &missing,
+            &[
+                value("employee_id", json!("E1234")),
+                value("department", json!("IT"))
+            ]
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use crate::database::DB;
+use hiqlite::{params, Param};
+use rauthy_common::clock;
+use rauthy_common::is_hiqlite;
+use rauthy_error::ErrorResponse;
+use sqlx::query;
+
+/// Keeps track of already sent `UserExpired` events. The `user_expires` is part of the primary
+/// key, which means that a new expiry for the same user will create a new event again.
+pub struct UserExpiryNotification;
+
+impl UserExpiryNotification {
+    /// Marks the expiry as notified. Returns `true` only for the first call for this expiry,
+    /// which makes sure that each expiry creates exactly one event, no matter how often the
+    /// scheduler runs or restarts.
+    pub async fn insert(user_id: String, user_expires: i64) -> Result<bool, ErrorResponse> {
+        let sent = clock::now().timestamp();
+
+        let rows_affected = if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_expiry_notifications (user_id, user_expires, sent)
+VALUES ($1, $2, $3)
+ON CONFLICT DO NOTHING"#,
+                    params!(user_id, user_expires, sent),
+                )
+                .await?
+        } else {
+            query!(
+                r#"
+INSERT INTO user_expiry_notifications (user_id, user_expires, sent)
+VALUES ($1, $2, $3)
+ON CONFLICT DO NOTHING"#,
+                user_id,
+                user_expires,
+                sent,
+            )
+            .execute(DB::conn())
+            .await?
+            .rows_affected() as usize
+        };
+
+        Ok(rows_affected > 0)
+    }
+}
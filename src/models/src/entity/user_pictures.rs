@@ -0,0 +1,216 @@
+use crate::database::{Cache, DB};
+use actix_web::web;
+use hiqlite::{params, Param};
+use image::imageops::FilterType;
+use image::ImageFormat;
+use rauthy_common::clock;
+use rauthy_common::constants::{CACHE_TTL_USER, CONTENT_TYPE_WEBP, IDX_USER_PICTURE};
+use rauthy_common::is_hiqlite;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+use std::io::Cursor;
+use tracing::debug;
+
+// The size a user picture will be resized to
+const RES_USER_PICTURE: u32 = 256;
+// The minimal size of an uploaded picture
+const RES_USER_PICTURE_MIN: u32 = 32;
+
+/// A users avatar, which will be served for the `picture` claim
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct UserPicture {
+    pub user_id: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+    pub updated: i64,
+}
+
+impl UserPicture {
+    pub async fn delete(user_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM user_pictures WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?;
+        } else {
+            query!("DELETE FROM user_pictures WHERE user_id = $1", user_id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        DB::client()
+            .delete(Cache::User, Self::cache_idx(user_id))
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn find(user_id: &str) -> Result<Self, ErrorResponse> {
+        let slf = if is_hiqlite() {
+            DB::client()
+                .query_as_one(
+                    "SELECT * FROM user_pictures WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM user_pictures WHERE user_id = $1",
+                user_id
+            )
+            .fetch_one(DB::conn())
+            .await?
+        };
+
+        Ok(slf)
+    }
+
+    /// Returns the timestamp of the last update, if the user has a picture.
+    /// This is called during each token creation, which is why the result is cached.
+    pub async fn find_updated(user_id: &str) -> Result<Option<i64>, ErrorResponse> {
+        let client = DB::client();
+        let idx = Self::cache_idx(user_id);
+        if let Some(updated) = client.get(Cache::User, &idx).await? {
+            return Ok(updated);
+        }
+
+        let updated = if is_hiqlite() {
+            client
+                .query_raw(
+                    "SELECT updated FROM user_pictures WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?
+                .into_iter()
+                .next()
+                .map(|mut row| row.get::<i64>("updated"))
+        } else {
+            query!(
+                "SELECT updated FROM user_pictures WHERE user_id = $1",
+                user_id
+            )
+            .fetch_optional(DB::conn())
+            .await?
+            .map(|row| row.updated)
+        };
+
+        client
+            .put(Cache::User, idx, &updated, CACHE_TTL_USER)
+            .await?;
+
+        Ok(updated)
+    }
+
+    /// Downscales the given JPG / PNG / WebP and saves it as WebP. A possibly existing picture
+    /// will be overwritten.
+    pub async fn upsert(
+        user_id: String,
+        picture: Vec<u8>,
+        content_type: mime::Mime,
+    ) -> Result<(), ErrorResponse> {
+        // SVGs are not accepted on purpose. They could only be sanitized and would be served
+        // from the Rauthy origin for any user.
+        match content_type.as_ref() {
+            "image/jpeg" | "image/png" | "image/webp" => {}
+            _ => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "Invalid mime type for a user picture",
+                ))
+            }
+        }
+
+        let img = image::load_from_memory(&picture)?;
+        debug!(
+            "current user picture width: {}, height: {}",
+            img.width(),
+            img.height()
+        );
+        if img.height() < RES_USER_PICTURE_MIN || img.width() < RES_USER_PICTURE_MIN {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("size must be at least {} px", RES_USER_PICTURE_MIN),
+            ));
+        }
+
+        // image resizing can be expensive -> do not block main thread
+        let data = web::block(move || {
+            let img = if img.height() > RES_USER_PICTURE || img.width() > RES_USER_PICTURE {
+                img.resize_to_fill(RES_USER_PICTURE, RES_USER_PICTURE, FilterType::Lanczos3)
+            } else {
+                img
+            };
+
+            let mut buf = Cursor::new(Vec::with_capacity(32 * 1024));
+            img.write_to(&mut buf, ImageFormat::WebP)?;
+            Ok::<Vec<u8>, ErrorResponse>(buf.into_inner())
+        })
+        .await??;
+
+        let slf = Self {
+            user_id,
+            content_type: CONTENT_TYPE_WEBP.to_string(),
+            data,
+            updated: clock::now().timestamp(),
+        };
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_pictures (user_id, content_type, data, updated)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(user_id) DO UPDATE SET content_type = $2, data = $3, updated = $4"#,
+                    params!(
+                        slf.user_id.clone(),
+                        slf.content_type.clone(),
+                        slf.data.clone(),
+                        slf.updated
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO user_pictures (user_id, content_type, data, updated)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(user_id) DO UPDATE
+SET content_type = $2, data = $3, updated = $4"#,
+                slf.user_id,
+                slf.content_type,
+                slf.data,
+                slf.updated,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        DB::client()
+            .put(
+                Cache::User,
+                Self::cache_idx(&slf.user_id),
+                &Some(slf.updated),
+                CACHE_TTL_USER,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// The URL for the `picture` claim. The `v` param changes with each upload, which makes it
+    /// possible to cache the picture for a long time.
+    pub fn claim_url(issuer: &str, user_id: &str, updated: i64) -> String {
+        format!("{}/users/{}/picture?v={}", issuer, user_id, updated)
+    }
+}
+
+impl UserPicture {
+    #[inline]
+    fn cache_idx(user_id: &str) -> String {
+        format!("{}{}", IDX_USER_PICTURE, user_id)
+    }
+}
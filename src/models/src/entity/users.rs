@@ -1,41 +1,57 @@
 use crate::app_state::{AppState, DbTxn};
 use crate::database::{Cache, DB};
-use crate::email::{send_email_change_info_new, send_email_confirm_change, send_pwd_reset};
+use crate::email::{
+    send_email_change_info_new, send_email_confirm_change, send_email_verify, send_mfa_reset_info,
+    send_pwd_reset,
+};
+use crate::entity::clients_access::ClientAccessPolicy;
 use crate::entity::colors::ColorEntity;
 use crate::entity::continuation_token::ContinuationToken;
-use crate::entity::groups::Group;
+use crate::entity::email_otp::UserEmailOtp;
+use crate::entity::groups::{Group, GroupInheritance};
+use crate::entity::jti_denylist::JtiDenylist;
 use crate::entity::magic_links::{MagicLink, MagicLinkUsage};
+use crate::entity::opaque_tokens::OpaqueToken;
 use crate::entity::password::PasswordPolicy;
 use crate::entity::password::RecentPasswordsEntity;
+use crate::entity::recovery_codes::MfaRecoveryCode;
 use crate::entity::refresh_tokens::RefreshToken;
+use crate::entity::role_mapping::RoleMappingRule;
 use crate::entity::roles::Role;
 use crate::entity::sessions::Session;
+use crate::entity::sms_otp::UserSmsOtp;
+use crate::entity::totp::UserTotp;
+use crate::entity::trusted_devices::TrustedDevice;
 use crate::entity::users_values::UserValues;
 use crate::entity::webauthn::{PasskeyEntity, WebauthnServiceReq};
+use crate::entity::yubico_otp::UserYubicoOtp;
 use crate::events::event::Event;
 use crate::language::Language;
 use crate::templates::UserEmailChangeConfirmHtml;
 use actix_web::{web, HttpRequest};
 use argon2::PasswordHash;
-use chrono::Utc;
 use hiqlite::{params, Param, Params};
 use rauthy_api_types::generic::SearchParamsIdx;
 use rauthy_api_types::users::{
-    NewUserRegistrationRequest, NewUserRequest, UpdateUserRequest, UpdateUserSelfRequest,
-    UserAccountTypeResponse, UserResponse, UserResponseSimple, UserValuesResponse,
+    MfaPurpose, NewUserRegistrationRequest, NewUserRequest, SortOrder, UpdateUserRequest,
+    UpdateUserSelfRequest, UserAccountTypeResponse, UserResponse, UserResponseSimple, UserSortKey,
+    UserValuesResponse, UsersSearchParams,
 };
+use rauthy_common::clock;
 use rauthy_common::constants::{
-    CACHE_TTL_APP, CACHE_TTL_USER, IDX_USERS, IDX_USER_COUNT, RAUTHY_ADMIN_ROLE,
-    WEBAUTHN_NO_PASSWORD_EXPIRY,
+    EmailOtpMode, ACCOUNT_LOCKOUT_DURATION, ACCOUNT_LOCKOUT_DURATION_MAX,
+    ACCOUNT_LOCKOUT_THRESHOLD, CACHE_TTL_APP, CACHE_TTL_USER, IDX_USERS, IDX_USER_COUNT,
+    MFA_EMAIL_OTP, RAUTHY_ADMIN_ROLE, WEBAUTHN_NO_PASSWORD_EXPIRY,
 };
 use rauthy_common::is_hiqlite;
 use rauthy_common::password_hasher::{ComparePasswords, HashPassword};
-use rauthy_common::utils::{new_store_id, real_ip_from_req};
-use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_common::utils::{
+    base64_url_no_pad_decode, base64_url_no_pad_encode, new_user_id, real_ip_from_req,
+};
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 use sqlx::{query_as, FromRow};
 use std::ops::Add;
-use time::OffsetDateTime;
 use tracing::{debug, error, trace, warn};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -85,6 +101,18 @@ pub struct User {
     pub user_expires: Option<i64>,
     pub auth_provider_id: Option<String>,
     pub federation_uid: Option<String>,
+    /// Immutable identifier from an external system, which can only be set during creation
+    pub external_id: Option<String>,
+    /// Tombstone for soft-deleted users, which can be restored until they are purged
+    pub deleted_at: Option<i64>,
+    /// Forces a password change after the next successful login before any code is issued
+    pub must_change_password: bool,
+    /// Timestamp of the last successful verification of the E-Mail address. `None` for addresses
+    /// which have been verified before this value was tracked.
+    pub email_verified_at: Option<i64>,
+    /// Set after an admin has reset all 2nd factors. The user must enroll a new one before any
+    /// login other than the account dashboard is possible.
+    pub mfa_reenroll: bool,
 }
 
 // CRUD
@@ -110,12 +138,15 @@ impl User {
 
         let count = if is_hiqlite() {
             client
-                .query_raw("SELECT COUNT (*) AS count FROM users", params!())
+                .query_raw(
+                    "SELECT COUNT (*) AS count FROM users WHERE deleted_at IS NULL",
+                    params!(),
+                )
                 .await?
                 .remove(0)
                 .get("count")
         } else {
-            sqlx::query!("SELECT COUNT (*) count FROM users")
+            sqlx::query!("SELECT COUNT (*) count FROM users WHERE deleted_at IS NULL")
                 .fetch_one(DB::conn())
                 .await?
                 .count
@@ -199,18 +230,34 @@ impl User {
         Ok(new_user)
     }
 
+    /// Soft-deletes the user. It will be excluded from all lookups and logins, but can be
+    /// restored until it is purged after `USER_DELETE_GRACE_DAYS`.
+    ///
+    /// The E-Mail is replaced with a tombstone, so it can be used for a new user right away.
     pub async fn delete(&self) -> Result<(), ErrorResponse> {
         Session::delete_by_user(&self.id).await?;
+        RefreshToken::invalidate_for_user(&self.id).await?;
+        OpaqueToken::invalidate_for_user(&self.id).await?;
+        JtiDenylist::deny_for_user(&self.id).await?;
 
-        let client = DB::client();
+        let now = clock::now().timestamp();
+        let email = Self::email_tombstone(&self.id, &self.email);
         if is_hiqlite() {
-            client
-                .execute("DELETE FROM users WHERE id = $1", params!(&self.id))
+            DB::client()
+                .execute(
+                    "UPDATE users SET deleted_at = $1, email = $2 WHERE id = $3",
+                    params!(now, email, &self.id),
+                )
                 .await?;
         } else {
-            sqlx::query!("DELETE FROM users WHERE id = $1", self.id)
-                .execute(DB::conn())
-                .await?;
+            sqlx::query!(
+                "UPDATE users SET deleted_at = $1, email = $2 WHERE id = $3",
+                now,
+                email,
+                self.id
+            )
+            .execute(DB::conn())
+            .await?;
         }
 
         Self::invalidate_cache(&self.id, &self.email).await?;
@@ -219,6 +266,80 @@ impl User {
         Ok(())
     }
 
+    /// Permanently deletes all users, which have been soft-deleted before the given timestamp.
+    pub async fn purge_deleted(deleted_before: i64) -> Result<u64, ErrorResponse> {
+        let rows = if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM users WHERE deleted_at < $1",
+                    params!(deleted_before),
+                )
+                .await? as u64
+        } else {
+            sqlx::query!("DELETE FROM users WHERE deleted_at < $1", deleted_before)
+                .execute(DB::conn())
+                .await?
+                .rows_affected()
+        };
+
+        if rows > 0 {
+            // possible client access policy entries are removed via `ON DELETE CASCADE`
+            ClientAccessPolicy::invalidate_cache().await?;
+        }
+
+        Ok(rows)
+    }
+
+    /// Restores a soft-deleted user. Fails, if its E-Mail has been taken by another user in
+    /// the meantime.
+    pub async fn restore(id: String) -> Result<Self, ErrorResponse> {
+        let mut slf: Self = if is_hiqlite() {
+            DB::client()
+                .query_as_one(
+                    "SELECT * FROM users WHERE id = $1 AND deleted_at IS NOT NULL",
+                    params!(&id),
+                )
+                .await?
+        } else {
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM users WHERE id = $1 AND deleted_at IS NOT NULL",
+                id
+            )
+            .fetch_one(DB::conn())
+            .await?
+        };
+
+        let tombstone = Self::email_tombstone(&slf.id, "");
+        if let Some(email) = slf.email.strip_prefix(&tombstone) {
+            slf.email = email.to_string();
+        }
+        Self::is_email_free(slf.email.clone()).await?;
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "UPDATE users SET deleted_at = NULL, email = $1 WHERE id = $2",
+                    params!(&slf.email, &slf.id),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                "UPDATE users SET deleted_at = NULL, email = $1 WHERE id = $2",
+                slf.email,
+                slf.id
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+        slf.deleted_at = None;
+
+        Self::invalidate_cache(&slf.id, &slf.email).await?;
+        Self::count_inc().await?;
+
+        Ok(slf)
+    }
+
     pub async fn exists(id: String) -> Result<(), ErrorResponse> {
         let idx = format!("{}_{}", IDX_USERS, id);
 
@@ -229,7 +350,10 @@ impl User {
 
         if is_hiqlite() {
             let rows = DB::client()
-                .query_raw("SELECT 1 FROM users WHERE id = $1", params!(id))
+                .query_raw(
+                    "SELECT 1 FROM users WHERE id = $1 AND deleted_at IS NULL",
+                    params!(id),
+                )
                 .await?;
             if rows.is_empty() {
                 return Err(ErrorResponse::new(
@@ -238,9 +362,12 @@ impl User {
                 ));
             }
         } else {
-            sqlx::query!("SELECT id FROM users WHERE id = $1", id)
-                .fetch_one(DB::conn())
-                .await?;
+            sqlx::query!(
+                "SELECT id FROM users WHERE id = $1 AND deleted_at IS NULL",
+                id
+            )
+            .fetch_one(DB::conn())
+            .await?;
         }
 
         Ok(())
@@ -256,12 +383,19 @@ impl User {
 
         let slf = if is_hiqlite() {
             client
-                .query_as_one("SELECT * FROM users WHERE id = $1", params!(id))
+                .query_as_one(
+                    "SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL",
+                    params!(id),
+                )
                 .await?
         } else {
-            sqlx::query_as!(Self, "SELECT * FROM users WHERE id = $1", id)
-                .fetch_one(DB::conn())
-                .await?
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL",
+                id
+            )
+            .fetch_one(DB::conn())
+            .await?
         };
 
         client.put(Cache::User, idx, &slf, CACHE_TTL_USER).await?;
@@ -280,12 +414,19 @@ impl User {
 
         let slf = if is_hiqlite() {
             client
-                .query_as_one("SELECT * FROM users WHERE email = $1", params!(email))
+                .query_as_one(
+                    "SELECT * FROM users WHERE email = $1 AND deleted_at IS NULL",
+                    params!(email),
+                )
                 .await?
         } else {
-            sqlx::query_as!(Self, "SELECT * FROM users WHERE email = $1", email)
-                .fetch_one(DB::conn())
-                .await?
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM users WHERE email = $1 AND deleted_at IS NULL",
+                email
+            )
+            .fetch_one(DB::conn())
+            .await?
         };
 
         client.put(Cache::User, idx, &slf, CACHE_TTL_USER).await?;
@@ -299,14 +440,14 @@ impl User {
         let slf = if is_hiqlite() {
             DB::client()
                 .query_as_one(
-                    "SELECT * FROM users WHERE auth_provider_id = $1 AND federation_uid = $2",
+                    "SELECT * FROM users WHERE auth_provider_id = $1 AND federation_uid = $2 AND deleted_at IS NULL",
                     params!(auth_provider_id, federation_uid),
                 )
                 .await?
         } else {
             sqlx::query_as!(
                 Self,
-                "SELECT * FROM users WHERE auth_provider_id = $1 AND federation_uid = $2",
+                "SELECT * FROM users WHERE auth_provider_id = $1 AND federation_uid = $2 AND deleted_at IS NULL",
                 auth_provider_id,
                 federation_uid
             )
@@ -317,15 +458,42 @@ impl User {
         Ok(slf)
     }
 
+    pub async fn find_by_external_id(external_id: &str) -> Result<Self, ErrorResponse> {
+        let slf = if is_hiqlite() {
+            DB::client()
+                .query_as_one(
+                    "SELECT * FROM users WHERE external_id = $1 AND deleted_at IS NULL",
+                    params!(external_id),
+                )
+                .await?
+        } else {
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM users WHERE external_id = $1 AND deleted_at IS NULL",
+                external_id
+            )
+            .fetch_one(DB::conn())
+            .await?
+        };
+
+        Ok(slf)
+    }
+
     pub async fn find_all() -> Result<Vec<Self>, ErrorResponse> {
         let res = if is_hiqlite() {
             DB::client()
-                .query_as("SELECT * FROM users ORDER BY created_at ASC", params!())
+                .query_as(
+                    "SELECT * FROM users WHERE deleted_at IS NULL ORDER BY created_at ASC",
+                    params!(),
+                )
                 .await?
         } else {
-            sqlx::query_as!(Self, "SELECT * FROM users ORDER BY created_at ASC")
-                .fetch_all(DB::conn())
-                .await?
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM users WHERE deleted_at IS NULL ORDER BY created_at ASC"
+            )
+            .fetch_all(DB::conn())
+            .await?
         };
 
         Ok(res)
@@ -335,14 +503,14 @@ impl User {
         let res = if is_hiqlite() {
             DB::client()
                 .query_as(
-                    "SELECT id, email, created_at, last_login FROM users ORDER BY created_at ASC",
+                    "SELECT id, email, created_at, last_login FROM users WHERE deleted_at IS NULL ORDER BY created_at ASC",
                     params!(),
                 )
                 .await?
         } else {
             sqlx::query_as!(
                 UserResponseSimple,
-                "SELECT id, email, created_at, last_login FROM users ORDER BY created_at ASC"
+                "SELECT id, email, created_at, last_login FROM users WHERE deleted_at IS NULL ORDER BY created_at ASC"
             )
             .fetch_all(DB::conn())
             .await?
@@ -357,12 +525,19 @@ impl User {
 
         let res = if is_hiqlite() {
             DB::client()
-                .query_as("SELECT * FROM users WHERE groups LIKE $1", params!(like))
+                .query_as(
+                    "SELECT * FROM users WHERE groups LIKE $1 AND deleted_at IS NULL",
+                    params!(like),
+                )
                 .await?
         } else {
-            sqlx::query_as!(Self, "SELECT * FROM users WHERE groups LIKE $1", like)
-                .fetch_all(DB::conn())
-                .await?
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM users WHERE groups LIKE $1 AND deleted_at IS NULL",
+                like
+            )
+            .fetch_all(DB::conn())
+            .await?
         };
 
         Ok(res)
@@ -374,28 +549,42 @@ impl User {
 
         let res = if is_hiqlite() {
             DB::client()
-                .query_as("SELECT * FROM users WHERE roles LIKE $1", params!(like))
+                .query_as(
+                    "SELECT * FROM users WHERE roles LIKE $1 AND deleted_at IS NULL",
+                    params!(like),
+                )
                 .await?
         } else {
-            sqlx::query_as!(Self, "SELECT * FROM users WHERE roles LIKE $1", like)
-                .fetch_all(DB::conn())
-                .await?
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM users WHERE roles LIKE $1 AND deleted_at IS NULL",
+                like
+            )
+            .fetch_all(DB::conn())
+            .await?
         };
 
         Ok(res)
     }
 
     pub async fn find_expired() -> Result<Vec<Self>, ErrorResponse> {
-        let now = Utc::now().add(chrono::Duration::seconds(10)).timestamp();
+        let now = clock::now().add(chrono::Duration::seconds(10)).timestamp();
 
         let res = if is_hiqlite() {
             DB::client()
-                .query_as("SELECT * FROM users WHERE user_expires < $1", params!(now))
+                .query_as(
+                    "SELECT * FROM users WHERE user_expires < $1 AND deleted_at IS NULL",
+                    params!(now),
+                )
                 .await?
         } else {
-            sqlx::query_as!(Self, "SELECT * FROM users WHERE user_expires < $1", now)
-                .fetch_all(DB::conn())
-                .await?
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM users WHERE user_expires < $1 AND deleted_at IS NULL",
+                now
+            )
+            .fetch_all(DB::conn())
+            .await?
         };
 
         Ok(res)
@@ -441,7 +630,7 @@ impl User {
                             r#"
 SELECT id, email, created_at, last_login
 FROM users
-WHERE created_at <= $1 AND id != $2
+WHERE created_at <= $1 AND id != $2 AND deleted_at IS NULL
 ORDER BY created_at DESC
 LIMIT $3
 OFFSET $4"#,
@@ -457,7 +646,7 @@ OFFSET $4"#,
                         r#"
 SELECT id, email, created_at, last_login
 FROM users
-WHERE created_at <= $1 AND id != $2
+WHERE created_at <= $1 AND id != $2 AND deleted_at IS NULL
 ORDER BY created_at DESC
 LIMIT $3
 OFFSET $4"#,
@@ -480,7 +669,7 @@ OFFSET $4"#,
                             r#"
 SELECT id, email, created_at, last_login
 FROM users
-WHERE created_at <= $1 AND id != $2
+WHERE created_at <= $1 AND id != $2 AND deleted_at IS NULL
 ORDER BY created_at DESC
 LIMIT $3
 OFFSET $4"#,
@@ -493,7 +682,7 @@ OFFSET $4"#,
                         r#"
 SELECT id, email, created_at, last_login
 FROM users
-WHERE created_at >= $1 AND id != $2
+WHERE created_at >= $1 AND id != $2 AND deleted_at IS NULL
 ORDER BY created_at ASC
 LIMIT $3
 OFFSET $4"#,
@@ -516,6 +705,7 @@ OFFSET $4"#,
                         r#"
 SELECT id, email, created_at, last_login
 FROM users
+WHERE deleted_at IS NULL
 ORDER BY created_at DESC
 LIMIT $1
 OFFSET $2"#,
@@ -531,6 +721,7 @@ OFFSET $2"#,
                     r#"
 SELECT id, email, created_at, last_login
 FROM users
+WHERE deleted_at IS NULL
 ORDER BY created_at DESC
 LIMIT $1
 OFFSET $2"#,
@@ -551,6 +742,7 @@ OFFSET $2"#,
                         r#"
 SELECT id, email, created_at, last_login
 FROM users
+WHERE deleted_at IS NULL
 ORDER BY created_at ASC
 LIMIT $1
 OFFSET $2"#,
@@ -563,6 +755,7 @@ OFFSET $2"#,
                     r#"
 SELECT id, email, created_at, last_login
 FROM users
+WHERE deleted_at IS NULL
 ORDER BY created_at ASC
 LIMIT $1
 OFFSET $2"#,
@@ -581,7 +774,119 @@ OFFSET $2"#,
         Ok((res, token))
     }
 
-    pub async fn insert(new_user: User) -> Result<Self, ErrorResponse> {
+    /// Searches users with optional filters, sorting and cursor pagination. Everything is pushed
+    /// down into the database, so this scales with any amount of users.
+    ///
+    /// Returns the cursor for the next page as well, if there are more results.
+    pub async fn search_filtered(
+        search: &UsersSearchParams,
+    ) -> Result<(Vec<UserResponseSimple>, Option<String>), ErrorResponse> {
+        let sort = search.sort.clone().unwrap_or_default();
+        let limit = search.limit.unwrap_or(50) as usize;
+        let cursor = search
+            .cursor
+            .as_deref()
+            .map(|c| UserSearchCursor::try_from_str(c, &sort))
+            .transpose()?;
+
+        // Only static, whitelisted values are ever formatted into the query. Everything coming
+        // from the request is bound as a param.
+        let col = match sort {
+            UserSortKey::CreatedAt => "created_at",
+            UserSortKey::Email => "email",
+            UserSortKey::GivenName => "given_name",
+            UserSortKey::FamilyName => "COALESCE(family_name, '')",
+            UserSortKey::LastLogin => "COALESCE(last_login, 0)",
+        };
+        let (cmp, order) = match search.order.clone().unwrap_or_default() {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+
+        let mut sql = if is_hiqlite() {
+            r#"
+SELECT * FROM users
+WHERE deleted_at IS NULL
+    AND ($1 IS NULL OR email LIKE $1 OR given_name LIKE $1 OR family_name LIKE $1)
+    AND ($2 IS NULL OR enabled = $2)
+    AND ($3 IS NULL OR instr(',' || roles || ',', ',' || $3 || ',') > 0)
+    AND ($4 IS NULL OR instr(',' || groups || ',', ',' || $4 || ',') > 0)
+    AND ($5 IS NULL OR (auth_provider_id IS NOT NULL) = $5)"#
+                .to_string()
+        } else {
+            r#"
+SELECT * FROM users
+WHERE deleted_at IS NULL
+    AND ($1::varchar IS NULL OR email ILIKE $1 OR given_name ILIKE $1 OR family_name ILIKE $1)
+    AND ($2::bool IS NULL OR enabled = $2)
+    AND ($3::varchar IS NULL OR $3 = ANY(string_to_array(roles, ',')))
+    AND ($4::varchar IS NULL OR $4 = ANY(string_to_array(groups, ',')))
+    AND ($5::bool IS NULL OR (auth_provider_id IS NOT NULL) = $5)"#
+                .to_string()
+        };
+        if cursor.is_some() {
+            sql.push_str(&format!(
+                "\n    AND ({col} {cmp} $6 OR ({col} = $6 AND id {cmp} $7))"
+            ));
+        }
+        // we fetch one more to know if there is a next page
+        sql.push_str(&format!(
+            "\nORDER BY {col} {order}, id {order}\nLIMIT {}",
+            limit + 1
+        ));
+
+        let q = search.q.as_ref().map(|q| format!("%{}%", q));
+        let mut users: Vec<Self> = if is_hiqlite() {
+            let mut params = params!(
+                q,
+                search.enabled,
+                search.role.clone(),
+                search.group.clone(),
+                search.federated
+            );
+            if let Some(cursor) = cursor {
+                match cursor.value {
+                    UserSearchCursorValue::Int(v) => params.push(Param::from(v)),
+                    UserSearchCursorValue::Text(v) => params.push(Param::from(v)),
+                }
+                params.push(Param::from(cursor.id));
+            }
+            DB::client().query_as(sql, params).await?
+        } else {
+            let mut query = sqlx::query_as::<_, Self>(&sql)
+                .bind(q)
+                .bind(search.enabled)
+                .bind(search.role.as_deref())
+                .bind(search.group.as_deref())
+                .bind(search.federated);
+            if let Some(cursor) = cursor {
+                query = match cursor.value {
+                    UserSearchCursorValue::Int(v) => query.bind(v),
+                    UserSearchCursorValue::Text(v) => query.bind(v),
+                };
+                query = query.bind(cursor.id);
+            }
+            query.fetch_all(DB::conn()).await?
+        };
+
+        let next_cursor = if users.len() > limit {
+            users.truncate(limit);
+            users
+                .last()
+                .map(|user| UserSearchCursor::from_user(user, &sort).encode())
+        } else {
+            None
+        };
+
+        let res = users.into_iter().map(UserResponseSimple::from).collect();
+        Ok((res, next_cursor))
+    }
+
+    pub async fn insert(mut new_user: User) -> Result<Self, ErrorResponse> {
+        RoleMappingRule::apply(&mut new_user, None).await?;
+        if new_user.email_verified && new_user.email_verified_at.is_none() {
+            new_user.email_verified_at = Some(new_user.created_at);
+        }
         let lang = new_user.language.as_str();
 
         if is_hiqlite() {
@@ -590,8 +895,9 @@ OFFSET $2"#,
                     r#"
 INSERT INTO USERS
 (id, email, given_name, family_name, roles, groups, enabled, email_verified, created_at,
-last_login, language, user_expires, auth_provider_id, federation_uid)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"#,
+last_login, language, user_expires, auth_provider_id, federation_uid, external_id,
+email_verified_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)"#,
                     params!(
                         &new_user.id,
                         &new_user.email,
@@ -606,7 +912,9 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"#,
                         lang,
                         new_user.user_expires,
                         &new_user.auth_provider_id,
-                        &new_user.federation_uid
+                        &new_user.federation_uid,
+                        &new_user.external_id,
+                        new_user.email_verified_at
                     ),
                 )
                 .await?;
@@ -615,8 +923,9 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"#,
                 r#"
 INSERT INTO USERS
 (id, email, given_name, family_name, roles, groups, enabled, email_verified, created_at,
-last_login, language, user_expires, auth_provider_id, federation_uid)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"#,
+last_login, language, user_expires, auth_provider_id, federation_uid, external_id,
+email_verified_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)"#,
                 new_user.id,
                 new_user.email,
                 new_user.given_name,
@@ -631,6 +940,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)"#,
                 new_user.user_expires,
                 new_user.auth_provider_id,
                 new_user.federation_uid,
+                new_user.external_id,
+                new_user.email_verified_at,
             )
             .execute(DB::conn())
             .await?;
@@ -675,8 +986,9 @@ UPDATE USERS SET
 email = $1, given_name = $2, family_name = $3, password = $4, roles = $5, groups = $6, enabled = $7,
 email_verified = $8, password_expires = $9, last_login = $10, last_failed_login = $11,
 failed_login_attempts = $12, language = $13, webauthn_user_id = $14, user_expires = $15,
-auth_provider_id = $16, federation_uid = $17
-WHERE id = $18"#,
+auth_provider_id = $16, federation_uid = $17, must_change_password = $18,
+email_verified_at = $19, mfa_reenroll = $20
+WHERE id = $21"#,
             params!(
                 self.email,
                 self.given_name,
@@ -695,6 +1007,9 @@ WHERE id = $18"#,
                 self.user_expires,
                 self.auth_provider_id,
                 self.federation_uid,
+                self.must_change_password,
+                self.email_verified_at,
+                self.mfa_reenroll,
                 self.id
             ),
         ));
@@ -715,8 +1030,9 @@ UPDATE USERS SET
 email = $1, given_name = $2, family_name = $3, password = $4, roles = $5, groups = $6, enabled = $7,
 email_verified = $8, password_expires = $9, last_login = $10, last_failed_login = $11,
 failed_login_attempts = $12, language = $13, webauthn_user_id = $14, user_expires = $15,
-auth_provider_id = $16, federation_uid = $17
-WHERE id = $18"#,
+auth_provider_id = $16, federation_uid = $17, must_change_password = $18,
+email_verified_at = $19, mfa_reenroll = $20
+WHERE id = $21"#,
         )
         .bind(&self.email)
         .bind(&self.given_name)
@@ -735,6 +1051,9 @@ WHERE id = $18"#,
         .bind(self.user_expires)
         .bind(&self.auth_provider_id)
         .bind(&self.federation_uid)
+        .bind(self.must_change_password)
+        .bind(self.email_verified_at)
+        .bind(self.mfa_reenroll)
         .bind(&self.id)
         .execute(&mut **txn)
         .await?;
@@ -758,8 +1077,9 @@ UPDATE USERS SET
 email = $1, given_name = $2, family_name = $3, password = $4, roles = $5, groups = $6, enabled = $7,
 email_verified = $8, password_expires = $9, last_login = $10, last_failed_login = $11,
 failed_login_attempts = $12, language = $13, webauthn_user_id = $14, user_expires = $15,
-auth_provider_id = $16, federation_uid = $17
-WHERE id = $18"#,
+auth_provider_id = $16, federation_uid = $17, must_change_password = $18,
+email_verified_at = $19, mfa_reenroll = $20
+WHERE id = $21"#,
                     params!(
                         &self.email,
                         &self.given_name,
@@ -778,6 +1098,9 @@ WHERE id = $18"#,
                         self.user_expires,
                         &self.auth_provider_id,
                         &self.federation_uid,
+                        self.must_change_password,
+                        self.email_verified_at,
+                        self.mfa_reenroll,
                         &self.id
                     ),
                 )
@@ -789,8 +1112,9 @@ UPDATE USERS SET
 email = $1, given_name = $2, family_name = $3, password = $4, roles = $5, groups = $6, enabled = $7,
 email_verified = $8, password_expires = $9, last_login = $10, last_failed_login = $11,
 failed_login_attempts = $12, language = $13, webauthn_user_id = $14, user_expires = $15,
-auth_provider_id = $16, federation_uid = $17
-WHERE id = $18"#,
+auth_provider_id = $16, federation_uid = $17, must_change_password = $18,
+email_verified_at = $19, mfa_reenroll = $20
+WHERE id = $21"#,
             )
             .bind(&self.email)
             .bind(&self.given_name)
@@ -809,6 +1133,9 @@ WHERE id = $18"#,
             .bind(self.user_expires)
             .bind(&self.auth_provider_id)
             .bind(&self.federation_uid)
+            .bind(self.must_change_password)
+            .bind(self.email_verified_at)
+            .bind(self.mfa_reenroll)
             .bind(&self.id)
             .execute(DB::conn())
             .await?;
@@ -817,6 +1144,8 @@ WHERE id = $18"#,
         if !self.enabled {
             Session::invalidate_for_user(&self.id).await?;
             RefreshToken::invalidate_for_user(&self.id).await?;
+            OpaqueToken::invalidate_for_user(&self.id).await?;
+            JtiDenylist::deny_for_user(&self.id).await?;
         }
 
         if let Some(email) = old_email {
@@ -849,7 +1178,7 @@ WHERE id = $18"#,
                             r#"
 SELECT id, email, created_at, last_login
 FROM users
-WHERE id LIKE $1
+WHERE id LIKE $1 AND deleted_at IS NULL
 ORDER BY created_at ASC
 LIMIT $2"#,
                             params!(q, limit),
@@ -861,7 +1190,7 @@ LIMIT $2"#,
                         r#"
 SELECT id, email, created_at, last_login
 FROM users
-WHERE id LIKE $1
+WHERE id LIKE $1 AND deleted_at IS NULL
 ORDER BY created_at ASC
 LIMIT $2"#,
                         q,
@@ -878,7 +1207,7 @@ LIMIT $2"#,
                             r#"
 SELECT id, email, created_at, last_login
 FROM users
-WHERE email LIKE $1
+WHERE email LIKE $1 AND deleted_at IS NULL
 ORDER BY created_at ASC
 LIMIT $2"#,
                             params!(q, limit),
@@ -890,7 +1219,7 @@ LIMIT $2"#,
                         r#"
 SELECT id, email, created_at, last_login
 FROM users
-WHERE email LIKE $1
+WHERE email LIKE $1 AND deleted_at IS NULL
 ORDER BY created_at ASC
 LIMIT $2"#,
                         q,
@@ -915,17 +1244,20 @@ LIMIT $2"#,
         user_id: String,
         email_verified: bool,
     ) -> Result<(), ErrorResponse> {
+        let verified_at = email_verified.then(|| clock::now().timestamp());
+
         if is_hiqlite() {
             DB::client()
                 .execute(
-                    "UPDATE users SET email_verified = $1 WHERE id = $2",
-                    params!(email_verified, user_id),
+                    "UPDATE users SET email_verified = $1, email_verified_at = $2 WHERE id = $3",
+                    params!(email_verified, verified_at, user_id),
                 )
                 .await?;
         } else {
             sqlx::query!(
-                "UPDATE users SET email_verified = $1 WHERE id = $2",
+                "UPDATE users SET email_verified = $1, email_verified_at = $2 WHERE id = $3",
                 email_verified,
+                verified_at,
                 user_id
             )
             .execute(DB::conn())
@@ -968,17 +1300,25 @@ LIMIT $2"#,
         user.groups = Group::sanitize(upd_user.groups).await?;
 
         user.enabled = upd_user.enabled;
-        user.email_verified = upd_user.email_verified;
+        if user.email_verified != upd_user.email_verified {
+            user.set_email_verified_now(upd_user.email_verified);
+        }
         user.user_expires = upd_user.user_expires;
+        if let Some(must_change_password) = upd_user.must_change_password {
+            user.must_change_password = must_change_password;
+        }
 
         user.save(old_email.clone()).await?;
 
         if upd_user.password.is_some() {
             data.tx_events
-                .send_async(Event::user_password_reset(
-                    format!("Reset done by admin for user {}", user.email),
-                    None,
-                ))
+                .send_async(
+                    Event::user_password_reset(
+                        format!("Reset done by admin for user {}", user.email),
+                        None,
+                    )
+                    .with_user_id(user.id.clone()),
+                )
                 .await
                 .unwrap();
         }
@@ -994,7 +1334,9 @@ LIMIT $2"#,
 
             let event_text = format!("Change by admin: {} -> {}", old_email, user.email);
             data.tx_events
-                .send_async(Event::user_email_change(event_text, None))
+                .send_async(
+                    Event::user_email_change(event_text, None).with_user_id(user.id.clone()),
+                )
                 .await
                 .unwrap();
         }
@@ -1049,12 +1391,7 @@ LIMIT $2"#,
                 user.validate_password(data, pwd_curr).await?;
             } else if let Some(mfa_code) = upd_user.mfa_code {
                 let svc_req = WebauthnServiceReq::find(mfa_code).await?;
-                if svc_req.user_id != user.id {
-                    return Err(ErrorResponse::new(
-                        ErrorResponseType::Forbidden,
-                        "User ID does not match",
-                    ));
-                }
+                svc_req.validate(&user.id, MfaPurpose::PasswordNew)?;
                 svc_req.delete().await?;
             } else {
                 return Err(ErrorResponse::new(
@@ -1107,6 +1444,7 @@ LIMIT $2"#,
             email_verified: user.email_verified,
             user_expires: user.user_expires,
             user_values: upd_user.user_values,
+            must_change_password: None,
         };
 
         // a user cannot become a new admin from a self-req
@@ -1170,19 +1508,21 @@ impl User {
     }
 
     pub async fn apply_password_rules(&mut self, plain_pwd: &str) -> Result<(), ErrorResponse> {
-        let rules = PasswordPolicy::find().await?;
+        let rules = PasswordPolicy::find_for_user(self).await?;
 
         if plain_pwd.len() < rules.length_min as usize {
             return Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
                 format!("Minimum password length is {}", rules.length_min),
-            ));
+            )
+            .with_code(ErrorCode::PasswordPolicy));
         }
         if plain_pwd.len() > rules.length_max as usize {
             return Err(ErrorResponse::new(
                 ErrorResponseType::BadRequest,
                 format!("Maximum password length is {}", rules.length_max),
-            ));
+            )
+            .with_code(ErrorCode::PasswordPolicy));
         }
 
         let mut count_lower = 0;
@@ -1210,7 +1550,8 @@ impl User {
                     "New password does not include the minimum lower character count: {}",
                     lower_req
                 ),
-            ));
+            )
+            .with_code(ErrorCode::PasswordPolicy));
         }
 
         let upper_req = rules.include_upper_case.unwrap_or(0);
@@ -1221,7 +1562,8 @@ impl User {
                     "New password does not include the minimum upper character count: {}",
                     upper_req
                 ),
-            ));
+            )
+            .with_code(ErrorCode::PasswordPolicy));
         }
 
         let digit_req = rules.include_digits.unwrap_or(0);
@@ -1232,7 +1574,8 @@ impl User {
                     "New password does not include the minimum digit count: {}",
                     digit_req
                 ),
-            ));
+            )
+            .with_code(ErrorCode::PasswordPolicy));
         }
 
         let special_req = rules.include_special.unwrap_or(0);
@@ -1243,7 +1586,8 @@ impl User {
                     "New password does not include the minimum special character count: {}",
                     special_req
                 ),
-            ));
+            )
+            .with_code(ErrorCode::PasswordPolicy));
         }
 
         let new_hash = HashPassword::hash_password(plain_pwd.to_string()).await?;
@@ -1263,7 +1607,8 @@ impl User {
                                     "The new password must not be one of the last {} used passwords",
                                     recent_req,
                                 ),
-                            ));
+                            )
+                            .with_code(ErrorCode::PasswordPolicy));
                         }
 
                         new_recent.push(old_hash);
@@ -1289,7 +1634,7 @@ impl User {
             self.password_expires = None;
         } else {
             let password_expires = rules.valid_days.map(|d| {
-                OffsetDateTime::now_utc()
+                clock::now_utc()
                     .add(::time::Duration::days(d as i64))
                     .unix_timestamp()
             });
@@ -1297,6 +1642,7 @@ impl User {
         }
 
         self.password = Some(new_hash);
+        self.must_change_password = false;
 
         Ok(())
     }
@@ -1316,7 +1662,7 @@ impl User {
     #[inline]
     pub fn check_expired(&self) -> Result<(), ErrorResponse> {
         if let Some(ts) = self.user_expires {
-            if Utc::now().timestamp() > ts {
+            if clock::now().timestamp() > ts {
                 trace!("User has expired");
                 return Err(ErrorResponse::new(
                     ErrorResponseType::Disabled,
@@ -1327,6 +1673,156 @@ impl User {
         Ok(())
     }
 
+    /// Returns the timestamp of the automatic unlock, if the account is currently locked after
+    /// too many failed password logins.
+    ///
+    /// The lockout duration starts at `ACCOUNT_LOCKOUT_DURATION` and doubles with each further
+    /// failed attempt after the threshold has been reached, up to `ACCOUNT_LOCKOUT_DURATION_MAX`.
+    pub fn locked_until(&self) -> Option<i64> {
+        let until = Self::lockout_end(
+            self.failed_login_attempts.unwrap_or_default(),
+            self.last_failed_login?,
+            *ACCOUNT_LOCKOUT_THRESHOLD as i64,
+            *ACCOUNT_LOCKOUT_DURATION as i64,
+            *ACCOUNT_LOCKOUT_DURATION_MAX as i64,
+        )?;
+        (until > clock::now().timestamp()).then_some(until)
+    }
+
+    fn lockout_end(
+        attempts: i64,
+        last_failed_login: i64,
+        threshold: i64,
+        duration: i64,
+        duration_max: i64,
+    ) -> Option<i64> {
+        if threshold == 0 || attempts < threshold {
+            return None;
+        }
+
+        let exp = (attempts - threshold).min(32) as u32;
+        let secs = duration.saturating_mul(1 << exp).min(duration_max);
+        Some(last_failed_login + secs)
+    }
+
+    /// Must only be called after the password has been verified successfully. Otherwise, the
+    /// lock state would be revealed to anyone, who only knows the username.
+    #[inline]
+    pub fn check_locked(&self) -> Result<(), ErrorResponse> {
+        if self.locked_until().is_some() {
+            trace!("User is locked");
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Disabled,
+                "The account is locked after too many failed logins",
+            )
+            .with_code(ErrorCode::AccountLocked));
+        }
+        Ok(())
+    }
+
+    /// Increases the failed login counter and sends a `UserLocked` event, if this attempt
+    /// locked the account.
+    pub async fn register_failed_login(
+        &mut self,
+        data: &web::Data<AppState>,
+        req: &HttpRequest,
+    ) -> Result<(), ErrorResponse> {
+        // The counter is increased inside the DB, so concurrent failed logins cannot overwrite
+        // each other.
+        let now = clock::now().timestamp();
+        let attempts: i64 = if is_hiqlite() {
+            let mut row = DB::client()
+                .execute_returning_one(
+                    r#"
+UPDATE users
+SET failed_login_attempts = COALESCE(failed_login_attempts, 0) + 1, last_failed_login = $1
+WHERE id = $2
+RETURNING failed_login_attempts"#,
+                    params!(now, self.id.clone()),
+                )
+                .await?;
+            row.get("failed_login_attempts")
+        } else {
+            sqlx::query!(
+                r#"
+UPDATE users
+SET failed_login_attempts = COALESCE(failed_login_attempts, 0) + 1, last_failed_login = $1
+WHERE id = $2
+RETURNING failed_login_attempts"#,
+                now,
+                self.id,
+            )
+            .fetch_one(DB::conn())
+            .await?
+            .failed_login_attempts
+            .unwrap_or(1)
+        };
+        self.last_failed_login = Some(now);
+        self.failed_login_attempts = Some(attempts);
+        Self::invalidate_cache(&self.id, &self.email).await?;
+
+        if let Some(until) = self.locked_until() {
+            Event::user_locked(
+                self.email.clone(),
+                until,
+                real_ip_from_req(req)?.to_string(),
+            )
+            .with_user_id(self.id.clone())
+            .send(&data.tx_events)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Resets the failed login counter, which lifts a possibly existing lock immediately.
+    pub async fn unlock(
+        &mut self,
+        data: &web::Data<AppState>,
+        req: &HttpRequest,
+    ) -> Result<(), ErrorResponse> {
+        let was_locked = self.locked_until().is_some();
+
+        self.last_failed_login = None;
+        self.failed_login_attempts = None;
+        self.save(None).await?;
+
+        if was_locked {
+            Event::user_unlocked(self.email.clone(), real_ip_from_req(req)?.to_string())
+                .with_user_id(self.id.clone())
+                .send(&data.tx_events)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets `email_verified` and keeps `email_verified_at` in sync with it.
+    pub fn set_email_verified_now(&mut self, verified: bool) {
+        self.email_verified = verified;
+        self.email_verified_at = verified.then(|| clock::now().timestamp());
+    }
+
+    /// Sends out a new Magic Link to verify the current E-Mail address. Possibly existing links
+    /// of the same type will be invalidated.
+    pub async fn request_email_verification(
+        &self,
+        data: &web::Data<AppState>,
+    ) -> Result<(), ErrorResponse> {
+        if self.email_verified {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "The E-Mail address has been verified already",
+            ));
+        }
+
+        MagicLink::invalidate_all_email_verify(&self.id).await?;
+        let ml = MagicLink::create(self.id.clone(), 60, MagicLinkUsage::EmailVerify).await?;
+        send_email_verify(data, &ml, self).await;
+
+        Ok(())
+    }
+
     pub async fn confirm_email_address(
         data: &web::Data<AppState>,
         req: HttpRequest,
@@ -1346,6 +1842,23 @@ impl User {
                 ));
             }
             MagicLinkUsage::EmailChange(email) => email,
+            MagicLinkUsage::EmailVerify => {
+                let mut user = Self::find(user_id).await?;
+                user.set_email_verified_now(true);
+                user.save(None).await?;
+                ml.invalidate().await?;
+
+                // the same page is rendered, which will show the verification info when both
+                // addresses are equal
+                let colors = ColorEntity::find_rauthy().await?;
+                let lang = Language::try_from(&req).unwrap_or_default();
+                return Ok(UserEmailChangeConfirmHtml::build(
+                    &colors,
+                    &lang,
+                    &user.email,
+                    &user.email,
+                ));
+            }
         };
 
         let mut user = Self::find(user_id).await?;
@@ -1358,7 +1871,7 @@ impl User {
         // save data
         let old_email = user.email;
         user.email = new_email;
-        user.email_verified = true;
+        user.set_email_verified_now(true);
         user.save(Some(old_email.clone())).await?;
         ml.invalidate().await?;
 
@@ -1372,7 +1885,7 @@ impl User {
         let event_text = format!("{} -> {}", old_email, user.email);
         let ip = real_ip_from_req(&req).ok();
         data.tx_events
-            .send_async(Event::user_email_change(event_text, ip))
+            .send_async(Event::user_email_change(event_text, ip).with_user_id(user.id.clone()))
             .await
             .unwrap();
 
@@ -1456,6 +1969,7 @@ impl User {
             roles,
             groups,
             user_expires: new_user.user_expires,
+            external_id: new_user.external_id,
             ..Default::default()
         };
 
@@ -1484,11 +1998,66 @@ impl User {
         res
     }
 
+    /// Returns the effective groups and roles, including everything inherited via nested groups.
+    pub async fn get_groups_roles_effective(
+        &self,
+    ) -> Result<(Vec<String>, Vec<String>), ErrorResponse> {
+        GroupInheritance::resolve(self.get_groups(), self.get_roles()).await
+    }
+
     #[inline(always)]
     pub fn has_webauthn_enabled(&self) -> bool {
         self.webauthn_user_id.is_some()
     }
 
+    /// Removes all 2nd factors of the user, signs out all devices and flags the account to
+    /// require a new 2nd factor at the next login. The user will be notified via E-Mail.
+    pub async fn reset_mfa(data: &web::Data<AppState>, id: String) -> Result<Self, ErrorResponse> {
+        for pk in PasskeyEntity::find_for_user(&id).await? {
+            PasskeyEntity::delete(id.clone(), pk.name).await?;
+        }
+        UserTotp::delete(&id).await?;
+        UserYubicoOtp::delete(&id).await?;
+        UserSmsOtp::delete(&id).await?;
+        if *MFA_EMAIL_OTP == EmailOtpMode::OptIn {
+            UserEmailOtp::set_enabled(id.clone(), false).await?;
+        }
+        MfaRecoveryCode::delete_all_for_user(&id).await?;
+        TrustedDevice::delete_all_for_user(&id).await?;
+
+        // deleting the last passkey modifies the user as well -> fetch it afterward
+        let mut user = Self::find(id).await?;
+        user.mfa_reenroll = true;
+        user.save(None).await?;
+
+        Session::invalidate_for_user(&user.id).await?;
+        RefreshToken::invalidate_for_user(&user.id).await?;
+        OpaqueToken::invalidate_for_user(&user.id).await?;
+
+        send_mfa_reset_info(data, &user).await;
+
+        Ok(user)
+    }
+
+    /// After an MFA reset, a login is only possible to the account dashboard, until a new
+    /// 2nd factor has been enrolled. `has_otp` must be set, if the user has TOTP, Yubico OTP or
+    /// SMS OTP.
+    pub fn validate_mfa_reenroll(
+        &self,
+        client_id: &str,
+        has_otp: bool,
+    ) -> Result<(), ErrorResponse> {
+        if self.mfa_reenroll && client_id != "rauthy" && !self.has_webauthn_enabled() && !has_otp {
+            trace!("The user must enroll a new 2nd factor after an MFA reset");
+            Err(ErrorResponse::new(
+                ErrorResponseType::MfaRequired,
+                "A new MFA device must be registered for this account",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn into_response(self, user_values: Option<UserValues>) -> UserResponse {
         let roles = self.get_roles();
         let groups = if self.groups.is_some() {
@@ -1497,6 +2066,7 @@ impl User {
             None
         };
         let account_type = UserAccountTypeResponse::from(self.account_type());
+        let locked_until = self.locked_until();
 
         UserResponse {
             id: self.id,
@@ -1508,11 +2078,13 @@ impl User {
             groups,
             enabled: self.enabled,
             email_verified: self.email_verified,
+            email_verified_at: self.email_verified_at,
             password_expires: self.password_expires,
             created_at: self.created_at,
             last_login: self.last_login,
             last_failed_login: self.last_failed_login,
             failed_login_attempts: self.failed_login_attempts,
+            locked_until,
             user_expires: self.user_expires,
             account_type,
             webauthn_user_id: self.webauthn_user_id,
@@ -1521,6 +2093,9 @@ impl User {
                 .unwrap_or_default(),
             auth_provider_id: self.auth_provider_id,
             federation_uid: self.federation_uid,
+            external_id: self.external_id,
+            must_change_password: self.must_change_password,
+            mfa_reenroll: self.mfa_reenroll,
         }
     }
 
@@ -1553,6 +2128,12 @@ impl User {
         self.get_roles().contains(&RAUTHY_ADMIN_ROLE)
     }
 
+    /// The E-Mail of a soft-deleted user. It contains the unique user ID, which means it can
+    /// never collide with any other address, and the original E-Mail for a possible restore.
+    fn email_tombstone(id: &str, email: &str) -> String {
+        format!("deleted${}${}", id, email)
+    }
+
     async fn is_email_free(email: String) -> Result<(), ErrorResponse> {
         match User::find_by_email(email).await {
             Ok(_) => Err(ErrorResponse::new(
@@ -1608,7 +2189,7 @@ impl User {
         let ml_res = MagicLink::find_by_user(self.id.clone()).await;
         // if an active magic link already exists - invalidate it.
         if let Ok(mut ml) = ml_res {
-            if ml.exp > OffsetDateTime::now_utc().unix_timestamp() {
+            if ml.exp > clock::now_utc().unix_timestamp() {
                 warn!(
                     "Password reset request with already existing valid magic link from: {}",
                     real_ip_from_req(&req)?
@@ -1641,7 +2222,7 @@ impl User {
         }
 
         if let Some(exp) = self.password_expires {
-            if exp < OffsetDateTime::now_utc().unix_timestamp() {
+            if exp < clock::now_utc().unix_timestamp() {
                 // TODO introduce some "is allowed to refresh" variable
                 if !self.enabled {
                     return Err(ErrorResponse::new(
@@ -1668,7 +2249,8 @@ impl User {
                     Err(ErrorResponse::new(
                         ErrorResponseType::Unauthorized,
                         "Invalid user credentials",
-                    ))
+                    )
+                    .with_code(ErrorCode::InvalidCredentials))
                 };
             }
         }
@@ -1676,10 +2258,10 @@ impl User {
         if self.match_passwords(plain_password).await? {
             Ok(())
         } else {
-            Err(ErrorResponse::new(
-                ErrorResponseType::Unauthorized,
-                "Invalid user credentials",
-            ))
+            Err(
+                ErrorResponse::new(ErrorResponseType::Unauthorized, "Invalid user credentials")
+                    .with_code(ErrorCode::InvalidCredentials),
+            )
         }
     }
 }
@@ -1687,7 +2269,7 @@ impl User {
 impl Default for User {
     fn default() -> Self {
         Self {
-            id: new_store_id(),
+            id: new_user_id(),
             email: String::default(),
             given_name: String::default(),
             family_name: None,
@@ -1697,7 +2279,7 @@ impl Default for User {
             enabled: true,
             email_verified: false,
             password_expires: None,
-            created_at: OffsetDateTime::now_utc().unix_timestamp(),
+            created_at: clock::now_utc().unix_timestamp(),
             last_login: None,
             last_failed_login: None,
             failed_login_attempts: None,
@@ -1706,8 +2288,76 @@ impl Default for User {
             user_expires: None,
             auth_provider_id: None,
             federation_uid: None,
+            external_id: None,
+            deleted_at: None,
+            must_change_password: false,
+            email_verified_at: None,
+            mfa_reenroll: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum UserSearchCursorValue {
+    Int(i64),
+    Text(String),
+}
+
+/// Points to the last user of a page for `User::search_filtered()`. It contains the value of the
+/// sort key, which makes it independent of any offsets.
+#[derive(Debug, PartialEq)]
+struct UserSearchCursor {
+    value: UserSearchCursorValue,
+    id: String,
+}
+
+impl UserSearchCursor {
+    fn from_user(user: &User, sort: &UserSortKey) -> Self {
+        let value = match sort {
+            UserSortKey::CreatedAt => UserSearchCursorValue::Int(user.created_at),
+            UserSortKey::Email => UserSearchCursorValue::Text(user.email.clone()),
+            UserSortKey::GivenName => UserSearchCursorValue::Text(user.given_name.clone()),
+            UserSortKey::FamilyName => {
+                UserSearchCursorValue::Text(user.family_name.clone().unwrap_or_default())
+            }
+            UserSortKey::LastLogin => UserSearchCursorValue::Int(user.last_login.unwrap_or(0)),
+        };
+
+        Self {
+            value,
+            id: user.id.clone(),
         }
     }
+
+    fn encode(&self) -> String {
+        let plain = match &self.value {
+            UserSearchCursorValue::Int(v) => format!("{}\n{}", self.id, v),
+            UserSearchCursorValue::Text(v) => format!("{}\n{}", self.id, v),
+        };
+        base64_url_no_pad_encode(plain.as_bytes())
+    }
+
+    fn try_from_str(cursor: &str, sort: &UserSortKey) -> Result<Self, ErrorResponse> {
+        let err = || ErrorResponse::new(ErrorResponseType::BadRequest, "Invalid cursor");
+
+        let bytes = base64_url_no_pad_decode(cursor).map_err(|_| err())?;
+        let plain = String::from_utf8(bytes).map_err(|_| err())?;
+        let (id, value) = plain.split_once('\n').ok_or_else(err)?;
+
+        let value = match sort {
+            UserSortKey::CreatedAt | UserSortKey::LastLogin => {
+                UserSearchCursorValue::Int(value.parse::<i64>().map_err(|_| err())?)
+            }
+            UserSortKey::Email | UserSortKey::GivenName | UserSortKey::FamilyName => {
+                UserSearchCursorValue::Text(value.to_string())
+            }
+        };
+
+        Ok(Self {
+            value,
+            id: id.to_string(),
+        })
+    }
 }
 
 impl From<User> for UserResponseSimple {
@@ -1728,6 +2378,41 @@ mod tests {
     use pretty_assertions::assert_eq;
     use std::ops::Sub;
 
+    #[test]
+    fn test_email_tombstone() {
+        let tombstone = User::email_tombstone("user123", "alfred@batcave.io");
+        assert_eq!(tombstone, "deleted$user123$alfred@batcave.io");
+        assert_eq!(
+            tombstone.strip_prefix(&User::email_tombstone("user123", "")),
+            Some("alfred@batcave.io")
+        );
+        assert_eq!(
+            tombstone.strip_prefix(&User::email_tombstone("user456", "")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_lockout_end() {
+        let ts = 1_700_000_000;
+
+        // disabled or below the threshold
+        assert_eq!(User::lockout_end(10, ts, 0, 300, 86400), None);
+        assert_eq!(User::lockout_end(4, ts, 5, 300, 86400), None);
+
+        // the duration doubles with each attempt after the threshold
+        assert_eq!(User::lockout_end(5, ts, 5, 300, 86400), Some(ts + 300));
+        assert_eq!(User::lockout_end(6, ts, 5, 300, 86400), Some(ts + 600));
+        assert_eq!(User::lockout_end(7, ts, 5, 300, 86400), Some(ts + 1200));
+
+        // capped at the max duration, even for a huge amount of attempts
+        assert_eq!(User::lockout_end(14, ts, 5, 300, 86400), Some(ts + 86400));
+        assert_eq!(
+            User::lockout_end(i64::MAX, ts, 5, 300, 86400),
+            Some(ts + 86400)
+        );
+    }
+
     #[test]
     fn test_session_impl() {
         let mut user = User {
@@ -1740,20 +2425,25 @@ mod tests {
             groups: Some("admin,user".to_string()),
             enabled: true,
             email_verified: true,
-            password_expires: Some(OffsetDateTime::now_utc().unix_timestamp()),
-            created_at: OffsetDateTime::now_utc().unix_timestamp(),
+            password_expires: Some(clock::now_utc().unix_timestamp()),
+            created_at: clock::now_utc().unix_timestamp(),
             last_login: None,
             last_failed_login: None,
             failed_login_attempts: None,
             language: Language::En,
             webauthn_user_id: None,
             user_expires: Some(
-                OffsetDateTime::now_utc()
+                clock::now_utc()
                     .sub(::time::Duration::seconds(2))
                     .unix_timestamp(),
             ),
             auth_provider_id: None,
             federation_uid: None,
+            external_id: None,
+            deleted_at: None,
+            must_change_password: false,
+            email_verified_at: None,
+            mfa_reenroll: false,
         };
         let session = Session::try_new(&user, 1, None);
         assert!(session.is_err());
@@ -1780,7 +2470,7 @@ mod tests {
 
     fn check_password_expired(user: &User) -> Result<(), ErrorResponse> {
         if let Some(exp) = user.password_expires {
-            if exp < OffsetDateTime::now_utc().unix_timestamp() {
+            if exp < clock::now_utc().unix_timestamp() {
                 return Err(ErrorResponse::new(
                     ErrorResponseType::PasswordExpired,
                     String::from("The password has expired"),
@@ -1803,7 +2493,7 @@ mod tests {
             enabled: false,
             email_verified: true,
             password_expires: None,
-            created_at: OffsetDateTime::now_utc().unix_timestamp(),
+            created_at: clock::now_utc().unix_timestamp(),
             last_login: None,
             last_failed_login: None,
             failed_login_attempts: None,
@@ -1812,6 +2502,11 @@ mod tests {
             user_expires: None,
             auth_provider_id: None,
             federation_uid: None,
+            external_id: None,
+            deleted_at: None,
+            must_change_password: false,
+            email_verified_at: None,
+            mfa_reenroll: false,
         };
 
         // enabled
@@ -1821,7 +2516,7 @@ mod tests {
 
         // password expiry
         assert!(check_password_expired(&user).is_ok());
-        user.password_expires = Some(OffsetDateTime::now_utc().unix_timestamp() + 1);
+        user.password_expires = Some(clock::now_utc().unix_timestamp() + 1);
         assert!(check_password_expired(&user).is_ok());
         // password expiry is validated with second accuracy
         std::thread::sleep(core::time::Duration::from_secs(2));
@@ -1909,4 +2604,34 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_user_search_cursor() {
+        let mut user = User {
+            id: "SomeId123".to_string(),
+            email: "mail@localhost.de".to_string(),
+            created_at: 1234567890,
+            ..Default::default()
+        };
+
+        let cursor = UserSearchCursor::from_user(&user, &UserSortKey::Email);
+        let parsed = UserSearchCursor::try_from_str(&cursor.encode(), &UserSortKey::Email).unwrap();
+        assert_eq!(cursor, parsed);
+        assert_eq!(
+            parsed.value,
+            UserSearchCursorValue::Text("mail@localhost.de".to_string())
+        );
+
+        user.last_login = None;
+        let cursor = UserSearchCursor::from_user(&user, &UserSortKey::LastLogin);
+        let parsed =
+            UserSearchCursor::try_from_str(&cursor.encode(), &UserSortKey::LastLogin).unwrap();
+        assert_eq!(parsed.value, UserSearchCursorValue::Int(0));
+        assert_eq!(parsed.id, "SomeId123");
+
+        // a text cursor must not be accepted for a numeric sort key
+        let cursor = UserSearchCursor::from_user(&user, &UserSortKey::Email).encode();
+        assert!(UserSearchCursor::try_from_str(&cursor, &UserSortKey::CreatedAt).is_err());
+        assert!(UserSearchCursor::try_from_str("invalid", &UserSortKey::CreatedAt).is_err());
+    }
 }
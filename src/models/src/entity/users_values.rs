@@ -2,9 +2,9 @@ use crate::database::{Cache, DB};
 use hiqlite::{params, Param};
 use jwt_simple::prelude::{Deserialize, Serialize};
 use rauthy_api_types::users::{UserValuesRequest, UserValuesResponse};
-use rauthy_common::constants::{CACHE_TTL_USER, IDX_USERS_VALUES};
+use rauthy_common::constants::{CACHE_TTL_USER, EMAIL_DEFAULT_TZ, IDX_USERS_VALUES};
 use rauthy_common::is_hiqlite;
-use rauthy_error::ErrorResponse;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
 use sqlx::FromRow;
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
@@ -16,9 +16,20 @@ pub struct UserValues {
     pub zip: Option<i32>,
     pub city: Option<String>,
     pub country: Option<String>,
+    // may not exist when migrating from an older SQLite instance
+    #[sqlx(default)]
+    pub tz: Option<String>,
 }
 
 impl UserValues {
+    /// Returns the users timezone, or the configured `EMAIL_DEFAULT_TZ` if none is set.
+    pub fn tz(&self) -> chrono_tz::Tz {
+        self.tz
+            .as_deref()
+            .and_then(|tz| tz.parse::<chrono_tz::Tz>().ok())
+            .unwrap_or(*EMAIL_DEFAULT_TZ)
+    }
+
     #[inline(always)]
     fn cache_idx(user_id: &str) -> String {
         format!("{}_{}", IDX_USERS_VALUES, user_id)
@@ -53,15 +64,24 @@ impl UserValues {
         user_id: String,
         values: UserValuesRequest,
     ) -> Result<Option<Self>, ErrorResponse> {
+        if let Some(tz) = &values.tz {
+            if tz.parse::<chrono_tz::Tz>().is_err() {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("'{}' is not a valid IANA timezone", tz),
+                ));
+            }
+        }
+
         if is_hiqlite() {
             DB::client()
                 .execute(
                     r#"
 INSERT INTO
-users_values (id, birthdate, phone, street, zip, city, country)
-VALUES ($1, $2, $3, $4, $5, $6, $7)
+users_values (id, birthdate, phone, street, zip, city, country, tz)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
 ON CONFLICT(id) DO UPDATE
-SET birthdate = $2, phone = $3, street = $4, zip = $5, city = $6, country = $7"#,
+SET birthdate = $2, phone = $3, street = $4, zip = $5, city = $6, country = $7, tz = $8"#,
                     params!(
                         &user_id,
                         &values.birthdate,
@@ -69,7 +89,8 @@ SET birthdate = $2, phone = $3, street = $4, zip = $5, city = $6, country = $7"#
                         &values.street,
                         values.zip,
                         &values.city,
-                        &values.country
+                        &values.country,
+                        &values.tz
                     ),
                 )
                 .await?;
@@ -77,10 +98,10 @@ SET birthdate = $2, phone = $3, street = $4, zip = $5, city = $6, country = $7"#
             sqlx::query!(
                 r#"
 INSERT INTO
-users_values (id, birthdate, phone, street, zip, city, country)
-VALUES ($1, $2, $3, $4, $5, $6, $7)
+users_values (id, birthdate, phone, street, zip, city, country, tz)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
 ON CONFLICT(id) DO UPDATE
-SET birthdate = $2, phone = $3, street = $4, zip = $5, city = $6, country = $7"#,
+SET birthdate = $2, phone = $3, street = $4, zip = $5, city = $6, country = $7, tz = $8"#,
                 user_id,
                 values.birthdate,
                 values.phone,
@@ -88,6 +109,7 @@ SET birthdate = $2, phone = $3, street = $4, zip = $5, city = $6, country = $7"#
                 values.zip,
                 values.city,
                 values.country,
+                values.tz,
             )
             .execute(DB::conn())
             .await?;
@@ -102,6 +124,7 @@ SET birthdate = $2, phone = $3, street = $4, zip = $5, city = $6, country = $7"#
             zip: values.zip,
             city: values.city,
             country: values.country,
+            tz: values.tz,
         });
         DB::client()
             .put(Cache::User, idx, &slf, CACHE_TTL_USER)
@@ -120,6 +143,7 @@ impl From<UserValues> for UserValuesResponse {
             zip: value.zip,
             city: value.city,
             country: value.country,
+            tz: value.tz,
         }
     }
 }
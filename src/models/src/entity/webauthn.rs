@@ -1,21 +1,26 @@
 use crate::api_cookie::ApiCookie;
-use crate::app_state::{AppState, DbTxn};
+use crate::app_state::{DbTxn, WebauthnRp};
 use crate::database::{Cache, DB};
+use crate::entity::clients::Client;
+use crate::entity::fido_mds::FidoMds;
 use crate::entity::password::PasswordPolicy;
 use crate::entity::users::{AccountType, User};
+use crate::events::event::Event;
 use actix_web::cookie::Cookie;
 use actix_web::http::header;
 use actix_web::http::header::HeaderValue;
-use actix_web::{web, HttpResponse};
-use chrono::Utc;
+use actix_web::HttpResponse;
 use cryptr::EncValue;
 use hiqlite::{params, Param, Params};
 use rauthy_api_types::users::{
-    MfaPurpose, PasskeyResponse, WebauthnAuthFinishRequest, WebauthnAuthStartResponse,
-    WebauthnLoginFinishResponse, WebauthnRegFinishRequest, WebauthnRegStartRequest,
+    MfaPurpose, PasskeyResponse, UserAttrValueRequest, WebauthnAuthFinishRequest,
+    WebauthnAuthStartResponse, WebauthnDiscoverableStartResponse, WebauthnLoginFinishResponse,
+    WebauthnRegFinishRequest, WebauthnRegStartRequest,
 };
+use rauthy_common::clock;
 use rauthy_common::constants::{
-    CACHE_TTL_WEBAUTHN, CACHE_TTL_WEBAUTHN_DATA, COOKIE_MFA, IDX_WEBAUTHN, WEBAUTHN_FORCE_UV,
+    CACHE_TTL_WEBAUTHN, CACHE_TTL_WEBAUTHN_DATA, COOKIE_MFA, IDX_WEBAUTHN, WEBAUTHN_AAGUID_ALLOW,
+    WEBAUTHN_AAGUID_DENY, WEBAUTHN_FORCE_RESIDENT_KEY, WEBAUTHN_FORCE_UV, WEBAUTHN_MDS_ENABLE,
     WEBAUTHN_NO_PASSWORD_EXPIRY, WEBAUTHN_RENEW_EXP, WEBAUTHN_REQ_EXP,
 };
 use rauthy_common::is_hiqlite;
@@ -31,7 +36,8 @@ use tracing::{error, info, warn};
 use utoipa::ToSchema;
 use webauthn_rs::prelude::*;
 use webauthn_rs_proto::{
-    AuthenticatorSelectionCriteria, ResidentKeyRequirement, UserVerificationPolicy,
+    AttestationConveyancePreference, AuthenticatorSelectionCriteria, AuthenticatorTransport,
+    ExtnState, ResidentKeyRequirement, UserVerificationPolicy,
 };
 
 #[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
@@ -44,6 +50,8 @@ pub struct PasskeyEntity {
     pub registered: i64,
     pub last_used: i64,
     pub user_verified: Option<bool>,
+    /// The last time this passkey has been used from another device via hybrid transport
+    pub last_used_hybrid: Option<i64>,
 }
 
 // CRUD
@@ -59,7 +67,7 @@ impl PasskeyEntity {
     ) -> Result<(), ErrorResponse> {
         // json, because bincode does not support deserialize from any, which would be the case here
         let passkey = serde_json::to_string(&pk)?;
-        let now = Utc::now().timestamp();
+        let now = clock::now().timestamp();
 
         let entity = Self {
             user_id,
@@ -70,6 +78,7 @@ impl PasskeyEntity {
             registered: now,
             last_used: now,
             user_verified: Some(user_verified),
+            last_used_hybrid: None,
         };
 
         let user_email = user.as_ref().map(|u| u.email.clone());
@@ -184,11 +193,11 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
 
             // in this case, we need to check against the current password policy,
             // if the password should expire again
-            let policy = PasswordPolicy::find().await?;
+            let policy = PasswordPolicy::find_for_user(&user).await?;
             if let Some(valid_days) = policy.valid_days {
                 if user.password.is_some() {
                     user.password_expires = Some(
-                        Utc::now()
+                        clock::now()
                             .add(chrono::Duration::days(valid_days as i64))
                             .timestamp(),
                     );
@@ -226,6 +235,46 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
         Ok(())
     }
 
+    /// Renames a passkey. The name is part of the primary key and must be unique per user.
+    pub async fn rename(user_id: &str, name: &str, new_name: &str) -> Result<(), ErrorResponse> {
+        if name == new_name {
+            return Ok(());
+        }
+        // makes sure the passkey exists
+        Self::find(user_id, name).await?;
+        if Self::find(user_id, new_name).await.is_ok() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "A passkey with this name already exists",
+            ));
+        }
+
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "UPDATE passkeys SET name = $1 WHERE user_id = $2 AND name = $3",
+                    params!(new_name, user_id, name),
+                )
+                .await?;
+        } else {
+            sqlx::query!(
+                "UPDATE passkeys SET name = $1 WHERE user_id = $2 AND name = $3",
+                new_name,
+                user_id,
+                name,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Self::clear_caches_by_id_name(user_id, None, name).await?;
+        DB::client()
+            .delete(Cache::Webauthn, Self::cache_idx_single(user_id, new_name))
+            .await?;
+
+        Ok(())
+    }
+
     /// MUST call `PasskeyEntity::clear_caches_by_id_name()` after txn commit!
     async fn delete_by_id_name(
         user_id: &str,
@@ -311,6 +360,27 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
         Ok(slf)
     }
 
+    pub async fn find_by_cred_id(credential_id: &[u8]) -> Result<Self, ErrorResponse> {
+        let slf = if is_hiqlite() {
+            DB::client()
+                .query_as_one(
+                    "SELECT * FROM passkeys WHERE credential_id = $1",
+                    params!(credential_id.to_vec()),
+                )
+                .await?
+        } else {
+            sqlx::query_as!(
+                Self,
+                "SELECT * FROM passkeys WHERE credential_id = $1",
+                credential_id,
+            )
+            .fetch_one(DB::conn())
+            .await?
+        };
+
+        Ok(slf)
+    }
+
     pub async fn find_cred_ids_for_user(user_id: &str) -> Result<Vec<CredentialID>, ErrorResponse> {
         let idx = Self::cache_idx_creds(user_id);
         let client = DB::client();
@@ -460,9 +530,15 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
         txn.push((
             r#"
 UPDATE passkeys
-SET passkey = $1, last_used = $2
-WHERE user_id = $3 AND name = $4"#,
-            params!(&self.passkey, self.last_used, &self.user_id, &self.name),
+SET passkey = $1, last_used = $2, last_used_hybrid = $3
+WHERE user_id = $4 AND name = $5"#,
+            params!(
+                &self.passkey,
+                self.last_used,
+                self.last_used_hybrid,
+                &self.user_id,
+                &self.name
+            ),
         ));
     }
 
@@ -470,10 +546,11 @@ WHERE user_id = $3 AND name = $4"#,
         sqlx::query!(
             r#"
 UPDATE passkeys
-SET passkey = $1, last_used = $2
-WHERE user_id = $3 AND name = $4"#,
+SET passkey = $1, last_used = $2, last_used_hybrid = $3
+WHERE user_id = $4 AND name = $5"#,
             self.passkey,
             self.last_used,
+            self.last_used_hybrid,
             self.user_id,
             self.name,
         )
@@ -483,6 +560,46 @@ WHERE user_id = $3 AND name = $4"#,
         Ok(())
     }
 
+    /// Saves the updated credential and the login timestamps after a successful authentication.
+    async fn update_after_auth(
+        mut self,
+        user: &mut User,
+        auth_result: &AuthenticationResult,
+        hybrid: bool,
+    ) -> Result<(), ErrorResponse> {
+        // The counter and backup state only change from time to time, but `last_used`
+        // must be updated with each login to be able to tell unused keys apart.
+        if auth_result.needs_update() {
+            let mut pk = self.get_pk();
+            if pk.update_credential(auth_result) == Some(true) {
+                self.passkey = serde_json::to_string(&pk)?;
+            }
+        }
+
+        let now = clock::now_utc().unix_timestamp();
+        self.last_used = now;
+        if hybrid {
+            self.last_used_hybrid = Some(now);
+        }
+        user.last_login = Some(now);
+        user.last_failed_login = None;
+        user.failed_login_attempts = None;
+
+        if is_hiqlite() {
+            let mut txn = Vec::with_capacity(2);
+            self.update_passkey_txn_append(&mut txn);
+            user.clone().save_txn_append(&mut txn);
+            DB::client().txn(txn).await?;
+        } else {
+            let mut txn = DB::txn().await?;
+            self.update_passkey_txn(&mut txn).await?;
+            user.save_txn(&mut txn).await?;
+            txn.commit().await?;
+        }
+
+        self.update_caches_after_update().await
+    }
+
     async fn update_caches_after_update(&self) -> Result<(), ErrorResponse> {
         let client = DB::client();
 
@@ -513,6 +630,49 @@ impl PasskeyEntity {
         serde_json::from_str(&self.passkey).unwrap()
     }
 
+    /// The AAGUID is only known, if an attestation has been requested during the registration.
+    pub fn aaguid(&self) -> Option<String> {
+        attested_aaguid(&Credential::from(self.get_pk())).map(|(id, _)| id)
+    }
+
+    /// Returns `true`, if the assertion most likely happened on another device via hybrid
+    /// transport (caBLE), for instance by scanning a QR code with a phone.
+    fn is_hybrid_auth(&self, authenticator_attachment: Option<&str>) -> bool {
+        let cred = Credential::from(self.get_pk());
+        is_hybrid_auth(
+            authenticator_attachment,
+            cred.transports.as_deref(),
+            cred.backup_eligible,
+        )
+    }
+
+    /// Resolves the authenticator model name from the FIDO MDS, if it is given.
+    /// The transports and backup flags are taken from the stored credential.
+    pub fn into_response(self, mds: Option<&FidoMds>) -> PasskeyResponse {
+        let aaguid = self.aaguid();
+        let model = aaguid
+            .as_ref()
+            .zip(mds)
+            .and_then(|(id, mds)| mds.entries.get(id))
+            .map(|entry| entry.description.clone());
+
+        let cred = Credential::from(self.get_pk());
+        let transports = transport_names(cred.transports.as_deref());
+
+        PasskeyResponse {
+            name: self.name,
+            registered: self.registered,
+            last_used: self.last_used,
+            user_verified: self.user_verified,
+            aaguid,
+            model,
+            transports,
+            backup_eligible: cred.backup_eligible,
+            backup_state: cred.backup_state,
+            last_used_hybrid: self.last_used_hybrid,
+        }
+    }
+
     /// Index for a single passkey for a user
     #[inline]
     fn cache_idx_single(user_id: &str, name: &str) -> String {
@@ -538,17 +698,6 @@ impl PasskeyEntity {
     }
 }
 
-impl From<PasskeyEntity> for PasskeyResponse {
-    fn from(value: PasskeyEntity) -> Self {
-        Self {
-            name: value.name,
-            registered: value.registered,
-            last_used: value.last_used,
-            user_verified: value.user_verified,
-        }
-    }
-}
-
 #[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
 pub struct WebauthnCookie {
     pub email: String,
@@ -557,7 +706,7 @@ pub struct WebauthnCookie {
 
 impl WebauthnCookie {
     pub fn new(email: String) -> Self {
-        let exp = OffsetDateTime::now_utc().add(::time::Duration::hours(*WEBAUTHN_RENEW_EXP));
+        let exp = clock::now_utc().add(::time::Duration::hours(*WEBAUTHN_RENEW_EXP));
         Self { email, exp }
     }
 
@@ -566,7 +715,7 @@ impl WebauthnCookie {
         let enc = EncValue::encrypt(&ser)?.into_bytes();
         let b64 = base64_encode(&enc);
 
-        let max_age = self.exp.unix_timestamp() - Utc::now().timestamp();
+        let max_age = self.exp.unix_timestamp() - clock::now().timestamp();
         Ok(ApiCookie::build(COOKIE_MFA, b64, max_age))
     }
 
@@ -582,7 +731,7 @@ impl WebauthnCookie {
         let dec = EncValue::try_from(bytes)?.decrypt()?;
         let slf = bincode::deserialize::<Self>(&dec)?;
 
-        if slf.exp < OffsetDateTime::now_utc() {
+        if slf.exp < clock::now_utc() {
             Err(ErrorResponse::new(
                 ErrorResponseType::SessionExpired,
                 "Webauthn Cookie has expired",
@@ -599,6 +748,9 @@ pub struct WebauthnData {
     // auth_state cannot be serialized directly with bincode -> no support for deserialize from any
     pub auth_state_json: String,
     pub data: WebauthnAdditionalData,
+    /// Empty for ceremonies, which have been started before an update to multiple RP IDs.
+    #[serde(default)]
+    pub rp_id: String,
 }
 
 // CURD
@@ -634,6 +786,49 @@ impl WebauthnData {
     }
 }
 
+/// The state of an authentication ceremony for a discoverable credential, where the user is not
+/// known before the ceremony has been finished.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebauthnDiscoverableData {
+    pub code: String,
+    pub rp_id: String,
+    // auth_state cannot be serialized directly with bincode -> no support for deserialize from any
+    pub auth_state_json: String,
+}
+
+// CRUD
+impl WebauthnDiscoverableData {
+    pub async fn delete(&self) -> Result<(), ErrorResponse> {
+        DB::client()
+            .delete(Cache::Webauthn, self.code.clone())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn find(code: String) -> Result<Self, ErrorResponse> {
+        let res: Option<Self> = DB::client().get(Cache::Webauthn, code).await?;
+        match res {
+            None => Err(ErrorResponse::new(
+                ErrorResponseType::NotFound,
+                "Webauthn Discoverable Data not found",
+            )),
+            Some(res) => Ok(res),
+        }
+    }
+
+    pub async fn save(&self) -> Result<(), ErrorResponse> {
+        DB::client()
+            .put(
+                Cache::Webauthn,
+                self.code.clone(),
+                self,
+                *CACHE_TTL_WEBAUTHN_DATA,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
 // This is the data, that will be passed to the client as response to a successful MFA auth
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub enum WebauthnAdditionalData {
@@ -685,8 +880,22 @@ impl WebauthnAdditionalData {
 pub struct WebauthnLoginReq {
     pub code: String,
     pub user_id: String,
+    pub client_id: String,
     pub header_loc: String,
     pub header_origin: Option<String>,
+    /// A terms of service version accepted during this login, which will be persisted after
+    /// the successful MFA step
+    pub tos_accept: Option<String>,
+    /// Values for custom attributes required at login, which will be saved after the
+    /// successful MFA step
+    pub profile_values: Option<Vec<UserAttrValueRequest>>,
+    /// Scopes granted on the consent screen during this login, which will be persisted after
+    /// the successful MFA step
+    pub consent_accept: Option<Vec<String>>,
+    /// The session will be turned into a "remember me" session after the successful MFA step
+    pub remember_me: bool,
+    /// The device will be trusted for future logins after the successful MFA step
+    pub trust_device: bool,
 }
 
 // CRUD
@@ -726,14 +935,16 @@ impl WebauthnLoginReq {
 pub struct WebauthnServiceReq {
     pub code: String,
     pub user_id: String,
+    pub purpose: MfaPurpose,
 }
 
 // CRUD
 impl WebauthnServiceReq {
-    pub fn new(user_id: String) -> Self {
+    pub fn new(user_id: String, purpose: MfaPurpose) -> Self {
         Self {
             code: get_rand(48),
             user_id,
+            purpose,
         }
     }
 
@@ -768,8 +979,28 @@ impl WebauthnServiceReq {
     }
 }
 
+impl WebauthnServiceReq {
+    /// Makes sure that this request was created by the given user for the given purpose.
+    /// A code from a successful assertion must never be usable for another operation.
+    pub fn validate(&self, user_id: &str, purpose: MfaPurpose) -> Result<(), ErrorResponse> {
+        if self.user_id != user_id {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "User ID does not match",
+            ));
+        }
+        if self.purpose != purpose {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "MFA purpose does not match",
+            ));
+        }
+        Ok(())
+    }
+}
+
 pub async fn auth_start(
-    data: &web::Data<AppState>,
+    rp: &WebauthnRp,
     user_id: String,
     purpose: MfaPurpose,
 ) -> Result<WebauthnAuthStartResponse, ErrorResponse> {
@@ -779,8 +1010,8 @@ pub async fn auth_start(
             let d = WebauthnLoginReq::find(code).await?;
             WebauthnAdditionalData::Login(d)
         }
-        MfaPurpose::PasswordNew | MfaPurpose::PasswordReset => {
-            let svc_req = WebauthnServiceReq::new(user_id.clone());
+        MfaPurpose::PasswordNew | MfaPurpose::PasswordReset | MfaPurpose::ApiKeyExchange => {
+            let svc_req = WebauthnServiceReq::new(user_id.clone(), purpose);
             svc_req.save().await?;
             WebauthnAdditionalData::Service(svc_req)
         }
@@ -812,7 +1043,7 @@ pub async fn auth_start(
         ));
     }
 
-    match data.webauthn.start_passkey_authentication(pks.as_slice()) {
+    match rp.webauthn.start_passkey_authentication(pks.as_slice()) {
         Ok((mut rcr, auth_state)) => {
             if force_uv {
                 rcr.public_key.user_verification = UserVerificationPolicy::Required;
@@ -823,6 +1054,7 @@ pub async fn auth_start(
             let auth_state_json = serde_json::to_string(&auth_state)?;
             let auth_data = WebauthnData {
                 code: get_rand(48),
+                rp_id: rp.rp_id.clone(),
                 auth_state_json,
                 data: add_data,
             };
@@ -847,11 +1079,12 @@ pub async fn auth_start(
 }
 
 pub async fn auth_finish(
-    data: &web::Data<AppState>,
+    rp: &WebauthnRp,
     user_id: String,
     req: WebauthnAuthFinishRequest,
-) -> Result<WebauthnAdditionalData, ErrorResponse> {
+) -> Result<(WebauthnAdditionalData, bool), ErrorResponse> {
     let auth_data = WebauthnData::find(req.code).await?;
+    validate_rp_id(rp, &auth_data.rp_id)?;
     let auth_state = serde_json::from_str(&auth_data.auth_state_json)?;
 
     let mut user = User::find(user_id).await?;
@@ -859,7 +1092,7 @@ pub async fn auth_finish(
 
     let pks = PasskeyEntity::find_for_user(&user.id).await?;
 
-    match data
+    match rp
         .webauthn
         .finish_passkey_authentication(&req.data, &auth_state)
     {
@@ -876,43 +1109,24 @@ pub async fn auth_finish(
             }
             let uid = user.id.clone();
 
-            if auth_result.needs_update() {
-                for mut pk_entity in pks {
-                    let mut pk = pk_entity.get_pk();
-                    if let Some(updated) = pk.update_credential(&auth_result) {
-                        if updated {
-                            pk_entity.passkey = serde_json::to_string(&pk)?;
-                        }
-
-                        let now = OffsetDateTime::now_utc().unix_timestamp();
-                        pk_entity.last_used = now;
-                        user.last_login = Some(now);
-                        user.last_failed_login = None;
-                        user.failed_login_attempts = None;
-
-                        if is_hiqlite() {
-                            let mut txn = Vec::with_capacity(2);
-                            pk_entity.update_passkey_txn_append(&mut txn);
-                            user.save_txn_append(&mut txn);
-                            DB::client().txn(txn).await?;
-                        } else {
-                            let mut txn = DB::txn().await?;
-                            pk_entity.update_passkey_txn(&mut txn).await?;
-                            user.save_txn(&mut txn).await?;
-                            txn.commit().await?;
-                        }
-
-                        pk_entity.update_caches_after_update().await?;
-
-                        // there can only be exactly one key that needs the update
-                        break;
-                    }
-                }
+            let mut hybrid = false;
+            if let Some(pk_entity) = pks
+                .into_iter()
+                .find(|pk| pk.credential_id[..] == auth_result.cred_id()[..])
+            {
+                FidoMds::validate_not_compromised(pk_entity.aaguid().as_deref()).await?;
+                hybrid = pk_entity.is_hybrid_auth(req.authenticator_attachment.as_deref());
+                pk_entity
+                    .update_after_auth(&mut user, &auth_result, hybrid)
+                    .await?;
             }
 
-            info!("Webauthn Authentication successful for user {}", uid);
+            info!(
+                "Webauthn Authentication successful for user {} - hybrid: {}",
+                uid, hybrid
+            );
 
-            Ok(auth_data.data)
+            Ok((auth_data.data, hybrid))
         }
         Err(err) => {
             error!("Webauthn Auth Finish: {:?}", err);
@@ -924,15 +1138,223 @@ pub async fn auth_finish(
     }
 }
 
+/// Starts an authentication ceremony for a discoverable credential without knowing the user
+/// upfront, which makes the passkey autofill (conditional mediation) on the login page possible.
+pub async fn auth_start_discoverable(
+    rp: &WebauthnRp,
+) -> Result<WebauthnDiscoverableStartResponse, ErrorResponse> {
+    match rp.webauthn.start_discoverable_authentication() {
+        Ok((rcr, auth_state)) => {
+            // cannot be serialized with bincode -> no deserialize from any
+            let auth_state_json = serde_json::to_string(&auth_state)?;
+            let auth_data = WebauthnDiscoverableData {
+                code: get_rand(48),
+                rp_id: rp.rp_id.clone(),
+                auth_state_json,
+            };
+            auth_data.save().await?;
+
+            Ok(WebauthnDiscoverableStartResponse {
+                code: auth_data.code,
+                rcr,
+                exp: *WEBAUTHN_REQ_EXP,
+            })
+        }
+
+        Err(err) => {
+            error!("Webauthn discoverable challenge authentication: {:?}", err);
+            Err(ErrorResponse::new(
+                ErrorResponseType::Internal,
+                "Internal error with Webauthn Challenge Authentication",
+            ))
+        }
+    }
+}
+
+/// Finishes an authentication ceremony for a discoverable credential and returns the user the
+/// passkey belongs to, and if the login happened cross-device via hybrid transport. The passkey
+/// is the only factor in this case, which means user verification is always required.
+pub async fn auth_finish_discoverable(
+    rp: &WebauthnRp,
+    req: &WebauthnAuthFinishRequest,
+) -> Result<(User, bool), ErrorResponse> {
+    let auth_data = WebauthnDiscoverableData::find(req.code.clone()).await?;
+    // the challenge must never be used twice
+    auth_data.delete().await?;
+    validate_rp_id(rp, &auth_data.rp_id)?;
+    let auth_state = serde_json::from_str(&auth_data.auth_state_json)?;
+
+    let err_unknown = || {
+        ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            "Unknown or invalid passkey",
+        )
+    };
+    let (passkey_user_id, cred_id) = rp
+        .webauthn
+        .identify_discoverable_authentication(&req.data)
+        .map_err(|_| err_unknown())?;
+    let pk_entity = PasskeyEntity::find_by_cred_id(cred_id)
+        .await
+        .map_err(|_| err_unknown())?;
+    if pk_entity.passkey_user_id != passkey_user_id.to_string() {
+        warn!(
+            "Webauthn discoverable credential with mismatching user handle for user {}",
+            pk_entity.user_id
+        );
+        return Err(err_unknown());
+    }
+    let mut user = User::find(pk_entity.user_id.clone()).await?;
+
+    let creds = [DiscoverableKey::from(pk_entity.get_pk())];
+    match rp
+        .webauthn
+        .finish_discoverable_authentication(&req.data, auth_state, &creds)
+    {
+        Ok(auth_result) => {
+            if !auth_result.user_verified() {
+                warn!(
+                    "Webauthn discoverable Authentication without User Verification for user {:?}",
+                    user.id
+                );
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Forbidden,
+                    "User Presence only is not allowed - Verification is needed",
+                ));
+            }
+            FidoMds::validate_not_compromised(pk_entity.aaguid().as_deref()).await?;
+            let hybrid = pk_entity.is_hybrid_auth(req.authenticator_attachment.as_deref());
+            pk_entity
+                .update_after_auth(&mut user, &auth_result, hybrid)
+                .await?;
+
+            info!(
+                "Webauthn discoverable Authentication successful for user {} - hybrid: {}",
+                user.id, hybrid
+            );
+            Ok((user, hybrid))
+        }
+        Err(err) => {
+            error!("Webauthn discoverable Auth Finish: {:?}", err);
+            Err(ErrorResponse::new(
+                ErrorResponseType::Unauthorized,
+                format!("{err}"),
+            ))
+        }
+    }
+}
+
+/// A ceremony must always be finished with the same RP it has been started with. An empty
+/// `rp_id` is accepted for ceremonies, which have been started before the update. The RP ID hash
+/// inside the authenticator data is still verified by the RP finishing the ceremony.
+#[inline]
+fn validate_rp_id(rp: &WebauthnRp, rp_id: &str) -> Result<(), ErrorResponse> {
+    if !rp_id.is_empty() && rp.rp_id != rp_id {
+        warn!(
+            "Webauthn ceremony started for RP ID '{}' but finished for '{}'",
+            rp_id, rp.rp_id
+        );
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "Webauthn ceremony has been started for another RP ID",
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the AAGUID from the attestation and if it can be trusted. A self attestation can
+/// claim any AAGUID. It is only trusted, if the attestation statement has been signed with an
+/// authenticator certificate.
+fn attested_aaguid(cred: &Credential) -> Option<(String, bool)> {
+    let aaguid = match &cred.attestation.metadata {
+        AttestationMetadata::Packed { aaguid } | AttestationMetadata::Tpm { aaguid, .. } => aaguid,
+        _ => return None,
+    };
+    if aaguid.is_nil() {
+        return None;
+    }
+
+    let trusted = matches!(
+        cred.attestation.data,
+        ParsedAttestationData::Basic(_)
+            | ParsedAttestationData::AttCa(_)
+            | ParsedAttestationData::AnonCa(_)
+    );
+    Some((aaguid.to_string(), trusted))
+}
+
+/// Validates a new credential against `WEBAUTHN_FORCE_RESIDENT_KEY`, `WEBAUTHN_AAGUID_ALLOW`,
+/// `WEBAUTHN_AAGUID_DENY` and the FIDO MDS.
+async fn validate_attestation_policy(
+    user_id: &str,
+    cred: &Credential,
+) -> Result<(), ErrorResponse> {
+    // The `credProps` extension is optional for clients. We can only reject keys, which have
+    // reported that they did not create a resident key.
+    if *WEBAUTHN_FORCE_RESIDENT_KEY {
+        if let ExtnState::Set(props) | ExtnState::Unsigned(props) = &cred.extensions.cred_props {
+            if !props.rk {
+                warn!(
+                    "Webauthn Registration Ceremony without a resident key for user {:?}",
+                    user_id
+                );
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Forbidden,
+                    "This security key cannot store a discoverable credential",
+                ));
+            }
+        }
+    }
+
+    let attested = attested_aaguid(cred);
+    // a compromised model is rejected, even if it only claims to be one
+    FidoMds::validate_not_compromised(attested.as_ref().map(|(id, _)| id.as_str())).await?;
+
+    if WEBAUTHN_AAGUID_ALLOW.is_empty() && WEBAUTHN_AAGUID_DENY.is_empty() {
+        return Ok(());
+    }
+
+    let aaguid = attested.and_then(|(id, trusted)| trusted.then_some(id));
+
+    if is_aaguid_allowed(
+        aaguid.as_deref(),
+        &WEBAUTHN_AAGUID_ALLOW,
+        &WEBAUTHN_AAGUID_DENY,
+    ) {
+        Ok(())
+    } else {
+        warn!(
+            "Webauthn Registration Ceremony with a not allowed authenticator {:?} for user {:?}",
+            aaguid, user_id
+        );
+        Err(ErrorResponse::new(
+            ErrorResponseType::Forbidden,
+            "This type of security key is not allowed",
+        ))
+    }
+}
+
+/// A non-empty allow list requires a known AAGUID. The deny list always takes precedence.
+fn is_aaguid_allowed(aaguid: Option<&str>, allow: &[String], deny: &[String]) -> bool {
+    match aaguid {
+        Some(id) if deny.iter().any(|d| d == id) => false,
+        Some(id) => allow.is_empty() || allow.iter().any(|a| a == id),
+        None => allow.is_empty(),
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct WebauthnReg {
     pub user_id: String,
     pub passkey_user_id: Uuid,
     pub reg_state: String,
+    /// Empty for ceremonies, which have been started before an update to multiple RP IDs.
+    #[serde(default)]
+    pub rp_id: String,
 }
 
 pub async fn reg_start(
-    data: &web::Data<AppState>,
+    rp: &WebauthnRp,
     user_id: String,
     req: WebauthnRegStartRequest,
 ) -> Result<CreationChallengeResponse, ErrorResponse> {
@@ -944,7 +1366,7 @@ pub async fn reg_start(
     };
     let cred_ids = PasskeyEntity::find_cred_ids_for_user(&user.id).await?;
 
-    match data.webauthn.start_passkey_registration(
+    match rp.webauthn.start_passkey_registration(
         passkey_user_id,
         &user.email,
         &user.email,
@@ -966,9 +1388,30 @@ pub async fn reg_start(
                         })
                     };
             };
+            if *WEBAUTHN_FORCE_RESIDENT_KEY {
+                if let Some(auth_sel) = ccr.public_key.authenticator_selection.as_mut() {
+                    auth_sel.resident_key = Some(ResidentKeyRequirement::Required);
+                    auth_sel.require_resident_key = true;
+                } else {
+                    ccr.public_key.authenticator_selection = Some(AuthenticatorSelectionCriteria {
+                        authenticator_attachment: None,
+                        resident_key: Some(ResidentKeyRequirement::Required),
+                        require_resident_key: true,
+                        user_verification: UserVerificationPolicy::Preferred,
+                    });
+                }
+            }
+            if !WEBAUTHN_AAGUID_ALLOW.is_empty()
+                || !WEBAUTHN_AAGUID_DENY.is_empty()
+                || *WEBAUTHN_MDS_ENABLE
+            {
+                // without an attestation, most browsers remove the AAGUID
+                ccr.public_key.attestation = Some(AttestationConveyancePreference::Direct);
+            }
 
             let reg_data = WebauthnReg {
                 user_id: user.id.clone(),
+                rp_id: rp.rp_id.clone(),
                 passkey_user_id,
                 // the reg_state cannot be serialized with bincode -> missing deserialize from Any
                 reg_state: serde_json::to_string(&reg_state)?,
@@ -994,7 +1437,7 @@ pub async fn reg_start(
 }
 
 pub async fn reg_finish(
-    data: &web::Data<AppState>,
+    rp: &WebauthnRp,
     id: String,
     req: WebauthnRegFinishRequest,
 ) -> Result<(), ErrorResponse> {
@@ -1013,9 +1456,10 @@ pub async fn reg_finish(
 
     client.delete(Cache::Webauthn, idx).await?;
     let reg_data = res.unwrap();
+    validate_rp_id(rp, &reg_data.rp_id)?;
 
     let reg_state = serde_json::from_str::<PasskeyRegistration>(&reg_data.reg_state)?;
-    match data
+    match rp
         .webauthn
         .finish_passkey_registration(&req.data, &reg_state)
     {
@@ -1034,6 +1478,7 @@ pub async fn reg_finish(
                     "User Presence only is not allowed - Verification is needed",
                 ));
             }
+            validate_attestation_policy(&user.id, &cred).await?;
 
             let user_id = user.id.clone();
             let create_user = if user.webauthn_user_id.is_none() {
@@ -1069,3 +1514,157 @@ pub async fn reg_finish(
 
     Ok(())
 }
+
+/// Creates a `PasskeyHybridLogin` event for a cross-device passkey login, or rejects it with a
+/// `PasskeyHybridDenied` event, if the client does not allow hybrid transport.
+pub async fn validate_hybrid_login(
+    client_id: String,
+    user_id: String,
+    ip: String,
+    tx_events: &flume::Sender<Event>,
+) -> Result<(), ErrorResponse> {
+    let client = Client::find_maybe_ephemeral(client_id).await?;
+    let text = format!("User {} via client {}", user_id, client.id);
+
+    if let Err(err) = client.validate_passkey_hybrid(true) {
+        Event::passkey_hybrid_denied(text, ip)
+            .with_user_id(user_id)
+            .send(tx_events)
+            .await?;
+        return Err(err);
+    }
+
+    Event::passkey_hybrid_login(text, ip)
+        .with_user_id(user_id)
+        .send(tx_events)
+        .await
+}
+
+/// The transport hints as they are named in the WebAuthn spec, like `usb` or `hybrid`
+fn transport_names(transports: Option<&[AuthenticatorTransport]>) -> Vec<String> {
+    transports
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|t| match serde_json::to_value(t) {
+            Ok(serde_json::Value::String(s)) => Some(s),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A cross-platform attachment alone is true for USB / NFC security keys as well. Hybrid
+/// transport is assumed, if the credential is either known for it, or it is a synced passkey,
+/// which can only be used cross-platform via hybrid.
+fn is_hybrid_auth(
+    authenticator_attachment: Option<&str>,
+    transports: Option<&[AuthenticatorTransport]>,
+    backup_eligible: bool,
+) -> bool {
+    if authenticator_attachment != Some("cross-platform") {
+        return false;
+    }
+    backup_eligible
+        || transports
+            .unwrap_or_default()
+            .iter()
+            .any(|t| matches!(t, AuthenticatorTransport::Hybrid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_hybrid_auth() {
+        let hybrid = [
+            AuthenticatorTransport::Hybrid,
+            AuthenticatorTransport::Internal,
+        ];
+        let usb = [AuthenticatorTransport::Usb, AuthenticatorTransport::Nfc];
+
+        // no or platform attachment
+        assert!(!is_hybrid_auth(None, Some(&hybrid), true));
+        assert!(!is_hybrid_auth(Some("platform"), Some(&hybrid), true));
+
+        // security keys
+        assert!(!is_hybrid_auth(Some("cross-platform"), Some(&usb), false));
+        assert!(!is_hybrid_auth(Some("cross-platform"), None, false));
+
+        // phones
+        assert!(is_hybrid_auth(Some("cross-platform"), Some(&hybrid), false));
+        assert!(is_hybrid_auth(Some("cross-platform"), None, true));
+    }
+
+    #[test]
+    fn test_transport_names() {
+        assert!(transport_names(None).is_empty());
+        assert_eq!(
+            transport_names(Some(&[
+                AuthenticatorTransport::Usb,
+                AuthenticatorTransport::Nfc,
+                AuthenticatorTransport::Internal,
+                AuthenticatorTransport::Hybrid,
+            ])),
+            vec!["usb", "nfc", "internal", "hybrid"]
+        );
+    }
+
+    #[test]
+    fn test_service_req_validate() {
+        let req = WebauthnServiceReq::new("user123".to_string(), MfaPurpose::ApiKeyExchange);
+        assert_eq!(req.code.len(), 48);
+
+        assert!(req.validate("user123", MfaPurpose::ApiKeyExchange).is_ok());
+
+        let err = req
+            .validate("user456", MfaPurpose::ApiKeyExchange)
+            .unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Forbidden);
+
+        let err = req
+            .validate("user123", MfaPurpose::PasswordNew)
+            .unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Forbidden);
+        let err = req
+            .validate("user123", MfaPurpose::PasswordReset)
+            .unwrap_err();
+        assert_eq!(err.error, ErrorResponseType::Forbidden);
+    }
+
+    #[test]
+    fn test_is_aaguid_allowed() {
+        let yubikey = "cb69481e-8ff7-4039-93ec-0a2729a154a8".to_string();
+        let other = "ee882879-721c-4913-9775-3dfcce97072a".to_string();
+
+        // no policy
+        assert!(is_aaguid_allowed(None, &[], &[]));
+        assert!(is_aaguid_allowed(Some(&yubikey), &[], &[]));
+
+        // allow list
+        let allow = vec![yubikey.clone()];
+        assert!(is_aaguid_allowed(Some(&yubikey), &allow, &[]));
+        assert!(!is_aaguid_allowed(Some(&other), &allow, &[]));
+        assert!(!is_aaguid_allowed(None, &allow, &[]));
+
+        // deny list
+        let deny = vec![other.clone()];
+        assert!(is_aaguid_allowed(Some(&yubikey), &[], &deny));
+        assert!(is_aaguid_allowed(None, &[], &deny));
+        assert!(!is_aaguid_allowed(Some(&other), &[], &deny));
+
+        // deny takes precedence
+        let allow = vec![yubikey.clone(), other.clone()];
+        assert!(!is_aaguid_allowed(Some(&other), &allow, &deny));
+    }
+
+    #[test]
+    fn test_reg_without_rp_id() {
+        let json = r#"{
+            "user_id": "user1",
+            "passkey_user_id": "cb69481e-8ff7-4039-93ec-0a2729a154a8",
+            "reg_state": "{}"
+        }"#;
+        let reg = serde_json::from_str::<WebauthnReg>(json).unwrap();
+        assert!(reg.rp_id.is_empty());
+    }
+}
@@ -1,8 +1,12 @@
 use crate::app_state::AppState;
 use crate::database::{Cache, DB};
 use crate::entity::scopes::Scope;
+use crate::JwtAcrValue;
 use actix_web::web;
-use rauthy_common::constants::{CACHE_TTL_APP, ENABLE_DYN_CLIENT_REG, GRANT_TYPE_DEVICE_CODE};
+use rauthy_common::constants::{
+    CACHE_TTL_APP, ENABLE_DYN_CLIENT_REG, GRANT_TYPE_DEVICE_CODE, MTLS_CLIENT_CERT_HEADER,
+    OPEN_USER_REG, RESPONSE_TYPES_SUPPORTED,
+};
 use rauthy_error::ErrorResponse;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -15,25 +19,47 @@ pub struct WellKnown {
     pub device_authorization_endpoint: String,
     pub token_endpoint: String,
     pub introspection_endpoint: String,
+    pub revocation_endpoint: String,
     pub userinfo_endpoint: String,
     pub end_session_endpoint: String,
+    pub check_session_iframe: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub registration_endpoint: Option<String>,
     pub jwks_uri: String,
     pub grant_types_supported: Vec<String>,
     pub response_types_supported: Vec<String>,
+    pub response_modes_supported: Vec<String>,
     pub subject_types_supported: Vec<String>,
     pub id_token_signing_alg_values_supported: Vec<String>,
+    pub id_token_encryption_alg_values_supported: Vec<String>,
+    pub id_token_encryption_enc_values_supported: Vec<String>,
+    pub userinfo_signing_alg_values_supported: Vec<String>,
+    pub userinfo_encryption_alg_values_supported: Vec<String>,
+    pub userinfo_encryption_enc_values_supported: Vec<String>,
     pub token_endpoint_auth_methods_supported: Vec<String>,
     pub token_endpoint_auth_signing_alg_values_supported: Vec<String>,
+    pub acr_values_supported: Vec<String>,
     pub claims_supported: Vec<String>,
     pub claim_types_supported: Vec<String>,
     pub scopes_supported: Vec<String>,
     pub code_challenge_methods_supported: Vec<String>,
     pub dpop_signing_alg_values_supported: Vec<String>,
+    pub authorization_signing_alg_values_supported: Vec<String>,
+    pub request_object_signing_alg_values_supported: Vec<String>,
+    pub request_object_encryption_alg_values_supported: Vec<String>,
+    pub request_object_encryption_enc_values_supported: Vec<String>,
+    pub request_parameter_supported: bool,
+    pub request_uri_parameter_supported: bool,
+    pub require_request_uri_registration: bool,
+    pub frontchannel_logout_supported: bool,
+    pub frontchannel_logout_session_supported: bool,
+    pub backchannel_logout_supported: bool,
+    pub backchannel_logout_session_supported: bool,
+    pub tls_client_certificate_bound_access_tokens: bool,
     pub service_documentation: String,
     pub ui_locales_supported: Vec<String>,
     pub claims_parameter_supported: bool,
+    pub prompt_values_supported: Vec<String>,
 }
 
 const IDX: &str = ".well-known";
@@ -83,10 +109,12 @@ impl WellKnown {
         let device_authorization_endpoint = format!("{}/oidc/device", issuer);
         let token_endpoint = format!("{}/oidc/token", issuer);
         let introspection_endpoint = format!("{}/oidc/introspect", issuer);
+        let revocation_endpoint = format!("{}/oidc/revoke", issuer);
         let userinfo_endpoint = format!("{}/oidc/userinfo", issuer);
         let registration_endpoint =
             ENABLE_DYN_CLIENT_REG.then_some(format!("{}/clients_dyn", issuer));
         let end_session_endpoint = format!("{}/oidc/logout", issuer);
+        let check_session_iframe = format!("{}/oidc/session/check", issuer);
         let jwks_uri = format!("{}/oidc/certs", issuer);
         let grant_types_supported = vec![
             "authorization_code".to_string(),
@@ -95,37 +123,66 @@ impl WellKnown {
             "refresh_token".to_string(),
             GRANT_TYPE_DEVICE_CODE.to_string(),
         ];
-        let response_types_supported = vec!["code".to_string()];
-        let subject_types_supported = vec!["public".to_string()];
+        let response_types_supported = RESPONSE_TYPES_SUPPORTED
+            .iter()
+            .map(|typ| typ.to_string())
+            .collect();
+        // the `jwt` modes are only allowed for clients with an `auth_response_alg`
+        let response_modes_supported = vec![
+            "query".to_string(),
+            "jwt".to_string(),
+            "query.jwt".to_string(),
+            "fragment.jwt".to_string(),
+        ];
+        let subject_types_supported = vec!["public".to_string(), "pairwise".to_string()];
         let id_token_signing_alg_values_supported = vec![
             "RS256".to_string(),
             "RS384".to_string(),
             "RS512".to_string(),
+            "ES384".to_string(),
             "EdDSA".to_string(),
         ];
-        let token_endpoint_auth_methods_supported = vec![
+        // the same algorithms are supported for ID tokens and userinfo responses
+        let encryption_alg_values_supported =
+            vec!["RSA-OAEP".to_string(), "RSA-OAEP-256".to_string()];
+        let encryption_enc_values_supported = vec!["A128GCM".to_string(), "A256GCM".to_string()];
+        let mut token_endpoint_auth_methods_supported = vec![
             "client_secret_post".to_string(),
             "client_secret_basic".to_string(),
+            "client_secret_jwt".to_string(),
+            "private_key_jwt".to_string(),
         ];
+        if MTLS_CLIENT_CERT_HEADER.is_some() {
+            token_endpoint_auth_methods_supported.push("tls_client_auth".to_string());
+            token_endpoint_auth_methods_supported.push("self_signed_tls_client_auth".to_string());
+        }
         let token_endpoint_auth_signing_alg_values_supported = vec![
             "RS256".to_string(),
             "RS384".to_string(),
             "RS512".to_string(),
+            "ES384".to_string(),
             "EdDSA".to_string(),
+            "HS256".to_string(),
+            "HS384".to_string(),
+            "HS512".to_string(),
         ];
         let claims_supported = vec![
             "iss".to_string(),
             "azp".to_string(),
             "amr".to_string(),
+            "acr".to_string(),
             "sub".to_string(),
+            "sid".to_string(),
             "preferred_username".to_string(),
             "email".to_string(),
             "email_verified".to_string(),
             "given_name".to_string(),
             "family_name".to_string(),
+            "picture".to_string(),
             "roles".to_string(),
             "groups".to_string(),
             "custom".to_string(),
+            "client_metadata".to_string(),
         ];
         let claim_types_supported = vec![
             "normal".to_string(),
@@ -138,11 +195,31 @@ impl WellKnown {
         // if *ENABLE_WEB_ID {
         //     claims_supported.push("webid".to_string());
         // }
+        // `create` needs the open user registration to be enabled
+        let mut prompt_values_supported = vec!["none".to_string(), "login".to_string()];
+        if *OPEN_USER_REG {
+            prompt_values_supported.push("create".to_string());
+        }
         let code_challenge_methods_supported = vec!["plain".to_string(), "S256".to_string()];
         let dpop_signing_alg_values_supported = vec![
             "RS256".to_string(),
             "RS384".to_string(),
             "RS512".to_string(),
+            "ES384".to_string(),
+            "EdDSA".to_string(),
+        ];
+        let authorization_signing_alg_values_supported = vec![
+            "RS256".to_string(),
+            "RS384".to_string(),
+            "RS512".to_string(),
+            "ES384".to_string(),
+            "EdDSA".to_string(),
+        ];
+        let request_object_signing_alg_values_supported = vec![
+            "RS256".to_string(),
+            "RS384".to_string(),
+            "RS512".to_string(),
+            "ES384".to_string(),
             "EdDSA".to_string(),
         ];
 
@@ -160,24 +237,50 @@ impl WellKnown {
             device_authorization_endpoint,
             token_endpoint,
             introspection_endpoint,
+            revocation_endpoint,
             userinfo_endpoint,
             end_session_endpoint,
+            check_session_iframe,
             registration_endpoint,
             jwks_uri,
             grant_types_supported,
             response_types_supported,
+            response_modes_supported,
             subject_types_supported,
+            userinfo_signing_alg_values_supported: id_token_signing_alg_values_supported.clone(),
             id_token_signing_alg_values_supported,
+            id_token_encryption_alg_values_supported: encryption_alg_values_supported.clone(),
+            id_token_encryption_enc_values_supported: encryption_enc_values_supported.clone(),
+            userinfo_encryption_alg_values_supported: encryption_alg_values_supported,
+            userinfo_encryption_enc_values_supported: encryption_enc_values_supported.clone(),
             token_endpoint_auth_methods_supported,
             token_endpoint_auth_signing_alg_values_supported,
+            acr_values_supported: JwtAcrValue::all()
+                .iter()
+                .map(|acr| acr.as_str().to_string())
+                .collect(),
             claims_supported,
             claim_types_supported,
             scopes_supported,
             code_challenge_methods_supported,
             dpop_signing_alg_values_supported,
+            authorization_signing_alg_values_supported,
+            request_object_signing_alg_values_supported,
+            // request objects are decrypted with a key derived from the client secret
+            request_object_encryption_alg_values_supported: vec!["dir".to_string()],
+            request_object_encryption_enc_values_supported: encryption_enc_values_supported,
+            request_parameter_supported: true,
+            request_uri_parameter_supported: true,
+            require_request_uri_registration: true,
+            frontchannel_logout_supported: true,
+            frontchannel_logout_session_supported: true,
+            backchannel_logout_supported: true,
+            backchannel_logout_session_supported: true,
+            tls_client_certificate_bound_access_tokens: MTLS_CLIENT_CERT_HEADER.is_some(),
             service_documentation,
             ui_locales_supported,
             claims_parameter_supported: true,
+            prompt_values_supported,
         }
     }
 }
@@ -0,0 +1,341 @@
+use crate::database::DB;
+use hiqlite::{params, Param};
+use rauthy_common::clock;
+use rauthy_common::constants::{
+    RAUTHY_VERSION, RE_YUBICO_OTP, YUBICO_OTP_CLIENT_ID, YUBICO_OTP_SECRET_KEY, YUBICO_OTP_URL,
+};
+use rauthy_common::is_hiqlite;
+use rauthy_common::utils::{base64_decode, base64_encode, constant_time_eq, get_rand};
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
+use reqwest::tls;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, FromRow};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// The length of the encrypted part of each OTP. Everything in front of it is the public ID,
+/// which identifies the key.
+const YUBICO_OTP_ENC_LEN: usize = 32;
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// A classic Yubico OTP key as an additional 2nd factor, for older keys without FIDO2 support.
+/// Only available, if `YUBICO_OTP_CLIENT_ID` has been configured.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserYubicoOtp {
+    pub user_id: String,
+    /// The ModHex encoded public ID of the registered key
+    pub public_id: String,
+    pub created: i64,
+    pub last_used: i64,
+}
+
+// CRUD
+impl UserYubicoOtp {
+    pub async fn delete(user_id: &str) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    "DELETE FROM user_yubico_otp WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?;
+        } else {
+            query!("DELETE FROM user_yubico_otp WHERE user_id = $1", user_id)
+                .execute(DB::conn())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn find(user_id: &str) -> Result<Option<Self>, ErrorResponse> {
+        let slf = if is_hiqlite() {
+            DB::client()
+                .query_as(
+                    "SELECT * FROM user_yubico_otp WHERE user_id = $1",
+                    params!(user_id),
+                )
+                .await?
+                .into_iter()
+                .next()
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM user_yubico_otp WHERE user_id = $1",
+                user_id
+            )
+            .fetch_optional(DB::conn())
+            .await?
+        };
+
+        Ok(slf)
+    }
+
+    async fn save(&self) -> Result<(), ErrorResponse> {
+        if is_hiqlite() {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_yubico_otp (user_id, public_id, created, last_used)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(user_id) DO UPDATE
+SET public_id = $2, created = $3, last_used = $4"#,
+                    params!(
+                        self.user_id.clone(),
+                        self.public_id.clone(),
+                        self.created,
+                        self.last_used
+                    ),
+                )
+                .await?;
+        } else {
+            query!(
+                r#"
+INSERT INTO user_yubico_otp (user_id, public_id, created, last_used)
+VALUES ($1, $2, $3, $4)
+ON CONFLICT(user_id) DO UPDATE
+SET public_id = $2, created = $3, last_used = $4"#,
+                self.user_id,
+                self.public_id,
+                self.created,
+                self.last_used,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UserYubicoOtp {
+    #[inline]
+    pub fn is_available() -> bool {
+        YUBICO_OTP_CLIENT_ID.is_some()
+    }
+
+    /// Registers the key, which generated the given OTP. The OTP must be valid, which proves
+    /// the possession of the key.
+    pub async fn enroll(user_id: String, otp: &str) -> Result<(), ErrorResponse> {
+        if !Self::is_available() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Yubico OTP is not enabled",
+            ));
+        }
+        if Self::find(&user_id).await?.is_some() {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Yubico OTP is already enabled for this user",
+            ));
+        }
+
+        let otp = otp.trim();
+        let public_id = Self::public_id(otp).ok_or_else(Self::err_invalid)?;
+        Self::verify_remote(otp).await?;
+
+        let now = clock::now().timestamp();
+        Self {
+            user_id,
+            public_id: public_id.to_string(),
+            created: now,
+            last_used: now,
+        }
+        .save()
+        .await
+    }
+
+    pub async fn is_enabled(user_id: &str) -> Result<bool, ErrorResponse> {
+        if !Self::is_available() {
+            return Ok(false);
+        }
+        Ok(Self::find(user_id).await?.is_some())
+    }
+
+    /// Validates an OTP against the registered key of the user.
+    pub async fn validate_for_user(user_id: &str, otp: &str) -> Result<(), ErrorResponse> {
+        let Some(mut slf) = Self::find(user_id).await? else {
+            return Err(Self::err_invalid());
+        };
+        let otp = otp.trim();
+        let public_id = Self::public_id(otp).ok_or_else(Self::err_invalid)?;
+        if !constant_time_eq(public_id.as_bytes(), slf.public_id.as_bytes()) {
+            return Err(Self::err_invalid());
+        }
+
+        // replays are rejected by the validation server
+        Self::verify_remote(otp).await?;
+        slf.last_used = clock::now().timestamp();
+        slf.save().await
+    }
+
+    /// Returns the public ID of the key, which generated the OTP.
+    fn public_id(otp: &str) -> Option<&str> {
+        if !RE_YUBICO_OTP.is_match(otp) {
+            return None;
+        }
+        let public_id = &otp[..otp.len() - YUBICO_OTP_ENC_LEN];
+        if public_id.is_empty() {
+            None
+        } else {
+            Some(public_id)
+        }
+    }
+
+    /// Validates the OTP against the `YUBICO_OTP_URL` with the Yubico validation protocol v2.0.
+    async fn verify_remote(otp: &str) -> Result<(), ErrorResponse> {
+        let Some(client_id) = YUBICO_OTP_CLIENT_ID.as_deref() else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "Yubico OTP is not enabled",
+            ));
+        };
+        let key = YUBICO_OTP_SECRET_KEY
+            .as_deref()
+            .map(base64_decode)
+            .transpose()?;
+        let nonce = get_rand(32);
+
+        let mut params = BTreeMap::from([
+            ("id", client_id.to_string()),
+            ("nonce", nonce.clone()),
+            ("otp", otp.to_string()),
+        ]);
+        if let Some(key) = &key {
+            let h = Self::sign(key, &params);
+            params.insert("h", h);
+        }
+
+        let body = Self::client()
+            .get(YUBICO_OTP_URL.as_str())
+            .query(&params)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(Self::err_connection)?
+            .text()
+            .await
+            .map_err(Self::err_connection)?;
+
+        let resp = Self::parse_response(&body);
+        if let Some(key) = &key {
+            let h = resp.get("h").map(String::as_str).unwrap_or_default();
+            let mut signed = resp.clone();
+            signed.remove("h");
+            if !constant_time_eq(h.as_bytes(), Self::sign(key, &signed).as_bytes()) {
+                error!("Invalid signature in the Yubico OTP validation response");
+                return Err(Self::err_connection("invalid response signature"));
+            }
+        }
+        if resp.get("otp").map(String::as_str) != Some(otp)
+            || resp.get("nonce").map(String::as_str) != Some(nonce.as_str())
+        {
+            error!("Yubico OTP validation response does not match the request");
+            return Err(Self::err_connection("response does not match the request"));
+        }
+
+        match resp.get("status").map(String::as_str) {
+            Some("OK") => Ok(()),
+            Some(status @ ("BAD_OTP" | "REPLAYED_OTP" | "REPLAYED_REQUEST")) => {
+                warn!("Yubico OTP rejected: {}", status);
+                Err(Self::err_invalid())
+            }
+            status => Err(Self::err_connection(status.unwrap_or("no status"))),
+        }
+    }
+
+    /// Signs the alphabetically sorted `key=value` pairs with HMAC-SHA1.
+    fn sign<K: AsRef<str>>(key: &[u8], params: &BTreeMap<K, String>) -> String {
+        let msg = params
+            .iter()
+            .map(|(k, v)| format!("{}={}", k.as_ref(), v))
+            .collect::<Vec<_>>()
+            .join("&");
+        let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, key);
+        base64_encode(hmac::sign(&key, msg.as_bytes()).as_ref())
+    }
+
+    /// The validation server responds with one `key=value` pair per line.
+    fn parse_response(body: &str) -> BTreeMap<String, String> {
+        body.lines()
+            .filter_map(|line| {
+                let (k, v) = line.trim().split_once('=')?;
+                Some((k.to_string(), v.to_string()))
+            })
+            .collect()
+    }
+
+    fn client() -> &'static reqwest::Client {
+        HTTP_CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!("Rauthy v{} Yubico OTP", RAUTHY_VERSION))
+                .min_tls_version(tls::Version::TLS_1_2)
+                .build()
+                .unwrap()
+        })
+    }
+
+    fn err_connection(err: impl std::fmt::Display) -> ErrorResponse {
+        ErrorResponse::new(
+            ErrorResponseType::Connection,
+            format!("Unable to validate the Yubico OTP: {}", err),
+        )
+    }
+
+    fn err_invalid() -> ErrorResponse {
+        ErrorResponse::new(ErrorResponseType::Unauthorized, "Invalid Yubico OTP")
+            .with_code(ErrorCode::InvalidCredentials)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_id() {
+        assert_eq!(
+            UserYubicoOtp::public_id("ccccccbcgujhingjrdejhgfnuetrgigvejhhgbkugded"),
+            Some("ccccccbcgujh")
+        );
+        // the encrypted part only
+        assert_eq!(
+            UserYubicoOtp::public_id("ingjrdejhgfnuetrgigvejhhgbkugded"),
+            None
+        );
+        // not ModHex
+        assert_eq!(
+            UserYubicoOtp::public_id("ccccccbcgujhingjrdejhgfnuetrgigvejhhgbkugdea"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_and_sign() {
+        let body =
+            "h=abc=\r\nt=2024-01-01T00:00:00Z0123\r\notp=cccc\r\nnonce=xyz\r\nstatus=OK\r\n\r\n";
+        let resp = UserYubicoOtp::parse_response(body);
+        assert_eq!(resp.len(), 5);
+        assert_eq!(resp.get("h").unwrap(), "abc=");
+        assert_eq!(resp.get("status").unwrap(), "OK");
+
+        let key = base64_decode("mG5be6ZJU1qBGz24yPh/ESM3UdU=").unwrap();
+        let params = BTreeMap::from([
+            ("id", "1".to_string()),
+            ("nonce", "jrFwbaYFhn0HoxZIsd9LQ6w2ceU".to_string()),
+            (
+                "otp",
+                "vvungrrdhvtklknvrtvuvbbkeidikkvgglrvdgrfcdft".to_string(),
+            ),
+        ]);
+        let h = UserYubicoOtp::sign(&key, &params);
+        assert_eq!(base64_decode(&h).unwrap().len(), 20);
+        assert_eq!(h, UserYubicoOtp::sign(&key, &params));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::events::event::{Event, EventType};
+use std::collections::HashMap;
+use std::env;
+
+/// Events are considered identical, if they have the same type and affect the same user.
+/// Events without a user are grouped by their IP instead.
+type AggregateKey = (i16, Option<String>, Option<String>);
+
+struct Aggregate {
+    window_end: i64,
+    suppressed: u32,
+    latest: Option<Event>,
+}
+
+/// Collapses identical events within a configurable window into a single event with a counter.
+///
+/// The first event for a key is always forwarded immediately. All identical events within the
+/// window after it will be suppressed and only the latest one will be emitted with the
+/// `aggregated_count` as soon as the window is over.
+pub struct EventAggregator {
+    window_millis: i64,
+    aggregates: HashMap<AggregateKey, Aggregate>,
+}
+
+impl EventAggregator {
+    pub fn from_env() -> Self {
+        let window_secs = env::var("EVENT_AGGREGATE_WINDOW_SECS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse::<u32>()
+            .expect("Cannot parse EVENT_AGGREGATE_WINDOW_SECS to u32");
+        Self::new(window_secs)
+    }
+
+    pub fn new(window_secs: u32) -> Self {
+        Self {
+            window_millis: window_secs as i64 * 1000,
+            aggregates: HashMap::default(),
+        }
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.window_millis > 0
+    }
+
+    /// Returns the event, if it should be handled right away.
+    pub fn check(&mut self, event: Event, now_millis: i64) -> Option<Event> {
+        if !self.is_enabled() || !Self::can_aggregate(&event.typ) {
+            return Some(event);
+        }
+
+        let key = if event.user_id.is_some() {
+            (event.typ.value(), event.user_id.clone(), None)
+        } else {
+            (event.typ.value(), None, event.ip.clone())
+        };
+        match self.aggregates.get_mut(&key) {
+            Some(agg) if agg.window_end > now_millis => {
+                agg.suppressed += 1;
+                agg.latest = Some(event);
+                None
+            }
+            _ => {
+                self.aggregates.insert(
+                    key,
+                    Aggregate {
+                        window_end: now_millis + self.window_millis,
+                        suppressed: 0,
+                        latest: None,
+                    },
+                );
+                Some(event)
+            }
+        }
+    }
+
+    /// Removes all expired windows and returns a summary event for each of them, which had
+    /// suppressed events. The summary gets the current timestamp, because it is emitted after
+    /// all events, which have been forwarded in the meantime.
+    pub fn flush_expired(&mut self, now_millis: i64) -> Vec<Event> {
+        let mut res = Vec::new();
+
+        self.aggregates.retain(|_, agg| {
+            if agg.window_end > now_millis {
+                return true;
+            }
+
+            if let Some(mut event) = agg.latest.take() {
+                event.timestamp = now_millis;
+                event.aggregated_count = Some(agg.suppressed as i64);
+                res.push(event);
+            }
+            false
+        });
+
+        res
+    }
+
+    /// The IP blacklisting depends on these events being forwarded immediately.
+    #[inline]
+    fn can_aggregate(typ: &EventType) -> bool {
+        !matches!(
+            typ,
+            EventType::IpBlacklisted | EventType::IpBlacklistRemoved | EventType::Test
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::event::EventLevel;
+
+    fn event(typ: EventType, ip: &str, data: i64) -> Event {
+        Event::new(
+            EventLevel::Info,
+            typ,
+            Some(ip.to_string()),
+            Some(data),
+            None,
+        )
+    }
+
+    fn user_event(typ: EventType, ip: &str, user_id: &str) -> Event {
+        Event::new(EventLevel::Warning, typ, Some(ip.to_string()), None, None)
+            .with_user_id(user_id.to_string())
+    }
+
+    #[test]
+    fn test_event_aggregator() {
+        let mut agg = EventAggregator::new(60);
+        let ip = "192.168.1.1";
+
+        // the first one is always forwarded
+        assert!(agg
+            .check(event(EventType::InvalidLogins, ip, 1), 0)
+            .is_some());
+        // duplicates within the window are suppressed
+        assert!(agg
+            .check(event(EventType::InvalidLogins, ip, 2), 1000)
+            .is_none());
+        assert!(agg
+            .check(event(EventType::InvalidLogins, ip, 3), 2000)
+            .is_none());
+        // other IPs are independent
+        assert!(agg
+            .check(event(EventType::InvalidLogins, "192.168.1.2", 1), 2000)
+            .is_some());
+        // the blacklisting is never aggregated
+        assert!(agg
+            .check(event(EventType::IpBlacklisted, ip, 0), 0)
+            .is_some());
+        assert!(agg
+            .check(event(EventType::IpBlacklisted, ip, 0), 0)
+            .is_some());
+
+        assert!(agg.flush_expired(59_000).is_empty());
+        let flushed = agg.flush_expired(60_000);
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].data, Some(3));
+        assert_eq!(flushed[0].aggregated_count, Some(2));
+        assert_eq!(flushed[0].text, None);
+        // emitted after the events forwarded in the meantime
+        assert_eq!(flushed[0].timestamp, 60_000);
+
+        // a new window starts after the flush
+        assert!(agg
+            .check(event(EventType::InvalidLogins, ip, 4), 61_000)
+            .is_some());
+    }
+
+    #[test]
+    fn test_event_aggregator_per_user() {
+        let mut agg = EventAggregator::new(60);
+
+        assert!(agg
+            .check(user_event(EventType::MfaFailed, "192.168.1.1", "user1"), 0)
+            .is_some());
+        // the same user from another IP is still a duplicate
+        assert!(agg
+            .check(
+                user_event(EventType::MfaFailed, "192.168.1.2", "user1"),
+                1000
+            )
+            .is_none());
+        // but another user from the same IP is not
+        assert!(agg
+            .check(
+                user_event(EventType::MfaFailed, "192.168.1.1", "user2"),
+                1000
+            )
+            .is_some());
+        // and neither is another type for the same user
+        assert!(agg
+            .check(
+                user_event(EventType::MfaRemoved, "192.168.1.1", "user1"),
+                1000
+            )
+            .is_some());
+
+        let flushed = agg.flush_expired(61_000);
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].user_id.as_deref(), Some("user1"));
+        assert_eq!(flushed[0].ip.as_deref(), Some("192.168.1.2"));
+        assert_eq!(flushed[0].aggregated_count, Some(1));
+        assert_eq!(flushed[0].timestamp, 61_000);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::entity::continuation_token::ContinuationToken;
+use crate::events::event::Event;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use s3_simple::{Bucket, BucketOptions, Credentials, Region};
+use std::env;
+use std::io::Write;
+use tracing::{debug, info};
+
+const ARCHIVE_BATCH_SIZE: i64 = 10_000;
+
+/// Archives events to an S3-compatible bucket before they are deleted by the retention job.
+///
+/// Each batch is written as a gzip compressed NDJSON object. The object name is built from the
+/// first and last event inside the batch, which makes retries after a failed upload idempotent.
+pub struct EventArchive {
+    bucket: Bucket,
+    prefix: String,
+}
+
+impl EventArchive {
+    pub fn from_env() -> Option<Self> {
+        let url = env::var("EVENT_ARCHIVE_S3_URL").ok()?;
+        let url = url.trim();
+        if url.is_empty() {
+            return None;
+        }
+
+        let bucket = env::var("EVENT_ARCHIVE_S3_BUCKET")
+            .expect("EVENT_ARCHIVE_S3_BUCKET is missing while EVENT_ARCHIVE_S3_URL is set");
+        let region = env::var("EVENT_ARCHIVE_S3_REGION")
+            .expect("EVENT_ARCHIVE_S3_REGION is missing while EVENT_ARCHIVE_S3_URL is set");
+        let path_style = env::var("EVENT_ARCHIVE_S3_PATH_STYLE")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse::<bool>()
+            .expect("Cannot parse EVENT_ARCHIVE_S3_PATH_STYLE to bool");
+        let key = env::var("EVENT_ARCHIVE_S3_KEY")
+            .expect("EVENT_ARCHIVE_S3_KEY is missing while EVENT_ARCHIVE_S3_URL is set");
+        let secret = env::var("EVENT_ARCHIVE_S3_SECRET")
+            .expect("EVENT_ARCHIVE_S3_SECRET is missing while EVENT_ARCHIVE_S3_URL is set");
+        let prefix = env::var("EVENT_ARCHIVE_S3_PREFIX")
+            .unwrap_or_else(|_| "rauthy/events/".to_string())
+            .trim()
+            .to_string();
+
+        let url = url.parse().expect("Cannot parse EVENT_ARCHIVE_S3_URL");
+        let bucket = Bucket::new(
+            url,
+            bucket,
+            Region(region),
+            Credentials::new(key, secret),
+            Some(BucketOptions {
+                path_style,
+                list_objects_v2: true,
+            }),
+        )
+        .expect("Cannot build the S3 bucket for the event archive");
+
+        info!("Events will be archived to S3 before being cleaned up");
+        Some(Self { bucket, prefix })
+    }
+
+    /// Uploads all events older than `before` (unix millis). Only if this succeeds, the events
+    /// should be deleted afterward.
+    pub async fn archive_before(&self, before: i64) -> Result<usize, ErrorResponse> {
+        let mut archived = 0;
+        let mut after = None;
+
+        loop {
+            let events = Event::find_before_paginated(before, after, ARCHIVE_BATCH_SIZE).await?;
+            let (Some(first), Some(last)) = (events.first(), events.last()) else {
+                break;
+            };
+
+            let object = format!(
+                "{}events_{}_{}_{}.ndjson.gz",
+                self.prefix, first.timestamp, last.timestamp, last.id
+            );
+            let body = Self::compress(&events)?;
+            self.bucket.put(&object, &body).await.map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::Connection,
+                    format!("Uploading events archive {} to S3: {}", object, err),
+                )
+            })?;
+            debug!("Archived {} events to {}", events.len(), object);
+
+            archived += events.len();
+            if (events.len() as i64) < ARCHIVE_BATCH_SIZE {
+                break;
+            }
+            after = Some(ContinuationToken::new(last.id.clone(), last.timestamp));
+        }
+
+        Ok(archived)
+    }
+
+    fn compress(events: &[Event]) -> Result<Vec<u8>, ErrorResponse> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        for event in events {
+            encoder.write_all(event.as_json().as_bytes())?;
+            encoder.write_all(b"\n")?;
+        }
+        Ok(encoder.finish()?)
+    }
+}
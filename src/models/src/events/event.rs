@@ -1,18 +1,25 @@
 use crate::database::DB;
+use crate::entity::continuation_token::ContinuationToken;
 use crate::events::{
+    EVENT_LEVEL_CLIENT_CREATED, EVENT_LEVEL_CLIENT_DELETED, EVENT_LEVEL_CLIENT_SECRET_ROTATED,
     EVENT_LEVEL_FAILED_LOGIN, EVENT_LEVEL_FAILED_LOGINS_10, EVENT_LEVEL_FAILED_LOGINS_15,
     EVENT_LEVEL_FAILED_LOGINS_20, EVENT_LEVEL_FAILED_LOGINS_25, EVENT_LEVEL_FAILED_LOGINS_7,
-    EVENT_LEVEL_IP_BLACKLISTED, EVENT_LEVEL_JWKS_ROTATE, EVENT_LEVEL_NEW_RAUTHY_ADMIN,
-    EVENT_LEVEL_NEW_RAUTHY_VERSION, EVENT_LEVEL_NEW_USER, EVENT_LEVEL_RAUTHY_HEALTHY,
-    EVENT_LEVEL_RAUTHY_START, EVENT_LEVEL_RAUTHY_UNHEALTHY, EVENT_LEVEL_SECRETS_MIGRATED,
-    EVENT_LEVEL_USER_EMAIL_CHANGE, EVENT_LEVEL_USER_PASSWORD_RESET,
+    EVENT_LEVEL_IP_BLACKLISTED, EVENT_LEVEL_IP_BLACKLIST_REMOVED, EVENT_LEVEL_JWKS_ROTATE,
+    EVENT_LEVEL_MFA_ENROLLED, EVENT_LEVEL_MFA_FAILED, EVENT_LEVEL_MFA_REMOVED,
+    EVENT_LEVEL_NEW_RAUTHY_ADMIN, EVENT_LEVEL_NEW_RAUTHY_VERSION, EVENT_LEVEL_NEW_USER,
+    EVENT_LEVEL_POSSIBLE_BRUTE_FORCE, EVENT_LEVEL_RAUTHY_HEALTHY, EVENT_LEVEL_RAUTHY_START,
+    EVENT_LEVEL_RAUTHY_UNHEALTHY, EVENT_LEVEL_SECRETS_MIGRATED, EVENT_LEVEL_SESSION_LIMIT,
+    EVENT_LEVEL_SESSION_REVOKED, EVENT_LEVEL_USER_EMAIL_CHANGE, EVENT_LEVEL_USER_EXPIRED,
+    EVENT_LEVEL_USER_LOCKED, EVENT_LEVEL_USER_PASSWORD_RESET, EVENT_LEVEL_USER_UNLOCKED,
 };
 use chrono::{DateTime, Timelike, Utc};
 use hiqlite::{params, Param, Row};
+use rauthy_api_types::users::UserActivityResponse;
+use rauthy_common::clock;
 use rauthy_common::constants::EMAIL_SUB_PREFIX;
 use rauthy_common::is_hiqlite;
 use rauthy_common::utils::{get_local_hostname, get_rand};
-use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_error::{request_id, ErrorResponse, ErrorResponseType};
 use rauthy_notify::{Notification, NotificationLevel};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgRow;
@@ -153,6 +160,22 @@ pub enum EventType {
     UserEmailChange,
     UserPasswordReset,
     Test,
+    MfaFailed,
+    MfaEnrolled,
+    MfaRemoved,
+    ClientCreated,
+    ClientDeleted,
+    ClientSecretRotated,
+    SessionRevoked,
+    UserImpersonated,
+    UserExpired,
+    UserLocked,
+    UserUnlocked,
+    RefreshTokenReused,
+    SessionLimitReached,
+    SessionAnomaly,
+    PasskeyHybridLogin,
+    PasskeyHybridDenied,
 }
 
 impl Default for EventType {
@@ -180,6 +203,22 @@ impl Display for EventType {
             EventType::UserEmailChange => write!(f, "User's E-Mail has been changed"),
             EventType::UserPasswordReset => write!(f, "User has reset its password"),
             EventType::Test => write!(f, "TEST"),
+            EventType::MfaFailed => write!(f, "MFA failed"),
+            EventType::MfaEnrolled => write!(f, "MFA device enrolled"),
+            EventType::MfaRemoved => write!(f, "MFA device removed"),
+            EventType::ClientCreated => write!(f, "Client created"),
+            EventType::ClientDeleted => write!(f, "Client deleted"),
+            EventType::ClientSecretRotated => write!(f, "Client secret rotated"),
+            EventType::SessionRevoked => write!(f, "Session revoked"),
+            EventType::UserImpersonated => write!(f, "User impersonated"),
+            EventType::UserExpired => write!(f, "User expired"),
+            EventType::UserLocked => write!(f, "User locked"),
+            EventType::UserUnlocked => write!(f, "User unlocked"),
+            EventType::RefreshTokenReused => write!(f, "Refresh token reused"),
+            EventType::SessionLimitReached => write!(f, "Session limit reached"),
+            EventType::SessionAnomaly => write!(f, "Session anomaly"),
+            EventType::PasskeyHybridLogin => write!(f, "Cross-device passkey login"),
+            EventType::PasskeyHybridDenied => write!(f, "Cross-device passkey login denied"),
         }
     }
 }
@@ -202,6 +241,60 @@ impl From<rauthy_api_types::events::EventType> for EventType {
             rauthy_api_types::events::EventType::UserEmailChange => Self::UserEmailChange,
             rauthy_api_types::events::EventType::UserPasswordReset => Self::UserPasswordReset,
             rauthy_api_types::events::EventType::Test => Self::Test,
+            rauthy_api_types::events::EventType::MfaFailed => Self::MfaFailed,
+            rauthy_api_types::events::EventType::MfaEnrolled => Self::MfaEnrolled,
+            rauthy_api_types::events::EventType::MfaRemoved => Self::MfaRemoved,
+            rauthy_api_types::events::EventType::ClientCreated => Self::ClientCreated,
+            rauthy_api_types::events::EventType::ClientDeleted => Self::ClientDeleted,
+            rauthy_api_types::events::EventType::ClientSecretRotated => Self::ClientSecretRotated,
+            rauthy_api_types::events::EventType::SessionRevoked => Self::SessionRevoked,
+            rauthy_api_types::events::EventType::UserImpersonated => Self::UserImpersonated,
+            rauthy_api_types::events::EventType::UserExpired => Self::UserExpired,
+            rauthy_api_types::events::EventType::UserLocked => Self::UserLocked,
+            rauthy_api_types::events::EventType::UserUnlocked => Self::UserUnlocked,
+            rauthy_api_types::events::EventType::RefreshTokenReused => Self::RefreshTokenReused,
+            rauthy_api_types::events::EventType::SessionLimitReached => Self::SessionLimitReached,
+            rauthy_api_types::events::EventType::SessionAnomaly => Self::SessionAnomaly,
+            rauthy_api_types::events::EventType::PasskeyHybridLogin => Self::PasskeyHybridLogin,
+            rauthy_api_types::events::EventType::PasskeyHybridDenied => Self::PasskeyHybridDenied,
+        }
+    }
+}
+
+impl From<EventType> for rauthy_api_types::events::EventType {
+    fn from(value: EventType) -> Self {
+        match value {
+            EventType::InvalidLogins => Self::InvalidLogins,
+            EventType::IpBlacklisted => Self::IpBlacklisted,
+            EventType::IpBlacklistRemoved => Self::IpBlacklistRemoved,
+            EventType::JwksRotated => Self::JwksRotated,
+            EventType::NewUserRegistered => Self::NewUserRegistered,
+            EventType::NewRauthyAdmin => Self::NewRauthyAdmin,
+            EventType::NewRauthyVersion => Self::NewRauthyVersion,
+            EventType::PossibleBruteForce => Self::PossibleBruteForce,
+            EventType::RauthyStarted => Self::RauthyStarted,
+            EventType::RauthyHealthy => Self::RauthyHealthy,
+            EventType::RauthyUnhealthy => Self::RauthyUnhealthy,
+            EventType::SecretsMigrated => Self::SecretsMigrated,
+            EventType::UserEmailChange => Self::UserEmailChange,
+            EventType::UserPasswordReset => Self::UserPasswordReset,
+            EventType::Test => Self::Test,
+            EventType::MfaFailed => Self::MfaFailed,
+            EventType::MfaEnrolled => Self::MfaEnrolled,
+            EventType::MfaRemoved => Self::MfaRemoved,
+            EventType::ClientCreated => Self::ClientCreated,
+            EventType::ClientDeleted => Self::ClientDeleted,
+            EventType::ClientSecretRotated => Self::ClientSecretRotated,
+            EventType::SessionRevoked => Self::SessionRevoked,
+            EventType::UserImpersonated => Self::UserImpersonated,
+            EventType::UserExpired => Self::UserExpired,
+            EventType::UserLocked => Self::UserLocked,
+            EventType::UserUnlocked => Self::UserUnlocked,
+            EventType::RefreshTokenReused => Self::RefreshTokenReused,
+            EventType::SessionLimitReached => Self::SessionLimitReached,
+            EventType::SessionAnomaly => Self::SessionAnomaly,
+            EventType::PasskeyHybridLogin => Self::PasskeyHybridLogin,
+            EventType::PasskeyHybridDenied => Self::PasskeyHybridDenied,
         }
     }
 }
@@ -224,6 +317,22 @@ impl EventType {
             Self::UserEmailChange => "UserEmailChange",
             Self::UserPasswordReset => "UserPasswordReset",
             Self::Test => "TEST",
+            Self::MfaFailed => "MfaFailed",
+            Self::MfaEnrolled => "MfaEnrolled",
+            Self::MfaRemoved => "MfaRemoved",
+            Self::ClientCreated => "ClientCreated",
+            Self::ClientDeleted => "ClientDeleted",
+            Self::ClientSecretRotated => "ClientSecretRotated",
+            Self::SessionRevoked => "SessionRevoked",
+            Self::UserImpersonated => "UserImpersonated",
+            Self::UserExpired => "UserExpired",
+            Self::UserLocked => "UserLocked",
+            Self::UserUnlocked => "UserUnlocked",
+            Self::RefreshTokenReused => "RefreshTokenReused",
+            Self::SessionLimitReached => "SessionLimitReached",
+            Self::SessionAnomaly => "SessionAnomaly",
+            Self::PasskeyHybridLogin => "PasskeyHybridLogin",
+            Self::PasskeyHybridDenied => "PasskeyHybridDenied",
         }
     }
 
@@ -244,6 +353,22 @@ impl EventType {
             EventType::UserEmailChange => 12,
             EventType::UserPasswordReset => 13,
             EventType::Test => 14,
+            EventType::MfaFailed => 15,
+            EventType::MfaEnrolled => 16,
+            EventType::MfaRemoved => 17,
+            EventType::ClientCreated => 18,
+            EventType::ClientDeleted => 19,
+            EventType::ClientSecretRotated => 20,
+            EventType::SessionRevoked => 21,
+            EventType::UserImpersonated => 22,
+            EventType::UserExpired => 23,
+            EventType::UserLocked => 24,
+            EventType::UserUnlocked => 25,
+            EventType::RefreshTokenReused => 26,
+            EventType::SessionLimitReached => 27,
+            EventType::SessionAnomaly => 28,
+            EventType::PasskeyHybridLogin => 29,
+            EventType::PasskeyHybridDenied => 30,
         }
     }
 }
@@ -266,6 +391,22 @@ impl From<String> for EventType {
             "UserEmailChange" => Self::UserEmailChange,
             "UserPasswordReset" => Self::UserPasswordReset,
             "TEST" => Self::Test,
+            "MfaFailed" => Self::MfaFailed,
+            "MfaEnrolled" => Self::MfaEnrolled,
+            "MfaRemoved" => Self::MfaRemoved,
+            "ClientCreated" => Self::ClientCreated,
+            "ClientDeleted" => Self::ClientDeleted,
+            "ClientSecretRotated" => Self::ClientSecretRotated,
+            "SessionRevoked" => Self::SessionRevoked,
+            "UserImpersonated" => Self::UserImpersonated,
+            "UserExpired" => Self::UserExpired,
+            "UserLocked" => Self::UserLocked,
+            "UserUnlocked" => Self::UserUnlocked,
+            "RefreshTokenReused" => Self::RefreshTokenReused,
+            "SessionLimitReached" => Self::SessionLimitReached,
+            "SessionAnomaly" => Self::SessionAnomaly,
+            "PasskeyHybridLogin" => Self::PasskeyHybridLogin,
+            "PasskeyHybridDenied" => Self::PasskeyHybridDenied,
             // just return test to never panic
             _ => Self::Test,
         }
@@ -296,6 +437,22 @@ impl From<i64> for EventType {
             12 => EventType::UserEmailChange,
             13 => EventType::UserPasswordReset,
             14 => EventType::Test,
+            15 => EventType::MfaFailed,
+            16 => EventType::MfaEnrolled,
+            17 => EventType::MfaRemoved,
+            18 => EventType::ClientCreated,
+            19 => EventType::ClientDeleted,
+            20 => EventType::ClientSecretRotated,
+            21 => EventType::SessionRevoked,
+            22 => EventType::UserImpersonated,
+            23 => EventType::UserExpired,
+            24 => EventType::UserLocked,
+            25 => EventType::UserUnlocked,
+            26 => EventType::RefreshTokenReused,
+            27 => EventType::SessionLimitReached,
+            28 => EventType::SessionAnomaly,
+            29 => EventType::PasskeyHybridLogin,
+            30 => EventType::PasskeyHybridDenied,
             _ => EventType::Test,
         }
     }
@@ -310,6 +467,15 @@ pub struct Event {
     pub ip: Option<String>,
     pub data: Option<i64>,
     pub text: Option<String>,
+    /// The `user_id` of the affected user, if any. Makes it possible to scope events by the
+    /// groups of this user for delegated admins.
+    pub user_id: Option<String>,
+    /// The id of the request, which generated this event, to correlate it with access logs
+    pub request_id: Option<String>,
+    /// The number of identical events, which have been collapsed into this one by the
+    /// `EVENT_AGGREGATE_WINDOW_SECS`
+    #[serde(default)]
+    pub aggregated_count: Option<i64>,
 }
 
 impl<'r> From<hiqlite::Row<'r>> for Event {
@@ -322,6 +488,9 @@ impl<'r> From<hiqlite::Row<'r>> for Event {
             ip: row.get("ip"),
             data: row.get("data"),
             text: row.get("text"),
+            user_id: row.get("user_id"),
+            request_id: row.get("request_id"),
+            aggregated_count: row.get("aggregated_count"),
         }
     }
 }
@@ -336,6 +505,9 @@ impl<'r> FromRow<'r, PgRow> for Event {
             ip: row.get("ip"),
             data: row.get("data"),
             text: row.get("text"),
+            user_id: row.try_get("user_id").unwrap_or_default(),
+            request_id: row.try_get("request_id").unwrap_or_default(),
+            aggregated_count: row.try_get("aggregated_count").unwrap_or_default(),
         })
     }
 }
@@ -350,10 +522,25 @@ impl<'r> FromRow<'r, SqliteRow> for Event {
             ip: row.get("ip"),
             data: row.get("data"),
             text: row.get("text"),
+            user_id: row.try_get("user_id").unwrap_or_default(),
+            request_id: row.try_get("request_id").unwrap_or_default(),
+            aggregated_count: row.try_get("aggregated_count").unwrap_or_default(),
         })
     }
 }
 
+impl From<Event> for UserActivityResponse {
+    fn from(value: Event) -> Self {
+        Self {
+            id: value.id,
+            timestamp: value.timestamp,
+            typ: value.typ.into(),
+            ip: value.ip,
+            text: value.text,
+        }
+    }
+}
+
 impl From<&Event> for Notification {
     fn from(value: &Event) -> Self {
         let icon = match value.level {
@@ -365,7 +552,15 @@ impl From<&Event> for Notification {
         let head = format!("{} {} - {}", icon, *EMAIL_SUB_PREFIX, value.level.as_str());
 
         let d = DateTime::from_timestamp(value.timestamp / 1000, 0).unwrap_or_default();
-        let row_1 = format!("{} {}", d.format("%Y/%m/%d %H:%M:%S"), value.typ);
+        let row_1 = match value.aggregated_count {
+            Some(count) => format!(
+                "{} {} (+{} similar)",
+                d.format("%Y/%m/%d %H:%M:%S"),
+                value.typ,
+                count
+            ),
+            None => format!("{} {}", d.format("%Y/%m/%d %H:%M:%S"), value.typ),
+        };
 
         let row_2 = match value.typ {
             EventType::InvalidLogins => Some(format!(
@@ -409,6 +604,53 @@ impl From<&Event> for Notification {
             EventType::UserEmailChange => value.text.clone(),
             EventType::UserPasswordReset => value.text.clone(),
             EventType::Test => value.text.clone(),
+            EventType::MfaFailed => Some(format!(
+                "MFA failed for `{}` from IP: `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::MfaEnrolled | EventType::MfaRemoved => Some(format!(
+                "User `{}` from IP: `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::ClientCreated
+            | EventType::ClientDeleted
+            | EventType::ClientSecretRotated => Some(format!(
+                "Client `{}` from IP: `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::SessionRevoked
+            | EventType::UserImpersonated
+            | EventType::RefreshTokenReused
+            | EventType::SessionLimitReached
+            | EventType::SessionAnomaly
+            | EventType::PasskeyHybridLogin
+            | EventType::PasskeyHybridDenied => Some(format!(
+                "{} from IP: `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
+            EventType::UserExpired => Some(format!(
+                "User `{}` has expired",
+                value.text.as_deref().unwrap_or_default(),
+            )),
+            EventType::UserLocked => {
+                let d =
+                    DateTime::from_timestamp(value.data.unwrap_or_default(), 0).unwrap_or_default();
+                Some(format!(
+                    "User `{}` locked until {} after failed logins from IP: `{}`",
+                    value.text.as_deref().unwrap_or_default(),
+                    d.format("%Y/%m/%d %H:%M:%S"),
+                    value.ip.as_deref().unwrap_or_default()
+                ))
+            }
+            EventType::UserUnlocked => Some(format!(
+                "User `{}` unlocked from IP: `{}`",
+                value.text.as_deref().unwrap_or_default(),
+                value.ip.as_deref().unwrap_or_default()
+            )),
         };
 
         Self {
@@ -447,8 +689,9 @@ impl Event {
             DB::client()
                 .execute(
                     r#"
-INSERT INTO events (id, timestamp, level, typ, ip, data, text)
-VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+INSERT INTO events
+(id, timestamp, level, typ, ip, data, text, user_id, request_id, aggregated_count)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
                     params!(
                         &self.id,
                         self.timestamp,
@@ -456,15 +699,19 @@ VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
                         typ,
                         &self.ip,
                         self.data,
-                        &self.text
+                        &self.text,
+                        &self.user_id,
+                        &self.request_id,
+                        self.aggregated_count
                     ),
                 )
                 .await?;
         } else {
             query!(
                 r#"
-INSERT INTO events (id, timestamp, level, typ, ip, data, text)
-VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+INSERT INTO events
+(id, timestamp, level, typ, ip, data, text, user_id, request_id, aggregated_count)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
                 self.id,
                 self.timestamp,
                 level,
@@ -472,6 +719,9 @@ VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
                 self.ip,
                 self.data,
                 self.text,
+                self.user_id,
+                self.request_id,
+                self.aggregated_count,
             )
             .execute(DB::conn())
             .await?;
@@ -485,8 +735,10 @@ VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
         mut until: i64,
         level: EventLevel,
         typ: Option<EventType>,
+        group: Option<&str>,
     ) -> Result<Vec<Self>, ErrorResponse> {
         let level = level.value();
+        let group = group.map(String::from);
 
         // Events are special inside Rauthy -> they use ms precision.
         // To keep the API internally the same, we expect timestamps in seconds though.
@@ -501,8 +753,9 @@ VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
                         r#"
 SELECT * FROM events
 WHERE timestamp >= $1 AND timestamp <= $2 AND level >= $3 AND typ = $4
+    AND ($5 IS NULL OR user_id IN (SELECT id FROM users WHERE instr(',' || groups || ',', ',' || $5 || ',') > 0))
 ORDER BY timestamp DESC"#,
-                        params!(from, until, level, typ),
+                        params!(from, until, level, typ, group),
                     )
                     .await?
             } else {
@@ -511,11 +764,13 @@ ORDER BY timestamp DESC"#,
                     r#"
 SELECT * FROM events
 WHERE timestamp >= $1 AND timestamp <= $2 AND level >= $3 AND typ = $4
+    AND ($5::varchar IS NULL OR user_id IN (SELECT id FROM users WHERE $5 = ANY(string_to_array(groups, ','))))
 ORDER BY timestamp DESC"#,
                     from,
                     until,
                     level,
                     typ,
+                    group,
                 )
                 .fetch_all(DB::conn())
                 .await?
@@ -526,8 +781,9 @@ ORDER BY timestamp DESC"#,
                     r#"
 SELECT * FROM events
 WHERE timestamp >= $1 AND timestamp <= $2 AND level >= $3
+    AND ($4 IS NULL OR user_id IN (SELECT id FROM users WHERE instr(',' || groups || ',', ',' || $4 || ',') > 0))
 ORDER BY timestamp DESC"#,
-                    params!(from, until, level),
+                    params!(from, until, level, group),
                 )
                 .await?
         } else {
@@ -536,10 +792,221 @@ ORDER BY timestamp DESC"#,
                 r#"
 SELECT * FROM events
 WHERE timestamp >= $1 AND timestamp <= $2 AND level >= $3
+    AND ($4::varchar IS NULL OR user_id IN (SELECT id FROM users WHERE $4 = ANY(string_to_array(groups, ','))))
 ORDER BY timestamp DESC"#,
                 from,
                 until,
                 level,
+                group,
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Returns events in a stable ascending `(timestamp, id)` order, starting right after the
+    /// given `continuation_token`. This makes it possible to incrementally pull events without
+    /// re-fetching huge windows. The returned token points to the last event of this page and
+    /// will be `None`, if there are no more events inside the given range.
+    ///
+    /// If a `group` is given, only events about users, which are currently in this group, will
+    /// be returned.
+    pub async fn find_paginated(
+        from: i64,
+        until: i64,
+        level: EventLevel,
+        typ: Option<EventType>,
+        group: Option<&str>,
+        continuation_token: Option<ContinuationToken>,
+        limit: i64,
+    ) -> Result<(Vec<Self>, Option<ContinuationToken>), ErrorResponse> {
+        let level = level.value();
+        let group = group.map(String::from);
+        let until = until * 1000;
+        // An empty id will always sort before any real event id, which means we can use the
+        // same query for the first page, while still including events with `timestamp == from`.
+        let (ts, id) = match continuation_token {
+            None => (from * 1000, String::default()),
+            Some(token) => (token.ts, token.id),
+        };
+
+        let res: Vec<Self> = if let Some(typ) = typ {
+            let typ = typ.value();
+            if is_hiqlite() {
+                DB::client()
+                    .query_map(
+                        r#"
+SELECT * FROM events
+WHERE (timestamp > $1 OR (timestamp = $1 AND id > $2))
+    AND timestamp <= $3 AND level >= $4 AND typ = $5
+    AND ($7 IS NULL OR user_id IN (SELECT id FROM users WHERE instr(',' || groups || ',', ',' || $7 || ',') > 0))
+ORDER BY timestamp ASC, id ASC
+LIMIT $6"#,
+                        params!(ts, id, until, level, typ, limit, group),
+                    )
+                    .await?
+            } else {
+                query_as!(
+                    Self,
+                    r#"
+SELECT * FROM events
+WHERE (timestamp > $1 OR (timestamp = $1 AND id > $2))
+    AND timestamp <= $3 AND level >= $4 AND typ = $5
+    AND ($7::varchar IS NULL OR user_id IN (SELECT id FROM users WHERE $7 = ANY(string_to_array(groups, ','))))
+ORDER BY timestamp ASC, id ASC
+LIMIT $6"#,
+                    ts,
+                    id,
+                    until,
+                    level,
+                    typ,
+                    limit,
+                    group,
+                )
+                .fetch_all(DB::conn())
+                .await?
+            }
+        } else if is_hiqlite() {
+            DB::client()
+                .query_map(
+                    r#"
+SELECT * FROM events
+WHERE (timestamp > $1 OR (timestamp = $1 AND id > $2))
+    AND timestamp <= $3 AND level >= $4
+    AND ($6 IS NULL OR user_id IN (SELECT id FROM users WHERE instr(',' || groups || ',', ',' || $6 || ',') > 0))
+ORDER BY timestamp ASC, id ASC
+LIMIT $5"#,
+                    params!(ts, id, until, level, limit, group),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                r#"
+SELECT * FROM events
+WHERE (timestamp > $1 OR (timestamp = $1 AND id > $2))
+    AND timestamp <= $3 AND level >= $4
+    AND ($6::varchar IS NULL OR user_id IN (SELECT id FROM users WHERE $6 = ANY(string_to_array(groups, ','))))
+ORDER BY timestamp ASC, id ASC
+LIMIT $5"#,
+                ts,
+                id,
+                until,
+                level,
+                limit,
+                group,
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        // If we got less than requested, we know that there are no more events right now.
+        let token = if res.len() as i64 == limit {
+            res.last()
+                .map(|evt| ContinuationToken::new(evt.id.clone(), evt.timestamp))
+        } else {
+            None
+        };
+
+        Ok((res, token))
+    }
+
+    /// Returns events older than `before` (unix millis) in a stable ascending `(timestamp, id)`
+    /// order, starting right after the given position.
+    pub async fn find_before_paginated(
+        before: i64,
+        after: Option<ContinuationToken>,
+        limit: i64,
+    ) -> Result<Vec<Self>, ErrorResponse> {
+        let (ts, id) = match after {
+            None => (i64::MIN, String::default()),
+            Some(token) => (token.ts, token.id),
+        };
+
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_map(
+                    r#"
+SELECT * FROM events
+WHERE (timestamp > $1 OR (timestamp = $1 AND id > $2)) AND timestamp < $3
+ORDER BY timestamp ASC, id ASC
+LIMIT $4"#,
+                    params!(ts, id, before, limit),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                r#"
+SELECT * FROM events
+WHERE (timestamp > $1 OR (timestamp = $1 AND id > $2)) AND timestamp < $3
+ORDER BY timestamp ASC, id ASC
+LIMIT $4"#,
+                ts,
+                id,
+                before,
+                limit,
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Returns all events linked to the given user, newest first.
+    pub async fn find_for_user(user_id: &str) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_map(
+                    "SELECT * FROM events WHERE user_id = $1 ORDER BY timestamp DESC",
+                    params!(user_id),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                "SELECT * FROM events WHERE user_id = $1 ORDER BY timestamp DESC",
+                user_id,
+            )
+            .fetch_all(DB::conn())
+            .await?
+        };
+
+        Ok(res)
+    }
+
+    /// Returns the latest security relevant events for the given user, which are safe to be
+    /// shown to the user itself.
+    ///
+    /// The included types are `NewUserRegistered (4)`, `UserEmailChange (12)`,
+    /// `UserPasswordReset (13)`, `MfaFailed (15)`, `MfaEnrolled (16)`, `MfaRemoved (17)`,
+    /// `SessionRevoked (21)`, `UserImpersonated (22)`, `UserLocked (24)` and
+    /// `UserUnlocked (25)`.
+    pub async fn find_user_activity(user_id: &str, limit: i64) -> Result<Vec<Self>, ErrorResponse> {
+        let res = if is_hiqlite() {
+            DB::client()
+                .query_map(
+                    r#"
+SELECT * FROM events
+WHERE user_id = $1 AND typ IN (4, 12, 13, 15, 16, 17, 21, 22, 24, 25)
+ORDER BY timestamp DESC
+LIMIT $2"#,
+                    params!(user_id, limit),
+                )
+                .await?
+        } else {
+            query_as!(
+                Self,
+                r#"
+SELECT * FROM events
+WHERE user_id = $1 AND typ IN (4, 12, 13, 15, 16, 17, 21, 22, 24, 25)
+ORDER BY timestamp DESC
+LIMIT $2"#,
+                user_id,
+                limit,
             )
             .fetch_all(DB::conn())
             .await?
@@ -587,15 +1054,24 @@ impl Event {
 
         Self {
             id,
-            timestamp: Utc::now().timestamp_millis(),
+            timestamp: clock::now().timestamp_millis(),
             level,
             typ,
             ip,
             data,
             text,
+            user_id: None,
+            request_id: request_id::current(),
+            aggregated_count: None,
         }
     }
 
+    /// Links this event to the affected user.
+    pub fn with_user_id(mut self, user_id: String) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
     /// The EventLevel will change depending on the amount of invalid logins
     pub fn invalid_login(failed_logins: u32, ip: String) -> Self {
         let level = match failed_logins {
@@ -617,7 +1093,7 @@ impl Event {
 
     pub fn brute_force(ip: String) -> Self {
         Self::new(
-            EventLevel::Critical,
+            EVENT_LEVEL_POSSIBLE_BRUTE_FORCE.get().cloned().unwrap(),
             EventType::PossibleBruteForce,
             Some(ip),
             None,
@@ -625,6 +1101,36 @@ impl Event {
         )
     }
 
+    pub fn client_created(client_id: String, ip: Option<String>) -> Self {
+        Self::new(
+            EVENT_LEVEL_CLIENT_CREATED.get().cloned().unwrap(),
+            EventType::ClientCreated,
+            ip,
+            None,
+            Some(client_id),
+        )
+    }
+
+    pub fn client_deleted(client_id: String, ip: Option<String>) -> Self {
+        Self::new(
+            EVENT_LEVEL_CLIENT_DELETED.get().cloned().unwrap(),
+            EventType::ClientDeleted,
+            ip,
+            None,
+            Some(client_id),
+        )
+    }
+
+    pub fn client_secret_rotated(client_id: String, ip: Option<String>) -> Self {
+        Self::new(
+            EVENT_LEVEL_CLIENT_SECRET_ROTATED.get().cloned().unwrap(),
+            EventType::ClientSecretRotated,
+            ip,
+            None,
+            Some(client_id),
+        )
+    }
+
     pub fn ip_blacklisted(exp: DateTime<Utc>, ip: String) -> Self {
         Self::new(
             EVENT_LEVEL_IP_BLACKLISTED.get().cloned().unwrap(),
@@ -637,7 +1143,7 @@ impl Event {
 
     pub fn ip_blacklist_removed(ip: String) -> Self {
         Self::new(
-            EVENT_LEVEL_IP_BLACKLISTED.get().cloned().unwrap(),
+            EVENT_LEVEL_IP_BLACKLIST_REMOVED.get().cloned().unwrap(),
             EventType::IpBlacklistRemoved,
             Some(ip),
             None,
@@ -645,6 +1151,36 @@ impl Event {
         )
     }
 
+    pub fn mfa_failed(email: String, ip: String) -> Self {
+        Self::new(
+            EVENT_LEVEL_MFA_FAILED.get().cloned().unwrap(),
+            EventType::MfaFailed,
+            Some(ip),
+            None,
+            Some(email),
+        )
+    }
+
+    pub fn mfa_enrolled(email: String, ip: String) -> Self {
+        Self::new(
+            EVENT_LEVEL_MFA_ENROLLED.get().cloned().unwrap(),
+            EventType::MfaEnrolled,
+            Some(ip),
+            None,
+            Some(email),
+        )
+    }
+
+    pub fn mfa_removed(email: String, ip: String) -> Self {
+        Self::new(
+            EVENT_LEVEL_MFA_REMOVED.get().cloned().unwrap(),
+            EventType::MfaRemoved,
+            Some(ip),
+            None,
+            Some(email),
+        )
+    }
+
     pub fn new_user(email: String, ip: String) -> Self {
         Self::new(
             EVENT_LEVEL_NEW_USER.get().cloned().unwrap(),
@@ -741,6 +1277,121 @@ impl Event {
         )
     }
 
+    /// `text` should describe which sessions have been revoked, like `All sessions` or
+    /// `Sessions for user: admin@localhost.de`.
+    pub fn session_revoked(text: String, ip: String) -> Self {
+        Self::new(
+            EVENT_LEVEL_SESSION_REVOKED.get().cloned().unwrap(),
+            EventType::SessionRevoked,
+            Some(ip),
+            None,
+            Some(text),
+        )
+    }
+
+    /// `text` should name the user and whether older sessions have been evicted or the new
+    /// login has been rejected.
+    pub fn session_limit_reached(text: String, ip: String) -> Self {
+        Self::new(
+            EVENT_LEVEL_SESSION_LIMIT.get().cloned().unwrap(),
+            EventType::SessionLimitReached,
+            Some(ip),
+            None,
+            Some(text),
+        )
+    }
+
+    /// Sent when a session is used from another country or network than before. This is always
+    /// a `Warning`.
+    pub fn session_anomaly(text: String, ip: String) -> Self {
+        Self::new(
+            EventLevel::Warning,
+            EventType::SessionAnomaly,
+            Some(ip),
+            None,
+            Some(text),
+        )
+    }
+
+    /// Sent after a successful passkey login, where the assertion has been done on another device
+    /// via hybrid transport, like a phone scanning a QR code. This is always a `Notice`.
+    pub fn passkey_hybrid_login(text: String, ip: String) -> Self {
+        Self::new(
+            EventLevel::Notice,
+            EventType::PasskeyHybridLogin,
+            Some(ip),
+            None,
+            Some(text),
+        )
+    }
+
+    /// Sent when a cross-device passkey login has been rejected, because the client does not
+    /// allow hybrid transport. This is always a `Warning`.
+    pub fn passkey_hybrid_denied(text: String, ip: String) -> Self {
+        Self::new(
+            EventLevel::Warning,
+            EventType::PasskeyHybridDenied,
+            Some(ip),
+            None,
+            Some(text),
+        )
+    }
+
+    /// Sent when an already rotated refresh token has been replayed after the grace window.
+    /// This is always `Critical`, because the token has most probably been stolen.
+    pub fn refresh_token_reused(text: String, ip: String) -> Self {
+        Self::new(
+            EventLevel::Critical,
+            EventType::RefreshTokenReused,
+            Some(ip),
+            None,
+            Some(text),
+        )
+    }
+
+    /// `text` should name the admin and the impersonated user. This event is always `Critical`,
+    /// because it grants full access to another account.
+    pub fn user_impersonated(text: String, ip: String) -> Self {
+        Self::new(
+            EventLevel::Critical,
+            EventType::UserImpersonated,
+            Some(ip),
+            None,
+            Some(text),
+        )
+    }
+
+    pub fn user_expired(email: String) -> Self {
+        Self::new(
+            EVENT_LEVEL_USER_EXPIRED.get().cloned().unwrap(),
+            EventType::UserExpired,
+            None,
+            None,
+            Some(email),
+        )
+    }
+
+    /// `locked_until` is the unix timestamp of the automatic unlock.
+    pub fn user_locked(email: String, locked_until: i64, ip: String) -> Self {
+        Self::new(
+            EVENT_LEVEL_USER_LOCKED.get().cloned().unwrap(),
+            EventType::UserLocked,
+            Some(ip),
+            Some(locked_until),
+            Some(email),
+        )
+    }
+
+    pub fn user_unlocked(email: String, ip: String) -> Self {
+        Self::new(
+            EVENT_LEVEL_USER_UNLOCKED.get().cloned().unwrap(),
+            EventType::UserUnlocked,
+            Some(ip),
+            None,
+            Some(email),
+        )
+    }
+
     pub fn test(ip: IpAddr) -> Self {
         Self::new(
             EventLevel::Info,
@@ -811,6 +1462,33 @@ impl Event {
             EventType::Test => {
                 format!("Test Message: {}", self.text.as_deref().unwrap_or_default())
             }
+            EventType::MfaFailed | EventType::MfaEnrolled | EventType::MfaRemoved => {
+                format!("User: {}", self.text.as_deref().unwrap_or_default())
+            }
+            EventType::ClientCreated
+            | EventType::ClientDeleted
+            | EventType::ClientSecretRotated => {
+                format!("Client: {}", self.text.as_deref().unwrap_or_default())
+            }
+            EventType::SessionRevoked
+            | EventType::UserImpersonated
+            | EventType::RefreshTokenReused
+            | EventType::SessionLimitReached
+            | EventType::SessionAnomaly
+            | EventType::PasskeyHybridLogin
+            | EventType::PasskeyHybridDenied => self.text.clone().unwrap_or_default(),
+            EventType::UserExpired | EventType::UserUnlocked => {
+                format!("User: {}", self.text.as_deref().unwrap_or_default())
+            }
+            EventType::UserLocked => {
+                let d =
+                    DateTime::from_timestamp(self.data.unwrap_or_default(), 0).unwrap_or_default();
+                format!(
+                    "User {} locked until {}",
+                    self.text.as_deref().unwrap_or_default(),
+                    d.format("%Y/%m/%d %H:%M:%S")
+                )
+            }
         }
     }
 
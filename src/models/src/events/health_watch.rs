@@ -1,12 +1,19 @@
 use crate::database::DB;
 use crate::entity::is_db_alive;
-use crate::events::event::Event;
+use crate::events::event::{Event, EventType};
+use rauthy_common::clock;
+use rauthy_common::constants::RAUTHY_VERSION;
+use rauthy_common::utils::get_local_hostname;
+use rauthy_notify::Notification;
+use serde::Serialize;
+use std::env;
 use std::time::Duration;
-use tracing::debug;
+use tracing::{debug, error, info};
 
 pub async fn watch_health(tx_events: flume::Sender<Event>) {
     debug!("Rauthy health watcher started");
 
+    let webhook = HealthWebhook::from_env();
     let mut interval = tokio::time::interval(Duration::from_secs(30));
     let mut was_healthy_after_startup = false;
     let mut last_state = false;
@@ -35,6 +42,12 @@ pub async fn watch_health(tx_events: flume::Sender<Event>) {
         };
 
         let is_good_now = db_healthy && cache_healthy;
+        // only transitions after the first healthy state are interesting for the webhook
+        if was_healthy_after_startup && is_good_now != last_state {
+            if let Some(webhook) = &webhook {
+                webhook.send(db_healthy, cache_healthy);
+            }
+        }
         if !was_healthy_after_startup && is_good_now {
             was_healthy_after_startup = true;
         }
@@ -47,3 +60,96 @@ pub async fn watch_health(tx_events: flume::Sender<Event>) {
         last_state = is_good_now;
     }
 }
+
+/// Calls an external webhook on each `RauthyUnhealthy` / `RauthyHealthy` transition, which makes
+/// it possible to page on-call directly via the generic webhooks of incident tooling.
+#[derive(Debug, Clone)]
+struct HealthWebhook {
+    url: String,
+    auth_header: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct HealthWebhookPayload {
+    event: String,
+    status: &'static str,
+    host: String,
+    version: &'static str,
+    timestamp: i64,
+    components: HealthComponents,
+}
+
+#[derive(Debug, Serialize)]
+struct HealthComponents {
+    db: &'static str,
+    cache: &'static str,
+}
+
+impl HealthWebhook {
+    fn from_env() -> Option<Self> {
+        let url = env::var("HEALTH_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty())?;
+        info!("Health transitions will be sent to the webhook {}", url);
+
+        Some(Self {
+            url,
+            auth_header: env::var("HEALTH_WEBHOOK_AUTH_HEADER").ok(),
+        })
+    }
+
+    #[inline]
+    fn status(healthy: bool) -> &'static str {
+        if healthy {
+            "healthy"
+        } else {
+            "unhealthy"
+        }
+    }
+
+    /// Sends the current status in the background with a few retries to never block the
+    /// health watcher itself.
+    fn send(&self, db_healthy: bool, cache_healthy: bool) {
+        let is_healthy = db_healthy && cache_healthy;
+        let typ = if is_healthy {
+            EventType::RauthyHealthy
+        } else {
+            EventType::RauthyUnhealthy
+        };
+        let payload = HealthWebhookPayload {
+            event: typ.as_str().to_string(),
+            status: Self::status(is_healthy),
+            host: get_local_hostname(),
+            version: RAUTHY_VERSION,
+            timestamp: clock::now().timestamp(),
+            components: HealthComponents {
+                db: Self::status(db_healthy),
+                cache: Self::status(cache_healthy),
+            },
+        };
+        let slf = self.clone();
+
+        tokio::spawn(async move {
+            for i in 1..=3 {
+                let mut req = Notification::client().await.post(&slf.url).json(&payload);
+                if let Some(auth) = &slf.auth_header {
+                    req = req.header("Authorization", auth);
+                }
+
+                match req.send().await {
+                    Ok(resp) if resp.status().is_success() => {
+                        debug!("Health webhook sent successfully");
+                        return;
+                    }
+                    Ok(resp) => {
+                        error!("Health webhook returned status {}", resp.status());
+                    }
+                    Err(err) => {
+                        error!("Sending health webhook: {}", err);
+                    }
+                }
+                tokio::time::sleep(Duration::from_secs(5 * i)).await;
+            }
+        });
+    }
+}
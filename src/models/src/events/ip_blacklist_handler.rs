@@ -1,4 +1,5 @@
 use chrono::{DateTime, Utc};
+use rauthy_common::clock;
 use std::collections::HashMap;
 use std::time::Duration;
 use tokio::sync::oneshot;
@@ -54,7 +55,7 @@ pub async fn run(tx: flume::Sender<IpBlacklistReq>, rx: flume::Receiver<IpBlackl
             Ok(req) => match req {
                 IpBlacklistReq::CheckExp => {
                     debug!("Running IpBlacklistReq::CheckExp");
-                    let now = Utc::now();
+                    let now = clock::now();
                     let mut remove = Vec::default();
                     for (k, v) in data_blacklist.iter() {
                         if &now > v {
@@ -1,10 +1,15 @@
 use crate::database::DB;
+use crate::events::aggregator::EventAggregator;
 use crate::events::event::{Event, EventLevel, EventType};
 use crate::events::ip_blacklist_handler::{IpBlacklist, IpBlacklistReq, IpLoginFailedSet};
+use crate::events::metrics::EventMetrics;
 use crate::events::notifier::EventNotifier;
-use crate::events::EVENT_PERSIST_LEVEL;
+use crate::events::stream::EventStream;
+use crate::events::syslog::EventExporterSyslog;
+use crate::events::{EVENT_PERSIST_LEVEL, EVENT_SILENCED_TYPES};
 use actix_web_lab::sse;
 use chrono::DateTime;
+use rauthy_common::clock;
 use rauthy_common::constants::EVENTS_LATEST_LIMIT;
 use rauthy_error::ErrorResponse;
 use std::collections::{HashMap, VecDeque};
@@ -39,8 +44,35 @@ impl EventListener {
         tokio::spawn(Self::router(rx_router, tx_ip_blacklist));
         tokio::spawn(Self::raft_events_listener(tx_router));
 
-        while let Ok(event) = rx_event.recv_async().await {
-            tokio::spawn(Self::handle_event(event));
+        let silenced = EVENT_SILENCED_TYPES.get().unwrap();
+        let mut aggregator = EventAggregator::from_env();
+        let mut interval = time::interval(Duration::from_secs(1));
+
+        loop {
+            tokio::select! {
+                res = rx_event.recv_async() => {
+                    let Ok(event) = res else {
+                        break;
+                    };
+                    // the metrics should reflect each single occurrence
+                    EventMetrics::observe(&event);
+                    if silenced.contains(&event.typ) {
+                        debug!("Dropping silenced Event: {}", event);
+                        continue;
+                    }
+                    let now = clock::now().timestamp_millis();
+                    if let Some(event) = aggregator.check(event, now) {
+                        tokio::spawn(Self::handle_event(event));
+                    }
+                }
+
+                _ = interval.tick(), if aggregator.is_enabled() => {
+                    let now = clock::now().timestamp_millis();
+                    for event in aggregator.flush_expired(now) {
+                        tokio::spawn(Self::handle_event(event));
+                    }
+                }
+            }
         }
 
         Ok(())
@@ -48,12 +80,16 @@ impl EventListener {
 
     #[tracing::instrument(level = "debug", skip_all)]
     async fn handle_event(event: Event) {
+        EventStream::publish(&event);
+
         // insert into DB
         if &event.level.value() >= EVENT_PERSIST_LEVEL.get().unwrap() {
             while let Err(err) = event.insert().await {
                 error!("Inserting Event into Database: {:?}", err);
                 time::sleep(Duration::from_secs(1)).await;
             }
+
+            EventExporterSyslog::export(&event).await;
         }
 
         // notify raft members
@@ -214,6 +250,22 @@ impl EventListener {
                         EventType::UserEmailChange => {}
                         EventType::UserPasswordReset => {}
                         EventType::Test => {}
+                        EventType::MfaFailed => {}
+                        EventType::MfaEnrolled => {}
+                        EventType::MfaRemoved => {}
+                        EventType::ClientCreated => {}
+                        EventType::ClientDeleted => {}
+                        EventType::ClientSecretRotated => {}
+                        EventType::SessionRevoked => {}
+                        EventType::UserImpersonated => {}
+                        EventType::UserExpired => {}
+                        EventType::UserLocked => {}
+                        EventType::UserUnlocked => {}
+                        EventType::RefreshTokenReused => {}
+                        EventType::SessionLimitReached => {}
+                        EventType::SessionAnomaly => {}
+                        EventType::PasskeyHybridLogin => {}
+                        EventType::PasskeyHybridDenied => {}
                     }
 
                     // pre-compute the payload
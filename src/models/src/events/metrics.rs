@@ -0,0 +1,78 @@
+use crate::events::event::{Event, EventType};
+use prometheus::{IntCounter, IntCounterVec, Opts, Registry};
+use std::sync::OnceLock;
+
+static EVENT_METRICS: OnceLock<EventMetrics> = OnceLock::new();
+
+/// Prometheus counters derived from the event pipeline, which makes it possible to run alerting
+/// on metrics instead of parsing logs.
+///
+/// Each instance only counts the events it generated itself. In an HA deployment, the counters
+/// must be summed up across all instances.
+pub struct EventMetrics {
+    events_total: IntCounterVec,
+    invalid_logins_total: IntCounter,
+    ip_blacklisted_total: IntCounter,
+}
+
+impl EventMetrics {
+    /// Registers the counters with the given registry. As long as this has not been called,
+    /// `observe()` will be a noop.
+    pub fn register(registry: &Registry) {
+        let events_total = IntCounterVec::new(
+            Opts::new("rauthy_events_total", "Total number of generated events"),
+            &["type", "level"],
+        )
+        .unwrap();
+        let invalid_logins_total = IntCounter::new(
+            "rauthy_invalid_logins_total",
+            "Total number of invalid logins",
+        )
+        .unwrap();
+        let ip_blacklisted_total = IntCounter::new(
+            "rauthy_ip_blacklisted_total",
+            "Total number of blacklisted IPs",
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(events_total.clone()))
+            .expect("Cannot register rauthy_events_total");
+        registry
+            .register(Box::new(invalid_logins_total.clone()))
+            .expect("Cannot register rauthy_invalid_logins_total");
+        registry
+            .register(Box::new(ip_blacklisted_total.clone()))
+            .expect("Cannot register rauthy_ip_blacklisted_total");
+
+        let slf = Self {
+            events_total,
+            invalid_logins_total,
+            ip_blacklisted_total,
+        };
+        if EVENT_METRICS.set(slf).is_err() {
+            panic!("EventMetrics::register should only be called once");
+        }
+    }
+
+    /// Counts the given event. This should be called for each new event before any silencing
+    /// or aggregation happens to always reflect the real numbers.
+    pub fn observe(event: &Event) {
+        let Some(slf) = EVENT_METRICS.get() else {
+            return;
+        };
+
+        slf.events_total
+            .with_label_values(&[
+                event.typ.as_str(),
+                event.level.as_str().to_lowercase().as_str(),
+            ])
+            .inc();
+
+        match event.typ {
+            EventType::InvalidLogins => slf.invalid_logins_total.inc(),
+            EventType::IpBlacklisted => slf.ip_blacklisted_total.inc(),
+            _ => {}
+        }
+    }
+}
@@ -1,14 +1,19 @@
-use crate::events::event::EventLevel;
+use crate::events::event::{EventLevel, EventType};
 use rauthy_error::ErrorResponse;
 use std::env;
 use std::sync::OnceLock;
 use tracing::info;
 
+pub mod aggregator;
+pub mod archive;
 pub mod event;
 pub mod health_watch;
 pub mod ip_blacklist_handler;
 pub mod listener;
+pub mod metrics;
 pub mod notifier;
+pub mod stream;
+pub mod syslog;
 
 pub static EVENT_PERSIST_LEVEL: OnceLock<i16> = OnceLock::new();
 pub static EVENT_LEVEL_NEW_USER: OnceLock<EventLevel> = OnceLock::new();
@@ -22,12 +27,26 @@ pub static EVENT_LEVEL_RAUTHY_START: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_RAUTHY_HEALTHY: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_RAUTHY_UNHEALTHY: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_IP_BLACKLISTED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_IP_BLACKLIST_REMOVED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_POSSIBLE_BRUTE_FORCE: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_FAILED_LOGINS_25: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_FAILED_LOGINS_20: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_FAILED_LOGINS_15: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_FAILED_LOGINS_10: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_FAILED_LOGINS_7: OnceLock<EventLevel> = OnceLock::new();
 pub static EVENT_LEVEL_FAILED_LOGIN: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_MFA_FAILED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_MFA_ENROLLED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_MFA_REMOVED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_CLIENT_CREATED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_CLIENT_DELETED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_CLIENT_SECRET_ROTATED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_SESSION_REVOKED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_SESSION_LIMIT: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_USER_EXPIRED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_USER_LOCKED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_LEVEL_USER_UNLOCKED: OnceLock<EventLevel> = OnceLock::new();
+pub static EVENT_SILENCED_TYPES: OnceLock<Vec<EventType>> = OnceLock::new();
 
 pub fn init_event_vars() -> Result<(), ErrorResponse> {
     let level = map_env_var_level("EVENT_PERSIST_LEVEL", EventLevel::Info);
@@ -91,10 +110,19 @@ pub fn init_event_vars() -> Result<(), ErrorResponse> {
             EventLevel::Critical,
         ))
         .unwrap();
-    EVENT_LEVEL_IP_BLACKLISTED
+    let level_blacklisted = map_env_var_level("EVENT_LEVEL_IP_BLACKLISTED", EventLevel::Warning);
+    // defaults to the blacklisted level to not change the behavior of existing deployments
+    EVENT_LEVEL_IP_BLACKLIST_REMOVED
         .set(map_env_var_level(
-            "EVENT_LEVEL_IP_BLACKLISTED",
-            EventLevel::Warning,
+            "EVENT_LEVEL_IP_BLACKLIST_REMOVED",
+            level_blacklisted.clone(),
+        ))
+        .unwrap();
+    EVENT_LEVEL_IP_BLACKLISTED.set(level_blacklisted).unwrap();
+    EVENT_LEVEL_POSSIBLE_BRUTE_FORCE
+        .set(map_env_var_level(
+            "EVENT_LEVEL_POSSIBLE_BRUTE_FORCE",
+            EventLevel::Critical,
         ))
         .unwrap();
     EVENT_LEVEL_FAILED_LOGINS_25
@@ -133,6 +161,99 @@ pub fn init_event_vars() -> Result<(), ErrorResponse> {
             EventLevel::Info,
         ))
         .unwrap();
+    EVENT_LEVEL_MFA_FAILED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_MFA_FAILED",
+            EventLevel::Warning,
+        ))
+        .unwrap();
+    EVENT_LEVEL_MFA_ENROLLED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_MFA_ENROLLED",
+            EventLevel::Notice,
+        ))
+        .unwrap();
+    EVENT_LEVEL_MFA_REMOVED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_MFA_REMOVED",
+            EventLevel::Notice,
+        ))
+        .unwrap();
+    EVENT_LEVEL_CLIENT_CREATED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_CLIENT_CREATED",
+            EventLevel::Notice,
+        ))
+        .unwrap();
+    EVENT_LEVEL_CLIENT_DELETED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_CLIENT_DELETED",
+            EventLevel::Notice,
+        ))
+        .unwrap();
+    EVENT_LEVEL_CLIENT_SECRET_ROTATED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_CLIENT_SECRET_ROTATED",
+            EventLevel::Notice,
+        ))
+        .unwrap();
+    EVENT_LEVEL_SESSION_REVOKED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_SESSION_REVOKED",
+            EventLevel::Notice,
+        ))
+        .unwrap();
+    EVENT_LEVEL_SESSION_LIMIT
+        .set(map_env_var_level(
+            "EVENT_LEVEL_SESSION_LIMIT",
+            EventLevel::Notice,
+        ))
+        .unwrap();
+    EVENT_LEVEL_USER_EXPIRED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_USER_EXPIRED",
+            EventLevel::Notice,
+        ))
+        .unwrap();
+    EVENT_LEVEL_USER_LOCKED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_USER_LOCKED",
+            EventLevel::Warning,
+        ))
+        .unwrap();
+    EVENT_LEVEL_USER_UNLOCKED
+        .set(map_env_var_level(
+            "EVENT_LEVEL_USER_UNLOCKED",
+            EventLevel::Notice,
+        ))
+        .unwrap();
+
+    let silenced = env::var("EVENT_SILENCE_TYPES")
+        .unwrap_or_default()
+        .split(' ')
+        .filter(|typ| !typ.is_empty())
+        .map(|typ| {
+            let typ = serde_json::from_value::<EventType>(serde_json::Value::String(
+                typ.trim().to_string(),
+            ))
+            .unwrap_or_else(|_| panic!("Unknown EventType in EVENT_SILENCE_TYPES: {}", typ));
+            // these are necessary for the IP blacklisting and can therefore never be silenced
+            if matches!(
+                typ,
+                EventType::InvalidLogins
+                    | EventType::IpBlacklisted
+                    | EventType::IpBlacklistRemoved
+                    | EventType::Test
+            ) {
+                panic!("EventType {:?} cannot be silenced", typ);
+            }
+            typ
+        })
+        .collect::<Vec<EventType>>();
+    if !silenced.is_empty() {
+        info!("Silenced EventTypes: {:?}", silenced);
+    }
+    EVENT_SILENCED_TYPES.set(silenced).unwrap();
 
     Ok(())
 }
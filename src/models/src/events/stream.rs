@@ -0,0 +1,390 @@
+use crate::events::event::Event;
+use rauthy_error::ErrorResponse;
+use std::env;
+use std::sync::OnceLock;
+use tracing::{error, warn};
+
+static STREAM_SINKS: OnceLock<Vec<flume::Sender<Event>>> = OnceLock::new();
+
+/// Publishes all events to external streaming platforms like Kafka or NATS.
+///
+/// Each configured sink has its own bounded buffer and publisher task, which makes it
+/// independent of the SSE listener. If a sink cannot keep up and its buffer is full, new events
+/// will be dropped for this sink only.
+pub struct EventStream;
+
+impl EventStream {
+    pub async fn init() -> Result<(), ErrorResponse> {
+        let buffer = env::var("EVENT_STREAM_BUFFER")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse::<usize>()
+            .expect("Cannot parse EVENT_STREAM_BUFFER to usize");
+
+        let sinks = [Self::kafka_sink(buffer)?, Self::nats_sink(buffer)]
+            .into_iter()
+            .flatten()
+            .collect();
+        STREAM_SINKS
+            .set(sinks)
+            .expect("EventStream::init should only be called once");
+
+        Ok(())
+    }
+
+    /// Hands over the event to all configured sinks without waiting for the actual publishing.
+    pub fn publish(event: &Event) {
+        let Some(sinks) = STREAM_SINKS.get() else {
+            return;
+        };
+
+        for tx in sinks {
+            if let Err(err) = tx.try_send(event.clone()) {
+                warn!("Dropping Event for stream sink: {}", err);
+            }
+        }
+    }
+
+    #[cfg(feature = "event-kafka")]
+    fn kafka_sink(buffer: usize) -> Result<Option<flume::Sender<Event>>, ErrorResponse> {
+        let Ok(brokers) = env::var("EVENT_STREAM_KAFKA_BROKERS") else {
+            return Ok(None);
+        };
+        let config = kafka::KafkaConfig::from_env(&brokers)?;
+        let (tx, rx) = flume::bounded(buffer);
+        tokio::spawn(kafka::publisher(config, rx));
+        Ok(Some(tx))
+    }
+
+    #[cfg(not(feature = "event-kafka"))]
+    fn kafka_sink(_buffer: usize) -> Result<Option<flume::Sender<Event>>, ErrorResponse> {
+        if env::var("EVENT_STREAM_KAFKA_BROKERS").is_ok() {
+            error!(
+                "EVENT_STREAM_KAFKA_BROKERS is set, but Rauthy has been compiled without the \
+                'event-kafka' feature - events will not be published to Kafka"
+            );
+        }
+        Ok(None)
+    }
+
+    #[cfg(feature = "event-nats")]
+    fn nats_sink(buffer: usize) -> Option<flume::Sender<Event>> {
+        let url = env::var("EVENT_STREAM_NATS_URL").ok()?;
+        let subject =
+            env::var("EVENT_STREAM_NATS_SUBJECT").unwrap_or_else(|_| "rauthy.events".to_string());
+        let token = env::var("EVENT_STREAM_NATS_TOKEN").ok();
+        let (tx, rx) = flume::bounded(buffer);
+        tokio::spawn(nats::publisher(url, subject, token, rx));
+        Some(tx)
+    }
+
+    #[cfg(not(feature = "event-nats"))]
+    fn nats_sink(_buffer: usize) -> Option<flume::Sender<Event>> {
+        if env::var("EVENT_STREAM_NATS_URL").is_ok() {
+            error!(
+                "EVENT_STREAM_NATS_URL is set, but Rauthy has been compiled without the \
+                'event-nats' feature - events will not be published to NATS"
+            );
+        }
+        None
+    }
+}
+
+#[cfg(feature = "event-kafka")]
+mod kafka {
+    use crate::events::event::Event;
+    use chrono::Utc;
+    use rauthy_error::{ErrorResponse, ErrorResponseType};
+    use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+    use rskafka::client::{Client, ClientBuilder, SaslConfig};
+    use rskafka::record::Record;
+    use rustls_kafka as rustls;
+    use std::collections::{BTreeMap, HashMap};
+    use std::env;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time;
+    use tracing::{error, info, warn};
+
+    /// The Kafka producer config from the environment.
+    pub struct KafkaConfig {
+        brokers: Vec<String>,
+        topic: String,
+        partitions: i32,
+        tls: Option<Arc<rustls::ClientConfig>>,
+        sasl: Option<(String, String)>,
+    }
+
+    impl KafkaConfig {
+        pub fn from_env(brokers: &str) -> Result<Self, ErrorResponse> {
+            let brokers = brokers
+                .split(',')
+                .map(|b| b.trim().to_string())
+                .filter(|b| !b.is_empty())
+                .collect::<Vec<_>>();
+            let topic = env::var("EVENT_STREAM_KAFKA_TOPIC")
+                .unwrap_or_else(|_| "rauthy-events".to_string());
+
+            let partitions = env::var("EVENT_STREAM_KAFKA_PARTITIONS")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse::<i32>()
+                .expect("Cannot parse EVENT_STREAM_KAFKA_PARTITIONS to i32");
+            if partitions < 1 {
+                panic!("EVENT_STREAM_KAFKA_PARTITIONS must be at least 1");
+            }
+
+            let tls = if env::var("EVENT_STREAM_KAFKA_TLS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse::<bool>()
+                .expect("Cannot parse EVENT_STREAM_KAFKA_TLS to bool")
+            {
+                Some(tls_config(
+                    env::var("EVENT_STREAM_KAFKA_ROOT_CA_PATH").ok(),
+                )?)
+            } else {
+                None
+            };
+
+            let sasl = match (
+                env::var("EVENT_STREAM_KAFKA_SASL_USER"),
+                env::var("EVENT_STREAM_KAFKA_SASL_PASSWORD"),
+            ) {
+                (Ok(user), Ok(password)) => Some((user, password)),
+                (Err(_), Err(_)) => None,
+                _ => panic!(
+                    "EVENT_STREAM_KAFKA_SASL_USER and EVENT_STREAM_KAFKA_SASL_PASSWORD must be \
+                    set together"
+                ),
+            };
+            if sasl.is_some() && tls.is_none() {
+                warn!(
+                    "SASL PLAIN for Kafka is used without TLS - the credentials will be sent in \
+                    plain text"
+                );
+            }
+
+            Ok(Self {
+                brokers,
+                topic,
+                partitions,
+                tls,
+                sasl,
+            })
+        }
+
+        async fn connect(&self) -> Result<Client, rskafka::client::error::Error> {
+            let mut builder = ClientBuilder::new(self.brokers.clone());
+            if let Some(tls) = &self.tls {
+                builder = builder.tls_config(tls.clone());
+            }
+            if let Some((username, password)) = &self.sasl {
+                builder = builder.sasl_config(SaslConfig::Plain {
+                    username: username.clone(),
+                    password: password.clone(),
+                });
+            }
+            builder.build().await
+        }
+    }
+
+    /// Uses the `EVENT_STREAM_KAFKA_ROOT_CA_PATH`, if given, and the default Mozilla roots
+    /// otherwise.
+    fn tls_config(ca_path: Option<String>) -> Result<Arc<rustls::ClientConfig>, ErrorResponse> {
+        let mut roots = rustls::RootCertStore::empty();
+        if let Some(path) = ca_path {
+            let mut reader = BufReader::new(File::open(path)?);
+            for cert in rustls_pemfile::certs(&mut reader) {
+                roots
+                    .add(&rustls::Certificate(cert?.to_vec()))
+                    .map_err(|err| {
+                        ErrorResponse::new(
+                            ErrorResponseType::Internal,
+                            format!(
+                                "Invalid EVENT_STREAM_KAFKA_ROOT_CA_PATH certificate: {}",
+                                err
+                            ),
+                        )
+                    })?;
+            }
+        } else {
+            roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+                rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject.as_ref(),
+                    ta.subject_public_key_info.as_ref(),
+                    ta.name_constraints.as_ref().map(|nc| nc.as_ref()),
+                )
+            }));
+        }
+
+        let config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(Arc::new(config))
+    }
+
+    /// Events with the same key always end up in the same partition, which keeps their order.
+    /// With a single partition, the order is kept across all events.
+    fn partition_for(key: &str, partitions: i32) -> i32 {
+        // FNV-1a is stable across restarts and versions, unlike the std `DefaultHasher`
+        let hash = key.bytes().fold(0x811c9dc5u32, |hash, b| {
+            (hash ^ b as u32).wrapping_mul(0x01000193)
+        });
+        (hash % partitions as u32) as i32
+    }
+
+    pub async fn publisher(config: KafkaConfig, rx: flume::Receiver<Event>) {
+        info!(
+            "Events will be published to the Kafka topic '{}' with {} partition(s)",
+            config.topic, config.partitions
+        );
+
+        let mut client = None;
+        let mut partition_clients: HashMap<i32, PartitionClient> = HashMap::new();
+        while let Ok(event) = rx.recv_async().await {
+            let key = event.typ.as_str();
+            let partition = partition_for(key, config.partitions);
+            let record = Record {
+                key: Some(key.as_bytes().to_vec()),
+                value: Some(event.as_json().into_bytes()),
+                headers: BTreeMap::default(),
+                timestamp: chrono::DateTime::from_timestamp_millis(event.timestamp)
+                    .unwrap_or_else(Utc::now),
+            };
+
+            loop {
+                if client.is_none() {
+                    match config.connect().await {
+                        Ok(c) => client = Some(c),
+                        Err(err) => {
+                            error!("Connecting to Kafka: {}", err);
+                            time::sleep(Duration::from_secs(5)).await;
+                            continue;
+                        }
+                    }
+                }
+
+                if !partition_clients.contains_key(&partition) {
+                    match client
+                        .as_ref()
+                        .unwrap()
+                        .partition_client(
+                            config.topic.clone(),
+                            partition,
+                            UnknownTopicHandling::Retry,
+                        )
+                        .await
+                    {
+                        Ok(pc) => {
+                            partition_clients.insert(partition, pc);
+                        }
+                        Err(err) => {
+                            error!("Connecting to Kafka partition {}: {}", partition, err);
+                            client = None;
+                            partition_clients.clear();
+                            time::sleep(Duration::from_secs(5)).await;
+                            continue;
+                        }
+                    }
+                }
+
+                match partition_clients
+                    .get(&partition)
+                    .unwrap()
+                    .produce(vec![record.clone()], Compression::NoCompression)
+                    .await
+                {
+                    Ok(_) => break,
+                    Err(err) => {
+                        error!("Publishing Event to Kafka: {}", err);
+                        client = None;
+                        partition_clients.clear();
+                        time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::events::event::EventType;
+        use std::collections::HashSet;
+
+        #[test]
+        fn test_partition_for() {
+            let types = [
+                EventType::InvalidLogins,
+                EventType::IpBlacklisted,
+                EventType::NewUserRegistered,
+                EventType::NewRauthyAdmin,
+                EventType::UserExpired,
+                EventType::UserPasswordReset,
+            ];
+
+            for typ in &types {
+                assert_eq!(partition_for(typ.as_str(), 1), 0);
+                let partition = partition_for(typ.as_str(), 4);
+                assert!((0..4).contains(&partition));
+                // stable across calls
+                assert_eq!(partition_for(typ.as_str(), 4), partition);
+            }
+
+            let used = types
+                .iter()
+                .map(|typ| partition_for(typ.as_str(), 4))
+                .collect::<HashSet<_>>();
+            assert!(used.len() > 1);
+        }
+
+        #[test]
+        fn test_tls_config() {
+            assert!(tls_config(None).is_ok());
+            assert!(tls_config(Some("/does/not/exist.pem".to_string())).is_err());
+        }
+    }
+}
+
+#[cfg(feature = "event-nats")]
+mod nats {
+    use crate::events::event::Event;
+    use std::time::Duration;
+    use tokio::time;
+    use tracing::{error, info};
+
+    pub async fn publisher(
+        url: String,
+        subject: String,
+        token: Option<String>,
+        rx: flume::Receiver<Event>,
+    ) {
+        info!(
+            "Events will be published to the NATS subject '{}.*'",
+            subject
+        );
+
+        let client = loop {
+            let mut opts = async_nats::ConnectOptions::new();
+            if let Some(token) = &token {
+                opts = opts.token(token.clone());
+            }
+            match opts.connect(&url).await {
+                Ok(client) => break client,
+                Err(err) => {
+                    error!("Connecting to NATS: {}", err);
+                    time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        };
+
+        // The NATS client handles re-connects on its own and buffers messages in between.
+        while let Ok(event) = rx.recv_async().await {
+            let subject = format!("{}.{}", subject, event.typ.as_str());
+            if let Err(err) = client.publish(subject, event.as_json().into()).await {
+                error!("Publishing Event to NATS: {}", err);
+            }
+        }
+    }
+}
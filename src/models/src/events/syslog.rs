@@ -0,0 +1,376 @@
+use crate::events::event::{Event, EventLevel};
+use chrono::{DateTime, SecondsFormat};
+use rauthy_common::constants::RAUTHY_VERSION;
+use rauthy_common::utils::get_local_hostname;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rustls_pki_types::ServerName;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, OnceLock};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::Mutex;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use tracing::{error, info};
+
+static EXPORTER_SYSLOG: OnceLock<EventExporterSyslog> = OnceLock::new();
+
+/// The facility used for all messages: 10 -> security / authorization messages (authpriv)
+const FACILITY: u8 = 10;
+/// The private enterprise number used for the RFC5424 structured data
+const SD_ID: &str = "rauthy@32473";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SyslogProto {
+    Udp,
+    Tcp,
+    Tls,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SyslogFormat {
+    Rfc5424,
+    Cef,
+}
+
+enum SyslogConn {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+/// Forwards all persisted events to a syslog endpoint either in RFC5424 or CEF format.
+pub struct EventExporterSyslog {
+    proto: SyslogProto,
+    format: SyslogFormat,
+    host: String,
+    port: u16,
+    hostname: String,
+    tls_connector: Option<TlsConnector>,
+    conn: Mutex<Option<SyslogConn>>,
+}
+
+impl EventExporterSyslog {
+    pub fn init() -> Result<(), ErrorResponse> {
+        let Ok(url) = env::var("EVENT_SYSLOG_URL") else {
+            return Ok(());
+        };
+
+        let (proto, addr) = match url.split_once("://") {
+            Some(("udp", addr)) => (SyslogProto::Udp, addr),
+            Some(("tcp", addr)) => (SyslogProto::Tcp, addr),
+            Some(("tls", addr)) => (SyslogProto::Tls, addr),
+            _ => panic!("EVENT_SYSLOG_URL must start with one of: udp://, tcp://, tls://"),
+        };
+        let (host, port) = addr
+            .rsplit_once(':')
+            .expect("EVENT_SYSLOG_URL must contain a port");
+        let port = port
+            .parse::<u16>()
+            .expect("Cannot parse the port from EVENT_SYSLOG_URL");
+
+        let format = match env::var("EVENT_SYSLOG_FORMAT")
+            .unwrap_or_else(|_| "rfc5424".to_string())
+            .as_str()
+        {
+            "rfc5424" => SyslogFormat::Rfc5424,
+            "cef" => SyslogFormat::Cef,
+            _ => panic!("Cannot parse EVENT_SYSLOG_FORMAT. Possible values: rfc5424, cef"),
+        };
+
+        let tls_connector = if proto == SyslogProto::Tls {
+            let mut roots = rustls::RootCertStore::empty();
+            if let Ok(path) = env::var("EVENT_SYSLOG_ROOT_CA_PATH") {
+                let mut reader = BufReader::new(File::open(path)?);
+                for cert in rustls_pemfile::certs(&mut reader) {
+                    roots.add(cert?).map_err(|err| {
+                        ErrorResponse::new(
+                            ErrorResponseType::Internal,
+                            format!("Invalid EVENT_SYSLOG_ROOT_CA_PATH certificate: {}", err),
+                        )
+                    })?;
+                }
+            } else {
+                roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            }
+
+            let config = rustls::ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            Some(TlsConnector::from(Arc::new(config)))
+        } else {
+            None
+        };
+
+        info!(
+            "Events will be exported to syslog at {} in {:?} format",
+            url, format
+        );
+
+        let slf = Self {
+            proto,
+            format,
+            host: host.to_string(),
+            port,
+            hostname: get_local_hostname(),
+            tls_connector,
+            conn: Mutex::new(None),
+        };
+        if EXPORTER_SYSLOG.set(slf).is_err() {
+            panic!("EventExporterSyslog::init should only be called once");
+        }
+
+        Ok(())
+    }
+
+    /// Exports the given `Event`, if the syslog exporter is configured.
+    pub async fn export(event: &Event) {
+        let Some(slf) = EXPORTER_SYSLOG.get() else {
+            return;
+        };
+
+        let msg = match slf.format {
+            SyslogFormat::Rfc5424 => slf.fmt_rfc5424(event),
+            SyslogFormat::Cef => slf.fmt_cef(event),
+        };
+
+        // A broken stream will only be noticed on the next write. In this case, we
+        // re-connect and try a second time before giving up.
+        for _ in 0..2 {
+            match slf.write(msg.as_bytes()).await {
+                Ok(_) => return,
+                Err(err) => {
+                    error!("Exporting Event to syslog: {}", err.message);
+                    *slf.conn.lock().await = None;
+                }
+            }
+        }
+    }
+
+    async fn write(&self, msg: &[u8]) -> Result<(), ErrorResponse> {
+        let mut lock = self.conn.lock().await;
+        if lock.is_none() {
+            *lock = Some(self.connect().await?);
+        }
+
+        match lock.as_mut().unwrap() {
+            SyslogConn::Udp(socket) => {
+                socket.send(msg).await?;
+            }
+            // stream transports use octet counting framing -> RFC6587
+            SyslogConn::Tcp(stream) => {
+                stream
+                    .write_all(format!("{} ", msg.len()).as_bytes())
+                    .await?;
+                stream.write_all(msg).await?;
+            }
+            SyslogConn::Tls(stream) => {
+                stream
+                    .write_all(format!("{} ", msg.len()).as_bytes())
+                    .await?;
+                stream.write_all(msg).await?;
+                stream.flush().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn connect(&self) -> Result<SyslogConn, ErrorResponse> {
+        let addr = (self.host.as_str(), self.port);
+
+        match self.proto {
+            SyslogProto::Udp => {
+                let socket = UdpSocket::bind("0.0.0.0:0").await?;
+                socket.connect(addr).await?;
+                Ok(SyslogConn::Udp(socket))
+            }
+            SyslogProto::Tcp => Ok(SyslogConn::Tcp(TcpStream::connect(addr).await?)),
+            SyslogProto::Tls => {
+                let server_name = ServerName::try_from(self.host.clone()).map_err(|_| {
+                    ErrorResponse::new(
+                        ErrorResponseType::Internal,
+                        "Invalid server name in EVENT_SYSLOG_URL",
+                    )
+                })?;
+                let tcp = TcpStream::connect(addr).await?;
+                let stream = self
+                    .tls_connector
+                    .as_ref()
+                    .unwrap()
+                    .connect(server_name, tcp)
+                    .await?;
+                Ok(SyslogConn::Tls(Box::new(stream)))
+            }
+        }
+    }
+
+    /// `<PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID`
+    fn header(&self, event: &Event) -> String {
+        let severity = match event.level {
+            EventLevel::Info => 6,
+            EventLevel::Notice => 5,
+            EventLevel::Warning => 4,
+            EventLevel::Critical => 2,
+        };
+        let ts = DateTime::from_timestamp_millis(event.timestamp)
+            .unwrap_or_default()
+            .to_rfc3339_opts(SecondsFormat::Millis, true);
+
+        format!(
+            "<{}>1 {} {} rauthy - {}",
+            FACILITY * 8 + severity,
+            ts,
+            self.hostname,
+            event.typ.as_str(),
+        )
+    }
+
+    fn fmt_rfc5424(&self, event: &Event) -> String {
+        let mut sd = format!(
+            "[{} id=\"{}\" level=\"{}\"",
+            SD_ID,
+            escape_sd(&event.id),
+            event.level.as_str(),
+        );
+        if let Some(ip) = &event.ip {
+            sd.push_str(&format!(" ip=\"{}\"", escape_sd(ip)));
+        }
+        if let Some(data) = event.data {
+            sd.push_str(&format!(" data=\"{}\"", data));
+        }
+        if let Some(text) = &event.text {
+            sd.push_str(&format!(" text=\"{}\"", escape_sd(text)));
+        }
+        if let Some(request_id) = &event.request_id {
+            sd.push_str(&format!(" request_id=\"{}\"", escape_sd(request_id)));
+        }
+        if let Some(count) = event.aggregated_count {
+            sd.push_str(&format!(" aggregated_count=\"{}\"", count));
+        }
+        sd.push(']');
+
+        format!("{} {} {}", self.header(event), sd, event)
+    }
+
+    fn fmt_cef(&self, event: &Event) -> String {
+        let severity = match event.level {
+            EventLevel::Info => 3,
+            EventLevel::Notice => 5,
+            EventLevel::Warning => 7,
+            EventLevel::Critical => 10,
+        };
+
+        let mut ext = format!(
+            "rt={} externalId={}",
+            event.timestamp,
+            escape_cef_ext(&event.id)
+        );
+        if let Some(ip) = &event.ip {
+            ext.push_str(&format!(" src={}", escape_cef_ext(ip)));
+        }
+        if let Some(data) = event.data {
+            ext.push_str(&format!(" cn1Label=data cn1={}", data));
+        }
+        if let Some(text) = &event.text {
+            ext.push_str(&format!(" msg={}", escape_cef_ext(text)));
+        }
+        if let Some(request_id) = &event.request_id {
+            ext.push_str(&format!(
+                " cs1Label=requestId cs1={}",
+                escape_cef_ext(request_id)
+            ));
+        }
+        if let Some(count) = event.aggregated_count {
+            ext.push_str(&format!(" cnt={}", count));
+        }
+
+        format!(
+            "{} - CEF:0|Rauthy|Rauthy|{}|{}|{}|{}|{}",
+            self.header(event),
+            escape_cef_header(RAUTHY_VERSION),
+            event.typ.value(),
+            escape_cef_header(event.typ.as_str()),
+            severity,
+            ext,
+        )
+    }
+}
+
+/// Escapes a PARAM-VALUE for RFC5424 structured data.
+fn escape_sd(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '"' || c == '\\' || c == ']' {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+fn escape_cef_header(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn escape_cef_ext(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::event::EventType;
+
+    fn exporter(format: SyslogFormat) -> EventExporterSyslog {
+        EventExporterSyslog {
+            proto: SyslogProto::Udp,
+            format,
+            host: "localhost".to_string(),
+            port: 514,
+            hostname: "rauthy-0".to_string(),
+            tls_connector: None,
+            conn: Mutex::new(None),
+        }
+    }
+
+    fn event() -> Event {
+        Event {
+            id: "abc123".to_string(),
+            timestamp: 1720000000123,
+            level: EventLevel::Warning,
+            typ: EventType::MfaFailed,
+            ip: Some("192.168.1.1".to_string()),
+            data: None,
+            text: Some("a\"b]c=d".to_string()),
+            user_id: None,
+            request_id: None,
+            aggregated_count: None,
+        }
+    }
+
+    #[test]
+    fn test_fmt_rfc5424() {
+        let msg = exporter(SyslogFormat::Rfc5424).fmt_rfc5424(&event());
+        assert!(msg.starts_with(
+            "<84>1 2024-07-03T09:46:40.123Z rauthy-0 rauthy - MfaFailed [rauthy@32473 id=\"abc123\" level=\"WARNING\" ip=\"192.168.1.1\" text=\"a\\\"b\\]c=d\"] EVENT "
+        ));
+    }
+
+    #[test]
+    fn test_fmt_cef() {
+        let msg = exporter(SyslogFormat::Cef).fmt_cef(&event());
+        let expected = format!(
+            "<84>1 2024-07-03T09:46:40.123Z rauthy-0 rauthy - MfaFailed - CEF:0|Rauthy|Rauthy|{}|{}|MfaFailed|7|rt=1720000000123 externalId=abc123 src=192.168.1.1 msg=a\"b]c\\=d",
+            RAUTHY_VERSION,
+            EventType::MfaFailed.value(),
+        );
+        assert_eq!(msg, expected);
+    }
+}
@@ -0,0 +1,365 @@
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tracing::info;
+
+static GEO_IP: OnceLock<Box<dyn GeoIpLookup>> = OnceLock::new();
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+// City level GeoIP locations can easily be off by this much
+const IMPOSSIBLE_TRAVEL_MIN_KM: f64 = 100.0;
+
+/// Approximate location information for an IP address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoIpInfo {
+    /// ISO 3166-1 alpha-2 country code
+    pub country: Option<String>,
+    /// Autonomous System Number
+    pub asn: Option<u32>,
+    /// Latitude and longitude in degrees
+    pub location: Option<(f64, f64)>,
+}
+
+impl GeoIpInfo {
+    /// Returns a description of the anomaly, if `to` is located in another country, or if
+    /// `check_asn` is set, belongs to another autonomous system. With a `max_speed` in km/h,
+    /// a travel between both locations within `elapsed_secs`, which would have been faster,
+    /// is an anomaly as well. Unknown values never trigger an anomaly.
+    pub fn anomaly(
+        &self,
+        to: &Self,
+        check_asn: bool,
+        max_speed: u32,
+        elapsed_secs: i64,
+    ) -> Option<String> {
+        if max_speed > 0 {
+            if let (Some(from), Some(to)) = (self.location, to.location) {
+                let km = distance_km(from, to);
+                // GeoIP locations are not exact -> small distances are never an anomaly
+                let hours = elapsed_secs.max(1) as f64 / 3600.0;
+                if km > IMPOSSIBLE_TRAVEL_MIN_KM && km / hours > max_speed as f64 {
+                    return Some(format!(
+                        "impossible travel of {:.0} km in {} minutes",
+                        km,
+                        elapsed_secs / 60
+                    ));
+                }
+            }
+        }
+
+        if let (Some(from), Some(to)) = (&self.country, &to.country) {
+            if from != to {
+                return Some(format!("country changed from {} to {}", from, to));
+            }
+        }
+
+        if check_asn {
+            if let (Some(from), Some(to)) = (self.asn, to.asn) {
+                if from != to {
+                    return Some(format!("ASN changed from AS{} to AS{}", from, to));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// The great-circle distance between two `(latitude, longitude)` pairs via the haversine formula.
+fn distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// Any source for GeoIP lookups can be plugged in by implementing this trait and passing it
+/// to `GeoIp::init_with()`.
+pub trait GeoIpLookup: Send + Sync {
+    fn lookup(&self, ip: &IpAddr) -> Option<GeoIpInfo>;
+}
+
+pub struct GeoIp;
+
+impl GeoIp {
+    /// Loads the database from `SESSION_ANOMALY_GEOIP_DB`, if it is set. Without a database,
+    /// lookups will always return `None`.
+    pub fn init() -> Result<(), ErrorResponse> {
+        let Ok(path) = env::var("SESSION_ANOMALY_GEOIP_DB") else {
+            return Ok(());
+        };
+
+        let format =
+            env::var("SESSION_ANOMALY_GEOIP_FORMAT").unwrap_or_else(|_| "ip2asn".to_string());
+        let db = match format.as_str() {
+            "ip2asn" => RangeDb::from_file(&path, RangeDb::parse_ip2asn)?,
+            "dbip-city" => RangeDb::from_file(&path, RangeDb::parse_dbip_city)?,
+            _ => panic!("SESSION_ANOMALY_GEOIP_FORMAT must be one of: ip2asn, dbip-city"),
+        };
+        info!(
+            "Loaded {} GeoIP ranges from {} for session anomaly detection",
+            db.ranges.len(),
+            path
+        );
+        Self::init_with(Box::new(db));
+
+        Ok(())
+    }
+
+    /// Sets a custom `GeoIpLookup`. Can only be called once.
+    pub fn init_with(lookup: Box<dyn GeoIpLookup>) {
+        if GEO_IP.set(lookup).is_err() {
+            panic!("GeoIp has already been initialized");
+        }
+    }
+
+    #[inline]
+    pub fn is_enabled() -> bool {
+        GEO_IP.get().is_some()
+    }
+
+    pub fn lookup(ip: &IpAddr) -> Option<GeoIpInfo> {
+        GEO_IP.get().and_then(|db| db.lookup(ip))
+    }
+}
+
+/// A range based database, which can be loaded from one of the supported free formats.
+struct RangeDb {
+    /// Sorted by `start`, ranges do not overlap
+    ranges: Vec<(IpAddr, IpAddr, GeoIpInfo)>,
+}
+
+impl GeoIpLookup for RangeDb {
+    fn lookup(&self, ip: &IpAddr) -> Option<GeoIpInfo> {
+        let idx = self.ranges.partition_point(|(start, _, _)| start <= ip);
+        if idx == 0 {
+            return None;
+        }
+
+        let (_, end, info) = &self.ranges[idx - 1];
+        if ip <= end {
+            Some(info.clone())
+        } else {
+            None
+        }
+    }
+}
+
+impl RangeDb {
+    fn from_file<F>(path: &str, parse_line: F) -> Result<Self, ErrorResponse>
+    where
+        F: Fn(&str) -> Option<(IpAddr, IpAddr, GeoIpInfo)>,
+    {
+        let file = File::open(path).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("Cannot open SESSION_ANOMALY_GEOIP_DB {}: {}", path, err),
+            )
+        })?;
+        Self::parse(BufReader::new(file), parse_line)
+    }
+
+    fn parse<R, F>(reader: R, parse_line: F) -> Result<Self, ErrorResponse>
+    where
+        R: BufRead,
+        F: Fn(&str) -> Option<(IpAddr, IpAddr, GeoIpInfo)>,
+    {
+        let mut ranges = Vec::new();
+
+        for line in reader.lines() {
+            let line = line.map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    format!("Cannot read SESSION_ANOMALY_GEOIP_DB: {}", err),
+                )
+            })?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let range = parse_line(&line).ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    format!("Invalid line in SESSION_ANOMALY_GEOIP_DB: {}", line),
+                )
+            })?;
+            ranges.push(range);
+        }
+
+        ranges.sort_by_key(|r| r.0);
+        Ok(Self { ranges })
+    }
+
+    /// The format of the free `ip2asn-combined.tsv` from [iptoasn.com](https://iptoasn.com):
+    /// `range_start  range_end  AS_number  country_code  AS_description`
+    fn parse_ip2asn(line: &str) -> Option<(IpAddr, IpAddr, GeoIpInfo)> {
+        let mut cols = line.split('\t');
+        let start = IpAddr::from_str(cols.next()?).ok()?;
+        let end = IpAddr::from_str(cols.next()?).ok()?;
+        let asn = cols.next()?;
+        let country = cols.next()?;
+
+        // `0` and `None` are used for unrouted ranges
+        let asn = asn.parse::<u32>().ok().filter(|asn| *asn != 0);
+        let country = Some(country)
+            .filter(|c| !c.is_empty() && *c != "None")
+            .map(String::from);
+
+        Some((
+            start,
+            end,
+            GeoIpInfo {
+                country,
+                asn,
+                location: None,
+            },
+        ))
+    }
+
+    /// The format of the free `dbip-city-lite.csv` from [db-ip.com](https://db-ip.com):
+    /// `ip_start,ip_end,continent,country,stateprov,city,latitude,longitude`
+    fn parse_dbip_city(line: &str) -> Option<(IpAddr, IpAddr, GeoIpInfo)> {
+        // only the `stateprov` and `city` may contain quoted commas
+        let mut head = line.splitn(5, ',');
+        let start = IpAddr::from_str(head.next()?).ok()?;
+        let end = IpAddr::from_str(head.next()?).ok()?;
+        let _continent = head.next()?;
+        let country = head.next()?;
+        let mut tail = head.next()?.rsplitn(3, ',');
+        let longitude = tail.next()?.parse::<f64>().ok()?;
+        let latitude = tail.next()?.parse::<f64>().ok()?;
+
+        // `ZZ` is used for reserved ranges
+        let country = Some(country)
+            .filter(|c| !c.is_empty() && *c != "ZZ")
+            .map(String::from);
+
+        Some((
+            start,
+            end,
+            GeoIpInfo {
+                country,
+                asn: None,
+                location: Some((latitude, longitude)),
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip2asn_lookup() {
+        let tsv = "1.0.0.0\t1.0.0.255\t13335\tUS\tCLOUDFLARENET\n\
+            2.16.0.0\t2.16.0.255\t20940\tDE\tAKAMAI-ASN1\n\
+            10.0.0.0\t10.255.255.255\t0\tNone\tNot routed\n\
+            2001:db8::\t2001:db8::ffff\t64500\tFR\tEXAMPLE\n";
+        let db = RangeDb::parse(tsv.as_bytes(), RangeDb::parse_ip2asn).unwrap();
+
+        let info = db.lookup(&IpAddr::from_str("1.0.0.1").unwrap()).unwrap();
+        assert_eq!(info.country.as_deref(), Some("US"));
+        assert_eq!(info.asn, Some(13335));
+
+        let info = db.lookup(&IpAddr::from_str("2.16.0.10").unwrap()).unwrap();
+        assert_eq!(info.country.as_deref(), Some("DE"));
+
+        let info = db.lookup(&IpAddr::from_str("10.1.2.3").unwrap()).unwrap();
+        assert_eq!(info.country, None);
+        assert_eq!(info.asn, None);
+
+        let info = db
+            .lookup(&IpAddr::from_str("2001:db8::1").unwrap())
+            .unwrap();
+        assert_eq!(info.country.as_deref(), Some("FR"));
+
+        assert!(db.lookup(&IpAddr::from_str("1.0.1.0").unwrap()).is_none());
+        assert!(db.lookup(&IpAddr::from_str("0.0.0.1").unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_dbip_city_lookup() {
+        let csv = "1.0.0.0,1.0.0.255,OC,AU,Queensland,\"South Brisbane\",-27.4767,153.017\n\
+            2.16.0.0,2.16.0.255,EU,DE,Hesse,\"Frankfurt am Main\",50.1109,8.68213\n\
+            3.0.0.0,3.0.0.255,NA,US,\"District of Columbia\",\"Washington, D.C.\",38.8951,-77.0364\n\
+            10.0.0.0,10.255.255.255,ZZ,ZZ,,,0,0\n";
+        let db = RangeDb::parse(csv.as_bytes(), RangeDb::parse_dbip_city).unwrap();
+
+        let info = db.lookup(&IpAddr::from_str("2.16.0.10").unwrap()).unwrap();
+        assert_eq!(info.country.as_deref(), Some("DE"));
+        assert_eq!(info.asn, None);
+        assert_eq!(info.location, Some((50.1109, 8.68213)));
+
+        let info = db.lookup(&IpAddr::from_str("3.0.0.1").unwrap()).unwrap();
+        assert_eq!(info.country.as_deref(), Some("US"));
+        assert_eq!(info.location, Some((38.8951, -77.0364)));
+
+        let info = db.lookup(&IpAddr::from_str("10.1.2.3").unwrap()).unwrap();
+        assert_eq!(info.country, None);
+
+        assert!(
+            RangeDb::parse("1.0.0.0,1.0.0.255,OC".as_bytes(), RangeDb::parse_dbip_city).is_err()
+        );
+    }
+
+    #[test]
+    fn test_distance_km() {
+        let frankfurt = (50.1109, 8.68213);
+        let new_york = (40.7128, -74.006);
+        let km = distance_km(frankfurt, new_york);
+        assert!((6190.0..6230.0).contains(&km), "{}", km);
+        assert_eq!(distance_km(frankfurt, frankfurt), 0.0);
+    }
+
+    #[test]
+    fn test_anomaly() {
+        let info = |country: Option<&str>, asn: Option<u32>| GeoIpInfo {
+            country: country.map(String::from),
+            asn,
+            location: None,
+        };
+
+        let from = info(Some("DE"), Some(3320));
+        assert!(from
+            .anomaly(&info(Some("DE"), Some(3320)), true, 0, 60)
+            .is_none());
+        assert!(from
+            .anomaly(&info(Some("US"), Some(3320)), false, 0, 60)
+            .is_some());
+        assert!(from
+            .anomaly(&info(Some("DE"), Some(13335)), false, 0, 60)
+            .is_none());
+        assert!(from
+            .anomaly(&info(Some("DE"), Some(13335)), true, 0, 60)
+            .is_some());
+        assert!(from.anomaly(&info(None, None), true, 0, 60).is_none());
+    }
+
+    #[test]
+    fn test_anomaly_impossible_travel() {
+        let info = |country: &str, location: (f64, f64)| GeoIpInfo {
+            country: Some(country.to_string()),
+            asn: None,
+            location: Some(location),
+        };
+        let frankfurt = info("DE", (50.1109, 8.68213));
+        let berlin = info("DE", (52.52, 13.405));
+        let offenbach = info("DE", (50.0956, 8.7761));
+
+        // ~420 km within 10 minutes in the same country
+        let anomaly = frankfurt.anomaly(&berlin, false, 1000, 600).unwrap();
+        assert!(anomaly.starts_with("impossible travel"), "{}", anomaly);
+        // possible with a train
+        assert!(frankfurt.anomaly(&berlin, false, 1000, 4 * 3600).is_none());
+        // disabled
+        assert!(frankfurt.anomaly(&berlin, false, 0, 600).is_none());
+        // GeoIP inaccuracies within a city are ignored, even without time in between
+        assert!(frankfurt.anomaly(&offenbach, false, 1000, 0).is_none());
+    }
+}
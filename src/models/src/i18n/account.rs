@@ -14,6 +14,11 @@ pub struct I18nAccount<'a> {
     access_exp: &'a str,
     access_renew: &'a str,
     access_renew_delete: &'a str,
+    apps: &'a str,
+    apps_desc: &'a str,
+    apps_none: &'a str,
+    apps_revoke: &'a str,
+    apps_scopes: &'a str,
     birthdate: &'a str,
     cancel: &'a str,
     city: &'a str,
@@ -28,6 +33,8 @@ pub struct I18nAccount<'a> {
     email: &'a str,
     email_update_confirm: &'a str,
     email_verified: &'a str,
+    email_verify_resend: &'a str,
+    email_verify_sent: &'a str,
     family_name: &'a str,
     federated_convert_password_1: &'a str,
     federated_convert_password_2: &'a str,
@@ -58,6 +65,9 @@ pub struct I18nAccount<'a> {
     password_policy_follow: &'a str,
     password_reset: &'a str,
     phone: &'a str,
+    picture: &'a str,
+    picture_delete: &'a str,
+    picture_upload: &'a str,
     provider_link: &'a str,
     provider_link_desc: &'a str,
     provider_unlink: &'a str,
@@ -111,6 +121,11 @@ least once."#,
             access_exp: "Access Expires",
             access_renew: "Access Renew Until",
             access_renew_delete: "Delete the possibility to renew",
+            apps: "Apps",
+            apps_desc: "Applications you have granted access to your account",
+            apps_none: "No applications have been granted access so far",
+            apps_revoke: "Revoke access",
+            apps_scopes: "Granted scopes",
             birthdate: "Birthdate",
             cancel: "Cancel",
             city: "City",
@@ -131,6 +146,8 @@ page."#,
 sent out to your new address. You need to click the confirmation link inside it. Once it has been
 confirmed, your new address will be updated."#,
             email_verified: "E-Mail verified",
+            email_verify_resend: "Resend verification",
+            email_verify_sent: "A new verification link has been sent to your E-Mail address.",
             family_name: "Family Name",
             federated_convert_password_1: r#"You have a federated account. This means you log in
 by using an external authentication provider. Your current provider is:"#,
@@ -164,6 +181,9 @@ external provider or by local password. Do you want to request a reset?"#,
             password_policy_follow: "You must follow the password policy",
             password_reset: "Password Reset",
             phone: "Phone",
+            picture: "Picture",
+            picture_delete: "Delete picture",
+            picture_upload: "Upload picture",
             provider_link: "Federate Account",
             provider_link_desc: r#"You can link this account to one of the following login providers.
 After activating this function, you will be redirected to the login page of the chosen one.
@@ -208,6 +228,11 @@ einmalige zusätzliche Verifizierung des Passwortes nicht mehr möglich sein wir
             access_exp: "Zugang erlischt",
             access_renew: "Zugang Erneuerung bis",
             access_renew_delete: "Möglichkeit zur Erneuerung löschen",
+            apps: "Apps",
+            apps_desc: "Anwendungen, denen Sie Zugriff auf Ihren Account gewährt haben",
+            apps_none: "Bisher wurde keiner Anwendung Zugriff gewährt",
+            apps_revoke: "Zugriff widerrufen",
+            apps_scopes: "Gewährte Scopes",
             birthdate: "Geburtsdatum",
             cancel: "Abbrechen",
             city: "Stadt",
@@ -227,6 +252,8 @@ Passkeys. Nur Passkeys mit zusätzlicher Benutzerverifizierung werden akzeptiert
 mit einem Bestätigungslink wurde an die neue Adresse geschickt. Das Update muss über den
 enthaltenen Link bestätigt werden. Nach der Bestätigung wird die neue Adresse gesetzt."#,
             email_verified: "E-Mail verifiziert",
+            email_verify_resend: "Bestätigung erneut senden",
+            email_verify_sent: "Ein neuer Bestätigungslink wurde an Ihre E-Mail Adresse gesendet.",
             family_name: "Nachname",
             federated_convert_password_1: r#"Dies ist ein verknüpfter Account. Das bedeutet, dass
 der Login via externem Provider zur Authenzifizierung geschieht. Der derzeitige Provider ist:"#,
@@ -260,6 +287,9 @@ Login entweder per externem Provider oder lokalem Password möglich. Passwort Re
             password_policy_follow: "Befolgen Sie die Passwort Regeln",
             password_reset: "Passwort Reset",
             phone: "Telefon",
+            picture: "Bild",
+            picture_delete: "Bild löschen",
+            picture_upload: "Bild hochladen",
             provider_link: "Account Verbinden",
             provider_link_desc: r#"Dieser Account kann mit einem der folgenden Login Provider
 verbunden werden. Nach der Aktivierung des Prozesses wird eine Weiterleitung auf die Login Seite
@@ -304,6 +334,11 @@ hinzufügen:"#,
             access_exp: "过期",
             access_renew: "续期至",
             access_renew_delete: "禁止续期",
+            apps: "应用",
+            apps_desc: "您已授权访问您账户的应用",
+            apps_none: "目前还没有授权任何应用",
+            apps_revoke: "撤销访问",
+            apps_scopes: "已授权的范围",
             birthdate: "生日",
             cancel: "取消",
             city: "城市",
@@ -322,6 +357,8 @@ hinzufügen:"#,
             email_update_confirm: r#"电子邮件地址未被更新。我们已向您的新邮箱发送了一封消息。
 您需要点击其中的确认链接，电子邮件地址将在被确认后更新。"#,
             email_verified: "已验证电子邮箱",
+            email_verify_resend: "重新发送验证邮件",
+            email_verify_sent: "新的验证链接已发送至您的电子邮箱。",
             family_name: "姓",
             federated_convert_password_1: r#"您有一个联合账户。
 这意味着您是通过外部鉴权提供者登陆的。您当前的提供者是："#,
@@ -354,6 +391,9 @@ hinzufügen:"#,
             password_policy_follow: "密码不符合要求。",
             password_reset: "重置密码",
             phone: "手机",
+            picture: "头像",
+            picture_delete: "删除头像",
+            picture_upload: "上传头像",
             provider_link: "联合账户",
             provider_link_desc: r#"您可以将此账户连接到下列登陆提供者之一。
 激活此功能后，您将被重定向至所选提供者的登陆页面。在成功登陆后，如果电子邮件匹配，您的账户将被连接。"#,
@@ -393,6 +433,11 @@ WebID被一些网络用于去中心化登陆。如果您不知道这是什么，
             access_exp: "접근 만료",
             access_renew: "접근 갱신 기한",
             access_renew_delete: "갱신할 가능성을 제거",
+            apps: "앱",
+            apps_desc: "계정에 대한 접근을 허용한 애플리케이션",
+            apps_none: "아직 접근을 허용한 애플리케이션이 없습니다",
+            apps_revoke: "접근 취소",
+            apps_scopes: "허용된 범위",
             birthdate: "생년월일",
             cancel: "취소",
             city: "도시",
@@ -411,6 +456,8 @@ WebID被一些网络用于去中心化登陆。如果您不知道这是什么，
             email_update_confirm: r#"아직 이메일 주소가 변경되지 않았습니다. 새 주소로 메시지가 전송되었습니다.
 내부에 있는 승인 링크를 클릭해야 합니다. 승인되면 이메일 주소가 새 주소로 변경될 겁니다."#,
             email_verified: "이메일 인증 여부",
+            email_verify_resend: "인증 메일 재전송",
+            email_verify_sent: "새로운 인증 링크가 이메일 주소로 전송되었습니다.",
             family_name: "성",
             federated_convert_password_1: r#"페더레이션 계정을 가지고 있습니다. 즉, 외부 인증 제공자를 사용하여
 로그인한다는 것을 의미합니다. 현재 제공자는 다음과 같습니다:"#,
@@ -443,6 +490,9 @@ WebID被一些网络用于去中心化登陆。如果您不知道这是什么，
             password_policy_follow: "비밀번호 정책을 준수해야 합니다.",
             password_reset: "비밀번호 초기화",
             phone: "전화번호",
+            picture: "프로필 사진",
+            picture_delete: "사진 삭제",
+            picture_upload: "사진 업로드",
             provider_link: "페더레이션 계정",
             provider_link_desc: r#"이 계정은 다음 중 하나의 로그인 제공자에 연결할 수 있습니다.
 이 기능을 활성화하면, 선택한 것의 로그인 페이지로 리다이렉트될 겁니다. 성공적으로 로그인하고 이메일이 일치하면, 계정이 연결될 겁니다."#,
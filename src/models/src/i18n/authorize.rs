@@ -5,7 +5,11 @@ use serde::Serialize;
 #[derive(Debug, Default, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct I18nAuthorize<'a> {
+    client_access_denied: &'a str,
     client_force_mfa: &'a str,
+    consent_accept: &'a str,
+    consent_decline: &'a str,
+    consent_info: &'a str,
     email: &'a str,
     email_bad_format: &'a str,
     email_required: &'a str,
@@ -19,9 +23,16 @@ pub struct I18nAuthorize<'a> {
     password_forgotten: &'a str,
     password_request: &'a str,
     password_required: &'a str,
+    profile_info: &'a str,
+    profile_submit: &'a str,
     provide_mfa: &'a str,
+    remember_me: &'a str,
     request_expires: &'a str,
     sign_up: &'a str,
+    tos_accept: &'a str,
+    tos_info: &'a str,
+    tos_link: &'a str,
+    trust_device: &'a str,
 }
 
 impl SsrJson for I18nAuthorize<'_> {
@@ -42,8 +53,13 @@ impl SsrJson for I18nAuthorize<'_> {
 impl I18nAuthorize<'_> {
     fn build_en() -> Self {
         Self {
+            client_access_denied: r#"You are not allowed to access this application.
+Please contact your administrator, if you think this is a mistake."#,
             client_force_mfa: r#"This login forces MFA to achieve higher security.
 To get access, you need to log in to your account and add at least one additional Passkey"#,
+            consent_accept: "Allow",
+            consent_decline: "Decline",
+            consent_info: "The application requests access to the following scopes:",
             email: "E-Mail",
             email_bad_format: "Bad E-Mail format",
             email_required: "E-Mail is required",
@@ -57,17 +73,29 @@ To get access, you need to log in to your account and add at least one additiona
             password_forgotten: "Password forgotten?",
             password_request: "Request",
             password_required: "Password is required",
+            profile_info: "Please complete your profile to continue.",
+            profile_submit: "Continue",
             provide_mfa: "Please login with your MFA device",
+            remember_me: "Remember me",
             request_expires: "Request expires",
             sign_up: "User Registration",
+            tos_accept: "Accept",
+            tos_info: "Please read and accept the current Terms of Service to continue.",
+            tos_link: "Terms of Service",
+            trust_device: "Trust this device",
         }
     }
 
     fn build_de() -> Self {
         Self {
+            client_access_denied: r#"Sie haben keinen Zugriff auf diese Anwendung.
+Bitte wenden Sie sich an Ihren Administrator, falls dies ein Fehler ist."#,
             client_force_mfa: r#"Dieser Login setzt MFA voraus für eine erhöhte Sicherheit.
 Um Zugang zu bekommen, müssen Sie sie in Ihren Account einloggen und mindestens einen Passkey
 hinzufügen."#,
+            consent_accept: "Erlauben",
+            consent_decline: "Ablehnen",
+            consent_info: "Die Anwendung fordert Zugriff auf die folgenden Scopes an:",
             email: "E-Mail",
             email_bad_format: "Inkorrektes E-Mail Format",
             email_required: "E-Mail ist notwendig",
@@ -81,16 +109,27 @@ hinzufügen."#,
             password_forgotten: "Password vergessen?",
             password_request: "Anfordern",
             password_required: "Password ist notwendig",
+            profile_info: "Bitte vervollständigen Sie Ihr Profil, um fortzufahren.",
+            profile_submit: "Weiter",
             provide_mfa: "Bitte stellen Sie Ihr MFA Gerät zur Verfügung",
+            remember_me: "Angemeldet bleiben",
             request_expires: "Anfrage läuft ab",
             sign_up: "Benutzer Registrierung",
+            tos_accept: "Akzeptieren",
+            tos_info: "Bitte lesen und akzeptieren Sie die aktuellen Nutzungsbedingungen, um fortzufahren.",
+            tos_link: "Nutzungsbedingungen",
+            trust_device: "Diesem Gerät vertrauen",
         }
     }
 
     fn build_zh_hans() -> Self {
         Self {
+            client_access_denied: "您无权访问此应用。如果您认为这是一个错误，请联系管理员。",
             client_force_mfa: r#"本次登陆强制使用多因子认证以增强安全性。
 要完成登陆，请登入您的账户并添加一个登陆密钥。"#,
+            consent_accept: "允许",
+            consent_decline: "拒绝",
+            consent_info: "该应用请求访问以下范围：",
             email: "电子邮件地址",
             email_bad_format: "错误的电子邮件地址格式",
             email_required: "电子邮件地址必填。",
@@ -104,16 +143,28 @@ hinzufügen."#,
             password_forgotten: "忘记密码",
             password_request: "请求",
             password_required: "密码必填。",
+            profile_info: "请完善您的个人资料以继续。",
+            profile_submit: "继续",
             provide_mfa: "请使用MFA设备登陆",
+            remember_me: "记住我",
             request_expires: "请求过期",
             sign_up: "用户注册",
+            tos_accept: "接受",
+            tos_info: "请阅读并接受当前的服务条款以继续。",
+            tos_link: "服务条款",
+            trust_device: "信任此设备",
         }
     }
 
     fn build_ko() -> Self {
         Self {
+            client_access_denied:
+                "이 애플리케이션에 접근할 권한이 없습니다. 잘못된 경우 관리자에게 문의하세요.",
             client_force_mfa: r#"이 로그인은 더 높은 수준의 보안을 위해서 MFA를 강제합니다.
 접근하려면, 계정에 로그인하고 최소 하나 이상의 패스키를 추가해야 합니다."#,
+            consent_accept: "허용",
+            consent_decline: "거부",
+            consent_info: "애플리케이션이 다음 범위에 대한 접근을 요청합니다:",
             email: "이메일",
             email_bad_format: "잘못된 이메일 형식입니다.",
             email_required: "이메일이 필요합니다.",
@@ -127,9 +178,16 @@ hinzufügen."#,
             password_forgotten: "비밀번호를 잊으셨나요?",
             password_request: "요청",
             password_required: "비밀번호는 필요합니다.",
+            profile_info: "계속하려면 프로필을 완성해 주세요.",
+            profile_submit: "계속",
             provide_mfa: "MFA 기기를 통해 로그인해 주세요.",
+            remember_me: "로그인 상태 유지",
             request_expires: "요청 만료일",
             sign_up: "사용자 가입",
+            tos_accept: "동의",
+            tos_info: "계속하려면 현재 서비스 약관을 읽고 동의해 주세요.",
+            tos_link: "서비스 약관",
+            trust_device: "이 기기 신뢰",
         }
     }
 }
@@ -5,7 +5,9 @@ use serde::Serialize;
 #[derive(Debug, Serialize)]
 pub struct I18nEmailChangeInfoNew<'a> {
     pub subject: &'a str,
+    pub subject_verify: &'a str,
     pub header: &'a str,
+    pub header_verify: &'a str,
     pub click_link: &'a str,
     pub validity: &'a str,
     pub expires: &'a str,
@@ -31,7 +33,9 @@ impl I18nEmailChangeInfoNew<'_> {
     fn build_en() -> Self {
         Self {
             subject: "E-Mail Change Request",
+            subject_verify: "E-Mail Verification",
             header: "E-Mail change request for",
+            header_verify: "E-Mail verification for",
             click_link: "Click the link below to confirm your E-Mail address.",
             validity: "This link is only valid for a short period of time for security reasons.",
             expires: "Link expires:",
@@ -42,7 +46,9 @@ impl I18nEmailChangeInfoNew<'_> {
     fn build_de() -> Self {
         Self {
             subject: "E-Mail Wechsel Anfrage",
+            subject_verify: "E-Mail Bestätigung",
             header: "E-Mail Wechsel angefordert für",
+            header_verify: "E-Mail Bestätigung für",
             click_link:
                 "Klicken Sie auf den unten stehenden Link die E-Mail Adresse zu bestätigen.",
             validity: "Dieser Link ist aus Sicherheitsgründen nur für kurze Zeit gültig.",
@@ -54,7 +60,9 @@ impl I18nEmailChangeInfoNew<'_> {
     fn build_zh_hans() -> Self {
         Self {
             subject: "电子邮件更改请求",
+            subject_verify: "电子邮件验证",
             header: "电子邮件更改请求：",
+            header_verify: "电子邮件验证：",
             click_link: "点击下方链接以确认您的电子邮件地址。",
             validity: "出于安全考虑，此链接仅在短时间内有效。",
             expires: "链接过期时间：",
@@ -65,7 +73,9 @@ impl I18nEmailChangeInfoNew<'_> {
     fn build_ko() -> Self {
         Self {
             subject: "이메일 변경 요청",
+            subject_verify: "이메일 인증",
             header: "이메일 변경 요청:",
+            header_verify: "이메일 인증:",
             click_link: "이메일 주소를 승인하려면 아래에 있는 링크를 클릭해 주세요.",
             validity: "이 링크는 보안상의 이유로 짧은 시간 동안에만 유효합니다.",
             expires: "링크 만료일:",
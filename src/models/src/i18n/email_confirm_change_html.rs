@@ -5,8 +5,10 @@ use serde::Serialize;
 #[derive(Debug, Serialize)]
 pub struct I18nEmailConfirmChangeHtml<'a> {
     pub title: &'a str,
+    pub title_verified: &'a str,
     pub text_changed: &'a str,
     pub text_login: &'a str,
+    pub text_verified: &'a str,
     pub to: &'a str,
 }
 
@@ -29,8 +31,10 @@ impl I18nEmailConfirmChangeHtml<'_> {
     fn build_en() -> Self {
         Self {
             title: "E-Mail Change confirmed",
+            title_verified: "E-Mail verified",
             text_changed: "Your E-Mail address has been changed from",
             text_login: "You can now log in using your new address.",
+            text_verified: "Your E-Mail address has been verified successfully:",
             to: "to",
         }
     }
@@ -38,8 +42,10 @@ impl I18nEmailConfirmChangeHtml<'_> {
     fn build_de() -> Self {
         Self {
             title: "E-Mail Wechsel bestätigt",
+            title_verified: "E-Mail bestätigt",
             text_changed: "Ihre E-Mail Adresse wurde erfolgreich geändert von",
             text_login: "Sie können sich jetzt mit der neuen Adresse einloggen.",
+            text_verified: "Ihre E-Mail Adresse wurde erfolgreich bestätigt:",
             to: "zu",
         }
     }
@@ -47,8 +53,10 @@ impl I18nEmailConfirmChangeHtml<'_> {
     fn build_zh_hans() -> Self {
         Self {
             title: "电子邮件地址已更新",
+            title_verified: "电子邮件地址已验证",
             text_changed: "您的电子邮件地址已从",
             text_login: "您现在可以使用您的新地址进行登陆。",
+            text_verified: "您的电子邮件地址已成功验证：",
             to: "更新为",
         }
     }
@@ -56,8 +64,10 @@ impl I18nEmailConfirmChangeHtml<'_> {
     fn build_ko() -> Self {
         Self {
             title: "이메일 변경이 승인되었습니다.",
+            title_verified: "이메일이 인증되었습니다.",
             text_changed: "이메일 주소가 다음으로부터 변경되었습니다",
             text_login: "이제 새로운 주소로 로그인할 수 있습니다.",
+            text_verified: "이메일 주소가 성공적으로 인증되었습니다:",
             to: "에서",
         }
     }
@@ -0,0 +1,287 @@
+use crate::entity::jwk::JwkKeyPairAlg;
+use crate::kms::{ecdsa_der_to_raw, Kms, KmsProvider};
+use async_trait::async_trait;
+use jwt_simple::algorithms::{RS256KeyPair, RSAKeyPairLike};
+use jwt_simple::claims::Claims;
+use jwt_simple::prelude::coarsetime;
+use rauthy_common::utils::{base64_decode, base64_encode};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rsa::sha2::{Digest, Sha256, Sha384, Sha512};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::env;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+const API_URL: &str = "https://cloudkms.googleapis.com/v1";
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+const SCOPE: &str = "https://www.googleapis.com/auth/cloudkms";
+
+/// Signs tokens with asymmetric keys inside the Google Cloud KMS. The keys are configured with
+/// their full key version name like
+/// `projects/{project}/locations/{location}/keyRings/{ring}/cryptoKeys/{key}/cryptoKeyVersions/1`.
+///
+/// Access tokens are created from the service account key in `GOOGLE_APPLICATION_CREDENTIALS`,
+/// if set, and fetched from the metadata server otherwise.
+pub struct KmsGcp {
+    credentials: Option<ServiceAccountKey>,
+    access_token: Mutex<Option<(String, Instant)>>,
+    key_rs256: Option<String>,
+    key_rs384: Option<String>,
+    key_rs512: Option<String>,
+    key_es384: Option<String>,
+    key_eddsa: Option<String>,
+}
+
+impl KmsGcp {
+    pub fn from_env() -> Result<Self, ErrorResponse> {
+        let credentials = match env::var("GOOGLE_APPLICATION_CREDENTIALS") {
+            Ok(path) => {
+                let file = std::fs::read_to_string(&path).map_err(|err| {
+                    ErrorResponse::new(
+                        ErrorResponseType::Internal,
+                        format!(
+                            "Cannot read GOOGLE_APPLICATION_CREDENTIALS {}: {}",
+                            path, err
+                        ),
+                    )
+                })?;
+                let key = serde_json::from_str::<ServiceAccountKey>(&file).map_err(|err| {
+                    ErrorResponse::new(
+                        ErrorResponseType::Internal,
+                        format!("Invalid service account key in {}: {}", path, err),
+                    )
+                })?;
+                Some(key)
+            }
+            Err(_) => None,
+        };
+
+        let slf = Self {
+            credentials,
+            access_token: Mutex::new(None),
+            key_rs256: env::var("JWK_KMS_KEY_RS256").ok(),
+            key_rs384: env::var("JWK_KMS_KEY_RS384").ok(),
+            key_rs512: env::var("JWK_KMS_KEY_RS512").ok(),
+            key_es384: env::var("JWK_KMS_KEY_ES384").ok(),
+            key_eddsa: env::var("JWK_KMS_KEY_EDDSA").ok(),
+        };
+
+        if slf.key_rs256.is_none()
+            && slf.key_rs384.is_none()
+            && slf.key_rs512.is_none()
+            && slf.key_es384.is_none()
+            && slf.key_eddsa.is_none()
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Internal,
+                "At least one of JWK_KMS_KEY_RS256, JWK_KMS_KEY_RS384, JWK_KMS_KEY_RS512, \
+                JWK_KMS_KEY_ES384 or JWK_KMS_KEY_EDDSA must be set for the GCP KMS",
+            ));
+        }
+
+        Ok(slf)
+    }
+
+    /// Returns a cached access token, as long as it is valid for at least another minute.
+    async fn access_token(&self) -> Result<String, ErrorResponse> {
+        let mut lock = self.access_token.lock().await;
+        if let Some((token, valid_until)) = lock.as_ref() {
+            if *valid_until > Instant::now() {
+                return Ok(token.clone());
+            }
+        }
+
+        let res = match &self.credentials {
+            Some(credentials) => {
+                debug!(
+                    "Fetching a new GCP access token for {}",
+                    credentials.client_email
+                );
+                Kms::client()
+                    .post(&credentials.token_uri)
+                    .form(&[
+                        ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                        ("assertion", &credentials.assertion()?),
+                    ])
+                    .send()
+                    .await
+            }
+            None => {
+                debug!("Fetching a new GCP access token from the metadata server");
+                Kms::client()
+                    .get(METADATA_TOKEN_URL)
+                    .header("Metadata-Flavor", "Google")
+                    .send()
+                    .await
+            }
+        };
+        let token = Self::parse_response::<TokenResponse>(res).await?;
+
+        let valid_for = Duration::from_secs(token.expires_in.saturating_sub(60));
+        *lock = Some((token.access_token.clone(), Instant::now() + valid_for));
+
+        Ok(token.access_token)
+    }
+
+    async fn parse_response<T: DeserializeOwned>(
+        res: reqwest::Result<reqwest::Response>,
+    ) -> Result<T, ErrorResponse> {
+        let res = res.map_err(|err| Kms::err_kms("GCP", err))?;
+        if !res.status().is_success() {
+            let status = res.status();
+            let text = res.text().await.unwrap_or_default();
+            return Err(Kms::err_kms("GCP", format!("{} - {}", status, text)));
+        }
+
+        res.json::<T>()
+            .await
+            .map_err(|err| Kms::err_kms("GCP", err))
+    }
+}
+
+#[async_trait]
+impl KmsProvider for KmsGcp {
+    fn key_id(&self, alg: &JwkKeyPairAlg) -> Option<&str> {
+        match alg {
+            JwkKeyPairAlg::RS256 => self.key_rs256.as_deref(),
+            JwkKeyPairAlg::RS384 => self.key_rs384.as_deref(),
+            JwkKeyPairAlg::RS512 => self.key_rs512.as_deref(),
+            JwkKeyPairAlg::ES384 => self.key_es384.as_deref(),
+            JwkKeyPairAlg::EdDSA => self.key_eddsa.as_deref(),
+        }
+    }
+
+    async fn public_key(&self, key_id: &str) -> Result<Vec<u8>, ErrorResponse> {
+        debug!("Fetching the public key for {} from the GCP KMS", key_id);
+
+        let res = Kms::client()
+            .get(format!("{}/{}/publicKey", API_URL, key_id))
+            .bearer_auth(self.access_token().await?)
+            .send()
+            .await;
+        let res = Self::parse_response::<PublicKeyResponse>(res).await?;
+        pem_to_der(&res.pem)
+    }
+
+    async fn sign(
+        &self,
+        key_id: &str,
+        alg: &JwkKeyPairAlg,
+        input: &[u8],
+    ) -> Result<Vec<u8>, ErrorResponse> {
+        let payload = match alg {
+            JwkKeyPairAlg::RS256 => {
+                json!({ "digest": { "sha256": base64_encode(&Sha256::digest(input)) } })
+            }
+            JwkKeyPairAlg::RS384 | JwkKeyPairAlg::ES384 => {
+                json!({ "digest": { "sha384": base64_encode(&Sha384::digest(input)) } })
+            }
+            JwkKeyPairAlg::RS512 => {
+                json!({ "digest": { "sha512": base64_encode(&Sha512::digest(input)) } })
+            }
+            // Ed25519 keys sign the full message instead of a digest
+            JwkKeyPairAlg::EdDSA => json!({ "data": base64_encode(input) }),
+        };
+
+        let res = Kms::client()
+            .post(format!("{}/{}:asymmetricSign", API_URL, key_id))
+            .bearer_auth(self.access_token().await?)
+            .json(&payload)
+            .send()
+            .await;
+        let res = Self::parse_response::<SignResponse>(res).await?;
+        let signature = base64_decode(&res.signature)?;
+
+        if *alg == JwkKeyPairAlg::ES384 {
+            ecdsa_der_to_raw("GCP", &signature)
+        } else {
+            Ok(signature)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    #[serde(default = "default_token_uri")]
+    token_uri: String,
+}
+
+fn default_token_uri() -> String {
+    "https://oauth2.googleapis.com/token".to_string()
+}
+
+impl ServiceAccountKey {
+    /// The self-signed JWT for the `jwt-bearer` grant
+    fn assertion(&self) -> Result<String, ErrorResponse> {
+        #[derive(Serialize, Deserialize)]
+        struct Scope {
+            scope: String,
+        }
+
+        let key = RS256KeyPair::from_pem(&self.private_key)
+            .map_err(|err| Kms::err_kms("GCP", format!("invalid service account key: {}", err)))?;
+        let claims = Claims::with_custom_claims(
+            Scope {
+                scope: SCOPE.to_string(),
+            },
+            coarsetime::Duration::from_secs(3600),
+        )
+        .with_issuer(&self.client_email)
+        .with_audience(&self.token_uri);
+
+        key.sign(claims)
+            .map_err(|err| Kms::err_kms("GCP", format!("cannot sign the assertion: {}", err)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct PublicKeyResponse {
+    pem: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignResponse {
+    signature: String,
+}
+
+/// Extracts the DER encoded `SubjectPublicKeyInfo` from a `PUBLIC KEY` PEM.
+fn pem_to_der(pem: &str) -> Result<Vec<u8>, ErrorResponse> {
+    let b64 = pem
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with("-----"))
+        .collect::<String>();
+    base64_decode(&b64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rsa::pkcs8::{EncodePublicKey, LineEnding};
+
+    #[test]
+    fn test_pem_to_der() {
+        let key = p384::SecretKey::from_slice(&[7u8; 48])
+            .unwrap()
+            .public_key();
+        let der = key.to_public_key_der().unwrap().into_vec();
+        let pem = key.to_public_key_pem(LineEnding::LF).unwrap();
+
+        assert_eq!(pem_to_der(&pem).unwrap(), der);
+        // the GCP KMS returns the PEM with a trailing newline
+        assert_eq!(pem_to_der(&format!("{}\n", pem)).unwrap(), der);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::entity::jwk::JwkKeyPairAlg;
+use crate::kms::aws::KmsAws;
+use crate::kms::gcp::KmsGcp;
+#[cfg(feature = "pkcs11")]
+use crate::kms::pkcs11::KmsPkcs11;
+use async_trait::async_trait;
+use rauthy_common::constants::RAUTHY_VERSION;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use reqwest::tls;
+use std::env;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::info;
+
+pub mod aws;
+pub mod gcp;
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+
+static KMS_PROVIDER: OnceLock<Box<dyn KmsProvider>> = OnceLock::new();
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// An external key management system or HSM, which holds the private keys for token signing.
+/// Any backend can be plugged in by implementing this trait and passing it to `Kms::init_with()`.
+#[async_trait]
+pub trait KmsProvider: Send + Sync {
+    /// The provider specific ID of the key, which should sign new tokens with the given
+    /// algorithm. With `None`, a locally generated key is used for this algorithm.
+    fn key_id(&self, alg: &JwkKeyPairAlg) -> Option<&str>;
+
+    /// Returns the public key as DER encoded `SubjectPublicKeyInfo`.
+    async fn public_key(&self, key_id: &str) -> Result<Vec<u8>, ErrorResponse>;
+
+    /// Signs the JWS signing input. ECDSA signatures must be returned as `r || s` with a fixed
+    /// size, which is the format JWS expects.
+    async fn sign(
+        &self,
+        key_id: &str,
+        alg: &JwkKeyPairAlg,
+        input: &[u8],
+    ) -> Result<Vec<u8>, ErrorResponse>;
+}
+
+pub struct Kms;
+
+impl Kms {
+    /// Builds the provider from `JWK_KMS_PROVIDER`, if it is set. Without a provider, all signing
+    /// keys are generated locally and stored encrypted inside the database.
+    pub fn init() -> Result<(), ErrorResponse> {
+        let Ok(name) = env::var("JWK_KMS_PROVIDER") else {
+            return Ok(());
+        };
+
+        let provider: Box<dyn KmsProvider> = match name.as_str() {
+            "aws" => Box::new(KmsAws::from_env()?),
+            "gcp" => Box::new(KmsGcp::from_env()?),
+            #[cfg(feature = "pkcs11")]
+            "pkcs11" => Box::new(KmsPkcs11::from_env()?),
+            #[cfg(not(feature = "pkcs11"))]
+            "pkcs11" => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    "JWK_KMS_PROVIDER 'pkcs11' needs Rauthy to be built with the 'pkcs11' feature",
+                ));
+            }
+            _ => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    "JWK_KMS_PROVIDER must be one of: aws, gcp, pkcs11",
+                ));
+            }
+        };
+        info!("External signing keys enabled with KMS provider {}", name);
+        Self::init_with(provider);
+
+        Ok(())
+    }
+
+    /// Sets a custom `KmsProvider`. Can only be called once.
+    pub fn init_with(provider: Box<dyn KmsProvider>) {
+        if KMS_PROVIDER.set(provider).is_err() {
+            panic!("Kms has already been initialized");
+        }
+    }
+
+    /// Returns the ID of the KMS key, which signs new tokens for this algorithm, if any.
+    #[inline]
+    pub fn key_id(alg: &JwkKeyPairAlg) -> Option<&'static str> {
+        KMS_PROVIDER.get().and_then(|provider| provider.key_id(alg))
+    }
+
+    pub async fn public_key(key_id: &str) -> Result<Vec<u8>, ErrorResponse> {
+        Self::provider()?.public_key(key_id).await
+    }
+
+    pub async fn sign(
+        key_id: &str,
+        alg: &JwkKeyPairAlg,
+        input: &[u8],
+    ) -> Result<Vec<u8>, ErrorResponse> {
+        Self::provider()?.sign(key_id, alg, input).await
+    }
+
+    fn provider() -> Result<&'static dyn KmsProvider, ErrorResponse> {
+        KMS_PROVIDER
+            .get()
+            .map(|provider| provider.as_ref())
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    "No KMS provider has been configured",
+                )
+            })
+    }
+
+    pub(crate) fn client() -> &'static reqwest::Client {
+        HTTP_CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!("Rauthy v{} KMS", RAUTHY_VERSION))
+                .min_tls_version(tls::Version::TLS_1_2)
+                .pool_idle_timeout(Duration::from_secs(600))
+                .build()
+                .unwrap()
+        })
+    }
+
+    pub(crate) fn err_kms(provider: &str, err: impl std::fmt::Display) -> ErrorResponse {
+        ErrorResponse::new(
+            ErrorResponseType::Connection,
+            format!("Error from the {} KMS: {}", provider, err),
+        )
+    }
+
+    fn env_required(name: &str) -> Result<String, ErrorResponse> {
+        env::var(name).map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("{} must be set for the configured JWK_KMS_PROVIDER", name),
+            )
+        })
+    }
+}
+
+/// Cloud KMS return ECDSA signatures DER encoded, while JWS needs the plain `r || s`.
+pub(crate) fn ecdsa_der_to_raw(provider: &str, der: &[u8]) -> Result<Vec<u8>, ErrorResponse> {
+    let signature = p384::ecdsa::Signature::from_der(der)
+        .map_err(|err| Kms::err_kms(provider, format!("invalid ECDSA signature: {}", err)))?;
+    Ok(signature.to_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p384::ecdsa::signature::Signer;
+    use p384::ecdsa::{Signature, SigningKey};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_ecdsa_der_to_raw() {
+        let key = SigningKey::from_slice(&[7u8; 48]).unwrap();
+        let signature: Signature = key.sign(b"header.payload");
+
+        let raw = ecdsa_der_to_raw("Test", signature.to_der().as_bytes()).unwrap();
+        assert_eq!(raw.len(), 96);
+        assert_eq!(raw, signature.to_bytes().to_vec());
+
+        assert!(ecdsa_der_to_raw("Test", &raw).is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use crate::entity::jwk::JwkKeyPairAlg;
+use crate::kms::{Kms, KmsProvider};
+use async_trait::async_trait;
+use cryptoki::context::{CInitializeArgs, Pkcs11};
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, AttributeType, KeyType, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::types::AuthPin;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rsa::pkcs8::EncodePublicKey;
+use rsa::sha2::{Digest, Sha384};
+use rsa::{BigUint, RsaPublicKey};
+use std::env;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info};
+
+/// The DER encoded `SubjectPublicKeyInfo` of an Ed25519 key without the 32 byte key itself.
+const ED25519_SPKI_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+/// Signs tokens with keys inside an HSM, which is accessed through its PKCS#11 module. The keys
+/// are found by their `CKA_LABEL`, which must be the same for the private and the public key.
+///
+/// Only available with the `pkcs11` feature, because the module is loaded dynamically.
+pub struct KmsPkcs11 {
+    session: Arc<Mutex<Session>>,
+    key_rs256: Option<String>,
+    key_rs384: Option<String>,
+    key_rs512: Option<String>,
+    key_es384: Option<String>,
+    key_eddsa: Option<String>,
+}
+
+impl KmsPkcs11 {
+    pub fn from_env() -> Result<Self, ErrorResponse> {
+        let module = Kms::env_required("JWK_KMS_PKCS11_MODULE")?;
+        let token_label = Kms::env_required("JWK_KMS_PKCS11_TOKEN_LABEL")?;
+        let pin = Kms::env_required("JWK_KMS_PKCS11_PIN")?;
+
+        let pkcs11 = Pkcs11::new(&module).map_err(|err| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("Cannot load the PKCS#11 module {}: {}", module, err),
+            )
+        })?;
+        pkcs11
+            .initialize(CInitializeArgs::OsThreads)
+            .map_err(err_pkcs11)?;
+
+        let mut slot = None;
+        for s in pkcs11.get_slots_with_token().map_err(err_pkcs11)? {
+            let info = pkcs11.get_token_info(s).map_err(err_pkcs11)?;
+            if info.label() == token_label {
+                slot = Some(s);
+                break;
+            }
+        }
+        let Some(slot) = slot else {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("No PKCS#11 token with the label {} found", token_label),
+            ));
+        };
+
+        let session = pkcs11.open_ro_session(slot).map_err(err_pkcs11)?;
+        session
+            .login(UserType::User, Some(&AuthPin::new(pin)))
+            .map_err(err_pkcs11)?;
+        info!("Logged into the PKCS#11 token {}", token_label);
+
+        let slf = Self {
+            session: Arc::new(Mutex::new(session)),
+            key_rs256: env::var("JWK_KMS_KEY_RS256").ok(),
+            key_rs384: env::var("JWK_KMS_KEY_RS384").ok(),
+            key_rs512: env::var("JWK_KMS_KEY_RS512").ok(),
+            key_es384: env::var("JWK_KMS_KEY_ES384").ok(),
+            key_eddsa: env::var("JWK_KMS_KEY_EDDSA").ok(),
+        };
+
+        if slf.key_rs256.is_none()
+            && slf.key_rs384.is_none()
+            && slf.key_rs512.is_none()
+            && slf.key_es384.is_none()
+            && slf.key_eddsa.is_none()
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::Internal,
+                "At least one of JWK_KMS_KEY_RS256, JWK_KMS_KEY_RS384, JWK_KMS_KEY_RS512, \
+                JWK_KMS_KEY_ES384 or JWK_KMS_KEY_EDDSA must be set for PKCS#11",
+            ));
+        }
+
+        Ok(slf)
+    }
+
+    /// All PKCS#11 calls are blocking and a session must not be used concurrently.
+    async fn with_session<F, T>(&self, f: F) -> Result<T, ErrorResponse>
+    where
+        F: FnOnce(&Session) -> Result<T, ErrorResponse> + Send + 'static,
+        T: Send + 'static,
+    {
+        let session = self.session.clone();
+        tokio::task::spawn_blocking(move || {
+            let session = session.lock().unwrap();
+            f(&session)
+        })
+        .await
+        .map_err(|err| Kms::err_kms("PKCS#11", err))?
+    }
+}
+
+#[async_trait]
+impl KmsProvider for KmsPkcs11 {
+    fn key_id(&self, alg: &JwkKeyPairAlg) -> Option<&str> {
+        match alg {
+            JwkKeyPairAlg::RS256 => self.key_rs256.as_deref(),
+            JwkKeyPairAlg::RS384 => self.key_rs384.as_deref(),
+            JwkKeyPairAlg::RS512 => self.key_rs512.as_deref(),
+            JwkKeyPairAlg::ES384 => self.key_es384.as_deref(),
+            JwkKeyPairAlg::EdDSA => self.key_eddsa.as_deref(),
+        }
+    }
+
+    async fn public_key(&self, key_id: &str) -> Result<Vec<u8>, ErrorResponse> {
+        debug!("Reading the public key {} from PKCS#11", key_id);
+
+        let label = key_id.to_string();
+        self.with_session(move |session| {
+            let handle = find_key(session, ObjectClass::PUBLIC_KEY, &label)?;
+            let attrs = session
+                .get_attributes(
+                    handle,
+                    &[
+                        AttributeType::KeyType,
+                        AttributeType::Modulus,
+                        AttributeType::PublicExponent,
+                        AttributeType::EcPoint,
+                    ],
+                )
+                .map_err(err_pkcs11)?;
+
+            let mut key_type = None;
+            let mut modulus = None;
+            let mut exponent = None;
+            let mut ec_point = None;
+            for attr in attrs {
+                match attr {
+                    Attribute::KeyType(typ) => key_type = Some(typ),
+                    Attribute::Modulus(n) => modulus = Some(n),
+                    Attribute::PublicExponent(e) => exponent = Some(e),
+                    Attribute::EcPoint(point) => ec_point = Some(point),
+                    _ => {}
+                }
+            }
+
+            match (key_type, modulus, exponent, ec_point) {
+                (Some(KeyType::RSA), Some(n), Some(e), _) => rsa_spki(&n, &e),
+                (Some(KeyType::EC), _, _, Some(point)) => p384_spki(&point),
+                (Some(KeyType::EC_EDWARDS), _, _, Some(point)) => ed25519_spki(&point),
+                _ => Err(Kms::err_kms(
+                    "PKCS#11",
+                    format!("unsupported key type for {}", label),
+                )),
+            }
+        })
+        .await
+    }
+
+    async fn sign(
+        &self,
+        key_id: &str,
+        alg: &JwkKeyPairAlg,
+        input: &[u8],
+    ) -> Result<Vec<u8>, ErrorResponse> {
+        let (mechanism, data) = match alg {
+            JwkKeyPairAlg::RS256 => (Mechanism::Sha256RsaPkcs, input.to_vec()),
+            JwkKeyPairAlg::RS384 => (Mechanism::Sha384RsaPkcs, input.to_vec()),
+            JwkKeyPairAlg::RS512 => (Mechanism::Sha512RsaPkcs, input.to_vec()),
+            // `CKM_ECDSA` signs a pre-computed digest and already returns `r || s`
+            JwkKeyPairAlg::ES384 => (Mechanism::Ecdsa, Sha384::digest(input).to_vec()),
+            JwkKeyPairAlg::EdDSA => (Mechanism::Eddsa, input.to_vec()),
+        };
+
+        let label = key_id.to_string();
+        self.with_session(move |session| {
+            let handle = find_key(session, ObjectClass::PRIVATE_KEY, &label)?;
+            session.sign(&mechanism, handle, &data).map_err(err_pkcs11)
+        })
+        .await
+    }
+}
+
+fn err_pkcs11(err: cryptoki::error::Error) -> ErrorResponse {
+    Kms::err_kms("PKCS#11", err)
+}
+
+fn find_key(
+    session: &Session,
+    class: ObjectClass,
+    label: &str,
+) -> Result<ObjectHandle, ErrorResponse> {
+    let handles = session
+        .find_objects(&[
+            Attribute::Class(class),
+            Attribute::Label(label.as_bytes().to_vec()),
+        ])
+        .map_err(err_pkcs11)?;
+
+    match handles.as_slice() {
+        [handle] => Ok(*handle),
+        [] => Err(Kms::err_kms(
+            "PKCS#11",
+            format!("no {} with the label {} found", class, label),
+        )),
+        _ => Err(Kms::err_kms(
+            "PKCS#11",
+            format!("the label {} is not unique for {}", label, class),
+        )),
+    }
+}
+
+fn rsa_spki(modulus: &[u8], exponent: &[u8]) -> Result<Vec<u8>, ErrorResponse> {
+    let key = RsaPublicKey::new(
+        BigUint::from_bytes_be(modulus),
+        BigUint::from_bytes_be(exponent),
+    )
+    .map_err(|err| Kms::err_kms("PKCS#11", err))?;
+    let der = key
+        .to_public_key_der()
+        .map_err(|err| Kms::err_kms("PKCS#11", err))?;
+    Ok(der.into_vec())
+}
+
+fn p384_spki(ec_point: &[u8]) -> Result<Vec<u8>, ErrorResponse> {
+    // uncompressed SEC1 point: 0x04 || x || y
+    let point = unwrap_ec_point(ec_point, 97)?;
+    let key = p384::PublicKey::from_sec1_bytes(point)
+        .map_err(|err| Kms::err_kms("PKCS#11", format!("invalid P-384 key: {}", err)))?;
+    let der = key
+        .to_public_key_der()
+        .map_err(|err| Kms::err_kms("PKCS#11", err))?;
+    Ok(der.into_vec())
+}
+
+fn ed25519_spki(ec_point: &[u8]) -> Result<Vec<u8>, ErrorResponse> {
+    let point = unwrap_ec_point(ec_point, 32)?;
+    let mut der = ED25519_SPKI_PREFIX.to_vec();
+    der.extend_from_slice(point);
+    Ok(der)
+}
+
+/// `CKA_EC_POINT` should be a DER encoded OCTET STRING, but some modules return the plain point.
+fn unwrap_ec_point(ec_point: &[u8], len: usize) -> Result<&[u8], ErrorResponse> {
+    if ec_point.len() == len + 2 && ec_point[0] == 0x04 && ec_point[1] as usize == len {
+        Ok(&ec_point[2..])
+    } else if ec_point.len() == len {
+        Ok(ec_point)
+    } else {
+        Err(Kms::err_kms(
+            "PKCS#11",
+            format!("unexpected CKA_EC_POINT length {}", ec_point.len()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_public_key_spki() {
+        let key = p384::SecretKey::from_slice(&[7u8; 48])
+            .unwrap()
+            .public_key();
+        let point = key.to_sec1_bytes().to_vec();
+        let expected = key.to_public_key_der().unwrap().into_vec();
+
+        let mut wrapped = vec![0x04, 97];
+        wrapped.extend_from_slice(&point);
+        assert_eq!(p384_spki(&wrapped).unwrap(), expected);
+        assert_eq!(p384_spki(&point).unwrap(), expected);
+        assert!(p384_spki(&point[..96]).is_err());
+
+        let ed = ed25519_compact::KeyPair::from_seed(ed25519_compact::Seed::new([7u8; 32]));
+        let mut wrapped = vec![0x04, 32];
+        wrapped.extend_from_slice(ed.pk.as_ref());
+        let spki = ed25519_spki(&wrapped).unwrap();
+        let pk = jwt_simple::algorithms::Ed25519PublicKey::from_der(&spki).unwrap();
+        assert_eq!(pk.to_bytes(), ed.pk.to_vec());
+    }
+}
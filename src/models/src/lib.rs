@@ -6,7 +6,11 @@ use crate::entity::sessions::Session;
 use crate::entity::users::User;
 use crate::entity::users_values::UserValues;
 use actix_web::http::header::{HeaderName, HeaderValue};
-use rauthy_api_types::oidc::JktClaim;
+use jwt_simple::claims::{Claims, JWTClaims};
+use jwt_simple::prelude::{Duration, UnixTimeStamp};
+use rauthy_api_types::oidc::CnfClaim;
+use rauthy_api_types::users::UserAttrConfigValueResponse;
+use rauthy_common::clock;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -21,14 +25,21 @@ pub mod database;
 pub mod email;
 pub mod entity;
 pub mod events;
+pub mod geo_ip;
 pub mod i18n;
+pub mod kms;
 pub mod language;
 pub mod migration;
+pub mod sms;
+pub mod static_assets;
 pub mod templates;
 
 pub enum AuthStep {
     LoggedIn(AuthStepLoggedIn),
     AwaitWebauthn(AuthStepAwaitWebauthn),
+    AwaitTos(AuthStepAwaitTos),
+    AwaitProfile(AuthStepAwaitProfile),
+    AwaitConsent(AuthStepAwaitConsent),
     ProviderLink,
 }
 
@@ -38,6 +49,8 @@ pub struct AuthStepLoggedIn {
     pub header_loc: (HeaderName, HeaderValue),
     pub header_csrf: (HeaderName, HeaderValue),
     pub header_origin: Option<(HeaderName, HeaderValue)>,
+    /// The 2nd factor has been verified and the user wants to trust this device from now on
+    pub trust_device: bool,
 }
 
 pub struct AuthStepAwaitWebauthn {
@@ -47,9 +60,36 @@ pub struct AuthStepAwaitWebauthn {
     pub user_id: String,
     pub email: String,
     pub exp: u64,
+    /// The user can send a TOTP code with the password instead
+    pub has_totp: bool,
     pub session: Session,
 }
 
+/// The credentials are valid, but the current terms of service must be accepted first.
+pub struct AuthStepAwaitTos {
+    pub version: String,
+    pub url: Option<String>,
+    pub header_csrf: (HeaderName, HeaderValue),
+    pub header_origin: Option<(HeaderName, HeaderValue)>,
+}
+
+/// The credentials are valid, but values for custom attributes required at login are missing.
+pub struct AuthStepAwaitProfile {
+    pub attrs: Vec<UserAttrConfigValueResponse>,
+    pub header_csrf: (HeaderName, HeaderValue),
+    pub header_origin: Option<(HeaderName, HeaderValue)>,
+}
+
+/// The credentials are valid, but the client requires consent for scopes, which the user has not
+/// granted so far.
+pub struct AuthStepAwaitConsent {
+    pub client_id: String,
+    pub client_name: Option<String>,
+    pub scopes: Vec<String>,
+    pub header_csrf: (HeaderName, HeaderValue),
+    pub header_origin: Option<(HeaderName, HeaderValue)>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RequestId {
     pub id: String,
@@ -76,6 +116,26 @@ impl Display for ListenScheme {
     }
 }
 
+/// The current time from the Rauthy `clock` for issuing and validating JWTs. `jwt-simple` would
+/// read the system clock otherwise.
+#[inline]
+pub fn jwt_now() -> UnixTimeStamp {
+    UnixTimeStamp::from_secs(clock::now().timestamp().max(0) as u64)
+}
+
+/// Same as `Claims::with_custom_claims()`, but based on `jwt_now()`.
+pub fn jwt_claims<T>(custom_claims: T, valid_for: Duration) -> JWTClaims<T>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    let now = jwt_now();
+    let mut claims = Claims::with_custom_claims(custom_claims, valid_for);
+    claims.issued_at = Some(now);
+    claims.invalid_before = Some(now);
+    claims.expires_at = Some(now + valid_for);
+    claims
+}
+
 // This is used for the token info endpoint
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JwtCommonClaims {
@@ -87,7 +147,7 @@ pub struct JwtCommonClaims {
     pub preferred_username: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub did: Option<String>,
-    pub cnf: Option<JktClaim>,
+    pub cnf: Option<CnfClaim>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -171,6 +231,12 @@ impl From<AddressClaim> for rauthy_api_types::oidc::AddressClaim {
     }
 }
 
+/// RFC 8693 actor claim, which identifies the admin behind an impersonated session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActClaim {
+    pub sub: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JwtAccessClaims {
     pub typ: JwtTokenType,
@@ -180,6 +246,9 @@ pub struct JwtAccessClaims {
     pub allowed_origins: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub did: Option<String>,
+    /// The session the token has been issued for, which makes it possible to deny it on logout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
     // user part
     #[serde(skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
@@ -190,9 +259,13 @@ pub struct JwtAccessClaims {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub groups: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub cnf: Option<JktClaim>,
+    pub cnf: Option<CnfClaim>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub act: Option<ActClaim>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub custom: Option<HashMap<String, serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_metadata: Option<HashMap<String, serde_json::Value>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -200,8 +273,11 @@ pub struct JwtIdClaims {
     pub azp: String,
     pub typ: JwtTokenType,
     pub amr: Vec<String>,
+    pub acr: String,
     pub auth_time: i64,
     pub at_hash: String,
+    // may be renamed or dropped by a client claim template
+    #[serde(default)]
     pub preferred_username: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
@@ -219,15 +295,24 @@ pub struct JwtIdClaims {
     pub locale: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub phone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub picture: Option<String>,
+    #[serde(default)]
     pub roles: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub groups: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub cnf: Option<JktClaim>,
+    pub cnf: Option<CnfClaim>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub act: Option<ActClaim>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub custom: Option<HashMap<String, serde_json::Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub webid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_metadata: Option<HashMap<String, serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -238,7 +323,7 @@ pub struct JwtRefreshClaims {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub auth_time: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub cnf: Option<JktClaim>,
+    pub cnf: Option<CnfClaim>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub did: Option<String>,
 }
@@ -295,3 +380,111 @@ impl Display for JwtAmrValue {
         }
     }
 }
+
+/// The supported Authentication Context Class References. The order defines the level, and a
+/// higher level always satisfies a lower one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum JwtAcrValue {
+    Pwd,
+    Mfa,
+}
+
+impl JwtAcrValue {
+    pub fn all() -> [Self; 2] {
+        [Self::Pwd, Self::Mfa]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pwd => "urn:rauthy:acr:pwd",
+            Self::Mfa => "urn:rauthy:acr:mfa",
+        }
+    }
+
+    /// Returns the lowest level from the space separated `acr_values`, which would satisfy the
+    /// request. Unknown values will be ignored, because `acr_values` are voluntary.
+    pub fn min_requested(acr_values: Option<&str>) -> Option<Self> {
+        acr_values?
+            .split(' ')
+            .filter_map(|value| Self::all().into_iter().find(|acr| acr.as_str() == value))
+            .min()
+    }
+
+    /// Makes sure that a user can satisfy the requested `acr_values` at all.
+    /// `has_otp` must be set, if the user has TOTP, Yubico OTP or SMS OTP enabled.
+    pub fn validate_user(
+        acr_values: Option<&str>,
+        user: &User,
+        has_otp: bool,
+    ) -> Result<(), ErrorResponse> {
+        if Self::min_requested(acr_values) == Some(Self::Mfa)
+            && !user.has_webauthn_enabled()
+            && !has_otp
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::MfaRequired,
+                "MFA is required for the requested 'acr_values'",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Makes sure that an already authenticated session satisfies the requested `acr_values`.
+    pub fn validate_session(
+        acr_values: Option<&str>,
+        session: &Session,
+    ) -> Result<(), ErrorResponse> {
+        if Self::min_requested(acr_values) == Some(Self::Mfa) && !session.is_mfa {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::MfaRequired,
+                "The session does not satisfy the requested 'acr_values'",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl From<&JwtAmrValue> for JwtAcrValue {
+    fn from(value: &JwtAmrValue) -> Self {
+        match value {
+            JwtAmrValue::Pwd => Self::Pwd,
+            JwtAmrValue::Mfa => Self::Mfa,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_acr_min_requested() {
+        assert_eq!(JwtAcrValue::min_requested(None), None);
+        assert_eq!(JwtAcrValue::min_requested(Some("unknown")), None);
+        assert_eq!(
+            JwtAcrValue::min_requested(Some("urn:rauthy:acr:mfa")),
+            Some(JwtAcrValue::Mfa)
+        );
+        assert_eq!(
+            JwtAcrValue::min_requested(Some("unknown urn:rauthy:acr:mfa")),
+            Some(JwtAcrValue::Mfa)
+        );
+        // the lowest requested level is enough
+        assert_eq!(
+            JwtAcrValue::min_requested(Some("urn:rauthy:acr:mfa urn:rauthy:acr:pwd")),
+            Some(JwtAcrValue::Pwd)
+        );
+    }
+
+    #[test]
+    fn test_acr_validate_session() {
+        let mut session = Session::new(3600, None);
+        assert!(JwtAcrValue::validate_session(None, &session).is_ok());
+        assert!(JwtAcrValue::validate_session(Some("urn:rauthy:acr:pwd"), &session).is_ok());
+        assert!(JwtAcrValue::validate_session(Some("urn:rauthy:acr:mfa"), &session).is_err());
+
+        session.is_mfa = true;
+        assert!(JwtAcrValue::validate_session(Some("urn:rauthy:acr:mfa"), &session).is_ok());
+    }
+}
@@ -52,6 +52,37 @@ pub async fn anti_lockout(issuer: &str) -> Result<(), ErrorResponse> {
         force_mfa: *ADMIN_FORCE_MFA,
         client_uri: Some(PUB_URL_WITH_SCHEME.to_string()),
         contacts: RAUTHY_ADMIN_EMAIL.clone(),
+        auth_response_alg: None,
+        jwks: None,
+        request_uris: None,
+        frontchannel_logout_uri: None,
+        tls_client_auth: None,
+        tls_client_auth_subject_dn: None,
+        allowed_resources: None,
+        jwks_uri: None,
+        subject_type: None,
+        sector_identifier_uri: None,
+        opaque_access_tokens: false,
+        id_token_encrypted_response_alg: None,
+        id_token_encrypted_response_enc: None,
+        userinfo_encrypted_response_alg: None,
+        userinfo_encrypted_response_enc: None,
+        userinfo_signed_response_alg: None,
+        refresh_token_rotation: true,
+        refresh_token_grace_time: None,
+        refresh_token_reuse_detection: false,
+        consent_required: false,
+        id_token_lifetime: None,
+        refresh_token_lifetime: None,
+        claim_template: None,
+        session_lifetime: None,
+        session_timeout: None,
+        deny_hybrid_passkeys: false,
+        require_pkce_s256: false,
+        response_types: "code".to_string(),
+        require_signed_request_object: false,
+        backchannel_logout_uri: None,
+        token_endpoint_auth_method: None,
     };
 
     // MUST NOT use `insert or replace` syntax
@@ -1,25 +1,46 @@
 use crate::entity::api_keys::ApiKeyEntity;
+use crate::entity::audit_log::AuditLog;
 use crate::entity::auth_providers::AuthProvider;
 use crate::entity::clients::Client;
+use crate::entity::clients_access::{
+    ClientAccessPolicyEntity, ClientAccessPolicyGroup, ClientAccessPolicyUser,
+};
 use crate::entity::clients_dyn::ClientDyn;
+use crate::entity::clients_user_metadata::ClientUserMetadata;
 use crate::entity::colors::ColorEntity;
 use crate::entity::config::ConfigEntity;
+use crate::entity::consents::UserConsent;
 use crate::entity::devices::DeviceEntity;
-use crate::entity::groups::Group;
+use crate::entity::email_otp::UserEmailOtp;
+use crate::entity::groups::{Group, GroupNested, GroupRole};
+use crate::entity::jti_denylist::IssuedAccessToken;
 use crate::entity::jwk::Jwk;
+use crate::entity::login_history::LoginHistory;
+use crate::entity::login_stats::LoginStatsRow;
 use crate::entity::logos::Logo;
 use crate::entity::magic_links::MagicLink;
-use crate::entity::password::RecentPasswordsEntity;
+use crate::entity::opaque_tokens::OpaqueToken;
+use crate::entity::password::{PasswordPolicyGroup, RecentPasswordsEntity};
+use crate::entity::recovery_codes::MfaRecoveryCode;
 use crate::entity::refresh_tokens::RefreshToken;
 use crate::entity::refresh_tokens_devices::RefreshTokenDevice;
+use crate::entity::role_mapping::RoleMappingRule;
 use crate::entity::roles::Role;
 use crate::entity::scopes::Scope;
+use crate::entity::session_devices::SessionDevice;
 use crate::entity::sessions::Session;
+use crate::entity::sessions_clients::SessionClient;
+use crate::entity::sms_otp::UserSmsOtp;
+use crate::entity::tos::TosAcceptance;
+use crate::entity::totp::UserTotp;
+use crate::entity::trusted_devices::TrustedDevice;
 use crate::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use crate::entity::user_pictures::UserPicture;
 use crate::entity::users::User;
 use crate::entity::users_values::UserValues;
 use crate::entity::webauthn::PasskeyEntity;
 use crate::entity::webids::WebId;
+use crate::entity::yubico_otp::UserYubicoOtp;
 use crate::events::event::Event;
 use crate::migration::inserts;
 use rauthy_error::ErrorResponse;
@@ -75,6 +96,48 @@ pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorR
         .await?;
     inserts::passkeys(before).await?;
 
+    // USER TOTP
+    debug!("Migrating table: user_totp");
+    let before = sqlx::query_as::<_, UserTotp>("SELECT * FROM user_totp")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_totp(before).await?;
+
+    // MFA RECOVERY CODES
+    debug!("Migrating table: mfa_recovery_codes");
+    let before = sqlx::query_as::<_, MfaRecoveryCode>("SELECT * FROM mfa_recovery_codes")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::mfa_recovery_codes(before).await?;
+
+    // USER EMAIL OTP
+    debug!("Migrating table: user_email_otp");
+    let before = sqlx::query_as::<_, UserEmailOtp>("SELECT * FROM user_email_otp")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_email_otp(before).await?;
+
+    // USER SMS OTP
+    debug!("Migrating table: user_sms_otp");
+    let before = sqlx::query_as::<_, UserSmsOtp>("SELECT * FROM user_sms_otp")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_sms_otp(before).await?;
+
+    // TRUSTED DEVICES
+    debug!("Migrating table: trusted_devices");
+    let before = sqlx::query_as::<_, TrustedDevice>("SELECT * FROM trusted_devices")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::trusted_devices(before).await?;
+
+    // USER YUBICO OTP
+    debug!("Migrating table: user_yubico_otp");
+    let before = sqlx::query_as::<_, UserYubicoOtp>("SELECT * FROM user_yubico_otp")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_yubico_otp(before).await?;
+
     // Do not change the order - tables below have FKs to clients
     // CLIENTS
     debug!("Migrating table: clients");
@@ -83,6 +146,22 @@ pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorR
         .await?;
     inserts::clients(before).await?;
 
+    // CLIENT ACCESS POLICIES
+    debug!("Migrating table: client_access_policies");
+    let before =
+        sqlx::query_as::<_, ClientAccessPolicyEntity>("SELECT * FROM client_access_policies")
+            .fetch_all(&db_from)
+            .await?;
+    inserts::client_access_policies(before).await?;
+
+    // CLIENT ACCESS POLICY USERS
+    debug!("Migrating table: client_access_policy_users");
+    let before =
+        sqlx::query_as::<_, ClientAccessPolicyUser>("SELECT * FROM client_access_policy_users")
+            .fetch_all(&db_from)
+            .await?;
+    inserts::client_access_policy_users(before).await?;
+
     // CLIENTS DYN
     debug!("Migrating table: clients_dyn");
     let before = sqlx::query_as::<_, ClientDyn>("SELECT * FROM clients_dyn")
@@ -113,6 +192,15 @@ pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorR
         .await?;
     inserts::groups(before).await?;
 
+    // CLIENT ACCESS POLICY GROUPS
+    // must come after the groups, which would remove them via `ON DELETE CASCADE` otherwise
+    debug!("Migrating table: client_access_policy_groups");
+    let before =
+        sqlx::query_as::<_, ClientAccessPolicyGroup>("SELECT * FROM client_access_policy_groups")
+            .fetch_all(&db_from)
+            .await?;
+    inserts::client_access_policy_groups(before).await?;
+
     // JWKS
     debug!("Migrating table: jwks");
     let before = sqlx::query_as::<_, Jwk>("SELECT * FROM jwks")
@@ -135,6 +223,13 @@ pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorR
     let bytes: Vec<u8> = res.get("data");
     inserts::password_policy(bytes).await?;
 
+    // PASSWORD POLICIES GROUPS
+    debug!("Migrating table: password_policies_groups");
+    let before = sqlx::query_as::<_, PasswordPolicyGroup>("SELECT * FROM password_policies_groups")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::password_policies_groups(before).await?;
+
     // REFRESH TOKENS
     debug!("Migrating table: refresh_tokens");
     let before = sqlx::query_as::<_, RefreshToken>("SELECT * FROM refresh_tokens")
@@ -142,6 +237,20 @@ pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorR
         .await?;
     inserts::refresh_tokens(before).await?;
 
+    // OPAQUE TOKENS
+    debug!("Migrating table: opaque_tokens");
+    let before = sqlx::query_as::<_, OpaqueToken>("SELECT * FROM opaque_tokens")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::opaque_tokens(before).await?;
+
+    // ISSUED ACCESS TOKENS
+    debug!("Migrating table: issued_access_tokens");
+    let before = sqlx::query_as::<_, IssuedAccessToken>("SELECT * FROM issued_access_tokens")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::issued_access_tokens(before).await?;
+
     // ROLES
     debug!("Migrating table: roles");
     let before = sqlx::query_as::<_, Role>("SELECT * FROM roles")
@@ -149,6 +258,27 @@ pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorR
         .await?;
     inserts::roles(before).await?;
 
+    // GROUPS NESTED
+    debug!("Migrating table: groups_nested");
+    let before = sqlx::query_as::<_, GroupNested>("SELECT * FROM groups_nested")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::groups_nested(before).await?;
+
+    // GROUPS ROLES
+    debug!("Migrating table: groups_roles");
+    let before = sqlx::query_as::<_, GroupRole>("SELECT * FROM groups_roles")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::groups_roles(before).await?;
+
+    // ROLE MAPPING RULES
+    debug!("Migrating table: role_mapping_rules");
+    let before = sqlx::query_as::<_, RoleMappingRule>("SELECT * FROM role_mapping_rules")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::role_mapping_rules(before).await?;
+
     // SCOPES
     debug!("Migrating table: scopes");
     let before = sqlx::query_as::<_, Scope>("SELECT * FROM scopes")
@@ -163,6 +293,55 @@ pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorR
         .await?;
     inserts::events(before).await?;
 
+    // AUDIT LOG
+    debug!("Migrating table: audit_log");
+    let before = sqlx::query_as::<_, AuditLog>("SELECT * FROM audit_log")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::audit_log(before).await?;
+
+    // LOGIN STATS
+    debug!("Migrating table: login_stats");
+    let before = sqlx::query_as::<_, LoginStatsRow>("SELECT * FROM login_stats")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::login_stats(before).await?;
+
+    // LOGIN HISTORY
+    debug!("Migrating table: login_history");
+    let before = sqlx::query_as::<_, LoginHistory>("SELECT * FROM login_history")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::login_history(before).await?;
+
+    // TOS ACCEPTANCES
+    debug!("Migrating table: tos_acceptances");
+    let before = sqlx::query_as::<_, TosAcceptance>("SELECT * FROM tos_acceptances")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::tos_acceptances(before).await?;
+
+    // USER PICTURES
+    debug!("Migrating table: user_pictures");
+    let before = sqlx::query_as::<_, UserPicture>("SELECT * FROM user_pictures")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_pictures(before).await?;
+
+    // CLIENT USER METADATA
+    debug!("Migrating table: client_user_metadata");
+    let before = sqlx::query_as::<_, ClientUserMetadata>("SELECT * FROM client_user_metadata")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::client_user_metadata(before).await?;
+
+    // USER CONSENTS
+    debug!("Migrating table: user_consents");
+    let before = sqlx::query_as::<_, UserConsent>("SELECT * FROM user_consents")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_consents(before).await?;
+
     // USER ATTR CONFIG
     debug!("Migrating table: user_attr_config");
     let before = sqlx::query_as::<_, UserAttrConfigEntity>("SELECT * FROM user_attr_config")
@@ -205,6 +384,20 @@ pub async fn migrate_from_sqlite(db_from: sqlx::SqlitePool) -> Result<(), ErrorR
         .await?;
     inserts::sessions(before).await?;
 
+    // SESSIONS CLIENTS
+    debug!("Migrating table: sessions_clients");
+    let before = sqlx::query_as::<_, SessionClient>("SELECT * FROM sessions_clients")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::sessions_clients(before).await?;
+
+    // SESSION DEVICES
+    debug!("Migrating table: session_devices");
+    let before = sqlx::query_as::<_, SessionDevice>("SELECT * FROM session_devices")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::session_devices(before).await?;
+
     // RECENT PASSWORDS
     debug!("Migrating table: recent_passwords");
     let before = sqlx::query_as::<_, RecentPasswordsEntity>("SELECT * FROM recent_passwords")
@@ -270,6 +463,48 @@ pub async fn migrate_from_postgres(db_from: sqlx::PgPool) -> Result<(), ErrorRes
         .await?;
     inserts::passkeys(before).await?;
 
+    // USER TOTP
+    debug!("Migrating table: user_totp");
+    let before = sqlx::query_as::<_, UserTotp>("SELECT * FROM user_totp")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_totp(before).await?;
+
+    // MFA RECOVERY CODES
+    debug!("Migrating table: mfa_recovery_codes");
+    let before = sqlx::query_as::<_, MfaRecoveryCode>("SELECT * FROM mfa_recovery_codes")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::mfa_recovery_codes(before).await?;
+
+    // USER EMAIL OTP
+    debug!("Migrating table: user_email_otp");
+    let before = sqlx::query_as::<_, UserEmailOtp>("SELECT * FROM user_email_otp")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_email_otp(before).await?;
+
+    // USER SMS OTP
+    debug!("Migrating table: user_sms_otp");
+    let before = sqlx::query_as::<_, UserSmsOtp>("SELECT * FROM user_sms_otp")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_sms_otp(before).await?;
+
+    // TRUSTED DEVICES
+    debug!("Migrating table: trusted_devices");
+    let before = sqlx::query_as::<_, TrustedDevice>("SELECT * FROM trusted_devices")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::trusted_devices(before).await?;
+
+    // USER YUBICO OTP
+    debug!("Migrating table: user_yubico_otp");
+    let before = sqlx::query_as::<_, UserYubicoOtp>("SELECT * FROM user_yubico_otp")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_yubico_otp(before).await?;
+
     // Do not change the order - tables below have FKs to clients
     // CLIENTS
     debug!("Migrating table: clients");
@@ -278,6 +513,22 @@ pub async fn migrate_from_postgres(db_from: sqlx::PgPool) -> Result<(), ErrorRes
         .await?;
     inserts::clients(before).await?;
 
+    // CLIENT ACCESS POLICIES
+    debug!("Migrating table: client_access_policies");
+    let before =
+        sqlx::query_as::<_, ClientAccessPolicyEntity>("SELECT * FROM client_access_policies")
+            .fetch_all(&db_from)
+            .await?;
+    inserts::client_access_policies(before).await?;
+
+    // CLIENT ACCESS POLICY USERS
+    debug!("Migrating table: client_access_policy_users");
+    let before =
+        sqlx::query_as::<_, ClientAccessPolicyUser>("SELECT * FROM client_access_policy_users")
+            .fetch_all(&db_from)
+            .await?;
+    inserts::client_access_policy_users(before).await?;
+
     // CLIENTS DYN
     debug!("Migrating table: clients_dyn");
     let before = sqlx::query_as::<_, ClientDyn>("SELECT * FROM clients_dyn")
@@ -308,6 +559,15 @@ pub async fn migrate_from_postgres(db_from: sqlx::PgPool) -> Result<(), ErrorRes
         .await?;
     inserts::groups(before).await?;
 
+    // CLIENT ACCESS POLICY GROUPS
+    // must come after the groups, which would remove them via `ON DELETE CASCADE` otherwise
+    debug!("Migrating table: client_access_policy_groups");
+    let before =
+        sqlx::query_as::<_, ClientAccessPolicyGroup>("SELECT * FROM client_access_policy_groups")
+            .fetch_all(&db_from)
+            .await?;
+    inserts::client_access_policy_groups(before).await?;
+
     // JWKS
     debug!("Migrating table: jwks");
     let before = sqlx::query_as::<_, Jwk>("SELECT * FROM jwks")
@@ -330,6 +590,13 @@ pub async fn migrate_from_postgres(db_from: sqlx::PgPool) -> Result<(), ErrorRes
     let bytes: Vec<u8> = res.get("data");
     inserts::password_policy(bytes).await?;
 
+    // PASSWORD POLICIES GROUPS
+    debug!("Migrating table: password_policies_groups");
+    let before = sqlx::query_as::<_, PasswordPolicyGroup>("SELECT * FROM password_policies_groups")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::password_policies_groups(before).await?;
+
     // REFRESH TOKENS
     debug!("Migrating table: refresh_tokens");
     let before = sqlx::query_as::<_, RefreshToken>("SELECT * FROM refresh_tokens")
@@ -337,6 +604,20 @@ pub async fn migrate_from_postgres(db_from: sqlx::PgPool) -> Result<(), ErrorRes
         .await?;
     inserts::refresh_tokens(before).await?;
 
+    // OPAQUE TOKENS
+    debug!("Migrating table: opaque_tokens");
+    let before = sqlx::query_as::<_, OpaqueToken>("SELECT * FROM opaque_tokens")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::opaque_tokens(before).await?;
+
+    // ISSUED ACCESS TOKENS
+    debug!("Migrating table: issued_access_tokens");
+    let before = sqlx::query_as::<_, IssuedAccessToken>("SELECT * FROM issued_access_tokens")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::issued_access_tokens(before).await?;
+
     // ROLES
     debug!("Migrating table: roles");
     let before = sqlx::query_as::<_, Role>("SELECT * FROM roles")
@@ -344,6 +625,27 @@ pub async fn migrate_from_postgres(db_from: sqlx::PgPool) -> Result<(), ErrorRes
         .await?;
     inserts::roles(before).await?;
 
+    // GROUPS NESTED
+    debug!("Migrating table: groups_nested");
+    let before = sqlx::query_as::<_, GroupNested>("SELECT * FROM groups_nested")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::groups_nested(before).await?;
+
+    // GROUPS ROLES
+    debug!("Migrating table: groups_roles");
+    let before = sqlx::query_as::<_, GroupRole>("SELECT * FROM groups_roles")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::groups_roles(before).await?;
+
+    // ROLE MAPPING RULES
+    debug!("Migrating table: role_mapping_rules");
+    let before = sqlx::query_as::<_, RoleMappingRule>("SELECT * FROM role_mapping_rules")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::role_mapping_rules(before).await?;
+
     // SCOPES
     debug!("Migrating table: scopes");
     let before = sqlx::query_as::<_, Scope>("SELECT * FROM scopes")
@@ -361,6 +663,55 @@ pub async fn migrate_from_postgres(db_from: sqlx::PgPool) -> Result<(), ErrorRes
         .collect::<Vec<_>>();
     inserts::events(before).await?;
 
+    // AUDIT LOG
+    debug!("Migrating table: audit_log");
+    let before = sqlx::query_as::<_, AuditLog>("SELECT * FROM audit_log")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::audit_log(before).await?;
+
+    // LOGIN STATS
+    debug!("Migrating table: login_stats");
+    let before = sqlx::query_as::<_, LoginStatsRow>("SELECT * FROM login_stats")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::login_stats(before).await?;
+
+    // LOGIN HISTORY
+    debug!("Migrating table: login_history");
+    let before = sqlx::query_as::<_, LoginHistory>("SELECT * FROM login_history")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::login_history(before).await?;
+
+    // TOS ACCEPTANCES
+    debug!("Migrating table: tos_acceptances");
+    let before = sqlx::query_as::<_, TosAcceptance>("SELECT * FROM tos_acceptances")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::tos_acceptances(before).await?;
+
+    // USER PICTURES
+    debug!("Migrating table: user_pictures");
+    let before = sqlx::query_as::<_, UserPicture>("SELECT * FROM user_pictures")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_pictures(before).await?;
+
+    // CLIENT USER METADATA
+    debug!("Migrating table: client_user_metadata");
+    let before = sqlx::query_as::<_, ClientUserMetadata>("SELECT * FROM client_user_metadata")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::client_user_metadata(before).await?;
+
+    // USER CONSENTS
+    debug!("Migrating table: user_consents");
+    let before = sqlx::query_as::<_, UserConsent>("SELECT * FROM user_consents")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::user_consents(before).await?;
+
     // USER ATTR CONFIG
     debug!("Migrating table: user_attr_config");
     let before = sqlx::query_as::<_, UserAttrConfigEntity>("SELECT * FROM user_attr_config")
@@ -403,6 +754,20 @@ pub async fn migrate_from_postgres(db_from: sqlx::PgPool) -> Result<(), ErrorRes
         .await?;
     inserts::sessions(before).await?;
 
+    // SESSIONS CLIENTS
+    debug!("Migrating table: sessions_clients");
+    let before = sqlx::query_as::<_, SessionClient>("SELECT * FROM sessions_clients")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::sessions_clients(before).await?;
+
+    // SESSION DEVICES
+    debug!("Migrating table: session_devices");
+    let before = sqlx::query_as::<_, SessionDevice>("SELECT * FROM session_devices")
+        .fetch_all(&db_from)
+        .await?;
+    inserts::session_devices(before).await?;
+
     // RECENT PASSWORDS
     debug!("Migrating table: recent_passwords");
     let before = sqlx::query_as::<_, RecentPasswordsEntity>("SELECT * FROM recent_passwords")
@@ -1,8 +1,8 @@
 use crate::database::DB;
 use crate::entity::jwk::Jwk;
 use crate::entity::magic_links::{MagicLink, MagicLinkUsage};
-use chrono::Utc;
 use hiqlite::{params, Param};
+use rauthy_common::clock;
 use rauthy_common::is_hiqlite;
 use rauthy_error::ErrorResponse;
 use std::ops::Add;
@@ -179,7 +179,7 @@ VALUES ($1, $2, $3, $4, $5)"#,
         user_id: "2PYV3STNz3MN7VnPjJVcPQap".to_string(),
         csrf_token: "8jINPnFznLF9o905QuE2n9CD4rTraQO4E4fOWPZTAkbgNHqM".to_string(),
         cookie: None,
-        exp: Utc::now().add(chrono::Duration::days(1)).timestamp(),
+        exp: clock::now().add(chrono::Duration::days(1)).timestamp(),
         used: false,
         usage: MagicLinkUsage::PasswordReset(None).to_string(),
     };
@@ -1,22 +1,24 @@
 use crate::database::DB;
 use crate::entity::api_keys::ApiKeyEntity;
 use crate::entity::jwk::{Jwk, JwkKeyPairAlg};
+use crate::kms::Kms;
 use actix_web::web;
 use argon2::password_hash::SaltString;
 use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
 use cryptr::{EncKeys, EncValue};
 use hiqlite::{params, Param};
 use jwt_simple::algorithms::{
-    Ed25519KeyPair, EdDSAKeyPairLike, RS256KeyPair, RS384KeyPair, RS512KeyPair, RSAKeyPairLike,
+    ECDSAP384KeyPairLike, ES384KeyPair, Ed25519KeyPair, EdDSAKeyPairLike, RS256KeyPair,
+    RS384KeyPair, RS512KeyPair, RSAKeyPairLike,
 };
 use rand_core::OsRng;
 use rauthy_api_types::api_keys::ApiKeyRequest;
+use rauthy_common::clock;
 use rauthy_common::is_hiqlite;
 use rauthy_common::utils::{base64_decode, get_rand};
 use rauthy_error::ErrorResponse;
 use ring::digest;
 use std::env;
-use time::OffsetDateTime;
 use tracing::{debug, info};
 use validator::Validate;
 
@@ -183,76 +185,94 @@ pub async fn migrate_init_prod(argon2_params: Params, issuer: &str) -> Result<()
         let enc_key_active = &EncKeys::get_static().enc_key_active;
 
         // generate JWKs
+        // algorithms with a key inside the KMS are registered via `JWKS::register_kms_keys()`
         info!("Generating new JWKs - this might take a few seconds");
-        let mut entities = Vec::with_capacity(4);
+        let mut entities = Vec::with_capacity(5);
 
         // RSA256
-        let jwk_plain = web::block(|| {
-            RS256KeyPair::generate(2048)
-                .unwrap()
-                .with_key_id(&get_rand(24))
-        })
-        .await?;
-        let jwk = EncValue::encrypt(jwk_plain.to_der().unwrap().as_slice())?
-            .into_bytes()
-            .to_vec();
-        entities.push(Jwk {
-            kid: jwk_plain.key_id().as_ref().unwrap().clone(),
-            created_at: OffsetDateTime::now_utc().unix_timestamp(),
-            signature: JwkKeyPairAlg::RS256,
-            enc_key_id: enc_key_active.clone(),
-            jwk,
-        });
+        if Kms::key_id(&JwkKeyPairAlg::RS256).is_none() {
+            let jwk_plain = web::block(|| {
+                RS256KeyPair::generate(2048)
+                    .unwrap()
+                    .with_key_id(&get_rand(24))
+            })
+            .await?;
+            let jwk = EncValue::encrypt(jwk_plain.to_der().unwrap().as_slice())?
+                .into_bytes()
+                .to_vec();
+            entities.push(Jwk {
+                kid: jwk_plain.key_id().as_ref().unwrap().clone(),
+                created_at: clock::now_utc().unix_timestamp(),
+                signature: JwkKeyPairAlg::RS256,
+                enc_key_id: enc_key_active.clone(),
+                jwk,
+                kms_key_id: None,
+            });
+        }
 
         // RS384
-        let jwk_plain = web::block(|| {
-            RS384KeyPair::generate(3072)
-                .unwrap()
-                .with_key_id(&get_rand(24))
-        })
-        .await?;
-        let jwk = EncValue::encrypt(jwk_plain.to_der().unwrap().as_slice())?
-            .into_bytes()
-            .to_vec();
-        entities.push(Jwk {
-            kid: jwk_plain.key_id().as_ref().unwrap().clone(),
-            created_at: OffsetDateTime::now_utc().unix_timestamp(),
-            signature: JwkKeyPairAlg::RS384,
-            enc_key_id: enc_key_active.clone(),
-            jwk,
-        });
+        if Kms::key_id(&JwkKeyPairAlg::RS384).is_none() {
+            let jwk_plain = web::block(|| {
+                RS384KeyPair::generate(3072)
+                    .unwrap()
+                    .with_key_id(&get_rand(24))
+            })
+            .await?;
+            let jwk = EncValue::encrypt(jwk_plain.to_der().unwrap().as_slice())?
+                .into_bytes()
+                .to_vec();
+            entities.push(Jwk {
+                kid: jwk_plain.key_id().as_ref().unwrap().clone(),
+                created_at: clock::now_utc().unix_timestamp(),
+                signature: JwkKeyPairAlg::RS384,
+                enc_key_id: enc_key_active.clone(),
+                jwk,
+                kms_key_id: None,
+            });
+        }
 
         // RSA512
-        let jwk_plain = web::block(|| {
-            RS512KeyPair::generate(4096)
-                .unwrap()
-                .with_key_id(&get_rand(24))
-        })
-        .await?;
-        let jwk = EncValue::encrypt(jwk_plain.to_der().unwrap().as_slice())?
-            .into_bytes()
-            .to_vec();
-        entities.push(Jwk {
-            kid: jwk_plain.key_id().as_ref().unwrap().clone(),
-            created_at: OffsetDateTime::now_utc().unix_timestamp(),
-            signature: JwkKeyPairAlg::RS512,
-            enc_key_id: enc_key_active.clone(),
-            jwk,
-        });
+        if Kms::key_id(&JwkKeyPairAlg::RS512).is_none() {
+            let jwk_plain = web::block(|| {
+                RS512KeyPair::generate(4096)
+                    .unwrap()
+                    .with_key_id(&get_rand(24))
+            })
+            .await?;
+            let jwk = EncValue::encrypt(jwk_plain.to_der().unwrap().as_slice())?
+                .into_bytes()
+                .to_vec();
+            entities.push(Jwk {
+                kid: jwk_plain.key_id().as_ref().unwrap().clone(),
+                created_at: clock::now_utc().unix_timestamp(),
+                signature: JwkKeyPairAlg::RS512,
+                enc_key_id: enc_key_active.clone(),
+                jwk,
+                kms_key_id: None,
+            });
+        }
+
+        // ES384
+        if Kms::key_id(&JwkKeyPairAlg::ES384).is_none() {
+            entities.push(generate_es384().await?);
+        }
 
         // Ed25519
-        let jwk_plain =
-            web::block(|| Ed25519KeyPair::generate().with_key_id(&get_rand(24))).await?;
-        let jwk = EncValue::encrypt(jwk_plain.to_der().as_slice())?
-            .into_bytes()
-            .to_vec();
-        entities.push(Jwk {
-            kid: jwk_plain.key_id().as_ref().unwrap().clone(),
-            created_at: OffsetDateTime::now_utc().unix_timestamp(),
-            signature: JwkKeyPairAlg::EdDSA,
-            enc_key_id: enc_key_active.clone(),
-            jwk,
-        });
+        if Kms::key_id(&JwkKeyPairAlg::EdDSA).is_none() {
+            let jwk_plain =
+                web::block(|| Ed25519KeyPair::generate().with_key_id(&get_rand(24))).await?;
+            let jwk = EncValue::encrypt(jwk_plain.to_der().as_slice())?
+                .into_bytes()
+                .to_vec();
+            entities.push(Jwk {
+                kid: jwk_plain.key_id().as_ref().unwrap().clone(),
+                created_at: clock::now_utc().unix_timestamp(),
+                signature: JwkKeyPairAlg::EdDSA,
+                enc_key_id: enc_key_active.clone(),
+                jwk,
+                kms_key_id: None,
+            });
+        }
 
         for e in entities {
             e.save().await?;
@@ -263,3 +283,46 @@ pub async fn migrate_init_prod(argon2_params: Params, issuer: &str) -> Result<()
 
     Ok(())
 }
+
+/// Existing deployments do not have an ES384 key until the next JWKS rotation. This generates
+/// the missing key at startup, so clients can be switched to ES384 right away.
+pub async fn migrate_jwk_es384() -> Result<(), ErrorResponse> {
+    let signature = JwkKeyPairAlg::ES384.as_str().to_string();
+    let jwks: Vec<Jwk> = if is_hiqlite() {
+        DB::client()
+            .query_as(
+                "SELECT * FROM jwks WHERE signature = $1 LIMIT 1",
+                params!(signature),
+            )
+            .await?
+    } else {
+        sqlx::query_as::<_, Jwk>("SELECT * FROM jwks WHERE signature = $1 LIMIT 1")
+            .bind(signature)
+            .fetch_all(DB::conn())
+            .await?
+    };
+
+    if jwks.is_empty() && Kms::key_id(&JwkKeyPairAlg::ES384).is_none() {
+        info!("Generating missing ES384 JWK");
+        generate_es384().await?.save().await?;
+    }
+
+    Ok(())
+}
+
+async fn generate_es384() -> Result<Jwk, ErrorResponse> {
+    let enc_key_active = &EncKeys::get_static().enc_key_active;
+    let jwk_plain = web::block(|| ES384KeyPair::generate().with_key_id(&get_rand(24))).await?;
+    let jwk = EncValue::encrypt(jwk_plain.to_der().unwrap().as_slice())?
+        .into_bytes()
+        .to_vec();
+
+    Ok(Jwk {
+        kid: jwk_plain.key_id().as_ref().unwrap().clone(),
+        created_at: clock::now_utc().unix_timestamp(),
+        signature: JwkKeyPairAlg::ES384,
+        enc_key_id: enc_key_active.clone(),
+        jwk,
+        kms_key_id: None,
+    })
+}
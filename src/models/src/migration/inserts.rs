@@ -1,26 +1,47 @@
 use crate::database::DB;
 use crate::entity::api_keys::ApiKeyEntity;
+use crate::entity::audit_log::AuditLog;
 use crate::entity::auth_providers::AuthProvider;
 use crate::entity::clients::Client;
+use crate::entity::clients_access::{
+    ClientAccessPolicyEntity, ClientAccessPolicyGroup, ClientAccessPolicyUser,
+};
 use crate::entity::clients_dyn::ClientDyn;
+use crate::entity::clients_user_metadata::ClientUserMetadata;
 use crate::entity::colors::ColorEntity;
 use crate::entity::config::ConfigEntity;
+use crate::entity::consents::UserConsent;
 use crate::entity::devices::DeviceEntity;
-use crate::entity::groups::Group;
+use crate::entity::email_otp::UserEmailOtp;
+use crate::entity::groups::{Group, GroupNested, GroupRole};
+use crate::entity::jti_denylist::IssuedAccessToken;
 use crate::entity::jwk::Jwk;
+use crate::entity::login_history::LoginHistory;
+use crate::entity::login_stats::LoginStatsRow;
 use crate::entity::logos::Logo;
 use crate::entity::magic_links::MagicLink;
-use crate::entity::password::RecentPasswordsEntity;
+use crate::entity::opaque_tokens::OpaqueToken;
+use crate::entity::password::{PasswordPolicyGroup, RecentPasswordsEntity};
+use crate::entity::recovery_codes::MfaRecoveryCode;
 use crate::entity::refresh_tokens::RefreshToken;
 use crate::entity::refresh_tokens_devices::RefreshTokenDevice;
+use crate::entity::role_mapping::RoleMappingRule;
 use crate::entity::roles::Role;
 use crate::entity::scopes::Scope;
+use crate::entity::session_devices::SessionDevice;
 use crate::entity::sessions::Session;
+use crate::entity::sessions_clients::SessionClient;
+use crate::entity::sms_otp::UserSmsOtp;
+use crate::entity::tos::TosAcceptance;
+use crate::entity::totp::UserTotp;
+use crate::entity::trusted_devices::TrustedDevice;
 use crate::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
+use crate::entity::user_pictures::UserPicture;
 use crate::entity::users::User;
 use crate::entity::users_values::UserValues;
 use crate::entity::webauthn::PasskeyEntity;
 use crate::entity::webids::WebId;
+use crate::entity::yubico_otp::UserYubicoOtp;
 use crate::events::event::Event;
 use hiqlite::{params, Param};
 use rauthy_common::is_hiqlite;
@@ -73,6 +94,219 @@ VALUES ($1, $2, $3, $4, $5, $6)"#,
     Ok(())
 }
 
+pub async fn audit_log(data_before: Vec<AuditLog>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM audit_log", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO audit_log
+(seq, timestamp, actor, action, entity, entity_id, diff, request_id, prev_hash, hash)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
+                    params!(
+                        b.seq,
+                        b.timestamp,
+                        b.actor,
+                        b.action,
+                        b.entity,
+                        b.entity_id,
+                        b.diff,
+                        b.request_id,
+                        b.prev_hash,
+                        b.hash
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM audit_log")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO audit_log
+(seq, timestamp, actor, action, entity, entity_id, diff, request_id, prev_hash, hash)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
+                b.seq,
+                b.timestamp,
+                b.actor,
+                b.action,
+                b.entity,
+                b.entity_id,
+                b.diff,
+                b.request_id,
+                b.prev_hash,
+                b.hash,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn login_stats(data_before: Vec<LoginStatsRow>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM login_stats", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO login_stats (bucket, kind, subject, failed, blacklisted)
+VALUES ($1, $2, $3, $4, $5)"#,
+                    params!(b.bucket, b.kind, b.subject, b.failed, b.blacklisted),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM login_stats")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO login_stats (bucket, kind, subject, failed, blacklisted)
+VALUES ($1, $2, $3, $4, $5)"#,
+                b.bucket,
+                b.kind,
+                b.subject,
+                b.failed,
+                b.blacklisted,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn login_history(data_before: Vec<LoginHistory>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM login_history", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO login_history (id, user_id, timestamp, ip, user_agent, client_id, mfa, success)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                    params!(
+                        b.id,
+                        b.user_id,
+                        b.timestamp,
+                        b.ip,
+                        b.user_agent,
+                        b.client_id,
+                        b.mfa,
+                        b.success
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM login_history")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO login_history (id, user_id, timestamp, ip, user_agent, client_id, mfa, success)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                b.id,
+                b.user_id,
+                b.timestamp,
+                b.ip,
+                b.user_agent,
+                b.client_id,
+                b.mfa,
+                b.success,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn tos_acceptances(data_before: Vec<TosAcceptance>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM tos_acceptances", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO tos_acceptances (user_id, version, accepted_at, ip)
+VALUES ($1, $2, $3, $4)"#,
+                    params!(b.user_id, b.version, b.accepted_at, b.ip),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM tos_acceptances")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO tos_acceptances (user_id, version, accepted_at, ip)
+VALUES ($1, $2, $3, $4)"#,
+                b.user_id,
+                b.version,
+                b.accepted_at,
+                b.ip,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn user_pictures(data_before: Vec<UserPicture>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM user_pictures", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_pictures (user_id, content_type, data, updated)
+VALUES ($1, $2, $3, $4)"#,
+                    params!(b.user_id, b.content_type, b.data, b.updated),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM user_pictures")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO user_pictures (user_id, content_type, data, updated)
+VALUES ($1, $2, $3, $4)"#,
+                b.user_id,
+                b.content_type,
+                b.data,
+                b.updated,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn auth_provider_logos(data_before: Vec<Logo>) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
@@ -236,30 +470,68 @@ pub async fn clients(data_before: Vec<Client>) -> Result<(), ErrorResponse> {
 INSERT INTO clients
 (id, name, enabled, confidential, secret, secret_kid, redirect_uris, post_logout_redirect_uris,
 allowed_origins, flows_enabled, access_token_alg, id_token_alg, auth_code_lifetime,
-access_token_lifetime, scopes, default_scopes, challenge, force_mfa, client_uri, contacts)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)"#,
+access_token_lifetime, scopes, default_scopes, challenge, force_mfa, client_uri, contacts,
+auth_response_alg, jwks, request_uris, frontchannel_logout_uri,
+tls_client_auth, tls_client_auth_subject_dn, allowed_resources, jwks_uri,
+subject_type, sector_identifier_uri,
+opaque_access_tokens, id_token_encrypted_response_alg, id_token_encrypted_response_enc,
+userinfo_encrypted_response_alg, userinfo_encrypted_response_enc, userinfo_signed_response_alg, refresh_token_rotation, refresh_token_grace_time, refresh_token_reuse_detection, consent_required, id_token_lifetime, refresh_token_lifetime, claim_template, session_lifetime, session_timeout, deny_hybrid_passkeys, require_pkce_s256, response_types, require_signed_request_object, backchannel_logout_uri, token_endpoint_auth_method)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20,
+$21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33,
+$34, $35, $36, $37, $38, $39, $40, $41, $42, $43, $44, $45, $46, $47, $48, $49, $50, $51)"#,
                     params!(
-            b.id,
-            b.name,
-            b.enabled,
-            b.confidential,
-            b.secret,
-            b.secret_kid,
-            b.redirect_uris,
-            b.post_logout_redirect_uris,
-            b.allowed_origins,
-            b.flows_enabled,
-            b.access_token_alg,
-            b.id_token_alg,
-            b.auth_code_lifetime,
-            b.access_token_lifetime,
-            b.scopes,
-            b.default_scopes,
-            b.challenge,
-            b.force_mfa,
-            b.client_uri,
-            b.contacts
-        )
+                        b.id,
+                        b.name,
+                        b.enabled,
+                        b.confidential,
+                        b.secret,
+                        b.secret_kid,
+                        b.redirect_uris,
+                        b.post_logout_redirect_uris,
+                        b.allowed_origins,
+                        b.flows_enabled,
+                        b.access_token_alg,
+                        b.id_token_alg,
+                        b.auth_code_lifetime,
+                        b.access_token_lifetime,
+                        b.scopes,
+                        b.default_scopes,
+                        b.challenge,
+                        b.force_mfa,
+                        b.client_uri,
+                        b.contacts,
+                        b.auth_response_alg,
+                        b.jwks,
+                        b.request_uris,
+                        b.frontchannel_logout_uri,
+                        b.tls_client_auth,
+                        b.tls_client_auth_subject_dn,
+                        b.allowed_resources,
+                        b.jwks_uri,
+                        b.subject_type,
+                        b.sector_identifier_uri,
+                        b.opaque_access_tokens,
+                        b.id_token_encrypted_response_alg,
+                        b.id_token_encrypted_response_enc,
+                        b.userinfo_encrypted_response_alg,
+                        b.userinfo_encrypted_response_enc,
+                        b.userinfo_signed_response_alg,
+                        b.refresh_token_rotation,
+                        b.refresh_token_grace_time,
+                        b.refresh_token_reuse_detection,
+                        b.consent_required,
+                        b.id_token_lifetime,
+                        b.refresh_token_lifetime,
+                        b.claim_template,
+                        b.session_lifetime,
+                        b.session_timeout,
+                        b.deny_hybrid_passkeys,
+                        b.require_pkce_s256,
+                        &b.response_types,
+                        b.require_signed_request_object,
+                        &b.backchannel_logout_uri,
+                        &b.token_endpoint_auth_method
+                    ),
                 )
                 .await?;
         }
@@ -269,77 +541,101 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
             .await?;
         for b in data_before {
             sqlx::query!(
-              r#"
+                r#"
 INSERT INTO clients
 (id, name, enabled, confidential, secret, secret_kid, redirect_uris, post_logout_redirect_uris,
 allowed_origins, flows_enabled, access_token_alg, id_token_alg, auth_code_lifetime,
-access_token_lifetime, scopes, default_scopes, challenge, force_mfa, client_uri, contacts)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)"#,
-            b.id,
-            b.name,
-            b.enabled,
-            b.confidential,
-            b.secret,
-            b.secret_kid,
-            b.redirect_uris,
-            b.post_logout_redirect_uris,
-            b.allowed_origins,
-            b.flows_enabled,
-            b.access_token_alg,
-            b.id_token_alg,
-            b.auth_code_lifetime,
-            b.access_token_lifetime,
-            b.scopes,
-            b.default_scopes,
-            b.challenge,
-            b.force_mfa,
-            b.client_uri,
-            b.contacts
+access_token_lifetime, scopes, default_scopes, challenge, force_mfa, client_uri, contacts,
+auth_response_alg, jwks, request_uris, frontchannel_logout_uri,
+tls_client_auth, tls_client_auth_subject_dn, allowed_resources, jwks_uri,
+subject_type, sector_identifier_uri,
+opaque_access_tokens, id_token_encrypted_response_alg, id_token_encrypted_response_enc,
+userinfo_encrypted_response_alg, userinfo_encrypted_response_enc, userinfo_signed_response_alg, refresh_token_rotation, refresh_token_grace_time, refresh_token_reuse_detection, consent_required, id_token_lifetime, refresh_token_lifetime, claim_template, session_lifetime, session_timeout, deny_hybrid_passkeys, require_pkce_s256, response_types, require_signed_request_object, backchannel_logout_uri, token_endpoint_auth_method)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20,
+$21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33,
+$34, $35, $36, $37, $38, $39, $40, $41, $42, $43, $44, $45, $46, $47, $48, $49, $50, $51)"#,
+                b.id,
+                b.name,
+                b.enabled,
+                b.confidential,
+                b.secret,
+                b.secret_kid,
+                b.redirect_uris,
+                b.post_logout_redirect_uris,
+                b.allowed_origins,
+                b.flows_enabled,
+                b.access_token_alg,
+                b.id_token_alg,
+                b.auth_code_lifetime,
+                b.access_token_lifetime,
+                b.scopes,
+                b.default_scopes,
+                b.challenge,
+                b.force_mfa,
+                b.client_uri,
+                b.contacts,
+                b.auth_response_alg,
+                b.jwks,
+                b.request_uris,
+                b.frontchannel_logout_uri,
+                b.tls_client_auth,
+                b.tls_client_auth_subject_dn,
+                b.allowed_resources,
+                b.jwks_uri,
+                b.subject_type,
+                b.sector_identifier_uri,
+                b.opaque_access_tokens,
+                b.id_token_encrypted_response_alg,
+                b.id_token_encrypted_response_enc,
+                b.userinfo_encrypted_response_alg,
+                b.userinfo_encrypted_response_enc,
+                b.userinfo_signed_response_alg,
+                b.refresh_token_rotation,
+                b.refresh_token_grace_time,
+                b.refresh_token_reuse_detection,
+                b.consent_required,
+                b.id_token_lifetime,
+                b.refresh_token_lifetime,
+                b.claim_template,
+                b.session_lifetime,
+                b.session_timeout,
+                b.deny_hybrid_passkeys,
+                b.require_pkce_s256,
+                b.response_types,
+                b.require_signed_request_object,
+                b.backchannel_logout_uri,
+                b.token_endpoint_auth_method
             )
-                .execute(DB::conn())
-                .await?;
+            .execute(DB::conn())
+            .await?;
         }
     }
     Ok(())
 }
 
-pub async fn clients_dyn(data_before: Vec<ClientDyn>) -> Result<(), ErrorResponse> {
+pub async fn client_access_policies(
+    data_before: Vec<ClientAccessPolicyEntity>,
+) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
-            .execute("DELETE FROM clients_dyn", params!())
+            .execute("DELETE FROM client_access_policies", params!())
             .await?;
         for b in data_before {
             DB::client()
                 .execute(
-                    r#"
-INSERT INTO
-clients_dyn (id, created, last_used, registration_token, token_endpoint_auth_method)
-VALUES ($1, $2, $3, $4, $5)"#,
-                    params!(
-                        b.id,
-                        b.created,
-                        b.last_used,
-                        b.registration_token,
-                        b.token_endpoint_auth_method
-                    ),
+                    "INSERT INTO client_access_policies (client_id) VALUES ($1)",
+                    params!(b.client_id),
                 )
                 .await?;
         }
     } else {
-        sqlx::query("DELETE FROM clients_dyn")
+        sqlx::query("DELETE FROM client_access_policies")
             .execute(DB::conn())
             .await?;
         for b in data_before {
             sqlx::query!(
-                r#"
-INSERT INTO
-clients_dyn (id, created, last_used, registration_token, token_endpoint_auth_method)
-VALUES ($1, $2, $3, $4, $5)"#,
-                b.id,
-                b.created,
-                b.last_used,
-                b.registration_token,
-                b.token_endpoint_auth_method
+                "INSERT INTO client_access_policies (client_id) VALUES ($1)",
+                b.client_id,
             )
             .execute(DB::conn())
             .await?;
@@ -348,28 +644,34 @@ VALUES ($1, $2, $3, $4, $5)"#,
     Ok(())
 }
 
-pub async fn colors(data_before: Vec<ColorEntity>) -> Result<(), ErrorResponse> {
+pub async fn client_access_policy_users(
+    data_before: Vec<ClientAccessPolicyUser>,
+) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
-            .execute("DELETE FROM colors", params!())
+            .execute("DELETE FROM client_access_policy_users", params!())
             .await?;
         for b in data_before {
             DB::client()
                 .execute(
-                    "INSERT INTO colors (client_id, data) VALUES ($1, $2)",
-                    params!(b.client_id, b.data),
+                    r#"
+INSERT INTO client_access_policy_users (client_id, user_id)
+VALUES ($1, $2)"#,
+                    params!(b.client_id, b.user_id),
                 )
                 .await?;
         }
     } else {
-        sqlx::query("DELETE FROM colors")
+        sqlx::query("DELETE FROM client_access_policy_users")
             .execute(DB::conn())
             .await?;
         for b in data_before {
             sqlx::query!(
-                "INSERT INTO colors (client_id, data) VALUES ($1, $2)",
+                r#"
+INSERT INTO client_access_policy_users (client_id, user_id)
+VALUES ($1, $2)"#,
                 b.client_id,
-                b.data,
+                b.user_id,
             )
             .execute(DB::conn())
             .await?;
@@ -378,28 +680,34 @@ pub async fn colors(data_before: Vec<ColorEntity>) -> Result<(), ErrorResponse>
     Ok(())
 }
 
-pub async fn config(data_before: Vec<ConfigEntity>) -> Result<(), ErrorResponse> {
+pub async fn client_access_policy_groups(
+    data_before: Vec<ClientAccessPolicyGroup>,
+) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
-            .execute("DELETE FROM config", params!())
+            .execute("DELETE FROM client_access_policy_groups", params!())
             .await?;
         for b in data_before {
             DB::client()
                 .execute(
-                    "INSERT INTO config (id, data) VALUES ($1, $2)",
-                    params!(b.id, b.data),
+                    r#"
+INSERT INTO client_access_policy_groups (client_id, group_id)
+VALUES ($1, $2)"#,
+                    params!(b.client_id, b.group_id),
                 )
                 .await?;
         }
     } else {
-        sqlx::query("DELETE FROM config")
+        sqlx::query("DELETE FROM client_access_policy_groups")
             .execute(DB::conn())
             .await?;
         for b in data_before {
             sqlx::query!(
-                "INSERT INTO config (id, data) VALUES ($1, $2)",
-                b.id,
-                b.data,
+                r#"
+INSERT INTO client_access_policy_groups (client_id, group_id)
+VALUES ($1, $2)"#,
+                b.client_id,
+                b.group_id,
             )
             .execute(DB::conn())
             .await?;
@@ -408,49 +716,36 @@ pub async fn config(data_before: Vec<ConfigEntity>) -> Result<(), ErrorResponse>
     Ok(())
 }
 
-pub async fn devices(data_before: Vec<DeviceEntity>) -> Result<(), ErrorResponse> {
+pub async fn client_user_metadata(
+    data_before: Vec<ClientUserMetadata>,
+) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
-            .execute("DELETE FROM devices", params!())
+            .execute("DELETE FROM client_user_metadata", params!())
             .await?;
         for b in data_before {
             DB::client()
                 .execute(
                     r#"
-INSERT INTO devices
-(id, client_id, user_id, created, access_exp, refresh_exp, peer_ip, name)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
-                    params!(
-                        b.id,
-                        b.client_id,
-                        b.user_id,
-                        b.created,
-                        b.access_exp,
-                        b.refresh_exp,
-                        b.peer_ip,
-                        b.name
-                    ),
+INSERT INTO client_user_metadata (client_id, user_id, data, updated)
+VALUES ($1, $2, $3, $4)"#,
+                    params!(b.client_id, b.user_id, b.data, b.updated),
                 )
                 .await?;
         }
     } else {
-        sqlx::query("DELETE FROM devices")
+        sqlx::query("DELETE FROM client_user_metadata")
             .execute(DB::conn())
             .await?;
         for b in data_before {
             sqlx::query!(
                 r#"
-INSERT INTO devices
-(id, client_id, user_id, created, access_exp, refresh_exp, peer_ip, name)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
-                b.id,
+INSERT INTO client_user_metadata (client_id, user_id, data, updated)
+VALUES ($1, $2, $3, $4)"#,
                 b.client_id,
                 b.user_id,
-                b.created,
-                b.access_exp,
-                b.refresh_exp,
-                b.peer_ip,
-                b.name
+                b.data,
+                b.updated,
             )
             .execute(DB::conn())
             .await?;
@@ -459,45 +754,252 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
     Ok(())
 }
 
-pub async fn events(data_before: Vec<Event>) -> Result<(), ErrorResponse> {
+pub async fn user_consents(data_before: Vec<UserConsent>) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
-            .execute("DELETE FROM events", params!())
+            .execute("DELETE FROM user_consents", params!())
             .await?;
         for b in data_before {
             DB::client()
                 .execute(
                     r#"
-INSERT INTO events (id, timestamp, level, typ, ip, data, text)
-VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+INSERT INTO user_consents (user_id, client_id, scopes, created_at, last_updated)
+VALUES ($1, $2, $3, $4, $5)"#,
                     params!(
-                        b.id,
-                        b.timestamp,
-                        b.level.value(),
-                        b.typ.value(),
-                        b.ip,
-                        b.data,
-                        b.text
+                        b.user_id,
+                        b.client_id,
+                        b.scopes,
+                        b.created_at,
+                        b.last_updated
                     ),
                 )
                 .await?;
         }
     } else {
-        sqlx::query("DELETE FROM events")
+        sqlx::query("DELETE FROM user_consents")
             .execute(DB::conn())
             .await?;
         for b in data_before {
             sqlx::query!(
                 r#"
-INSERT INTO events (id, timestamp, level, typ, ip, data, text)
-VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+INSERT INTO user_consents (user_id, client_id, scopes, created_at, last_updated)
+VALUES ($1, $2, $3, $4, $5)"#,
+                b.user_id,
+                b.client_id,
+                b.scopes,
+                b.created_at,
+                b.last_updated,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn clients_dyn(data_before: Vec<ClientDyn>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM clients_dyn", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO
+clients_dyn (id, created, last_used, registration_token, token_endpoint_auth_method)
+VALUES ($1, $2, $3, $4, $5)"#,
+                    params!(
+                        b.id,
+                        b.created,
+                        b.last_used,
+                        b.registration_token,
+                        b.token_endpoint_auth_method
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM clients_dyn")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO
+clients_dyn (id, created, last_used, registration_token, token_endpoint_auth_method)
+VALUES ($1, $2, $3, $4, $5)"#,
+                b.id,
+                b.created,
+                b.last_used,
+                b.registration_token,
+                b.token_endpoint_auth_method
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn colors(data_before: Vec<ColorEntity>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM colors", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    "INSERT INTO colors (client_id, data) VALUES ($1, $2)",
+                    params!(b.client_id, b.data),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM colors")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                "INSERT INTO colors (client_id, data) VALUES ($1, $2)",
+                b.client_id,
+                b.data,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn config(data_before: Vec<ConfigEntity>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM config", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    "INSERT INTO config (id, data) VALUES ($1, $2)",
+                    params!(b.id, b.data),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM config")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                "INSERT INTO config (id, data) VALUES ($1, $2)",
+                b.id,
+                b.data,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn devices(data_before: Vec<DeviceEntity>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM devices", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO devices
+(id, client_id, user_id, created, access_exp, refresh_exp, peer_ip, name)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                    params!(
+                        b.id,
+                        b.client_id,
+                        b.user_id,
+                        b.created,
+                        b.access_exp,
+                        b.refresh_exp,
+                        b.peer_ip,
+                        b.name
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM devices")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO devices
+(id, client_id, user_id, created, access_exp, refresh_exp, peer_ip, name)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                b.id,
+                b.client_id,
+                b.user_id,
+                b.created,
+                b.access_exp,
+                b.refresh_exp,
+                b.peer_ip,
+                b.name
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn events(data_before: Vec<Event>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM events", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO events
+(id, timestamp, level, typ, ip, data, text, user_id, request_id, aggregated_count)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
+                    params!(
+                        b.id,
+                        b.timestamp,
+                        b.level.value(),
+                        b.typ.value(),
+                        b.ip,
+                        b.data,
+                        b.text,
+                        b.user_id,
+                        b.request_id,
+                        b.aggregated_count
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM events")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO events
+(id, timestamp, level, typ, ip, data, text, user_id, request_id, aggregated_count)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
                 b.id,
                 b.timestamp,
                 b.level.value(),
                 b.typ.value(),
                 b.ip,
                 b.data,
-                b.text
+                b.text,
+                b.user_id,
+                b.request_id,
+                b.aggregated_count
             )
             .execute(DB::conn())
             .await?;
@@ -514,8 +1016,8 @@ pub async fn groups(data_before: Vec<Group>) -> Result<(), ErrorResponse> {
         for b in data_before {
             DB::client()
                 .execute(
-                    "INSERT INTO groups (id, name) VALUES ($1, $2)",
-                    params!(b.id, b.name),
+                    "INSERT INTO groups (id, name, force_mfa) VALUES ($1, $2, $3)",
+                    params!(b.id, b.name, b.force_mfa),
                 )
                 .await?;
         }
@@ -525,9 +1027,10 @@ pub async fn groups(data_before: Vec<Group>) -> Result<(), ErrorResponse> {
             .await?;
         for b in data_before {
             sqlx::query!(
-                "INSERT INTO groups (id, name) VALUES ($1, $2)",
+                "INSERT INTO groups (id, name, force_mfa) VALUES ($1, $2, $3)",
                 b.id,
                 b.name,
+                b.force_mfa,
             )
             .execute(DB::conn())
             .await?;
@@ -543,14 +1046,15 @@ pub async fn jwks(data_before: Vec<Jwk>) -> Result<(), ErrorResponse> {
             DB::client()
                 .execute(
                     r#"
-INSERT INTO jwks (kid, created_at, signature, enc_key_id, jwk)
-VALUES ($1, $2, $3, $4, $5)"#,
+INSERT INTO jwks (kid, created_at, signature, enc_key_id, jwk, kms_key_id)
+VALUES ($1, $2, $3, $4, $5, $6)"#,
                     params!(
                         b.kid,
                         b.created_at,
                         b.signature.as_str(),
                         &b.enc_key_id,
-                        b.jwk
+                        b.jwk,
+                        b.kms_key_id
                     ),
                 )
                 .await?;
@@ -560,13 +1064,14 @@ VALUES ($1, $2, $3, $4, $5)"#,
         for b in data_before {
             sqlx::query!(
                 r#"
-INSERT INTO jwks (kid, created_at, signature, enc_key_id, jwk)
-VALUES ($1, $2, $3, $4, $5)"#,
+INSERT INTO jwks (kid, created_at, signature, enc_key_id, jwk, kms_key_id)
+VALUES ($1, $2, $3, $4, $5, $6)"#,
                 b.kid,
                 b.created_at,
                 b.signature.as_str(),
                 &b.enc_key_id,
-                b.jwk
+                b.jwk,
+                b.kms_key_id
             )
             .execute(DB::conn())
             .await?;
@@ -600,22 +1105,438 @@ VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
                 .await?;
         }
     } else {
-        sqlx::query("DELETE FROM magic_links")
+        sqlx::query("DELETE FROM magic_links")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO magic_links
+(id, user_id, csrf_token, cookie, exp, used, usage)
+VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+                b.id,
+                b.user_id,
+                b.csrf_token,
+                b.cookie,
+                b.exp,
+                b.used,
+                b.usage
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn passkeys(data_before: Vec<PasskeyEntity>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM passkeys", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO passkeys
+(user_id, name, passkey_user_id, passkey, credential_id, registered, last_used, user_verified,
+last_used_hybrid)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                    params!(
+                        b.user_id,
+                        b.name,
+                        b.passkey_user_id,
+                        b.passkey,
+                        b.credential_id,
+                        b.registered,
+                        b.last_used,
+                        b.user_verified,
+                        b.last_used_hybrid
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM passkeys")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO passkeys
+(user_id, name, passkey_user_id, passkey, credential_id, registered, last_used, user_verified,
+last_used_hybrid)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                b.user_id,
+                b.name,
+                b.passkey_user_id,
+                b.passkey,
+                b.credential_id,
+                b.registered,
+                b.last_used,
+                b.user_verified,
+                b.last_used_hybrid
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn user_totp(data_before: Vec<UserTotp>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM user_totp", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_totp (user_id, secret, confirmed, created, last_step)
+VALUES ($1, $2, $3, $4, $5)"#,
+                    params!(b.user_id, b.secret, b.confirmed, b.created, b.last_step),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM user_totp")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO user_totp (user_id, secret, confirmed, created, last_step)
+VALUES ($1, $2, $3, $4, $5)"#,
+                b.user_id,
+                b.secret,
+                b.confirmed,
+                b.created,
+                b.last_step
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn mfa_recovery_codes(data_before: Vec<MfaRecoveryCode>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM mfa_recovery_codes", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO mfa_recovery_codes (user_id, code_hash, created)
+VALUES ($1, $2, $3)"#,
+                    params!(b.user_id, b.code_hash, b.created),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM mfa_recovery_codes")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO mfa_recovery_codes (user_id, code_hash, created)
+VALUES ($1, $2, $3)"#,
+                b.user_id,
+                b.code_hash,
+                b.created
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn user_email_otp(data_before: Vec<UserEmailOtp>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM user_email_otp", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_email_otp (user_id, enabled, code_hash, expires, attempts, last_sent)
+VALUES ($1, $2, $3, $4, $5, $6)"#,
+                    params!(
+                        b.user_id,
+                        b.enabled,
+                        b.code_hash,
+                        b.expires,
+                        b.attempts,
+                        b.last_sent
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM user_email_otp")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO user_email_otp (user_id, enabled, code_hash, expires, attempts, last_sent)
+VALUES ($1, $2, $3, $4, $5, $6)"#,
+                b.user_id,
+                b.enabled,
+                b.code_hash,
+                b.expires,
+                b.attempts,
+                b.last_sent
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn user_sms_otp(data_before: Vec<UserSmsOtp>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM user_sms_otp", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_sms_otp
+(user_id, phone, verified, created, code_hash, expires, attempts, last_sent)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                    params!(
+                        b.user_id,
+                        b.phone,
+                        b.verified,
+                        b.created,
+                        b.code_hash,
+                        b.expires,
+                        b.attempts,
+                        b.last_sent
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM user_sms_otp")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO user_sms_otp
+(user_id, phone, verified, created, code_hash, expires, attempts, last_sent)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                b.user_id,
+                b.phone,
+                b.verified,
+                b.created,
+                b.code_hash,
+                b.expires,
+                b.attempts,
+                b.last_sent
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn trusted_devices(data_before: Vec<TrustedDevice>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM trusted_devices", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO trusted_devices
+(id, user_id, name, user_agent, created, expires, last_used)
+VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+                    params!(
+                        b.id,
+                        b.user_id,
+                        b.name,
+                        b.user_agent,
+                        b.created,
+                        b.expires,
+                        b.last_used
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM trusted_devices")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO trusted_devices
+(id, user_id, name, user_agent, created, expires, last_used)
+VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+                b.id,
+                b.user_id,
+                b.name,
+                b.user_agent,
+                b.created,
+                b.expires,
+                b.last_used
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn user_yubico_otp(data_before: Vec<UserYubicoOtp>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM user_yubico_otp", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO user_yubico_otp (user_id, public_id, created, last_used)
+VALUES ($1, $2, $3, $4)"#,
+                    params!(b.user_id, b.public_id, b.created, b.last_used),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM user_yubico_otp")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO user_yubico_otp (user_id, public_id, created, last_used)
+VALUES ($1, $2, $3, $4)"#,
+                b.user_id,
+                b.public_id,
+                b.created,
+                b.last_used
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn password_policy(bytes: Vec<u8>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute(
+                "UPDATE config SET data = $1 WHERE id = 'password_policy'",
+                params!(bytes),
+            )
+            .await?;
+    } else {
+        sqlx::query!(
+            "UPDATE config SET data = $1 WHERE id = 'password_policy'",
+            bytes,
+        )
+        .execute(DB::conn())
+        .await?;
+    }
+    Ok(())
+}
+
+pub async fn password_policies_groups(
+    data_before: Vec<PasswordPolicyGroup>,
+) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM password_policies_groups", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO password_policies_groups
+(group_id, length_min, length_max, include_lower_case, include_upper_case, include_digits,
+include_special, valid_days, not_recently_used)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                    params!(
+                        b.group_id,
+                        b.length_min,
+                        b.length_max,
+                        b.include_lower_case,
+                        b.include_upper_case,
+                        b.include_digits,
+                        b.include_special,
+                        b.valid_days,
+                        b.not_recently_used
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM password_policies_groups")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO password_policies_groups
+(group_id, length_min, length_max, include_lower_case, include_upper_case, include_digits,
+include_special, valid_days, not_recently_used)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                b.group_id,
+                b.length_min,
+                b.length_max,
+                b.include_lower_case,
+                b.include_upper_case,
+                b.include_digits,
+                b.include_special,
+                b.valid_days,
+                b.not_recently_used,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn recent_passwords(
+    data_before: Vec<RecentPasswordsEntity>,
+) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM recent_passwords", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    "INSERT INTO recent_passwords (user_id, passwords) VALUES ($1, $2)",
+                    params!(b.user_id, b.passwords),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM recent_passwords")
             .execute(DB::conn())
             .await?;
         for b in data_before {
             sqlx::query!(
-                r#"
-INSERT INTO magic_links
-(id, user_id, csrf_token, cookie, exp, used, usage)
-VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
-                b.id,
+                "INSERT INTO recent_passwords (user_id, passwords) VALUES ($1, $2)",
                 b.user_id,
-                b.csrf_token,
-                b.cookie,
-                b.exp,
-                b.used,
-                b.usage
+                b.passwords
             )
             .execute(DB::conn())
             .await?;
@@ -624,49 +1545,36 @@ VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
     Ok(())
 }
 
-pub async fn passkeys(data_before: Vec<PasskeyEntity>) -> Result<(), ErrorResponse> {
+pub async fn issued_access_tokens(
+    data_before: Vec<IssuedAccessToken>,
+) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
-            .execute("DELETE FROM passkeys", params!())
+            .execute("DELETE FROM issued_access_tokens", params!())
             .await?;
         for b in data_before {
             DB::client()
                 .execute(
                     r#"
-INSERT INTO passkeys
-(user_id, name, passkey_user_id, passkey, credential_id, registered, last_used, user_verified)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
-                    params!(
-                        b.user_id,
-                        b.name,
-                        b.passkey_user_id,
-                        b.passkey,
-                        b.credential_id,
-                        b.registered,
-                        b.last_used,
-                        b.user_verified
-                    ),
+INSERT INTO issued_access_tokens (jti, user_id, session_id, exp)
+VALUES ($1, $2, $3, $4)"#,
+                    params!(b.jti, b.user_id, b.session_id, b.exp),
                 )
                 .await?;
         }
     } else {
-        sqlx::query("DELETE FROM passkeys")
+        sqlx::query("DELETE FROM issued_access_tokens")
             .execute(DB::conn())
             .await?;
         for b in data_before {
             sqlx::query!(
                 r#"
-INSERT INTO passkeys
-(user_id, name, passkey_user_id, passkey, credential_id, registered, last_used, user_verified)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+INSERT INTO issued_access_tokens (jti, user_id, session_id, exp)
+VALUES ($1, $2, $3, $4)"#,
+                b.jti,
                 b.user_id,
-                b.name,
-                b.passkey_user_id,
-                b.passkey,
-                b.credential_id,
-                b.registered,
-                b.last_used,
-                b.user_verified
+                b.session_id,
+                b.exp,
             )
             .execute(DB::conn())
             .await?;
@@ -675,49 +1583,36 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
     Ok(())
 }
 
-pub async fn password_policy(bytes: Vec<u8>) -> Result<(), ErrorResponse> {
-    if is_hiqlite() {
-        DB::client()
-            .execute(
-                "UPDATE config SET data = $1 WHERE id = 'password_policy'",
-                params!(bytes),
-            )
-            .await?;
-    } else {
-        sqlx::query!(
-            "UPDATE config SET data = $1 WHERE id = 'password_policy'",
-            bytes,
-        )
-        .execute(DB::conn())
-        .await?;
-    }
-    Ok(())
-}
-
-pub async fn recent_passwords(
-    data_before: Vec<RecentPasswordsEntity>,
-) -> Result<(), ErrorResponse> {
+pub async fn opaque_tokens(data_before: Vec<OpaqueToken>) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
-            .execute("DELETE FROM recent_passwords", params!())
+            .execute("DELETE FROM opaque_tokens", params!())
             .await?;
         for b in data_before {
             DB::client()
                 .execute(
-                    "INSERT INTO recent_passwords (user_id, passwords) VALUES ($1, $2)",
-                    params!(b.user_id, b.passwords),
+                    r#"
+INSERT INTO opaque_tokens (id, client_id, user_id, session_id, exp, claims)
+VALUES ($1, $2, $3, $4, $5, $6)"#,
+                    params!(b.id, b.client_id, b.user_id, b.session_id, b.exp, b.claims),
                 )
                 .await?;
         }
     } else {
-        sqlx::query("DELETE FROM recent_passwords")
+        sqlx::query("DELETE FROM opaque_tokens")
             .execute(DB::conn())
             .await?;
         for b in data_before {
             sqlx::query!(
-                "INSERT INTO recent_passwords (user_id, passwords) VALUES ($1, $2)",
+                r#"
+INSERT INTO opaque_tokens (id, client_id, user_id, session_id, exp, claims)
+VALUES ($1, $2, $3, $4, $5, $6)"#,
+                b.id,
+                b.client_id,
                 b.user_id,
-                b.passwords
+                b.session_id,
+                b.exp,
+                b.claims,
             )
             .execute(DB::conn())
             .await?;
@@ -735,9 +1630,20 @@ pub async fn refresh_tokens(data_before: Vec<RefreshToken>) -> Result<(), ErrorR
             DB::client()
                 .execute(
                     r#"
-INSERT INTO refresh_tokens (id, user_id, nbf, exp, scope)
-VALUES ($1, $2, $3, $4, $5)"#,
-                    params!(b.id, b.user_id, b.nbf, b.exp, b.scope),
+INSERT INTO refresh_tokens
+(id, user_id, nbf, exp, scope, client_id, family_id, session_id, rotated_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+                    params!(
+                        b.id,
+                        b.user_id,
+                        b.nbf,
+                        b.exp,
+                        b.scope,
+                        b.client_id,
+                        b.family_id,
+                        b.session_id,
+                        b.rotated_at
+                    ),
                 )
                 .await?;
         }
@@ -748,13 +1654,18 @@ VALUES ($1, $2, $3, $4, $5)"#,
         for b in data_before {
             sqlx::query!(
                 r#"
-INSERT INTO refresh_tokens (id, user_id, nbf, exp, scope)
-VALUES ($1, $2, $3, $4, $5)"#,
+INSERT INTO refresh_tokens
+(id, user_id, nbf, exp, scope, client_id, family_id, session_id, rotated_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
                 b.id,
                 b.user_id,
                 b.nbf,
                 b.exp,
                 b.scope,
+                b.client_id,
+                b.family_id,
+                b.session_id,
+                b.rotated_at,
             )
             .execute(DB::conn())
             .await?;
@@ -827,6 +1738,106 @@ pub async fn roles(data_before: Vec<Role>) -> Result<(), ErrorResponse> {
     Ok(())
 }
 
+pub async fn groups_nested(data_before: Vec<GroupNested>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM groups_nested", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    "INSERT INTO groups_nested (parent_id, child_id) VALUES ($1, $2)",
+                    params!(b.parent_id, b.child_id),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM groups_nested")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                "INSERT INTO groups_nested (parent_id, child_id) VALUES ($1, $2)",
+                b.parent_id,
+                b.child_id,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn groups_roles(data_before: Vec<GroupRole>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM groups_roles", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    "INSERT INTO groups_roles (group_id, role_id) VALUES ($1, $2)",
+                    params!(b.group_id, b.role_id),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM groups_roles")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                "INSERT INTO groups_roles (group_id, role_id) VALUES ($1, $2)",
+                b.group_id,
+                b.role_id,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn role_mapping_rules(data_before: Vec<RoleMappingRule>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM role_mapping_rules", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO role_mapping_rules (id, name, enabled, source, key, value, roles, groups)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                    params!(b.id, b.name, b.enabled, b.source, b.key, b.value, b.roles, b.groups),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM role_mapping_rules")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO role_mapping_rules (id, name, enabled, source, key, value, roles, groups)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                b.id,
+                b.name,
+                b.enabled,
+                b.source,
+                b.key,
+                b.value,
+                b.roles,
+                b.groups,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn scopes(data_before: Vec<Scope>) -> Result<(), ErrorResponse> {
     if is_hiqlite() {
         DB::client()
@@ -836,9 +1847,15 @@ pub async fn scopes(data_before: Vec<Scope>) -> Result<(), ErrorResponse> {
             DB::client()
                 .execute(
                     r#"
-INSERT INTO scopes (id, name, attr_include_access, attr_include_id)
-VALUES ($1, $2, $3, $4)"#,
-                    params!(b.id, b.name, b.attr_include_access, b.attr_include_id),
+INSERT INTO scopes (id, name, attr_include_access, attr_include_id, claim_mappings)
+VALUES ($1, $2, $3, $4, $5)"#,
+                    params!(
+                        b.id,
+                        b.name,
+                        b.attr_include_access,
+                        b.attr_include_id,
+                        b.claim_mappings
+                    ),
                 )
                 .await?;
         }
@@ -849,12 +1866,13 @@ VALUES ($1, $2, $3, $4)"#,
         for b in data_before {
             sqlx::query!(
                 r#"
-INSERT INTO scopes (id, name, attr_include_access, attr_include_id)
-VALUES ($1, $2, $3, $4)"#,
+INSERT INTO scopes (id, name, attr_include_access, attr_include_id, claim_mappings)
+VALUES ($1, $2, $3, $4, $5)"#,
                 b.id,
                 b.name,
                 b.attr_include_access,
-                b.attr_include_id
+                b.attr_include_id,
+                b.claim_mappings
             )
             .execute(DB::conn())
             .await?;
@@ -873,8 +1891,8 @@ pub async fn sessions(data_before: Vec<Session>) -> Result<(), ErrorResponse> {
                 .execute(
                     r#"
 INSERT INTO
-sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, created_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
                     params!(
                         b.id,
                         b.csrf_token,
@@ -884,7 +1902,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
                         b.is_mfa,
                         b.state,
                         b.exp,
-                        b.last_seen
+                        b.last_seen,
+                        b.created_at
                     ),
                 )
                 .await?;
@@ -897,8 +1916,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
             sqlx::query!(
                 r#"
 INSERT INTO
-sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
+sessions (id, csrf_token, user_id, roles, groups, is_mfa, state, exp, last_seen, created_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
                 b.id,
                 b.csrf_token,
                 b.user_id,
@@ -907,7 +1926,89 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#,
                 b.is_mfa,
                 b.state,
                 b.exp,
-                b.last_seen
+                b.last_seen,
+                b.created_at
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn sessions_clients(data_before: Vec<SessionClient>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM sessions_clients", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    "INSERT INTO sessions_clients (session_id, client_id) VALUES ($1, $2)",
+                    params!(b.session_id, b.client_id),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM sessions_clients")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                "INSERT INTO sessions_clients (session_id, client_id) VALUES ($1, $2)",
+                b.session_id,
+                b.client_id,
+            )
+            .execute(DB::conn())
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn session_devices(data_before: Vec<SessionDevice>) -> Result<(), ErrorResponse> {
+    if is_hiqlite() {
+        DB::client()
+            .execute("DELETE FROM session_devices", params!())
+            .await?;
+        for b in data_before {
+            DB::client()
+                .execute(
+                    r#"
+INSERT INTO session_devices
+(session_id, user_id, name, user_agent, ip, location, first_seen, last_seen)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                    params!(
+                        b.session_id,
+                        b.user_id,
+                        b.name,
+                        b.user_agent,
+                        b.ip,
+                        b.location,
+                        b.first_seen,
+                        b.last_seen
+                    ),
+                )
+                .await?;
+        }
+    } else {
+        sqlx::query("DELETE FROM session_devices")
+            .execute(DB::conn())
+            .await?;
+        for b in data_before {
+            sqlx::query!(
+                r#"
+INSERT INTO session_devices
+(session_id, user_id, name, user_agent, ip, location, first_seen, last_seen)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+                b.session_id,
+                b.user_id,
+                b.name,
+                b.user_agent,
+                b.ip,
+                b.location,
+                b.first_seen,
+                b.last_seen,
             )
             .execute(DB::conn())
             .await?;
@@ -924,8 +2025,19 @@ pub async fn user_attr_config(data_before: Vec<UserAttrConfigEntity>) -> Result<
         for b in data_before {
             DB::client()
                 .execute(
-                    "INSERT INTO user_attr_config (name, desc) VALUES ($1, $2)",
-                    params!(b.name, b.desc),
+                    r#"
+INSERT INTO user_attr_config
+(name, "desc", typ, required, regex, required_at_login, claim_eligible)
+VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+                    params!(
+                        b.name,
+                        b.desc,
+                        b.typ,
+                        b.required,
+                        b.regex,
+                        b.required_at_login,
+                        b.claim_eligible
+                    ),
                 )
                 .await?;
         }
@@ -935,9 +2047,17 @@ pub async fn user_attr_config(data_before: Vec<UserAttrConfigEntity>) -> Result<
             .await?;
         for b in data_before {
             sqlx::query!(
-                "INSERT INTO user_attr_config (name, \"desc\") VALUES ($1, $2)",
+                r#"
+INSERT INTO user_attr_config
+(name, "desc", typ, required, regex, required_at_login, claim_eligible)
+VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
                 b.name,
-                b.desc
+                b.desc,
+                b.typ,
+                b.required,
+                b.regex,
+                b.required_at_login,
+                b.claim_eligible
             )
             .execute(DB::conn())
             .await?;
@@ -987,8 +2107,10 @@ pub async fn users(data_before: Vec<User>) -> Result<(), ErrorResponse> {
 INSERT INTO users
 (id, email, given_name, family_name, password, roles, groups, enabled, email_verified,
 password_expires, created_at, last_login, last_failed_login, failed_login_attempts, language,
-webauthn_user_id, user_expires, auth_provider_id, federation_uid)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)"#,
+webauthn_user_id, user_expires, auth_provider_id, federation_uid, external_id, deleted_at,
+must_change_password, email_verified_at, mfa_reenroll)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19,
+$20, $21, $22, $23, $24)"#,
                     params!(
                         b.id,
                         b.email,
@@ -1008,7 +2130,12 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
                         b.webauthn_user_id,
                         b.user_expires,
                         b.auth_provider_id,
-                        b.federation_uid
+                        b.federation_uid,
+                        b.external_id,
+                        b.deleted_at,
+                        b.must_change_password,
+                        b.email_verified_at,
+                        b.mfa_reenroll
                     ),
                 )
                 .await?;
@@ -1021,8 +2148,10 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
 INSERT INTO users
 (id, email, given_name, family_name, password, roles, groups, enabled, email_verified,
 password_expires, created_at, last_login, last_failed_login, failed_login_attempts, language,
-webauthn_user_id, user_expires, auth_provider_id, federation_uid)
-VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19)"#,
+webauthn_user_id, user_expires, auth_provider_id, federation_uid, external_id, deleted_at,
+must_change_password, email_verified_at, mfa_reenroll)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19,
+$20, $21, $22, $23, $24)"#,
                 b.id,
                 b.email,
                 b.given_name,
@@ -1041,7 +2170,12 @@ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $
                 b.webauthn_user_id,
                 b.user_expires,
                 b.auth_provider_id,
-                b.federation_uid
+                b.federation_uid,
+                b.external_id,
+                b.deleted_at,
+                b.must_change_password,
+                b.email_verified_at,
+                b.mfa_reenroll
             )
             .execute(DB::conn())
             .await?;
@@ -1060,8 +2194,8 @@ pub async fn users_values(data_before: Vec<UserValues>) -> Result<(), ErrorRespo
                 .execute(
                     r#"
 INSERT INTO
-users_values (id, birthdate, phone, street, zip, city, country)
-VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+users_values (id, birthdate, phone, street, zip, city, country, tz)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
                     params!(
                         b.id,
                         b.birthdate,
@@ -1069,7 +2203,8 @@ VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
                         b.street,
                         b.zip,
                         b.city,
-                        b.country
+                        b.country,
+                        b.tz
                     ),
                 )
                 .await?;
@@ -1082,15 +2217,16 @@ VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
             sqlx::query!(
                 r#"
 INSERT INTO
-users_values (id, birthdate, phone, street, zip, city, country)
-VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+users_values (id, birthdate, phone, street, zip, city, country, tz)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
                 b.id,
                 b.birthdate,
                 b.phone,
                 b.street,
                 b.zip,
                 b.city,
-                b.country
+                b.country,
+                b.tz
             )
             .execute(DB::conn())
             .await?;
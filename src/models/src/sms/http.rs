@@ -0,0 +1,54 @@
+use crate::sms::{Sms, SmsProvider};
+use async_trait::async_trait;
+use rauthy_error::ErrorResponse;
+use reqwest::header::AUTHORIZATION;
+use serde::Serialize;
+use tracing::debug;
+
+/// Sends messages as a JSON `POST` to any HTTP endpoint, for instance a self-hosted gateway:
+/// `{"to": "+49...", "from": "...", "text": "..."}`
+pub struct SmsHttp {
+    url: String,
+    auth_header: Option<String>,
+    from: String,
+}
+
+impl SmsHttp {
+    pub fn new(url: String, auth_header: Option<String>, from: String) -> Self {
+        Self {
+            url,
+            auth_header,
+            from,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct HttpSmsRequest<'a> {
+    to: &'a str,
+    from: &'a str,
+    text: &'a str,
+}
+
+#[async_trait]
+impl SmsProvider for SmsHttp {
+    async fn send(&self, to: &str, text: &str) -> Result<(), ErrorResponse> {
+        debug!("Sending SMS via HTTP gateway");
+
+        let mut req = Sms::client().post(&self.url).json(&HttpSmsRequest {
+            to,
+            from: &self.from,
+            text,
+        });
+        if let Some(auth) = &self.auth_header {
+            req = req.header(AUTHORIZATION, auth);
+        }
+
+        req.send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|err| Sms::err_send("HTTP gateway", err))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use crate::sms::http::SmsHttp;
+use crate::sms::twilio::SmsTwilio;
+use crate::sms::vonage::SmsVonage;
+use async_trait::async_trait;
+use rauthy_common::constants::RAUTHY_VERSION;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use reqwest::tls;
+use std::env;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::info;
+
+pub mod http;
+pub mod twilio;
+pub mod vonage;
+
+static SMS_PROVIDER: OnceLock<Box<dyn SmsProvider>> = OnceLock::new();
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Any SMS gateway can be plugged in by implementing this trait and passing it to
+/// `Sms::init_with()`.
+#[async_trait]
+pub trait SmsProvider: Send + Sync {
+    /// Sends the `text` to the `to` number in E.164 format.
+    async fn send(&self, to: &str, text: &str) -> Result<(), ErrorResponse>;
+}
+
+pub struct Sms;
+
+impl Sms {
+    /// Builds the provider from `SMS_PROVIDER`, if it is set. Without a provider, SMS OTP is
+    /// disabled.
+    pub fn init() -> Result<(), ErrorResponse> {
+        let Ok(name) = env::var("SMS_PROVIDER") else {
+            return Ok(());
+        };
+        let from = Self::env_required("SMS_FROM")?;
+
+        let provider: Box<dyn SmsProvider> = match name.as_str() {
+            "twilio" => Box::new(SmsTwilio::new(
+                Self::env_required("SMS_TWILIO_ACCOUNT_SID")?,
+                Self::env_required("SMS_TWILIO_AUTH_TOKEN")?,
+                from,
+            )),
+            "vonage" => Box::new(SmsVonage::new(
+                Self::env_required("SMS_VONAGE_API_KEY")?,
+                Self::env_required("SMS_VONAGE_API_SECRET")?,
+                from,
+            )),
+            "http" => Box::new(SmsHttp::new(
+                Self::env_required("SMS_HTTP_URL")?,
+                env::var("SMS_HTTP_AUTH_HEADER").ok(),
+                from,
+            )),
+            _ => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Internal,
+                    "SMS_PROVIDER must be one of: twilio, vonage, http",
+                ));
+            }
+        };
+        info!("SMS OTP enabled with provider {}", name);
+        Self::init_with(provider);
+
+        Ok(())
+    }
+
+    /// Sets a custom `SmsProvider`. Can only be called once.
+    pub fn init_with(provider: Box<dyn SmsProvider>) {
+        if SMS_PROVIDER.set(provider).is_err() {
+            panic!("Sms has already been initialized");
+        }
+    }
+
+    #[inline]
+    pub fn is_enabled() -> bool {
+        SMS_PROVIDER.get().is_some()
+    }
+
+    pub async fn send(to: &str, text: &str) -> Result<(), ErrorResponse> {
+        match SMS_PROVIDER.get() {
+            Some(provider) => provider.send(to, text).await,
+            None => Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "No SMS provider has been configured",
+            )),
+        }
+    }
+
+    pub(crate) fn client() -> &'static reqwest::Client {
+        HTTP_CLIENT.get_or_init(|| {
+            reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(10))
+                .timeout(Duration::from_secs(10))
+                .user_agent(format!("Rauthy v{} SMS", RAUTHY_VERSION))
+                .min_tls_version(tls::Version::TLS_1_2)
+                .build()
+                .unwrap()
+        })
+    }
+
+    pub(crate) fn err_send(provider: &str, err: impl std::fmt::Display) -> ErrorResponse {
+        ErrorResponse::new(
+            ErrorResponseType::Connection,
+            format!("Unable to send SMS via {}: {}", provider, err),
+        )
+    }
+
+    fn env_required(name: &str) -> Result<String, ErrorResponse> {
+        env::var(name).map_err(|_| {
+            ErrorResponse::new(
+                ErrorResponseType::Internal,
+                format!("{} must be set for the configured SMS_PROVIDER", name),
+            )
+        })
+    }
+}
@@ -0,0 +1,43 @@
+use crate::sms::{Sms, SmsProvider};
+use async_trait::async_trait;
+use rauthy_error::ErrorResponse;
+use tracing::debug;
+
+/// Sends messages via the Twilio Programmable Messaging API
+pub struct SmsTwilio {
+    account_sid: String,
+    auth_token: String,
+    from: String,
+}
+
+impl SmsTwilio {
+    pub fn new(account_sid: String, auth_token: String, from: String) -> Self {
+        Self {
+            account_sid,
+            auth_token,
+            from,
+        }
+    }
+}
+
+#[async_trait]
+impl SmsProvider for SmsTwilio {
+    async fn send(&self, to: &str, text: &str) -> Result<(), ErrorResponse> {
+        debug!("Sending SMS via Twilio");
+
+        let url = format!(
+            "https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json",
+            self.account_sid
+        );
+        Sms::client()
+            .post(url)
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .form(&[("To", to), ("From", self.from.as_str()), ("Body", text)])
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|err| Sms::err_send("Twilio", err))?;
+
+        Ok(())
+    }
+}
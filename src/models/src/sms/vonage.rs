@@ -0,0 +1,70 @@
+use crate::sms::{Sms, SmsProvider};
+use async_trait::async_trait;
+use rauthy_error::ErrorResponse;
+use serde::Deserialize;
+use tracing::debug;
+
+/// Sends messages via the Vonage (formerly Nexmo) SMS API
+pub struct SmsVonage {
+    api_key: String,
+    api_secret: String,
+    from: String,
+}
+
+impl SmsVonage {
+    pub fn new(api_key: String, api_secret: String, from: String) -> Self {
+        Self {
+            api_key,
+            api_secret,
+            from,
+        }
+    }
+}
+
+#[async_trait]
+impl SmsProvider for SmsVonage {
+    async fn send(&self, to: &str, text: &str) -> Result<(), ErrorResponse> {
+        debug!("Sending SMS via Vonage");
+
+        // Vonage expects the number without the leading `+`
+        let to = to.trim_start_matches('+');
+        let resp = Sms::client()
+            .post("https://rest.nexmo.com/sms/json")
+            .form(&[
+                ("api_key", self.api_key.as_str()),
+                ("api_secret", self.api_secret.as_str()),
+                ("from", self.from.as_str()),
+                ("to", to),
+                ("text", text),
+            ])
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|err| Sms::err_send("Vonage", err))?
+            .json::<VonageResponse>()
+            .await
+            .map_err(|err| Sms::err_send("Vonage", err))?;
+
+        // Vonage returns 200 for rejected messages as well, only a status of `0` is a success
+        match resp.messages.first() {
+            Some(msg) if msg.status == "0" => Ok(()),
+            Some(msg) => Err(Sms::err_send(
+                "Vonage",
+                msg.error_text.as_deref().unwrap_or(&msg.status),
+            )),
+            None => Err(Sms::err_send("Vonage", "empty response")),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VonageResponse {
+    messages: Vec<VonageMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VonageMessage {
+    status: String,
+    #[serde(rename = "error-text")]
+    error_text: Option<String>,
+}
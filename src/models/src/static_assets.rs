@@ -0,0 +1,235 @@
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use s3_simple::{Bucket, BucketOptions, Credentials, Region};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::fs;
+use tokio::sync::RwLock;
+use tracing::{error, info};
+
+static STATIC_ASSETS_OVERRIDE: OnceLock<Option<StaticAssetsOverride>> = OnceLock::new();
+
+/// A single asset from the override storage. The `etag` is the hash of its content, which will
+/// change with each update and makes sure clients never keep outdated files.
+#[derive(Debug)]
+pub struct OverrideAsset {
+    pub data: Vec<u8>,
+    pub etag: String,
+}
+
+enum OverrideSource {
+    Dir(PathBuf),
+    S3 { bucket: Bucket, prefix: String },
+}
+
+#[derive(Default)]
+struct OverrideIndex {
+    keys: HashSet<String>,
+    refreshed: Option<Instant>,
+}
+
+/// Serves static assets from an external directory or S3 storage with priority over the
+/// assets embedded into the binary. This makes it possible to customize the UI without
+/// re-building Rauthy.
+///
+/// The storage is listed lazily with the first lookup and again with the first lookup after the
+/// `refresh_interval`. Only files, which have been found during the last listing, will be looked
+/// up, which means that unknown paths will never hit the storage.
+pub struct StaticAssetsOverride {
+    source: OverrideSource,
+    refresh_interval: Duration,
+    index: RwLock<OverrideIndex>,
+    cache: RwLock<HashMap<String, Arc<OverrideAsset>>>,
+}
+
+impl StaticAssetsOverride {
+    /// Returns the override storage, if one has been configured.
+    pub fn get() -> Option<&'static Self> {
+        STATIC_ASSETS_OVERRIDE.get_or_init(Self::from_env).as_ref()
+    }
+
+    fn from_env() -> Option<Self> {
+        let source = if let Some(path) = env::var("STATIC_ASSETS_OVERRIDE_PATH")
+            .ok()
+            .filter(|p| !p.trim().is_empty())
+        {
+            info!("Static assets will be served from {} with priority", path);
+            OverrideSource::Dir(PathBuf::from(path.trim()))
+        } else if let Some(url) = env::var("STATIC_ASSETS_S3_URL")
+            .ok()
+            .filter(|u| !u.trim().is_empty())
+        {
+            let bucket = env::var("STATIC_ASSETS_S3_BUCKET")
+                .expect("STATIC_ASSETS_S3_BUCKET is missing while STATIC_ASSETS_S3_URL is set");
+            let region = env::var("STATIC_ASSETS_S3_REGION")
+                .expect("STATIC_ASSETS_S3_REGION is missing while STATIC_ASSETS_S3_URL is set");
+            let path_style = env::var("STATIC_ASSETS_S3_PATH_STYLE")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse::<bool>()
+                .expect("Cannot parse STATIC_ASSETS_S3_PATH_STYLE to bool");
+            let key = env::var("STATIC_ASSETS_S3_KEY")
+                .expect("STATIC_ASSETS_S3_KEY is missing while STATIC_ASSETS_S3_URL is set");
+            let secret = env::var("STATIC_ASSETS_S3_SECRET")
+                .expect("STATIC_ASSETS_S3_SECRET is missing while STATIC_ASSETS_S3_URL is set");
+            let prefix = env::var("STATIC_ASSETS_S3_PREFIX")
+                .unwrap_or_else(|_| "rauthy/static/".to_string())
+                .trim()
+                .to_string();
+
+            let bucket = Bucket::new(
+                url.trim()
+                    .parse()
+                    .expect("Cannot parse STATIC_ASSETS_S3_URL"),
+                bucket,
+                Region(region),
+                Credentials::new(key, secret),
+                Some(BucketOptions {
+                    path_style,
+                    list_objects_v2: true,
+                }),
+            )
+            .expect("Cannot build the S3 bucket for static assets");
+
+            info!("Static assets will be served from S3 with priority");
+            OverrideSource::S3 { bucket, prefix }
+        } else {
+            return None;
+        };
+
+        let refresh_interval = env::var("STATIC_ASSETS_OVERRIDE_REFRESH_SECS")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse::<u64>()
+            .expect("Cannot parse STATIC_ASSETS_OVERRIDE_REFRESH_SECS to u64");
+
+        Some(Self {
+            source,
+            refresh_interval: Duration::from_secs(refresh_interval),
+            index: RwLock::new(OverrideIndex::default()),
+            cache: RwLock::new(HashMap::default()),
+        })
+    }
+
+    /// Looks up the asset for the given path. Returns `None`, if it does not exist inside the
+    /// override storage, which means the embedded asset should be served.
+    pub async fn find(&self, path: &str) -> Option<Arc<OverrideAsset>> {
+        self.refresh_index_if_needed().await;
+        if !self.index.read().await.keys.contains(path) {
+            return None;
+        }
+
+        if let Some(asset) = self.cache.read().await.get(path) {
+            return Some(asset.clone());
+        }
+
+        let data = match self.fetch(path).await {
+            Ok(data) => data,
+            Err(err) => {
+                error!("Fetching static asset override {}: {}", path, err);
+                return None;
+            }
+        };
+        let asset = Arc::new(OverrideAsset {
+            etag: format!("\"{}\"", hex::encode(hmac_sha256::Hash::hash(&data))),
+            data,
+        });
+        self.cache
+            .write()
+            .await
+            .insert(path.to_string(), asset.clone());
+
+        Some(asset)
+    }
+
+    /// Re-builds the index of existing files after the `refresh_interval` and clears the cache
+    /// to pick up any changes.
+    async fn refresh_index_if_needed(&self) {
+        let is_fresh = |index: &OverrideIndex| {
+            index
+                .refreshed
+                .map(|ts| ts.elapsed() < self.refresh_interval)
+                .unwrap_or(false)
+        };
+
+        if is_fresh(&*self.index.read().await) {
+            return;
+        }
+
+        let mut index = self.index.write().await;
+        // another task may have refreshed it while we were waiting for the lock
+        if is_fresh(&index) {
+            return;
+        }
+
+        match self.list().await {
+            Ok(keys) => {
+                index.keys = keys;
+                self.cache.write().await.clear();
+            }
+            Err(err) => {
+                // keep serving the last known state and try again after the next interval
+                error!("Listing static asset overrides: {}", err);
+            }
+        }
+        index.refreshed = Some(Instant::now());
+    }
+
+    async fn list(&self) -> Result<HashSet<String>, ErrorResponse> {
+        let mut keys = HashSet::new();
+
+        match &self.source {
+            OverrideSource::Dir(root) => {
+                let mut dirs = vec![root.clone()];
+                while let Some(dir) = dirs.pop() {
+                    let mut entries = fs::read_dir(&dir).await?;
+                    while let Some(entry) = entries.next_entry().await? {
+                        let path = entry.path();
+                        if entry.file_type().await?.is_dir() {
+                            dirs.push(path);
+                        } else if let Ok(rel) = path.strip_prefix(root) {
+                            let key = rel
+                                .components()
+                                .map(|c| c.as_os_str().to_string_lossy())
+                                .collect::<Vec<_>>()
+                                .join("/");
+                            keys.insert(key);
+                        }
+                    }
+                }
+            }
+
+            OverrideSource::S3 { bucket, prefix } => {
+                let res = bucket.list(prefix, None).await.map_err(|err| {
+                    ErrorResponse::new(ErrorResponseType::Connection, err.to_string())
+                })?;
+                for object in res.into_iter().flat_map(|page| page.contents) {
+                    if let Some(key) = object.key.strip_prefix(prefix.as_str()) {
+                        if !key.is_empty() && !key.ends_with('/') {
+                            keys.insert(key.to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Must only be called with keys from the index, which makes sure we never access anything
+    /// outside the override storage.
+    async fn fetch(&self, key: &str) -> Result<Vec<u8>, ErrorResponse> {
+        match &self.source {
+            OverrideSource::Dir(root) => Ok(fs::read(root.join(key)).await?),
+            OverrideSource::S3 { bucket, prefix } => {
+                let res = bucket
+                    .get(format!("{}{}", prefix, key))
+                    .await
+                    .map_err(|err| {
+                        ErrorResponse::new(ErrorResponseType::Connection, err.to_string())
+                    })?;
+                Ok(res.bytes().await?.to_vec())
+            }
+        }
+    }
+}
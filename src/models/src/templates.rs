@@ -20,6 +20,12 @@ use rauthy_common::constants::{
 use std::borrow::Cow;
 use std::fmt::{Debug, Display, Formatter};
 
+/// Renders the CSP nonce for the current request into the templates. Only tags, which are
+/// part of the template itself, carry `nonce="{{ crate::templates::csp_nonce() }}"`.
+pub fn csp_nonce() -> String {
+    rauthy_common::utils::csp_nonce()
+}
+
 #[derive(Debug, Clone)]
 pub enum FrontendAction {
     Refresh,
@@ -1442,6 +1448,18 @@ impl TooManyRequestsHtml {
     }
 }
 
+#[derive(Default, Template)]
+#[template(path = "oidc/check_session.html")]
+pub struct CheckSessionHtml<'a> {
+    pub cookie_name: &'a str,
+}
+
+impl CheckSessionHtml<'_> {
+    pub fn build(cookie_name: &str) -> String {
+        CheckSessionHtml { cookie_name }.render().unwrap()
+    }
+}
+
 #[derive(Default, Template)]
 #[template(path = "html/users/{id}/email_confirm/email_confirm.html")]
 pub struct UserEmailChangeConfirmHtml<'a> {
@@ -1546,3 +1564,38 @@ impl UserRegisterHtml<'_> {
         .expect("rendering register.html")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rauthy_common::utils::CSP_NONCE;
+
+    #[derive(Template)]
+    #[template(
+        source = r#"<script nonce="{{ crate::templates::csp_nonce() }}">{{ data }}</script>"#,
+        ext = "html"
+    )]
+    struct NonceHtml<'a> {
+        data: &'a str,
+    }
+
+    #[tokio::test]
+    async fn test_csp_nonce() {
+        let html = NonceHtml {
+            data: "<script>alert(1)</script>",
+        };
+
+        let rendered = CSP_NONCE
+            .scope("abc123".to_string(), async { html.render().unwrap() })
+            .await;
+        assert_eq!(
+            rendered,
+            r#"<script nonce="abc123">&lt;script&gt;alert(1)&lt;/script&gt;</script>"#
+        );
+
+        assert_eq!(
+            html.render().unwrap(),
+            r#"<script nonce="">&lt;script&gt;alert(1)&lt;/script&gt;</script>"#
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use rauthy_common::clock;
+use rauthy_models::database::DB;
+use rauthy_models::entity::api_keys::ApiKeyEntity;
+use std::time::Duration;
+use tracing::{debug, error};
+
+/// Cleans up expired API Keys, which have been created via a Passkey exchange. Static API Keys
+/// are never touched.
+pub async fn api_keys_exchanged_cleanup() {
+    let mut interval = tokio::time::interval(Duration::from_secs(3600));
+
+    loop {
+        interval.tick().await;
+
+        if !DB::client().is_leader_cache().await {
+            debug!("Running HA mode without being the leader - skipping api_keys_exchanged_cleanup scheduler");
+            continue;
+        }
+
+        debug!("Running api_keys_exchanged_cleanup scheduler");
+
+        match ApiKeyEntity::delete_expired_exchanged(clock::now().timestamp()).await {
+            Ok(count) => debug!("Cleaned up {} expired exchanged API Keys", count),
+            Err(err) => error!("Exchanged API Keys Cleanup Error: {:?}", err),
+        }
+    }
+}
@@ -1,13 +1,15 @@
-use chrono::Utc;
 use hiqlite::{params, Param};
+use rauthy_common::clock;
 use rauthy_common::is_hiqlite;
 use rauthy_models::database::DB;
+use rauthy_models::entity::trusted_devices::TrustedDevice;
 use std::ops::Sub;
 use std::time::Duration;
 use tracing::{debug, error};
 
 /// Cleans up fully expired devices. These need to do a full re-authentication anyway.
-/// All devices that are expired for at least 1 day will be removed.
+/// All devices that are expired for at least 1 day will be removed. Expired trusted devices for
+/// MFA are removed as well.
 pub async fn devices_cleanup() {
     let mut interval = tokio::time::interval(Duration::from_secs(24 * 3600));
 
@@ -21,7 +23,7 @@ pub async fn devices_cleanup() {
 
         debug!("Running devices_cleanup scheduler");
 
-        let threshold = Utc::now().sub(chrono::Duration::days(1)).timestamp();
+        let threshold = clock::now().sub(chrono::Duration::days(1)).timestamp();
         if is_hiqlite() {
             let res = DB::client()
                 .execute(
@@ -61,5 +63,9 @@ AND (refresh_exp IS NULL OR refresh_exp < $1)"#,
                 }
             }
         };
+
+        if let Err(err) = TrustedDevice::delete_expired().await {
+            error!("devices_cleanup error for trusted devices: {:?}", err);
+        }
     }
 }
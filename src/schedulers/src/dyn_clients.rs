@@ -1,5 +1,5 @@
-use chrono::Utc;
 use hiqlite::params;
+use rauthy_common::clock;
 use rauthy_common::constants::{
     DYN_CLIENT_CLEANUP_INTERVAL, DYN_CLIENT_CLEANUP_MINUTES, DYN_CLIENT_REG_TOKEN,
     ENABLE_DYN_CLIENT_REG,
@@ -65,7 +65,7 @@ pub async fn dyn_client_cleanup() {
             }
         };
 
-        let threshold = Utc::now().timestamp() - *DYN_CLIENT_CLEANUP_MINUTES;
+        let threshold = clock::now().timestamp() - *DYN_CLIENT_CLEANUP_MINUTES;
         let mut cleaned_up = 0;
         for client in clients {
             if client.created < threshold {
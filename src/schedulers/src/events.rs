@@ -1,13 +1,16 @@
-use chrono::Utc;
 use hiqlite::{params, Param};
+use rauthy_common::clock;
 use rauthy_common::is_hiqlite;
 use rauthy_models::database::DB;
+use rauthy_models::entity::login_stats::LoginStats;
+use rauthy_models::events::archive::EventArchive;
 use std::env;
 use std::ops::Sub;
 use std::time::Duration;
 use tracing::{debug, error};
 
-/// Cleans up all Events that exceed the configured EVENT_CLEANUP_DAYS
+/// Cleans up all Events and login statistics that exceed the configured EVENT_CLEANUP_DAYS.
+/// If an S3 archive is configured, events will only be deleted after a successful upload.
 pub async fn events_cleanup() {
     let mut interval = tokio::time::interval(Duration::from_secs(3600));
 
@@ -15,6 +18,7 @@ pub async fn events_cleanup() {
         .unwrap_or_else(|_| "31".to_string())
         .parse::<u32>()
         .expect("Cannot parse EVENT_CLEANUP_DAYS to u32") as i64;
+    let archive = EventArchive::from_env();
 
     loop {
         interval.tick().await;
@@ -26,10 +30,39 @@ pub async fn events_cleanup() {
 
         debug!("Running events_cleanup scheduler");
 
-        let threshold = Utc::now()
+        let threshold = clock::now()
             .sub(chrono::Duration::days(cleanup_days))
             .timestamp_millis();
 
+        let archived = match &archive {
+            None => true,
+            Some(archive) => match archive.archive_before(threshold).await {
+                Ok(count) => {
+                    debug!("Archived {} expired events", count);
+                    true
+                }
+                Err(err) => {
+                    error!(
+                        "Events archive error - skipping events cleanup this time: {:?}",
+                        err
+                    );
+                    false
+                }
+            },
+        };
+
+        match LoginStats::delete_before(threshold / 1000).await {
+            Ok(rows_affected) => {
+                debug!("Cleaned up {} expired login stats", rows_affected);
+            }
+            Err(err) => error!("Login stats cleanup error: {:?}", err),
+        }
+
+        if !archived {
+            // keep the events until they could be archived successfully
+            continue;
+        }
+
         if is_hiqlite() {
             let res = DB::client()
                 .execute(
@@ -0,0 +1,44 @@
+use rauthy_common::constants::WEBAUTHN_MDS_ENABLE;
+use rauthy_models::database::DB;
+use rauthy_models::entity::fido_mds::FidoMds;
+use std::time::Duration;
+use tracing::{debug, error, info};
+
+/// Fetches the FIDO Metadata Service BLOB, if `WEBAUTHN_MDS_ENABLE` is set.
+/// The BLOB is updated about once a month. Runs every 24 hours.
+pub async fn fido_mds_refresh() {
+    if !*WEBAUTHN_MDS_ENABLE {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(3600 * 24));
+
+    loop {
+        interval.tick().await;
+
+        if !DB::client().is_leader_cache().await {
+            debug!(
+                "Running HA mode without being the leader - skipping fido_mds_refresh scheduler"
+            );
+            continue;
+        }
+
+        debug!("Running fido_mds_refresh scheduler");
+
+        match FidoMds::lookup().await {
+            Ok(mds) => {
+                info!(
+                    "Fetched FIDO MDS BLOB no {} with {} authenticators",
+                    mds.no,
+                    mds.entries.len()
+                );
+                if let Err(err) = mds.upsert().await {
+                    error!("Saving the FIDO MDS BLOB: {:?}", err);
+                }
+            }
+            Err(err) => {
+                error!("Fetching the FIDO MDS BLOB: {:?}", err);
+            }
+        }
+    }
+}
@@ -1,13 +1,13 @@
 use crate::sleep_schedule_next;
 use actix_web::web;
-use chrono::Utc;
-use hiqlite::params;
-use rauthy_common::constants::IDX_JWK_KID;
+use hiqlite::{params, Param};
+use rauthy_common::clock;
+use rauthy_common::constants::{IDX_JWKS, IDX_JWK_KID, JWK_AUTOROTATE_CRON, JWK_RETIREMENT_DAYS};
 use rauthy_common::is_hiqlite;
 use rauthy_models::app_state::AppState;
 use rauthy_models::database::{Cache, DB};
 use rauthy_models::entity::jwk::{Jwk, JWKS};
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::ops::Sub;
 use std::str::FromStr;
 use std::time::Duration;
@@ -16,13 +16,16 @@ use tracing::{debug, error, info};
 /// Auto-Rotates JWKS
 pub async fn jwks_auto_rotate(data: web::Data<AppState>) {
     // sec min hour day_of_month month day_of_week year
-    let schedule = cron::Schedule::from_str("0 30 3 1 * * *").unwrap();
+    let schedule = cron::Schedule::from_str(&JWK_AUTOROTATE_CRON)
+        .expect("JWK_AUTOROTATE_CRON cannot be parsed - bad format");
 
     loop {
         sleep_schedule_next(&schedule).await;
 
         if !DB::client().is_leader_cache().await {
-            debug!("Running HA mode without being the leader - skipping jwks_cleanup scheduler");
+            debug!(
+                "Running HA mode without being the leader - skipping jwks_auto_rotate scheduler"
+            );
             continue;
         }
 
@@ -32,7 +35,8 @@ pub async fn jwks_auto_rotate(data: web::Data<AppState>) {
     }
 }
 
-/// Cleans up old / expired JWKSs
+/// Cleans up retired JWKs. A key stays published for `JWK_RETIREMENT_DAYS` after it has been
+/// replaced by a newer one, so existing tokens can still be validated.
 pub async fn jwks_cleanup() {
     let mut interval = tokio::time::interval(Duration::from_secs(3600 * 24));
 
@@ -46,17 +50,18 @@ pub async fn jwks_cleanup() {
 
         debug!("Running jwks_cleanup scheduler");
 
-        // clean up all JWKs older than 90 days
-        let cleanup_threshold = Utc::now().sub(chrono::Duration::days(90)).timestamp();
+        let cleanup_threshold = clock::now()
+            .sub(chrono::Duration::days(*JWK_RETIREMENT_DAYS as i64))
+            .timestamp();
 
         // find all existing jwks
         let res = if is_hiqlite() {
             DB::client()
-                .query_as("SELECT * FROM jwks ORDER BY created_at asc", params!())
+                .query_as("SELECT * FROM jwks ORDER BY created_at DESC", params!())
                 .await
                 .map_err(|err| err.to_string())
         } else {
-            sqlx::query_as::<_, Jwk>("SELECT * FROM jwks ORDER BY created_at asc")
+            sqlx::query_as::<_, Jwk>("SELECT * FROM jwks ORDER BY created_at DESC")
                 .fetch_all(DB::conn())
                 .await
                 .map_err(|err| err.to_string())
@@ -73,31 +78,13 @@ pub async fn jwks_cleanup() {
             }
         };
 
-        // TODO after rdbms migration has been done, a nice query can do this more easily
-
-        // At this point, the latest / current one will always be the first for each key type.
-        // Skip it and check the created timestamps for all following to be older than the max time
-        // they could be needed (offline tokens) and delete them.
-        let mut found = HashSet::with_capacity(4);
-        let mut to_delete: HashSet<String> = HashSet::default();
-        for jwk in jwks_all {
-            let signature = jwk.signature.to_string();
-            if found.contains(&signature) {
-                // We already found the first JWK for the current key type -> check created timestamp
-                if jwk.created_at < cleanup_threshold {
-                    to_delete.insert(jwk.kid);
-                }
-            } else {
-                found.insert(signature);
-            }
-        }
-
-        // finally, delete all expired JWKs
+        // finally, delete all retired JWKs
+        let to_delete = retired_kids(jwks_all, cleanup_threshold);
         let count = to_delete.len();
         for kid in to_delete {
             if is_hiqlite() {
                 if let Err(err) = DB::client()
-                    .execute("DELETE FROM jwks WHERE kid = $1", params!())
+                    .execute("DELETE FROM jwks WHERE kid = $1", params!(kid.clone()))
                     .await
                 {
                     error!("Cannot clean up JWK {} in jwks_cleanup: {}", kid, err);
@@ -117,6 +104,76 @@ pub async fn jwks_cleanup() {
                 error!("Error deleting JWK from cache: {}", err);
             }
         }
+
+        if count > 0 {
+            // the retired keys must not be published anymore
+            if let Err(err) = DB::client().delete(Cache::App, IDX_JWKS).await {
+                error!("Error deleting JWKS from cache: {}", err);
+            }
+        }
         info!("Cleaned up old JWKs: {}", count);
     }
 }
+
+/// Expects the `jwks` ordered by `created_at DESC`. The latest key for each algorithm is always
+/// kept. Any older key is retired, when its successor has been created before the `threshold`.
+fn retired_kids(jwks: Vec<Jwk>, threshold: i64) -> Vec<String> {
+    let mut successor_created: HashMap<String, i64> = HashMap::with_capacity(5);
+    let mut retired = Vec::new();
+
+    for jwk in jwks {
+        let signature = jwk.signature.to_string();
+        if let Some(created) = successor_created.get(&signature) {
+            if *created < threshold {
+                retired.push(jwk.kid);
+                continue;
+            }
+        }
+        successor_created.insert(signature, jwk.created_at);
+    }
+
+    retired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rauthy_models::entity::jwk::JwkKeyPairAlg;
+
+    fn jwk(kid: &str, signature: JwkKeyPairAlg, created_at: i64) -> Jwk {
+        Jwk {
+            kid: kid.to_string(),
+            created_at,
+            signature,
+            enc_key_id: "key1".to_string(),
+            jwk: Vec::default(),
+            kms_key_id: None,
+        }
+    }
+
+    fn jwks() -> Vec<Jwk> {
+        vec![
+            jwk("ed_3", JwkKeyPairAlg::EdDSA, 300),
+            jwk("rs_2", JwkKeyPairAlg::RS256, 250),
+            jwk("ed_2", JwkKeyPairAlg::EdDSA, 200),
+            jwk("ed_1", JwkKeyPairAlg::EdDSA, 100),
+            jwk("rs_1", JwkKeyPairAlg::RS256, 50),
+        ]
+    }
+
+    #[test]
+    fn test_retired_kids() {
+        // `ed_2` was replaced at 300 -> still in its grace period
+        // `ed_1` was replaced at 200 -> retired
+        // `rs_1` was replaced at 250 -> retired
+        let retired = retired_kids(jwks(), 260);
+        assert_eq!(retired, vec!["ed_1".to_string(), "rs_1".to_string()]);
+
+        // the latest keys are never retired
+        let retired = retired_kids(jwks(), 1000);
+        assert_eq!(
+            retired,
+            vec!["ed_2".to_string(), "ed_1".to_string(), "rs_1".to_string()]
+        );
+    }
+}
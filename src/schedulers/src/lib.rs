@@ -4,10 +4,12 @@ use std::time::Duration;
 use tokio::time;
 use tracing::info;
 
+mod api_keys;
 mod app_version;
 mod devices;
 mod dyn_clients;
 mod events;
+mod fido_mds;
 mod jwks;
 mod magic_links;
 mod passwords;
@@ -19,16 +21,22 @@ mod users;
 pub async fn spawn(data: web::Data<AppState>) {
     info!("Starting schedulers");
 
+    tokio::spawn(api_keys::api_keys_exchanged_cleanup());
     tokio::spawn(dyn_clients::dyn_client_cleanup());
     tokio::spawn(events::events_cleanup());
+    tokio::spawn(fido_mds::fido_mds_refresh());
     tokio::spawn(devices::devices_cleanup());
     tokio::spawn(magic_links::magic_link_cleanup());
     tokio::spawn(tokens::refresh_tokens_cleanup());
+    tokio::spawn(tokens::opaque_tokens_cleanup());
+    tokio::spawn(tokens::issued_access_tokens_cleanup());
     tokio::spawn(sessions::sessions_cleanup());
     tokio::spawn(jwks::jwks_auto_rotate(data.clone()));
     tokio::spawn(jwks::jwks_cleanup());
     tokio::spawn(passwords::password_expiry_checker(data.clone()));
-    tokio::spawn(users::user_expiry_checker());
+    tokio::spawn(users::user_expiry_checker(data.clone()));
+    tokio::spawn(users::users_deleted_purge());
+    tokio::spawn(users::login_history_cleanup());
     tokio::spawn(app_version::app_version_check(data));
 }
 
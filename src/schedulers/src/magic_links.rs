@@ -1,5 +1,5 @@
-use chrono::Utc;
 use hiqlite::{params, Param};
+use rauthy_common::clock;
 use rauthy_common::is_hiqlite;
 use rauthy_error::ErrorResponse;
 use rauthy_models::database::DB;
@@ -26,7 +26,7 @@ pub async fn magic_link_cleanup() {
         debug!("Running magic_link_cleanup scheduler");
 
         // allow 300 seconds of clock skew before cleaning up magic links
-        let exp = Utc::now().sub(chrono::Duration::seconds(300)).timestamp();
+        let exp = clock::now().sub(chrono::Duration::seconds(300)).timestamp();
 
         // Check for expired and unused magic links that are bound to a user which has no password
         // at all. These users should be deleted since they never cared about the (very important)
@@ -1,24 +1,29 @@
 use crate::sleep_schedule_next;
 use actix_web::web;
-use chrono::Utc;
+use chrono::Timelike;
 use hiqlite::{params, Param};
+use rauthy_common::clock;
+use rauthy_common::constants::{
+    EMAIL_DEFAULT_TZ, PASSWORD_EXPIRY_QUIET_HOURS, PASSWORD_EXPIRY_REMIND_DAYS,
+};
 use rauthy_common::is_hiqlite;
 use rauthy_error::ErrorResponse;
 use rauthy_models::app_state::AppState;
 use rauthy_models::database::DB;
 use rauthy_models::email::send_pwd_reset_info;
+use rauthy_models::entity::password_expiry_reminders::PasswordExpiryReminder;
 use rauthy_models::entity::users::User;
+use rauthy_models::entity::users_values::UserValues;
+use std::collections::HashSet;
 use std::ops::Add;
 use std::str::FromStr;
 use tracing::{debug, error};
 
 /// Checks soon expiring passwords and notifies the user accordingly.
-/// Runs once every night at 04:30.
+/// Runs once every hour to be able to respect the configured quiet hours.
 pub async fn password_expiry_checker(data: web::Data<AppState>) {
     // sec min hour day_of_month month day_of_week year
-    // TODO convert to interval that runs multiple times a day + keep track of sent warnings?
-    // -> would require persisting sent emails and other things though
-    let schedule = cron::Schedule::from_str("0 30 4 * * * *").unwrap();
+    let schedule = cron::Schedule::from_str("0 30 * * * * *").unwrap();
 
     loop {
         sleep_schedule_next(&schedule).await;
@@ -30,44 +35,127 @@ pub async fn password_expiry_checker(data: web::Data<AppState>) {
 
         debug!("Running password_expiry_checker scheduler");
 
-        // warns, if the duration until the expiry is between 9 and 10 days, to only warn once
-        // TODO we could keep track about sent warnings in an additional DB, in case the deployment
-        // is offline for some longer time
-        let now = Utc::now();
-        let lower = now.add(chrono::Duration::days(9)).timestamp();
-        let upper = now.add(chrono::Duration::days(10)).timestamp();
-
-        let expiring_users: Result<Vec<User>, ErrorResponse> = if is_hiqlite() {
-            DB::client()
-                .query_as(
-                    "SELECT * FROM users WHERE password_expires <= $1 AND password_expires > $2",
-                    params!(upper, lower),
-                )
-                .await
-                .map_err(ErrorResponse::from)
-        } else {
-            sqlx::query_as!(
-                User,
-                "SELECT * FROM users WHERE password_expires <= $1 AND password_expires > $2",
-                upper,
-                lower
+        if let Err(err) = notify_expiring_passwords(&data).await {
+            error!("password_expiry_checker error: {}", err.message);
+        }
+    }
+}
+
+async fn notify_expiring_passwords(data: &web::Data<AppState>) -> Result<(), ErrorResponse> {
+    PasswordExpiryReminder::delete_expired().await?;
+
+    let Some(max_days) = PASSWORD_EXPIRY_REMIND_DAYS.last() else {
+        return Ok(());
+    };
+
+    let now = clock::now();
+    let lower = now.timestamp();
+    let upper = now.add(chrono::Duration::days(*max_days)).timestamp();
+
+    let users: Vec<User> = if is_hiqlite() {
+        DB::client()
+            .query_as(
+                r#"
+SELECT * FROM users
+WHERE password_expires <= $1 AND password_expires > $2 AND deleted_at IS NULL"#,
+                params!(upper, lower),
             )
-            .fetch_all(DB::conn())
-            .await
-            .map_err(ErrorResponse::from)
+            .await?
+    } else {
+        sqlx::query_as!(
+            User,
+            r#"
+SELECT * FROM users
+WHERE password_expires <= $1 AND password_expires > $2 AND deleted_at IS NULL"#,
+            upper,
+            lower
+        )
+        .fetch_all(DB::conn())
+        .await?
+    };
+    if users.is_empty() {
+        return Ok(());
+    }
+
+    let sent = PasswordExpiryReminder::find_active()
+        .await?
+        .into_iter()
+        .map(|r| (r.user_id, r.password_expires, r.remind_days))
+        .collect::<HashSet<_>>();
+
+    for user in users {
+        // the query only returns users with a password expiry
+        let Some(expires) = user.password_expires else {
+            continue;
         };
 
-        match expiring_users {
-            Ok(users_to_notify) => {
-                for user in users_to_notify {
-                    send_pwd_reset_info(&data, &user).await;
-                    debug!("User {} notified about password expiry", user.email);
-                }
-            }
+        // We only care about the closest reached threshold. If a deployment has been offline
+        // for some time, this makes sure that we only send a single reminder for all the
+        // thresholds that have been missed in between.
+        let remaining = expires - lower;
+        let Some(remind_days) = PASSWORD_EXPIRY_REMIND_DAYS
+            .iter()
+            .find(|days| remaining <= *days * 86400)
+        else {
+            continue;
+        };
+
+        if sent.contains(&(user.id.clone(), expires, *remind_days)) {
+            continue;
+        }
 
-            Err(err) => {
-                error!("password_expiry_checker error: {}", err.message);
+        if let Some(quiet_hours) = *PASSWORD_EXPIRY_QUIET_HOURS {
+            let tz = UserValues::find(&user.id)
+                .await?
+                .map(|values| values.tz())
+                .unwrap_or(*EMAIL_DEFAULT_TZ);
+            let hour = now.with_timezone(&tz).hour();
+            if is_quiet_hour(hour, quiet_hours) {
+                debug!(
+                    "Skipping password expiry reminder for {} during quiet hours",
+                    user.email
+                );
+                continue;
             }
-        };
+        }
+
+        send_pwd_reset_info(data, &user).await;
+        PasswordExpiryReminder::insert(user.id.clone(), expires, *remind_days).await?;
+        debug!(
+            "User {} notified about password expiry in {} days",
+            user.email, remind_days
+        );
+    }
+
+    Ok(())
+}
+
+/// Quiet hours may wrap around midnight, like `22-7`.
+fn is_quiet_hour(hour: u32, (start, end): (u32, u32)) -> bool {
+    if start <= end {
+        hour >= start && hour < end
+    } else {
+        hour >= start || hour < end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_quiet_hour() {
+        assert!(is_quiet_hour(22, (22, 7)));
+        assert!(is_quiet_hour(0, (22, 7)));
+        assert!(is_quiet_hour(6, (22, 7)));
+        assert!(!is_quiet_hour(7, (22, 7)));
+        assert!(!is_quiet_hour(12, (22, 7)));
+
+        assert!(is_quiet_hour(12, (12, 14)));
+        assert!(is_quiet_hour(13, (12, 14)));
+        assert!(!is_quiet_hour(14, (12, 14)));
+        assert!(!is_quiet_hour(11, (12, 14)));
+
+        assert!(!is_quiet_hour(5, (5, 5)));
     }
 }
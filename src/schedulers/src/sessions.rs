@@ -1,5 +1,5 @@
-use chrono::Utc;
 use hiqlite::{params, Param};
+use rauthy_common::clock;
 use rauthy_common::is_hiqlite;
 use rauthy_models::database::DB;
 use std::ops::Sub;
@@ -22,7 +22,7 @@ pub async fn sessions_cleanup() {
 
         debug!("Running sessions_cleanup scheduler");
 
-        let thres = Utc::now().sub(chrono::Duration::hours(24)).timestamp();
+        let thres = clock::now().sub(chrono::Duration::hours(24)).timestamp();
 
         if is_hiqlite() {
             if let Err(err) = DB::client()
@@ -1,7 +1,9 @@
-use chrono::Utc;
 use hiqlite::{params, Param};
+use rauthy_common::clock;
 use rauthy_common::is_hiqlite;
 use rauthy_models::database::DB;
+use rauthy_models::entity::jti_denylist::IssuedAccessToken;
+use rauthy_models::entity::opaque_tokens::OpaqueToken;
 use std::time::Duration;
 use tracing::{debug, error};
 
@@ -18,7 +20,7 @@ pub async fn refresh_tokens_cleanup() {
 
         debug!("Running refresh_tokens_cleanup scheduler");
 
-        let now = Utc::now().timestamp();
+        let now = clock::now().timestamp();
 
         if is_hiqlite() {
             if let Err(err) = DB::client()
@@ -36,3 +38,41 @@ pub async fn refresh_tokens_cleanup() {
         }
     }
 }
+
+pub async fn opaque_tokens_cleanup() {
+    let mut interval = tokio::time::interval(Duration::from_secs(3600));
+
+    loop {
+        interval.tick().await;
+
+        if !DB::client().is_leader_cache().await {
+            debug!("Running HA mode without being the leader - skipping opaque_tokens_cleanup scheduler");
+            continue;
+        }
+
+        debug!("Running opaque_tokens_cleanup scheduler");
+
+        if let Err(err) = OpaqueToken::delete_expired().await {
+            error!("Opaque Token Cleanup Error: {:?}", err)
+        }
+    }
+}
+
+pub async fn issued_access_tokens_cleanup() {
+    let mut interval = tokio::time::interval(Duration::from_secs(3600));
+
+    loop {
+        interval.tick().await;
+
+        if !DB::client().is_leader_cache().await {
+            debug!("Running HA mode without being the leader - skipping issued_access_tokens_cleanup scheduler");
+            continue;
+        }
+
+        debug!("Running issued_access_tokens_cleanup scheduler");
+
+        if let Err(err) = IssuedAccessToken::delete_expired().await {
+            error!("Issued Access Token Cleanup Error: {:?}", err)
+        }
+    }
+}
@@ -1,18 +1,25 @@
-use chrono::Utc;
+use actix_web::web;
+use rauthy_common::clock;
+use rauthy_common::constants::{LOGIN_HISTORY_RETENTION_DAYS, USER_DELETE_GRACE_DAYS};
+use rauthy_models::app_state::AppState;
 use rauthy_models::database::DB;
+use rauthy_models::entity::login_history::LoginHistory;
 use rauthy_models::entity::refresh_tokens::RefreshToken;
 use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::user_expiry_notifications::UserExpiryNotification;
 use rauthy_models::entity::users::User;
+use rauthy_models::events::event::Event;
 use std::env;
 use std::time::Duration;
 use tracing::{debug, error, info};
 
-pub async fn user_expiry_checker() {
+pub async fn user_expiry_checker(data: web::Data<AppState>) {
     let secs = env::var("SCHED_USER_EXP_MINS")
         .unwrap_or_else(|_| "60".to_string())
         .parse::<u64>()
-        .expect("Cannot parse 'SCHED_USER_EXP_MINS' to u64");
-    let mut interval = tokio::time::interval(Duration::from_secs(secs * 60));
+        .expect("Cannot parse 'SCHED_USER_EXP_MINS' to u64")
+        * 60;
+    let mut interval = tokio::time::interval(Duration::from_secs(secs));
     let cleanup_after_secs = env::var("SCHED_USER_EXP_DELETE_MINS")
         .map(|s| {
             s.parse::<u64>()
@@ -38,7 +45,7 @@ pub async fn user_expiry_checker() {
 
         match User::find_expired().await {
             Ok(users) => {
-                let now = Utc::now().timestamp();
+                let now = clock::now().timestamp();
 
                 for user in users {
                     debug!("Found expired user {}: {}", user.id, user.email);
@@ -54,6 +61,27 @@ pub async fn user_expiry_checker() {
                         continue;
                     };
 
+                    // each expiry creates exactly one event, no matter how often the
+                    // scheduler runs, and even if it has not been running for a while
+                    match UserExpiryNotification::insert(user.id.clone(), exp_ts).await {
+                        Ok(true) => {
+                            if let Err(err) = Event::user_expired(user.email.clone())
+                                .with_user_id(user.id.clone())
+                                .send(&data.tx_events)
+                                .await
+                            {
+                                error!("Error sending UserExpired event: {:?}", err);
+                            }
+                        }
+                        Ok(false) => {}
+                        Err(err) => {
+                            error!(
+                                "Error saving the expiry notification for user {}: {:?}",
+                                user.id, err
+                            );
+                        }
+                    }
+
                     // invalidate all sessions
                     if let Err(err) = Session::invalidate_for_user(&user.id).await {
                         error!(
@@ -96,3 +124,52 @@ pub async fn user_expiry_checker() {
         };
     }
 }
+
+/// Permanently deletes soft-deleted users after `USER_DELETE_GRACE_DAYS`.
+pub async fn users_deleted_purge() {
+    let mut interval = tokio::time::interval(Duration::from_secs(3600));
+
+    loop {
+        interval.tick().await;
+
+        if !DB::client().is_leader_cache().await {
+            debug!(
+                "Running HA mode without being the leader - skipping users_deleted_purge scheduler"
+            );
+            continue;
+        }
+
+        debug!("Running users_deleted_purge scheduler");
+
+        let threshold = clock::now().timestamp() - *USER_DELETE_GRACE_DAYS as i64 * 24 * 60 * 60;
+        match User::purge_deleted(threshold).await {
+            Ok(count) => debug!("Purged {} soft-deleted users", count),
+            Err(err) => error!("users_deleted_purge error: {}", err.message),
+        }
+    }
+}
+
+/// Cleans up all login history entries older than `LOGIN_HISTORY_RETENTION_DAYS`.
+pub async fn login_history_cleanup() {
+    let mut interval = tokio::time::interval(Duration::from_secs(3600));
+
+    loop {
+        interval.tick().await;
+
+        if !DB::client().is_leader_cache().await {
+            debug!(
+                "Running HA mode without being the leader - skipping login_history_cleanup scheduler"
+            );
+            continue;
+        }
+
+        debug!("Running login_history_cleanup scheduler");
+
+        let threshold =
+            clock::now().timestamp() - *LOGIN_HISTORY_RETENTION_DAYS as i64 * 24 * 60 * 60;
+        match LoginHistory::delete_before(threshold).await {
+            Ok(count) => debug!("Cleaned up {} expired login history entries", count),
+            Err(err) => error!("login_history_cleanup error: {}", err.message),
+        }
+    }
+}
@@ -1,6 +1,17 @@
-use rauthy_api_types::clients::{ClientSecretResponse, UpdateClientRequest};
+use rauthy_api_types::clients::{
+    ClientRevokeAllResponse, ClientSecretResponse, UpdateClientRequest,
+};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::consents::UserConsent;
+use rauthy_models::entity::jwe::JweEncrypter;
+use rauthy_models::entity::logout_token::BackchannelLogout;
+use rauthy_models::entity::opaque_tokens::OpaqueToken;
+use rauthy_models::entity::pairwise_subjects::PairwiseSubject;
+use rauthy_models::entity::refresh_tokens::RefreshToken;
+use rauthy_models::entity::refresh_tokens_devices::RefreshTokenDevice;
+use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::sessions_clients::SessionClient;
 
 pub async fn update_client(
     id: String,
@@ -44,8 +55,139 @@ pub async fn update_client(
     client.scopes = Client::sanitize_scopes(client_req.scopes).await?;
     client.default_scopes = Client::sanitize_scopes(client_req.default_scopes).await?;
 
-    client.challenge = client_req.challenges.map(|c| c.join(","));
+    client.require_pkce_s256 = client_req.require_pkce_s256;
+    client.challenge = if client.require_pkce_s256 {
+        Some("S256".to_string())
+    } else {
+        client_req.challenges.map(|c| c.join(","))
+    };
+    client.response_types = client_req.response_types.join(",");
     client.force_mfa = client_req.force_mfa;
+    client.auth_response_alg = client_req.auth_response_alg.map(|alg| alg.to_string());
+
+    client.jwks = match client_req.jwks {
+        Some(jwks) if !jwks.trim().is_empty() => {
+            Client::parse_jwks(&jwks)?;
+            Some(jwks)
+        }
+        _ => None,
+    };
+    client.request_uris = client_req
+        .request_uris
+        .filter(|uris| !uris.is_empty())
+        .map(|uris| uris.join(","));
+    client.frontchannel_logout_uri = client_req.frontchannel_logout_uri;
+    client.backchannel_logout_uri = client_req.backchannel_logout_uri;
+
+    if client_req.require_signed_request_object && client.jwks.is_none() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "Signed request objects can only be required with a registered 'jwks'",
+        ));
+    }
+    client.require_signed_request_object = client_req.require_signed_request_object;
+
+    match client_req.tls_client_auth.as_deref() {
+        Some(_) if !client.confidential => {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "mTLS client authentication is only possible for confidential clients",
+            ));
+        }
+        Some("tls_client_auth")
+            if client_req
+                .tls_client_auth_subject_dn
+                .as_deref()
+                .unwrap_or_default()
+                .trim()
+                .is_empty() =>
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "'tls_client_auth' needs a 'tls_client_auth_subject_dn'",
+            ));
+        }
+        Some("self_signed_tls_client_auth") if client.jwks.is_none() => {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "'self_signed_tls_client_auth' needs a JWKS with the 'x5c' of the certificate",
+            ));
+        }
+        _ => {}
+    }
+    client.tls_client_auth = client_req.tls_client_auth;
+    match client_req.token_endpoint_auth_method.as_deref() {
+        Some(_) if !client.confidential => {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "A 'token_endpoint_auth_method' is only possible for confidential clients",
+            ));
+        }
+        Some("private_key_jwt")
+            if client.jwks.is_none()
+                && client_req
+                    .jwks_uri
+                    .as_deref()
+                    .unwrap_or_default()
+                    .trim()
+                    .is_empty() =>
+        {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "'private_key_jwt' needs a 'jwks' or 'jwks_uri'",
+            ));
+        }
+        _ => {}
+    }
+    client.token_endpoint_auth_method = client_req.token_endpoint_auth_method;
+    client.tls_client_auth_subject_dn = client_req
+        .tls_client_auth_subject_dn
+        .filter(|dn| !dn.trim().is_empty());
+    client.allowed_resources = client_req
+        .allowed_resources
+        .filter(|res| !res.is_empty())
+        .map(|res| res.join(","));
+    client.jwks_uri = client_req.jwks_uri.filter(|uri| !uri.trim().is_empty());
+    // `public` is the default and will not be saved explicitly
+    client.subject_type = client_req.subject_type.filter(|typ| typ == "pairwise");
+    client.sector_identifier_uri = client_req
+        .sector_identifier_uri
+        .filter(|uri| !uri.trim().is_empty());
+    PairwiseSubject::validate_client(&client).await?;
+    client.opaque_access_tokens = client_req.opaque_access_tokens;
+    JweEncrypter::validate_config(
+        client_req.id_token_encrypted_response_alg.as_deref(),
+        client_req.id_token_encrypted_response_enc.as_deref(),
+    )?;
+    JweEncrypter::validate_config(
+        client_req.userinfo_encrypted_response_alg.as_deref(),
+        client_req.userinfo_encrypted_response_enc.as_deref(),
+    )?;
+    client.id_token_encrypted_response_alg = client_req.id_token_encrypted_response_alg;
+    client.id_token_encrypted_response_enc = client_req.id_token_encrypted_response_enc;
+    client.userinfo_encrypted_response_alg = client_req.userinfo_encrypted_response_alg;
+    client.userinfo_encrypted_response_enc = client_req.userinfo_encrypted_response_enc;
+    client.userinfo_signed_response_alg = client_req
+        .userinfo_signed_response_alg
+        .map(|alg| alg.to_string());
+    client.refresh_token_rotation = client_req.refresh_token_rotation;
+    client.refresh_token_grace_time = client_req.refresh_token_grace_time;
+    client.refresh_token_reuse_detection = client_req.refresh_token_reuse_detection;
+    client.id_token_lifetime = client_req.id_token_lifetime;
+    client.refresh_token_lifetime = client_req.refresh_token_lifetime;
+    client.claim_template = client_req
+        .claim_template
+        .map(|tpl| serde_json::to_string(&tpl))
+        .transpose()?;
+    client.session_lifetime = client_req.session_lifetime;
+    client.session_timeout = client_req.session_timeout;
+    client.deny_hybrid_passkeys = client_req.deny_hybrid_passkeys;
+    client.consent_required = client_req.consent_required;
+    // with an inline JWKS, a missing encryption key can be caught right away
+    if client.jwks.is_some() {
+        JweEncrypter::for_id_token(&client).await?;
+        JweEncrypter::for_userinfo(&client).await?;
+    }
 
     client.contacts = client_req.contacts.map(|c| c.join(","));
     client.client_uri = client_req.client_uri;
@@ -87,3 +229,46 @@ pub async fn generate_new_secret(id: String) -> Result<ClientSecretResponse, Err
         secret: Some(clear),
     })
 }
+
+/// Revokes all refresh tokens and opaque access tokens, which have been issued for this client,
+/// including the ones for devices, and all consents given to it. All users with an active token
+/// or a session with this client will have their sessions invalidated as well, which forces a new
+/// login. Each client inside these sessions receives a back-channel logout.
+pub async fn revoke_all(
+    id: String,
+    issuer: String,
+) -> Result<ClientRevokeAllResponse, ErrorResponse> {
+    let client = Client::find(id).await?;
+
+    OpaqueToken::invalidate_for_client(&client.id).await?;
+    let mut user_ids = RefreshToken::invalidate_for_client(&client.id).await?;
+    user_ids.extend(RefreshTokenDevice::invalidate_for_client(&client.id).await?);
+    user_ids.extend(SessionClient::find_user_ids(&client.id).await?);
+    user_ids.sort();
+    user_ids.dedup();
+
+    let mut logouts = Vec::with_capacity(user_ids.len());
+    for user_id in &user_ids {
+        // must be resolved while the sessions still exist
+        let for_user = BackchannelLogout::find_for_user(user_id).await?;
+        if !for_user.iter().any(|l| l.client_id == client.id) {
+            // the client may only hold refresh tokens without any linked session
+            logouts.push(BackchannelLogout {
+                client_id: client.id.clone(),
+                user_id: user_id.clone(),
+                session_id: None,
+            });
+        }
+        logouts.extend(for_user);
+
+        Session::invalidate_for_user(user_id).await?;
+    }
+    UserConsent::delete_for_client(&client.id).await?;
+
+    BackchannelLogout::send_all(issuer, logouts);
+
+    Ok(ClientRevokeAllResponse {
+        id: client.id,
+        users: user_ids.len(),
+    })
+}
@@ -7,5 +7,8 @@ pub mod encryption;
 pub mod login_delay;
 pub mod oidc;
 pub mod password_reset;
+pub mod scim;
 pub mod suspicious_request_block;
 pub mod token_set;
+pub mod user_export;
+pub mod user_import;
@@ -1,9 +1,10 @@
 use actix_web::{web, HttpResponse};
-use chrono::Utc;
+use rauthy_common::clock;
 use rauthy_common::constants::IDX_LOGIN_TIME;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::database::{Cache, DB};
+use rauthy_models::entity::login_stats::LoginStats;
 use rauthy_models::events::event::Event;
 use rauthy_models::events::ip_blacklist_handler::{IpBlacklistReq, IpFailedLoginCheck};
 use rauthy_models::templates::TooManyRequestsHtml;
@@ -19,10 +20,14 @@ Handles the login delay.
 With every successful login, a new average login time is calculated for how
 long it took for a successful login. If a login failed though, the answer will be delayed by the
 current average for a successful login, to prevent things like username enumeration.
+
+Each failed login will be counted in the pre-aggregated login statistics for the targeted user,
+which is the given login name.
  */
 pub async fn handle_login_delay(
     data: &web::Data<AppState>,
     peer_ip: IpAddr,
+    target: Option<String>,
     start: Duration,
     res: Result<HttpResponse, ErrorResponse>,
     has_password_been_hashed: bool,
@@ -88,6 +93,19 @@ pub async fn handle_login_delay(
                 .await
                 .unwrap();
 
+            // the blacklisting happens at exactly these thresholds
+            let is_blacklisted = matches!(failed_logins, 7 | 10 | 15 | 20) || failed_logins >= 25;
+            if let Err(err) = LoginStats::record_failed(
+                &peer_ip,
+                target.as_deref(),
+                is_blacklisted,
+                clock::now().timestamp(),
+            )
+            .await
+            {
+                error!("Recording failed login stats: {}", err);
+            }
+
             let sleep_time_median = {
                 let time_taken = end.sub(start).as_millis() as u64;
                 let mut sleep_time_median = 0;
@@ -101,7 +119,7 @@ pub async fn handle_login_delay(
             let sleep_time = match failed_logins as u64 {
                 // n-th blacklist -> blocks for 24h with each invalid request
                 t if t >= 25 => {
-                    let not_before = Utc::now().add(chrono::Duration::seconds(86400));
+                    let not_before = clock::now().add(chrono::Duration::seconds(86400));
                     let ts = not_before.timestamp();
                     let html = TooManyRequestsHtml::build(peer_ip.to_string(), ts);
 
@@ -120,7 +138,7 @@ pub async fn handle_login_delay(
 
                 // 4th blacklist
                 20 => {
-                    let not_before = Utc::now().add(chrono::Duration::seconds(3600));
+                    let not_before = clock::now().add(chrono::Duration::seconds(3600));
                     let ts = not_before.timestamp();
                     let html = TooManyRequestsHtml::build(peer_ip.to_string(), ts);
 
@@ -139,7 +157,7 @@ pub async fn handle_login_delay(
 
                 // 3rd blacklist
                 15 => {
-                    let not_before = Utc::now().add(chrono::Duration::seconds(900));
+                    let not_before = clock::now().add(chrono::Duration::seconds(900));
                     let ts = not_before.timestamp();
                     let html = TooManyRequestsHtml::build(peer_ip.to_string(), ts);
 
@@ -158,7 +176,7 @@ pub async fn handle_login_delay(
 
                 // 2nd blacklist
                 10 => {
-                    let not_before = Utc::now().add(chrono::Duration::seconds(600));
+                    let not_before = clock::now().add(chrono::Duration::seconds(600));
                     let ts = not_before.timestamp();
                     let html = TooManyRequestsHtml::build(peer_ip.to_string(), ts);
 
@@ -177,7 +195,7 @@ pub async fn handle_login_delay(
 
                 // 1st blacklist
                 7 => {
-                    let not_before = Utc::now().add(chrono::Duration::seconds(60));
+                    let not_before = clock::now().add(chrono::Duration::seconds(60));
                     let ts = not_before.timestamp();
                     let html = TooManyRequestsHtml::build(peer_ip.to_string(), ts);
 
@@ -1,27 +1,92 @@
 use actix_web::http::header;
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest};
-use chrono::Utc;
-use rauthy_api_types::oidc::{LoginRefreshRequest, LoginRequest};
-use rauthy_common::constants::{COOKIE_MFA, SESSION_RENEW_MFA, WEBAUTHN_REQ_EXP};
-use rauthy_common::utils::get_rand;
-use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_api_types::oidc::{LoginPasskeyRequest, LoginRefreshRequest, LoginRequest};
+use rauthy_api_types::users::UserAttrConfigValueResponse;
+use rauthy_common::clock;
+use rauthy_common::constants::{
+    SessionLimitBehavior, COOKIE_MFA, SESSION_LIMIT_BEHAVIOR, SESSION_REMEMBER_ME_DAYS,
+    SESSION_RENEW_MFA, TOS_URL, WEBAUTHN_REQ_EXP,
+};
+use rauthy_common::utils::{get_rand, real_ip_from_req};
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
 use rauthy_models::api_cookie::ApiCookie;
 use rauthy_models::app_state::AppState;
-use rauthy_models::entity::auth_codes::AuthCode;
+use rauthy_models::entity::auth_codes::{AuthCode, AuthResponseMode};
+use rauthy_models::entity::claims_request::ClaimsRequest;
 use rauthy_models::entity::clients::Client;
-use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::clients_access::ClientAccessPolicy;
+use rauthy_models::entity::consents::UserConsent;
+use rauthy_models::entity::email_otp::UserEmailOtp;
+use rauthy_models::entity::groups::Group;
+use rauthy_models::entity::magic_links::{MagicLink, MagicLinkUsage};
+use rauthy_models::entity::recovery_codes::MfaRecoveryCode;
+use rauthy_models::entity::refresh_tokens::RefreshToken;
+use rauthy_models::entity::role_mapping::RoleMappingRule;
+use rauthy_models::entity::session_devices::SessionDevice;
+use rauthy_models::entity::sessions::{Session, SessionAuthMethod};
+use rauthy_models::entity::sms_otp::UserSmsOtp;
+use rauthy_models::entity::tos::TosAcceptance;
+use rauthy_models::entity::totp::UserTotp;
+use rauthy_models::entity::trusted_devices::TrustedDevice;
+use rauthy_models::entity::user_attr::{UserAttrConfigEntity, UserAttrValueEntity};
 use rauthy_models::entity::users::{AccountType, User};
+use rauthy_models::entity::webauthn;
 use rauthy_models::entity::webauthn::{WebauthnCookie, WebauthnLoginReq};
-use rauthy_models::{AuthStep, AuthStepAwaitWebauthn, AuthStepLoggedIn};
-use std::fmt::Write;
+use rauthy_models::entity::yubico_otp::UserYubicoOtp;
+use rauthy_models::events::event::Event;
+use rauthy_models::{
+    AuthStep, AuthStepAwaitConsent, AuthStepAwaitProfile, AuthStepAwaitTos, AuthStepAwaitWebauthn,
+    AuthStepLoggedIn, JwtAcrValue,
+};
 use tracing::trace;
 
+/// A login with a discoverable passkey, which identifies the user. It replaces the E-Mail, the
+/// password and the 2nd factor, because user verification is mandatory in this case.
+pub async fn post_authorize_passkey(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    req_data: LoginPasskeyRequest,
+    session: Session,
+    add_login_delay: &mut bool,
+    user_needs_mfa: &mut bool,
+) -> Result<AuthStep, ErrorResponse> {
+    let (user, hybrid) =
+        webauthn::auth_finish_discoverable(data.webauthn.for_req(req), &req_data.passkey).await?;
+    if hybrid {
+        webauthn::validate_hybrid_login(
+            req_data.client_id.clone(),
+            user.id.clone(),
+            real_ip_from_req(req)?.to_string(),
+            &data.tx_events,
+        )
+        .await?;
+    }
+
+    // there is no password involved in this login
+    let mut has_password_been_hashed = false;
+    post_authorize(
+        data,
+        req,
+        req_data.into_login_request(user.email),
+        session,
+        true,
+        &mut has_password_been_hashed,
+        add_login_delay,
+        user_needs_mfa,
+    )
+    .await
+}
+
+/// `passkey_verified` must only be set, if the user has been identified with a discoverable
+/// passkey including user verification already.
+#[allow(clippy::too_many_arguments)]
 pub async fn post_authorize(
     data: &web::Data<AppState>,
     req: &HttpRequest,
     req_data: LoginRequest,
     mut session: Session,
+    passkey_verified: bool,
     has_password_been_hashed: &mut bool,
     add_login_delay: &mut bool,
     user_needs_mfa: &mut bool,
@@ -50,10 +115,18 @@ pub async fn post_authorize(
         };
 
     let account_type = user.account_type();
+    let auth_method = if req_data.password.is_some() {
+        SessionAuthMethod::Password
+    } else {
+        SessionAuthMethod::Passkey
+    };
 
-    // only allow an empty password, if the user has a passkey only account or a valid MFA cookie
-    let user_must_provide_password =
-        req_data.password.is_none() && account_type != AccountType::Passkey && mfa_cookie.is_none();
+    // only allow an empty password, if the user has a passkey only account, a valid MFA cookie
+    // or has already logged in with a discoverable passkey
+    let user_must_provide_password = req_data.password.is_none()
+        && account_type != AccountType::Passkey
+        && mfa_cookie.is_none()
+        && !passkey_verified;
     if user_must_provide_password {
         // if we get here, the UI did the first step from the login form
         // -> username only without password
@@ -79,63 +152,300 @@ pub async fn post_authorize(
     user.check_enabled()?;
     user.check_expired()?;
 
+    // users with a passkey do the Webauthn step, unless they send a TOTP code instead
+    let has_totp = UserTotp::is_enabled(&user.id).await?;
+    // a Yubico OTP key is the next option for users without a passkey or TOTP
+    let has_yubico_otp =
+        !user.has_webauthn_enabled() && !has_totp && UserYubicoOtp::is_enabled(&user.id).await?;
+    // SMS is only used, if the user has no other 2nd factor apart from E-Mail
+    let has_sms_otp = !user.has_webauthn_enabled()
+        && !has_totp
+        && !has_yubico_otp
+        && UserSmsOtp::is_enabled(&user.id).await?;
+    // E-Mail OTP is only a fallback for users without any other 2nd factor
+    let has_email_otp = !user.has_webauthn_enabled()
+        && !has_totp
+        && !has_yubico_otp
+        && !has_sms_otp
+        && UserEmailOtp::is_enabled(&user.id).await?;
+    // set, if the 2nd factor has been verified with a TOTP, a Yubico OTP, an SMS, a recovery code
+    // or a discoverable passkey already
+    let mut mfa_verified = passkey_verified;
+    // set, if the 2nd factor can be skipped, because it has been verified on this device before
+    let mut device_trusted = false;
+    let wants_trust_device = req_data.trust_device == Some(true) && TrustedDevice::is_enabled();
+
     // TODO should we move the password hashing as far back as possible? -> most expensive operation
     // maybe it makes sense to do additional DB requests instead of hashing a password?
     // what about brute force attempts in that case?
     // -> identify the best ordering and if it maybe makes sense to check the client first
     if let Some(pwd) = req_data.password {
         *has_password_been_hashed = true;
-        user.validate_password(data, pwd).await?;
+        if let Err(err) = user.validate_password(data, pwd).await {
+            if err.code == ErrorCode::InvalidCredentials {
+                user.register_failed_login(data, req).await?;
+            }
+            return Err(err);
+        }
+        // a lockout only blocks password logins, passkey only accounts can still log in
+        user.check_locked()?;
+
+        // a trusted device never replaces the 2nd factor, if the client explicitly requests MFA
+        let has_2fa = user.has_webauthn_enabled()
+            || has_totp
+            || has_yubico_otp
+            || has_sms_otp
+            || has_email_otp;
+        device_trusted = has_2fa
+            && JwtAcrValue::min_requested(req_data.acr_values.as_deref()) != Some(JwtAcrValue::Mfa)
+            && TrustedDevice::is_trusted(req, &user.id).await;
+
+        if let Some(code) = req_data.recovery_code.as_deref() {
+            // recovery codes replace the 2nd factor and are only valid for users with MFA
+            if !has_totp && !has_yubico_otp && !has_sms_otp && !user.has_webauthn_enabled() {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "MFA is not enabled for this user",
+                ));
+            }
+            if let Err(err) = MfaRecoveryCode::redeem(&user.id, code).await {
+                user.register_failed_login(data, req).await?;
+                return Err(err);
+            }
+            mfa_verified = true;
+        } else if device_trusted {
+            trace!("Skipping the 2nd factor for a trusted device");
+        } else if has_totp && (req_data.totp.is_some() || !user.has_webauthn_enabled()) {
+            let Some(code) = req_data.totp.as_deref() else {
+                // the UI needs to ask for the code and send the login again
+                *add_login_delay = false;
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Unauthorized,
+                    "User needs to provide a TOTP code",
+                )
+                .with_code(ErrorCode::TotpRequired));
+            };
+            if let Err(err) = UserTotp::validate_for_user(&user.id, code).await {
+                user.register_failed_login(data, req).await?;
+                return Err(err);
+            }
+            mfa_verified = true;
+        } else if has_yubico_otp {
+            let Some(otp) = req_data.yubico_otp.as_deref() else {
+                *add_login_delay = false;
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Unauthorized,
+                    "User needs to provide a Yubico OTP",
+                )
+                .with_code(ErrorCode::YubicoOtpRequired));
+            };
+            if let Err(err) = UserYubicoOtp::validate_for_user(&user.id, otp).await {
+                user.register_failed_login(data, req).await?;
+                return Err(err);
+            }
+            mfa_verified = true;
+        } else if has_sms_otp {
+            let Some(code) = req_data.sms_otp.as_deref() else {
+                UserSmsOtp::send_for_user(&user.id).await?;
+                *add_login_delay = false;
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Unauthorized,
+                    "User needs to provide the code sent via SMS",
+                )
+                .with_code(ErrorCode::SmsOtpRequired));
+            };
+            if let Err(err) = UserSmsOtp::validate_for_user(&user.id, code).await {
+                user.register_failed_login(data, req).await?;
+                return Err(err);
+            }
+            mfa_verified = true;
+        } else if has_email_otp {
+            let Some(code) = req_data.email_otp.as_deref() else {
+                UserEmailOtp::send_code(data, &user).await?;
+                *add_login_delay = false;
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::Unauthorized,
+                    "User needs to provide the code sent via E-Mail",
+                )
+                .with_code(ErrorCode::EmailOtpRequired));
+            };
+            if let Err(err) = UserEmailOtp::verify(&user.id, code).await {
+                user.register_failed_login(data, req).await?;
+                return Err(err);
+            }
+        }
 
         // update user info
         // in case of webauthn login, the info will be updated in the oidc finish step
-        user.last_login = Some(Utc::now().timestamp());
+        user.last_login = Some(clock::now().timestamp());
         user.last_failed_login = None;
         user.failed_login_attempts = None;
+        RoleMappingRule::apply(&mut user, None).await?;
         user.save(None).await?;
+
+        if user.must_change_password {
+            // no code must be issued before the password has been changed
+            // -> redirect into the password reset flow with a fresh magic link
+            let magic_link = MagicLink::create(
+                user.id.clone(),
+                data.ml_lt_pwd_reset as i64,
+                MagicLinkUsage::PasswordReset(None),
+            )
+            .await?;
+            let loc = format!(
+                "{}/users/{}/reset/{}?type={}",
+                data.issuer, user.id, magic_link.id, magic_link.usage
+            );
+
+            return Ok(AuthStep::LoggedIn(AuthStepLoggedIn {
+                user_id: user.id,
+                email: user.email,
+                header_loc: (header::LOCATION, HeaderValue::from_str(&loc)?),
+                header_csrf: Session::get_csrf_header(&session.csrf_token),
+                header_origin: None,
+                trust_device: false,
+            }));
+        }
     }
 
     // client validations
     let client = Client::find_maybe_ephemeral(req_data.client_id).await?;
-    client.validate_mfa(&user).inspect_err(|_| {
+    ClientAccessPolicy::validate_user(&client.id, &user).await?;
+    let has_otp = has_totp || has_yubico_otp || has_sms_otp;
+    client.validate_mfa(&user, has_otp).inspect_err(|_| {
         // in this case, we do not want to add a login delay
         // the user password was correct, we only need a passkey being added to the account
         *user_needs_mfa = true;
         *add_login_delay = false;
     })?;
+    Group::validate_mfa(&client.id, &user, has_otp)
+        .await
+        .inspect_err(|_| {
+            *user_needs_mfa = true;
+            *add_login_delay = false;
+        })?;
+    user.validate_mfa_reenroll(&client.id, has_otp)
+        .inspect_err(|_| {
+            *user_needs_mfa = true;
+            *add_login_delay = false;
+        })?;
+    if user.mfa_reenroll && (user.has_webauthn_enabled() || has_otp) {
+        // a new 2nd factor has been enrolled after the MFA reset
+        user.mfa_reenroll = false;
+        user.save(None).await?;
+    }
+    JwtAcrValue::validate_user(req_data.acr_values.as_deref(), &user, has_otp).inspect_err(
+        |_| {
+            *user_needs_mfa = true;
+            *add_login_delay = false;
+        },
+    )?;
     client.validate_redirect_uri(&req_data.redirect_uri)?;
+    client.validate_flow("authorization_code")?;
     client.validate_code_challenge(&req_data.code_challenge, &req_data.code_challenge_method)?;
     let header_origin = client.validate_origin(req, &data.listen_scheme, &data.public_url)?;
 
+    // the current terms of service must have been accepted before a code can be issued
+    let tos_accept = match TosAcceptance::pending_version(&user.id).await? {
+        Some(version) if req_data.tos_accept.as_deref() != Some(version) => {
+            return Ok(AuthStep::AwaitTos(AuthStepAwaitTos {
+                version: version.to_string(),
+                url: TOS_URL.clone(),
+                header_csrf: Session::get_csrf_header(&session.csrf_token),
+                header_origin,
+            }));
+        }
+        pending => pending.map(String::from),
+    };
+
+    // custom attributes required at login must be provided by the user, if they are missing
+    let missing_attrs = UserAttrConfigEntity::find_missing_at_login(&user.id).await?;
+    let profile_values = if missing_attrs.is_empty() {
+        None
+    } else if let Some(values) = req_data.profile_values {
+        UserAttrValueEntity::validate_at_login(&missing_attrs, &values)?;
+        Some(values)
+    } else {
+        return Ok(AuthStep::AwaitProfile(AuthStepAwaitProfile {
+            attrs: missing_attrs
+                .into_iter()
+                .map(UserAttrConfigValueResponse::from)
+                .collect(),
+            header_csrf: Session::get_csrf_header(&session.csrf_token),
+            header_origin,
+        }));
+    };
+
+    let mut scopes = client.sanitize_login_scopes(&req_data.scopes)?;
+    ClaimsRequest::extend_scopes(req_data.claims.as_deref(), &client, &mut scopes).await?;
+
+    // clients with `consent_required` need the requested scopes being granted by the user
+    let consent_accept =
+        match check_consent(&client, &user.id, &scopes, req_data.consent_accept).await? {
+            ConsentCheck::Granted(consent_accept) => consent_accept,
+            ConsentCheck::Required(missing) => {
+                return Ok(AuthStep::AwaitConsent(AuthStepAwaitConsent {
+                    client_id: client.id,
+                    client_name: client.name,
+                    scopes: missing,
+                    header_csrf: Session::get_csrf_header(&session.csrf_token),
+                    header_origin,
+                }));
+            }
+        };
+
+    // make sure the user does not exceed the max amount of concurrent sessions
+    let over_limit = Session::find_over_limit(&user.id, &session.id).await?;
+    if !over_limit.is_empty() {
+        enforce_session_limit(data, req, &user, over_limit).await?;
+    }
+
     // build authorization code
     let code_lifetime = if user.has_webauthn_enabled() {
         client.auth_code_lifetime + *WEBAUTHN_REQ_EXP as i32
     } else {
         client.auth_code_lifetime
     };
-    let scopes = client.sanitize_login_scopes(&req_data.scopes)?;
+    let response_mode = AuthResponseMode::parse(req_data.response_mode.as_deref(), &client)?;
+    client.validate_resource(req_data.resource.as_deref())?;
+    let remember_me = req_data.remember_me == Some(true) && *SESSION_REMEMBER_ME_DAYS > 0;
+    let auth_time = clock::now().timestamp();
     let code = AuthCode::new(
         user.id.clone(),
-        client.id,
+        client.id.clone(),
         Some(session.id.clone()),
         req_data.code_challenge,
         req_data.code_challenge_method,
         req_data.nonce,
         scopes,
+        req_data.resource,
+        auth_time,
         code_lifetime,
     );
     code.save().await?;
 
     // build location header
-    let mut loc = format!("{}?code={}", req_data.redirect_uri, code.id);
-    if let Some(state) = req_data.state {
-        write!(loc, "&state={}", state)?;
-    };
+    let loc = code
+        .response_location(
+            data,
+            &client,
+            &req_data.redirect_uri,
+            req_data.state.as_deref(),
+            response_mode,
+        )
+        .await?;
+
+    SessionDevice::upsert(&session.id, &user.id, req).await?;
+    session.set_login_origin(&client.id, auth_method);
+    if mfa_verified {
+        session.is_mfa = true;
+    }
+    session.save().await?;
 
     // TODO double check that we do not have any problems with the direct webauthn login here
     // TODO should we allow to skip this step if set so in the config?
     // check if we need to validate the 2nd factor
-    if user.has_webauthn_enabled() {
+    if user.has_webauthn_enabled() && !mfa_verified && !device_trusted {
         session.set_mfa(true).await?;
 
         let step = AuthStepAwaitWebauthn {
@@ -145,34 +455,101 @@ pub async fn post_authorize(
             user_id: user.id.clone(),
             email: user.email,
             exp: *WEBAUTHN_REQ_EXP,
+            has_totp,
             session,
         };
 
         WebauthnLoginReq {
             code: step.code.clone(),
             user_id: user.id,
+            client_id: client.id,
             header_loc: loc,
             header_origin: step
                 .header_origin
                 .as_ref()
                 .map(|h| h.1.to_str().unwrap().to_string()),
+            tos_accept,
+            profile_values,
+            consent_accept,
+            remember_me,
+            trust_device: wants_trust_device,
         }
         .save()
         .await?;
 
         Ok(AuthStep::AwaitWebauthn(step))
     } else {
+        if let Some(version) = tos_accept {
+            TosAcceptance::accept(req, user.id.clone(), version).await?;
+        }
+        if let Some(values) = profile_values {
+            UserAttrValueEntity::save_at_login(&user.id, values).await?;
+        }
+        if let Some(scopes) = consent_accept {
+            UserConsent::grant(user.id.clone(), client.id.clone(), scopes).await?;
+        }
+        if remember_me {
+            session.set_remember_me(user.user_expires).await?;
+        }
+        // a new trusted device only makes sense, if a 2nd factor has actually been verified
+        let trust_device = wants_trust_device && !device_trusted && (mfa_verified || has_email_otp);
+
         Ok(AuthStep::LoggedIn(AuthStepLoggedIn {
             user_id: user.id,
             email: user.email,
             header_loc: (header::LOCATION, HeaderValue::from_str(&loc).unwrap()),
             header_csrf: Session::get_csrf_header(&session.csrf_token),
             header_origin,
+            trust_device,
         }))
     }
 }
 
+/// Either evicts the given sessions or rejects the login, depending on the
+/// `SESSION_LIMIT_BEHAVIOR`.
+async fn enforce_session_limit(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    user: &User,
+    over_limit: Vec<Session>,
+) -> Result<(), ErrorResponse> {
+    let ip = real_ip_from_req(req)?.to_string();
+
+    match *SESSION_LIMIT_BEHAVIOR {
+        SessionLimitBehavior::Evict => {
+            let count = over_limit.len();
+            for session in over_limit {
+                RefreshToken::invalidate_for_session(&session.id).await?;
+                session.invalidate().await?;
+            }
+
+            Event::session_limit_reached(
+                format!("{} oldest sessions evicted for user: {}", count, user.email),
+                ip,
+            )
+            .with_user_id(user.id.clone())
+            .send(&data.tx_events)
+            .await?;
+
+            Ok(())
+        }
+        SessionLimitBehavior::Reject => {
+            Event::session_limit_reached(format!("Login rejected for user: {}", user.email), ip)
+                .with_user_id(user.id.clone())
+                .send(&data.tx_events)
+                .await?;
+
+            Err(ErrorResponse::new(
+                ErrorResponseType::Forbidden,
+                "The maximum number of active sessions has been reached",
+            )
+            .with_code(ErrorCode::AccessDenied))
+        }
+    }
+}
+
 pub async fn post_authorize_refresh(
+    data: &web::Data<AppState>,
     session: &Session,
     client: Client,
     header_origin: Option<(HeaderName, HeaderValue)>,
@@ -188,33 +565,65 @@ pub async fn post_authorize_refresh(
     user.check_enabled()?;
     user.check_expired()?;
 
-    client.validate_mfa(&user)?;
+    ClientAccessPolicy::validate_user(&client.id, &user).await?;
+    let has_otp = UserTotp::is_enabled(&user.id).await?
+        || UserYubicoOtp::is_enabled(&user.id).await?
+        || UserSmsOtp::is_enabled(&user.id).await?;
+    client.validate_mfa(&user, has_otp)?;
+    Group::validate_mfa(&client.id, &user, has_otp).await?;
+    user.validate_mfa_reenroll(&client.id, has_otp)?;
+    client.validate_session(session, clock::now().timestamp())?;
+    JwtAcrValue::validate_session(req_data.acr_values.as_deref(), session)?;
 
-    let scopes = client.sanitize_login_scopes(&req_data.scopes)?;
+    let mut scopes = client.sanitize_login_scopes(&req_data.scopes)?;
+    ClaimsRequest::extend_scopes(req_data.claims.as_deref(), &client, &mut scopes).await?;
+    let consent_accept =
+        match check_consent(&client, &user.id, &scopes, req_data.consent_accept).await? {
+            ConsentCheck::Granted(consent_accept) => consent_accept,
+            ConsentCheck::Required(missing) => {
+                return Ok(AuthStep::AwaitConsent(AuthStepAwaitConsent {
+                    client_id: client.id,
+                    client_name: client.name,
+                    scopes: missing,
+                    header_csrf: Session::get_csrf_header(&session.csrf_token),
+                    header_origin,
+                }));
+            }
+        };
+    let response_mode = AuthResponseMode::parse(req_data.response_mode.as_deref(), &client)?;
+    client.validate_resource(req_data.resource.as_deref())?;
     let code_lifetime = if user.has_webauthn_enabled() {
         client.auth_code_lifetime + *WEBAUTHN_REQ_EXP as i32
     } else {
         client.auth_code_lifetime
     };
 
+    // no new authentication happens here, the user keeps the `auth_time` of the session
+    let auth_time = session.last_auth();
     let code = AuthCode::new(
         user.id.clone(),
-        client.id,
+        client.id.clone(),
         Some(session.id.clone()),
         req_data.code_challenge,
         req_data.code_challenge_method,
         req_data.nonce,
         scopes,
+        req_data.resource,
+        auth_time,
         code_lifetime,
     );
     code.save().await?;
 
     // build location header
-    let header_loc = if let Some(s) = req_data.state {
-        format!("{}?code={}&state={}", req_data.redirect_uri, code.id, s)
-    } else {
-        format!("{}?code={}", req_data.redirect_uri, code.id)
-    };
+    let header_loc = code
+        .response_location(
+            data,
+            &client,
+            &req_data.redirect_uri,
+            req_data.state.as_deref(),
+            response_mode,
+        )
+        .await?;
 
     // check if we need to validate the 2nd factor
     if user.has_webauthn_enabled() && *SESSION_RENEW_MFA {
@@ -225,22 +634,34 @@ pub async fn post_authorize_refresh(
             user_id: user.id.clone(),
             email: user.email,
             exp: *WEBAUTHN_REQ_EXP,
+            // a session refresh has no password -> TOTP cannot be used here
+            has_totp: false,
             session: session.clone(),
         };
 
         let login_req = WebauthnLoginReq {
             code: step.code.clone(),
             user_id: user.id,
+            client_id: client.id,
             header_loc,
             header_origin: step
                 .header_origin
                 .as_ref()
                 .map(|h| h.1.to_str().unwrap().to_string()),
+            tos_accept: None,
+            profile_values: None,
+            consent_accept,
+            remember_me: false,
+            trust_device: false,
         };
         login_req.save().await?;
 
         Ok(AuthStep::AwaitWebauthn(step))
     } else {
+        if let Some(scopes) = consent_accept {
+            UserConsent::grant(user.id.clone(), client.id.clone(), scopes).await?;
+        }
+
         Ok(AuthStep::LoggedIn(AuthStepLoggedIn {
             user_id: user.id,
             email: user.email,
@@ -250,6 +671,39 @@ pub async fn post_authorize_refresh(
             ),
             header_csrf: Session::get_csrf_header(&session.csrf_token),
             header_origin,
+            trust_device: false,
         }))
     }
 }
+
+enum ConsentCheck {
+    /// Contains the newly accepted scopes, which must be persisted once the login succeeds.
+    Granted(Option<Vec<String>>),
+    /// Contains the scopes, which must be granted on the consent screen first.
+    Required(Vec<String>),
+}
+
+/// Checks the requested `scopes` against the consent already given by the user and the scopes
+/// accepted with the current request.
+async fn check_consent(
+    client: &Client,
+    user_id: &str,
+    scopes: &[String],
+    consent_accept: Option<Vec<String>>,
+) -> Result<ConsentCheck, ErrorResponse> {
+    if !client.consent_required {
+        return Ok(ConsentCheck::Granted(None));
+    }
+
+    let missing = UserConsent::find_missing(user_id, &client.id, scopes).await?;
+    if missing.is_empty() {
+        return Ok(ConsentCheck::Granted(None));
+    }
+
+    match consent_accept {
+        Some(accepted) if missing.iter().all(|s| accepted.contains(s)) => {
+            Ok(ConsentCheck::Granted(Some(missing)))
+        }
+        _ => Ok(ConsentCheck::Required(missing)),
+    }
+}
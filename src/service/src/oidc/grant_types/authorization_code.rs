@@ -1,10 +1,11 @@
 use crate::token_set::{
-    AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, TokenNonce, TokenScopes, TokenSet,
+    AuthCodeFlow, AuthTime, CertFingerprint, DeviceCodeFlow, DpopFingerprint, ImpersonatorId,
+    SessionId, TokenNonce, TokenResource, TokenScopes, TokenSet,
 };
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest};
-use chrono::Utc;
 use rauthy_api_types::oidc::TokenRequest;
+use rauthy_common::clock;
 use rauthy_common::constants::HEADER_DPOP_NONCE;
 use rauthy_common::utils::{base64_url_encode, real_ip_from_req};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -14,6 +15,7 @@ use rauthy_models::entity::clients::Client;
 use rauthy_models::entity::clients_dyn::ClientDyn;
 use rauthy_models::entity::dpop_proof::DPoPProof;
 use rauthy_models::entity::sessions::{Session, SessionState};
+use rauthy_models::entity::sessions_clients::SessionClient;
 use rauthy_models::entity::users::User;
 use ring::digest;
 use std::str::FromStr;
@@ -47,13 +49,19 @@ pub async fn grant_type_authorization_code(
             )
         })?;
     let header_origin = client.validate_origin(&req, &data.listen_scheme, &data.public_url)?;
-    if client.confidential {
-        let secret = client_secret.ok_or_else(|| {
-            warn!("'client_secret' is missing");
-            ErrorResponse::new(ErrorResponseType::BadRequest, "'client_secret' is missing")
-        })?;
-        client.validate_secret(&secret, &req)?;
-    }
+    let cert_fingerprint = if client.confidential {
+        client
+            .validate_client_auth(
+                &data.issuer,
+                client_secret,
+                req_data.client_assertion()?,
+                &req,
+            )
+            .await?
+            .map(|cert| CertFingerprint(cert.x5t_s256()))
+    } else {
+        None
+    };
     client.validate_flow("authorization_code")?;
 
     // check for DPoP header
@@ -95,13 +103,21 @@ pub async fn grant_type_authorization_code(
         warn!(err);
         return Err(ErrorResponse::new(ErrorResponseType::Unauthorized, err));
     }
-    if code.exp < Utc::now().timestamp() {
+    if code.exp < clock::now().timestamp() {
         warn!("The Authorization Code has expired");
         return Err(ErrorResponse::new(
             ErrorResponseType::SessionExpired,
             "The Authorization Code has expired",
         ));
     }
+    if client.require_pkce_s256 && code.challenge_method.as_deref() != Some("S256") {
+        // the PKCE policy may have changed since the code has been issued
+        warn!("'S256' PKCE is required for this client");
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "PKCE with code_challenge_method 'S256' is required for this client",
+        ));
+    }
     if code.challenge.is_some() {
         if req_data.code_verifier.is_none() {
             warn!("'code_verifier' is missing");
@@ -131,8 +147,28 @@ pub async fn grant_type_authorization_code(
                 ));
             }
         }
+    } else if client.challenge.is_some() {
+        // the PKCE policy may have changed since the code has been issued
+        warn!("'code_challenge' is required for this client");
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "'code_challenge' is required for this client",
+        ));
     }
 
+    // a resource at the token endpoint must not differ from the authorized one
+    let resource = match (code.resource.clone(), req_data.resource) {
+        (Some(authorized), Some(requested)) if authorized != requested => {
+            warn!("'resource' does not match the authorized one");
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "invalid_target: 'resource' does not match the authorized one",
+            ));
+        }
+        (authorized, requested) => requested.or(authorized),
+    };
+    client.validate_resource(resource.as_deref())?;
+
     // We will not perform another `redirect_uri` check at this point, like stated in the RFC.
     // It is just unnecessary because of the way Rauthy handles the flow init during GET /authorize.
     //
@@ -144,26 +180,45 @@ pub async fn grant_type_authorization_code(
     // An additional check at this point does not provide any security benefit but only uses resources.
 
     let user = User::find(code.user_id.clone()).await?;
+    let session = if let Some(sid) = &code.session_id {
+        Some(Session::find(sid.clone()).await?)
+    } else {
+        None
+    };
+    let impersonator = session
+        .as_ref()
+        .and_then(|s| s.impersonator_id.clone())
+        .map(ImpersonatorId);
+    // the user may have been disabled or expired since the impersonation has been started
+    if impersonator.is_some() {
+        user.check_enabled()?;
+        user.check_expired()?;
+    }
+    let session_id = session.as_ref().map(|s| SessionId(s.id.clone()));
+
     let token_set = TokenSet::from_user(
         &user,
         data,
         &client,
-        AuthTime::given(user.last_login.unwrap_or_else(|| Utc::now().timestamp())),
+        AuthTime::given(code.auth_time),
         dpop_fingerprint,
+        cert_fingerprint,
         code.nonce.clone().map(TokenNonce),
         Some(TokenScopes(code.scopes.join(" "))),
         AuthCodeFlow::Yes,
         DeviceCodeFlow::No,
+        impersonator,
+        session_id,
+        resource.map(TokenResource),
+        None,
     )
     .await?;
 
     // update session metadata
-    if code.session_id.is_some() {
-        let sid = code.session_id.as_ref().unwrap().clone();
-        let mut session = Session::find(sid).await?;
-
-        session.last_seen = Utc::now().timestamp();
+    if let Some(mut session) = session {
+        session.last_seen = clock::now().timestamp();
         session.state = SessionState::Auth.as_str().to_string();
+        session.auth_time = Some(code.auth_time);
         if let Err(err) = session.validate_user_expiry(&user) {
             code.delete().await?;
             return Err(err);
@@ -173,6 +228,9 @@ pub async fn grant_type_authorization_code(
         session.roles = Some(user.roles);
         session.groups = user.groups;
         session.save().await?;
+
+        // remember the client for a possible front-channel logout
+        SessionClient::insert(session.id, client.id.clone()).await?;
     }
     code.delete().await?;
 
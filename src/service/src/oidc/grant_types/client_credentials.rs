@@ -1,4 +1,4 @@
-use crate::token_set::{DpopFingerprint, TokenSet};
+use crate::token_set::{CertFingerprint, DpopFingerprint, TokenResource, TokenSet};
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest};
 use rauthy_api_types::oidc::TokenRequest;
@@ -16,13 +16,6 @@ pub async fn grant_type_credentials(
     req: HttpRequest,
     req_data: TokenRequest,
 ) -> Result<(TokenSet, Vec<(HeaderName, HeaderValue)>), ErrorResponse> {
-    if req_data.client_secret.is_none() {
-        return Err(ErrorResponse::new(
-            ErrorResponseType::BadRequest,
-            "'client_secret' is missing",
-        ));
-    }
-
     let (client_id, client_secret) = req_data.try_get_client_id_secret(&req)?;
     let client = Client::find(client_id).await?;
     if !client.confidential {
@@ -37,11 +30,17 @@ pub async fn grant_type_credentials(
             "client is disabled",
         ));
     }
-    let secret = client_secret.ok_or_else(|| {
-        ErrorResponse::new(ErrorResponseType::BadRequest, "'client_secret' is missing")
-    })?;
-    client.validate_secret(&secret, &req)?;
+    let cert_fingerprint = client
+        .validate_client_auth(
+            &data.issuer,
+            client_secret,
+            req_data.client_assertion()?,
+            &req,
+        )
+        .await?
+        .map(|cert| CertFingerprint(cert.x5t_s256()));
     client.validate_flow("client_credentials")?;
+    client.validate_resource(req_data.resource.as_deref())?;
     let header_origin = client.validate_origin(&req, &data.listen_scheme, &data.public_url)?;
 
     let mut headers = Vec::new();
@@ -65,6 +64,13 @@ pub async fn grant_type_credentials(
         ClientDyn::update_used(&client.id).await?;
     }
 
-    let ts = TokenSet::for_client_credentials(data, &client, dpop_fingerprint).await?;
+    let ts = TokenSet::for_client_credentials(
+        data,
+        &client,
+        dpop_fingerprint,
+        cert_fingerprint,
+        req_data.resource.map(TokenResource),
+    )
+    .await?;
     Ok((ts, headers))
 }
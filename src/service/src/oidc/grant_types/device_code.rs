@@ -1,7 +1,7 @@
 use crate::token_set::{AuthCodeFlow, AuthTime, DeviceCodeFlow, TokenScopes, TokenSet};
 use actix_web::{web, HttpResponse};
-use chrono::Utc;
 use rauthy_api_types::oidc::{OAuth2ErrorResponse, OAuth2ErrorTypeResponse, TokenRequest};
+use rauthy_common::clock;
 use rauthy_common::constants::DEVICE_GRANT_POLL_INTERVAL;
 use rauthy_common::utils::new_store_id;
 use rauthy_models::app_state::AppState;
@@ -64,13 +64,24 @@ pub async fn grant_type_device_code(
     }
 
     debug!("device oidc code poll request is valid");
+
+    if code.denied {
+        if let Err(err) = code.delete().await {
+            // should really never happen - in cache only
+            error!("Error deleting DeviceAuthCode: {:?}", err);
+        }
+        return HttpResponse::BadRequest().json(OAuth2ErrorResponse {
+            error: OAuth2ErrorTypeResponse::AccessDenied,
+            error_description: Some(Cow::from("the user has declined the request")),
+        });
+    }
     let mut error = OAuth2ErrorTypeResponse::AuthorizationPending;
     let mut error_description = Cow::default();
 
     // Check last_poll and make sure interval is being respected.
     // We allow it to be 500ms shorter than specified to not get into
     // possible problems with slightly inaccurate client implementations.
-    let now = Utc::now();
+    let now = clock::now();
     let poll_thres = now
         .sub(chrono::Duration::seconds(
             *DEVICE_GRANT_POLL_INTERVAL as i64,
@@ -167,9 +178,14 @@ pub async fn grant_type_device_code(
             AuthTime::now(),
             None,
             None,
+            None,
             code.scopes.map(TokenScopes),
             AuthCodeFlow::No,
             DeviceCodeFlow::Yes(id),
+            None,
+            None,
+            None,
+            None,
         )
         .await
         {
@@ -1,16 +1,22 @@
-use crate::token_set::{AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, TokenSet};
+use crate::token_set::{
+    AuthCodeFlow, AuthTime, CertFingerprint, DeviceCodeFlow, DpopFingerprint, TokenResource,
+    TokenSet,
+};
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest};
-use chrono::Utc;
 use rauthy_api_types::oidc::TokenRequest;
+use rauthy_common::clock;
 use rauthy_common::constants::HEADER_DPOP_NONCE;
 use rauthy_common::password_hasher::HashPassword;
 use rauthy_common::utils::real_ip_from_req;
-use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::clients_access::ClientAccessPolicy;
 use rauthy_models::entity::clients_dyn::ClientDyn;
 use rauthy_models::entity::dpop_proof::DPoPProof;
+use rauthy_models::entity::groups::Group;
+use rauthy_models::entity::role_mapping::RoleMappingRule;
 use rauthy_models::entity::users::User;
 use std::str::FromStr;
 use tracing::{info, warn};
@@ -35,18 +41,27 @@ pub async fn grant_type_password(
     }
 
     let (client_id, client_secret) = req_data.try_get_client_id_secret(&req)?;
+    let client_assertion = req_data.client_assertion()?.map(String::from);
     let email = req_data.username.as_ref().unwrap();
     let password = req_data.password.unwrap();
 
     let client = Client::find(client_id).await?;
     let header_origin = client.validate_origin(&req, &data.listen_scheme, &data.public_url)?;
-    if client.confidential {
-        let secret = client_secret.ok_or_else(|| {
-            ErrorResponse::new(ErrorResponseType::BadRequest, "Missing 'client_secret'")
-        })?;
-        client.validate_secret(&secret, &req)?;
-    }
+    let cert_fingerprint = if client.confidential {
+        client
+            .validate_client_auth(
+                &data.issuer,
+                client_secret,
+                client_assertion.as_deref(),
+                &req,
+            )
+            .await?
+            .map(|cert| CertFingerprint(cert.x5t_s256()))
+    } else {
+        None
+    };
     client.validate_flow("password")?;
+    client.validate_resource(req_data.resource.as_deref())?;
 
     let mut headers = Vec::new();
     let dpop_fingerprint =
@@ -70,10 +85,21 @@ pub async fn grant_type_password(
     let mut user = User::find_by_email(String::from(email)).await?;
     user.check_enabled()?;
     user.check_expired()?;
+    ClientAccessPolicy::validate_user(&client.id, &user).await?;
 
     match user.validate_password(data, password.clone()).await {
         Ok(_) => {
-            user.last_login = Some(Utc::now().timestamp());
+            user.check_locked()?;
+            // The password flow cannot verify a 2nd factor. This is checked only after a valid
+            // password to not leak any group memberships.
+            if Group::forces_mfa(&user).await? {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::MfaRequired,
+                    "MFA is required for the groups of this user, which is not possible with \
+                    the password flow",
+                ));
+            }
+            user.last_login = Some(clock::now().timestamp());
             user.last_failed_login = None;
             user.failed_login_attempts = None;
 
@@ -85,6 +111,7 @@ pub async fn grant_type_password(
                 // let new_hash = User::new_password_hash(&password, params).await?;
                 user.password = Some(new_hash);
             }
+            RoleMappingRule::apply(&mut user, None).await?;
 
             user.save(None).await?;
 
@@ -99,10 +126,15 @@ pub async fn grant_type_password(
                 &client,
                 AuthTime::now(),
                 dpop_fingerprint,
+                cert_fingerprint,
                 None,
                 None,
                 AuthCodeFlow::No,
                 DeviceCodeFlow::No,
+                None,
+                None,
+                req_data.resource.map(TokenResource),
+                None,
             )
             .await?;
             Ok((ts, headers))
@@ -114,10 +146,9 @@ pub async fn grant_type_password(
                 user.email
             );
 
-            user.last_failed_login = Some(Utc::now().timestamp());
-            user.failed_login_attempts = Some(&user.failed_login_attempts.unwrap_or(0) + 1);
-
-            user.save(None).await?;
+            if err.code == ErrorCode::InvalidCredentials {
+                user.register_failed_login(data, &req).await?;
+            }
 
             // TODO add expo increasing sleeps after failed login attempts here?
             Err(err)
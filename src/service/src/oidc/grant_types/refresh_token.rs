@@ -1,5 +1,5 @@
 use crate::oidc::validation;
-use crate::token_set::TokenSet;
+use crate::token_set::{CertFingerprint, TokenResource, TokenSet};
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest};
 use rauthy_api_types::oidc::TokenRequest;
@@ -26,20 +26,35 @@ pub async fn grant_type_refresh(
 
     let header_origin = client.validate_origin(&req, &data.listen_scheme, &data.public_url)?;
 
-    if client.confidential {
-        let secret = client_secret.ok_or_else(|| {
-            ErrorResponse::new(ErrorResponseType::BadRequest, "'client_secret' is missing")
-        })?;
-        client.validate_secret(&secret, &req)?;
-    }
+    let cert_fingerprint = if client.confidential {
+        client
+            .validate_client_auth(
+                &data.issuer,
+                client_secret,
+                req_data.client_assertion()?,
+                &req,
+            )
+            .await?
+            .map(|cert| CertFingerprint(cert.x5t_s256()))
+    } else {
+        None
+    };
 
     client.validate_flow("refresh_token")?;
+    client.validate_resource(req_data.resource.as_deref())?;
 
     let refresh_token = req_data.refresh_token.unwrap();
 
     // validate common refresh token claims first and get the payload
-    let (ts, dpop_none) =
-        validation::validate_refresh_token(Some(client), &refresh_token, data, &req).await?;
+    let (ts, dpop_none) = validation::validate_refresh_token(
+        Some(client),
+        &refresh_token,
+        data,
+        &req,
+        cert_fingerprint,
+        req_data.resource.map(TokenResource),
+    )
+    .await?;
 
     let mut headers = Vec::new();
     if let Some(h) = header_origin {
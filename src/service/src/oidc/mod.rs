@@ -15,6 +15,7 @@ pub mod authorize;
 mod grant_types;
 pub mod helpers;
 pub mod logout;
+pub mod revocation;
 pub mod token_info;
 pub mod userinfo;
 pub mod validation;
@@ -0,0 +1,115 @@
+use actix_web::{web, HttpRequest};
+use jwt_simple::claims::JWTClaims;
+use jwt_simple::prelude::*;
+use rauthy_api_types::oidc::TokenRevocationRequest;
+use rauthy_common::constants::JWT_CLOCK_SKEW_SECS;
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::jti_denylist::JtiDenylist;
+use rauthy_models::entity::jwk::{JwkKeyPair, JwkKeyPairAlg};
+use rauthy_models::entity::opaque_tokens::OpaqueToken;
+use rauthy_models::entity::refresh_tokens::RefreshToken;
+use rauthy_models::entity::refresh_tokens_devices::RefreshTokenDevice;
+use rauthy_models::entity::sessions::Session;
+use rauthy_models::jwt_now;
+use rauthy_models::{validate_jwt, JwtCommonClaims, JwtTokenType};
+use std::collections::HashSet;
+use tracing::{debug, info};
+
+/// Revokes the given token as defined in RFC 7009.
+///
+/// Revoking a refresh token invalidates its whole rotation family and the session it has been
+/// issued for. JWT access tokens will be added to the `jti` denylist until they expire, while
+/// opaque access tokens will be deleted right away.
+///
+/// Invalid, expired or unknown tokens are not an error, as the client cannot do anything about it.
+pub async fn revoke_token(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    payload: TokenRevocationRequest,
+) -> Result<(), ErrorResponse> {
+    let (client_id, client_secret) = payload.try_get_client_id_secret(req)?;
+    let client = Client::find_maybe_ephemeral(client_id)
+        .await
+        .map_err(|_| ErrorResponse::new(ErrorResponseType::Unauthorized, "invalid_client"))?;
+    if client.confidential {
+        client
+            .validate_client_auth(
+                &data.issuer,
+                client_secret,
+                payload.client_assertion()?,
+                req,
+            )
+            .await?;
+    }
+
+    let token = payload.token.as_str();
+    let Ok(claims) = token_claims(data, token).await else {
+        debug!("Ignoring revocation for an invalid or unknown token");
+        return Ok(());
+    };
+
+    // a client must only ever be able to revoke its own tokens
+    if claims.custom.azp != client.id {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::Unauthorized,
+            "The token has not been issued for this client",
+        ));
+    }
+
+    if OpaqueToken::is_opaque(token) {
+        return OpaqueToken::delete(token).await;
+    }
+    if claims.custom.typ != JwtTokenType::Refresh {
+        return match (claims.jwt_id, claims.expires_at) {
+            (Some(jti), Some(exp)) => JtiDenylist::deny(jti, exp.as_secs() as i64).await,
+            _ => {
+                debug!("JWT access tokens without a 'jti' cannot be revoked before they expire");
+                Ok(())
+            }
+        };
+    }
+
+    let (_, validation_str) = token.split_at(token.len() - 49);
+    if claims.custom.did.is_some() {
+        if let Ok(rt) = RefreshTokenDevice::find(validation_str).await {
+            rt.delete().await?;
+        }
+        return Ok(());
+    }
+
+    if let Some(sid) = RefreshToken::revoke_family(validation_str).await? {
+        if let Ok(session) = Session::find(sid).await {
+            session.invalidate().await?;
+        }
+    }
+    info!(
+        "Refresh token family for client '{}' has been revoked",
+        client.id
+    );
+
+    Ok(())
+}
+
+async fn token_claims(
+    data: &web::Data<AppState>,
+    token: &str,
+) -> Result<JWTClaims<JwtCommonClaims>, ErrorResponse> {
+    if OpaqueToken::is_opaque(token) {
+        return OpaqueToken::resolve(token).await;
+    }
+
+    let options = VerificationOptions {
+        allowed_issuers: Some(HashSet::from_strings(&[&data.issuer])),
+        // refresh tokens are usually not valid before the access token has expired
+        accept_future: true,
+        time_tolerance: Some(Duration::from_secs(*JWT_CLOCK_SKEW_SECS as u64)),
+        artificial_time: Some(jwt_now()),
+        ..Default::default()
+    };
+
+    let kid = JwkKeyPair::kid_from_token(token)?;
+    let kp = JwkKeyPair::find(kid).await?;
+    validate_jwt!(JwtCommonClaims, kp, token, options)
+}
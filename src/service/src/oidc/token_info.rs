@@ -7,14 +7,17 @@ use rauthy_common::constants::DANGER_DISABLE_INTROSPECT_AUTH;
 use rauthy_common::utils::base64_decode;
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
+use rauthy_models::entity::client_assertions::ClientAssertion;
 use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::mtls::ClientCert;
 use rauthy_models::{JwtAccessClaims, JwtCommonClaims};
-use tracing::error;
+use tracing::{error, warn};
 
 pub async fn get_token_info(
     data: &web::Data<AppState>,
     req: &HttpRequest,
     token: &str,
+    client_assertion: Option<&str>,
 ) -> Result<TokenInfo, ErrorResponse> {
     let claims_res = validation::validate_token::<JwtCommonClaims>(data, token).await;
     if claims_res.is_err() {
@@ -33,7 +36,27 @@ pub async fn get_token_info(
         });
     }
 
-    let client_id = check_client_auth(data, req, claims.custom.azp).await?;
+    let client_id = check_client_auth(data, req, claims.custom.azp, client_assertion).await?;
+
+    // If a client certificate has been forwarded, it must match the one a certificate bound token
+    // has been issued for. Otherwise, the resource server needs to compare the returned `cnf`.
+    if let Some(x5t) = claims
+        .custom
+        .cnf
+        .as_ref()
+        .and_then(|cnf| cnf.x5t_s256.as_deref())
+    {
+        let is_bound = ClientCert::opt_from_req(req)?
+            .map(|cert| cert.x5t_s256() == x5t)
+            .unwrap_or(true);
+        if !is_bound {
+            warn!("Client certificate does not match the 'x5t#S256' of the token");
+            return Ok(TokenInfo {
+                active: false,
+                ..Default::default()
+            });
+        }
+    }
 
     let aud_set = claims.audiences.unwrap().into_set();
     let aud = aud_set.into_iter().collect::<Vec<_>>().first().cloned();
@@ -57,21 +80,19 @@ async fn check_client_auth(
     data: &web::Data<AppState>,
     req: &HttpRequest,
     client_id: String,
+    client_assertion: Option<&str>,
 ) -> Result<String, ErrorResponse> {
     if *DANGER_DISABLE_INTROSPECT_AUTH {
         return Ok(client_id);
     }
 
-    let header_value = match req.headers().get(AUTHORIZATION) {
-        None => {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::WWWAuthenticate("authorization-header-missing".to_string()),
-                "Authorization header is missing",
-            ));
-        }
-        Some(h) => h,
-    };
-    let header = header_value.to_str().unwrap_or_default();
+    let header_value = req.headers().get(AUTHORIZATION);
+    if header_value.is_none() && client_assertion.is_none() {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::WWWAuthenticate("authorization-header-missing".to_string()),
+            "Authorization header is missing",
+        ));
+    }
 
     let client = Client::find(client_id).await.map_err(|_| {
         ErrorResponse::new(
@@ -87,6 +108,14 @@ async fn check_client_auth(
         ));
     }
 
+    let Some(header_value) = header_value else {
+        // the `sub` of the assertion must match the `azp` of the token
+        let assertion = client_assertion.unwrap_or_default();
+        ClientAssertion::validate(&data.issuer, &client, assertion).await?;
+        return Ok(client.id);
+    };
+    let header = header_value.to_str().unwrap_or_default();
+
     if let Some(token) = header.strip_prefix("Bearer ") {
         validate_token::<JwtAccessClaims>(data, token).await?;
         Ok(client.id)
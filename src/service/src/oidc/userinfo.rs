@@ -1,21 +1,80 @@
 use crate::oidc::{helpers, validation};
-use actix_web::{web, HttpRequest};
+use actix_web::{web, HttpRequest, HttpResponse};
+use jwt_simple::algorithms::{ECDSAP384KeyPairLike, EdDSAKeyPairLike, RSAKeyPairLike};
+use jwt_simple::prelude::coarsetime;
 use rauthy_api_types::users::Userinfo;
 use rauthy_common::constants::{ENABLE_WEB_ID, USERINFO_STRICT};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::clients_user_metadata::ClientUserMetadata;
 use rauthy_models::entity::devices::DeviceEntity;
+use rauthy_models::entity::jwe::JweEncrypter;
+use rauthy_models::entity::jwk::{JwkKeyPair, JwkKeyPairAlg};
+use rauthy_models::entity::scopes::{Scope, ScopeClaimTarget};
+use rauthy_models::entity::user_pictures::UserPicture;
 use rauthy_models::entity::users::User;
 use rauthy_models::entity::users_values::UserValues;
 use rauthy_models::entity::webids::WebId;
-use rauthy_models::{AddressClaim, JwtCommonClaims, JwtTokenType};
+use rauthy_models::jwt_claims;
+use rauthy_models::{sign_jwt, AddressClaim, JwtCommonClaims, JwtTokenType};
+use std::str::FromStr;
+
+// signed userinfo responses are only valid for a short time
+const USERINFO_JWT_LIFETIME: u64 = 60;
 
 /// Returns the 'userInfo' for the [/oidc/userinfo endpoint](crate::handlers::get_userinfo)<br>
 pub async fn get_userinfo(
     data: &web::Data<AppState>,
     req: HttpRequest,
 ) -> Result<Userinfo, ErrorResponse> {
+    userinfo_with_client(data, req)
+        .await
+        .map(|(userinfo, _)| userinfo)
+}
+
+/// Returns the userinfo response as JSON, or as a signed JWT, if the client has requested a
+/// `userinfo_signed_response_alg`. With userinfo encryption, the signed JWT will be encrypted.
+pub async fn get_userinfo_response(
+    data: &web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ErrorResponse> {
+    let (userinfo, client) = userinfo_with_client(data, req).await?;
+    let Some(client) = client else {
+        return Ok(HttpResponse::Ok().json(userinfo));
+    };
+    let jwe = JweEncrypter::for_userinfo(&client).await?;
+    if jwe.is_none() && client.userinfo_signed_response_alg.is_none() {
+        return Ok(HttpResponse::Ok().json(userinfo));
+    }
+
+    // an encrypted response must be signed first, with the ID token alg as the fallback
+    let claims = jwt_claims(
+        serde_json::to_value(userinfo)?,
+        coarsetime::Duration::from_secs(USERINFO_JWT_LIFETIME),
+    )
+    .with_issuer(data.issuer.clone())
+    .with_audience(client.id.clone());
+    let alg = client
+        .userinfo_signed_response_alg
+        .as_deref()
+        .unwrap_or(&client.id_token_alg);
+    let kp = JwkKeyPair::find_latest(JwkKeyPairAlg::from_str(alg)?).await?;
+    let token = sign_jwt!(kp, claims)?;
+
+    let body = match jwe {
+        None => token,
+        Some(jwe) => jwe.encrypt(token.as_bytes(), Some("JWT"))?,
+    };
+    Ok(HttpResponse::Ok()
+        .content_type("application/jwt")
+        .body(body))
+}
+
+async fn userinfo_with_client(
+    data: &web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<(Userinfo, Option<Client>), ErrorResponse> {
     // get bearer token
     let bearer = helpers::get_bearer_token_from_header(req.headers())?;
 
@@ -49,6 +108,20 @@ pub async fn get_userinfo(
         ));
     }
 
+    // ephemeral clients can never use pairwise subjects
+    let client =
+        if claims.custom.azp.starts_with("http://") || claims.custom.azp.starts_with("https://") {
+            None
+        } else {
+            Client::find(claims.custom.azp.clone()).await.ok()
+        };
+    // must match the `sub` from the ID token, which is unique per sector for pairwise clients
+    let sub = client
+        .as_ref()
+        .map(|client| client.subject_for(&user.id))
+        .transpose()?
+        .unwrap_or_else(|| user.id.clone());
+
     if *USERINFO_STRICT {
         // if the token has been issued to a device, make sure it still exists and is valid
         if let Some(device_id) = claims.custom.did {
@@ -65,7 +138,7 @@ pub async fn get_userinfo(
         // skip this check if the client is ephemeral
         if !(claims.custom.azp.starts_with("http://") || claims.custom.azp.starts_with("https://"))
         {
-            let client = Client::find(claims.custom.azp).await.map_err(|_| {
+            let client = client.as_ref().ok_or_else(|| {
                 ErrorResponse::new(
                     ErrorResponseType::WWWAuthenticate("client-not-found".to_string()),
                     "The client has not been found".to_string(),
@@ -81,14 +154,14 @@ pub async fn get_userinfo(
         }
     }
 
-    let roles = user.get_roles();
-    let groups = scope.contains("groups").then(|| user.get_groups());
+    let (groups, roles) = user.get_groups_roles_effective().await?;
+    let groups = scope.contains("groups").then_some(groups);
     let webid =
         (*ENABLE_WEB_ID && scope.contains("webid")).then(|| WebId::resolve_webid_uri(&user.id));
 
     let mut userinfo = Userinfo {
-        id: user.id.clone(),
-        sub: user.id.clone(),
+        id: sub.clone(),
+        sub,
         name: user.email_recipient_name(),
         roles,
         mfa_enabled: user.has_webauthn_enabled(),
@@ -109,12 +182,17 @@ pub async fn get_userinfo(
         family_name: None,
         locale: None,
         birthdate: None,
+        picture: None,
 
         // scope: phone
         phone: None,
 
         // scope: webid
         webid,
+
+        client_metadata: ClientUserMetadata::claim(&claims.custom.azp, &user.id).await?,
+
+        custom: None,
     };
 
     if scope.contains("email") {
@@ -130,6 +208,9 @@ pub async fn get_userinfo(
         userinfo.given_name = Some(user.given_name.clone());
         userinfo.family_name = user.family_name.clone();
         userinfo.locale = Some(user.language.to_string());
+        userinfo.picture = UserPicture::find_updated(&user.id)
+            .await?
+            .map(|updated| UserPicture::claim_url(&data.issuer, &user.id, updated));
 
         user_values = UserValues::find(&user.id).await?;
         user_values_fetched = true;
@@ -165,5 +246,20 @@ pub async fn get_userinfo(
         }
     }
 
-    Ok(userinfo)
+    // the ID token claim mappings of custom scopes apply to the userinfo as well
+    let custom_scopes = Scope::extract_custom(&scope);
+    if !custom_scopes.is_empty() {
+        let scopes = Scope::find_all().await?;
+        let scopes = scopes
+            .iter()
+            .filter(|s| custom_scopes.contains(s.name.as_str()))
+            .collect::<Vec<_>>();
+        let custom =
+            Scope::resolve_claim_mappings(&scopes, ScopeClaimTarget::Id, &user, None).await?;
+        if !custom.is_empty() {
+            userinfo.custom = Some(custom);
+        }
+    }
+
+    Ok((userinfo, client))
 }
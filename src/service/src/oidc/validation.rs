@@ -1,21 +1,33 @@
 use crate::token_set::{
-    AuthCodeFlow, AuthTime, DeviceCodeFlow, DpopFingerprint, TokenScopes, TokenSet,
+    AuthCodeFlow, AuthTime, CertFingerprint, DeviceCodeFlow, DpopFingerprint, RetainedRefreshToken,
+    SessionId, TokenResource, TokenScopes, TokenSet,
 };
 use actix_web::http::header::{HeaderName, HeaderValue};
 use actix_web::{web, HttpRequest};
-use chrono::Utc;
 use jwt_simple::claims;
 use jwt_simple::claims::JWTClaims;
 use jwt_simple::common::VerificationOptions;
 use jwt_simple::prelude::*;
-use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_common::clock;
+use rauthy_common::constants::JWT_CLOCK_SKEW_SECS;
+use rauthy_common::utils::real_ip_from_req;
+use rauthy_error::{ErrorCode, ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::clients_access::ClientAccessPolicy;
 use rauthy_models::entity::dpop_proof::DPoPProof;
+use rauthy_models::entity::groups::Group;
+use rauthy_models::entity::jti_denylist::JtiDenylist;
 use rauthy_models::entity::jwk::{JwkKeyPair, JwkKeyPairAlg};
+use rauthy_models::entity::opaque_tokens::OpaqueToken;
 use rauthy_models::entity::refresh_tokens::RefreshToken;
 use rauthy_models::entity::refresh_tokens_devices::RefreshTokenDevice;
+use rauthy_models::entity::sms_otp::UserSmsOtp;
+use rauthy_models::entity::totp::UserTotp;
 use rauthy_models::entity::users::User;
+use rauthy_models::entity::yubico_otp::UserYubicoOtp;
+use rauthy_models::events::event::Event;
+use rauthy_models::jwt_now;
 use rauthy_models::{validate_jwt, JwtRefreshClaims, JwtTokenType};
 use std::collections::HashSet;
 use tracing::debug;
@@ -28,6 +40,19 @@ pub async fn validate_auth_req_param(
     redirect_uri: &str,
     code_challenge: &Option<String>,
     code_challenge_method: &Option<String>,
+) -> Result<(Client, Option<(HeaderName, HeaderValue)>), ErrorResponse> {
+    let (client, header) = validate_auth_req_client(data, req, client_id, redirect_uri).await?;
+    validate_auth_req_policy(&client, code_challenge, code_challenge_method)?;
+    Ok((client, header))
+}
+
+/// Validates the client, origin and `redirect_uri` of an authorization request. Errors after
+/// this point may be sent back to the `redirect_uri`.
+pub async fn validate_auth_req_client(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    client_id: &str,
+    redirect_uri: &str,
 ) -> Result<(Client, Option<(HeaderName, HeaderValue)>), ErrorResponse> {
     // client exists
     let client = Client::find_maybe_ephemeral(String::from(client_id)).await?;
@@ -38,6 +63,20 @@ pub async fn validate_auth_req_param(
     // allowed redirect uris
     client.validate_redirect_uri(redirect_uri)?;
 
+    Ok((client, header))
+}
+
+/// Validates the allowed flow and PKCE policy of the client for an authorization request.
+pub fn validate_auth_req_policy(
+    client: &Client,
+    code_challenge: &Option<String>,
+    code_challenge_method: &Option<String>,
+) -> Result<(), ErrorResponse> {
+    // codes can only be issued with the `authorization_code` flow
+    client
+        .validate_flow("authorization_code")
+        .map_err(|err| err.with_code(ErrorCode::UnauthorizedClient))?;
+
     // code challenge + method
     if client.challenge.is_some() {
         if code_challenge.is_none() {
@@ -55,9 +94,11 @@ pub async fn validate_auth_req_param(
             };
             client.validate_challenge_method(&method)?;
         }
+    } else if client.require_pkce_s256 {
+        client.validate_challenge_method(code_challenge_method.as_deref().unwrap_or_default())?;
     }
 
-    Ok((client, header))
+    Ok(())
 }
 
 /// Validates a given JWT Access Token
@@ -65,9 +106,16 @@ pub async fn validate_token<T: serde::Serialize + for<'de> ::serde::Deserialize<
     data: &web::Data<AppState>,
     token: &str,
 ) -> Result<claims::JWTClaims<T>, ErrorResponse> {
+    // opaque access tokens only reference the claims, which are stored on the server
+    if OpaqueToken::is_opaque(token) {
+        return OpaqueToken::resolve(token).await;
+    }
+
     let options = jwt_simple::prelude::VerificationOptions {
         // allowed_audiences: Some(HashSet::from_strings(&[&])), // TODO
         allowed_issuers: Some(HashSet::from_strings(&[&data.issuer])),
+        time_tolerance: Some(Duration::from_secs(*JWT_CLOCK_SKEW_SECS as u64)),
+        artificial_time: Some(jwt_now()),
         ..Default::default()
     };
 
@@ -76,9 +124,12 @@ pub async fn validate_token<T: serde::Serialize + for<'de> ::serde::Deserialize<
 
     // retrieve jwk for kid
     let kp = JwkKeyPair::find(kid).await?;
-    validate_jwt!(T, kp, token, options)
+    let claims: JWTClaims<T> = validate_jwt!(T, kp, token, options)?;
 
-    // TODO check roles if we add more users / roles
+    // the token may have been revoked before its expiry
+    JtiDenylist::validate_token(token).await?;
+
+    Ok(claims)
 }
 
 pub async fn validate_refresh_token(
@@ -88,10 +139,14 @@ pub async fn validate_refresh_token(
     refresh_token: &str,
     data: &web::Data<AppState>,
     req: &HttpRequest,
+    cert_fingerprint: Option<CertFingerprint>,
+    resource: Option<TokenResource>,
 ) -> Result<(TokenSet, Option<String>), ErrorResponse> {
     let options = VerificationOptions {
         // allowed_audiences: Some(HashSet::from_strings(&[&])), // TODO change after making client non-opt
         allowed_issuers: Some(HashSet::from_strings(&[&data.issuer])),
+        time_tolerance: Some(Duration::from_secs(*JWT_CLOCK_SKEW_SECS as u64)),
+        artificial_time: Some(jwt_now()),
         ..Default::default()
     };
 
@@ -129,37 +184,48 @@ pub async fn validate_refresh_token(
     let header_origin = client.validate_origin(req, &data.listen_scheme, &data.public_url)?;
 
     // validate DPoP proof
-    let (dpop_fingerprint, dpop_nonce) = if let Some(cnf) = claims.custom.cnf {
-        // if the refresh token contains the 'cnf' header, we must validate the DPoP as well
-        if let Some(proof) = DPoPProof::opt_validated_from(req, &header_origin).await? {
-            let fingerprint = proof.jwk_fingerprint()?;
-            if fingerprint != cnf.jkt {
+    let (dpop_fingerprint, dpop_nonce) =
+        if let Some(jkt) = claims.custom.cnf.and_then(|cnf| cnf.jkt) {
+            // if the refresh token contains the 'cnf' header, we must validate the DPoP as well
+            if let Some(proof) = DPoPProof::opt_validated_from(req, &header_origin).await? {
+                let fingerprint = proof.jwk_fingerprint()?;
+                if fingerprint != jkt {
+                    return Err(ErrorResponse::new(
+                        ErrorResponseType::Forbidden,
+                        "The refresh token is bound to a missing DPoP proof",
+                    ));
+                }
+                debug!("DPoP-Bound refresh token accepted");
+                (Some(DpopFingerprint(fingerprint)), proof.claims.nonce)
+            } else {
                 return Err(ErrorResponse::new(
                     ErrorResponseType::Forbidden,
                     "The refresh token is bound to a missing DPoP proof",
                 ));
             }
-            debug!("DPoP-Bound refresh token accepted");
-            (Some(DpopFingerprint(fingerprint)), proof.claims.nonce)
         } else {
-            return Err(ErrorResponse::new(
-                ErrorResponseType::Forbidden,
-                "The refresh token is bound to a missing DPoP proof",
-            ));
-        }
-    } else {
-        (None, None)
-    };
+            (None, None)
+        };
 
     let mut user = User::find(uid).await?;
     user.check_enabled()?;
     user.check_expired()?;
+    ClientAccessPolicy::validate_user(&client.id, &user).await?;
+    // a group may force MFA after the refresh token has been issued with a password only
+    let has_otp = UserTotp::is_enabled(&user.id).await?
+        || UserYubicoOtp::is_enabled(&user.id).await?
+        || UserSmsOtp::is_enabled(&user.id).await?;
+    Group::validate_mfa(&client.id, &user, has_otp).await?;
 
     // validate that it exists in the db and invalidate it afterward
     let (_, validation_str) = refresh_token.split_at(refresh_token.len() - 49);
-    let now = Utc::now().timestamp();
-    let exp_at_secs = now + data.refresh_grace_time as i64;
-    let rt_scope = if let Some(device_id) = &claims.custom.did {
+    let now = clock::now().timestamp();
+    let grace_time = client
+        .refresh_token_grace_time
+        .map(|secs| secs as i64)
+        .unwrap_or(data.refresh_grace_time as i64);
+    let exp_at_secs = now + grace_time;
+    let (rt_scope, rt_family) = if let Some(device_id) = &claims.custom.did {
         let mut rt = RefreshTokenDevice::find(validation_str).await?;
 
         if &rt.device_id != device_id {
@@ -175,25 +241,52 @@ pub async fn validate_refresh_token(
             ));
         }
 
-        if rt.exp > exp_at_secs + 1 {
+        if client.refresh_token_rotation && rt.exp > exp_at_secs + 1 {
             rt.exp = exp_at_secs;
             rt.save().await?;
         }
-        rt.scope
+        (rt.scope, None)
     } else {
         let mut rt = RefreshToken::find(validation_str).await?;
-        if rt.exp > exp_at_secs + 1 {
-            rt.exp = exp_at_secs;
+
+        if let Some(rotated_at) = rt.rotated_at {
+            // with reuse detection, rotated tokens are kept until their original expiry to be
+            // able to detect a replay, which most probably means that the token has been stolen
+            if now > rotated_at + grace_time {
+                RefreshToken::revoke_family(&rt.id).await?;
+                Event::refresh_token_reused(
+                    format!(
+                        "Refresh token for client `{}` and user `{}` has been reused",
+                        client.id, user.email
+                    ),
+                    real_ip_from_req(req)?.to_string(),
+                )
+                .with_user_id(user.id)
+                .send(&data.tx_events)
+                .await?;
+
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::NotFound,
+                    "Refresh Token does not exist",
+                ));
+            }
+        } else if client.refresh_token_rotation {
+            rt.rotated_at = Some(now);
+            if !client.refresh_token_reuse_detection && rt.exp > exp_at_secs + 1 {
+                rt.exp = exp_at_secs;
+            }
             rt.save().await?;
         }
-        rt.scope
+
+        let family_id = rt.family_id.unwrap_or(rt.id);
+        (rt.scope, Some((family_id, rt.session_id)))
     };
 
     // at this point, everything has been validated -> we can issue a new TokenSet safely
     debug!("Refresh Token - all good!");
 
     // set last login
-    user.last_login = Some(Utc::now().timestamp());
+    user.last_login = Some(clock::now().timestamp());
     user.save(None).await?;
 
     let auth_time = if let Some(ts) = claims.custom.auth_time {
@@ -206,18 +299,35 @@ pub async fn validate_refresh_token(
         AuthTime::now()
     };
 
+    let (family_id, session_id) = rt_family.unzip();
     let ts = TokenSet::from_user(
         &user,
         data,
         &client,
         auth_time,
         dpop_fingerprint,
+        cert_fingerprint,
         None,
         rt_scope.map(TokenScopes),
         AuthCodeFlow::No,
         DeviceCodeFlow::No,
+        None,
+        session_id.flatten().map(SessionId),
+        resource,
+        (!client.refresh_token_rotation).then(|| RetainedRefreshToken(refresh_token.to_string())),
     )
     .await?;
 
+    // the rotated token inherits the family, so it can be revoked as a whole later on
+    if !client.refresh_token_rotation {
+        return Ok((ts, dpop_nonce));
+    }
+    if let (Some(family_id), Some(token)) = (family_id, &ts.refresh_token) {
+        let (_, validation_str) = token.split_at(token.len() - 49);
+        let mut rt = RefreshToken::find(validation_str).await?;
+        rt.family_id = Some(family_id);
+        rt.save().await?;
+    }
+
     Ok((ts, dpop_nonce))
 }
@@ -1,8 +1,8 @@
 use actix_web::{cookie, web, HttpRequest, HttpResponse};
-use chrono::Utc;
 use rauthy_api_types::users::{
-    PasswordResetRequest, WebauthnRegFinishRequest, WebauthnRegStartRequest,
+    MfaPurpose, PasswordResetRequest, WebauthnRegFinishRequest, WebauthnRegStartRequest,
 };
+use rauthy_common::clock;
 use rauthy_common::constants::{PWD_CSRF_HEADER, PWD_RESET_COOKIE};
 use rauthy_common::utils::{get_rand, real_ip_from_req};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
@@ -32,7 +32,7 @@ pub async fn handle_get_pwd_reset<'a>(
     let user = User::find(ml.user_id.clone()).await?;
 
     // get the html and insert values
-    let rules = PasswordPolicy::find().await?;
+    let rules = PasswordPolicy::find_for_user(&user).await?;
     let colors = ColorEntity::find_rauthy().await?;
     let lang = Language::try_from(&req).unwrap_or_default();
 
@@ -53,7 +53,7 @@ pub async fn handle_get_pwd_reset<'a>(
     ml.cookie = Some(cookie_val);
     ml.save().await?;
 
-    let age_secs = ml.exp - Utc::now().timestamp();
+    let age_secs = ml.exp - clock::now().timestamp();
     let cookie = ApiCookie::build(PWD_RESET_COOKIE, ml.cookie.unwrap(), age_secs);
 
     Ok((content, cookie))
@@ -87,7 +87,7 @@ pub async fn handle_put_user_passkey_start<'a>(
         }
     }
 
-    webauthn::reg_start(data, user.id, req_data)
+    webauthn::reg_start(data.webauthn.for_req(&req), user.id, req_data)
         .await
         .map(|ccr| HttpResponse::Ok().json(ccr))
 }
@@ -106,7 +106,7 @@ pub async fn handle_put_user_passkey_finish<'a>(
 
     // finish webauthn request -> always force UV for passkey only accounts
     debug!("ml is valid - finishing webauthn request");
-    webauthn::reg_finish(data, user_id.clone(), req_data).await?;
+    webauthn::reg_finish(data.webauthn.for_req(&req), user_id.clone(), req_data).await?;
 
     // validate csrf token
     match req.headers().get(PWD_CSRF_HEADER) {
@@ -117,7 +117,7 @@ pub async fn handle_put_user_passkey_finish<'a>(
             ));
         }
         Some(token) => {
-            if ml.csrf_token != token.to_str().unwrap_or("") {
+            if !ml.is_valid_csrf_token(token.to_str().unwrap_or("")) {
                 return Err(ErrorResponse::new(
                     ErrorResponseType::Unauthorized,
                     String::from("Invalid CSRF Token"),
@@ -129,7 +129,14 @@ pub async fn handle_put_user_passkey_finish<'a>(
     debug!("invalidating magic link pwd");
     // all good
     ml.invalidate().await?;
-    User::set_email_verified(user_id, true).await?;
+    User::set_email_verified(user_id.clone(), true).await?;
+
+    if let Ok(user) = User::find(user_id).await {
+        Event::mfa_enrolled(user.email, real_ip_from_req(&req)?.to_string())
+            .with_user_id(user.id)
+            .send(&data.tx_events)
+            .await?;
+    }
 
     // delete the cookie
     let cookie = ApiCookie::build(PWD_RESET_COOKIE, "", 0);
@@ -159,13 +166,8 @@ pub async fn handle_put_user_password_reset<'a>(
             }
             Some(code) => {
                 let svc_req = WebauthnServiceReq::find(code).await?;
-                if svc_req.user_id != user.id {
-                    // TODO delete the whole ML too?
-                    return Err(ErrorResponse::new(
-                        ErrorResponseType::Forbidden,
-                        "User ID does not match".to_string(),
-                    ));
-                }
+                // TODO delete the whole ML too?
+                svc_req.validate(&user.id, MfaPurpose::PasswordReset)?;
 
                 svc_req.delete().await?;
             }
@@ -180,7 +182,7 @@ pub async fn handle_put_user_password_reset<'a>(
 
     // all good
     ml.invalidate().await?;
-    user.email_verified = true;
+    user.set_email_verified_now(true);
     user.save(None).await?;
 
     let ip = match real_ip_from_req(&req).ok() {
@@ -191,10 +193,13 @@ pub async fn handle_put_user_password_reset<'a>(
         Some(ip) => ip.to_string(),
     };
     data.tx_events
-        .send_async(Event::user_password_reset(
-            format!("Reset via Password Reset Form: {}", user.email),
-            Some(ip),
-        ))
+        .send_async(
+            Event::user_password_reset(
+                format!("Reset via Password Reset Form: {}", user.email),
+                Some(ip),
+            )
+            .with_user_id(user.id.clone()),
+        )
         .await
         .unwrap();
 
@@ -0,0 +1,629 @@
+use actix_web::web;
+use chrono::DateTime;
+use rauthy_api_types::generic::Language;
+use rauthy_api_types::groups::NewGroupRequest;
+use rauthy_api_types::scim::{
+    ScimEmail, ScimGroup, ScimGroupListResponse, ScimListParams, ScimMeta, ScimName,
+    ScimPatchOperation, ScimPatchRequest, ScimRef, ScimUser, ScimUserListResponse,
+    SCIM_SCHEMA_GROUP, SCIM_SCHEMA_LIST, SCIM_SCHEMA_USER,
+};
+use rauthy_api_types::users::{NewUserRequest, UpdateUserRequest};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::groups::Group;
+use rauthy_models::entity::users::User;
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::debug;
+use validator::Validate;
+
+const SCIM_DEFAULT_COUNT: i64 = 100;
+
+/// The SCIM filters Rauthy can handle. Only simple `eq` comparisons are supported, which is all
+/// that Okta and Azure AD use for provisioning.
+#[derive(Debug, PartialEq)]
+pub enum ScimFilter {
+    Id(String),
+    ExternalId(String),
+    UserName(String),
+    DisplayName(String),
+}
+
+impl ScimFilter {
+    pub fn parse(filter: &str) -> Result<Self, ErrorResponse> {
+        let err = || {
+            ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                format!("invalidFilter: unsupported filter '{}'", filter),
+            )
+        };
+
+        let (attr, value) = parse_eq(filter).ok_or_else(err)?;
+        match attr.to_lowercase().as_str() {
+            "id" => Ok(Self::Id(value)),
+            "externalid" => Ok(Self::ExternalId(value)),
+            "username" | "emails" | "emails.value" => Ok(Self::UserName(value)),
+            "displayname" => Ok(Self::DisplayName(value)),
+            _ => Err(err()),
+        }
+    }
+}
+
+/// Splits an expression like `userName eq "admin"` into the attribute and the unquoted value
+fn parse_eq(expr: &str) -> Option<(&str, String)> {
+    let (attr, rest) = expr.trim().split_once(' ')?;
+    let (op, value) = rest.trim_start().split_once(' ')?;
+    if !op.eq_ignore_ascii_case("eq") {
+        return None;
+    }
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((attr, value.to_string()))
+}
+
+/// Maps a `NotFound` to an empty result, which is what a SCIM filter expects
+fn found_or_empty<T>(res: Result<T, ErrorResponse>) -> Result<Vec<T>, ErrorResponse> {
+    match res {
+        Ok(v) => Ok(vec![v]),
+        Err(err) if err.error == ErrorResponseType::NotFound => Ok(Vec::default()),
+        Err(err) => Err(err),
+    }
+}
+
+fn scim_meta(resource_type: &str, location: String, created: Option<i64>) -> Option<ScimMeta> {
+    Some(ScimMeta {
+        resource_type: resource_type.to_string(),
+        location,
+        created: created
+            .and_then(|ts| DateTime::from_timestamp(ts, 0))
+            .map(|dt| dt.to_rfc3339()),
+    })
+}
+
+/// Returns the 0-based offset and the page size for the given params
+fn scim_page(params: &ScimListParams) -> (usize, usize) {
+    let start = params.start_index.unwrap_or(1).max(1) - 1;
+    let count = params.count.unwrap_or(SCIM_DEFAULT_COUNT).max(0);
+    (start as usize, count as usize)
+}
+
+fn scim_bool(value: &Value) -> Result<bool, ErrorResponse> {
+    match value {
+        Value::Bool(b) => Ok(*b),
+        // Azure AD sends booleans as strings like "False"
+        Value::String(s) if s.eq_ignore_ascii_case("true") => Ok(true),
+        Value::String(s) if s.eq_ignore_ascii_case("false") => Ok(false),
+        _ => Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "invalidValue: expected a boolean",
+        )),
+    }
+}
+
+fn scim_string(value: &Value) -> Result<String, ErrorResponse> {
+    value.as_str().map(String::from).ok_or_else(|| {
+        ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "invalidValue: expected a string",
+        )
+    })
+}
+
+/// Extracts the E-Mail from either a plain string or a list of SCIM E-Mail objects, where the
+/// `primary` one wins.
+fn scim_email(value: &Value) -> Result<String, ErrorResponse> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Array(emails) => emails
+            .iter()
+            .find(|e| e.get("primary").map(|p| scim_bool(p).unwrap_or(false)) == Some(true))
+            .or_else(|| emails.first())
+            .and_then(|e| e.get("value"))
+            .and_then(|v| v.as_str())
+            .map(String::from)
+            .ok_or_else(|| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    "invalidValue: no E-Mail address given",
+                )
+            }),
+        _ => Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            "invalidValue: expected an E-Mail address",
+        )),
+    }
+}
+
+/// Extracts the member IDs from a list of SCIM member objects
+fn scim_member_ids(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(members) => members
+            .iter()
+            .filter_map(|m| m.get("value").and_then(|v| v.as_str()))
+            .map(String::from)
+            .collect(),
+        _ => Vec::default(),
+    }
+}
+
+/// Extracts the member ID from a path like `members[value eq "<id>"]`
+fn scim_member_id_from_path(path: &str) -> Option<String> {
+    let inner = path.strip_prefix("members[")?.strip_suffix(']')?;
+    let (attr, id) = parse_eq(inner)?;
+    if attr.eq_ignore_ascii_case("value") {
+        Some(id)
+    } else {
+        None
+    }
+}
+
+// Users
+
+pub fn user_to_scim(user: User, groups: &[Group], issuer: &str) -> ScimUser {
+    let user_groups = user.get_groups();
+    let groups = groups
+        .iter()
+        .filter(|g| user_groups.contains(&g.name))
+        .map(|g| ScimRef {
+            value: g.id.clone(),
+            display: Some(g.name.clone()),
+        })
+        .collect();
+
+    ScimUser {
+        schemas: vec![SCIM_SCHEMA_USER.to_string()],
+        meta: scim_meta(
+            "User",
+            format!("{}/scim/v2/Users/{}", issuer, user.id),
+            Some(user.created_at),
+        ),
+        id: Some(user.id),
+        external_id: user.external_id,
+        user_name: user.email.clone(),
+        name: Some(ScimName {
+            given_name: Some(user.given_name),
+            family_name: user.family_name,
+        }),
+        emails: vec![ScimEmail {
+            value: user.email,
+            primary: true,
+        }],
+        active: Some(user.enabled),
+        groups,
+    }
+}
+
+pub async fn list_users(
+    params: &ScimListParams,
+    issuer: &str,
+) -> Result<ScimUserListResponse, ErrorResponse> {
+    let users = match params
+        .filter
+        .as_deref()
+        .map(ScimFilter::parse)
+        .transpose()?
+    {
+        None => {
+            let mut users = User::find_all().await?;
+            users.sort_by_key(|u| u.created_at);
+            users
+        }
+        Some(ScimFilter::Id(id)) => found_or_empty(User::find(id).await)?,
+        Some(ScimFilter::ExternalId(id)) => found_or_empty(User::find_by_external_id(&id).await)?,
+        Some(ScimFilter::UserName(email)) => found_or_empty(User::find_by_email(email).await)?,
+        Some(ScimFilter::DisplayName(_)) => {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "invalidFilter: 'displayName' is not supported for Users",
+            ));
+        }
+    };
+
+    let groups = Group::find_all().await?;
+    let total_results = users.len();
+    let (start, count) = scim_page(params);
+    let resources = users
+        .into_iter()
+        .skip(start)
+        .take(count)
+        .map(|u| user_to_scim(u, &groups, issuer))
+        .collect::<Vec<_>>();
+
+    Ok(ScimUserListResponse {
+        schemas: vec![SCIM_SCHEMA_LIST.to_string()],
+        total_results,
+        start_index: start as i64 + 1,
+        items_per_page: resources.len(),
+        resources,
+    })
+}
+
+pub async fn create_user(
+    data: &web::Data<AppState>,
+    scim_user: ScimUser,
+) -> Result<User, ErrorResponse> {
+    let email = scim_user
+        .emails
+        .iter()
+        .find(|e| e.primary)
+        .or_else(|| scim_user.emails.first())
+        .map(|e| e.value.clone())
+        .unwrap_or(scim_user.user_name);
+    let name = scim_user.name.unwrap_or_default();
+
+    let new_user = NewUserRequest {
+        email,
+        family_name: name.family_name,
+        given_name: name.given_name.unwrap_or_default(),
+        language: Language::En,
+        groups: None,
+        roles: Vec::default(),
+        user_expires: None,
+        external_id: scim_user.external_id,
+    };
+    new_user.validate()?;
+
+    let mut user = User::from_new_user_req(new_user).await?;
+    user.enabled = scim_user.active.unwrap_or(true);
+    User::create(data, user, None).await
+}
+
+/// Applies a single attribute from a `PATCH` operation to the update request
+fn patch_user_attr(
+    upd: &mut UpdateUserRequest,
+    op: &str,
+    path: &str,
+    value: Option<&Value>,
+) -> Result<(), ErrorResponse> {
+    let path = path.to_lowercase();
+    let value = match (op, value) {
+        ("remove", _) => {
+            if path == "name.familyname" {
+                upd.family_name = None;
+            } else {
+                debug!("Ignoring unsupported SCIM remove for '{}'", path);
+            }
+            return Ok(());
+        }
+        (_, Some(value)) => value,
+        (_, None) => {
+            return Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "invalidValue: missing value",
+            ));
+        }
+    };
+
+    match path.as_str() {
+        "active" => upd.enabled = scim_bool(value)?,
+        "username" => upd.email = scim_string(value)?,
+        p if p.starts_with("emails") => upd.email = scim_email(value)?,
+        "name.givenname" => upd.given_name = scim_string(value)?,
+        "name.familyname" => upd.family_name = Some(scim_string(value)?),
+        "name" => {
+            if let Some(given_name) = value.get("givenName") {
+                upd.given_name = scim_string(given_name)?;
+            }
+            if let Some(family_name) = value.get("familyName") {
+                upd.family_name = Some(scim_string(family_name)?);
+            }
+        }
+        _ => debug!("Ignoring unsupported SCIM attribute '{}'", path),
+    }
+
+    Ok(())
+}
+
+fn patch_user_op(upd: &mut UpdateUserRequest, op: ScimPatchOperation) -> Result<(), ErrorResponse> {
+    let op_name = op.op.to_lowercase();
+    if !matches!(op_name.as_str(), "add" | "replace" | "remove") {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!("invalidSyntax: unsupported op '{}'", op.op),
+        ));
+    }
+
+    match op.path {
+        Some(path) => patch_user_attr(upd, &op_name, &path, op.value.as_ref()),
+        // without a path, the value contains all attributes to modify
+        None => match op.value {
+            Some(Value::Object(attrs)) => {
+                for (path, value) in attrs.iter() {
+                    patch_user_attr(upd, &op_name, path, Some(value))?;
+                }
+                Ok(())
+            }
+            _ => Err(ErrorResponse::new(
+                ErrorResponseType::BadRequest,
+                "invalidValue: expected an object without a path",
+            )),
+        },
+    }
+}
+
+pub async fn patch_user(
+    data: &web::Data<AppState>,
+    id: String,
+    patch: ScimPatchRequest,
+) -> Result<User, ErrorResponse> {
+    let user = User::find(id).await?;
+    let groups = user.get_groups();
+
+    let mut upd = UpdateUserRequest {
+        email: user.email.clone(),
+        given_name: user.given_name.clone(),
+        family_name: user.family_name.clone(),
+        language: Some(user.language.into()),
+        password: None,
+        roles: user.get_roles(),
+        groups: if groups.is_empty() {
+            None
+        } else {
+            Some(groups)
+        },
+        enabled: user.enabled,
+        email_verified: user.email_verified,
+        user_expires: user.user_expires,
+        user_values: None,
+        must_change_password: None,
+    };
+    for op in patch.operations {
+        patch_user_op(&mut upd, op)?;
+    }
+    upd.validate()?;
+
+    let (user, _, _) = User::update(data, user.id.clone(), upd, Some(user)).await?;
+    Ok(user)
+}
+
+// Groups
+
+async fn group_to_scim(group: Group, issuer: &str) -> Result<ScimGroup, ErrorResponse> {
+    let members = User::find_with_group(&group.name)
+        .await?
+        .into_iter()
+        .map(|u| ScimRef {
+            value: u.id,
+            display: Some(u.email),
+        })
+        .collect();
+
+    Ok(ScimGroup {
+        schemas: vec![SCIM_SCHEMA_GROUP.to_string()],
+        meta: scim_meta(
+            "Group",
+            format!("{}/scim/v2/Groups/{}", issuer, group.id),
+            None,
+        ),
+        id: Some(group.id),
+        display_name: group.name,
+        members,
+    })
+}
+
+pub async fn find_group(id: String, issuer: &str) -> Result<ScimGroup, ErrorResponse> {
+    group_to_scim(Group::find(id).await?, issuer).await
+}
+
+pub async fn list_groups(
+    params: &ScimListParams,
+    issuer: &str,
+) -> Result<ScimGroupListResponse, ErrorResponse> {
+    let filter = params
+        .filter
+        .as_deref()
+        .map(ScimFilter::parse)
+        .transpose()?;
+    let groups = Group::find_all()
+        .await?
+        .into_iter()
+        .filter(|g| match &filter {
+            None => true,
+            Some(ScimFilter::Id(id)) => &g.id == id,
+            Some(ScimFilter::DisplayName(name)) => &g.name == name,
+            Some(ScimFilter::ExternalId(_)) | Some(ScimFilter::UserName(_)) => false,
+        })
+        .collect::<Vec<_>>();
+
+    let total_results = groups.len();
+    let (start, count) = scim_page(params);
+    let mut resources = Vec::with_capacity(count.min(total_results));
+    for group in groups.into_iter().skip(start).take(count) {
+        resources.push(group_to_scim(group, issuer).await?);
+    }
+
+    Ok(ScimGroupListResponse {
+        schemas: vec![SCIM_SCHEMA_LIST.to_string()],
+        total_results,
+        start_index: start as i64 + 1,
+        items_per_page: resources.len(),
+        resources,
+    })
+}
+
+async fn add_members(group_name: &str, user_ids: Vec<String>) -> Result<(), ErrorResponse> {
+    for id in user_ids {
+        let mut user = User::find(id).await?;
+        if !user.get_groups().iter().any(|g| g == group_name) {
+            user.push_group(group_name);
+            user.save(None).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn remove_members(group_name: &str, user_ids: Vec<String>) -> Result<(), ErrorResponse> {
+    for id in user_ids {
+        let mut user = User::find(id).await?;
+        if user.get_groups().iter().any(|g| g == group_name) {
+            user.delete_group(group_name);
+            user.save(None).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn create_group(scim_group: ScimGroup) -> Result<Group, ErrorResponse> {
+    let group_req = NewGroupRequest {
+        group: scim_group.display_name,
+        force_mfa: None,
+    };
+    group_req.validate()?;
+
+    let group = Group::create(group_req).await?;
+    add_members(
+        &group.name,
+        scim_group.members.into_iter().map(|m| m.value).collect(),
+    )
+    .await?;
+
+    Ok(group)
+}
+
+pub async fn patch_group(id: String, patch: ScimPatchRequest) -> Result<Group, ErrorResponse> {
+    let mut group = Group::find(id).await?;
+
+    for op in patch.operations {
+        let op_name = op.op.to_lowercase();
+        let path = op.path.as_deref().unwrap_or_default();
+
+        // without a path, the value contains all attributes to modify
+        let mut attrs = HashMap::new();
+        if path.is_empty() {
+            if let Some(Value::Object(values)) = &op.value {
+                for (k, v) in values {
+                    attrs.insert(k.to_lowercase(), v.clone());
+                }
+            }
+        } else if let Some(value) = &op.value {
+            attrs.insert(path.to_lowercase(), value.clone());
+        }
+
+        match op_name.as_str() {
+            "add" | "replace" => {
+                if let Some(name) = attrs.get("displayname") {
+                    let group_req = NewGroupRequest {
+                        group: scim_string(name)?,
+                        force_mfa: None,
+                    };
+                    group_req.validate()?;
+                    group = Group::update(group.id, group_req.group, None).await?;
+                }
+
+                if let Some(members) = attrs.get("members") {
+                    let ids = scim_member_ids(members);
+                    if op_name == "replace" {
+                        let remove = User::find_with_group(&group.name)
+                            .await?
+                            .into_iter()
+                            .map(|u| u.id)
+                            .filter(|id| !ids.contains(id))
+                            .collect();
+                        remove_members(&group.name, remove).await?;
+                    }
+                    add_members(&group.name, ids).await?;
+                }
+            }
+
+            "remove" => {
+                let ids = if let Some(id) = scim_member_id_from_path(path) {
+                    vec![id]
+                } else if path.eq_ignore_ascii_case("members") {
+                    match &op.value {
+                        Some(value) => scim_member_ids(value),
+                        // removing the attribute itself means removing all members
+                        None => User::find_with_group(&group.name)
+                            .await?
+                            .into_iter()
+                            .map(|u| u.id)
+                            .collect(),
+                    }
+                } else {
+                    debug!("Ignoring unsupported SCIM remove for '{}'", path);
+                    continue;
+                };
+                remove_members(&group.name, ids).await?;
+            }
+
+            _ => {
+                return Err(ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("invalidSyntax: unsupported op '{}'", op.op),
+                ));
+            }
+        }
+    }
+
+    Ok(group)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_scim_filter() {
+        assert_eq!(
+            ScimFilter::parse(r#"userName eq "admin@localhost.de""#).unwrap(),
+            ScimFilter::UserName("admin@localhost.de".to_string())
+        );
+        assert_eq!(
+            ScimFilter::parse(r#"displayName EQ "admin""#).unwrap(),
+            ScimFilter::DisplayName("admin".to_string())
+        );
+        assert_eq!(
+            ScimFilter::parse(r#"externalId eq "123""#).unwrap(),
+            ScimFilter::ExternalId("123".to_string())
+        );
+        assert!(ScimFilter::parse(r#"nickName eq "123""#).is_err());
+        assert!(ScimFilter::parse(r#"userName sw "admin""#).is_err());
+        assert!(ScimFilter::parse("userName eq admin").is_err());
+
+        assert_eq!(
+            scim_member_id_from_path(r#"members[value eq "abc123"]"#),
+            Some("abc123".to_string())
+        );
+    }
+
+    #[test]
+    fn test_scim_patch_user() {
+        let mut upd = UpdateUserRequest {
+            email: "old@localhost.de".to_string(),
+            given_name: "Old".to_string(),
+            family_name: Some("Name".to_string()),
+            language: None,
+            password: None,
+            roles: Vec::default(),
+            groups: None,
+            enabled: true,
+            email_verified: true,
+            user_expires: None,
+            user_values: None,
+            must_change_password: None,
+        };
+
+        let op: ScimPatchOperation = serde_json::from_str(
+            r#"{"op":"Replace","value":{"active":"False","name.givenName":"New"}}"#,
+        )
+        .unwrap();
+        patch_user_op(&mut upd, op).unwrap();
+        assert!(!upd.enabled);
+        assert_eq!(upd.given_name, "New");
+
+        let op: ScimPatchOperation = serde_json::from_str(
+            r#"{"op":"replace","path":"emails[type eq \"work\"].value","value":"new@localhost.de"}"#,
+        )
+        .unwrap();
+        patch_user_op(&mut upd, op).unwrap();
+        assert_eq!(upd.email, "new@localhost.de");
+
+        let op: ScimPatchOperation =
+            serde_json::from_str(r#"{"op":"remove","path":"name.familyName"}"#).unwrap();
+        patch_user_op(&mut upd, op).unwrap();
+        assert_eq!(upd.family_name, None);
+
+        let op: ScimPatchOperation =
+            serde_json::from_str(r#"{"op":"move","path":"active","value":true}"#).unwrap();
+        assert!(patch_user_op(&mut upd, op).is_err());
+    }
+}
@@ -1,28 +1,36 @@
 use actix_web::web;
-use chrono::Utc;
-use jwt_simple::algorithms::{EdDSAKeyPairLike, RSAKeyPairLike};
-use jwt_simple::claims::Claims;
+use jwt_simple::algorithms::{ECDSAP384KeyPairLike, EdDSAKeyPairLike, RSAKeyPairLike};
 use jwt_simple::prelude::{coarsetime, UnixTimeStamp};
-use rauthy_api_types::oidc::JktClaim;
+use rauthy_api_types::oidc::CnfClaim;
+use rauthy_common::clock;
 use rauthy_common::constants::{
     DEVICE_GRANT_REFRESH_TOKEN_LIFETIME, DISABLE_REFRESH_TOKEN_NBF, ENABLE_SOLID_AUD,
     ENABLE_WEB_ID, REFRESH_TOKEN_LIFETIME,
 };
-use rauthy_common::utils::base64_url_no_pad_encode;
+use rauthy_common::utils::{base64_url_no_pad_encode, get_rand};
 use rauthy_error::{ErrorResponse, ErrorResponseType};
 use rauthy_models::app_state::AppState;
 use rauthy_models::entity::clients::Client;
+use rauthy_models::entity::clients_user_metadata::ClientUserMetadata;
+use rauthy_models::entity::jti_denylist::IssuedAccessToken;
+use rauthy_models::entity::jwe::JweEncrypter;
 use rauthy_models::entity::jwk::{JwkKeyPair, JwkKeyPairAlg};
+use rauthy_models::entity::opaque_tokens::OpaqueToken;
 use rauthy_models::entity::refresh_tokens::RefreshToken;
 use rauthy_models::entity::refresh_tokens_devices::RefreshTokenDevice;
-use rauthy_models::entity::scopes::Scope;
+use rauthy_models::entity::scopes::{Scope, ScopeClaimTarget};
+use rauthy_models::entity::sms_otp::UserSmsOtp;
+use rauthy_models::entity::totp::UserTotp;
 use rauthy_models::entity::user_attr::UserAttrValueEntity;
+use rauthy_models::entity::user_pictures::UserPicture;
 use rauthy_models::entity::users::User;
 use rauthy_models::entity::users_values::UserValues;
 use rauthy_models::entity::webids::WebId;
+use rauthy_models::entity::yubico_otp::UserYubicoOtp;
+use rauthy_models::jwt_claims;
 use rauthy_models::{
-    sign_jwt, AddressClaim, JwtAccessClaims, JwtAmrValue, JwtIdClaims, JwtRefreshClaims,
-    JwtTokenType,
+    sign_jwt, ActClaim, AddressClaim, JwtAccessClaims, JwtAcrValue, JwtAmrValue, JwtIdClaims,
+    JwtRefreshClaims, JwtTokenType,
 };
 use ring::digest;
 use serde::{Deserialize, Serialize};
@@ -45,6 +53,7 @@ impl TryFrom<&str> for AtHashAlg {
             "RS256" => Self::Sha256,
             "RS384" => Self::Sha384,
             "RS512" => Self::Sha512,
+            "ES384" => Self::Sha384,
             "EdDSA" => Self::Sha512,
             _ => {
                 return Err(ErrorResponse::new(
@@ -83,7 +92,7 @@ pub struct AuthTime(i64);
 
 impl AuthTime {
     pub fn now() -> Self {
-        Self(Utc::now().timestamp())
+        Self(clock::now().timestamp())
     }
 
     pub fn given(ts: i64) -> Self {
@@ -104,11 +113,29 @@ pub enum DeviceCodeFlow {
 #[derive(Clone)]
 pub struct DpopFingerprint(pub String);
 
+/// The `x5t#S256` thumbprint of the client certificate for mTLS bound access tokens
+pub struct CertFingerprint(pub String);
+
 pub struct TokenNonce(pub String);
 
+/// The ID of the admin behind an impersonated session
+#[derive(Clone)]
+pub struct ImpersonatorId(pub String);
+
+/// The ID of the session the tokens have been issued for, which will be the `sid` claim
+#[derive(Clone)]
+pub struct SessionId(pub String);
+
 /// Contains the scopes as a single String separated by `\s`
 pub struct TokenScopes(pub String);
 
+/// The requested target resource (RFC 8707), which will be the `aud` of the access token
+pub struct TokenResource(pub String);
+
+/// A refresh token, which will be returned as it is instead of issuing a new one, if the client
+/// has disabled refresh token rotation
+pub struct RetainedRefreshToken(pub String);
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TokenSet {
     pub access_token: String,
@@ -129,10 +156,14 @@ impl TokenSet {
         data: &web::Data<AppState>,
         client: &Client,
         dpop_fingerprint: Option<DpopFingerprint>,
+        cert_fingerprint: Option<CertFingerprint>,
         lifetime: i64,
         scope: Option<TokenScopes>,
         scope_customs: Option<(Vec<&Scope>, &Option<HashMap<String, Vec<u8>>>)>,
         device_code_flow: DeviceCodeFlow,
+        impersonator: Option<ImpersonatorId>,
+        resource: Option<TokenResource>,
+        session_id: Option<SessionId>,
     ) -> Result<String, ErrorResponse> {
         let did = match device_code_flow {
             DeviceCodeFlow::Yes(did) => Some(did),
@@ -146,27 +177,39 @@ impl TokenSet {
                 .unwrap_or_else(|| client.default_scopes.clone().replace(',', " ")),
             allowed_origins: None,
             did,
+            sid: session_id.map(|sid| sid.0),
             email: None,
             preferred_username: None,
             roles: None,
             groups: None,
-            cnf: dpop_fingerprint.map(|jkt| JktClaim { jkt: jkt.0 }),
+            cnf: match (dpop_fingerprint, cert_fingerprint) {
+                (None, None) => None,
+                (jkt, x5t) => Some(CnfClaim {
+                    jkt: jkt.map(|jkt| jkt.0),
+                    x5t_s256: x5t.map(|x5t| x5t.0),
+                }),
+            },
+            act: impersonator.map(|id| ActClaim { sub: id.0 }),
             custom: None,
+            client_metadata: None,
         };
 
         // add user specific claims if available
         let sub = if let Some(user) = user {
+            let (groups, roles) = user.get_groups_roles_effective().await?;
             custom_claims.preferred_username = Some(user.email.clone());
-            custom_claims.roles = Some(user.get_roles());
+            custom_claims.roles = Some(roles);
 
             if custom_claims.scope.contains("email") {
                 custom_claims.email = Some(user.email.clone());
             }
 
             if custom_claims.scope.contains("groups") {
-                custom_claims.groups = Some(user.get_groups());
+                custom_claims.groups = Some(groups);
             }
 
+            custom_claims.client_metadata = ClientUserMetadata::claim(&client.id, &user.id).await?;
+
             Some(&user.id)
         } else {
             None
@@ -175,7 +218,7 @@ impl TokenSet {
         if let Some((cust, user_attrs)) = scope_customs {
             let user_attrs = user_attrs.as_ref().unwrap();
             let mut attr = HashMap::with_capacity(cust.len());
-            for c in cust {
+            for c in &cust {
                 if let Some(csv) = &c.attr_include_access {
                     let scopes = csv.split(',');
                     for cust_name in scopes {
@@ -187,26 +230,65 @@ impl TokenSet {
                     }
                 }
             }
+            if let Some(user) = user {
+                attr.extend(
+                    Scope::resolve_claim_mappings(
+                        &cust,
+                        ScopeClaimTarget::Access,
+                        user,
+                        Some(user_attrs),
+                    )
+                    .await?,
+                );
+            }
             if !attr.is_empty() {
                 custom_claims.custom = Some(attr);
             }
         }
 
-        let mut claims = Claims::with_custom_claims(
-            custom_claims,
+        let sid = custom_claims.sid.clone();
+        let jti = get_rand(24);
+        let mut claims = jwt_claims(
+            client.claims_with_template(custom_claims)?,
             coarsetime::Duration::from_secs(lifetime as u64),
         )
         .with_issuer(data.issuer.clone())
-        .with_audience(client.id.to_string());
+        .with_jwt_id(jti.clone())
+        // with a requested resource, the token must only be accepted by this resource
+        .with_audience(
+            resource
+                .map(|res| res.0)
+                .unwrap_or_else(|| client.id.to_string()),
+        );
 
         if let Some(sub) = sub {
             claims = claims.with_subject(sub);
         }
 
+        // tokens for a user or session must be found by their `jti` if those are revoked
+        let exp = clock::now().timestamp() + lifetime;
+        if sub.is_some() || sid.is_some() {
+            IssuedAccessToken::insert(jti, sub.cloned(), sid.clone(), exp).await?;
+        }
+
+        // the claims stay on the server and the client only receives a reference to them
+        if client.opaque_access_tokens {
+            return OpaqueToken::create(
+                client.id.clone(),
+                sub.cloned(),
+                sid,
+                exp,
+                serde_json::to_string(&claims)?,
+            )
+            .await;
+        }
+
         // sign the token
         let key_pair_alg = JwkKeyPairAlg::from_str(&client.access_token_alg)?;
         let kp = JwkKeyPair::find_latest(key_pair_alg).await?;
-        sign_jwt!(kp, claims)
+        let token = sign_jwt!(kp, claims)?;
+
+        Ok(token)
     }
 
     /// Builds the id token for a user after all validation has been successful
@@ -223,20 +305,29 @@ impl TokenSet {
         scope: &str,
         scope_customs: Option<(Vec<&Scope>, &Option<HashMap<String, Vec<u8>>>)>,
         auth_code_flow: AuthCodeFlow,
+        impersonator: Option<ImpersonatorId>,
+        session_id: Option<SessionId>,
     ) -> Result<String, ErrorResponse> {
-        let amr = if user.has_webauthn_enabled() && auth_code_flow == AuthCodeFlow::Yes {
-            JwtAmrValue::Mfa.to_string()
+        let has_mfa = user.has_webauthn_enabled()
+            || UserTotp::is_enabled(&user.id).await?
+            || UserYubicoOtp::is_enabled(&user.id).await?
+            || UserSmsOtp::is_enabled(&user.id).await?;
+        let amr = if has_mfa && auth_code_flow == AuthCodeFlow::Yes {
+            JwtAmrValue::Mfa
         } else {
-            JwtAmrValue::Pwd.to_string()
+            JwtAmrValue::Pwd
         };
+        let acr = JwtAcrValue::from(&amr).as_str().to_string();
 
         let webid =
             (*ENABLE_WEB_ID && scope.contains("webid")).then(|| WebId::resolve_webid_uri(&user.id));
+        let (groups, roles) = user.get_groups_roles_effective().await?;
 
         let mut custom_claims = JwtIdClaims {
             azp: client.id.clone(),
             typ: JwtTokenType::Id,
-            amr: vec![amr],
+            amr: vec![amr.to_string()],
+            acr,
             auth_time: auth_time.get(),
             at_hash: at_hash.0,
             preferred_username: user.email.clone(),
@@ -248,11 +339,18 @@ impl TokenSet {
             birthdate: None,
             locale: None,
             phone: None,
-            roles: user.get_roles(),
+            picture: None,
+            roles,
             groups: None,
-            cnf: dpop_fingerprint.map(|jkt| JktClaim { jkt: jkt.0 }),
+            cnf: dpop_fingerprint.map(|jkt| CnfClaim {
+                jkt: Some(jkt.0),
+                x5t_s256: None,
+            }),
+            act: impersonator.map(|id| ActClaim { sub: id.0 }),
             custom: None,
             webid,
+            client_metadata: ClientUserMetadata::claim(&client.id, &user.id).await?,
+            sid: session_id.map(|sid| sid.0),
         };
 
         let mut user_values = None;
@@ -267,6 +365,9 @@ impl TokenSet {
             custom_claims.given_name = Some(user.given_name.clone());
             custom_claims.family_name = user.family_name.clone();
             custom_claims.locale = Some(user.language.to_string());
+            custom_claims.picture = UserPicture::find_updated(&user.id)
+                .await?
+                .map(|updated| UserPicture::claim_url(&data.issuer, &user.id, updated));
 
             user_values = UserValues::find(&user.id).await?;
             user_values_fetched = true;
@@ -303,13 +404,13 @@ impl TokenSet {
         }
 
         if scope.contains("groups") {
-            custom_claims.groups = Some(user.get_groups());
+            custom_claims.groups = Some(groups);
         }
 
         if let Some((cust, user_attrs)) = scope_customs {
             let user_attrs = user_attrs.as_ref().unwrap();
             let mut attr = HashMap::with_capacity(cust.len());
-            for c in cust {
+            for c in &cust {
                 if let Some(csv) = &c.attr_include_id {
                     let scopes = csv.split(',');
                     for cust_name in scopes {
@@ -321,16 +422,21 @@ impl TokenSet {
                     }
                 }
             }
+            attr.extend(
+                Scope::resolve_claim_mappings(&cust, ScopeClaimTarget::Id, user, Some(user_attrs))
+                    .await?,
+            );
             if !attr.is_empty() {
                 custom_claims.custom = Some(attr);
             }
         }
 
-        let mut claims = Claims::with_custom_claims(
-            custom_claims,
+        let mut claims = jwt_claims(
+            client.claims_with_template(custom_claims)?,
             coarsetime::Duration::from_secs(lifetime as u64),
         )
-        .with_subject(user.id.clone())
+        // clients with pairwise subjects receive a `sub` unique to their sector
+        .with_subject(client.subject_for(&user.id)?)
         .with_issuer(data.issuer.clone());
 
         // TODO should we maybe always include the "solid" claim here depending on if a webid exists?
@@ -351,8 +457,13 @@ impl TokenSet {
         // sign the token
         let key_pair_alg = JwkKeyPairAlg::from_str(&client.id_token_alg)?;
         let kp = JwkKeyPair::find_latest(key_pair_alg).await?;
+        let token = sign_jwt!(kp, claims)?;
 
-        sign_jwt!(kp, claims)
+        // the signed token will be nested inside the JWE
+        match JweEncrypter::for_id_token(client).await? {
+            Some(jwe) => jwe.encrypt(token.as_bytes(), Some("JWT")),
+            None => Ok(token),
+        }
     }
 
     /// Builds the refresh token for a user after all validation has been successful
@@ -367,6 +478,7 @@ impl TokenSet {
         scope: Option<TokenScopes>,
         is_mfa: bool,
         device_code_flow: DeviceCodeFlow,
+        session_id: Option<SessionId>,
     ) -> Result<String, ErrorResponse> {
         let did = if let DeviceCodeFlow::Yes(device_id) = device_code_flow {
             Some(device_id)
@@ -379,22 +491,34 @@ impl TokenSet {
             typ: JwtTokenType::Refresh,
             uid: user.id.clone(),
             auth_time: Some(auth_time.get()),
-            cnf: dpop_fingerprint.map(|jkt| JktClaim { jkt: jkt.0 }),
+            cnf: dpop_fingerprint.map(|jkt| CnfClaim {
+                jkt: Some(jkt.0),
+                x5t_s256: None,
+            }),
             did: did.clone(),
         };
 
         let nbf = if *DISABLE_REFRESH_TOKEN_NBF {
-            Utc::now()
+            clock::now()
         } else {
-            Utc::now().add(chrono::Duration::seconds(access_token_lifetime - 60))
+            clock::now().add(chrono::Duration::seconds(access_token_lifetime - 60))
         };
         let nbf_unix = UnixTimeStamp::from_secs(nbf.timestamp() as u64);
 
-        let claims =
-            Claims::with_custom_claims(custom_claims, coarsetime::Duration::from_hours(48))
-                .with_issuer(data.issuer.clone())
-                .invalid_before(nbf_unix)
-                .with_audience(client.id.to_string());
+        // a client specific lifetime overwrites the global ones
+        let exp = match (client.refresh_token_lifetime, &did) {
+            (Some(secs), _) => nbf.add(chrono::Duration::seconds(secs as i64)),
+            (None, Some(_)) => nbf.add(chrono::Duration::hours(
+                *DEVICE_GRANT_REFRESH_TOKEN_LIFETIME as i64,
+            )),
+            (None, None) => nbf.add(chrono::Duration::hours(*REFRESH_TOKEN_LIFETIME as i64)),
+        };
+        let jwt_lifetime = (exp.timestamp() - clock::now().timestamp()).max(0) as u64;
+
+        let claims = jwt_claims(custom_claims, coarsetime::Duration::from_secs(jwt_lifetime))
+            .with_issuer(data.issuer.clone())
+            .invalid_before(nbf_unix)
+            .with_audience(client.id.to_string());
 
         // sign the token
         let token = {
@@ -406,9 +530,6 @@ impl TokenSet {
         let validation_string = String::from(&token).split_off(token.len() - 49);
 
         if let Some(device_id) = did {
-            let exp = nbf.add(chrono::Duration::hours(
-                *DEVICE_GRANT_REFRESH_TOKEN_LIFETIME as i64,
-            ));
             RefreshTokenDevice::create(
                 validation_string,
                 device_id,
@@ -419,7 +540,6 @@ impl TokenSet {
             )
             .await?;
         } else {
-            let exp = nbf.add(chrono::Duration::hours(*REFRESH_TOKEN_LIFETIME as i64));
             RefreshToken::create(
                 validation_string,
                 user.id.clone(),
@@ -427,6 +547,8 @@ impl TokenSet {
                 exp,
                 scope.map(|s| s.0),
                 is_mfa,
+                client.id.clone(),
+                session_id.map(|sid| sid.0),
             )
             .await?;
         }
@@ -438,6 +560,8 @@ impl TokenSet {
         data: &web::Data<AppState>,
         client: &Client,
         dpop_fingerprint: Option<DpopFingerprint>,
+        cert_fingerprint: Option<CertFingerprint>,
+        resource: Option<TokenResource>,
     ) -> Result<Self, ErrorResponse> {
         let token_type = if dpop_fingerprint.is_some() {
             JwtTokenType::DPoP
@@ -449,10 +573,14 @@ impl TokenSet {
             data,
             client,
             dpop_fingerprint,
+            cert_fingerprint,
             client.access_token_lifetime as i64,
             None,
             None,
             DeviceCodeFlow::No,
+            None,
+            resource,
+            None,
         )
         .await?;
 
@@ -473,10 +601,15 @@ impl TokenSet {
         client: &Client,
         auth_time: AuthTime,
         dpop_fingerprint: Option<DpopFingerprint>,
+        cert_fingerprint: Option<CertFingerprint>,
         nonce: Option<TokenNonce>,
         scopes: Option<TokenScopes>,
         auth_code_flow: AuthCodeFlow,
         device_code_flow: DeviceCodeFlow,
+        impersonator: Option<ImpersonatorId>,
+        session_id: Option<SessionId>,
+        resource: Option<TokenResource>,
+        retained_refresh_token: Option<RetainedRefreshToken>,
     ) -> Result<Self, ErrorResponse> {
         let scopes = scopes.map(|s| s.0);
         let scope = if let Some(s) = &scopes {
@@ -498,10 +631,12 @@ impl TokenSet {
 
             for s in scps.as_ref().unwrap() {
                 if cust.contains(s.name.as_str()) {
-                    if s.attr_include_access.is_some() {
+                    if s.attr_include_access.is_some()
+                        || s.has_claim_mappings(ScopeClaimTarget::Access)
+                    {
                         customs_access.push(s);
                     }
-                    if s.attr_include_id.is_some() {
+                    if s.attr_include_id.is_some() || s.has_claim_mappings(ScopeClaimTarget::Id) {
                         customs_id.push(s);
                     }
                 }
@@ -536,9 +671,14 @@ impl TokenSet {
             (None, None)
         };
 
-        // set the correct lifetime
-        let lifetime = if let Some(ts) = user.user_expires {
-            let now = Utc::now().timestamp();
+        // set the correct lifetimes
+        let client_lt = client.access_token_lifetime.unsigned_abs() as i64;
+        let client_id_lt = client
+            .id_token_lifetime
+            .map(|lt| lt.unsigned_abs() as i64)
+            .unwrap_or(client_lt);
+        let (lifetime, id_lifetime) = if let Some(ts) = user.user_expires {
+            let now = clock::now().timestamp();
             let diff = ts - now;
             if diff < 1 {
                 return Err(ErrorResponse::new(
@@ -547,14 +687,9 @@ impl TokenSet {
                 ));
             }
 
-            let client_lt = client.access_token_lifetime.unsigned_abs() as i64;
-            if client_lt < diff {
-                client_lt
-            } else {
-                diff
-            }
+            (client_lt.min(diff), client_id_lt.min(diff))
         } else {
-            client.access_token_lifetime.unsigned_abs() as i64
+            (client_lt, client_id_lt)
         };
 
         let token_type = if dpop_fingerprint.is_some() {
@@ -567,10 +702,14 @@ impl TokenSet {
             data,
             client,
             dpop_fingerprint.clone(),
+            cert_fingerprint,
             lifetime,
             Some(TokenScopes(scope.clone())),
             customs_access,
             device_code_flow.clone(),
+            impersonator.clone(),
+            resource,
+            session_id.clone(),
         )
         .await?;
 
@@ -585,14 +724,19 @@ impl TokenSet {
             auth_time.clone(),
             dpop_fingerprint.clone(),
             at_hash,
-            lifetime,
+            id_lifetime,
             nonce,
             &scope,
             customs_id,
             auth_code_flow,
+            impersonator.clone(),
+            session_id.clone(),
         )
         .await?;
-        let refresh_token = if client.allow_refresh_token() {
+        // impersonated sessions must never outlive the session itself
+        let refresh_token = if let Some(rt) = retained_refresh_token {
+            Some(rt.0)
+        } else if client.allow_refresh_token() && impersonator.is_none() {
             Some(
                 Self::build_refresh_token(
                     user,
@@ -604,6 +748,7 @@ impl TokenSet {
                     scopes.map(TokenScopes),
                     user.has_webauthn_enabled(),
                     device_code_flow,
+                    session_id,
                 )
                 .await?,
             )
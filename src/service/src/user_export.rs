@@ -0,0 +1,103 @@
+use rauthy_api_types::sessions::{SessionResponse, SessionState};
+use rauthy_api_types::users::{
+    ClientUserMetadataResponse, DeviceResponse, LoginHistoryResponse, TosAcceptanceResponse,
+    UserActivityResponse, UserAttrValueResponse, UserExportResponse,
+};
+use rauthy_common::clock;
+use rauthy_error::ErrorResponse;
+use rauthy_models::entity::clients_user_metadata::ClientUserMetadata;
+use rauthy_models::entity::consents::UserConsent;
+use rauthy_models::entity::devices::DeviceEntity;
+use rauthy_models::entity::fido_mds::FidoMds;
+use rauthy_models::entity::login_history::LoginHistory;
+use rauthy_models::entity::sessions::Session;
+use rauthy_models::entity::tos::TosAcceptance;
+use rauthy_models::entity::user_attr::UserAttrValueEntity;
+use rauthy_models::entity::users::User;
+use rauthy_models::entity::users_values::UserValues;
+use rauthy_models::entity::webauthn::PasskeyEntity;
+use rauthy_models::events::event::Event;
+
+/// Collects all data Rauthy holds about the given user and returns it as pretty printed JSON,
+/// which can be used to answer data subject access requests.
+///
+/// Secrets like password hashes, session CSRF tokens or the passkey credentials themselves are
+/// never part of the export.
+pub async fn build(user: User) -> Result<String, ErrorResponse> {
+    let values = UserValues::find(&user.id).await?;
+    let attributes = UserAttrValueEntity::find_for_user(&user.id)
+        .await?
+        .into_iter()
+        .map(UserAttrValueResponse::from)
+        .collect();
+    let sessions = Session::find_for_user(&user.id).await?;
+    let devices = DeviceEntity::find_for_user(&user.id)
+        .await?
+        .into_iter()
+        .map(DeviceResponse::from)
+        .collect();
+    let mds = FidoMds::find().await;
+    let passkeys = PasskeyEntity::find_for_user(&user.id)
+        .await?
+        .into_iter()
+        .map(|pk| pk.into_response(mds.as_ref()))
+        .collect();
+    let login_history = LoginHistory::find_for_user(&user.id, u16::MAX)
+        .await?
+        .into_iter()
+        .map(LoginHistoryResponse::from)
+        .collect();
+    let tos_acceptances = TosAcceptance::find_for_user(&user.id)
+        .await?
+        .into_iter()
+        .map(TosAcceptanceResponse::from)
+        .collect();
+    let client_metadata = ClientUserMetadata::find_for_user(&user.id)
+        .await?
+        .into_iter()
+        .map(ClientUserMetadataResponse::from)
+        .collect();
+    let mut consents = Vec::new();
+    for consent in UserConsent::find_for_user(&user.id).await? {
+        consents.push(consent.into_response().await);
+    }
+    let events = Event::find_for_user(&user.id)
+        .await?
+        .into_iter()
+        .map(UserActivityResponse::from)
+        .collect();
+
+    let export = UserExportResponse {
+        exported_at: clock::now().timestamp(),
+        user: user.into_response(values),
+        attributes,
+        sessions: sessions
+            .iter()
+            .map(|s| SessionResponse {
+                id: &s.id,
+                user_id: s.user_id.as_deref(),
+                is_mfa: s.is_mfa,
+                state: SessionState::from(
+                    s.state()
+                        .unwrap_or(rauthy_models::entity::sessions::SessionState::Unknown),
+                ),
+                exp: s.exp,
+                last_seen: s.last_seen,
+                remote_ip: s.remote_ip.as_deref(),
+                impersonator_id: s.impersonator_id.as_deref(),
+                remember_me: s.remember_me,
+                client_id: s.client_id.as_deref(),
+                auth_method: s.auth_method.as_deref(),
+            })
+            .collect(),
+        devices,
+        passkeys,
+        login_history,
+        tos_acceptances,
+        client_metadata,
+        consents,
+        events,
+    };
+
+    Ok(serde_json::to_string_pretty(&export)?)
+}
@@ -0,0 +1,158 @@
+use actix_web::web;
+use rauthy_api_types::generic::Language;
+use rauthy_api_types::users::{NewUserRequest, UserImportRow};
+use rauthy_error::{ErrorResponse, ErrorResponseType};
+use rauthy_models::app_state::AppState;
+use rauthy_models::entity::users::User;
+use serde::Deserialize;
+use serde_json::Value;
+use validator::Validate;
+
+pub const USER_IMPORT_MAX_ROWS: usize = 1000;
+
+/// The flat representation of a `UserImportRow` inside a CSV
+#[derive(Debug, Deserialize)]
+struct CsvRow {
+    email: String,
+    given_name: String,
+    family_name: Option<String>,
+    language: Option<Language>,
+    groups: Option<String>,
+    roles: Option<String>,
+    user_expires: Option<i64>,
+    external_id: Option<String>,
+    send_magic_link: Option<bool>,
+}
+
+impl From<CsvRow> for UserImportRow {
+    fn from(row: CsvRow) -> Self {
+        let split = |s: Option<String>| {
+            s.map(|s| {
+                s.split(';')
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        Self {
+            email: row.email,
+            given_name: row.given_name,
+            family_name: row.family_name.filter(|n| !n.is_empty()),
+            language: row.language,
+            groups: split(row.groups),
+            roles: split(row.roles),
+            user_expires: row.user_expires,
+            external_id: row.external_id.filter(|id| !id.is_empty()),
+            send_magic_link: row.send_magic_link,
+        }
+    }
+}
+
+/// Parses the import payload. A single bad row does not fail the whole import. Instead, the
+/// error will be returned for this row only.
+pub fn parse_rows(
+    body: &[u8],
+    is_csv: bool,
+) -> Result<Vec<Result<UserImportRow, String>>, ErrorResponse> {
+    let rows = if is_csv {
+        csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(body)
+            .deserialize::<CsvRow>()
+            .map(|res| res.map(UserImportRow::from).map_err(|err| err.to_string()))
+            .collect::<Vec<_>>()
+    } else {
+        serde_json::from_slice::<Vec<Value>>(body)
+            .map_err(|err| {
+                ErrorResponse::new(
+                    ErrorResponseType::BadRequest,
+                    format!("Expected a JSON array of users: {}", err),
+                )
+            })?
+            .into_iter()
+            .map(|v| serde_json::from_value::<UserImportRow>(v).map_err(|err| err.to_string()))
+            .collect::<Vec<_>>()
+    };
+
+    if rows.len() > USER_IMPORT_MAX_ROWS {
+        return Err(ErrorResponse::new(
+            ErrorResponseType::BadRequest,
+            format!(
+                "A single import must not contain more than {} users",
+                USER_IMPORT_MAX_ROWS
+            ),
+        ));
+    }
+
+    Ok(rows)
+}
+
+/// Validates and creates a single user from the import.
+pub async fn import_row(
+    data: &web::Data<AppState>,
+    row: UserImportRow,
+) -> Result<User, ErrorResponse> {
+    let send_magic_link = row.send_magic_link.unwrap_or(true);
+    let new_user = NewUserRequest {
+        email: row.email,
+        family_name: row.family_name,
+        given_name: row.given_name,
+        language: row.language.unwrap_or(Language::En),
+        groups: row.groups,
+        roles: row.roles.unwrap_or_default(),
+        user_expires: row.user_expires,
+        external_id: row.external_id,
+    };
+    new_user.validate()?;
+
+    let user = User::from_new_user_req(new_user).await?;
+    if send_magic_link {
+        User::create(data, user, None).await
+    } else {
+        User::insert(user).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_rows() {
+        let csv = r#"email,given_name,family_name,language,groups,roles,user_expires,external_id,send_magic_link
+alice@localhost.de,Alice,,de,admin;user,,,ext-alice,false
+bob@localhost.de,Bob,Builder,,,,not_a_number,,
+"#;
+        let rows = parse_rows(csv.as_bytes(), true).unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let alice = rows[0].as_ref().unwrap();
+        assert_eq!(alice.email, "alice@localhost.de");
+        assert_eq!(alice.family_name, None);
+        assert_eq!(alice.language, Some(Language::De));
+        assert_eq!(
+            alice.groups,
+            Some(vec!["admin".to_string(), "user".to_string()])
+        );
+        assert_eq!(alice.external_id.as_deref(), Some("ext-alice"));
+        assert_eq!(alice.send_magic_link, Some(false));
+        assert!(rows[1].is_err());
+
+        let json = r#"[
+            {"email": "alice@localhost.de", "given_name": "Alice", "roles": ["admin"]},
+            {"email": "bob@localhost.de"}
+        ]"#;
+        let rows = parse_rows(json.as_bytes(), false).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0].as_ref().unwrap().roles,
+            Some(vec!["admin".to_string()])
+        );
+        // given_name is missing
+        assert!(rows[1].is_err());
+
+        assert!(parse_rows(b"{}", false).is_err());
+    }
+}